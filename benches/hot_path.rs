@@ -0,0 +1,199 @@
+//! Benchmarks for the per-block work done by producers and consumers.
+//!
+//! `cargo bench --bench hot_path`. The Postgres groups only run when `BENCH_DATABASE_URL` points
+//! at a scratch database; they apply migrations and write rows under the `BENCH` chain.
+
+use blockchain_data_ingestion::aggregation::address_activity::AddressActivityIndexer;
+use blockchain_data_ingestion::enrichment::transfers::{decode_erc20_transfers, transfer_topic};
+use blockchain_data_ingestion::integrity::headers::compute_header_hash;
+use blockchain_data_ingestion::integrity::roots::ordered_trie_root;
+use blockchain_data_ingestion::storage::db::run_migrations;
+use blockchain_data_ingestion::storage::sinks::postgres::PostgresSink;
+use blockchain_data_ingestion::storage::sinks::Sink;
+use blockchain_data_ingestion::streams::consumers::hooks::ConsumerHook;
+use blockchain_data_ingestion::streams::schemas::cdc::{BlockRowImage, ChangeEnvelope, ChangeSource, CDC_CONNECTOR};
+use blockchain_data_ingestion::streams::schemas::schema::{decode, encode, MessageSchema, WireFormat};
+use blockchain_data_ingestion::streams::schemas::versioning::{decode_versioned, encode_versioned};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use ethers::types::{Address, Block, Bloom, Bytes, Log, Transaction, H256, H64, U256, U64};
+use sqlx::postgres::PgPoolOptions;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+const CHAIN: &str = "BENCH";
+
+/// Typical mainnet block sizes, in transactions.
+const TX_COUNTS: [usize; 3] = [0, 150, 1000];
+
+fn synthetic_transaction(block_number: u64, index: usize) -> Transaction {
+    Transaction {
+        hash: H256::from_low_u64_be(block_number << 20 | index as u64),
+        nonce: U256::from(index),
+        block_number: Some(U64::from(block_number)),
+        transaction_index: Some(U64::from(index)),
+        from: Address::from_low_u64_be(index as u64 + 1),
+        to: Some(Address::from_low_u64_be(index as u64 + 2)),
+        value: U256::exp10(18),
+        gas_price: Some(U256::from(30_000_000_000u64)),
+        gas: U256::from(21_000),
+        input: Bytes::from(vec![0xa9; 68]),
+        v: U64::from(27),
+        r: U256::one(),
+        s: U256::one(),
+        transaction_type: Some(U64::from(2)),
+        ..Default::default()
+    }
+}
+
+fn synthetic_block(block_number: u64, tx_count: usize) -> Block<Transaction> {
+    Block {
+        hash: Some(H256::from_low_u64_be(block_number)),
+        parent_hash: H256::from_low_u64_be(block_number - 1),
+        author: Some(Address::zero()),
+        number: Some(U64::from(block_number)),
+        gas_used: U256::from(21_000 * tx_count),
+        gas_limit: U256::from(30_000_000),
+        timestamp: U256::from(1_700_000_000 + 12 * block_number),
+        logs_bloom: Some(Bloom::zero()),
+        mix_hash: Some(H256::zero()),
+        nonce: Some(H64::zero()),
+        base_fee_per_gas: Some(U256::from(7)),
+        size: Some(U256::from(1_000 + 200 * tx_count)),
+        transactions: (0..tx_count).map(|i| synthetic_transaction(block_number, i)).collect(),
+        ..Default::default()
+    }
+}
+
+fn synthetic_transfer_logs(count: usize) -> Vec<Log> {
+    (0..count)
+        .map(|i| Log {
+            address: Address::from_low_u64_be(0xdac17f958d2ee523),
+            topics: vec![
+                transfer_topic(),
+                H256::from(Address::from_low_u64_be(i as u64 + 1)),
+                H256::from(Address::from_low_u64_be(i as u64 + 2)),
+            ],
+            data: Bytes::from(H256::from_low_u64_be(1_000_000).as_bytes().to_vec()),
+            block_number: Some(U64::from(1)),
+            transaction_hash: Some(H256::from_low_u64_be(i as u64)),
+            log_index: Some(U256::from(i)),
+            ..Default::default()
+        })
+        .collect()
+}
+
+fn block_serialization(c: &mut Criterion) {
+    let mut group = c.benchmark_group("block_serialization");
+    for tx_count in TX_COUNTS {
+        let block = synthetic_block(1, tx_count);
+        let encoded = serde_json::to_vec(&block).unwrap();
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("encode", tx_count), &block, |b, block| {
+            b.iter(|| serde_json::to_vec(black_box(block)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("decode", tx_count), &encoded, |b, encoded| {
+            b.iter(|| serde_json::from_slice::<Block<Transaction>>(black_box(encoded)).unwrap())
+        });
+        // What consumers run: the version stamp is read first, then the block decoded in place.
+        let stamped = encode_versioned(WireFormat::Json, &block).unwrap();
+        group.bench_with_input(BenchmarkId::new("decode_versioned", tx_count), &stamped, |b, stamped| {
+            b.iter(|| decode_versioned::<Block<Transaction>>(black_box(stamped)).unwrap())
+        });
+        let encoded_messagepack = encode(WireFormat::MessagePack, &block).unwrap();
+        group.bench_with_input(BenchmarkId::new("encode_messagepack", tx_count), &block, |b, block| {
+            b.iter(|| encode(WireFormat::MessagePack, black_box(block)).unwrap())
+        });
+        group.bench_with_input(BenchmarkId::new("decode_messagepack", tx_count), &encoded_messagepack, |b, encoded| {
+            b.iter(|| decode::<Block<Transaction>>(black_box(encoded)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+fn envelope_encoding(c: &mut Criterion) {
+    let image = BlockRowImage {
+        chain_name: CHAIN.to_string(),
+        block_number: 19_000_000,
+        hash: format!("{:?}", H256::repeat_byte(0xab)),
+        canonical: true,
+    };
+    let envelope = ChangeEnvelope {
+        before: Some(image.clone()),
+        after: Some(BlockRowImage { canonical: false, ..image }),
+        source: ChangeSource {
+            connector: CDC_CONNECTOR.to_string(),
+            name: CHAIN.to_string(),
+            table: "blocks".to_string(),
+            ts_ms: 1_700_000_000_000,
+        },
+        op: "u".to_string(),
+        ts_ms: 1_700_000_000_000,
+    };
+
+    let mut group = c.benchmark_group("envelope_encoding");
+    group.throughput(Throughput::Elements(1));
+    group.bench_function("cdc_change_envelope", |b| b.iter(|| black_box(&envelope).serialize()));
+    group.finish();
+}
+
+fn transform_stages(c: &mut Criterion) {
+    let mut group = c.benchmark_group("transform_stages");
+    for tx_count in TX_COUNTS {
+        let block = synthetic_block(1, tx_count);
+        let encoded_transactions: Vec<Vec<u8>> = block.transactions.iter().map(|tx| tx.rlp().to_vec()).collect();
+        let logs = synthetic_transfer_logs(tx_count);
+
+        group.throughput(Throughput::Elements(1));
+        group.bench_with_input(BenchmarkId::new("header_hash", tx_count), &block, |b, block| {
+            b.iter(|| compute_header_hash(black_box(block)))
+        });
+        group.bench_with_input(BenchmarkId::new("transactions_root", tx_count), &encoded_transactions, |b, items| {
+            b.iter(|| ordered_trie_root(black_box(items)))
+        });
+        group.bench_with_input(BenchmarkId::new("decode_erc20_transfers", tx_count), &logs, |b, logs| {
+            b.iter(|| decode_erc20_transfers(black_box(logs)))
+        });
+    }
+    group.finish();
+}
+
+fn postgres_inserts(c: &mut Criterion) {
+    let Ok(database_url) = std::env::var("BENCH_DATABASE_URL") else {
+        eprintln!("BENCH_DATABASE_URL is not set, skipping Postgres benchmarks");
+        return;
+    };
+    let rt = tokio::runtime::Runtime::new().unwrap();
+    let pool = rt.block_on(async {
+        let pool = PgPoolOptions::new().max_connections(5).connect(&database_url).await.unwrap();
+        run_migrations(&pool).await.unwrap();
+        sqlx::query("DELETE FROM transactions WHERE chain_name = $1").bind(CHAIN).execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM blocks WHERE chain_name = $1").bind(CHAIN).execute(&pool).await.unwrap();
+        sqlx::query("DELETE FROM address_activity WHERE chain_name = $1").bind(CHAIN).execute(&pool).await.unwrap();
+        Arc::new(pool)
+    });
+    let sink = PostgresSink::new(Arc::clone(&pool));
+    let indexer = AddressActivityIndexer::new(Arc::clone(&pool));
+    // Each iteration writes a fresh height so inserts never hit the reorg path.
+    let next_block = AtomicU64::new(1);
+
+    let mut group = c.benchmark_group("postgres_inserts");
+    group.sample_size(10);
+    let tx_count = 150;
+    group.throughput(Throughput::Elements(tx_count as u64));
+    group.bench_function(BenchmarkId::new("postgres_sink_write_block", tx_count), |b| {
+        b.to_async(&rt).iter(|| async {
+            let block = synthetic_block(next_block.fetch_add(1, Ordering::Relaxed), tx_count);
+            sink.write_block(CHAIN, &block).await.unwrap();
+        })
+    });
+    group.bench_function(BenchmarkId::new("address_activity_batch", tx_count), |b| {
+        b.to_async(&rt).iter(|| async {
+            let block = synthetic_block(next_block.fetch_add(1, Ordering::Relaxed), tx_count);
+            indexer.on_block_committed(CHAIN, &block).await.unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, block_serialization, envelope_encoding, transform_stages, postgres_inserts);
+criterion_main!(benches);
@@ -0,0 +1,11 @@
+DROP INDEX IF EXISTS transactions_chain_block_idx;
+DROP INDEX IF EXISTS blocks_canonical_number_idx;
+
+DELETE FROM transactions WHERE NOT canonical;
+DELETE FROM blocks WHERE NOT canonical;
+
+ALTER TABLE transactions DROP COLUMN canonical;
+ALTER TABLE blocks DROP COLUMN canonical;
+
+ALTER TABLE blocks ADD CONSTRAINT blocks_block_number_key UNIQUE (block_number);
+ALTER TABLE transactions ADD CONSTRAINT transactions_block_number_fkey FOREIGN KEY (block_number) REFERENCES blocks (block_number);
@@ -0,0 +1,10 @@
+-- Track canonicality so reorged blocks are kept but marked orphaned instead of rejected.
+ALTER TABLE transactions DROP CONSTRAINT IF EXISTS transactions_block_number_fkey;
+ALTER TABLE blocks DROP CONSTRAINT IF EXISTS blocks_block_number_key;
+
+ALTER TABLE blocks ADD COLUMN canonical BOOLEAN NOT NULL DEFAULT TRUE;
+ALTER TABLE transactions ADD COLUMN canonical BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- Only one canonical block per height and chain.
+CREATE UNIQUE INDEX blocks_canonical_number_idx ON blocks (chain_name, block_number) WHERE canonical;
+CREATE INDEX transactions_chain_block_idx ON transactions (chain_name, block_number);
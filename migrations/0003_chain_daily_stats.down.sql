@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS blocks_chain_timestamp_idx;
+DROP TABLE IF EXISTS chain_daily_stats;
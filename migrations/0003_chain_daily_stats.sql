@@ -0,0 +1,14 @@
+-- Per chain, per UTC day rollups maintained by the aggregation job.
+CREATE TABLE chain_daily_stats (
+    chain_name TEXT NOT NULL,
+    day DATE NOT NULL,
+    block_count BIGINT NOT NULL,
+    tx_count BIGINT NOT NULL,
+    active_addresses BIGINT NOT NULL,
+    gas_used NUMERIC NOT NULL,
+    avg_gas_price NUMERIC,
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_name, day)
+);
+
+CREATE INDEX blocks_chain_timestamp_idx ON blocks (chain_name, timestamp);
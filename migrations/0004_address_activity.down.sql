@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS transactions_to_address_idx;
+DROP INDEX IF EXISTS transactions_from_address_idx;
+DROP TABLE IF EXISTS address_activity;
@@ -0,0 +1,13 @@
+-- Per address activity index maintained incrementally by the consumer.
+CREATE TABLE address_activity (
+    chain_name TEXT NOT NULL,
+    address TEXT NOT NULL,
+    first_seen_block BIGINT NOT NULL,
+    last_seen_block BIGINT NOT NULL,
+    tx_count BIGINT NOT NULL,
+    total_value NUMERIC NOT NULL,
+    PRIMARY KEY (chain_name, address)
+);
+
+CREATE INDEX transactions_from_address_idx ON transactions (chain_name, from_address);
+CREATE INDEX transactions_to_address_idx ON transactions (chain_name, to_address);
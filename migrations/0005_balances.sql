@@ -0,0 +1,8 @@
+-- Native balance time series for watched addresses.
+CREATE TABLE balances (
+    chain_name TEXT NOT NULL,
+    address TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    balance NUMERIC NOT NULL,
+    PRIMARY KEY (chain_name, address, block_number)
+);
@@ -0,0 +1,26 @@
+-- Decoded ERC-20 Transfer events.
+CREATE TABLE token_transfers (
+    id BIGSERIAL PRIMARY KEY,
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    log_index BIGINT NOT NULL,
+    token TEXT NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT NOT NULL,
+    value NUMERIC NOT NULL,
+    UNIQUE (chain_name, tx_hash, log_index)
+);
+
+CREATE INDEX token_transfers_token_idx ON token_transfers (chain_name, token, block_number);
+
+-- Holder balances derived from transfer deltas and reconciled against balanceOf.
+CREATE TABLE token_balances (
+    chain_name TEXT NOT NULL,
+    token TEXT NOT NULL,
+    holder TEXT NOT NULL,
+    balance NUMERIC NOT NULL,
+    last_updated_block BIGINT NOT NULL,
+    reconciled_at TIMESTAMP,
+    PRIMARY KEY (chain_name, token, holder)
+);
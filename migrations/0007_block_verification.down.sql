@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS blocks_parent_hash_idx;
+ALTER TABLE blocks DROP COLUMN verified;
+ALTER TABLE blocks DROP COLUMN header_valid;
@@ -0,0 +1,6 @@
+-- header_valid: keccak(rlp(header)) matches the hash the provider returned.
+-- verified: header_valid and linked by parent hashes to the configured checkpoint.
+ALTER TABLE blocks ADD COLUMN header_valid BOOLEAN;
+ALTER TABLE blocks ADD COLUMN verified BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE INDEX blocks_parent_hash_idx ON blocks (chain_name, parent_hash);
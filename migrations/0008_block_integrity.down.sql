@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS quarantined_blocks;
+ALTER TABLE blocks DROP COLUMN quarantined;
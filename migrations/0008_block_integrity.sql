@@ -0,0 +1,13 @@
+-- Blocks whose transactions or receipts did not match the roots in their header.
+ALTER TABLE blocks ADD COLUMN quarantined BOOLEAN NOT NULL DEFAULT FALSE;
+
+CREATE TABLE quarantined_blocks (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    hash TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    expected_root TEXT NOT NULL,
+    computed_root TEXT NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_name, hash, reason)
+);
@@ -0,0 +1,10 @@
+-- Calls and provider credits per chain and RPC method, per calendar month.
+CREATE TABLE rpc_usage (
+    provider TEXT NOT NULL,
+    chain_name TEXT NOT NULL,
+    method TEXT NOT NULL,
+    month DATE NOT NULL,
+    calls BIGINT NOT NULL DEFAULT 0,
+    credits BIGINT NOT NULL DEFAULT 0,
+    PRIMARY KEY (provider, chain_name, method, month)
+);
@@ -0,0 +1,7 @@
+-- Indexes created from the [indexes] config, so ones removed from it can be dropped.
+CREATE TABLE managed_indexes (
+    name TEXT PRIMARY KEY,
+    table_name TEXT NOT NULL,
+    definition TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS canonical_transactions;
+DROP TABLE IF EXISTS canonical_blocks;
@@ -0,0 +1,42 @@
+-- Chain-agnostic blocks and transactions for every chain, keyed by chain_id. Columns that only
+-- exist on EVM chains are nullable.
+CREATE TABLE canonical_blocks (
+    chain_id BIGINT NOT NULL,
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    hash TEXT NOT NULL,
+    parent_hash TEXT NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    tx_count BIGINT NOT NULL,
+    miner TEXT,
+    gas_used BIGINT,
+    gas_limit BIGINT,
+    base_fee_per_gas NUMERIC,
+    PRIMARY KEY (chain_id, block_number)
+);
+
+CREATE INDEX canonical_blocks_timestamp_idx ON canonical_blocks (timestamp);
+
+CREATE TABLE canonical_transactions (
+    chain_id BIGINT NOT NULL,
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    tx_index BIGINT NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT,
+    value NUMERIC NOT NULL,
+    nonce BIGINT,
+    gas NUMERIC,
+    gas_price NUMERIC,
+    max_fee_per_gas NUMERIC,
+    max_priority_fee_per_gas NUMERIC,
+    tx_type BIGINT,
+    input TEXT,
+    PRIMARY KEY (chain_id, block_number, tx_index)
+);
+
+CREATE INDEX canonical_transactions_hash_idx ON canonical_transactions (tx_hash);
+CREATE INDEX canonical_transactions_from_idx ON canonical_transactions (from_address, chain_id);
+CREATE INDEX canonical_transactions_to_idx ON canonical_transactions (to_address, chain_id);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS transactions_failed_idx;
+ALTER TABLE transactions DROP COLUMN revert_reason;
+ALTER TABLE transactions DROP COLUMN status;
@@ -0,0 +1,6 @@
+-- Receipt status (1 success, 0 failure, NULL before Byzantium or not yet fetched) and, for
+-- failed transactions, the decoded revert reason.
+ALTER TABLE transactions ADD COLUMN status SMALLINT;
+ALTER TABLE transactions ADD COLUMN revert_reason TEXT;
+
+CREATE INDEX transactions_failed_idx ON transactions (chain_name, block_number) WHERE status = 0;
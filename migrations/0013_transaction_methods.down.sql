@@ -0,0 +1,2 @@
+ALTER TABLE transactions DROP COLUMN method_name;
+ALTER TABLE transactions DROP COLUMN method_selector;
@@ -0,0 +1,3 @@
+-- First four bytes of calldata and the function name they decode to, if known.
+ALTER TABLE transactions ADD COLUMN method_selector TEXT;
+ALTER TABLE transactions ADD COLUMN method_name TEXT;
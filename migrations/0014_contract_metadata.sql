@@ -0,0 +1,16 @@
+-- Contracts seen being created, with verified source metadata once a source has it.
+CREATE TABLE contract_metadata (
+    chain_name TEXT NOT NULL,
+    address TEXT NOT NULL,
+    created_block BIGINT NOT NULL,
+    verified BOOLEAN NOT NULL DEFAULT FALSE,
+    source TEXT,
+    name TEXT,
+    compiler_version TEXT,
+    abi JSONB,
+    attempts INT NOT NULL DEFAULT 0,
+    checked_at TIMESTAMP,
+    PRIMARY KEY (chain_name, address)
+);
+
+CREATE INDEX contract_metadata_pending_idx ON contract_metadata (chain_name, created_block) WHERE NOT verified;
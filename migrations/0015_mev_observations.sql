@@ -0,0 +1,16 @@
+-- Likely MEV activity flagged per block: sandwiches, backruns and builder tags.
+CREATE TABLE mev_observations (
+    id BIGSERIAL PRIMARY KEY,
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    tx_hashes TEXT[] NOT NULL,
+    address TEXT,
+    details JSONB NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX mev_observations_block_idx ON mev_observations (chain_name, block_number);
+CREATE INDEX mev_observations_block_hash_idx ON mev_observations (chain_name, block_hash);
+CREATE INDEX mev_observations_address_idx ON mev_observations (address, kind);
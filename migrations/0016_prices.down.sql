@@ -0,0 +1,2 @@
+ALTER TABLE token_transfers DROP COLUMN IF EXISTS value_usd;
+DROP TABLE IF EXISTS prices;
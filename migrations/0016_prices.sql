@@ -0,0 +1,12 @@
+-- USD prices of native and ERC-20 tokens, recorded at the first block of each window.
+CREATE TABLE prices (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    asset TEXT NOT NULL,
+    price_usd NUMERIC NOT NULL,
+    source TEXT NOT NULL,
+    PRIMARY KEY (chain_name, asset, block_number)
+);
+
+ALTER TABLE token_transfers ADD COLUMN value_usd NUMERIC;
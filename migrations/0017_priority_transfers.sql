@@ -0,0 +1,17 @@
+-- Transfers of allowlisted tokens, written by the realtime producer ahead of block consumption.
+CREATE TABLE priority_transfers (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    log_index BIGINT NOT NULL,
+    token TEXT NOT NULL,
+    from_address TEXT NOT NULL,
+    to_address TEXT NOT NULL,
+    value NUMERIC NOT NULL,
+    seen_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_name, tx_hash, log_index)
+);
+
+CREATE INDEX priority_transfers_to_idx ON priority_transfers (chain_name, to_address, block_number);
+CREATE INDEX priority_transfers_block_hash_idx ON priority_transfers (chain_name, block_hash);
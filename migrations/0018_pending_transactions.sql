@@ -0,0 +1,21 @@
+-- Mempool transactions and how they resolved: pending, mined, replaced or dropped.
+CREATE TABLE pending_transactions (
+    chain_name TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    from_address TEXT NOT NULL,
+    nonce BIGINT NOT NULL,
+    to_address TEXT,
+    value NUMERIC NOT NULL,
+    fee_bid NUMERIC NOT NULL,
+    status TEXT NOT NULL,
+    first_seen TIMESTAMP NOT NULL,
+    resolved_at TIMESTAMP,
+    block_number BIGINT,
+    block_hash TEXT,
+    replaced_by TEXT,
+    PRIMARY KEY (chain_name, tx_hash)
+);
+
+CREATE INDEX pending_transactions_sender_idx ON pending_transactions (chain_name, from_address, nonce);
+CREATE INDEX pending_transactions_pending_idx ON pending_transactions (chain_name, first_seen) WHERE status = 'pending';
+CREATE INDEX pending_transactions_block_hash_idx ON pending_transactions (chain_name, block_hash) WHERE block_hash IS NOT NULL;
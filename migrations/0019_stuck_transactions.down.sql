@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS transactions_sender_nonce_idx;
+DROP TABLE IF EXISTS stuck_transactions;
@@ -0,0 +1,17 @@
+-- Stuck transactions and nonce gaps of watched addresses. Open findings have no resolved_at.
+CREATE TABLE stuck_transactions (
+    id BIGSERIAL PRIMARY KEY,
+    chain_name TEXT NOT NULL,
+    address TEXT NOT NULL,
+    kind TEXT NOT NULL,
+    nonce BIGINT NOT NULL,
+    tx_hash TEXT,
+    message TEXT NOT NULL,
+    detected_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMP
+);
+
+CREATE UNIQUE INDEX stuck_transactions_open_idx ON stuck_transactions (chain_name, address, kind, nonce) WHERE resolved_at IS NULL;
+
+-- Next-nonce lookups for watched senders.
+CREATE INDEX IF NOT EXISTS transactions_sender_nonce_idx ON transactions (chain_name, from_address, nonce);
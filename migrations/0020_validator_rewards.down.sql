@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS validator_rewards;
+DROP TABLE IF EXISTS validator_withdrawals;
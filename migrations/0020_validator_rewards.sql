@@ -0,0 +1,30 @@
+-- Execution-layer withdrawals of every validator.
+CREATE TABLE validator_withdrawals (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    withdrawal_index BIGINT NOT NULL,
+    validator_index BIGINT NOT NULL,
+    address TEXT NOT NULL,
+    amount_gwei BIGINT NOT NULL,
+    PRIMARY KEY (chain_name, withdrawal_index)
+);
+
+CREATE INDEX validator_withdrawals_validator_idx ON validator_withdrawals (chain_name, validator_index, block_number);
+CREATE INDEX validator_withdrawals_block_hash_idx ON validator_withdrawals (chain_name, block_hash);
+
+-- Sampled beacon balances of configured validators, with the reward earned since the previous sample.
+CREATE TABLE validator_rewards (
+    chain_name TEXT NOT NULL,
+    validator_index BIGINT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    slot BIGINT NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    balance_gwei BIGINT NOT NULL,
+    withdrawn_gwei BIGINT,
+    reward_gwei BIGINT,
+    PRIMARY KEY (chain_name, validator_index, block_number)
+);
+
+CREATE INDEX validator_rewards_block_hash_idx ON validator_rewards (chain_name, block_hash);
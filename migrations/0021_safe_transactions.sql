@@ -0,0 +1,21 @@
+-- Gnosis Safe executions, from execTransaction calls and ExecutionSuccess/ExecutionFailure events.
+CREATE TABLE safe_transactions (
+    id BIGSERIAL PRIMARY KEY,
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    tx_index BIGINT NOT NULL,
+    safe_address TEXT NOT NULL,
+    nonce BIGINT,
+    safe_tx_hash TEXT,
+    to_address TEXT,
+    value NUMERIC,
+    operation SMALLINT,
+    signers_count INT,
+    success BOOLEAN NOT NULL
+);
+
+CREATE INDEX safe_transactions_block_idx ON safe_transactions (chain_name, block_number);
+CREATE INDEX safe_transactions_safe_idx ON safe_transactions (chain_name, safe_address, nonce);
+CREATE INDEX safe_transactions_block_hash_idx ON safe_transactions (chain_name, block_hash);
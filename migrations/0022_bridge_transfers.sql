@@ -0,0 +1,22 @@
+-- Decoded bridge deposits and withdrawals. Both sides of a transfer share a correlation_key.
+CREATE TABLE bridge_transfers (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    log_index BIGINT NOT NULL,
+    bridge TEXT NOT NULL,
+    direction TEXT NOT NULL,
+    source_chain_id BIGINT,
+    destination_chain_id BIGINT,
+    token TEXT,
+    remote_token TEXT,
+    from_address TEXT,
+    to_address TEXT,
+    amount NUMERIC,
+    correlation_key TEXT,
+    PRIMARY KEY (chain_name, tx_hash, log_index)
+);
+
+CREATE INDEX bridge_transfers_correlation_idx ON bridge_transfers (correlation_key);
+CREATE INDEX bridge_transfers_block_hash_idx ON bridge_transfers (chain_name, block_hash);
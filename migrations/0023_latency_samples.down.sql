@@ -0,0 +1 @@
+DROP TABLE IF EXISTS latency_samples;
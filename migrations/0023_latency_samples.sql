@@ -0,0 +1,12 @@
+-- End-to-end latency of each committed block, from its timestamp to its commit to the sink.
+CREATE TABLE latency_samples (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    block_timestamp TIMESTAMP NOT NULL,
+    committed_at TIMESTAMP NOT NULL,
+    latency_ms BIGINT NOT NULL,
+    PRIMARY KEY (chain_name, block_hash)
+);
+
+CREATE INDEX latency_samples_committed_at_idx ON latency_samples (chain_name, committed_at);
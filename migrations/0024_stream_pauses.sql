@@ -0,0 +1,8 @@
+-- Schema streams paused by an operator. Producers skip a stream while it has a row here.
+CREATE TABLE stream_pauses (
+    chain_name TEXT NOT NULL,
+    schema_name TEXT NOT NULL,
+    reason TEXT,
+    paused_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_name, schema_name)
+);
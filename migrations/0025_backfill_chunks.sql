@@ -0,0 +1,16 @@
+-- Historical backfills, split into chunks that workers claim, retry and complete independently.
+CREATE TABLE backfill_chunks (
+    id BIGSERIAL PRIMARY KEY,
+    chain_name TEXT NOT NULL,
+    schema_name TEXT NOT NULL,
+    start_block BIGINT NOT NULL,
+    end_block BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending', -- pending, running, done or failed
+    attempts INT NOT NULL DEFAULT 0,
+    last_error TEXT,
+    next_attempt_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMP NOT NULL DEFAULT NOW(),
+    UNIQUE (chain_name, schema_name, start_block, end_block)
+);
+
+CREATE INDEX backfill_chunks_claim_idx ON backfill_chunks (chain_name, schema_name, status, start_block);
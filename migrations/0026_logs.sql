@@ -0,0 +1,26 @@
+-- Raw and decoded event logs, range-partitioned by block_number. Partitions of 1,000,000 blocks
+-- (logs_p<n> holds n * 1,000,000 up to the next) are created by the writer as blocks reach them.
+-- Keep rows of the table earlier versions generated for the `logs` schema.
+ALTER TABLE IF EXISTS logs RENAME TO logs_generated;
+
+CREATE TABLE logs (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block_hash TEXT NOT NULL,
+    tx_hash TEXT NOT NULL,
+    tx_index BIGINT NOT NULL,
+    log_index BIGINT NOT NULL,
+    address TEXT NOT NULL,
+    topic0 TEXT,
+    topic1 TEXT,
+    topic2 TEXT,
+    topic3 TEXT,
+    data TEXT NOT NULL,
+    event_name TEXT,
+    params JSONB,
+    PRIMARY KEY (chain_name, block_number, block_hash, log_index)
+) PARTITION BY RANGE (block_number);
+
+CREATE INDEX logs_address_topic0_idx ON logs (chain_name, address, topic0, block_number);
+CREATE INDEX logs_topic0_idx ON logs (chain_name, topic0, block_number);
+CREATE INDEX logs_tx_hash_idx ON logs (chain_name, tx_hash);
@@ -0,0 +1,15 @@
+-- Excluded values are gone; restore the constraints with empty placeholders.
+UPDATE blocks SET miner = COALESCE(miner, ''), difficulty = COALESCE(difficulty, ''),
+    total_difficulty = COALESCE(total_difficulty, ''), receipts_root = COALESCE(receipts_root, ''),
+    transactions = COALESCE(transactions, '[]'::jsonb)
+WHERE miner IS NULL OR difficulty IS NULL OR total_difficulty IS NULL OR receipts_root IS NULL OR transactions IS NULL;
+UPDATE transactions SET gas_price = COALESCE(gas_price, ''), gas = COALESCE(gas, ''), input = COALESCE(input, '')
+WHERE gas_price IS NULL OR gas IS NULL OR input IS NULL;
+ALTER TABLE blocks ALTER COLUMN miner SET NOT NULL;
+ALTER TABLE blocks ALTER COLUMN difficulty SET NOT NULL;
+ALTER TABLE blocks ALTER COLUMN total_difficulty SET NOT NULL;
+ALTER TABLE blocks ALTER COLUMN receipts_root SET NOT NULL;
+ALTER TABLE blocks ALTER COLUMN transactions SET NOT NULL;
+ALTER TABLE transactions ALTER COLUMN gas_price SET NOT NULL;
+ALTER TABLE transactions ALTER COLUMN gas SET NOT NULL;
+ALTER TABLE transactions ALTER COLUMN input SET NOT NULL;
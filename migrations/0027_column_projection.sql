@@ -0,0 +1,9 @@
+-- Columns a chain's projection can exclude are stored as NULL for that chain.
+ALTER TABLE blocks ALTER COLUMN miner DROP NOT NULL;
+ALTER TABLE blocks ALTER COLUMN difficulty DROP NOT NULL;
+ALTER TABLE blocks ALTER COLUMN total_difficulty DROP NOT NULL;
+ALTER TABLE blocks ALTER COLUMN receipts_root DROP NOT NULL;
+ALTER TABLE blocks ALTER COLUMN transactions DROP NOT NULL;
+ALTER TABLE transactions ALTER COLUMN gas_price DROP NOT NULL;
+ALTER TABLE transactions ALTER COLUMN gas DROP NOT NULL;
+ALTER TABLE transactions ALTER COLUMN input DROP NOT NULL;
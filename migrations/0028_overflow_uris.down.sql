@@ -0,0 +1,4 @@
+ALTER TABLE logs DROP COLUMN data_uri;
+UPDATE logs SET data = '' WHERE data IS NULL;
+ALTER TABLE logs ALTER COLUMN data SET NOT NULL;
+ALTER TABLE transactions DROP COLUMN input_uri;
@@ -0,0 +1,5 @@
+-- Calldata and log data over a chain's overflow cap live in object storage; the row holds the
+-- object's URI in place of the data.
+ALTER TABLE transactions ADD COLUMN input_uri TEXT;
+ALTER TABLE logs ALTER COLUMN data DROP NOT NULL;
+ALTER TABLE logs ADD COLUMN data_uri TEXT;
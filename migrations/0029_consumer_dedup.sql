@@ -0,0 +1,12 @@
+-- Recently stored block hashes (and block_hash:tx_hash keys of transaction-only writes) per
+-- stream, so consumers skip redelivered and overlapping blocks across restarts.
+CREATE TABLE consumer_dedup (
+    chain_name TEXT NOT NULL,
+    stream TEXT NOT NULL,
+    key TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    recorded_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_name, stream, key)
+);
+
+CREATE INDEX consumer_dedup_block_idx ON consumer_dedup (chain_name, stream, block_number);
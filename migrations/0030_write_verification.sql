@@ -0,0 +1,11 @@
+-- Committed blocks whose read-back row differed from the message they were written from.
+CREATE TABLE write_mismatches (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    hash TEXT NOT NULL,
+    field TEXT NOT NULL,
+    expected TEXT NOT NULL,
+    stored TEXT NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (chain_name, hash, field)
+);
@@ -0,0 +1,16 @@
+-- Header fields of blocks consumed from the `headers` schema, apart from the full `blocks` rows.
+CREATE TABLE block_headers (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    hash TEXT NOT NULL,
+    parent_hash TEXT NOT NULL,
+    timestamp TIMESTAMP NOT NULL,
+    miner TEXT,
+    gas_used BIGINT NOT NULL,
+    gas_limit BIGINT NOT NULL,
+    base_fee_per_gas TEXT,
+    canonical BOOLEAN NOT NULL DEFAULT TRUE,
+    PRIMARY KEY (chain_name, hash)
+);
+
+CREATE INDEX block_headers_block_idx ON block_headers (chain_name, block_number);
@@ -0,0 +1,13 @@
+-- Changes made through the admin API and attempts the caller's role didn't allow.
+CREATE TABLE admin_audit_log (
+    id BIGSERIAL PRIMARY KEY,
+    actor TEXT NOT NULL,
+    role TEXT NOT NULL,
+    action TEXT NOT NULL,
+    target TEXT NOT NULL,
+    allowed BOOLEAN NOT NULL,
+    details JSONB NOT NULL,
+    at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX admin_audit_log_at_idx ON admin_audit_log (at);
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS shutdown_summaries;
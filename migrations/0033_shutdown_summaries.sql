@@ -0,0 +1,16 @@
+-- One row per graceful shutdown: what was in flight, what was drained and where each chain ended.
+CREATE TABLE shutdown_summaries (
+    id BIGSERIAL PRIMARY KEY,
+    instance TEXT NOT NULL,
+    duration_ms BIGINT NOT NULL,
+    timed_out BOOLEAN NOT NULL,
+    in_flight_messages BIGINT NOT NULL,
+    acked_messages BIGINT NOT NULL,
+    unacked_messages BIGINT NOT NULL,
+    flushed_batches BIGINT NOT NULL,
+    cursors JSONB NOT NULL,   -- last stored block per chain
+    consumers JSONB NOT NULL, -- how each consumer drained
+    at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX shutdown_summaries_at_idx ON shutdown_summaries (at);
@@ -0,0 +1,9 @@
+-- What each chain resumed and repaired at startup, one row per chain and start.
+CREATE TABLE recovery_reports (
+    id BIGSERIAL PRIMARY KEY,
+    chain_name TEXT NOT NULL,
+    report JSONB NOT NULL,
+    at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX recovery_reports_chain_at_idx ON recovery_reports (chain_name, at);
@@ -0,0 +1,11 @@
+-- Periodic samples of the pipeline's metrics, for dashboards built on the database.
+CREATE TABLE pipeline_metrics (
+    id BIGSERIAL PRIMARY KEY,
+    metric TEXT NOT NULL,
+    labels JSONB NOT NULL, -- e.g. {"chain": "ETH"}
+    value DOUBLE PRECISION NOT NULL, -- counters hold their increase since the previous sample
+    at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX pipeline_metrics_metric_at_idx ON pipeline_metrics (metric, at);
+CREATE INDEX pipeline_metrics_at_idx ON pipeline_metrics (at);
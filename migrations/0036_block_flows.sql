@@ -0,0 +1,14 @@
+-- Net flows per address and token in each block, from native value transfers and ERC-20 transfers.
+CREATE TABLE block_flows (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    address TEXT NOT NULL,
+    token TEXT NOT NULL, -- contract address, or 'native'
+    inflow NUMERIC NOT NULL,
+    outflow NUMERIC NOT NULL,
+    net NUMERIC NOT NULL,
+    transfer_count INTEGER NOT NULL,
+    PRIMARY KEY (chain_name, block_number, address, token)
+);
+
+CREATE INDEX block_flows_address_idx ON block_flows (chain_name, address, block_number);
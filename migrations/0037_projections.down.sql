@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS projection_blocks;
+DROP TABLE IF EXISTS projection_checkpoints;
@@ -0,0 +1,21 @@
+-- How far each registered projection has folded each chain's schema stream.
+CREATE TABLE projection_checkpoints (
+    projection TEXT NOT NULL,
+    chain_name TEXT NOT NULL,
+    schema_name TEXT NOT NULL,
+    last_block BIGINT NOT NULL,
+    last_block_hash TEXT NOT NULL,
+    blocks_folded BIGINT NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (projection, chain_name, schema_name)
+);
+
+-- Blocks each projection has folded, so redeliveries are skipped and reorged blocks reverted.
+CREATE TABLE projection_blocks (
+    projection TEXT NOT NULL,
+    chain_name TEXT NOT NULL,
+    schema_name TEXT NOT NULL,
+    block_hash TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    PRIMARY KEY (projection, chain_name, schema_name, block_hash)
+);
@@ -0,0 +1,5 @@
+ALTER TABLE transactions DROP COLUMN IF EXISTS fee_total;
+ALTER TABLE transactions DROP COLUMN IF EXISTS fee_l1_data;
+ALTER TABLE transactions DROP COLUMN IF EXISTS fee_priority;
+ALTER TABLE transactions DROP COLUMN IF EXISTS fee_base;
+ALTER TABLE transactions DROP COLUMN IF EXISTS fee_model;
@@ -0,0 +1,7 @@
+-- Fees normalized across chains by their fee model, in wei: `fee_total` is always
+-- `fee_base + fee_priority + fee_l1_data`.
+ALTER TABLE transactions ADD COLUMN fee_model TEXT;
+ALTER TABLE transactions ADD COLUMN fee_base NUMERIC;
+ALTER TABLE transactions ADD COLUMN fee_priority NUMERIC;
+ALTER TABLE transactions ADD COLUMN fee_l1_data NUMERIC;
+ALTER TABLE transactions ADD COLUMN fee_total NUMERIC;
@@ -0,0 +1 @@
+DROP TABLE IF EXISTS timestamp_anomalies;
@@ -0,0 +1,15 @@
+-- Blocks whose timestamps are implausible against the ingesting host's clock or their parent.
+CREATE TABLE timestamp_anomalies (
+    id BIGSERIAL PRIMARY KEY,
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    hash TEXT NOT NULL,
+    block_timestamp BIGINT NOT NULL,
+    skew_secs BIGINT NOT NULL, -- block timestamp minus ingestion time
+    kind TEXT NOT NULL, -- 'future', 'past' or 'before_parent'
+    detail TEXT NOT NULL,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    UNIQUE (chain_name, hash, kind)
+);
+
+CREATE INDEX timestamp_anomalies_chain_idx ON timestamp_anomalies (chain_name, block_number);
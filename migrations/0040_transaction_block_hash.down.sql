@@ -0,0 +1,2 @@
+DROP INDEX IF EXISTS transactions_block_hash_idx;
+ALTER TABLE transactions DROP COLUMN block_hash;
@@ -0,0 +1,6 @@
+-- The block each transaction was included in, so a reorg orphans exactly the transactions of the
+-- replaced block, even when the shards of an address-sharded stream store them separately. NULL
+-- for rows stored before.
+ALTER TABLE transactions ADD COLUMN block_hash TEXT;
+
+CREATE INDEX transactions_block_hash_idx ON transactions (chain_name, block_hash);
@@ -0,0 +1,14 @@
+-- Blocks a projection failed to fold, kept with the block itself so the fold can be retried
+-- after the consumer has acknowledged it.
+CREATE TABLE projection_failures (
+    projection TEXT NOT NULL,
+    chain_name TEXT NOT NULL,
+    schema_name TEXT NOT NULL,
+    block_hash TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    block JSONB NOT NULL,
+    error TEXT NOT NULL,
+    attempts INTEGER NOT NULL DEFAULT 1,
+    failed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    PRIMARY KEY (projection, chain_name, schema_name, block_hash)
+);
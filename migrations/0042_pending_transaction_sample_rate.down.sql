@@ -0,0 +1 @@
+ALTER TABLE pending_transactions DROP COLUMN sample_rate;
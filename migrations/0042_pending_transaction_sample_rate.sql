@@ -0,0 +1,2 @@
+-- Share of the mempool a sampled tracker recorded, NULL when it recorded every transaction.
+ALTER TABLE pending_transactions ADD COLUMN sample_rate DOUBLE PRECISION;
@@ -0,0 +1,3 @@
+-- The blob fee of EIP-4844 transactions, `blobGasUsed * blobGasPrice` in wei. `fee_total` now
+-- also includes it.
+ALTER TABLE transactions ADD COLUMN fee_blob NUMERIC;
@@ -0,0 +1,4 @@
+ALTER TABLE backfill_chunks DROP CONSTRAINT IF EXISTS backfill_chunks_grid_key;
+ALTER TABLE backfill_chunks ADD UNIQUE (chain_name, schema_name, start_block, end_block);
+ALTER TABLE backfill_chunks DROP COLUMN IF EXISTS produced_through;
+ALTER TABLE backfill_chunks DROP COLUMN IF EXISTS grid_start;
@@ -0,0 +1,27 @@
+-- Backfill chunks are keyed by their cell on the chunk grid, so a later run extends the partial
+-- chunk at either end of a range instead of planning an overlapping one, and `produced_through`
+-- keeps the blocks already produced from being produced again.
+ALTER TABLE backfill_chunks ADD COLUMN grid_start BIGINT;
+ALTER TABLE backfill_chunks ADD COLUMN produced_through BIGINT;
+
+-- Chunks planned before this share a start when a later run re-planned a trailing partial one;
+-- the widest covers the others.
+DELETE FROM backfill_chunks narrower USING backfill_chunks wider
+WHERE narrower.chain_name = wider.chain_name
+    AND narrower.schema_name = wider.schema_name
+    AND narrower.start_block = wider.start_block
+    AND narrower.end_block < wider.end_block;
+
+UPDATE backfill_chunks SET grid_start = start_block,
+    produced_through = CASE WHEN status = 'done' THEN end_block END;
+ALTER TABLE backfill_chunks ALTER COLUMN grid_start SET NOT NULL;
+
+DO $$
+DECLARE
+    old_key TEXT;
+BEGIN
+    SELECT conname INTO old_key FROM pg_constraint
+    WHERE conrelid = 'backfill_chunks'::regclass AND contype = 'u';
+    EXECUTE format('ALTER TABLE backfill_chunks DROP CONSTRAINT %I', old_key);
+END $$;
+ALTER TABLE backfill_chunks ADD CONSTRAINT backfill_chunks_grid_key UNIQUE (chain_name, schema_name, grid_start);
@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS shutdown_summaries_instance_at_idx;
+DROP INDEX IF EXISTS recovery_reports_chain_instance_at_idx;
+ALTER TABLE recovery_reports DROP COLUMN IF EXISTS instance;
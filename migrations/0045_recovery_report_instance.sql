@@ -0,0 +1,6 @@
+-- Which instance wrote each recovery report, so an unclean shutdown is detected per chain and
+-- instance against that instance's own shutdown summaries.
+ALTER TABLE recovery_reports ADD COLUMN instance TEXT NOT NULL DEFAULT '';
+
+CREATE INDEX recovery_reports_chain_instance_at_idx ON recovery_reports (chain_name, instance, at);
+CREATE INDEX shutdown_summaries_instance_at_idx ON shutdown_summaries (instance, at);
@@ -0,0 +1 @@
+DROP INDEX IF EXISTS mev_observations_finding_key;
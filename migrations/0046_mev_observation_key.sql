@@ -0,0 +1,11 @@
+-- One row per finding, so a block redelivered to the detector doesn't record it again.
+DELETE FROM mev_observations duplicate USING mev_observations kept
+WHERE duplicate.chain_name = kept.chain_name
+    AND duplicate.block_hash = kept.block_hash
+    AND duplicate.kind = kept.kind
+    AND duplicate.tx_hashes = kept.tx_hashes
+    AND COALESCE(duplicate.details ->> 'pool', '') = COALESCE(kept.details ->> 'pool', '')
+    AND duplicate.id > kept.id;
+
+CREATE UNIQUE INDEX mev_observations_finding_key
+    ON mev_observations (chain_name, block_hash, kind, tx_hashes, (COALESCE(details ->> 'pool', '')));
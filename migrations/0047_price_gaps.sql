@@ -0,0 +1,10 @@
+-- Assets a price window couldn't price, until a later attempt at the same window does.
+CREATE TABLE price_gaps (
+    chain_name TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    asset TEXT NOT NULL,
+    reason TEXT NOT NULL,
+    detail TEXT,
+    recorded_at TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'UTC'),
+    PRIMARY KEY (chain_name, asset, block_number)
+);
@@ -0,0 +1,13 @@
+-- Each block's share of address_activity, so a redelivered block isn't counted twice and an
+-- orphaned one can be taken back out.
+CREATE TABLE address_block_activity (
+    chain_name TEXT NOT NULL,
+    block_hash TEXT NOT NULL,
+    address TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    tx_count BIGINT NOT NULL,
+    total_value NUMERIC NOT NULL,
+    PRIMARY KEY (chain_name, block_hash, address)
+);
+
+CREATE INDEX address_block_activity_address_idx ON address_block_activity (chain_name, address, block_number);
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS token_balance_blocks;
+ALTER TABLE token_balances DROP COLUMN IF EXISTS reconciled_block;
+DROP INDEX IF EXISTS token_transfers_block_hash_idx;
+ALTER TABLE token_transfers DROP COLUMN IF EXISTS block_hash;
@@ -0,0 +1,14 @@
+-- Which block each transfer came from, so an orphaned block's transfers can be taken back out.
+ALTER TABLE token_transfers ADD COLUMN block_hash TEXT;
+CREATE INDEX token_transfers_block_hash_idx ON token_transfers (chain_name, block_hash);
+
+-- The block a balance was last read from the node at. Transfers up to it are already in it.
+ALTER TABLE token_balances ADD COLUMN reconciled_block BIGINT;
+
+-- Blocks whose transfers were applied to token_balances, so a redelivered block isn't applied again.
+CREATE TABLE token_balance_blocks (
+    chain_name TEXT NOT NULL,
+    block_hash TEXT NOT NULL,
+    block_number BIGINT NOT NULL,
+    PRIMARY KEY (chain_name, block_hash)
+);
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Transaction, U256};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AddressActivityConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+#[derive(Default)]
+struct ActivityDelta {
+    tx_count: i64,
+    total_value: U256,
+}
+
+/// Keeps `address_activity` up to date, one batched upsert per committed block, so per-wallet
+/// lookups don't need to scan the transactions table. Each block's share is kept by block hash in
+/// `address_block_activity` and only added once, so redelivered and re-backfilled blocks aren't
+/// counted twice, and orphaned blocks are subtracted again.
+pub struct AddressActivityIndexer {
+    pg_pool: Arc<PgPool>,
+}
+
+impl AddressActivityIndexer {
+    pub fn new(pg_pool: Arc<PgPool>) -> Self {
+        Self { pg_pool }
+    }
+
+    /// Sums up per address activity for a block. An address is counted once per transaction even
+    /// if it is both sender and recipient.
+    fn collect_deltas(block: &Block<Transaction>) -> HashMap<Address, ActivityDelta> {
+        let mut deltas: HashMap<Address, ActivityDelta> = HashMap::new();
+        for transaction in &block.transactions {
+            let mut participants = vec![transaction.from];
+            if let Some(to) = transaction.to {
+                if to != transaction.from {
+                    participants.push(to);
+                }
+            }
+            for address in participants {
+                let delta = deltas.entry(address).or_default();
+                delta.tx_count += 1;
+                delta.total_value = delta.total_value.saturating_add(transaction.value);
+            }
+        }
+        deltas
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for AddressActivityIndexer {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let deltas = Self::collect_deltas(block);
+        if deltas.is_empty() {
+            return Ok(());
+        }
+
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let mut addresses = Vec::with_capacity(deltas.len());
+        let mut tx_counts = Vec::with_capacity(deltas.len());
+        let mut values = Vec::with_capacity(deltas.len());
+        for (address, delta) in deltas {
+            addresses.push(format!("{:?}", address));
+            tx_counts.push(delta.tx_count);
+            values.push(delta.total_value.to_string());
+        }
+
+        sqlx::query(
+            "WITH added AS (
+                INSERT INTO address_block_activity (chain_name, block_hash, address, block_number, tx_count, total_value)
+                SELECT $1, $2, address, $3, tx_count, total_value::numeric
+                FROM UNNEST($4::text[], $5::bigint[], $6::text[]) AS d(address, tx_count, total_value)
+                ON CONFLICT (chain_name, block_hash, address) DO NOTHING
+                RETURNING address, tx_count, total_value
+            )
+            INSERT INTO address_activity (chain_name, address, first_seen_block, last_seen_block, tx_count, total_value)
+            SELECT $1, address, $3, $3, tx_count, total_value FROM added
+            ON CONFLICT (chain_name, address) DO UPDATE SET
+                first_seen_block = LEAST(address_activity.first_seen_block, EXCLUDED.first_seen_block),
+                last_seen_block = GREATEST(address_activity.last_seen_block, EXCLUDED.last_seen_block),
+                tx_count = address_activity.tx_count + EXCLUDED.tx_count,
+                total_value = address_activity.total_value + EXCLUDED.total_value",
+        )
+        .bind(chain_name)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(block_number)
+        .bind(addresses)
+        .bind(tx_counts)
+        .bind(values)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        Ok(())
+    }
+
+    /// Takes the orphaned blocks' shares back out. First and last seen blocks are recomputed from
+    /// the blocks left, and addresses only the orphaned blocks had are removed.
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        let mut db_tx = self.pg_pool.begin().await?;
+        // The subqueries see the rows the DELETE removes, so they leave those blocks out themselves.
+        let addresses: Vec<String> = sqlx::query_scalar(
+            "WITH removed AS (
+                DELETE FROM address_block_activity WHERE chain_name = $1 AND block_hash = ANY($2)
+                RETURNING address, tx_count, total_value
+            ),
+            totals AS (
+                SELECT address, SUM(tx_count)::bigint AS tx_count, SUM(total_value) AS total_value FROM removed GROUP BY address
+            )
+            UPDATE address_activity a SET
+                tx_count = a.tx_count - t.tx_count,
+                total_value = a.total_value - t.total_value,
+                first_seen_block = COALESCE(
+                    (SELECT MIN(block_number) FROM address_block_activity b
+                    WHERE b.chain_name = $1 AND b.address = a.address AND b.block_hash <> ALL($2)),
+                    a.first_seen_block
+                ),
+                last_seen_block = COALESCE(
+                    (SELECT MAX(block_number) FROM address_block_activity b
+                    WHERE b.chain_name = $1 AND b.address = a.address AND b.block_hash <> ALL($2)),
+                    a.last_seen_block
+                )
+            FROM totals t
+            WHERE a.chain_name = $1 AND a.address = t.address
+            RETURNING a.address",
+        )
+        .bind(chain_name)
+        .bind(&hashes)
+        .fetch_all(&mut db_tx)
+        .await?;
+        sqlx::query("DELETE FROM address_activity WHERE chain_name = $1 AND address = ANY($2) AND tx_count <= 0")
+            .bind(chain_name)
+            .bind(&addresses)
+            .execute(&mut db_tx)
+            .await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+}
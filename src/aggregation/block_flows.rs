@@ -0,0 +1,160 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Transaction, H256, U256, U64};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::enrichment::transfers::decode_erc20_transfers;
+use crate::streams::consumers::hooks::ConsumerHook;
+
+/// `token` of native value transfers.
+const NATIVE: &str = "native";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BlockFlowsConfig {
+    pub enabled: bool,
+    /// Count the value of transactions that didn't revert, read from the block's receipts. Value
+    /// moved by internal calls isn't seen.
+    pub native: bool,
+    /// Count ERC-20 `Transfer` logs, fetched per block.
+    pub erc20: bool,
+}
+
+impl Default for BlockFlowsConfig {
+    fn default() -> Self {
+        Self { enabled: false, native: true, erc20: true }
+    }
+}
+
+#[derive(Default)]
+struct Flow {
+    inflow: U256,
+    outflow: U256,
+    transfer_count: i32,
+}
+
+impl Flow {
+    /// Signed decimal `inflow - outflow`.
+    fn net(&self) -> String {
+        if self.inflow >= self.outflow {
+            (self.inflow - self.outflow).to_string()
+        } else {
+            format!("-{}", self.outflow - self.inflow)
+        }
+    }
+}
+
+/// Writes each committed block's net flow per (address, token) to `block_flows`, so consumers of
+/// per-block exposure don't re-aggregate the raw transfers. Mints and burns count against the
+/// zero address. A block's rows are replaced whenever it is committed again, so redeliveries and
+/// reorgs leave only the canonical block's flows.
+pub struct BlockFlowAggregator {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    native: bool,
+    erc20: bool,
+}
+
+impl BlockFlowAggregator {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, config: &BlockFlowsConfig) -> Self {
+        Self { adapter, pg_pool, native: config.native, erc20: config.erc20 }
+    }
+
+    /// Hashes of the block's reverted transactions, which moved no value. Receipts are only
+    /// fetched for blocks with a value transfer to count.
+    async fn reverted(&self, block: &Block<Transaction>) -> Result<HashSet<H256>> {
+        if !block.transactions.iter().any(|transaction| transaction.to.is_some() && !transaction.value.is_zero()) {
+            return Ok(HashSet::new());
+        }
+        let receipts = self.adapter.get_block_receipts(block.number.unwrap_or_default().as_u64()).await?;
+        Ok(receipts
+            .into_iter()
+            .filter(|receipt| receipt.status == Some(U64::zero()))
+            .map(|receipt| receipt.transaction_hash)
+            .collect())
+    }
+
+    fn add(flows: &mut HashMap<(Address, String), Flow>, token: &str, from: Address, to: Address, value: U256) {
+        // A transfer to oneself moves nothing.
+        if value.is_zero() || from == to {
+            return;
+        }
+        let outgoing = flows.entry((from, token.to_string())).or_default();
+        outgoing.outflow = outgoing.outflow.saturating_add(value);
+        outgoing.transfer_count += 1;
+        let incoming = flows.entry((to, token.to_string())).or_default();
+        incoming.inflow = incoming.inflow.saturating_add(value);
+        incoming.transfer_count += 1;
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for BlockFlowAggregator {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let mut flows: HashMap<(Address, String), Flow> = HashMap::new();
+        if self.native {
+            let failed = self.reverted(block).await?;
+            for transaction in &block.transactions {
+                if failed.contains(&transaction.hash) {
+                    continue;
+                }
+                // Contract creations move their value to the new contract, whose address isn't known here.
+                if let Some(to) = transaction.to {
+                    Self::add(&mut flows, NATIVE, transaction.from, to, transaction.value);
+                }
+            }
+        }
+        if self.erc20 {
+            for transfer in decode_erc20_transfers(&self.adapter.get_logs(block_number).await?) {
+                Self::add(&mut flows, &format!("{:?}", transfer.token), transfer.from, transfer.to, transfer.value);
+            }
+        }
+
+        let mut addresses = Vec::with_capacity(flows.len());
+        let mut tokens = Vec::with_capacity(flows.len());
+        let mut inflows = Vec::with_capacity(flows.len());
+        let mut outflows = Vec::with_capacity(flows.len());
+        let mut nets = Vec::with_capacity(flows.len());
+        let mut transfer_counts = Vec::with_capacity(flows.len());
+        for ((address, token), flow) in flows {
+            addresses.push(format!("{:?}", address));
+            tokens.push(token);
+            inflows.push(flow.inflow.to_string());
+            outflows.push(flow.outflow.to_string());
+            nets.push(flow.net());
+            transfer_counts.push(flow.transfer_count);
+        }
+
+        let mut tx = self.pg_pool.begin().await?;
+        sqlx::query("DELETE FROM block_flows WHERE chain_name = $1 AND block_number = $2")
+            .bind(chain_name)
+            .bind(block_number as i64)
+            .execute(&mut tx)
+            .await?;
+        if !addresses.is_empty() {
+            sqlx::query(
+                "INSERT INTO block_flows (chain_name, block_number, address, token, inflow, outflow, net, transfer_count)
+                SELECT $1, $2, address, token, inflow::numeric, outflow::numeric, net::numeric, transfer_count
+                FROM UNNEST($3::text[], $4::text[], $5::text[], $6::text[], $7::text[], $8::int[])
+                    AS f(address, token, inflow, outflow, net, transfer_count)",
+            )
+            .bind(chain_name)
+            .bind(block_number as i64)
+            .bind(addresses)
+            .bind(tokens)
+            .bind(inflows)
+            .bind(outflows)
+            .bind(nets)
+            .bind(transfer_counts)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,127 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use log::{debug, error};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::streams::consumers::hooks::ConsumerHook;
+
+const SECONDS_PER_DAY: u64 = 86_400;
+
+#[derive(Debug, Deserialize)]
+pub struct AggregationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_interval_secs() -> u64 {
+    60
+}
+
+impl Default for AggregationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: default_interval_secs(),
+        }
+    }
+}
+
+/// Rolls ingested blocks and transactions up into `chain_daily_stats`.
+///
+/// As a consumer hook it only records which (chain, day) pairs received new blocks; the scheduled
+/// `run` loop then recomputes just those days, so the table stays current without full rescans.
+pub struct DailyStatsAggregator {
+    pg_pool: Arc<PgPool>,
+    interval: Duration,
+    // (chain_name, days since the unix epoch)
+    dirty_days: Mutex<HashSet<(String, i32)>>,
+}
+
+impl DailyStatsAggregator {
+    pub fn new(pg_pool: Arc<PgPool>, config: &AggregationConfig) -> Self {
+        Self {
+            pg_pool,
+            interval: Duration::from_secs(config.interval_secs),
+            dirty_days: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Periodically refreshes every day touched since the previous run. Runs forever.
+    pub async fn run(&self) -> Result<()> {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+
+            let days: Vec<(String, i32)> = self.dirty_days.lock().await.drain().collect();
+            for (chain_name, day) in days {
+                if let Err(e) = self.refresh_day(&chain_name, day).await {
+                    error!("Failed to aggregate daily stats for {} day {}: {}", chain_name, day, e);
+                    // Try again on the next tick.
+                    self.dirty_days.lock().await.insert((chain_name, day));
+                }
+            }
+        }
+    }
+
+    /// Recomputes the stats row for one chain and day from the canonical blocks and transactions.
+    pub async fn refresh_day(&self, chain_name: &str, day: i32) -> Result<()> {
+        sqlx::query(
+            "WITH day_blocks AS (
+                SELECT block_number, gas_used FROM blocks
+                WHERE chain_name = $1 AND canonical
+                  AND timestamp >= DATE '1970-01-01' + $2::integer
+                  AND timestamp < DATE '1970-01-01' + $2::integer + 1
+            ),
+            day_txs AS (
+                SELECT t.from_address, t.to_address, t.gas_price FROM transactions t
+                JOIN day_blocks b ON b.block_number = t.block_number
+                WHERE t.chain_name = $1 AND t.canonical
+            )
+            INSERT INTO chain_daily_stats (chain_name, day, block_count, tx_count, active_addresses, gas_used, avg_gas_price, updated_at)
+            SELECT
+                $1,
+                DATE '1970-01-01' + $2::integer,
+                (SELECT COUNT(*) FROM day_blocks),
+                (SELECT COUNT(*) FROM day_txs),
+                (SELECT COUNT(*) FROM (
+                    SELECT from_address FROM day_txs
+                    UNION
+                    SELECT to_address FROM day_txs WHERE to_address IS NOT NULL
+                ) addresses),
+                (SELECT COALESCE(SUM(gas_used), 0) FROM day_blocks),
+                (SELECT AVG(gas_price::numeric) FROM day_txs),
+                NOW()
+            ON CONFLICT (chain_name, day) DO UPDATE SET
+                block_count = EXCLUDED.block_count,
+                tx_count = EXCLUDED.tx_count,
+                active_addresses = EXCLUDED.active_addresses,
+                gas_used = EXCLUDED.gas_used,
+                avg_gas_price = EXCLUDED.avg_gas_price,
+                updated_at = EXCLUDED.updated_at",
+        )
+        .bind(chain_name)
+        .bind(day)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        debug!("Refreshed daily stats for {} day {}", chain_name, day);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for DailyStatsAggregator {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let day = (block.timestamp.as_u64() / SECONDS_PER_DAY) as i32;
+        self.dirty_days.lock().await.insert((chain_name.to_string(), day));
+        Ok(())
+    }
+}
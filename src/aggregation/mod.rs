@@ -0,0 +1,3 @@
+pub mod address_activity;
+pub mod block_flows;
+pub mod daily_stats;
@@ -0,0 +1,71 @@
+pub mod slo;
+pub mod stuck_transactions;
+pub mod webhook;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::sync::Arc;
+
+use crate::alerting::webhook::WebhookAlertHook;
+use crate::secrets;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AlertingConfig {
+    #[serde(default)]
+    pub webhooks: Vec<String>, // env vars holding webhook URLs, each POSTed every alert as JSON
+}
+
+/// A condition operators should look at.
+#[derive(Debug, Clone, Serialize)]
+pub struct Alert {
+    pub chain_name: String,
+    /// What was detected, e.g. `stuck_transaction` or `nonce_gap`.
+    pub kind: String,
+    pub message: String,
+    pub details: Value,
+}
+
+/// A destination for alerts. Embedding crates add their own through `Registries::alert_hooks`.
+#[async_trait]
+pub trait AlertHook: Send + Sync {
+    async fn fire(&self, alert: &Alert) -> Result<()>;
+}
+
+/// Fans each alert out to every configured hook. Alerts are always logged, so they are visible
+/// even with no hooks configured.
+#[derive(Clone)]
+pub struct Alerter {
+    hooks: Vec<Arc<dyn AlertHook>>,
+}
+
+impl Alerter {
+    pub fn new(config: &AlertingConfig, extra_hooks: &[Arc<dyn AlertHook>]) -> Self {
+        let hooks = config
+            .webhooks
+            .iter()
+            .map(|url| Arc::new(WebhookAlertHook::new(url)) as Arc<dyn AlertHook>)
+            .chain(extra_hooks.iter().cloned())
+            .collect();
+        Self { hooks }
+    }
+
+    /// Delivers `alert` to every hook, with secrets redacted. A failing hook is logged and doesn't
+    /// stop the others.
+    pub async fn fire(&self, alert: &Alert) {
+        let alert = &Alert {
+            chain_name: alert.chain_name.clone(),
+            kind: alert.kind.clone(),
+            message: secrets::redact(&alert.message),
+            details: secrets::redact_json(&alert.details),
+        };
+        warn!("Alert on {} ({}): {}", alert.chain_name, alert.kind, alert.message);
+        for hook in &self.hooks {
+            if let Err(e) = hook.fire(alert).await {
+                error!("Failed to deliver {} alert for {}: {}", alert.kind, alert.chain_name, e);
+            }
+        }
+    }
+}
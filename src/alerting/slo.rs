@@ -0,0 +1,145 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::alerting::{Alert, Alerter};
+use crate::metrics;
+use crate::streams::consumers::hooks::ConsumerHook;
+use crate::streams::schemas::evm::received_at;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SloConfig {
+    /// How many of the latest blocks the percentiles are computed over.
+    pub window_blocks: usize,
+    /// Latency thresholds in seconds. A percentile above its threshold is alerted.
+    pub p50_secs: Option<f64>,
+    pub p95_secs: Option<f64>,
+    pub p99_secs: Option<f64>,
+}
+
+impl Default for SloConfig {
+    fn default() -> Self {
+        Self { window_blocks: 1000, p50_secs: None, p95_secs: None, p99_secs: None }
+    }
+}
+
+/// Nearest-rank percentile of an ascending slice.
+fn percentile(sorted: &[f64], p: f64) -> f64 {
+    let rank = ((p / 100.0) * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Measures each committed block's end-to-end latency, from its timestamp to its commit to the
+/// sink, into `latency_samples`. The p50/p95/p99 over the last `window_blocks` are exported as
+/// `ingestion_latency_seconds` and checked against the configured thresholds: crossing one fires
+/// an `slo_breach` alert, and falling back under it an `slo_recovered` one.
+pub struct LatencyTracker {
+    pg_pool: Arc<PgPool>,
+    alerter: Alerter,
+    window_blocks: usize,
+    thresholds: Vec<(&'static str, f64, f64)>,
+    window: Mutex<VecDeque<f64>>,
+    breached: Mutex<HashSet<&'static str>>,
+}
+
+impl LatencyTracker {
+    pub fn new(pg_pool: Arc<PgPool>, alerter: Alerter, config: &SloConfig) -> Self {
+        let thresholds = [("p50", 50.0, config.p50_secs), ("p95", 95.0, config.p95_secs), ("p99", 99.0, config.p99_secs)]
+            .into_iter()
+            .filter_map(|(quantile, p, threshold)| threshold.map(|threshold| (quantile, p, threshold)))
+            .collect();
+        Self {
+            pg_pool,
+            alerter,
+            window_blocks: config.window_blocks.max(1),
+            thresholds,
+            window: Mutex::new(VecDeque::new()),
+            breached: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Adds a sample to the window and returns the window's sorted latencies.
+    fn observe(&self, latency_secs: f64) -> Vec<f64> {
+        let mut window = self.window.lock().unwrap_or_else(|e| e.into_inner());
+        window.push_back(latency_secs);
+        while window.len() > self.window_blocks {
+            window.pop_front();
+        }
+        let mut sorted: Vec<f64> = window.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        sorted
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for LatencyTracker {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        // Only realtime blocks carry the time they were received. Backfilled ones are old by
+        // design and would hold every percentile above its threshold.
+        if received_at(block).is_none() {
+            return Ok(());
+        }
+        let committed_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as i64;
+        let block_ms = block.timestamp.as_u64() as i64 * 1000;
+        let latency_ms = (committed_ms - block_ms).max(0);
+
+        sqlx::query(
+            "INSERT INTO latency_samples (chain_name, block_number, block_hash, block_timestamp, committed_at, latency_ms)
+            VALUES ($1, $2, $3, to_timestamp($4) AT TIME ZONE 'UTC', to_timestamp($5) AT TIME ZONE 'UTC', $6)
+            ON CONFLICT (chain_name, block_hash) DO NOTHING",
+        )
+        .bind(chain_name)
+        .bind(block.number.unwrap_or_default().as_u64() as i64)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(block_ms as f64 / 1000.0)
+        .bind(committed_ms as f64 / 1000.0)
+        .bind(latency_ms)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        let sorted = self.observe(latency_ms as f64 / 1000.0);
+        for (quantile, p) in [("p50", 50.0), ("p95", 95.0), ("p99", 99.0)] {
+            metrics::set_gauge(
+                "ingestion_latency_seconds",
+                &[("chain", chain_name), ("quantile", quantile)],
+                percentile(&sorted, p),
+            );
+        }
+
+        for (quantile, p, threshold) in &self.thresholds {
+            let value = percentile(&sorted, *p);
+            let changed = {
+                let mut breached = self.breached.lock().unwrap_or_else(|e| e.into_inner());
+                if value > *threshold {
+                    breached.insert(*quantile)
+                } else {
+                    breached.remove(quantile)
+                }
+            };
+            if !changed {
+                continue;
+            }
+            let (kind, message) = if value > *threshold {
+                ("slo_breach", format!("{} ingestion latency is {:.1}s, above the {:.1}s SLO", quantile, value, threshold))
+            } else {
+                ("slo_recovered", format!("{} ingestion latency is {:.1}s, back under the {:.1}s SLO", quantile, value, threshold))
+            };
+            self.alerter
+                .fire(&Alert {
+                    chain_name: chain_name.to_string(),
+                    kind: kind.to_string(),
+                    message,
+                    details: json!({ "quantile": quantile, "latency_secs": value, "threshold_secs": threshold, "window_blocks": sorted.len() }),
+                })
+                .await;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,181 @@
+use anyhow::{Context, Result};
+use ethers::types::Address;
+use log::error;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::alerting::{Alert, Alerter};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct StuckTransactionsConfig {
+    /// Pending transactions older than this are reported as stuck.
+    pub threshold_secs: u64,
+    pub interval_secs: u64,
+}
+
+impl Default for StuckTransactionsConfig {
+    fn default() -> Self {
+        Self { threshold_secs: 600, interval_secs: 60 }
+    }
+}
+
+/// A problem found for one address, identified by its kind and nonce.
+struct Finding {
+    kind: &'static str,
+    nonce: i64,
+    tx_hash: Option<String>,
+    message: String,
+}
+
+/// Background task that watches the pending transactions of a chain's `watched_addresses`, as
+/// tracked in `pending_transactions`:
+///
+/// - `stuck_transaction`: pending for longer than `threshold_secs`.
+/// - `nonce_gap`: a nonce between the next confirmed nonce and a pending transaction's that
+///   nothing pending uses, which holds up every later transaction.
+///
+/// Each finding is recorded in `stuck_transactions` and alerted once; it is resolved when it
+/// no longer holds.
+pub struct StuckTransactionMonitor {
+    pg_pool: Arc<PgPool>,
+    chain_name: String,
+    addresses: Vec<String>,
+    alerter: Alerter,
+    threshold: Duration,
+    interval: Duration,
+}
+
+impl StuckTransactionMonitor {
+    pub fn new(
+        pg_pool: Arc<PgPool>,
+        chain_name: &str,
+        watched_addresses: &[String],
+        alerter: Alerter,
+        config: &StuckTransactionsConfig,
+    ) -> Result<Self> {
+        // Stored addresses use the lowercase hex of `{:?}`.
+        let addresses = watched_addresses
+            .iter()
+            .map(|address| {
+                address
+                    .parse::<Address>()
+                    .map(|address| format!("{:?}", address))
+                    .with_context(|| format!("Invalid watched address `{}`", address))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            pg_pool,
+            chain_name: chain_name.to_string(),
+            addresses,
+            alerter,
+            threshold: Duration::from_secs(config.threshold_secs),
+            interval: Duration::from_secs(config.interval_secs),
+        })
+    }
+
+    /// Checks every watched address each interval. Runs forever.
+    pub async fn run(&self) -> Result<()> {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            for address in &self.addresses {
+                if let Err(e) = self.check(address).await {
+                    error!("Stuck transaction check for {} on {} failed: {}", address, self.chain_name, e);
+                }
+            }
+        }
+    }
+
+    async fn check(&self, address: &str) -> Result<()> {
+        let confirmed: Option<i64> = sqlx::query(
+            "SELECT MAX(nonce) + 1 AS next_nonce FROM transactions WHERE chain_name = $1 AND from_address = $2 AND canonical",
+        )
+        .bind(&self.chain_name)
+        .bind(address)
+        .fetch_one(self.pg_pool.as_ref())
+        .await?
+        .try_get("next_nonce")?;
+
+        let pending = sqlx::query(
+            "SELECT tx_hash, nonce, EXTRACT(EPOCH FROM NOW() - first_seen)::bigint AS age_secs
+            FROM pending_transactions
+            WHERE chain_name = $1 AND from_address = $2 AND status = 'pending'
+            ORDER BY nonce",
+        )
+        .bind(&self.chain_name)
+        .bind(address)
+        .fetch_all(self.pg_pool.as_ref())
+        .await?;
+
+        let mut findings = Vec::new();
+        // Without ingested history the next nonce is unknown, so gaps start at the lowest pending one.
+        let mut expected = confirmed.or_else(|| pending.first().and_then(|row| row.try_get("nonce").ok()));
+        for row in &pending {
+            let tx_hash: String = row.try_get("tx_hash")?;
+            let nonce: i64 = row.try_get("nonce")?;
+            let age_secs: i64 = row.try_get("age_secs")?;
+            if age_secs as u64 > self.threshold.as_secs() {
+                findings.push(Finding {
+                    kind: "stuck_transaction",
+                    nonce,
+                    tx_hash: Some(tx_hash),
+                    message: format!("Transaction with nonce {} from {} has been pending for {}s", nonce, address, age_secs),
+                });
+            }
+            if let Some(next) = expected {
+                for missing in next..nonce {
+                    findings.push(Finding {
+                        kind: "nonce_gap",
+                        nonce: missing,
+                        tx_hash: None,
+                        message: format!("Nonce {} of {} is missing, holding up nonce {}", missing, address, nonce),
+                    });
+                }
+                expected = Some(next.max(nonce + 1));
+            }
+        }
+
+        let mut open_keys = Vec::with_capacity(findings.len());
+        for finding in &findings {
+            open_keys.push(format!("{}:{}", finding.kind, finding.nonce));
+            let inserted = sqlx::query(
+                "INSERT INTO stuck_transactions (chain_name, address, kind, nonce, tx_hash, message)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (chain_name, address, kind, nonce) WHERE resolved_at IS NULL DO NOTHING",
+            )
+            .bind(&self.chain_name)
+            .bind(address)
+            .bind(finding.kind)
+            .bind(finding.nonce)
+            .bind(&finding.tx_hash)
+            .bind(&finding.message)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+            if inserted.rows_affected() == 1 {
+                self.alerter
+                    .fire(&Alert {
+                        chain_name: self.chain_name.clone(),
+                        kind: finding.kind.to_string(),
+                        message: finding.message.clone(),
+                        details: json!({ "address": address, "nonce": finding.nonce, "tx_hash": finding.tx_hash }),
+                    })
+                    .await;
+            }
+        }
+
+        sqlx::query(
+            "UPDATE stuck_transactions SET resolved_at = NOW()
+            WHERE chain_name = $1 AND address = $2 AND resolved_at IS NULL AND NOT (kind || ':' || nonce = ANY($3))",
+        )
+        .bind(&self.chain_name)
+        .bind(address)
+        .bind(&open_keys)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,27 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use reqwest::Client;
+use std::time::Duration;
+
+use crate::alerting::{Alert, AlertHook};
+
+/// POSTs each alert as JSON to a URL, e.g. a chat or paging integration's incoming webhook.
+pub struct WebhookAlertHook {
+    client: Client,
+    url: String,
+}
+
+impl WebhookAlertHook {
+    pub fn new(url: &str) -> Self {
+        let client = Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default();
+        Self { client, url: url.to_string() }
+    }
+}
+
+#[async_trait]
+impl AlertHook for WebhookAlertHook {
+    async fn fire(&self, alert: &Alert) -> Result<()> {
+        self.client.post(&self.url).json(alert).send().await?.error_for_status()?;
+        Ok(())
+    }
+}
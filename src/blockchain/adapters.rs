@@ -1,7 +1,35 @@
 use std::pin::Pin;
 use futures_core::{Future, Stream};
-use ethers::types::{Block, Transaction};
-use anyhow::Result as AnyResult;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+use anyhow::{anyhow, Result as AnyResult};
+
+/// A request the provider turned away for load rather than failed: rate limited (HTTP 429,
+/// JSON-RPC -32005) or timed out. Adapters return it inside their errors, so wrappers like
+/// `adaptive_throttle` can back off without parsing error messages.
+#[derive(Debug)]
+pub struct Throttled {
+    pub message: String,
+}
+
+impl std::fmt::Display for Throttled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl std::error::Error for Throttled {}
+
+/// Whether `error`, or an error it wraps, is `Throttled`.
+pub fn is_throttled(error: &anyhow::Error) -> bool {
+    error.downcast_ref::<Throttled>().is_some()
+}
+
+/// Keeps the logs of block `block_hash` out of what `get_logs` returned for its height, dropping
+/// those of another block there, e.g. the one that replaced it in a reorg. Logs that don't name
+/// their block are kept.
+pub fn logs_of_block(logs: Vec<Log>, block_hash: Option<H256>) -> Vec<Log> {
+    logs.into_iter().filter(|log| log.block_hash.is_none() || log.block_hash == block_hash).collect()
+}
 
 pub trait BlockchainAdapter: Send + Sync {
     // fn chain_name(&self) -> &str;
@@ -12,13 +40,85 @@ pub trait BlockchainAdapter: Send + Sync {
         block_number: u64,
     ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>>;
 
+    /// Fetches a block with transaction hashes instead of bodies, which is far cheaper to serve.
+    fn get_block_with_hashes(
+        &self,
+        _block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        Box::pin(async { Err(anyhow!("get_block_with_hashes is not supported by this adapter")) })
+    }
+
     /// Subscribes to new blocks, yielding full Block structs.
     fn subscribe_new_blocks(
         &self,
     ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>;
 
+    /// Subscribes to transactions entering the node's mempool, yielding full transactions.
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        Box::pin(futures_util::stream::once(async {
+            Err(anyhow!("subscribe_pending_transactions is not supported by this adapter"))
+        }))
+    }
+
     /// Retrieves the latest block number.
     fn get_latest_block_number(
         &self,
     ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>>;
+
+    /// Streams the blocks `start_block..=end_block` in order, for sources that can serve a range
+    /// faster than one request per block. `None` means callers should use `get_block_by_number`.
+    fn stream_blocks(
+        &self,
+        _start_block: u64,
+        _end_block: u64,
+    ) -> Option<Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>> {
+        None
+    }
+
+    /// Retrieves the native balance of an address as of a given block.
+    fn get_balance(
+        &self,
+        _address: Address,
+        _block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        Box::pin(async { Err(anyhow!("get_balance is not supported by this adapter")) })
+    }
+
+    /// Retrieves every log emitted in a given block.
+    fn get_logs(
+        &self,
+        _block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        Box::pin(async { Err(anyhow!("get_logs is not supported by this adapter")) })
+    }
+
+    /// Executes a read-only contract call (`eth_call`) as of a given block.
+    fn call(
+        &self,
+        _to: Address,
+        _data: Bytes,
+        _block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        Box::pin(async { Err(anyhow!("call is not supported by this adapter")) })
+    }
+
+    /// Executes `eth_call` with a full request, i.e. with its sender, value and gas, as of a
+    /// given block.
+    fn call_request(
+        &self,
+        _request: TransactionRequest,
+        _block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        Box::pin(async { Err(anyhow!("call_request is not supported by this adapter")) })
+    }
+
+    /// Retrieves the receipts of every transaction in a given block, in block order.
+    fn get_block_receipts(
+        &self,
+        _block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        Box::pin(async { Err(anyhow!("get_block_receipts is not supported by this adapter")) })
+    }
 }
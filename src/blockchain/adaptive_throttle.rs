@@ -0,0 +1,276 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::blockchain::adapters::{is_throttled, BlockchainAdapter, Throttled};
+use crate::metrics;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+use futures_core::{Future, Stream};
+use anyhow::Result as AnyResult;
+use log::{info, warn};
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// Rate decreases closer together than this count as one, so a burst of failures from
+/// concurrent requests halves the rate once rather than collapsing it.
+const DECREASE_COOLDOWN: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Deserialize)]
+pub struct AdaptiveThrottleConfig {
+    /// Request rate backfill starts at and ramps back up to.
+    pub max_requests_per_second: f64,
+    #[serde(default = "default_min_requests_per_second")]
+    pub min_requests_per_second: f64,
+    /// Factor the rate is multiplied by when the provider throttles or times out.
+    #[serde(default = "default_decrease_factor")]
+    pub decrease_factor: f64,
+    /// Requests per second regained for every second without errors.
+    #[serde(default = "default_ramp_up_per_sec")]
+    pub ramp_up_per_sec: f64,
+    /// Times a throttled request is retried before its error is returned.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+    /// A request taking longer than this is abandoned and counts as throttled.
+    #[serde(default = "default_request_timeout_secs")]
+    pub request_timeout_secs: u64,
+}
+
+fn default_min_requests_per_second() -> f64 {
+    1.0
+}
+
+fn default_decrease_factor() -> f64 {
+    0.5
+}
+
+fn default_ramp_up_per_sec() -> f64 {
+    1.0
+}
+
+fn default_max_retries() -> u32 {
+    8
+}
+
+fn default_request_timeout_secs() -> u64 {
+    30
+}
+
+struct ThrottleState {
+    rate: f64,
+    next_slot: Instant,
+    adjusted_at: Instant,
+    decreased_at: Option<Instant>,
+}
+
+/// Additive-increase/multiplicative-decrease request rate. Throttling errors cut the rate by
+/// `decrease_factor`; every success ramps it back up by `ramp_up_per_sec` per elapsed second.
+struct AdaptiveLimiter {
+    chain_name: String,
+    min_rate: f64,
+    max_rate: f64,
+    decrease_factor: f64,
+    ramp_up_per_sec: f64,
+    state: Mutex<ThrottleState>,
+}
+
+impl AdaptiveLimiter {
+    /// Waits until the caller may issue its next request at the current rate.
+    async fn acquire(&self) {
+        let slot = {
+            let mut state = self.state.lock().await;
+            let slot = state.next_slot.max(Instant::now());
+            state.next_slot = slot + Duration::from_secs_f64(1.0 / state.rate);
+            slot
+        };
+        tokio::time::sleep_until(slot).await;
+    }
+
+    async fn on_success(&self) {
+        let mut state = self.state.lock().await;
+        let now = Instant::now();
+        if state.rate < self.max_rate {
+            let elapsed = (now - state.adjusted_at).as_secs_f64();
+            state.rate = (state.rate + elapsed * self.ramp_up_per_sec).min(self.max_rate);
+            if state.rate == self.max_rate {
+                info!("Backfill requests for {} are back at {} per second", self.chain_name, self.max_rate);
+            }
+            self.report(state.rate);
+        }
+        state.adjusted_at = now;
+    }
+
+    async fn on_throttled(&self) {
+        metrics::increment_counter("backfill_throttled_requests_total", &[("chain", &self.chain_name)], 1);
+        let mut state = self.state.lock().await;
+        let now = Instant::now();
+        if state.decreased_at.map_or(false, |at| now - at < DECREASE_COOLDOWN) {
+            return;
+        }
+        state.rate = (state.rate * self.decrease_factor).max(self.min_rate);
+        state.decreased_at = Some(now);
+        state.adjusted_at = now;
+        warn!("Provider is throttling backfill for {}, slowing to {:.1} requests per second", self.chain_name, state.rate);
+        self.report(state.rate);
+    }
+
+    fn report(&self, rate: f64) {
+        metrics::set_gauge("backfill_requests_per_second", &[("chain", &self.chain_name)], rate);
+    }
+}
+
+/// Paces a chain's backfill requests at a rate that adapts to the provider. When the adapter
+/// reports a request as `Throttled` or it takes longer than `request_timeout_secs`, the rate drops and the request is retried after a backoff instead of
+/// failing the backfill; without errors the rate ramps back up to `max_requests_per_second`.
+pub struct AdaptiveThrottleAdapter {
+    inner: Arc<dyn BlockchainAdapter>,
+    limiter: Arc<AdaptiveLimiter>,
+    max_retries: u32,
+    request_timeout: Duration,
+}
+
+impl AdaptiveThrottleAdapter {
+    pub fn new(inner: Arc<dyn BlockchainAdapter>, chain_name: &str, config: &AdaptiveThrottleConfig) -> Self {
+        let max_rate = config.max_requests_per_second.max(0.001);
+        let min_rate = config.min_requests_per_second.clamp(0.001, max_rate);
+        let now = Instant::now();
+        let limiter = AdaptiveLimiter {
+            chain_name: chain_name.to_string(),
+            min_rate,
+            max_rate,
+            decrease_factor: config.decrease_factor.clamp(0.01, 1.0),
+            ramp_up_per_sec: config.ramp_up_per_sec.max(0.0),
+            state: Mutex::new(ThrottleState { rate: max_rate, next_slot: now, adjusted_at: now, decreased_at: None }),
+        };
+        limiter.report(max_rate);
+        Self {
+            inner,
+            limiter: Arc::new(limiter),
+            max_retries: config.max_retries,
+            request_timeout: Duration::from_secs(config.request_timeout_secs.max(1)),
+        }
+    }
+
+    fn throttled<T, F, Fut>(&self, call: F) -> Pin<Box<dyn Future<Output = AnyResult<T>> + Send>>
+    where
+        T: Send + 'static,
+        F: Fn(Arc<dyn BlockchainAdapter>) -> Fut + Send + 'static,
+        Fut: Future<Output = AnyResult<T>> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        let limiter = Arc::clone(&self.limiter);
+        let max_retries = self.max_retries;
+        let request_timeout = self.request_timeout;
+        Box::pin(async move {
+            let mut attempt = 0;
+            loop {
+                limiter.acquire().await;
+                let result = match tokio::time::timeout(request_timeout, call(Arc::clone(&inner))).await {
+                    Ok(result) => result,
+                    Err(_) => Err(anyhow::Error::new(Throttled {
+                        message: format!("Request timed out after {:?}", request_timeout),
+                    })),
+                };
+                match result {
+                    Ok(value) => {
+                        limiter.on_success().await;
+                        return Ok(value);
+                    }
+                    Err(e) if is_throttled(&e) && attempt < max_retries => {
+                        limiter.on_throttled().await;
+                        attempt += 1;
+                        tokio::time::sleep(Duration::from_millis(500 << attempt.min(6))).await;
+                    }
+                    Err(e) => {
+                        if is_throttled(&e) {
+                            limiter.on_throttled().await;
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+        })
+    }
+}
+
+impl BlockchainAdapter for AdaptiveThrottleAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        self.throttled(move |inner| inner.get_block_by_number(block_number))
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        self.throttled(move |inner| inner.get_block_with_hashes(block_number))
+    }
+
+    // A subscription is a single long-lived request, so it is not throttled.
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        self.inner.subscribe_new_blocks()
+    }
+
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        self.inner.subscribe_pending_transactions()
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        self.throttled(|inner| inner.get_latest_block_number())
+    }
+
+    fn stream_blocks(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Option<Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>> {
+        self.inner.stream_blocks(start_block, end_block)
+    }
+
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        self.throttled(move |inner| inner.get_balance(address, block_number))
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        self.throttled(move |inner| inner.get_logs(block_number))
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.throttled(move |inner| inner.call(to, data.clone(), block_number))
+    }
+
+    fn call_request(
+        &self,
+        request: TransactionRequest,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.throttled(move |inner| inner.call_request(request.clone(), block_number))
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        self.throttled(move |inner| inner.get_block_receipts(block_number))
+    }
+}
@@ -0,0 +1,225 @@
+use std::io::Write;
+use std::pin::Pin;
+use std::sync::Arc;
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::metrics;
+use crate::storage::objects::open_store;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use futures_core::{Future, Stream};
+use futures_util::StreamExt;
+use anyhow::Result as AnyResult;
+use log::warn;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+#[derive(Debug, Deserialize)]
+pub struct RawArchiveConfig {
+    /// Where archived responses go: `s3://bucket/prefix`, `gs://bucket/prefix` or
+    /// `file:///path`. Cloud credentials come from the usual `AWS_*`/`GOOGLE_*` env vars.
+    pub url: String,
+    /// Writes in flight at a time. Beyond that, fetches wait for a write to finish rather than
+    /// buffering responses without bound while the store is slow.
+    #[serde(default = "default_max_pending_writes")]
+    pub max_pending_writes: usize,
+}
+
+fn default_max_pending_writes() -> usize {
+    64
+}
+
+struct Archive {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    chain_name: String,
+    // One permit per write in flight.
+    writes: Arc<Semaphore>,
+}
+
+impl Archive {
+    fn key(&self, block_number: u64, method: &str) -> ObjectPath {
+        // Zero-padded, so listings come out in block order.
+        ObjectPath::from(format!("{}{}/{:012}/{}.json.gz", self.prefix, self.chain_name, block_number, method))
+    }
+
+    /// Gzips `response` and writes it in the background, waiting first while `max_pending_writes`
+    /// writes are in flight. Failures are logged and counted in `raw_archive_failures_total`.
+    /// The response is encoded before the returned future, so the future doesn't borrow it.
+    fn put<T: Serialize>(
+        self: &Arc<Self>,
+        block_number: u64,
+        method: &'static str,
+        response: &T,
+    ) -> impl Future<Output = ()> + Send + 'static {
+        let payload = serde_json::to_vec(response).map_err(anyhow::Error::from).and_then(|json| {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&json)?;
+            Ok(encoder.finish()?)
+        });
+        let archive = Arc::clone(self);
+        async move {
+            let Ok(permit) = Arc::clone(&archive.writes).acquire_owned().await else {
+                return;
+            };
+            tokio::spawn(async move {
+                let key = archive.key(block_number, method);
+                let result = match payload {
+                    Ok(payload) => archive.store.put(&key, payload.into()).await.map(|_| ()).map_err(anyhow::Error::from),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    warn!("Failed to archive {} of block {} for {}: {}", method, block_number, archive.chain_name, e);
+                    metrics::increment_counter("raw_archive_failures_total", &[("chain", &archive.chain_name)], 1);
+                }
+                drop(permit);
+            });
+        }
+    }
+}
+
+/// Also lands every block-keyed response of the wrapped adapter in object storage, as gzipped
+/// JSON at `{prefix}/{chain}/{block_number}/{method}.json.gz`, so schemas can be re-derived
+/// after decoder changes without fetching from nodes again. Responses are archived as the
+/// adapter returns them, in JSON-RPC JSON; errors aren't archived.
+pub struct RawArchiveAdapter {
+    inner: Arc<dyn BlockchainAdapter>,
+    archive: Arc<Archive>,
+}
+
+impl RawArchiveAdapter {
+    pub fn new(inner: Arc<dyn BlockchainAdapter>, chain_name: &str, config: &RawArchiveConfig) -> AnyResult<Self> {
+        let (store, prefix) = open_store(&config.url)?;
+        Ok(Self {
+            inner,
+            archive: Arc::new(Archive {
+                store,
+                prefix,
+                chain_name: chain_name.to_string(),
+                writes: Arc::new(Semaphore::new(config.max_pending_writes.max(1))),
+            }),
+        })
+    }
+
+    fn archived<T, F>(
+        &self,
+        block_number: u64,
+        method: &'static str,
+        call: F,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<T>> + Send>>
+    where
+        T: Serialize + Send + 'static,
+        F: Future<Output = AnyResult<T>> + Send + 'static,
+    {
+        let archive = Arc::clone(&self.archive);
+        Box::pin(async move {
+            let response = call.await?;
+            archive.put(block_number, method, &response).await;
+            Ok(response)
+        })
+    }
+
+    fn archive_blocks(
+        &self,
+        stream: Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        let archive = Arc::clone(&self.archive);
+        Box::pin(stream.then(move |block| {
+            let archive = Arc::clone(&archive);
+            async move {
+                if let Ok(block) = &block {
+                    if let Some(number) = block.number {
+                        archive.put(number.as_u64(), "eth_getBlockByNumber", block).await;
+                    }
+                }
+                block
+            }
+        }))
+    }
+}
+
+impl BlockchainAdapter for RawArchiveAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        self.archived(block_number, "eth_getBlockByNumber", self.inner.get_block_by_number(block_number))
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        self.archived(block_number, "eth_getBlockByNumber_hashes", self.inner.get_block_with_hashes(block_number))
+    }
+
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        self.archive_blocks(self.inner.subscribe_new_blocks())
+    }
+
+    // Pending transactions aren't keyed by block, so they aren't archived.
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        self.inner.subscribe_pending_transactions()
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        self.inner.get_latest_block_number()
+    }
+
+    fn stream_blocks(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Option<Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>> {
+        self.inner.stream_blocks(start_block, end_block).map(|stream| self.archive_blocks(stream))
+    }
+
+    // Account state is keyed by address rather than block, so it isn't archived.
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        self.inner.get_balance(address, block_number)
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        self.archived(block_number, "eth_getLogs", self.inner.get_logs(block_number))
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.inner.call(to, data, block_number)
+    }
+
+    fn call_request(
+        &self,
+        request: TransactionRequest,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.inner.call_request(request, block_number)
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        self.archived(block_number, "eth_getBlockReceipts", self.inner.get_block_receipts(block_number))
+    }
+}
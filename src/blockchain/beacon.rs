@@ -0,0 +1,77 @@
+use anyhow::{anyhow, Result};
+use reqwest::Client;
+use serde::Deserialize;
+use std::collections::HashMap;
+use tokio::sync::OnceCell;
+
+#[derive(Deserialize)]
+struct BeaconResponse<T> {
+    data: T,
+}
+
+#[derive(Deserialize)]
+struct Genesis {
+    genesis_time: String,
+}
+
+#[derive(Deserialize)]
+struct ValidatorBalance {
+    index: String,
+    balance: String,
+}
+
+/// Client for the standard Beacon node REST API, paired with an execution-layer chain.
+pub struct BeaconClient {
+    client: Client,
+    url: String,
+    seconds_per_slot: u64,
+    genesis_time: OnceCell<u64>,
+}
+
+impl BeaconClient {
+    pub fn new(url: &str, seconds_per_slot: u64) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            seconds_per_slot: seconds_per_slot.max(1),
+            genesis_time: OnceCell::new(),
+        }
+    }
+
+    async fn get<T: for<'de> Deserialize<'de>>(&self, path: &str) -> Result<T> {
+        let response: BeaconResponse<T> = self.client
+            .get(format!("{}{}", self.url, path))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(response.data)
+    }
+
+    /// The slot of the beacon block carrying the execution payload with `timestamp`.
+    pub async fn slot_at(&self, timestamp: u64) -> Result<u64> {
+        let genesis_time = *self.genesis_time
+            .get_or_try_init(|| async {
+                let genesis: Genesis = self.get("/eth/v1/beacon/genesis").await?;
+                genesis.genesis_time.parse::<u64>().map_err(|e| anyhow!("Invalid genesis_time: {}", e))
+            })
+            .await?;
+        timestamp
+            .checked_sub(genesis_time)
+            .map(|elapsed| elapsed / self.seconds_per_slot)
+            .ok_or_else(|| anyhow!("Timestamp {} is before beacon genesis {}", timestamp, genesis_time))
+    }
+
+    /// Balances in gwei of `validators` in the state at `slot`. Validators not yet active are missing.
+    pub async fn validator_balances(&self, slot: u64, validators: &[u64]) -> Result<HashMap<u64, u64>> {
+        let ids = validators.iter().map(|index| index.to_string()).collect::<Vec<_>>().join(",");
+        let balances: Vec<ValidatorBalance> = self
+            .get(&format!("/eth/v1/beacon/states/{}/validator_balances?id={}", slot, ids))
+            .await?;
+        balances
+            .into_iter()
+            .map(|balance| Ok((balance.index.parse()?, balance.balance.parse()?)))
+            .collect()
+    }
+}
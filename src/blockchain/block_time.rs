@@ -0,0 +1,80 @@
+use std::time::{Duration, Instant};
+
+/// Weight of the newest block interval in the running estimate.
+const SMOOTHING: f64 = 0.2;
+
+/// Bounds on how often an adapter in HTTP polling mode asks for the chain head.
+#[derive(Debug, Clone, Copy)]
+pub struct PollBounds {
+    pub min_interval: Duration,
+    pub max_interval: Duration,
+}
+
+impl Default for PollBounds {
+    fn default() -> Self {
+        Self { min_interval: Duration::from_millis(100), max_interval: Duration::from_secs(30) }
+    }
+}
+
+impl PollBounds {
+    /// Reads `min_poll_ms` and `max_poll_ms` from a chain's `adapter_options`.
+    pub fn from_options(options: &toml::Table) -> Self {
+        let default = Self::default();
+        let millis = |key: &str| options.get(key).and_then(|value| value.as_integer()).map(|ms| Duration::from_millis(ms.max(1) as u64));
+        let min_interval = millis("min_poll_ms").unwrap_or(default.min_interval);
+        let max_interval = millis("max_poll_ms").unwrap_or(default.max_interval).max(min_interval);
+        Self { min_interval, max_interval }
+    }
+}
+
+/// Learns a chain's block time from how fast its head advances between polls, and schedules the
+/// next poll for when a block is expected. After a block arrives, the next poll waits about one
+/// block time. Each poll that finds nothing new waits a quarter of it, so a late block is picked up
+/// quickly without hammering the node in between. Works the same for 12 s Ethereum blocks, 2 s L2
+/// blocks and 400 ms Solana slots, without per-chain tuning.
+#[derive(Debug)]
+pub struct BlockTimeEstimator {
+    bounds: PollBounds,
+    /// Smoothed seconds per block, `None` until the head has advanced once.
+    block_time: Option<f64>,
+    head: Option<(u64, Instant)>,
+    misses: u32,
+}
+
+impl BlockTimeEstimator {
+    pub fn new(bounds: PollBounds) -> Self {
+        Self { bounds, block_time: None, head: None, misses: 0 }
+    }
+
+    pub fn block_time(&self) -> Option<Duration> {
+        self.block_time.map(Duration::from_secs_f64)
+    }
+
+    /// Records the head a poll returned.
+    pub fn observe(&mut self, head: u64) {
+        let now = Instant::now();
+        match self.head {
+            Some((previous, at)) if head > previous => {
+                let sample = now.duration_since(at).as_secs_f64() / (head - previous) as f64;
+                self.block_time = Some(match self.block_time {
+                    Some(block_time) => block_time + SMOOTHING * (sample - block_time),
+                    None => sample,
+                });
+                self.head = Some((head, now));
+                self.misses = 0;
+            }
+            Some(_) => self.misses += 1,
+            None => self.head = Some((head, now)),
+        }
+    }
+
+    /// How long to wait before the next poll.
+    pub fn next_interval(&self) -> Duration {
+        let Some(block_time) = self.block_time else {
+            // Nothing learned yet: poll quickly until two heads have been seen.
+            return self.bounds.min_interval.max(Duration::from_millis(500)).min(self.bounds.max_interval);
+        };
+        let interval = if self.misses == 0 { block_time } else { block_time / 4.0 };
+        Duration::from_secs_f64(interval).clamp(self.bounds.min_interval, self.bounds.max_interval)
+    }
+}
@@ -0,0 +1,167 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::rate_limit::RateLimiter;
+use ethers::types::{Block, Log, Transaction, TransactionReceipt, H256, U64};
+use futures_core::{Future, Stream};
+use anyhow::{Result as AnyResult, anyhow};
+use reqwest::Client;
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+#[derive(Debug, Deserialize)]
+pub struct EtherscanConfig {
+    pub api_url: String, // env var holding the API base URL, e.g. https://api.etherscan.io/api
+    pub api_key: String, // env var holding the API key
+    #[serde(default = "default_requests_per_second")]
+    pub requests_per_second: f64,
+}
+
+fn default_requests_per_second() -> f64 {
+    5.0
+}
+
+struct EtherscanClient {
+    client: Client,
+    api_url: String,
+    api_key: String,
+    limiter: RateLimiter,
+}
+
+impl EtherscanClient {
+    /// Calls an Etherscan `module=proxy` action, which wraps the node's JSON-RPC response.
+    async fn proxy<T: DeserializeOwned>(&self, action: &str, params: &[(&str, String)]) -> AnyResult<T> {
+        self.limiter.acquire().await;
+
+        let mut query: Vec<(&str, String)> = vec![
+            ("module", "proxy".to_string()),
+            ("action", action.to_string()),
+            ("apikey", self.api_key.clone()),
+        ];
+        query.extend_from_slice(params);
+
+        let body: Value = self.client
+            .get(&self.api_url)
+            .query(&query)
+            .send()
+            .await
+            .map_err(|e| anyhow!("Etherscan {} request failed: {}", action, e))?
+            .json()
+            .await
+            .map_err(|e| anyhow!("Etherscan {} returned invalid JSON: {}", action, e))?;
+
+        // Rate limit and key errors come back as `{"status": "0", "message": "NOTOK", "result": "..."}`.
+        if body.get("status").and_then(Value::as_str) == Some("0") {
+            return Err(anyhow!("Etherscan {} failed: {}", action, body["result"]));
+        }
+        if let Some(error) = body.get("error") {
+            return Err(anyhow!("Etherscan {} failed: {}", action, error));
+        }
+
+        serde_json::from_value(body["result"].clone())
+            .map_err(|e| anyhow!("Unexpected Etherscan {} result: {}", action, e))
+    }
+}
+
+/// Fetches historical blocks, receipts and logs from an Etherscan-family API. Meant as a backfill
+/// source for ranges the RPC node has already pruned; it cannot follow the chain head. Its proxy
+/// only serves balances and calls at the head, so those aren't supported.
+#[derive(Clone)]
+pub struct EtherscanAdapter {
+    inner: Arc<EtherscanClient>,
+}
+
+impl EtherscanAdapter {
+    pub fn new(api_url: &str, api_key: &str, requests_per_second: f64) -> Self {
+        Self {
+            inner: Arc::new(EtherscanClient {
+                client: Client::new(),
+                api_url: api_url.to_string(),
+                api_key: api_key.to_string(),
+                limiter: RateLimiter::new(requests_per_second),
+            }),
+        }
+    }
+}
+
+impl BlockchainAdapter for EtherscanAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move {
+            inner
+                .proxy("eth_getBlockByNumber", &[
+                    ("tag", format!("{:#x}", block_number)),
+                    ("boolean", "true".to_string()),
+                ])
+                .await
+        })
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move {
+            inner
+                .proxy("eth_getBlockByNumber", &[
+                    ("tag", format!("{:#x}", block_number)),
+                    ("boolean", "false".to_string()),
+                ])
+                .await
+        })
+    }
+
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        Box::pin(futures_util::stream::once(async {
+            Err(anyhow!("EtherscanAdapter does not support block subscriptions"))
+        }))
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move {
+            let block_number: U64 = inner.proxy("eth_blockNumber", &[]).await?;
+            Ok(block_number.as_u64())
+        })
+    }
+
+    // The proxy has no `eth_getBlockReceipts`, so receipts are fetched one transaction at a time.
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        let block = self.get_block_with_hashes(block_number);
+        let inner = Arc::clone(&self.inner);
+        Box::pin(async move {
+            let block = block.await?.ok_or_else(|| anyhow!("Block {} not found on Etherscan", block_number))?;
+            let mut receipts = Vec::with_capacity(block.transactions.len());
+            for hash in block.transactions {
+                let receipt: Option<TransactionReceipt> = inner
+                    .proxy("eth_getTransactionReceipt", &[("txhash", format!("{:?}", hash))])
+                    .await?;
+                receipts.push(receipt.ok_or_else(|| anyhow!("Receipt of {:?} not found on Etherscan", hash))?);
+            }
+            Ok(receipts)
+        })
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        let receipts = self.get_block_receipts(block_number);
+        Box::pin(async move {
+            Ok(receipts.await?.into_iter().flat_map(|receipt| receipt.logs).collect())
+        })
+    }
+}
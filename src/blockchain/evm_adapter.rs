@@ -1,9 +1,12 @@
 use async_stream::try_stream;
 use std::pin::Pin;
-use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::adapters::{BlockchainAdapter, Throttled};
+use crate::blockchain::block_time::{BlockTimeEstimator, PollBounds};
+use crate::metrics;
+use crate::secrets::redact;
 use alloy::{
     providers::{Provider, ProviderBuilder, WsConnect},
-    transports::http::Http
+    transports::{http::Http, RpcError, TransportErrorKind}
 };
 use reqwest::Client;
 use alloy_network_primitives::{BlockResponse, BlockTransactionsKind, ReceiptResponse, BlockTransactions};
@@ -11,12 +14,33 @@ use std::sync::Arc;
 use futures_core::{Future, Stream};
 use anyhow::{Result as AnyResult, anyhow};
 use futures_util::StreamExt;
+use log::{debug, warn};
+use ethers::types::{Address, Block, Bytes, Filter, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+
+/// The error of a failed request, typed as `Throttled` when the provider rate limited it.
+fn rpc_error(context: String, error: RpcError<TransportErrorKind>) -> anyhow::Error {
+    let throttled = match &error {
+        RpcError::ErrorResp(payload) => matches!(payload.code, -32005 | 429),
+        RpcError::Transport(TransportErrorKind::HttpError(http)) => http.status == 429,
+        _ => false,
+    };
+    let message = format!("{}: {}", context, redact(&error.to_string()));
+    if throttled {
+        anyhow::Error::new(Throttled { message })
+    } else {
+        anyhow!(message)
+    }
+}
 
 #[derive(Clone)]
 pub struct EVMAdapter {
     chain_name: String,
     http_provider: Arc<Provider<Http<Client>>>,
-    ws_provider: Arc<Provider<WsConnect>>,
+    /// `None` in HTTP polling mode, when the chain has no `ws_url`.
+    ws_provider: Option<Arc<Provider<WsConnect>>>,
+    poll_bounds: PollBounds,
+    /// Transaction detail of the blocks fetched in HTTP polling mode.
+    poll_detail: BlockTransactionsKind,
 }
 
 impl EVMAdapter {
@@ -28,22 +52,107 @@ impl EVMAdapter {
         let http_client = ProviderBuilder::new()
             .on_http(http_url)
             .await
-            .map_err(|e| anyhow!("HTTP provider error: {}", e))?;
+            .map_err(|e| anyhow!("HTTP provider error for {}: {}", redact(http_url), redact(&e.to_string())))?;
 
-        let ws_client = WsConnect::new(ws_url)
-            .await
-            .map_err(|e| anyhow!("WebSocket connect error: {}", e))?;
-        let ws_provider = ProviderBuilder::new()
-            .on_ws(ws_client)
-            .await
-            .map_err(|e| anyhow!("WebSocket provider error: {}", e))?;
+        let ws_provider = if ws_url.is_empty() {
+            None
+        } else {
+            let ws_client = WsConnect::new(ws_url)
+                .await
+                .map_err(|e| anyhow!("WebSocket connect error for {}: {}", redact(ws_url), redact(&e.to_string())))?;
+            let ws_provider = ProviderBuilder::new()
+                .on_ws(ws_client)
+                .await
+                .map_err(|e| anyhow!("WebSocket provider error for {}: {}", redact(ws_url), redact(&e.to_string())))?;
+            Some(Arc::new(ws_provider))
+        };
 
         Ok(Self {
             chain_name: chain_name.to_string(),
             http_provider: Arc::new(http_client),
-            ws_provider: Arc::new(ws_provider),
+            ws_provider,
+            poll_bounds: PollBounds::default(),
+            poll_detail: BlockTransactionsKind::Full,
         })
     }
+
+    /// Sets how often the head may be polled in HTTP polling mode.
+    pub fn with_poll_bounds(mut self, poll_bounds: PollBounds) -> Self {
+        self.poll_bounds = poll_bounds;
+        self
+    }
+
+    /// Sets the transaction detail fetched for each new block in HTTP polling mode.
+    pub fn with_poll_detail(mut self, poll_detail: BlockTransactionsKind) -> Self {
+        self.poll_detail = poll_detail;
+        self
+    }
+
+    /// Follows the head over HTTP, fetching each new block, with the poll interval adapted to the
+    /// chain's block time. Failed polls and blocks the node doesn't serve yet are retried, with
+    /// the wait doubling up to `max_poll_ms`, so the stream only ends when it is dropped.
+    fn poll_new_blocks(&self) -> Pin<Box<dyn Stream<Item = AnyResult<BlockTransactions>> + Send>> {
+        let adapter = self.clone();
+        let stream = try_stream! {
+            let mut estimator = BlockTimeEstimator::new(adapter.poll_bounds);
+            let mut next_block = None;
+            let mut failures = 0u32;
+            loop {
+                match adapter.poll_once(&mut estimator, &mut next_block).await {
+                    Ok(blocks) => {
+                        failures = 0;
+                        for block in blocks {
+                            yield block;
+                        }
+                    }
+                    Err(e) => {
+                        failures = failures.saturating_add(1);
+                        warn!("Polling the head of {} failed ({} in a row), retrying: {}", adapter.chain_name, failures, e);
+                        metrics::increment_counter("poll_failures_total", &[("chain", &adapter.chain_name)], 1);
+                    }
+                }
+
+                let interval = if failures == 0 {
+                    estimator.next_interval()
+                } else {
+                    (estimator.next_interval() * 2u32.saturating_pow(failures.min(16))).min(adapter.poll_bounds.max_interval)
+                };
+                if let Some(block_time) = estimator.block_time() {
+                    metrics::set_gauge("chain_block_time_seconds", &[("chain", &adapter.chain_name)], block_time.as_secs_f64());
+                }
+                metrics::set_gauge("poll_interval_seconds", &[("chain", &adapter.chain_name)], interval.as_secs_f64());
+                tokio::time::sleep(interval).await;
+            }
+        };
+        Box::pin(stream)
+    }
+
+    /// Polls the head once and fetches the blocks up to it from `next_block` on. A block the
+    /// node doesn't return yet ends the poll early, and is fetched again by the next one.
+    async fn poll_once(&self, estimator: &mut BlockTimeEstimator, next_block: &mut Option<u64>) -> AnyResult<Vec<BlockTransactions>> {
+        let head = self.get_latest_block_number().await?;
+        estimator.observe(head);
+        let from = next_block.unwrap_or(head);
+        let mut blocks = Vec::new();
+        for block_number in from..=head {
+            let block = match self.get_block_by_number(block_number, self.poll_detail).await {
+                Ok(Some(block)) => block,
+                Ok(None) => {
+                    debug!("Block {} of {} not served yet, retrying on the next poll", block_number, self.chain_name);
+                    break;
+                }
+                // Blocks fetched so far are still yielded; the failed one is retried.
+                Err(e) if !blocks.is_empty() => {
+                    warn!("Fetching block {} of {} failed, retrying on the next poll: {}", block_number, self.chain_name, e);
+                    break;
+                }
+                Err(e) => return Err(e),
+            };
+            blocks.push(block);
+            *next_block = Some(block_number + 1);
+        }
+        Ok(blocks)
+    }
 }
 
 impl BlockchainAdapter for EVMAdapter {
@@ -59,22 +168,38 @@ impl BlockchainAdapter for EVMAdapter {
                 BlockTransactionsKind::Full => provider.get_block_with_txs(block_number).await,
                 BlockTransactionsKind::Hashes => provider.get_block_with_hashes(block_number).await,
             }
-            .map_err(|e| anyhow!("Error fetching block {}: {}", block_number, e))?;
+            .map_err(|e| rpc_error(format!("Error fetching block {}", block_number), e))?;
 
             Ok(block_opt)
         })
     }
     
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        let block = self.get_block_by_number(block_number, BlockTransactionsKind::Hashes);
+        Box::pin(async move {
+            // Both are the JSON-RPC block, so it converts through serde.
+            block
+                .await?
+                .map(|block| Ok(serde_json::from_value(serde_json::to_value(block)?)?))
+                .transpose()
+        })
+    }
+
     fn subscribe_new_blocks(
         &self,
     ) -> Pin<Box<dyn Stream<Item = AnyResult<BlockTransactions>> + Send>> {
-        let provider = Arc::clone(&self.ws_provider);
+        let Some(provider) = self.ws_provider.clone() else {
+            return self.poll_new_blocks();
+        };
     
         let stream = try_stream! {
             let mut sub = provider
                 .subscribe_blocks()
                 .await
-                .map_err(|e| anyhow!("subscribe_blocks() failed: {}", e))?;
+                .map_err(|e| anyhow!("subscribe_blocks() failed: {}", redact(&e.to_string())))?;
     
             while let Some(header) = sub.next().await {
                 yield header;
@@ -83,6 +208,29 @@ impl BlockchainAdapter for EVMAdapter {
         Box::pin(stream)
     }
 
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        let Some(provider) = self.ws_provider.clone() else {
+            let chain_name = self.chain_name.clone();
+            return Box::pin(futures_util::stream::once(async move {
+                Err(anyhow!("Pending transactions of {} need a `ws_url`", chain_name))
+            }));
+        };
+
+        let stream = try_stream! {
+            let mut sub = provider
+                .subscribe_full_pending_transactions()
+                .await
+                .map_err(|e| anyhow!("subscribe_full_pending_transactions() failed: {}", redact(&e.to_string())))?;
+
+            while let Some(transaction) = sub.next().await {
+                yield transaction;
+            }
+        };
+        Box::pin(stream)
+    }
+
     fn get_latest_block_number(
         &self,
     ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
@@ -91,9 +239,91 @@ impl BlockchainAdapter for EVMAdapter {
             let block_num = provider
                 .get_block_number()
                 .await
-                .map_err(|e| anyhow!("Error fetching latest block number: {}", e))?;
+                .map_err(|e| rpc_error("Error fetching latest block number".to_string(), e))?;
 
             Ok(block_num.as_u64())
         })
     }
+
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        let provider = Arc::clone(&self.http_provider);
+        Box::pin(async move {
+            let balance = provider
+                .get_balance(address, Some(block_number.into()))
+                .await
+                .map_err(|e| rpc_error(format!("Error fetching balance of {:?} at block {}", address, block_number), e))?;
+
+            Ok(balance)
+        })
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        let provider = Arc::clone(&self.http_provider);
+        Box::pin(async move {
+            let filter = Filter::new().select(block_number);
+            let logs = provider
+                .get_logs(&filter)
+                .await
+                .map_err(|e| rpc_error(format!("Error fetching logs for block {}", block_number), e))?;
+
+            Ok(logs)
+        })
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        let provider = Arc::clone(&self.http_provider);
+        Box::pin(async move {
+            let request = TransactionRequest::new().to(to).data(data);
+            let output = provider
+                .call(&request.into(), Some(block_number.into()))
+                .await
+                .map_err(|e| rpc_error(format!("Error calling {:?} at block {}", to, block_number), e))?;
+
+            Ok(output)
+        })
+    }
+
+    fn call_request(
+        &self,
+        request: TransactionRequest,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        let provider = Arc::clone(&self.http_provider);
+        Box::pin(async move {
+            let to = request.to.clone();
+            let output = provider
+                .call(&request.into(), Some(block_number.into()))
+                .await
+                .map_err(|e| rpc_error(format!("Error calling {:?} at block {}", to, block_number), e))?;
+
+            Ok(output)
+        })
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        let provider = Arc::clone(&self.http_provider);
+        Box::pin(async move {
+            let receipts = provider
+                .get_block_receipts(block_number)
+                .await
+                .map_err(|e| rpc_error(format!("Error fetching receipts for block {}", block_number), e))?;
+
+            Ok(receipts)
+        })
+    }
 }
@@ -0,0 +1,171 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::etherscan_adapter::EtherscanConfig;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+use futures_core::{Future, Stream};
+use anyhow::Result as AnyResult;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// How long a fetched chain head is reused before asking the primary again.
+const HEAD_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+pub struct HistoryFallbackConfig {
+    /// Blocks older than `head - pruning_horizon_blocks` are fetched from the fallback.
+    pub pruning_horizon_blocks: u64,
+    #[serde(flatten)]
+    pub etherscan: EtherscanConfig,
+}
+
+/// Routes every block-keyed request older than the primary node's pruning horizon (blocks,
+/// receipts, logs, balances and calls) to a fallback source (e.g. Etherscan) and everything else
+/// to the primary.
+pub struct HistoryFallbackAdapter {
+    primary: Arc<dyn BlockchainAdapter>,
+    fallback: Arc<dyn BlockchainAdapter>,
+    pruning_horizon_blocks: u64,
+    head: Arc<Mutex<Option<(u64, Instant)>>>,
+}
+
+impl HistoryFallbackAdapter {
+    pub fn new(
+        primary: Arc<dyn BlockchainAdapter>,
+        fallback: Arc<dyn BlockchainAdapter>,
+        pruning_horizon_blocks: u64,
+    ) -> Self {
+        Self {
+            primary,
+            fallback,
+            pruning_horizon_blocks,
+            head: Arc::new(Mutex::new(None)),
+        }
+    }
+}
+
+impl HistoryFallbackAdapter {
+    /// The source that still holds `block_number`.
+    fn source_for(&self, block_number: u64) -> impl Future<Output = AnyResult<Arc<dyn BlockchainAdapter>>> + Send + 'static {
+        let primary = Arc::clone(&self.primary);
+        let fallback = Arc::clone(&self.fallback);
+        let head = Arc::clone(&self.head);
+        let pruning_horizon_blocks = self.pruning_horizon_blocks;
+        async move {
+            let latest = {
+                let mut cached = head.lock().await;
+                match *cached {
+                    Some((latest, fetched_at)) if fetched_at.elapsed() < HEAD_TTL => latest,
+                    _ => {
+                        let latest = primary.get_latest_block_number().await?;
+                        *cached = Some((latest, Instant::now()));
+                        latest
+                    }
+                }
+            };
+
+            if block_number.saturating_add(pruning_horizon_blocks) < latest {
+                Ok(fallback)
+            } else {
+                Ok(primary)
+            }
+        }
+    }
+}
+
+impl BlockchainAdapter for HistoryFallbackAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        let source = self.source_for(block_number);
+        Box::pin(async move {
+            source.await?.get_block_by_number(block_number).await
+        })
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        let source = self.source_for(block_number);
+        Box::pin(async move {
+            source.await?.get_block_with_hashes(block_number).await
+        })
+    }
+
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        self.primary.subscribe_new_blocks()
+    }
+
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        self.primary.subscribe_pending_transactions()
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        self.primary.get_latest_block_number()
+    }
+
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        let source = self.source_for(block_number);
+        Box::pin(async move {
+            source.await?.get_balance(address, block_number).await
+        })
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        let source = self.source_for(block_number);
+        Box::pin(async move {
+            source.await?.get_logs(block_number).await
+        })
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        let source = self.source_for(block_number);
+        Box::pin(async move {
+            source.await?.call(to, data, block_number).await
+        })
+    }
+
+    fn call_request(
+        &self,
+        request: TransactionRequest,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        let source = self.source_for(block_number);
+        Box::pin(async move {
+            source.await?.call_request(request, block_number).await
+        })
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        let source = self.source_for(block_number);
+        Box::pin(async move {
+            source.await?.get_block_receipts(block_number).await
+        })
+    }
+}
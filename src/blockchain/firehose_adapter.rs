@@ -0,0 +1,232 @@
+use async_stream::try_stream;
+use std::pin::Pin;
+use std::sync::Arc;
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::firehose_pb as pb;
+use ethers::types::{Address, Block, Bytes, Transaction, H256, U256, U64};
+use futures_core::{Future, Stream};
+use futures_util::StreamExt;
+use anyhow::{Result as AnyResult, anyhow};
+use log::warn;
+use prost::Message;
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::metadata::MetadataValue;
+use tonic::transport::{Channel, ClientTlsConfig};
+
+const BLOCKS_PATH: &str = "/sf.firehose.v2.Stream/Blocks";
+
+/// Reads blocks from a StreamingFast Firehose endpoint over gRPC instead of JSON-RPC.
+/// Much faster than RPC for deep history since a whole range arrives on one stream.
+#[derive(Clone)]
+pub struct FirehoseAdapter {
+    chain_name: String,
+    channel: Channel,
+    api_token: Option<Arc<String>>,
+}
+
+impl FirehoseAdapter {
+    pub async fn new(
+        chain_name: &str,
+        endpoint: &str,
+        api_token: Option<&str>,
+    ) -> AnyResult<Self> {
+        let mut builder = Channel::from_shared(endpoint.to_string())
+            .map_err(|e| anyhow!("Invalid Firehose endpoint: {}", e))?;
+        if endpoint.starts_with("https://") {
+            builder = builder
+                .tls_config(ClientTlsConfig::new().with_native_roots())
+                .map_err(|e| anyhow!("Firehose TLS config error: {}", e))?;
+        }
+        let channel = builder
+            .connect()
+            .await
+            .map_err(|e| anyhow!("Firehose connect error: {}", e))?;
+
+        Ok(Self {
+            chain_name: chain_name.to_string(),
+            channel,
+            api_token: api_token.map(|token| Arc::new(token.to_string())),
+        })
+    }
+
+    fn blocks(
+        &self,
+        request: pb::Request,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<(pb::ForkStep, Block<Transaction>)>> + Send>> {
+        let channel = self.channel.clone();
+        let api_token = self.api_token.clone();
+        Box::pin(try_stream! {
+            let mut responses = open_blocks_stream(channel, api_token, request).await?;
+            while let Some(response) = responses.next().await {
+                let response = response.map_err(|e| anyhow!("Firehose stream error: {}", e))?;
+                let step = pb::ForkStep::try_from(response.step).unwrap_or(pb::ForkStep::StepUnset);
+                let any = response.block.ok_or_else(|| anyhow!("Firehose response without a block"))?;
+                let block = pb::Block::decode(any.value.as_slice())
+                    .map_err(|e| anyhow!("Failed to decode Firehose block: {}", e))?;
+                yield (step, to_ethers_block(block));
+            }
+        })
+    }
+}
+
+async fn open_blocks_stream(
+    channel: Channel,
+    api_token: Option<Arc<String>>,
+    request: pb::Request,
+) -> AnyResult<tonic::Streaming<pb::Response>> {
+    let mut grpc = tonic::client::Grpc::new(channel);
+    grpc.ready()
+        .await
+        .map_err(|e| anyhow!("Firehose service not ready: {}", e))?;
+
+    let mut request = tonic::Request::new(request);
+    if let Some(token) = api_token {
+        let value: MetadataValue<_> = format!("Bearer {}", token)
+            .parse()
+            .map_err(|e| anyhow!("Invalid Firehose API token: {}", e))?;
+        request.metadata_mut().insert("authorization", value);
+    }
+
+    let response = grpc
+        .server_streaming(request, PathAndQuery::from_static(BLOCKS_PATH), tonic::codec::ProstCodec::default())
+        .await
+        .map_err(|e| anyhow!("Firehose Blocks request failed: {}", e))?;
+    Ok(response.into_inner())
+}
+
+fn h256(bytes: &[u8]) -> H256 {
+    if bytes.len() == 32 { H256::from_slice(bytes) } else { H256::zero() }
+}
+
+fn address(bytes: &[u8]) -> Address {
+    if bytes.len() == 20 { Address::from_slice(bytes) } else { Address::zero() }
+}
+
+fn big_int(value: &Option<pb::BigInt>) -> U256 {
+    value
+        .as_ref()
+        .map(|value| U256::from_big_endian(&value.bytes))
+        .unwrap_or_default()
+}
+
+/// Maps a Firehose Ethereum block into the same shape the JSON-RPC adapter produces.
+fn to_ethers_block(block: pb::Block) -> Block<Transaction> {
+    let header = block.header.unwrap_or_default();
+    let hash = h256(&block.hash);
+    let number = U64::from(block.number);
+
+    let transactions = block
+        .transaction_traces
+        .into_iter()
+        .map(|trace| Transaction {
+            hash: h256(&trace.hash),
+            nonce: trace.nonce.into(),
+            block_hash: Some(hash),
+            block_number: Some(number),
+            transaction_index: Some(trace.index.into()),
+            from: address(&trace.from),
+            to: if trace.to.is_empty() { None } else { Some(address(&trace.to)) },
+            value: big_int(&trace.value),
+            gas_price: Some(big_int(&trace.gas_price)),
+            gas: trace.gas_limit.into(),
+            input: Bytes::from(trace.input),
+            ..Default::default()
+        })
+        .collect();
+
+    Block {
+        hash: Some(hash),
+        parent_hash: h256(&header.parent_hash),
+        uncles_hash: h256(&header.uncle_hash),
+        author: Some(address(&header.coinbase)),
+        state_root: h256(&header.state_root),
+        transactions_root: h256(&header.transactions_root),
+        receipts_root: h256(&header.receipt_root),
+        number: Some(number),
+        gas_used: header.gas_used.into(),
+        gas_limit: header.gas_limit.into(),
+        extra_data: Bytes::from(header.extra_data),
+        timestamp: header.timestamp.map(|ts| ts.seconds.max(0) as u64).unwrap_or_default().into(),
+        difficulty: big_int(&header.difficulty),
+        total_difficulty: Some(big_int(&header.total_difficulty)),
+        mix_hash: Some(h256(&header.mix_hash)),
+        base_fee_per_gas: header.base_fee_per_gas.as_ref().map(|fee| U256::from_big_endian(&fee.bytes)),
+        size: Some(block.size.into()),
+        transactions,
+        ..Default::default()
+    }
+}
+
+impl BlockchainAdapter for FirehoseAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        let mut stream = self.blocks(pb::Request {
+            start_block_num: block_number as i64,
+            stop_block_num: block_number,
+            final_blocks_only: true,
+            ..Default::default()
+        });
+        Box::pin(async move {
+            match stream.next().await {
+                Some(result) => Ok(Some(result?.1)),
+                None => Ok(None),
+            }
+        })
+    }
+
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        let chain_name = self.chain_name.clone();
+        // Start at the current head and follow the chain.
+        let stream = self.blocks(pb::Request {
+            start_block_num: -1,
+            ..Default::default()
+        });
+        Box::pin(stream.filter_map(move |result| {
+            let item = match result {
+                Ok((pb::ForkStep::StepUndo, block)) => {
+                    // The replacement block arrives as a later STEP_NEW and supersedes this one in the sink.
+                    warn!("Firehose undo on {} for block {:?}", chain_name, block.number);
+                    None
+                }
+                Ok((_, block)) => Some(Ok(block)),
+                Err(e) => Some(Err(e)),
+            };
+            futures_util::future::ready(item)
+        }))
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        let mut stream = self.blocks(pb::Request {
+            start_block_num: -1,
+            ..Default::default()
+        });
+        Box::pin(async move {
+            let (_, block) = stream
+                .next()
+                .await
+                .ok_or_else(|| anyhow!("Firehose stream ended before the head block"))??;
+            Ok(block.number.unwrap_or_default().as_u64())
+        })
+    }
+
+    fn stream_blocks(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Option<Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>> {
+        let stream = self.blocks(pb::Request {
+            start_block_num: start_block as i64,
+            stop_block_num: end_block,
+            final_blocks_only: true,
+            ..Default::default()
+        });
+        Some(Box::pin(stream.map(|result| result.map(|(_, block)| block))))
+    }
+}
@@ -0,0 +1,112 @@
+//! Subset of the StreamingFast `sf.firehose.v2` and `sf.ethereum.type.v2` protobuf definitions.
+//! Only the fields mapped into our schemas are declared; prost skips unknown fields when decoding.
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Request {
+    /// Negative values are relative to the chain head.
+    #[prost(int64, tag = "1")]
+    pub start_block_num: i64,
+    #[prost(string, tag = "2")]
+    pub cursor: String,
+    /// Inclusive. Zero streams forever.
+    #[prost(uint64, tag = "3")]
+    pub stop_block_num: u64,
+    #[prost(bool, tag = "4")]
+    pub final_blocks_only: bool,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Response {
+    #[prost(message, optional, tag = "1")]
+    pub block: Option<prost_types::Any>,
+    #[prost(enumeration = "ForkStep", tag = "6")]
+    pub step: i32,
+    #[prost(string, tag = "10")]
+    pub cursor: String,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, prost::Enumeration)]
+#[repr(i32)]
+pub enum ForkStep {
+    StepUnset = 0,
+    StepNew = 1,
+    StepUndo = 2,
+    StepFinal = 3,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct Block {
+    #[prost(bytes = "vec", tag = "2")]
+    pub hash: Vec<u8>,
+    #[prost(uint64, tag = "3")]
+    pub number: u64,
+    #[prost(uint64, tag = "4")]
+    pub size: u64,
+    #[prost(message, optional, tag = "5")]
+    pub header: Option<BlockHeader>,
+    #[prost(message, repeated, tag = "10")]
+    pub transaction_traces: Vec<TransactionTrace>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BlockHeader {
+    #[prost(bytes = "vec", tag = "1")]
+    pub parent_hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "2")]
+    pub uncle_hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "3")]
+    pub coinbase: Vec<u8>,
+    #[prost(bytes = "vec", tag = "4")]
+    pub state_root: Vec<u8>,
+    #[prost(bytes = "vec", tag = "5")]
+    pub transactions_root: Vec<u8>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub receipt_root: Vec<u8>,
+    #[prost(message, optional, tag = "8")]
+    pub difficulty: Option<BigInt>,
+    #[prost(uint64, tag = "10")]
+    pub gas_limit: u64,
+    #[prost(uint64, tag = "11")]
+    pub gas_used: u64,
+    #[prost(message, optional, tag = "12")]
+    pub timestamp: Option<prost_types::Timestamp>,
+    #[prost(bytes = "vec", tag = "13")]
+    pub extra_data: Vec<u8>,
+    #[prost(bytes = "vec", tag = "14")]
+    pub mix_hash: Vec<u8>,
+    #[prost(message, optional, tag = "17")]
+    pub total_difficulty: Option<BigInt>,
+    #[prost(message, optional, tag = "18")]
+    pub base_fee_per_gas: Option<BigInt>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct BigInt {
+    /// Big-endian unsigned integer.
+    #[prost(bytes = "vec", tag = "1")]
+    pub bytes: Vec<u8>,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct TransactionTrace {
+    #[prost(bytes = "vec", tag = "1")]
+    pub to: Vec<u8>,
+    #[prost(uint64, tag = "2")]
+    pub nonce: u64,
+    #[prost(message, optional, tag = "3")]
+    pub gas_price: Option<BigInt>,
+    #[prost(uint64, tag = "4")]
+    pub gas_limit: u64,
+    #[prost(message, optional, tag = "5")]
+    pub value: Option<BigInt>,
+    #[prost(bytes = "vec", tag = "6")]
+    pub input: Vec<u8>,
+    #[prost(uint64, tag = "10")]
+    pub gas_used: u64,
+    #[prost(uint32, tag = "20")]
+    pub index: u32,
+    #[prost(bytes = "vec", tag = "21")]
+    pub hash: Vec<u8>,
+    #[prost(bytes = "vec", tag = "22")]
+    pub from: Vec<u8>,
+}
@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::rate_limit::RateLimiter;
+use ethers::types::{Block, Log, Transaction, TransactionReceipt, H256};
+use futures_core::{Future, Stream};
+use anyhow::{Context, Result as AnyResult, anyhow};
+use crate::streams::schemas::evm::with_transaction_hashes;
+
+/// Replays blocks from JSON fixtures instead of talking to a node, so the pipeline can be tested
+/// deterministically.
+///
+/// Fixtures are replayed in order: a block at a height that was already replayed stands for a
+/// reorg. `get_block_by_number` answers with the last fixture at a height, i.e. the chain as it
+/// looks once replay has finished. Receipts given with `with_receipts` also answer `get_logs`.
+#[derive(Clone)]
+pub struct MockAdapter {
+    blocks: Arc<Vec<Block<Transaction>>>,
+    receipts: Arc<HashMap<u64, Vec<TransactionReceipt>>>,
+    limiter: Option<Arc<RateLimiter>>,
+}
+
+impl MockAdapter {
+    /// Replays `blocks` in order, at most `blocks_per_second` (unthrottled if `None`).
+    pub fn new(blocks: Vec<Block<Transaction>>, blocks_per_second: Option<f64>) -> Self {
+        Self {
+            blocks: Arc::new(blocks),
+            receipts: Arc::new(HashMap::new()),
+            limiter: blocks_per_second.map(|rate| Arc::new(RateLimiter::new(rate))),
+        }
+    }
+
+    /// Serves `receipts` for block `block_number`. Blocks without any have no receipts or logs.
+    pub fn with_receipts(mut self, block_number: u64, receipts: Vec<TransactionReceipt>) -> Self {
+        Arc::make_mut(&mut self.receipts).insert(block_number, receipts);
+        self
+    }
+
+    /// Loads fixtures from a JSON file holding a block or an array of blocks, or from a directory
+    /// of such files read in file name order.
+    pub fn from_fixtures(path: impl AsRef<Path>, blocks_per_second: Option<f64>) -> AnyResult<Self> {
+        let path = path.as_ref();
+        let mut files = Vec::new();
+        if path.is_dir() {
+            for entry in std::fs::read_dir(path).with_context(|| format!("Failed to read {}", path.display()))? {
+                let file = entry?.path();
+                if file.extension().map_or(false, |ext| ext == "json") {
+                    files.push(file);
+                }
+            }
+            files.sort();
+        } else {
+            files.push(path.to_path_buf());
+        }
+
+        let mut blocks = Vec::new();
+        for file in files {
+            let data = std::fs::read(&file).with_context(|| format!("Failed to read fixture {}", file.display()))?;
+            let value: serde_json::Value = serde_json::from_slice(&data)
+                .with_context(|| format!("Invalid JSON in fixture {}", file.display()))?;
+            if value.is_array() {
+                let fixtures: Vec<Block<Transaction>> = serde_json::from_value(value)
+                    .with_context(|| format!("Invalid blocks in fixture {}", file.display()))?;
+                blocks.extend(fixtures);
+            } else {
+                blocks.push(serde_json::from_value(value)
+                    .with_context(|| format!("Invalid block in fixture {}", file.display()))?);
+            }
+        }
+
+        Ok(Self::new(blocks, blocks_per_second))
+    }
+}
+
+impl BlockchainAdapter for MockAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        let blocks = Arc::clone(&self.blocks);
+        let limiter = self.limiter.clone();
+        Box::pin(async move {
+            if let Some(limiter) = limiter {
+                limiter.acquire().await;
+            }
+            Ok(blocks
+                .iter()
+                .rev()
+                .find(|block| block.number.map(|n| n.as_u64()) == Some(block_number))
+                .cloned())
+        })
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        let block = self.get_block_by_number(block_number);
+        Box::pin(async move {
+            block.await?.as_ref().map(with_transaction_hashes).transpose()
+        })
+    }
+
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        let blocks = Arc::clone(&self.blocks);
+        let limiter = self.limiter.clone();
+        Box::pin(async_stream::stream! {
+            for block in blocks.iter() {
+                if let Some(limiter) = &limiter {
+                    limiter.acquire().await;
+                }
+                yield Ok(block.clone());
+            }
+        })
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        let blocks = Arc::clone(&self.blocks);
+        Box::pin(async move {
+            blocks
+                .iter()
+                .filter_map(|block| block.number)
+                .max()
+                .map(|n| n.as_u64())
+                .ok_or_else(|| anyhow!("MockAdapter has no fixtures"))
+        })
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        let receipts = self.receipts.get(&block_number).cloned().unwrap_or_default();
+        Box::pin(async move { Ok(receipts.into_iter().flat_map(|receipt| receipt.logs).collect()) })
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        let receipts = self.receipts.get(&block_number).cloned().unwrap_or_default();
+        Box::pin(async move { Ok(receipts) })
+    }
+}
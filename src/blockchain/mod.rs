@@ -1,2 +1,19 @@
 pub mod adapters;
+pub mod adaptive_throttle;
+pub mod archive_adapter;
+pub mod beacon;
+pub mod block_time;
+pub mod etherscan_adapter;
 pub mod evm_adapter;
+pub mod fallback_adapter;
+pub mod firehose_adapter;
+pub mod firehose_pb;
+pub mod mock_adapter;
+pub mod rate_limit;
+pub mod recording_adapter;
+#[cfg(feature = "reth")]
+pub mod reth_db_adapter;
+pub mod registry;
+pub mod rpc_quota;
+pub mod rpc_usage;
+pub mod time_search;
@@ -0,0 +1,276 @@
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use crate::blockchain::adapters::BlockchainAdapter;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+use ethers::utils::{hex, keccak256};
+use futures_core::{Future, Stream};
+use futures_util::StreamExt;
+use anyhow::{Context, Result as AnyResult, anyhow};
+use serde::{Deserialize, Serialize};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordingMode {
+    /// Pass calls through to the node and write every response to `dir`.
+    Record,
+    /// Answer calls from `dir` only; no node is contacted.
+    Replay,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcRecordingConfig {
+    pub mode: RecordingMode,
+    pub dir: PathBuf,
+}
+
+// One recorded call. Provider errors are recorded too, so they replay as errors.
+#[derive(Serialize, Deserialize)]
+struct Recording {
+    method: String,
+    params: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+struct RecordingStore {
+    dir: PathBuf,
+    // Position in the recorded `eth_subscribe` sequence.
+    next_subscription_event: AtomicU64,
+}
+
+impl RecordingStore {
+    fn call_path(&self, method: &str, params: &Value) -> PathBuf {
+        let key = hex::encode(&keccak256(params.to_string())[..16]);
+        self.dir.join(method).join(format!("{}.json", key))
+    }
+
+    fn subscription_path(&self, sequence: u64) -> PathBuf {
+        self.dir.join("eth_subscribe").join(format!("{:08}.json", sequence))
+    }
+
+    async fn write(&self, path: &Path, recording: &Recording) -> AnyResult<()> {
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(path, serde_json::to_vec_pretty(recording)?)
+            .await
+            .with_context(|| format!("Failed to write recording {}", path.display()))
+    }
+
+    async fn read(&self, path: &Path) -> AnyResult<Option<Recording>> {
+        match tokio::fs::read(path).await {
+            Ok(data) => Ok(Some(serde_json::from_slice(&data)
+                .with_context(|| format!("Invalid recording {}", path.display()))?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn into_result<T: DeserializeOwned>(recording: Recording) -> AnyResult<T> {
+    match recording.error {
+        Some(error) => Err(anyhow!("{} (replayed)", error)),
+        None => Ok(serde_json::from_value(recording.result.unwrap_or(Value::Null))?),
+    }
+}
+
+/// Records every response of the wrapped adapter to disk, keyed by the JSON-RPC method and params
+/// it stands for, or replays such a recording without a node. Used to reproduce production
+/// incidents (a bad block, an odd provider response) locally.
+#[derive(Clone)]
+pub struct RecordReplayAdapter {
+    inner: Option<Arc<dyn BlockchainAdapter>>,
+    store: Arc<RecordingStore>,
+}
+
+impl RecordReplayAdapter {
+    /// Passes calls through to `inner` and records the responses in `dir`.
+    pub fn record(inner: Arc<dyn BlockchainAdapter>, dir: impl Into<PathBuf>) -> Self {
+        Self::with_inner(Some(inner), dir.into())
+    }
+
+    /// Serves calls from the recording in `dir`.
+    pub fn replay(dir: impl Into<PathBuf>) -> AnyResult<Self> {
+        let dir = dir.into();
+        if !dir.is_dir() {
+            return Err(anyhow!("Recording directory {} does not exist", dir.display()));
+        }
+        Ok(Self::with_inner(None, dir))
+    }
+
+    fn with_inner(inner: Option<Arc<dyn BlockchainAdapter>>, dir: PathBuf) -> Self {
+        Self {
+            inner,
+            store: Arc::new(RecordingStore {
+                dir,
+                next_subscription_event: AtomicU64::new(0),
+            }),
+        }
+    }
+
+    /// Records or replays one call. `call` is only invoked when recording.
+    fn dispatch<T, F, Fut>(
+        &self,
+        method: &'static str,
+        params: Value,
+        call: F,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<T>> + Send>>
+    where
+        T: Serialize + DeserializeOwned + Send + 'static,
+        F: FnOnce(Arc<dyn BlockchainAdapter>) -> Fut + Send + 'static,
+        Fut: Future<Output = AnyResult<T>> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        let store = Arc::clone(&self.store);
+        Box::pin(async move {
+            let path = store.call_path(method, &params);
+            let Some(inner) = inner else {
+                let recording = store
+                    .read(&path)
+                    .await?
+                    .ok_or_else(|| anyhow!("No recorded response for {} {}", method, params))?;
+                return into_result(recording);
+            };
+
+            let response = call(inner).await;
+            let recording = match &response {
+                Ok(result) => Recording { method: method.to_string(), params, result: Some(serde_json::to_value(result)?), error: None },
+                Err(e) => Recording { method: method.to_string(), params, result: None, error: Some(e.to_string()) },
+            };
+            store.write(&path, &recording).await?;
+            response
+        })
+    }
+}
+
+impl BlockchainAdapter for RecordReplayAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        self.dispatch("eth_getBlockByNumber", json!([format!("{:#x}", block_number), true]), move |inner| {
+            inner.get_block_by_number(block_number)
+        })
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        self.dispatch("eth_getBlockByNumber", json!([format!("{:#x}", block_number), false]), move |inner| {
+            inner.get_block_with_hashes(block_number)
+        })
+    }
+
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        let inner = self.inner.clone();
+        let store = Arc::clone(&self.store);
+        Box::pin(async_stream::stream! {
+            match inner {
+                Some(inner) => {
+                    let mut stream = inner.subscribe_new_blocks();
+                    while let Some(block) = stream.next().await {
+                        let sequence = store.next_subscription_event.fetch_add(1, Ordering::SeqCst);
+                        let recording = match &block {
+                            Ok(block) => Recording { method: "eth_subscribe".to_string(), params: json!(["newHeads"]), result: serde_json::to_value(block).ok(), error: None },
+                            Err(e) => Recording { method: "eth_subscribe".to_string(), params: json!(["newHeads"]), result: None, error: Some(e.to_string()) },
+                        };
+                        if let Err(e) = store.write(&store.subscription_path(sequence), &recording).await {
+                            yield Err(e);
+                        }
+                        yield block;
+                    }
+                }
+                None => {
+                    // Replays the recorded subscription in order, then ends.
+                    loop {
+                        let sequence = store.next_subscription_event.fetch_add(1, Ordering::SeqCst);
+                        match store.read(&store.subscription_path(sequence)).await {
+                            Ok(Some(recording)) => yield into_result(recording),
+                            Ok(None) => break,
+                            Err(e) => {
+                                yield Err(e);
+                                break;
+                            }
+                        }
+                    }
+                }
+            }
+        })
+    }
+
+    // The mempool is too noisy to record, so it is passed through live and unavailable in replay.
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        match &self.inner {
+            Some(inner) => inner.subscribe_pending_transactions(),
+            None => Box::pin(futures_util::stream::once(async {
+                Err(anyhow!("Pending transactions are not recorded, so they cannot be replayed"))
+            })),
+        }
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        self.dispatch("eth_blockNumber", json!([]), |inner| inner.get_latest_block_number())
+    }
+
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        self.dispatch("eth_getBalance", json!([address, format!("{:#x}", block_number)]), move |inner| {
+            inner.get_balance(address, block_number)
+        })
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        let block = format!("{:#x}", block_number);
+        self.dispatch("eth_getLogs", json!([{ "fromBlock": block, "toBlock": block }]), move |inner| {
+            inner.get_logs(block_number)
+        })
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        let params = json!([{ "to": to, "data": data }, format!("{:#x}", block_number)]);
+        self.dispatch("eth_call", params, move |inner| inner.call(to, data, block_number))
+    }
+
+    fn call_request(
+        &self,
+        request: TransactionRequest,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        let params = json!([request, format!("{:#x}", block_number)]);
+        self.dispatch("eth_call", params, move |inner| inner.call_request(request, block_number))
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        self.dispatch("eth_getBlockReceipts", json!([format!("{:#x}", block_number)]), move |inner| {
+            inner.get_block_receipts(block_number)
+        })
+    }
+}
@@ -0,0 +1,98 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use alloy_network_primitives::BlockTransactionsKind;
+use anyhow::{Context, Result};
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::block_time::PollBounds;
+use crate::blockchain::evm_adapter::EVMAdapter;
+use crate::blockchain::firehose_adapter::FirehoseAdapter;
+#[cfg(feature = "reth")]
+use crate::blockchain::reth_db_adapter::RethDbAdapter;
+
+/// What an adapter constructor gets to build a chain's source, with URLs and tokens already
+/// resolved from the environment.
+#[derive(Debug, Clone)]
+pub struct AdapterContext {
+    pub chain_name: String,
+    pub http_url: String,
+    pub ws_url: String,
+    pub api_token: Option<String>,
+    /// The chain's `adapter_options` table, for settings specific to one adapter type.
+    pub options: toml::Table,
+    /// The least transaction detail that serves every schema of the chain, for adapters that
+    /// fetch blocks on their own, e.g. while polling for new heads.
+    pub tx_detail: BlockTransactionsKind,
+}
+
+type AdapterFuture = Pin<Box<dyn Future<Output = Result<Arc<dyn BlockchainAdapter>>> + Send>>;
+type AdapterConstructor = Arc<dyn Fn(AdapterContext) -> AdapterFuture + Send + Sync>;
+
+/// Maps `adapter_type` config strings to adapter constructors, so crates embedding the pipeline
+/// can add chain types without patching it.
+#[derive(Clone)]
+pub struct AdapterRegistry {
+    constructors: HashMap<String, AdapterConstructor>,
+}
+
+impl AdapterRegistry {
+    /// A registry with no adapter types.
+    pub fn empty() -> Self {
+        Self { constructors: HashMap::new() }
+    }
+
+    /// Registers `constructor` for `adapter_type`, replacing any earlier registration.
+    pub fn register<F, Fut>(&mut self, adapter_type: &str, constructor: F) -> &mut Self
+    where
+        F: Fn(AdapterContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Arc<dyn BlockchainAdapter>>> + Send + 'static,
+    {
+        self.constructors.insert(
+            adapter_type.to_string(),
+            Arc::new(move |context| Box::pin(constructor(context)) as AdapterFuture),
+        );
+        self
+    }
+
+    pub fn contains(&self, adapter_type: &str) -> bool {
+        self.constructors.contains_key(adapter_type)
+    }
+
+    /// Builds an adapter of `adapter_type`, or returns `None` if the type is not registered.
+    pub async fn create(&self, adapter_type: &str, context: AdapterContext) -> Option<Result<Arc<dyn BlockchainAdapter>>> {
+        let constructor = self.constructors.get(adapter_type)?;
+        Some(constructor(context).await)
+    }
+}
+
+impl Default for AdapterRegistry {
+    /// A registry with the built-in `EVM` and `FIREHOSE` adapter types, and `RETH_DB` with the
+    /// `reth` feature.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("EVM", |context: AdapterContext| async move {
+            let adapter = EVMAdapter::new(&context.chain_name, &context.http_url, &context.ws_url)
+                .await
+                .context(format!("Failed to create EVMAdapter for {}", context.chain_name))?
+                .with_poll_bounds(PollBounds::from_options(&context.options))
+                .with_poll_detail(context.tx_detail);
+            Ok(Arc::new(adapter) as Arc<dyn BlockchainAdapter>)
+        });
+        registry.register("FIREHOSE", |context: AdapterContext| async move {
+            let adapter = FirehoseAdapter::new(&context.chain_name, &context.http_url, context.api_token.as_deref())
+                .await
+                .context(format!("Failed to create FirehoseAdapter for {}", context.chain_name))?;
+            Ok(Arc::new(adapter) as Arc<dyn BlockchainAdapter>)
+        });
+        // `http_url` names the env var holding the node's datadir.
+        #[cfg(feature = "reth")]
+        registry.register("RETH_DB", |context: AdapterContext| async move {
+            let chain = context.options.get("chain").and_then(|chain| chain.as_str()).unwrap_or("mainnet");
+            let adapter = RethDbAdapter::new(&context.chain_name, &context.http_url, chain)
+                .context(format!("Failed to create RethDbAdapter for {}", context.chain_name))?;
+            Ok(Arc::new(adapter) as Arc<dyn BlockchainAdapter>)
+        });
+        registry
+    }
+}
@@ -0,0 +1,275 @@
+use async_stream::try_stream;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::blockchain::adapters::BlockchainAdapter;
+use ethers::types::transaction::eip2930::{AccessList, AccessListItem};
+use ethers::types::{Address, Block, Bloom, Bytes, OtherFields, Transaction, Withdrawal, H256, H64, U256, U64};
+use futures_core::{Future, Stream};
+use anyhow::{Result as AnyResult, anyhow};
+use reth_chainspec::{ChainSpec, HOLESKY, MAINNET, SEPOLIA};
+use reth_db::{mdbx::DatabaseArguments, open_db_read_only, DatabaseEnv};
+use reth_node_ethereum::EthereumNode;
+use reth_node_types::NodeTypesWithDBAdapter;
+use reth_primitives::{BlockWithSenders, TransactionSigned, TxType};
+use reth_provider::{providers::StaticFileProvider, BlockNumReader, BlockReader, ProviderFactory, TransactionVariant};
+
+/// How often the database is checked for blocks the node imported since.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+type Factory = ProviderFactory<NodeTypesWithDBAdapter<EthereumNode, Arc<DatabaseEnv>>>;
+
+/// Reads blocks straight from a local Reth node's database and static files, read-only, instead
+/// of over JSON-RPC. Deep backfills run orders of magnitude faster, as nothing is serialized
+/// and no RPC limits apply. The node keeps running and owns the database.
+#[derive(Clone)]
+pub struct RethDbAdapter {
+    chain_name: String,
+    factory: Factory,
+}
+
+impl RethDbAdapter {
+    /// Opens the node's `datadir`, holding `db` and `static_files`. `chain` names its chain spec:
+    /// `mainnet`, `sepolia` or `holesky`.
+    pub fn new(chain_name: &str, datadir: &str, chain: &str) -> AnyResult<Self> {
+        let spec: Arc<ChainSpec> = match chain {
+            "mainnet" => MAINNET.clone(),
+            "sepolia" => SEPOLIA.clone(),
+            "holesky" => HOLESKY.clone(),
+            other => return Err(anyhow!("Unknown Reth chain `{}`", other)),
+        };
+        let datadir = Path::new(datadir);
+        let db = open_db_read_only(&datadir.join("db"), DatabaseArguments::default())
+            .map_err(|e| anyhow!("Failed to open Reth database in {}: {}", datadir.display(), e))?;
+        // Watched, so blocks the node moves into static files while running stay readable.
+        let static_files = StaticFileProvider::read_only(datadir.join("static_files"), true)
+            .map_err(|e| anyhow!("Failed to open Reth static files in {}: {}", datadir.display(), e))?;
+
+        Ok(Self {
+            chain_name: chain_name.to_string(),
+            factory: ProviderFactory::new(Arc::new(db), spec, static_files),
+        })
+    }
+
+    /// Reads `block_number` on a blocking thread, as MDBX reads block.
+    fn read_block(&self, block_number: u64) -> impl Future<Output = AnyResult<Option<Block<Transaction>>>> + Send + 'static {
+        let factory = self.factory.clone();
+        async move {
+            tokio::task::spawn_blocking(move || read_block(&factory, block_number)).await?
+        }
+    }
+}
+
+fn read_block(factory: &Factory, block_number: u64) -> AnyResult<Option<Block<Transaction>>> {
+    let provider = factory.provider().map_err(|e| anyhow!("Failed to open Reth provider: {}", e))?;
+    let block = provider
+        .block_with_senders(block_number.into(), TransactionVariant::WithHash)
+        .map_err(|e| anyhow!("Error reading block {} from Reth: {}", block_number, e))?;
+    Ok(block.map(to_ethers_block))
+}
+
+fn latest_block_number(factory: &Factory) -> AnyResult<u64> {
+    let provider = factory.provider().map_err(|e| anyhow!("Failed to open Reth provider: {}", e))?;
+    provider.best_block_number().map_err(|e| anyhow!("Error reading the latest block from Reth: {}", e))
+}
+
+fn h256(hash: impl AsRef<[u8]>) -> H256 {
+    H256::from_slice(hash.as_ref())
+}
+
+fn address(address: impl AsRef<[u8]>) -> Address {
+    Address::from_slice(address.as_ref())
+}
+
+fn u256(value: reth_primitives::U256) -> U256 {
+    U256::from_big_endian(&value.to_be_bytes::<32>())
+}
+
+/// Maps a signed transaction with every field JSON-RPC returns for it, so `Transaction::rlp`
+/// re-encodes it byte for byte and the transactions root can be verified.
+fn to_ethers_transaction(
+    index: usize,
+    transaction: &TransactionSigned,
+    from: Address,
+    hash: H256,
+    number: U64,
+    base_fee: Option<u64>,
+) -> Transaction {
+    let signature = transaction.signature();
+    let y_parity = signature.v().y_parity() as u64;
+    let legacy = transaction.tx_type() == TxType::Legacy;
+    let v = match (legacy, transaction.chain_id()) {
+        // EIP-155 replay protection.
+        (true, Some(chain_id)) => chain_id * 2 + 35 + y_parity,
+        (true, None) => 27 + y_parity,
+        (false, _) => y_parity,
+    };
+    // Mined transactions report what they paid per gas, which for fee-market transactions is the
+    // base fee plus the tip they could afford.
+    let dynamic_fee = transaction.is_dynamic_fee();
+    let gas_price = match base_fee {
+        Some(base_fee) if dynamic_fee => transaction
+            .max_fee_per_gas()
+            .min(base_fee as u128 + transaction.max_priority_fee_per_gas().unwrap_or_default()),
+        // For legacy and EIP-2930 transactions this is their gas price.
+        _ => transaction.max_fee_per_gas(),
+    };
+
+    // `ethers` has no EIP-4844 fields, so they go where its JSON-RPC decoding puts them.
+    let mut other = OtherFields::default();
+    if let Some(max_fee_per_blob_gas) = transaction.max_fee_per_blob_gas() {
+        other.insert("maxFeePerBlobGas".to_string(), serde_json::json!(U256::from(max_fee_per_blob_gas)));
+    }
+    if let Some(blob_versioned_hashes) = transaction.blob_versioned_hashes() {
+        let hashes: Vec<H256> = blob_versioned_hashes.iter().map(h256).collect();
+        other.insert("blobVersionedHashes".to_string(), serde_json::json!(hashes));
+    }
+    if !legacy {
+        other.insert("yParity".to_string(), serde_json::json!(U64::from(y_parity)));
+    }
+
+    Transaction {
+        hash: h256(transaction.hash()),
+        nonce: transaction.nonce().into(),
+        block_hash: Some(hash),
+        block_number: Some(number),
+        transaction_index: Some(index.into()),
+        from,
+        to: transaction.to().map(address),
+        value: u256(transaction.value()),
+        gas_price: Some(gas_price.into()),
+        gas: transaction.gas_limit().into(),
+        input: Bytes::from(transaction.input().to_vec()),
+        v: v.into(),
+        r: u256(signature.r()),
+        s: u256(signature.s()),
+        transaction_type: Some(U64::from(transaction.tx_type() as u8)),
+        access_list: transaction.access_list().map(|access_list| {
+            AccessList(
+                access_list
+                    .iter()
+                    .map(|item| AccessListItem {
+                        address: address(item.address),
+                        storage_keys: item.storage_keys.iter().map(h256).collect(),
+                    })
+                    .collect(),
+            )
+        }),
+        max_priority_fee_per_gas: transaction.max_priority_fee_per_gas().map(U256::from),
+        max_fee_per_gas: dynamic_fee.then(|| transaction.max_fee_per_gas().into()),
+        chain_id: transaction.chain_id().map(U256::from),
+        other,
+    }
+}
+
+/// Maps a Reth block into the same shape the JSON-RPC adapter produces, with every header field
+/// `compute_header_hash` needs.
+pub fn to_ethers_block(block: BlockWithSenders) -> Block<Transaction> {
+    let header = &block.block.header;
+    let hash = h256(header.hash_slow());
+    let number = U64::from(header.number);
+    let transactions = block
+        .block
+        .body
+        .transactions
+        .iter()
+        .zip(&block.senders)
+        .enumerate()
+        .map(|(index, (transaction, sender))| {
+            to_ethers_transaction(index, transaction, address(sender), hash, number, header.base_fee_per_gas)
+        })
+        .collect();
+
+    Block {
+        hash: Some(hash),
+        parent_hash: h256(header.parent_hash),
+        uncles_hash: h256(header.ommers_hash),
+        author: Some(address(header.beneficiary)),
+        state_root: h256(header.state_root),
+        transactions_root: h256(header.transactions_root),
+        receipts_root: h256(header.receipts_root),
+        number: Some(number),
+        gas_used: header.gas_used.into(),
+        gas_limit: header.gas_limit.into(),
+        extra_data: Bytes::from(header.extra_data.to_vec()),
+        logs_bloom: Some(Bloom::from_slice(header.logs_bloom.as_slice())),
+        timestamp: header.timestamp.into(),
+        difficulty: u256(header.difficulty),
+        uncles: block.block.body.ommers.iter().map(|ommer| h256(ommer.hash_slow())).collect(),
+        transactions,
+        mix_hash: Some(h256(header.mix_hash)),
+        nonce: Some(H64::from_slice(header.nonce.as_slice())),
+        base_fee_per_gas: header.base_fee_per_gas.map(U256::from),
+        blob_gas_used: header.blob_gas_used.map(U256::from),
+        excess_blob_gas: header.excess_blob_gas.map(U256::from),
+        withdrawals_root: header.withdrawals_root.map(h256),
+        withdrawals: block.block.body.withdrawals.as_ref().map(|withdrawals| {
+            withdrawals
+                .iter()
+                .map(|withdrawal| Withdrawal {
+                    index: withdrawal.index.into(),
+                    validator_index: withdrawal.validator_index.into(),
+                    address: address(withdrawal.address),
+                    amount: withdrawal.amount.into(),
+                })
+                .collect()
+        }),
+        parent_beacon_block_root: header.parent_beacon_block_root.map(h256),
+        ..Default::default()
+    }
+}
+
+impl BlockchainAdapter for RethDbAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        Box::pin(self.read_block(block_number))
+    }
+
+    /// Follows the node as it imports blocks, by polling its database.
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        let adapter = self.clone();
+        Box::pin(try_stream! {
+            let factory = adapter.factory.clone();
+            let mut next_block = tokio::task::spawn_blocking(move || latest_block_number(&factory)).await?? + 1;
+            loop {
+                match adapter.read_block(next_block).await? {
+                    Some(block) => {
+                        next_block += 1;
+                        yield block;
+                    }
+                    None => tokio::time::sleep(POLL_INTERVAL).await,
+                }
+            }
+        })
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        let factory = self.factory.clone();
+        Box::pin(async move { tokio::task::spawn_blocking(move || latest_block_number(&factory)).await? })
+    }
+
+    fn stream_blocks(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Option<Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>> {
+        let adapter = self.clone();
+        Some(Box::pin(try_stream! {
+            for block_number in start_block..=end_block {
+                let block = adapter
+                    .read_block(block_number)
+                    .await?
+                    .ok_or_else(|| anyhow!("Block {} of {} is not in the Reth database yet", block_number, adapter.chain_name))?;
+                yield block;
+            }
+        }))
+    }
+}
@@ -0,0 +1,243 @@
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::blockchain::adapters::BlockchainAdapter;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+use futures_core::{Future, Stream};
+use anyhow::Result as AnyResult;
+use serde::Deserialize;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+#[derive(Debug, Deserialize)]
+pub struct RpcQuotaConfig {
+    /// Requests per second the chain's provider allows, shared by realtime and backfill.
+    pub requests_per_second: f64,
+    /// Fraction of the quota reserved for realtime requests; backfill gets the rest, and never
+    /// less than `MIN_BACKFILL_SHARE`.
+    #[serde(default = "default_realtime_share")]
+    pub realtime_share: f64,
+}
+
+fn default_realtime_share() -> f64 {
+    0.8
+}
+
+/// Share of the quota backfill keeps however much realtime reserves, so it never stalls.
+pub const MIN_BACKFILL_SHARE: f64 = 0.05;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Priority {
+    Realtime,
+    Backfill,
+}
+
+struct Bucket {
+    rate: f64,
+    capacity: f64,
+    tokens: f64,
+}
+
+impl Bucket {
+    fn new(rate: f64) -> Self {
+        // One second of burst, but always room for a whole request.
+        let capacity = rate.max(1.0);
+        Self { rate, capacity, tokens: capacity }
+    }
+
+    fn refill(&mut self, elapsed: Duration) {
+        self.tokens = (self.tokens + elapsed.as_secs_f64() * self.rate).min(self.capacity);
+    }
+
+    fn try_take(&mut self, floor: f64) -> bool {
+        if self.tokens >= 1.0 + floor {
+            self.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn time_to_next_token(&self) -> Duration {
+        self.time_to_tokens(1.0)
+    }
+
+    fn time_to_tokens(&self, tokens: f64) -> Duration {
+        Duration::from_secs_f64(((tokens - self.tokens) / self.rate.max(0.001)).max(0.001))
+    }
+
+    /// Tokens backfill must leave in this bucket when borrowing it: half of it, so the head
+    /// stream keeps a burst, but never so much that a small bucket can't be borrowed at all.
+    fn reserve(&self) -> f64 {
+        (self.capacity / 2.0).min(self.capacity - 1.0).max(0.0)
+    }
+}
+
+struct Buckets {
+    realtime: Bucket,
+    backfill: Bucket,
+    refilled_at: Instant,
+}
+
+/// Token buckets splitting one request quota between realtime and backfill. Realtime requests
+/// may use backfill's tokens when their own run out; backfill borrows realtime's tokens while the
+/// realtime bucket is more than half full, i.e. while the head stream leaves its share idle, so
+/// the head stream always has burst left.
+pub struct PriorityRateLimiter {
+    buckets: Mutex<Buckets>,
+}
+
+impl PriorityRateLimiter {
+    pub fn new(config: &RpcQuotaConfig) -> Self {
+        let realtime_share = config.realtime_share.clamp(0.0, 1.0 - MIN_BACKFILL_SHARE);
+        Self {
+            buckets: Mutex::new(Buckets {
+                realtime: Bucket::new(config.requests_per_second * realtime_share),
+                backfill: Bucket::new(config.requests_per_second * (1.0 - realtime_share)),
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a request of the given priority may be issued.
+    pub async fn acquire(&self, priority: Priority) {
+        loop {
+            let wait = {
+                let mut buckets = self.buckets.lock().await;
+                let now = Instant::now();
+                let elapsed = now - buckets.refilled_at;
+                buckets.refilled_at = now;
+                buckets.realtime.refill(elapsed);
+                buckets.backfill.refill(elapsed);
+
+                let Buckets { realtime, backfill, .. } = &mut *buckets;
+                let acquired = match priority {
+                    Priority::Realtime => realtime.try_take(0.0) || backfill.try_take(0.0),
+                    Priority::Backfill => backfill.try_take(0.0) || realtime.try_take(realtime.reserve()),
+                };
+                if acquired {
+                    return;
+                }
+                match priority {
+                    Priority::Realtime => realtime.time_to_next_token().min(backfill.time_to_next_token()),
+                    Priority::Backfill => {
+                        backfill.time_to_next_token().min(realtime.time_to_tokens(1.0 + realtime.reserve()))
+                    }
+                }
+            };
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Issues the wrapped adapter's requests through a shared `PriorityRateLimiter` at a fixed
+/// priority. A chain's realtime and backfill paths get one instance each over the same limiter.
+pub struct PrioritizedAdapter {
+    inner: Arc<dyn BlockchainAdapter>,
+    limiter: Arc<PriorityRateLimiter>,
+    priority: Priority,
+}
+
+impl PrioritizedAdapter {
+    pub fn new(inner: Arc<dyn BlockchainAdapter>, limiter: Arc<PriorityRateLimiter>, priority: Priority) -> Self {
+        Self { inner, limiter, priority }
+    }
+
+    fn throttled<T, F, Fut>(&self, call: F) -> Pin<Box<dyn Future<Output = AnyResult<T>> + Send>>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<dyn BlockchainAdapter>) -> Fut + Send + 'static,
+        Fut: Future<Output = AnyResult<T>> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        let limiter = Arc::clone(&self.limiter);
+        let priority = self.priority;
+        Box::pin(async move {
+            limiter.acquire(priority).await;
+            call(inner).await
+        })
+    }
+}
+
+impl BlockchainAdapter for PrioritizedAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        self.throttled(move |inner| inner.get_block_by_number(block_number))
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        self.throttled(move |inner| inner.get_block_with_hashes(block_number))
+    }
+
+    // A subscription is a single long-lived request, so it is not throttled.
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        self.inner.subscribe_new_blocks()
+    }
+
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        self.inner.subscribe_pending_transactions()
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        self.throttled(|inner| inner.get_latest_block_number())
+    }
+
+    fn stream_blocks(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Option<Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>> {
+        self.inner.stream_blocks(start_block, end_block)
+    }
+
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        self.throttled(move |inner| inner.get_balance(address, block_number))
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        self.throttled(move |inner| inner.get_logs(block_number))
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.throttled(move |inner| inner.call(to, data, block_number))
+    }
+
+    fn call_request(
+        &self,
+        request: TransactionRequest,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.throttled(move |inner| inner.call_request(request, block_number))
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        self.throttled(move |inner| inner.get_block_receipts(block_number))
+    }
+}
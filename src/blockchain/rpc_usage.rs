@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::rate_limit::RateLimiter;
+use crate::metrics;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+use futures_core::{Future, Stream};
+use futures_util::StreamExt;
+use anyhow::Result as AnyResult;
+use log::{error, warn};
+use serde::Deserialize;
+use sqlx::{PgPool, Row};
+
+/// How often accumulated usage is written to `rpc_usage` and the monthly total re-read.
+const FLUSH_INTERVAL: Duration = Duration::from_secs(15);
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RpcCostConfig {
+    /// Provider account the credits are billed to; chains sharing it share the budget.
+    pub provider: String,
+    /// Credits per call for methods not listed in `methods`.
+    #[serde(default = "default_cost")]
+    pub default_cost: u64,
+    /// Credits per call by JSON-RPC method, e.g. `eth_getLogs = 75`.
+    #[serde(default)]
+    pub methods: HashMap<String, u64>,
+    /// Credits the provider account may spend per calendar month.
+    pub monthly_budget: Option<u64>,
+    /// Fraction of `monthly_budget` after which requests are throttled.
+    #[serde(default = "default_throttle_at")]
+    pub throttle_at: f64,
+    /// Request rate allowed once throttled.
+    #[serde(default = "default_throttled_requests_per_second")]
+    pub throttled_requests_per_second: f64,
+}
+
+fn default_cost() -> u64 {
+    1
+}
+
+fn default_throttle_at() -> f64 {
+    0.9
+}
+
+fn default_throttled_requests_per_second() -> f64 {
+    1.0
+}
+
+/// Accumulates a chain's calls and credits and periodically adds them to `rpc_usage`.
+pub struct RpcUsageMeter {
+    pg_pool: Arc<PgPool>,
+    chain_name: String,
+    config: RpcCostConfig,
+    // (calls, credits) per method since the last flush.
+    pending: Mutex<HashMap<&'static str, (u64, u64)>>,
+    // Credits the provider account has spent this month, as of the last flush.
+    month_credits: AtomicU64,
+    throttle: RateLimiter,
+}
+
+impl RpcUsageMeter {
+    pub fn new(pg_pool: Arc<PgPool>, chain_name: &str, config: RpcCostConfig) -> Self {
+        let throttle = RateLimiter::new(config.throttled_requests_per_second);
+        Self {
+            pg_pool,
+            chain_name: chain_name.to_string(),
+            config,
+            pending: Mutex::new(HashMap::new()),
+            month_credits: AtomicU64::new(0),
+            throttle,
+        }
+    }
+
+    fn record(&self, method: &'static str) {
+        let cost = self.config.methods.get(method).copied().unwrap_or(self.config.default_cost);
+        {
+            let mut pending = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            let usage = pending.entry(method).or_default();
+            usage.0 += 1;
+            usage.1 += cost;
+        }
+        self.month_credits.fetch_add(cost, Ordering::Relaxed);
+
+        let labels = [("provider", self.config.provider.as_str()), ("chain", self.chain_name.as_str()), ("method", method)];
+        metrics::increment_counter("rpc_calls_total", &labels, 1);
+        metrics::increment_counter("rpc_credits_total", &labels, cost);
+    }
+
+    /// Slows the caller down once the month's credits pass the throttle threshold.
+    async fn before_call(&self) {
+        let Some(budget) = self.config.monthly_budget else {
+            return;
+        };
+        if self.month_credits.load(Ordering::Relaxed) as f64 >= budget as f64 * self.config.throttle_at {
+            self.throttle.acquire().await;
+        }
+    }
+
+    /// Flushes usage every `FLUSH_INTERVAL`. Runs forever.
+    pub async fn run(&self) -> AnyResult<()> {
+        let mut ticker = tokio::time::interval(FLUSH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.flush().await {
+                error!("Failed to record RPC usage for {}: {}", self.chain_name, e);
+            }
+        }
+    }
+
+    async fn flush(&self) -> AnyResult<()> {
+        let pending = std::mem::take(&mut *self.pending.lock().unwrap_or_else(|e| e.into_inner()));
+        if let Err(e) = self.write_usage(&pending).await {
+            // Keep the usage for the next flush rather than under-reporting it.
+            let mut current = self.pending.lock().unwrap_or_else(|e| e.into_inner());
+            for (method, (calls, credits)) in pending {
+                let usage = current.entry(method).or_default();
+                usage.0 += calls;
+                usage.1 += credits;
+            }
+            return Err(e);
+        }
+
+        // Other chains billed to the same provider count against the same budget.
+        let row = sqlx::query(
+            "SELECT COALESCE(SUM(credits), 0)::bigint AS credits FROM rpc_usage
+            WHERE provider = $1 AND month = date_trunc('month', NOW())::date",
+        )
+        .bind(&self.config.provider)
+        .fetch_one(self.pg_pool.as_ref())
+        .await?;
+        let month_credits = row.try_get::<i64, _>("credits")? as u64;
+        self.month_credits.store(month_credits, Ordering::Relaxed);
+        metrics::set_gauge("rpc_month_credits", &[("provider", self.config.provider.as_str())], month_credits as f64);
+
+        if let Some(budget) = self.config.monthly_budget {
+            if month_credits as f64 >= budget as f64 * self.config.throttle_at {
+                warn!(
+                    "{} has used {} of its {} monthly credits, throttling {} to {} requests/s",
+                    self.config.provider, month_credits, budget, self.chain_name, self.config.throttled_requests_per_second
+                );
+            }
+        }
+        Ok(())
+    }
+
+    async fn write_usage(&self, usage: &HashMap<&'static str, (u64, u64)>) -> AnyResult<()> {
+        let mut tx = self.pg_pool.begin().await?;
+        for (method, (calls, credits)) in usage {
+            sqlx::query(
+                "INSERT INTO rpc_usage (provider, chain_name, method, month, calls, credits)
+                VALUES ($1, $2, $3, date_trunc('month', NOW())::date, $4, $5)
+                ON CONFLICT (provider, chain_name, method, month) DO UPDATE SET
+                    calls = rpc_usage.calls + EXCLUDED.calls,
+                    credits = rpc_usage.credits + EXCLUDED.credits",
+            )
+            .bind(&self.config.provider)
+            .bind(&self.chain_name)
+            .bind(*method)
+            .bind(*calls as i64)
+            .bind(*credits as i64)
+            .execute(&mut tx)
+            .await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+/// Counts the wrapped adapter's requests against a provider's credit budget.
+pub struct MeteredAdapter {
+    inner: Arc<dyn BlockchainAdapter>,
+    meter: Arc<RpcUsageMeter>,
+}
+
+impl MeteredAdapter {
+    pub fn new(inner: Arc<dyn BlockchainAdapter>, meter: Arc<RpcUsageMeter>) -> Self {
+        Self { inner, meter }
+    }
+
+    fn metered<T, F, Fut>(&self, method: &'static str, call: F) -> Pin<Box<dyn Future<Output = AnyResult<T>> + Send>>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<dyn BlockchainAdapter>) -> Fut + Send + 'static,
+        Fut: Future<Output = AnyResult<T>> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        let meter = Arc::clone(&self.meter);
+        Box::pin(async move {
+            meter.before_call().await;
+            // Providers bill failed calls too.
+            meter.record(method);
+            call(inner).await
+        })
+    }
+}
+
+impl BlockchainAdapter for MeteredAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        self.metered("eth_getBlockByNumber", move |inner| inner.get_block_by_number(block_number))
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        self.metered("eth_getBlockByNumber", move |inner| inner.get_block_with_hashes(block_number))
+    }
+
+    // Providers bill every subscription event.
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        let meter = Arc::clone(&self.meter);
+        let mut stream = self.inner.subscribe_new_blocks();
+        Box::pin(async_stream::stream! {
+            while let Some(block) = stream.next().await {
+                meter.record("eth_subscribe");
+                yield block;
+            }
+        })
+    }
+
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        let meter = Arc::clone(&self.meter);
+        let mut stream = self.inner.subscribe_pending_transactions();
+        Box::pin(async_stream::stream! {
+            while let Some(transaction) = stream.next().await {
+                meter.record("eth_subscribe");
+                yield transaction;
+            }
+        })
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        self.metered("eth_blockNumber", |inner| inner.get_latest_block_number())
+    }
+
+    fn stream_blocks(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Option<Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>> {
+        self.inner.stream_blocks(start_block, end_block)
+    }
+
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        self.metered("eth_getBalance", move |inner| inner.get_balance(address, block_number))
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        self.metered("eth_getLogs", move |inner| inner.get_logs(block_number))
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.metered("eth_call", move |inner| inner.call(to, data, block_number))
+    }
+
+    fn call_request(
+        &self,
+        request: TransactionRequest,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.metered("eth_call", move |inner| inner.call_request(request, block_number))
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        self.metered("eth_getBlockReceipts", move |inner| inner.get_block_receipts(block_number))
+    }
+}
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, Result};
+use toml::value::Datetime;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+
+/// Seconds since the Unix epoch of a TOML date or date-time. Dates mean midnight, and date-times
+/// without an offset are taken as UTC.
+pub fn unix_seconds(datetime: &Datetime) -> Result<u64> {
+    let date = datetime.date.ok_or_else(|| anyhow!("`{}` has no date", datetime))?;
+    let (hour, minute, second) = datetime.time.map_or((0, 0, 0), |time| (time.hour, time.minute, time.second));
+    let offset_minutes = match datetime.offset {
+        Some(toml::value::Offset::Custom { minutes }) => minutes as i64,
+        _ => 0,
+    };
+    let seconds = days_from_civil(date.year as i64, date.month as i64, date.day as i64) * 86_400
+        + hour as i64 * 3_600
+        + minute as i64 * 60
+        + second as i64
+        - offset_minutes * 60;
+    u64::try_from(seconds).map_err(|_| anyhow!("`{}` is before 1970", datetime))
+}
+
+/// Days from 1970-01-01 to a proleptic Gregorian date.
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+async fn block_timestamp(adapter: &dyn BlockchainAdapter, block_number: u64) -> Result<u64> {
+    let block = adapter
+        .get_block_by_number(block_number)
+        .await?
+        .ok_or_else(|| anyhow!("Block {} not found while searching by timestamp", block_number))?;
+    Ok(block.timestamp.as_u64())
+}
+
+/// The oldest block the adapter still serves. Pruned nodes and providers drop old blocks, and
+/// what they keep always runs up to the head, so the boundary is found by binary search unless
+/// the genesis block is there.
+async fn earliest_available_block(adapter: &dyn BlockchainAdapter, head: u64) -> Result<u64> {
+    if adapter.get_block_by_number(0).await?.is_some() {
+        return Ok(0);
+    }
+    let (mut low, mut high) = (1, head);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if adapter.get_block_by_number(middle).await?.is_some() {
+            high = middle;
+        } else {
+            low = middle + 1;
+        }
+    }
+    Ok(low)
+}
+
+/// The first block with a timestamp at or after `timestamp`, found by binary search over block
+/// timestamps, which never decrease. The search starts at the earliest block the adapter serves,
+/// which is also the answer for times before it. `None` if the head is still older, i.e. the time
+/// is in the future. Takes about log2(head) block requests, twice that on pruned nodes.
+pub async fn first_block_at_or_after(adapter: &dyn BlockchainAdapter, timestamp: u64) -> Result<Option<u64>> {
+    let head = adapter.get_latest_block_number().await?;
+    if block_timestamp(adapter, head).await? < timestamp {
+        return Ok(None);
+    }
+    let (mut low, mut high) = (earliest_available_block(adapter, head).await?, head);
+    while low < high {
+        let middle = low + (high - low) / 2;
+        if block_timestamp(adapter, middle).await? < timestamp {
+            low = middle + 1;
+        } else {
+            high = middle;
+        }
+    }
+    Ok(Some(low))
+}
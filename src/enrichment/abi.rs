@@ -0,0 +1,161 @@
+use anyhow::{Context, Result};
+use ethers::abi::{decode, Abi, AbiError, Event, ParamType, RawLog, Token};
+use ethers::types::{Log, H256};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+// Selectors of the errors Solidity emits without a declaration.
+const ERROR_STRING_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AbiConfig {
+    /// ABI JSON files, or directories of them. Both bare ABI arrays and build artifacts with an
+    /// `abi` field are accepted.
+    #[serde(default)]
+    pub paths: Vec<PathBuf>,
+}
+
+/// Contract ABIs known to the pipeline, indexed for decoding. ABIs can be added while running.
+#[derive(Default)]
+pub struct AbiRegistry {
+    errors: RwLock<HashMap<[u8; 4], AbiError>>,
+    functions: RwLock<HashMap<[u8; 4], String>>,
+    // Events sharing a topic can differ in which parameters are indexed.
+    events: RwLock<HashMap<H256, Vec<Event>>>,
+}
+
+/// A log decoded against a registered event.
+#[derive(Debug, Clone)]
+pub struct DecodedEvent {
+    pub name: String,
+    pub params: Vec<(String, Token)>,
+}
+
+impl DecodedEvent {
+    /// The parameters as a JSON object. Integers are decimal strings, so they keep full precision.
+    pub fn params_json(&self) -> Value {
+        Value::Object(self.params.iter().map(|(name, token)| (name.clone(), token_json(token))).collect())
+    }
+}
+
+fn token_json(token: &Token) -> Value {
+    match token {
+        Token::Address(address) => json!(format!("{:?}", address)),
+        Token::Uint(value) => json!(value.to_string()),
+        // Two's complement, as ethabi decodes signed integers into a U256.
+        Token::Int(value) if value.bit(255) => json!(format!("-{}", (!*value).overflowing_add(1.into()).0)),
+        Token::Int(value) => json!(value.to_string()),
+        Token::Bool(value) => json!(value),
+        Token::String(value) => json!(value),
+        Token::Bytes(bytes) | Token::FixedBytes(bytes) => json!(format!("0x{}", ethers::utils::hex::encode(bytes))),
+        Token::Array(tokens) | Token::FixedArray(tokens) | Token::Tuple(tokens) => {
+            Value::Array(tokens.iter().map(token_json).collect())
+        }
+    }
+}
+
+impl AbiRegistry {
+    pub fn load(config: &AbiConfig) -> Result<Self> {
+        let registry = Self::default();
+        for path in &config.paths {
+            if path.is_dir() {
+                let mut files = Vec::new();
+                for entry in std::fs::read_dir(path).with_context(|| format!("Failed to read {}", path.display()))? {
+                    let file = entry?.path();
+                    if file.extension().map_or(false, |ext| ext == "json") {
+                        files.push(file);
+                    }
+                }
+                files.sort();
+                for file in files {
+                    registry.add_abi(&read_abi(&file)?);
+                }
+            } else {
+                registry.add_abi(&read_abi(path)?);
+            }
+        }
+        Ok(registry)
+    }
+
+    pub fn add_abi(&self, abi: &Abi) {
+        let mut errors = self.errors.write().unwrap_or_else(|e| e.into_inner());
+        for error in abi.errors() {
+            let mut selector = [0u8; 4];
+            selector.copy_from_slice(&error.signature()[..4]);
+            errors.insert(selector, error.clone());
+        }
+        drop(errors);
+
+        let mut functions = self.functions.write().unwrap_or_else(|e| e.into_inner());
+        for function in abi.functions() {
+            let types: Vec<String> = function.inputs.iter().map(|input| input.kind.to_string()).collect();
+            functions.insert(function.short_signature(), format!("{}({})", function.name, types.join(",")));
+        }
+        drop(functions);
+
+        let mut events = self.events.write().unwrap_or_else(|e| e.into_inner());
+        for event in abi.events().filter(|event| !event.anonymous) {
+            let known = events.entry(event.signature()).or_default();
+            if !known.contains(event) {
+                known.push(event.clone());
+            }
+        }
+    }
+
+    /// Decodes a log against the registered events with its first topic.
+    pub fn decode_log(&self, log: &Log) -> Option<DecodedEvent> {
+        let events = self.events.read().unwrap_or_else(|e| e.into_inner());
+        let raw = RawLog { topics: log.topics.clone(), data: log.data.to_vec() };
+        events.get(log.topics.first()?)?.iter().find_map(|event| {
+            let parsed = event.parse_log(raw.clone()).ok()?;
+            Some(DecodedEvent {
+                name: event.name.clone(),
+                params: parsed.params.into_iter().map(|param| (param.name, param.value)).collect(),
+            })
+        })
+    }
+
+    /// Signature of a registered function, e.g. `transfer(address,uint256)`.
+    pub fn function_signature(&self, selector: [u8; 4]) -> Option<String> {
+        self.functions.read().unwrap_or_else(|e| e.into_inner()).get(&selector).cloned()
+    }
+
+    /// Renders revert data as the reason string, `Panic(0x..)`, or `Name(args)` for custom errors
+    /// of registered ABIs. `None` if the data matches none of them.
+    pub fn decode_revert(&self, data: &[u8]) -> Option<String> {
+        if data.len() < 4 {
+            return None;
+        }
+        let (selector, args) = data.split_at(4);
+        if selector == ERROR_STRING_SELECTOR {
+            return match decode(&[ParamType::String], args).ok()?.pop()? {
+                Token::String(reason) => Some(reason),
+                _ => None,
+            };
+        }
+        if selector == PANIC_SELECTOR {
+            let code = decode(&[ParamType::Uint(256)], args).ok()?.pop()?.into_uint()?;
+            return Some(format!("Panic({:#x})", code));
+        }
+
+        let errors = self.errors.read().unwrap_or_else(|e| e.into_inner());
+        let error = errors.get(selector)?;
+        let tokens = error.decode(args).ok()?;
+        let args: Vec<String> = tokens.iter().map(|token| token.to_string()).collect();
+        Some(format!("{}({})", error.name, args.join(", ")))
+    }
+}
+
+fn read_abi(path: &Path) -> Result<Abi> {
+    let contents = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut value: serde_json::Value = serde_json::from_str(&contents)
+        .with_context(|| format!("Invalid JSON in {}", path.display()))?;
+    if let Some(abi) = value.get_mut("abi") {
+        value = abi.take();
+    }
+    serde_json::from_value(value).with_context(|| format!("Invalid ABI in {}", path.display()))
+}
@@ -0,0 +1,168 @@
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, H256, U256};
+use log::info;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::enrichment::token_balances::store_transfers;
+use crate::enrichment::transfers::{insert_token_transfer, TokenTransfer};
+
+/// Largest page `alchemy_getAssetTransfers` accepts.
+const MAX_COUNT: &str = "0x3e8";
+
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    result: Option<AssetTransfersPage>,
+    error: Option<serde_json::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AssetTransfersPage {
+    transfers: Vec<AssetTransfer>,
+    page_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AssetTransfer {
+    block_num: String,
+    hash: String,
+    from: String,
+    to: Option<String>,
+    unique_id: String,
+    raw_contract: RawContract,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawContract {
+    value: Option<String>,
+    address: Option<String>,
+}
+
+fn parse_hex_u64(value: &str) -> Result<u64> {
+    u64::from_str_radix(value.trim_start_matches("0x"), 16)
+        .with_context(|| format!("Invalid hex quantity `{}`", value))
+}
+
+impl AssetTransfer {
+    fn into_token_transfer(self) -> Result<TokenTransfer> {
+        // ERC-20 unique ids look like `{tx_hash}:log:{log_index}`.
+        let log_index = self
+            .unique_id
+            .rsplit(':')
+            .next()
+            .and_then(|index| index.parse().ok())
+            .ok_or_else(|| anyhow!("Unexpected transfer uniqueId `{}`", self.unique_id))?;
+        let token = self
+            .raw_contract
+            .address
+            .ok_or_else(|| anyhow!("Transfer {} has no contract address", self.unique_id))?;
+
+        Ok(TokenTransfer {
+            token: token.parse::<Address>()?,
+            from: self.from.parse::<Address>()?,
+            to: self.to.unwrap_or_default().parse::<Address>().unwrap_or_default(),
+            value: U256::from_str_radix(self.raw_contract.value.as_deref().unwrap_or("0x0").trim_start_matches("0x"), 16)?,
+            block_number: parse_hex_u64(&self.block_num)?,
+            block_hash: None,
+            tx_hash: self.hash.parse::<H256>()?,
+            log_index,
+        })
+    }
+}
+
+/// Backfills `token_transfers` from Alchemy's `alchemy_getAssetTransfers` enhanced API, which is
+/// much faster than fetching and decoding every block's logs ourselves. With `track_balances`,
+/// the deltas of the transfers it stores are applied to `token_balances` as well, since the
+/// `TokenBalanceTracker` only applies those of transfers it inserts itself.
+pub struct AlchemyTransferBackfill {
+    client: Client,
+    url: String,
+    pg_pool: Arc<PgPool>,
+    track_balances: bool,
+}
+
+impl AlchemyTransferBackfill {
+    pub fn new(url: &str, pg_pool: Arc<PgPool>, track_balances: bool) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.to_string(),
+            pg_pool,
+            track_balances,
+        }
+    }
+
+    /// Pages through every ERC-20 transfer in `start_block..=end_block` and stores it.
+    pub async fn run(&self, chain_name: &str, start_block: u64, end_block: u64) -> Result<()> {
+        let mut page_key: Option<String> = None;
+        let mut total = 0usize;
+
+        loop {
+            let mut params = json!({
+                "fromBlock": format!("{:#x}", start_block),
+                "toBlock": format!("{:#x}", end_block),
+                "category": ["erc20"],
+                "withMetadata": false,
+                "excludeZeroValue": false,
+                "maxCount": MAX_COUNT,
+                "order": "asc",
+            });
+            if let Some(key) = &page_key {
+                params["pageKey"] = json!(key);
+            }
+
+            let response: RpcResponse = self
+                .client
+                .post(&self.url)
+                .json(&json!({
+                    "jsonrpc": "2.0",
+                    "id": 1,
+                    "method": "alchemy_getAssetTransfers",
+                    "params": [params],
+                }))
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            if let Some(error) = response.error {
+                return Err(anyhow!("alchemy_getAssetTransfers failed: {}", error));
+            }
+            let page = response
+                .result
+                .ok_or_else(|| anyhow!("alchemy_getAssetTransfers returned no result"))?;
+
+            let transfers = page
+                .transfers
+                .into_iter()
+                .map(AssetTransfer::into_token_transfer)
+                .collect::<Result<Vec<_>>>()?;
+            let mut tx = self.pg_pool.begin().await?;
+            if self.track_balances {
+                store_transfers(&mut tx, chain_name, &transfers).await?;
+            } else {
+                for transfer in &transfers {
+                    insert_token_transfer(&mut tx, chain_name, transfer).await?;
+                }
+            }
+            tx.commit().await?;
+            total += transfers.len();
+
+            match page.page_key {
+                Some(key) => page_key = Some(key),
+                None => break,
+            }
+        }
+
+        info!(
+            "Backfilled {} token transfers for {} blocks {}..={} from Alchemy",
+            total, chain_name, start_block, end_block
+        );
+        Ok(())
+    }
+}
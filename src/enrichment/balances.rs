@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Transaction};
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::streams::consumers::hooks::ConsumerHook;
+
+/// Records the native balance of each watched address every `every_n_blocks` ingested blocks,
+/// building the `balances` time series used for historical balance charts.
+pub struct BalanceTracker {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    addresses: Vec<Address>,
+    every_n_blocks: u64,
+}
+
+impl BalanceTracker {
+    pub fn new(
+        adapter: Arc<dyn BlockchainAdapter>,
+        pg_pool: Arc<PgPool>,
+        watched_addresses: &[String],
+        every_n_blocks: u64,
+    ) -> Result<Self> {
+        let addresses = watched_addresses
+            .iter()
+            .map(|address| {
+                address
+                    .parse::<Address>()
+                    .with_context(|| format!("Invalid watched address `{}`", address))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            adapter,
+            pg_pool,
+            addresses,
+            every_n_blocks: every_n_blocks.max(1),
+        })
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for BalanceTracker {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        if self.addresses.is_empty() || block_number % self.every_n_blocks != 0 {
+            return Ok(());
+        }
+
+        for address in &self.addresses {
+            let balance = self.adapter.get_balance(*address, block_number).await?;
+
+            sqlx::query(
+                "INSERT INTO balances (chain_name, address, block_number, balance) VALUES ($1, $2, $3, $4::numeric)
+                ON CONFLICT (chain_name, address, block_number) DO UPDATE SET balance = EXCLUDED.balance",
+            )
+            .bind(chain_name)
+            .bind(format!("{:?}", address))
+            .bind(block_number as i64)
+            .bind(balance.to_string())
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        }
+
+        Ok(())
+    }
+}
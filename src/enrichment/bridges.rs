@@ -0,0 +1,395 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::abi::{encode, parse_abi, Event, RawLog, Token};
+use ethers::types::{Address, Block, Log, Transaction, H256};
+use ethers::utils::keccak256;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+const OP_STANDARD_BRIDGE_EVENTS: &[(&str, Direction)] = &[
+    ("event ERC20BridgeInitiated(address indexed localToken, address indexed remoteToken, address indexed from, address to, uint256 amount, bytes extraData)", Direction::Outbound),
+    ("event ERC20BridgeFinalized(address indexed localToken, address indexed remoteToken, address indexed from, address to, uint256 amount, bytes extraData)", Direction::Inbound),
+    ("event ETHBridgeInitiated(address indexed from, address indexed to, uint256 amount, bytes extraData)", Direction::Outbound),
+    ("event ETHBridgeFinalized(address indexed from, address indexed to, uint256 amount, bytes extraData)", Direction::Inbound),
+];
+
+const OP_MESSENGER_EVENTS: &[&str] = &[
+    "event SentMessage(address indexed target, address sender, bytes message, uint256 messageNonce, uint256 gasLimit)",
+    "event SentMessageExtension1(address indexed sender, uint256 value)",
+    "event RelayedMessage(bytes32 indexed msgHash)",
+];
+
+const ARBITRUM_GATEWAY_EVENTS: &[(&str, Direction)] = &[
+    ("event DepositInitiated(address l1Token, address indexed _from, address indexed _to, uint256 indexed _sequenceNumber, uint256 _amount)", Direction::Outbound),
+    ("event DepositFinalized(address indexed l1Token, address indexed _from, address indexed _to, uint256 _amount)", Direction::Inbound),
+    ("event WithdrawalInitiated(address l1Token, address indexed _from, address indexed _to, uint256 indexed _l2ToL1Id, uint256 _exitNum, uint256 _amount)", Direction::Outbound),
+    ("event WithdrawalFinalized(address l1Token, address indexed _from, address indexed _to, uint256 indexed _exitNum, uint256 _amount)", Direction::Inbound),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BridgeKind {
+    OpStandardBridge,
+    ArbitrumGateway,
+    Custom,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Leaves this chain: this chain is the source.
+    Outbound,
+    /// Arrives on this chain: this chain is the destination.
+    Inbound,
+}
+
+impl Direction {
+    fn as_str(self) -> &'static str {
+        match self {
+            Direction::Outbound => "outbound",
+            Direction::Inbound => "inbound",
+        }
+    }
+}
+
+/// Which event parameters hold a custom bridge's transfer fields.
+#[derive(Debug, Clone, Deserialize)]
+pub struct BridgeFields {
+    pub token: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub amount: Option<String>,
+    /// A value both sides of the bridge emit, such as a deposit nonce.
+    pub key: String,
+    /// Counterparty chain id, for bridges serving several chains from one contract.
+    pub remote_chain_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BridgeContractConfig {
+    pub kind: BridgeKind,
+    pub address: String,
+    /// Chain id at the other end. Custom bridges may read it from the event instead.
+    pub remote_chain_id: Option<u64>,
+    /// Custom bridges only: a name for `bridge`, the event signature, its direction and fields.
+    pub name: Option<String>,
+    pub event: Option<String>, // e.g. "Locked(address indexed token, address from, uint256 amount, uint256 nonce)"
+    pub direction: Option<Direction>,
+    pub fields: Option<BridgeFields>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BridgeConfig {
+    #[serde(default)]
+    pub contracts: Vec<BridgeContractConfig>,
+}
+
+/// How the correlation key joining both sides of a transfer is derived.
+enum KeyScheme {
+    /// The CrossDomainMessenger message hash, from the messenger events in the same transaction.
+    OpMessage,
+    ArbitrumDeposit,
+    ArbitrumWithdrawal,
+    Field(String),
+}
+
+struct EventSpec {
+    bridge: String,
+    event: Event,
+    direction: Direction,
+    remote_chain_id: Option<u64>,
+    token: Option<String>,
+    remote_token: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    amount: Option<String>,
+    remote_chain_field: Option<String>,
+    key: KeyScheme,
+}
+
+struct BridgeTransfer {
+    tx_hash: H256,
+    log_index: i64,
+    bridge: String,
+    direction: Direction,
+    source_chain_id: Option<i64>,
+    destination_chain_id: Option<i64>,
+    token: Option<String>,
+    remote_token: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    amount: Option<String>,
+    correlation_key: Option<String>,
+}
+
+fn parse_event(signature: &str) -> Result<Event> {
+    let signature = if signature.starts_with("event ") { signature.to_string() } else { format!("event {}", signature) };
+    let abi = parse_abi(&[signature.as_str()]).map_err(|e| anyhow!("Invalid event `{}`: {}", signature, e))?;
+    abi.events().next().cloned().ok_or_else(|| anyhow!("No event in `{}`", signature))
+}
+
+fn format_token(token: &Token) -> String {
+    match token {
+        Token::Address(address) => format!("{:?}", address),
+        Token::Uint(value) | Token::Int(value) => value.to_string(),
+        Token::FixedBytes(bytes) | Token::Bytes(bytes) => format!("0x{}", ethers::utils::hex::encode(bytes)),
+        other => other.to_string(),
+    }
+}
+
+/// Decodes canonical bridge events into `bridge_transfers`. Each row gets the source and
+/// destination chain ids and a `correlation_key`, which the other side of the same transfer also
+/// gets, so cross-chain flows can be joined on `(correlation_key)` across chains.
+pub struct BridgeDecoder {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    chain_id: u64,
+    specs: HashMap<(Address, H256), EventSpec>,
+    messenger_events: HashMap<H256, Event>,
+    relay_message_selector: [u8; 4],
+}
+
+impl BridgeDecoder {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, chain_id: u64, config: &BridgeConfig) -> Result<Self> {
+        let mut specs = HashMap::new();
+        for contract in &config.contracts {
+            let address = contract
+                .address
+                .parse::<Address>()
+                .with_context(|| format!("Invalid bridge address `{}`", contract.address))?;
+            let field = |name: &str| Some(name.to_string());
+            match contract.kind {
+                BridgeKind::OpStandardBridge => {
+                    for (signature, direction) in OP_STANDARD_BRIDGE_EVENTS {
+                        let event = parse_event(signature)?;
+                        let erc20 = event.inputs.iter().any(|input| input.name == "localToken");
+                        specs.insert((address, event.signature()), EventSpec {
+                            bridge: "op_standard_bridge".to_string(),
+                            direction: *direction,
+                            remote_chain_id: contract.remote_chain_id,
+                            token: if erc20 { field("localToken") } else { None },
+                            remote_token: if erc20 { field("remoteToken") } else { None },
+                            from: field("from"),
+                            to: field("to"),
+                            amount: field("amount"),
+                            remote_chain_field: None,
+                            key: KeyScheme::OpMessage,
+                            event,
+                        });
+                    }
+                }
+                BridgeKind::ArbitrumGateway => {
+                    for (signature, direction) in ARBITRUM_GATEWAY_EVENTS {
+                        let event = parse_event(signature)?;
+                        let key = if event.name.starts_with("Deposit") { KeyScheme::ArbitrumDeposit } else { KeyScheme::ArbitrumWithdrawal };
+                        // Gateways name tokens by their L1 address on both chains.
+                        let (token, remote_token) = match (event.name.starts_with("Deposit"), *direction) {
+                            (true, Direction::Outbound) | (false, Direction::Inbound) => (field("l1Token"), None),
+                            _ => (None, field("l1Token")),
+                        };
+                        specs.insert((address, event.signature()), EventSpec {
+                            bridge: "arbitrum_gateway".to_string(),
+                            direction: *direction,
+                            remote_chain_id: contract.remote_chain_id,
+                            token,
+                            remote_token,
+                            from: field("_from"),
+                            to: field("_to"),
+                            amount: field("_amount"),
+                            remote_chain_field: None,
+                            key,
+                            event,
+                        });
+                    }
+                }
+                BridgeKind::Custom => {
+                    let (Some(signature), Some(direction), Some(fields)) = (&contract.event, contract.direction, &contract.fields) else {
+                        return Err(anyhow!("Custom bridge `{}` needs event, direction and fields", contract.address));
+                    };
+                    let event = parse_event(signature)?;
+                    let names = [&fields.token, &fields.from, &fields.to, &fields.amount, &Some(fields.key.clone()), &fields.remote_chain_id];
+                    if let Some(missing) = names.iter().filter_map(|name| name.as_ref()).find(|name| !event.inputs.iter().any(|input| &input.name == *name)) {
+                        return Err(anyhow!("Event `{}` of bridge `{}` has no parameter `{}`", event.name, contract.address, missing));
+                    }
+                    specs.insert((address, event.signature()), EventSpec {
+                        bridge: contract.name.clone().unwrap_or_else(|| "custom".to_string()),
+                        direction,
+                        remote_chain_id: contract.remote_chain_id,
+                        token: fields.token.clone(),
+                        remote_token: None,
+                        from: fields.from.clone(),
+                        to: fields.to.clone(),
+                        amount: fields.amount.clone(),
+                        remote_chain_field: fields.remote_chain_id.clone(),
+                        key: KeyScheme::Field(fields.key.clone()),
+                        event,
+                    });
+                }
+            }
+        }
+
+        let messenger_events = OP_MESSENGER_EVENTS
+            .iter()
+            .map(|signature| parse_event(signature).map(|event| (event.signature(), event)))
+            .collect::<Result<_>>()?;
+        let mut relay_message_selector = [0u8; 4];
+        relay_message_selector.copy_from_slice(&keccak256("relayMessage(uint256,address,address,uint256,uint256,bytes)")[..4]);
+
+        Ok(Self { adapter, pg_pool, chain_id, specs, messenger_events, relay_message_selector })
+    }
+
+    fn decode(event: &Event, log: &Log) -> Option<HashMap<String, Token>> {
+        let decoded = event.parse_log(RawLog { topics: log.topics.clone(), data: log.data.to_vec() }).ok()?;
+        Some(decoded.params.into_iter().map(|param| (param.name, param.value)).collect())
+    }
+
+    fn messenger_event<'a>(&'a self, log: &Log, name: &str) -> Option<(&'a Event, HashMap<String, Token>)> {
+        let event = self.messenger_events.get(log.topics.first()?).filter(|event| event.name == name)?;
+        Some((event, Self::decode(event, log)?))
+    }
+
+    /// The versioned CrossDomainMessenger hash of the message the bridge event at `log_index` sent
+    /// or relayed. The messenger logs it right after the bridge event in the same transaction.
+    fn op_message_hash(&self, tx_logs: &[&Log], log_index: u64, direction: Direction) -> Option<String> {
+        let mut later = tx_logs.iter().filter(|log| log.log_index.map_or(false, |index| index.as_u64() > log_index));
+        match direction {
+            Direction::Inbound => later
+                .filter_map(|log| self.messenger_event(log, "RelayedMessage"))
+                .find_map(|(_, params)| params.get("msgHash").map(format_token)),
+            Direction::Outbound => {
+                let (sent, messenger) = later.find_map(|log| self.messenger_event(log, "SentMessage").map(|(_, params)| (params, log.address)))?;
+                let value = later
+                    .filter(|log| log.address == messenger)
+                    .find_map(|log| self.messenger_event(log, "SentMessageExtension1"))
+                    .and_then(|(_, params)| params.get("value").cloned())
+                    .unwrap_or(Token::Uint(0.into()));
+                let mut call = self.relay_message_selector.to_vec();
+                call.extend(encode(&[
+                    sent.get("messageNonce")?.clone(),
+                    sent.get("sender")?.clone(),
+                    sent.get("target")?.clone(),
+                    value,
+                    sent.get("gasLimit")?.clone(),
+                    sent.get("message")?.clone(),
+                ]));
+                Some(format!("{:?}", H256::from(keccak256(call))))
+            }
+        }
+    }
+
+    fn transfers(&self, logs: &[Log]) -> Vec<BridgeTransfer> {
+        let mut by_tx: HashMap<H256, Vec<&Log>> = HashMap::new();
+        for log in logs {
+            if let Some(tx_hash) = log.transaction_hash {
+                by_tx.entry(tx_hash).or_default().push(log);
+            }
+        }
+
+        let mut transfers = Vec::new();
+        for log in logs {
+            let (Some(topic), Some(tx_hash), Some(log_index)) = (log.topics.first(), log.transaction_hash, log.log_index) else {
+                continue;
+            };
+            let Some(spec) = self.specs.get(&(log.address, *topic)) else {
+                continue;
+            };
+            let Some(params) = Self::decode(&spec.event, log) else {
+                continue;
+            };
+            let get = |name: &Option<String>| name.as_ref().and_then(|name| params.get(name)).map(format_token);
+            let joined = |names: &[&str]| names.iter().map(|name| params.get(*name).map(format_token)).collect::<Option<Vec<_>>>().map(|parts| parts.join(":"));
+
+            let correlation_key = match &spec.key {
+                KeyScheme::OpMessage => self.op_message_hash(&by_tx[&tx_hash], log_index.as_u64(), spec.direction).map(|hash| format!("op:{}", hash)),
+                KeyScheme::ArbitrumDeposit => joined(&["l1Token", "_from", "_to", "_amount"]).map(|key| format!("arbitrum:deposit:{}", key)),
+                KeyScheme::ArbitrumWithdrawal => joined(&["l1Token", "_exitNum"]).map(|key| format!("arbitrum:withdrawal:{}", key)),
+                KeyScheme::Field(name) => params.get(name).map(|value| format!("{}:{}", spec.bridge, format_token(value))),
+            };
+            let remote_chain_id = spec
+                .remote_chain_field
+                .as_ref()
+                .and_then(|name| params.get(name))
+                .and_then(|value| value.clone().into_uint())
+                .map(|value| value.as_u64())
+                .or(spec.remote_chain_id)
+                .map(|id| id as i64);
+            let (source_chain_id, destination_chain_id) = match spec.direction {
+                Direction::Outbound => (Some(self.chain_id as i64), remote_chain_id),
+                Direction::Inbound => (remote_chain_id, Some(self.chain_id as i64)),
+            };
+
+            transfers.push(BridgeTransfer {
+                tx_hash,
+                log_index: log_index.as_u64() as i64,
+                bridge: spec.bridge.clone(),
+                direction: spec.direction,
+                source_chain_id,
+                destination_chain_id,
+                token: get(&spec.token),
+                remote_token: get(&spec.remote_token),
+                from: get(&spec.from),
+                to: get(&spec.to),
+                amount: get(&spec.amount),
+                correlation_key,
+            });
+        }
+        transfers
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for BridgeDecoder {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let logs = self.adapter.get_logs(block_number).await?;
+        let transfers = self.transfers(&logs);
+        if transfers.is_empty() {
+            return Ok(());
+        }
+
+        let column = |f: fn(&BridgeTransfer) -> Option<String>| transfers.iter().map(f).collect::<Vec<_>>();
+        sqlx::query(
+            "INSERT INTO bridge_transfers (chain_name, block_number, block_hash, tx_hash, log_index, bridge, direction, source_chain_id, destination_chain_id, token, remote_token, from_address, to_address, amount, correlation_key)
+            SELECT $1, $2, $3, tx_hash, log_index, bridge, direction, source_chain_id, destination_chain_id, token, remote_token, from_address, to_address, amount::numeric, correlation_key
+            FROM UNNEST($4::text[], $5::bigint[], $6::text[], $7::text[], $8::bigint[], $9::bigint[], $10::text[], $11::text[], $12::text[], $13::text[], $14::text[], $15::text[])
+                AS t(tx_hash, log_index, bridge, direction, source_chain_id, destination_chain_id, token, remote_token, from_address, to_address, amount, correlation_key)
+            ON CONFLICT (chain_name, tx_hash, log_index) DO UPDATE SET
+                block_number = EXCLUDED.block_number,
+                block_hash = EXCLUDED.block_hash,
+                correlation_key = EXCLUDED.correlation_key",
+        )
+        .bind(chain_name)
+        .bind(block_number as i64)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(transfers.iter().map(|t| format!("{:?}", t.tx_hash)).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| t.log_index).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| t.bridge.clone()).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| t.direction.as_str()).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| t.source_chain_id).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| t.destination_chain_id).collect::<Vec<_>>())
+        .bind(column(|t| t.token.clone()))
+        .bind(column(|t| t.remote_token.clone()))
+        .bind(column(|t| t.from.clone()))
+        .bind(column(|t| t.to.clone()))
+        .bind(column(|t| t.amount.clone()))
+        .bind(column(|t| t.correlation_key.clone()))
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        sqlx::query("DELETE FROM bridge_transfers WHERE chain_name = $1 AND block_hash = ANY($2)")
+            .bind(chain_name)
+            .bind(&hashes)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        Ok(())
+    }
+}
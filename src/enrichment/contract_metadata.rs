@@ -0,0 +1,318 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::abi::Abi;
+use ethers::types::{Block, Transaction};
+use log::{error, info, warn};
+use reqwest::{Client, StatusCode};
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::rate_limit::RateLimiter;
+use crate::enrichment::abi::AbiRegistry;
+use crate::streams::consumers::hooks::ConsumerHook;
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MetadataSource {
+    Sourcify,
+    Etherscan,
+}
+
+impl MetadataSource {
+    fn as_str(self) -> &'static str {
+        match self {
+            MetadataSource::Sourcify => "sourcify",
+            MetadataSource::Etherscan => "etherscan",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ContractMetadataConfig {
+    pub enabled: bool,
+    /// Tried in order until one has the contract verified.
+    pub sources: Vec<MetadataSource>,
+    pub sourcify_url: String,
+    /// Etherscan's multichain (v2) endpoint; the chain is picked by `chain_id`.
+    pub etherscan_url: String,
+    pub etherscan_api_key: Option<String>, // env var holding the API key
+    pub requests_per_second: f64,
+    /// How often pending contracts are looked up. Contracts are usually verified a while after
+    /// deployment, so unverified ones are retried every interval until `max_attempts`.
+    pub interval_secs: u64,
+    pub max_attempts: i32,
+    pub batch_size: i64,
+}
+
+impl Default for ContractMetadataConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sources: vec![MetadataSource::Sourcify],
+            sourcify_url: "https://sourcify.dev/server".to_string(),
+            etherscan_url: "https://api.etherscan.io/v2/api".to_string(),
+            etherscan_api_key: None,
+            requests_per_second: 2.0,
+            interval_secs: 3600,
+            max_attempts: 24,
+            batch_size: 100,
+        }
+    }
+}
+
+/// Verified metadata of one contract.
+#[derive(Debug)]
+struct ContractMetadata {
+    source: MetadataSource,
+    name: Option<String>,
+    compiler_version: Option<String>,
+    abi: Option<Value>,
+}
+
+/// Records contracts created in committed blocks in `contract_metadata`, for
+/// `ContractMetadataFetcher` to look up.
+pub struct ContractCreationTracker {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+}
+
+impl ContractCreationTracker {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>) -> Self {
+        Self { adapter, pg_pool }
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for ContractCreationTracker {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        // Only creation transactions can deploy from the top level; receipts say where to.
+        if !block.transactions.iter().any(|tx| tx.to.is_none()) {
+            return Ok(());
+        }
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let receipts = self.adapter.get_block_receipts(block_number).await?;
+        let addresses: Vec<String> = receipts
+            .iter()
+            .filter_map(|receipt| receipt.contract_address)
+            .map(|address| format!("{:?}", address))
+            .collect();
+        if addresses.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            "INSERT INTO contract_metadata (chain_name, address, created_block)
+            SELECT $1, address, $2 FROM UNNEST($3::text[]) AS c(address)
+            ON CONFLICT (chain_name, address) DO NOTHING",
+        )
+        .bind(chain_name)
+        .bind(block_number as i64)
+        .bind(&addresses)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+}
+
+/// Background task that fetches verified source metadata for a chain's pending contracts and
+/// adds their ABIs to the registry.
+pub struct ContractMetadataFetcher {
+    pg_pool: Arc<PgPool>,
+    chain_name: String,
+    chain_id: u64,
+    abis: Arc<AbiRegistry>,
+    client: Client,
+    limiter: RateLimiter,
+    sources: Vec<MetadataSource>,
+    sourcify_url: String,
+    etherscan_url: String,
+    etherscan_api_key: Option<String>,
+    interval: Duration,
+    max_attempts: i32,
+    batch_size: i64,
+}
+
+impl ContractMetadataFetcher {
+    pub fn new(pg_pool: Arc<PgPool>, chain_name: &str, chain_id: u64, abis: Arc<AbiRegistry>, config: &ContractMetadataConfig) -> Self {
+        Self {
+            pg_pool,
+            chain_name: chain_name.to_string(),
+            chain_id,
+            abis,
+            client: Client::new(),
+            limiter: RateLimiter::new(config.requests_per_second),
+            sources: config.sources.clone(),
+            sourcify_url: config.sourcify_url.trim_end_matches('/').to_string(),
+            etherscan_url: config.etherscan_url.clone(),
+            etherscan_api_key: config.etherscan_api_key.clone(),
+            interval: Duration::from_secs(config.interval_secs),
+            max_attempts: config.max_attempts,
+            batch_size: config.batch_size.max(1),
+        }
+    }
+
+    /// Loads ABIs fetched by earlier runs, then looks up pending contracts every interval. Runs forever.
+    pub async fn run(&self) -> Result<()> {
+        self.load_known_abis().await?;
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.fetch_pending().await {
+                error!("Contract metadata lookup for {} failed: {}", self.chain_name, e);
+            }
+        }
+    }
+
+    async fn load_known_abis(&self) -> Result<()> {
+        let rows = sqlx::query("SELECT abi FROM contract_metadata WHERE chain_name = $1 AND abi IS NOT NULL")
+            .bind(&self.chain_name)
+            .fetch_all(self.pg_pool.as_ref())
+            .await?;
+        for row in &rows {
+            if let Ok(abi) = serde_json::from_value::<Abi>(row.try_get("abi")?) {
+                self.abis.add_abi(&abi);
+            }
+        }
+        info!("Loaded {} contract ABIs for {}", rows.len(), self.chain_name);
+        Ok(())
+    }
+
+    async fn fetch_pending(&self) -> Result<()> {
+        let rows = sqlx::query(
+            "SELECT address FROM contract_metadata
+            WHERE chain_name = $1 AND NOT verified AND attempts < $2
+                AND (checked_at IS NULL OR checked_at < NOW() - make_interval(secs => $3))
+            ORDER BY created_block DESC
+            LIMIT $4",
+        )
+        .bind(&self.chain_name)
+        .bind(self.max_attempts)
+        .bind(self.interval.as_secs_f64())
+        .bind(self.batch_size)
+        .fetch_all(self.pg_pool.as_ref())
+        .await?;
+
+        for row in rows {
+            let address: String = row.try_get("address")?;
+            let metadata = self.lookup(&address).await;
+            match &metadata {
+                Ok(Some(metadata)) => {
+                    sqlx::query(
+                        "UPDATE contract_metadata SET verified = TRUE, source = $3, name = $4, compiler_version = $5, abi = $6,
+                            attempts = attempts + 1, checked_at = NOW()
+                        WHERE chain_name = $1 AND address = $2",
+                    )
+                    .bind(&self.chain_name)
+                    .bind(&address)
+                    .bind(metadata.source.as_str())
+                    .bind(&metadata.name)
+                    .bind(&metadata.compiler_version)
+                    .bind(&metadata.abi)
+                    .execute(self.pg_pool.as_ref())
+                    .await?;
+                    if let Some(abi) = metadata.abi.clone().and_then(|abi| serde_json::from_value::<Abi>(abi).ok()) {
+                        self.abis.add_abi(&abi);
+                    }
+                }
+                Ok(None) | Err(_) => {
+                    if let Err(e) = metadata {
+                        error!("Failed to look up {} contract {}: {}", self.chain_name, address, e);
+                    }
+                    sqlx::query(
+                        "UPDATE contract_metadata SET attempts = attempts + 1, checked_at = NOW()
+                        WHERE chain_name = $1 AND address = $2",
+                    )
+                    .bind(&self.chain_name)
+                    .bind(&address)
+                    .execute(self.pg_pool.as_ref())
+                    .await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Metadata from the first source that has the contract verified. A source that fails is
+    /// logged and the next one tried; if none has it, the last failure is returned, if any.
+    async fn lookup(&self, address: &str) -> Result<Option<ContractMetadata>> {
+        let mut failure = None;
+        for source in &self.sources {
+            self.limiter.acquire().await;
+            let metadata = match source {
+                MetadataSource::Sourcify => self.lookup_sourcify(address).await,
+                MetadataSource::Etherscan => self.lookup_etherscan(address).await,
+            };
+            match metadata {
+                Ok(Some(metadata)) => return Ok(Some(metadata)),
+                Ok(None) => {}
+                Err(e) => {
+                    warn!(
+                        "Failed to look up {} contract {} on {}, trying the next source: {}",
+                        self.chain_name, address, source.as_str(), e
+                    );
+                    failure = Some(e);
+                }
+            }
+        }
+        failure.map_or(Ok(None), Err)
+    }
+
+    async fn lookup_sourcify(&self, address: &str) -> Result<Option<ContractMetadata>> {
+        let response = self.client
+            .get(format!("{}/v2/contract/{}/{}", self.sourcify_url, self.chain_id, address))
+            .query(&[("fields", "abi,compilation")])
+            .send()
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let body: Value = response.error_for_status()?.json().await?;
+        let compilation = &body["compilation"];
+        Ok(Some(ContractMetadata {
+            source: MetadataSource::Sourcify,
+            name: compilation["name"].as_str().map(str::to_string),
+            compiler_version: compilation["compilerVersion"].as_str().map(str::to_string),
+            abi: Some(body["abi"].clone()).filter(|abi| abi.is_array()),
+        }))
+    }
+
+    async fn lookup_etherscan(&self, address: &str) -> Result<Option<ContractMetadata>> {
+        let api_key = self.etherscan_api_key.as_deref()
+            .ok_or_else(|| anyhow!("contract_metadata.etherscan_api_key is not set"))?;
+        let body: Value = self.client
+            .get(&self.etherscan_url)
+            .query(&[
+                ("chainid", self.chain_id.to_string().as_str()),
+                ("module", "contract"),
+                ("action", "getsourcecode"),
+                ("address", address),
+                ("apikey", api_key),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if body.get("status").and_then(Value::as_str) == Some("0") {
+            return Err(anyhow!("Etherscan getsourcecode failed: {}", body["result"]));
+        }
+
+        let result = &body["result"][0];
+        // Unverified contracts come back with an empty name and a placeholder ABI string.
+        let name = result["ContractName"].as_str().filter(|name| !name.is_empty());
+        if name.is_none() {
+            return Ok(None);
+        }
+        Ok(Some(ContractMetadata {
+            source: MetadataSource::Etherscan,
+            name: name.map(str::to_string),
+            compiler_version: result["CompilerVersion"].as_str().map(str::to_string),
+            abi: result["ABI"].as_str().and_then(|abi| serde_json::from_str(abi).ok()),
+        }))
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction, TransactionReceipt, H256, U256};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::streams::consumers::hooks::ConsumerHook;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FeesConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// How a chain charges for transactions, set per chain as `fee_model`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeeModelKind {
+    /// Ethereum and EVM chains without an L1 component: base fee burned, priority fee to the
+    /// block producer.
+    #[default]
+    Eip1559,
+    /// Optimism, Base and other OP Stack chains, which add an L1 data fee on top of execution.
+    OpStack,
+    /// Arbitrum, which charges L1 calldata as extra gas at the L2 price.
+    Arbitrum,
+}
+
+impl FeeModelKind {
+    pub fn model(self) -> Arc<dyn FeeModel> {
+        match self {
+            FeeModelKind::Eip1559 => Arc::new(Eip1559FeeModel),
+            FeeModelKind::OpStack => Arc::new(OpStackFeeModel),
+            FeeModelKind::Arbitrum => Arc::new(ArbitrumFeeModel),
+        }
+    }
+}
+
+/// A transaction's fee split into components, in the chain's native unit (wei). `total` is
+/// always the sum of the others, so fees compare across chains whatever their model.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeBreakdown {
+    /// Burned by the protocol, e.g. the EIP-1559 base fee.
+    pub base: U256,
+    /// Paid to the block producer or sequencer for execution.
+    pub priority: U256,
+    /// Paid for posting the transaction's data to L1.
+    pub l1_data: U256,
+    /// Burned for the blobs of an EIP-4844 transaction.
+    pub blob: U256,
+    pub total: U256,
+}
+
+impl FeeBreakdown {
+    pub fn new(base: U256, priority: U256, l1_data: U256, blob: U256) -> Self {
+        let total = base.saturating_add(priority).saturating_add(l1_data).saturating_add(blob);
+        Self { base, priority, l1_data, blob, total }
+    }
+}
+
+/// Computes what a transaction paid from its block and receipt.
+pub trait FeeModel: Send + Sync {
+    /// Recorded with the fee columns, e.g. `eip1559`.
+    fn name(&self) -> &'static str;
+
+    fn fee(&self, block: &Block<Transaction>, transaction: &Transaction, receipt: &TransactionReceipt) -> FeeBreakdown;
+}
+
+/// Price per gas actually paid, from the receipt, or the transaction's own price on nodes that
+/// predate `effectiveGasPrice`.
+fn effective_gas_price(transaction: &Transaction, receipt: &TransactionReceipt) -> U256 {
+    receipt.effective_gas_price.or(transaction.gas_price).unwrap_or_default()
+}
+
+/// Splits `gas` at `price` into the burned base fee and the rest.
+fn execution_fee(block: &Block<Transaction>, gas: U256, price: U256) -> (U256, U256) {
+    let base_fee = block.base_fee_per_gas.unwrap_or_default().min(price);
+    (gas.saturating_mul(base_fee), gas.saturating_mul(price - base_fee))
+}
+
+/// A receipt field chains add on top of Ethereum's, e.g. `l1Fee`.
+fn extra_field(receipt: &TransactionReceipt, key: &str) -> U256 {
+    receipt.other.get_deserialized::<U256>(key).and_then(|value| value.ok()).unwrap_or_default()
+}
+
+/// `blobGasUsed * blobGasPrice` from the receipt, zero for transactions without blobs.
+fn blob_fee(receipt: &TransactionReceipt) -> U256 {
+    extra_field(receipt, "blobGasUsed").saturating_mul(extra_field(receipt, "blobGasPrice"))
+}
+
+pub struct Eip1559FeeModel;
+
+impl FeeModel for Eip1559FeeModel {
+    fn name(&self) -> &'static str {
+        "eip1559"
+    }
+
+    fn fee(&self, block: &Block<Transaction>, transaction: &Transaction, receipt: &TransactionReceipt) -> FeeBreakdown {
+        let (base, priority) = execution_fee(block, receipt.gas_used.unwrap_or_default(), effective_gas_price(transaction, receipt));
+        FeeBreakdown::new(base, priority, U256::zero(), blob_fee(receipt))
+    }
+}
+
+pub struct OpStackFeeModel;
+
+impl FeeModel for OpStackFeeModel {
+    fn name(&self) -> &'static str {
+        "op_stack"
+    }
+
+    fn fee(&self, block: &Block<Transaction>, transaction: &Transaction, receipt: &TransactionReceipt) -> FeeBreakdown {
+        let (base, priority) = execution_fee(block, receipt.gas_used.unwrap_or_default(), effective_gas_price(transaction, receipt));
+        FeeBreakdown::new(base, priority, extra_field(receipt, "l1Fee"), U256::zero())
+    }
+}
+
+pub struct ArbitrumFeeModel;
+
+impl FeeModel for ArbitrumFeeModel {
+    fn name(&self) -> &'static str {
+        "arbitrum"
+    }
+
+    /// `gasUsed` includes `gasUsedForL1`, the L1 calldata cost converted to L2 gas.
+    fn fee(&self, block: &Block<Transaction>, transaction: &Transaction, receipt: &TransactionReceipt) -> FeeBreakdown {
+        let price = effective_gas_price(transaction, receipt);
+        let gas_used = receipt.gas_used.unwrap_or_default();
+        let l1_gas = extra_field(receipt, "gasUsedForL1").min(gas_used);
+        let (base, priority) = execution_fee(block, gas_used - l1_gas, price);
+        FeeBreakdown::new(base, priority, l1_gas.saturating_mul(price), U256::zero())
+    }
+}
+
+/// Sets the normalized fee columns of each committed block's `transactions` rows from its
+/// receipts, using the chain's fee model.
+pub struct FeeRecorder {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    model: Arc<dyn FeeModel>,
+}
+
+impl FeeRecorder {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, model: Arc<dyn FeeModel>) -> Self {
+        Self { adapter, pg_pool, model }
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for FeeRecorder {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        if block.transactions.is_empty() {
+            return Ok(());
+        }
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let receipts = self.adapter.get_block_receipts(block_number).await?;
+        let transactions: HashMap<H256, &Transaction> = block.transactions.iter().map(|tx| (tx.hash, tx)).collect();
+
+        let mut hashes = Vec::with_capacity(receipts.len());
+        let mut base_fees = Vec::with_capacity(receipts.len());
+        let mut priority_fees = Vec::with_capacity(receipts.len());
+        let mut l1_data_fees = Vec::with_capacity(receipts.len());
+        let mut blob_fees = Vec::with_capacity(receipts.len());
+        let mut total_fees = Vec::with_capacity(receipts.len());
+        for receipt in &receipts {
+            let Some(transaction) = transactions.get(&receipt.transaction_hash) else {
+                continue;
+            };
+            let fee = self.model.fee(block, transaction, receipt);
+            hashes.push(format!("{:?}", receipt.transaction_hash));
+            base_fees.push(fee.base.to_string());
+            priority_fees.push(fee.priority.to_string());
+            l1_data_fees.push(fee.l1_data.to_string());
+            blob_fees.push(fee.blob.to_string());
+            total_fees.push(fee.total.to_string());
+        }
+
+        sqlx::query(
+            "UPDATE transactions t SET fee_model = $3, fee_base = f.base::numeric, fee_priority = f.priority::numeric,
+                fee_l1_data = f.l1_data::numeric, fee_blob = f.blob::numeric, fee_total = f.total::numeric
+            FROM UNNEST($4::text[], $5::text[], $6::text[], $7::text[], $8::text[], $9::text[])
+                AS f(tx_hash, base, priority, l1_data, blob, total)
+            WHERE t.chain_name = $1 AND t.block_number = $2 AND t.tx_hash = f.tx_hash AND t.canonical",
+        )
+        .bind(chain_name)
+        .bind(block_number as i64)
+        .bind(self.model.name())
+        .bind(&hashes)
+        .bind(&base_fees)
+        .bind(&priority_fees)
+        .bind(&l1_data_fees)
+        .bind(&blob_fees)
+        .bind(&total_fees)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use futures_util::future::try_join_all;
+
+use crate::blockchain::adapters::{logs_of_block, BlockchainAdapter};
+use crate::enrichment::abi::AbiRegistry;
+use crate::storage::overflow::OverflowStore;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+/// Blocks per `logs` partition. Partitions are shared by every chain, and existing ones can't be
+/// re-cut, so this never changes.
+const PARTITION_BLOCKS: u64 = 1_000_000;
+
+#[derive(Debug, Deserialize)]
+pub struct LogsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Also decode logs against the registered ABIs into `event_name` and `params`.
+    #[serde(default = "default_decode")]
+    pub decode: bool,
+}
+
+fn default_decode() -> bool {
+    true
+}
+
+impl Default for LogsConfig {
+    fn default() -> Self {
+        Self { enabled: false, decode: default_decode() }
+    }
+}
+
+/// Bulk-inserts every committed block's logs into `logs`, raw (address, topics, data) and, for
+/// events of registered ABIs, decoded. The table is partitioned by block range; the partition a
+/// block falls into is created on first use.
+pub struct LogWriter {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    abis: Option<Arc<AbiRegistry>>,
+    overflow: Option<Arc<OverflowStore>>,
+    partitions: Mutex<HashSet<u64>>,
+}
+
+impl LogWriter {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, abis: Arc<AbiRegistry>, config: &LogsConfig) -> Self {
+        Self {
+            adapter,
+            pg_pool,
+            abis: config.decode.then_some(abis),
+            overflow: None,
+            partitions: Mutex::new(HashSet::new()),
+        }
+    }
+
+    /// Moves oversized log data to `overflow` and stores its URI in `data_uri` instead.
+    pub fn with_overflow(mut self, overflow: Arc<OverflowStore>) -> Self {
+        self.overflow = Some(overflow);
+        self
+    }
+
+    async fn ensure_partition(&self, block_number: u64) -> Result<()> {
+        let partition = block_number / PARTITION_BLOCKS;
+        if self.partitions.lock().unwrap_or_else(|e| e.into_inner()).contains(&partition) {
+            return Ok(());
+        }
+        // DDL takes no bind parameters; the name and bounds are numbers.
+        let result = sqlx::query(&format!(
+            "CREATE TABLE IF NOT EXISTS logs_p{} PARTITION OF logs FOR VALUES FROM ({}) TO ({})",
+            partition,
+            partition * PARTITION_BLOCKS,
+            (partition + 1) * PARTITION_BLOCKS
+        ))
+        .execute(self.pg_pool.as_ref())
+        .await;
+        match result {
+            Ok(_) => {}
+            // Another consumer created it first.
+            Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("42P07") => {}
+            Err(e) => return Err(e.into()),
+        }
+        self.partitions.lock().unwrap_or_else(|e| e.into_inner()).insert(partition);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for LogWriter {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        // Fetched by height, so drop the logs of another block there, e.g. after a reorg.
+        let logs = logs_of_block(self.adapter.get_logs(block_number).await?, block.hash);
+        if logs.is_empty() {
+            return Ok(());
+        }
+        self.ensure_partition(block_number).await?;
+        let data_uris = match &self.overflow {
+            Some(overflow) => {
+                try_join_all(logs.iter().map(|log| {
+                    let key = format!("{:?}-{}", log.transaction_hash.unwrap_or_default(), log.log_index.unwrap_or_default());
+                    async move { overflow.offload("log_data", &key, &log.data).await }
+                }))
+                .await?
+            }
+            None => vec![None; logs.len()],
+        };
+
+        let topic = |index: usize| -> Vec<Option<String>> {
+            logs.iter().map(|log| log.topics.get(index).map(|topic| format!("{:?}", topic))).collect()
+        };
+        let decoded: Vec<_> = logs
+            .iter()
+            .map(|log| self.abis.as_ref().and_then(|abis| abis.decode_log(log)))
+            .collect();
+
+        sqlx::query(
+            "INSERT INTO logs (chain_name, block_number, block_hash, tx_hash, tx_index, log_index, address, topic0, topic1, topic2, topic3, data, data_uri, event_name, params)
+            SELECT $1, $2, $3, tx_hash, tx_index, log_index, address, topic0, topic1, topic2, topic3, data, data_uri, event_name, params::jsonb
+            FROM UNNEST($4::text[], $5::bigint[], $6::bigint[], $7::text[], $8::text[], $9::text[], $10::text[], $11::text[], $12::text[], $13::text[], $14::text[], $15::text[])
+                AS l(tx_hash, tx_index, log_index, address, topic0, topic1, topic2, topic3, data, data_uri, event_name, params)
+            ON CONFLICT (chain_name, block_number, block_hash, log_index) DO NOTHING",
+        )
+        .bind(chain_name)
+        .bind(block_number as i64)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(logs.iter().map(|log| format!("{:?}", log.transaction_hash.unwrap_or_default())).collect::<Vec<_>>())
+        .bind(logs.iter().map(|log| log.transaction_index.unwrap_or_default().as_u64() as i64).collect::<Vec<_>>())
+        .bind(logs.iter().map(|log| log.log_index.unwrap_or_default().as_u64() as i64).collect::<Vec<_>>())
+        .bind(logs.iter().map(|log| format!("{:?}", log.address)).collect::<Vec<_>>())
+        .bind(topic(0))
+        .bind(topic(1))
+        .bind(topic(2))
+        .bind(topic(3))
+        .bind(logs.iter().zip(&data_uris).map(|(log, uri)| uri.is_none().then(|| log.data.to_string())).collect::<Vec<_>>())
+        .bind(&data_uris)
+        .bind(decoded.iter().map(|event| event.as_ref().map(|event| event.name.clone())).collect::<Vec<_>>())
+        .bind(decoded.iter().map(|event| event.as_ref().map(|event| event.params_json().to_string())).collect::<Vec<_>>())
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        let numbers: Vec<i64> = orphaned.iter().map(|block| block.block_number).collect();
+        // The block numbers let Postgres skip the partitions the blocks aren't in.
+        sqlx::query("DELETE FROM logs WHERE chain_name = $1 AND block_number = ANY($2) AND block_hash = ANY($3)")
+            .bind(chain_name)
+            .bind(&numbers)
+            .bind(&hashes)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        Ok(())
+    }
+}
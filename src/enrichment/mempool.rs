@@ -0,0 +1,235 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use futures_util::StreamExt;
+use log::{error, warn};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::metrics;
+use crate::streams::sampling::Sampler;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MempoolConfig {
+    /// Pending transactions not mined or replaced within this many seconds are marked dropped.
+    pub ttl_secs: u64,
+    pub flush_interval_ms: u64,
+    pub batch_size: usize,
+}
+
+impl Default for MempoolConfig {
+    fn default() -> Self {
+        Self { ttl_secs: 10_800, flush_interval_ms: 1000, batch_size: 1000 }
+    }
+}
+
+/// The fee a transaction bids, which decides whether it may replace another with its nonce.
+fn fee_bid(transaction: &Transaction) -> String {
+    transaction.max_fee_per_gas.or(transaction.gas_price).unwrap_or_default().to_string()
+}
+
+/// Tracks a chain's mempool transactions in `pending_transactions` until they resolve:
+///
+/// - `mined` once a committed block includes them, linking that block. Orphaned blocks put them
+///   back to `pending`.
+/// - `replaced` when another transaction with the same sender and nonce bids a higher fee or gets
+///   mined first, linking the replacement.
+/// - `dropped` when still pending after `ttl_secs`.
+///
+/// `run` feeds it the mempool stream; as a `ConsumerHook` it sees the block stream.
+pub struct PendingTransactionTracker {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    chain_name: String,
+    ttl: Duration,
+    flush_interval: Duration,
+    batch_size: usize,
+    sampler: Option<Sampler>,
+}
+
+impl PendingTransactionTracker {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, chain_name: &str, config: &MempoolConfig) -> Self {
+        Self {
+            adapter,
+            pg_pool,
+            chain_name: chain_name.to_string(),
+            ttl: Duration::from_secs(config.ttl_secs),
+            flush_interval: Duration::from_millis(config.flush_interval_ms.max(1)),
+            batch_size: config.batch_size.max(1),
+            sampler: None,
+        }
+    }
+
+    /// Records only the pending transactions `sampler` keeps, with its rate in `sample_rate`.
+    pub fn with_sampling(mut self, sampler: Sampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Records pending transactions in batches and expires stale ones, resubscribing whenever the
+    /// stream ends. Runs forever.
+    pub async fn run(&self) -> Result<()> {
+        let mut flush_ticker = tokio::time::interval(self.flush_interval);
+        let mut sweep_ticker = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            let mut stream = self.adapter.subscribe_pending_transactions();
+            let mut buffer: Vec<Transaction> = Vec::new();
+            loop {
+                tokio::select! {
+                    transaction = stream.next() => match transaction {
+                        Some(Ok(transaction)) => {
+                            if self.sampler.is_some_and(|sampler| !sampler.keeps_hash(&transaction.hash)) {
+                                metrics::increment_counter("mempool_sampled_out_transactions_total", &[("chain", &self.chain_name)], 1);
+                                continue;
+                            }
+                            buffer.push(transaction);
+                            if buffer.len() < self.batch_size {
+                                continue;
+                            }
+                        }
+                        Some(Err(e)) => {
+                            warn!("Mempool subscription for {} failed: {}", self.chain_name, e);
+                            break;
+                        }
+                        None => break,
+                    },
+                    _ = flush_ticker.tick() => {}
+                    _ = sweep_ticker.tick() => {
+                        if let Err(e) = self.expire().await {
+                            error!("Failed to expire pending transactions for {}: {}", self.chain_name, e);
+                        }
+                        continue;
+                    }
+                }
+                if let Err(e) = self.record(std::mem::take(&mut buffer)).await {
+                    error!("Failed to record pending transactions for {}: {}", self.chain_name, e);
+                }
+            }
+            if let Err(e) = self.record(buffer).await {
+                error!("Failed to record pending transactions for {}: {}", self.chain_name, e);
+            }
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+
+    async fn record(&self, transactions: Vec<Transaction>) -> Result<()> {
+        if transactions.is_empty() {
+            return Ok(());
+        }
+        let hashes: Vec<String> = transactions.iter().map(|tx| format!("{:?}", tx.hash)).collect();
+        let senders: Vec<String> = transactions.iter().map(|tx| format!("{:?}", tx.from)).collect();
+        let nonces: Vec<i64> = transactions.iter().map(|tx| tx.nonce.as_u64() as i64).collect();
+        let fees: Vec<String> = transactions.iter().map(fee_bid).collect();
+        let mut db_tx = self.pg_pool.begin().await?;
+
+        let inserted = sqlx::query(
+            "INSERT INTO pending_transactions (chain_name, tx_hash, from_address, nonce, to_address, value, fee_bid, status, first_seen, sample_rate)
+            SELECT $1, tx_hash, from_address, nonce, to_address, value::numeric, fee_bid::numeric, 'pending', NOW(), $8
+            FROM UNNEST($2::text[], $3::text[], $4::bigint[], $5::text[], $6::text[], $7::text[])
+                AS t(tx_hash, from_address, nonce, to_address, value, fee_bid)
+            ON CONFLICT (chain_name, tx_hash) DO NOTHING",
+        )
+        .bind(&self.chain_name)
+        .bind(&hashes)
+        .bind(&senders)
+        .bind(&nonces)
+        .bind(transactions.iter().map(|tx| tx.to.map(|to| format!("{:?}", to))).collect::<Vec<_>>())
+        .bind(transactions.iter().map(|tx| tx.value.to_string()).collect::<Vec<_>>())
+        .bind(&fees)
+        .bind(self.sampler.map(|sampler| sampler.rate()))
+        .execute(&mut db_tx)
+        .await?;
+
+        let replaced = sqlx::query(
+            "UPDATE pending_transactions p SET status = 'replaced', replaced_by = n.tx_hash, resolved_at = NOW()
+            FROM UNNEST($2::text[], $3::text[], $4::bigint[], $5::text[]) AS n(tx_hash, from_address, nonce, fee_bid)
+            WHERE p.chain_name = $1 AND p.status = 'pending' AND p.from_address = n.from_address AND p.nonce = n.nonce
+                AND p.tx_hash <> n.tx_hash AND p.fee_bid < n.fee_bid::numeric",
+        )
+        .bind(&self.chain_name)
+        .bind(&hashes)
+        .bind(&senders)
+        .bind(&nonces)
+        .bind(&fees)
+        .execute(&mut db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+        metrics::increment_counter("mempool_transactions_total", &[("chain", &self.chain_name), ("status", "pending")], inserted.rows_affected());
+        metrics::increment_counter("mempool_transactions_total", &[("chain", &self.chain_name), ("status", "replaced")], replaced.rows_affected());
+        Ok(())
+    }
+
+    async fn expire(&self) -> Result<()> {
+        let dropped = sqlx::query(
+            "UPDATE pending_transactions SET status = 'dropped', resolved_at = NOW()
+            WHERE chain_name = $1 AND status = 'pending' AND first_seen < NOW() - make_interval(secs => $2)",
+        )
+        .bind(&self.chain_name)
+        .bind(self.ttl.as_secs_f64())
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        metrics::increment_counter("mempool_transactions_total", &[("chain", &self.chain_name), ("status", "dropped")], dropped.rows_affected());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for PendingTransactionTracker {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        if block.transactions.is_empty() {
+            return Ok(());
+        }
+        let hashes: Vec<String> = block.transactions.iter().map(|tx| format!("{:?}", tx.hash)).collect();
+        let mut db_tx = self.pg_pool.begin().await?;
+
+        // Being mined settles it, even if it was thought replaced or dropped.
+        let mined = sqlx::query(
+            "UPDATE pending_transactions SET status = 'mined', block_number = $2, block_hash = $3, replaced_by = NULL, resolved_at = NOW()
+            WHERE chain_name = $1 AND tx_hash = ANY($4) AND status <> 'mined'",
+        )
+        .bind(chain_name)
+        .bind(block.number.unwrap_or_default().as_u64() as i64)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(&hashes)
+        .execute(&mut db_tx)
+        .await?;
+
+        // Whatever else was waiting on a mined nonce can no longer be mined.
+        let replaced = sqlx::query(
+            "UPDATE pending_transactions p SET status = 'replaced', replaced_by = n.tx_hash, resolved_at = NOW()
+            FROM UNNEST($2::text[], $3::text[], $4::bigint[]) AS n(tx_hash, from_address, nonce)
+            WHERE p.chain_name = $1 AND p.status IN ('pending', 'dropped') AND p.from_address = n.from_address
+                AND p.nonce = n.nonce AND p.tx_hash <> n.tx_hash",
+        )
+        .bind(chain_name)
+        .bind(&hashes)
+        .bind(block.transactions.iter().map(|tx| format!("{:?}", tx.from)).collect::<Vec<_>>())
+        .bind(block.transactions.iter().map(|tx| tx.nonce.as_u64() as i64).collect::<Vec<_>>())
+        .execute(&mut db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+        metrics::increment_counter("mempool_transactions_total", &[("chain", chain_name), ("status", "mined")], mined.rows_affected());
+        metrics::increment_counter("mempool_transactions_total", &[("chain", chain_name), ("status", "replaced")], replaced.rows_affected());
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        sqlx::query(
+            "UPDATE pending_transactions SET status = 'pending', block_number = NULL, block_hash = NULL, resolved_at = NULL
+            WHERE chain_name = $1 AND block_hash = ANY($2)",
+        )
+        .bind(chain_name)
+        .bind(&hashes)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Log, Transaction, H256};
+use ethers::utils::keccak256;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::metrics;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+// Builders that sign their blocks' extraData, matched case-insensitively.
+const KNOWN_BUILDER_TAGS: &[&str] = &[
+    "beaverbuild", "titan", "rsync", "flashbots", "builder0x69", "bloxroute", "buildai",
+    "jetbldr", "penguinbuild", "eden", "blocknative", "manifold",
+];
+
+const SWAP_EVENTS: &[&str] = &[
+    // Uniswap V2 and its forks.
+    "Swap(address,uint256,uint256,uint256,uint256,address)",
+    // Uniswap V3 and its forks.
+    "Swap(address,address,int256,int256,uint160,uint128,int24)",
+];
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct MevDetectionConfig {
+    pub sandwiches: bool,
+    pub backruns: bool,
+    pub builder_tags: bool,
+    /// Tags to look for in extraData on top of the built-in ones.
+    pub extra_builder_tags: Vec<String>,
+}
+
+impl Default for MevDetectionConfig {
+    fn default() -> Self {
+        Self { sandwiches: true, backruns: true, builder_tags: true, extra_builder_tags: Vec::new() }
+    }
+}
+
+/// A swap, identified by the pool that emitted it and the transaction that caused it.
+struct Swap {
+    tx_index: usize,
+    pool: Address,
+    /// Who received the output: the second indexed address of both V2 and V3 `Swap` events.
+    recipient: Option<Address>,
+}
+
+struct Observation {
+    kind: &'static str,
+    tx_hashes: Vec<String>,
+    address: Option<String>,
+    details: Value,
+}
+
+/// Flags likely MEV in committed blocks into `mev_observations`:
+///
+/// - `sandwich`: one searcher (same sender, or same bot contract receiving the swaps' output)
+///   swaps in a pool before and after a different searcher's swap in that pool.
+/// - `backrun`: a transaction swapping through two or more pools right after another sender's
+///   swap in one of them, the shape of an arbitrage closing the price gap.
+/// - `builder`: the block's extraData carries a known builder tag.
+///
+/// These are heuristics over swap events, so they flag candidates rather than prove intent.
+pub struct MevDetector {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    sandwiches: bool,
+    backruns: bool,
+    builder_tags: Option<Vec<String>>,
+    swap_topics: HashSet<H256>,
+}
+
+impl MevDetector {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, config: &MevDetectionConfig) -> Self {
+        let builder_tags = config.builder_tags.then(|| {
+            KNOWN_BUILDER_TAGS
+                .iter()
+                .map(|tag| tag.to_string())
+                .chain(config.extra_builder_tags.iter().map(|tag| tag.to_lowercase()))
+                .collect()
+        });
+        Self {
+            adapter,
+            pg_pool,
+            sandwiches: config.sandwiches,
+            backruns: config.backruns,
+            builder_tags,
+            swap_topics: SWAP_EVENTS.iter().map(|event| H256::from(keccak256(event.as_bytes()))).collect(),
+        }
+    }
+
+    fn swaps(&self, block: &Block<Transaction>, logs: &[Log]) -> Vec<Swap> {
+        let positions: HashMap<H256, usize> = block.transactions.iter().enumerate().map(|(i, tx)| (tx.hash, i)).collect();
+        logs.iter()
+            .filter(|log| log.topics.first().map_or(false, |topic| self.swap_topics.contains(topic)))
+            .filter_map(|log| {
+                Some(Swap {
+                    tx_index: *positions.get(&log.transaction_hash?)?,
+                    pool: log.address,
+                    recipient: log.topics.get(2).map(|topic| Address::from(*topic)),
+                })
+            })
+            .collect()
+    }
+
+    fn find_sandwiches(block: &Block<Transaction>, swaps: &[Swap]) -> Vec<Observation> {
+        let txs = &block.transactions;
+        // A bot contract keeps what it swaps, unlike a router, which many unrelated senders call
+        // and which passes the output on to them.
+        let bots: HashMap<usize, Address> = swaps
+            .iter()
+            .filter_map(|swap| Some((swap.tx_index, txs[swap.tx_index].to.filter(|to| swap.recipient == Some(*to))?)))
+            .collect();
+        let same_searcher = |a: usize, b: usize| {
+            txs[a].from == txs[b].from || bots.get(&a).map_or(false, |bot| bots.get(&b) == Some(bot))
+        };
+        let mut by_pool: HashMap<Address, Vec<usize>> = HashMap::new();
+        for swap in swaps {
+            let indexes = by_pool.entry(swap.pool).or_default();
+            if indexes.last() != Some(&swap.tx_index) {
+                indexes.push(swap.tx_index);
+            }
+        }
+
+        let mut observations = Vec::new();
+        for (pool, indexes) in by_pool {
+            for (n, &front) in indexes.iter().enumerate() {
+                // The nearest later swap by the same searcher, with someone else's swaps in between.
+                let Some(back_n) = indexes[n + 1..].iter().position(|&i| same_searcher(front, i)).map(|p| n + 1 + p) else {
+                    continue;
+                };
+                let victims: Vec<usize> = indexes[n + 1..back_n]
+                    .iter()
+                    .copied()
+                    .filter(|&i| !same_searcher(front, i))
+                    .collect();
+                if victims.is_empty() {
+                    continue;
+                }
+                let back = indexes[back_n];
+                observations.push(Observation {
+                    kind: "sandwich",
+                    tx_hashes: vec![format!("{:?}", txs[front].hash), format!("{:?}", txs[back].hash)],
+                    address: Some(format!("{:?}", txs[front].from)),
+                    details: json!({
+                        "pool": format!("{:?}", pool),
+                        "victims": victims.iter().map(|&i| format!("{:?}", txs[i].hash)).collect::<Vec<_>>(),
+                    }),
+                });
+            }
+        }
+        observations
+    }
+
+    fn find_backruns(block: &Block<Transaction>, swaps: &[Swap]) -> Vec<Observation> {
+        let txs = &block.transactions;
+        let mut pools_by_tx: HashMap<usize, HashSet<Address>> = HashMap::new();
+        for swap in swaps {
+            pools_by_tx.entry(swap.tx_index).or_default().insert(swap.pool);
+        }
+
+        let mut observations = Vec::new();
+        for (&index, pools) in &pools_by_tx {
+            if index == 0 || pools.len() < 2 {
+                continue;
+            }
+            let Some(target_pools) = pools_by_tx.get(&(index - 1)) else {
+                continue;
+            };
+            let (target, backrun) = (&txs[index - 1], &txs[index]);
+            if target.from == backrun.from {
+                continue;
+            }
+            let Some(pool) = target_pools.intersection(pools).next() else {
+                continue;
+            };
+            observations.push(Observation {
+                kind: "backrun",
+                tx_hashes: vec![format!("{:?}", backrun.hash)],
+                address: Some(format!("{:?}", backrun.from)),
+                details: json!({
+                    "pool": format!("{:?}", pool),
+                    "target": format!("{:?}", target.hash),
+                    "pools": pools.len(),
+                }),
+            });
+        }
+        observations
+    }
+
+    fn find_builder_tag(&self, block: &Block<Transaction>) -> Option<Observation> {
+        let tags = self.builder_tags.as_ref()?;
+        let extra_data = String::from_utf8_lossy(&block.extra_data).to_lowercase();
+        let tag = tags.iter().find(|tag| extra_data.contains(tag.as_str()))?;
+        Some(Observation {
+            kind: "builder",
+            tx_hashes: Vec::new(),
+            address: block.author.map(|author| format!("{:?}", author)),
+            details: json!({
+                "tag": tag,
+                "extra_data": String::from_utf8_lossy(&block.extra_data).trim_matches(char::from(0)).to_string(),
+            }),
+        })
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for MevDetector {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let mut observations: Vec<Observation> = self.find_builder_tag(block).into_iter().collect();
+        if (self.sandwiches || self.backruns) && block.transactions.len() > 1 {
+            let block_number = block.number.unwrap_or_default().as_u64();
+            let logs = self.adapter.get_logs(block_number).await?;
+            let swaps = self.swaps(block, &logs);
+            if self.sandwiches {
+                observations.extend(Self::find_sandwiches(block, &swaps));
+            }
+            if self.backruns {
+                observations.extend(Self::find_backruns(block, &swaps));
+            }
+        }
+        if observations.is_empty() {
+            return Ok(());
+        }
+
+        let mut db_tx = self.pg_pool.begin().await?;
+        for observation in &observations {
+            sqlx::query(
+                "INSERT INTO mev_observations (chain_name, block_number, block_hash, kind, tx_hashes, address, details)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ON CONFLICT (chain_name, block_hash, kind, tx_hashes, (COALESCE(details ->> 'pool', ''))) DO NOTHING",
+            )
+            .bind(chain_name)
+            .bind(block.number.unwrap_or_default().as_u64() as i64)
+            .bind(format!("{:?}", block.hash.unwrap_or_default()))
+            .bind(observation.kind)
+            .bind(&observation.tx_hashes)
+            .bind(&observation.address)
+            .bind(&observation.details)
+            .execute(&mut db_tx)
+            .await?;
+            metrics::increment_counter("mev_observations_total", &[("chain", chain_name), ("kind", observation.kind)], 1);
+        }
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        sqlx::query("DELETE FROM mev_observations WHERE chain_name = $1 AND block_hash = ANY($2)")
+            .bind(chain_name)
+            .bind(&hashes)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,17 @@
+pub mod abi;
+pub mod asset_transfers;
+pub mod balances;
+pub mod bridges;
+pub mod contract_metadata;
+pub mod fees;
+pub mod logs;
+pub mod mempool;
+pub mod mev;
+pub mod prices;
+pub mod priority_transfers;
+pub mod receipts;
+pub mod safe;
+pub mod selectors;
+pub mod token_balances;
+pub mod transfers;
+pub mod validators;
@@ -0,0 +1,325 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Bytes, Transaction, I256, U256};
+use log::warn;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::Value;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::metrics;
+use crate::streams::consumers::hooks::ConsumerHook;
+
+/// `latestRoundData()` selector of Chainlink aggregators.
+const LATEST_ROUND_DATA_SELECTOR: [u8; 4] = [0xfe, 0xaf, 0x96, 0x8c];
+/// `decimals()` selector.
+const DECIMALS_SELECTOR: [u8; 4] = [0x31, 0x3c, 0xe5, 0x67];
+
+/// Asset key of the chain's native token in `prices`; ERC-20 prices are keyed by token address.
+const NATIVE_ASSET: &str = "native";
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PriceSource {
+    /// Reads Chainlink USD aggregators on the chain itself, as of the window's block.
+    Chainlink,
+    /// Asks a CoinGecko-compatible `simple/price` endpoint for current prices.
+    Rest,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PricedToken {
+    pub address: Address,
+    pub decimals: u8,
+    pub feed: Option<Address>, // Chainlink USD aggregator
+    pub id: Option<String>,    // REST provider asset id
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PriceFeedConfig {
+    pub source: PriceSource,
+    /// Prices are recorded at the first block of every window of this many blocks.
+    #[serde(default = "default_window_blocks")]
+    pub window_blocks: u64,
+    pub native_feed: Option<Address>,
+    pub native_id: Option<String>,
+    #[serde(default)]
+    pub tokens: Vec<PricedToken>,
+    #[serde(default = "default_rest_url")]
+    pub rest_url: String,
+    pub api_key: Option<String>, // env var holding the REST provider API key
+    /// REST providers only know current prices, so windows of older blocks are skipped.
+    #[serde(default = "default_max_rest_lag_secs")]
+    pub max_rest_lag_secs: u64,
+    #[serde(default)]
+    pub annotate_transfers: bool, // fills token_transfers.value_usd for priced tokens
+}
+
+fn default_window_blocks() -> u64 {
+    300
+}
+
+fn default_rest_url() -> String {
+    "https://api.coingecko.com/api/v3/simple/price".to_string()
+}
+
+fn default_max_rest_lag_secs() -> u64 {
+    900
+}
+
+/// An asset a window couldn't price, and why.
+struct PriceGap {
+    asset: String,
+    /// `feed_failed`, `stale`, `request_failed` or `not_returned`.
+    reason: &'static str,
+    detail: Option<String>,
+}
+
+/// Records USD prices of a chain's native token and configured ERC-20 tokens into `prices`, once
+/// per block window, and optionally values the block's `token_transfers` at the latest recorded
+/// price. Assets a window couldn't price are recorded in `price_gaps` and counted in
+/// `price_gaps_total`, so they can be filled in later.
+pub struct PriceRecorder {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    client: Client,
+    source: PriceSource,
+    window_blocks: u64,
+    native_feed: Option<Address>,
+    native_id: Option<String>,
+    tokens: Vec<PricedToken>,
+    rest_url: String,
+    api_key: Option<String>,
+    max_rest_lag_secs: u64,
+    annotate_transfers: bool,
+    feed_decimals: Mutex<HashMap<Address, u32>>,
+}
+
+impl PriceRecorder {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, config: &PriceFeedConfig) -> Self {
+        Self {
+            adapter,
+            pg_pool,
+            client: Client::new(),
+            source: config.source,
+            window_blocks: config.window_blocks.max(1),
+            native_feed: config.native_feed,
+            native_id: config.native_id.clone(),
+            tokens: config.tokens.clone(),
+            rest_url: config.rest_url.clone(),
+            api_key: config.api_key.clone(),
+            max_rest_lag_secs: config.max_rest_lag_secs,
+            annotate_transfers: config.annotate_transfers,
+            feed_decimals: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// `(asset, USD price)` pairs from the configured source, as decimal strings, and the assets
+    /// the source is configured for but couldn't price.
+    async fn fetch_prices(&self, block: &Block<Transaction>) -> Result<(Vec<(String, String)>, Vec<PriceGap>)> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let native = (NATIVE_ASSET.to_string(), self.native_feed, self.native_id.clone());
+        let assets = std::iter::once(native)
+            .chain(self.tokens.iter().map(|token| (format!("{:?}", token.address), token.feed, token.id.clone())));
+
+        match self.source {
+            PriceSource::Chainlink => {
+                let (mut prices, mut gaps) = (Vec::new(), Vec::new());
+                for (asset, feed, _) in assets {
+                    let Some(feed) = feed else { continue };
+                    match self.chainlink_price(feed, block_number).await {
+                        Ok(price) => prices.push((asset, price)),
+                        Err(e) => gaps.push(PriceGap {
+                            asset,
+                            reason: "feed_failed",
+                            detail: Some(format!("feed {:?}: {}", feed, e)),
+                        }),
+                    }
+                }
+                Ok((prices, gaps))
+            }
+            PriceSource::Rest => {
+                let ids: Vec<(String, String)> = assets.filter_map(|(asset, _, id)| Some((asset, id?))).collect();
+                if ids.is_empty() {
+                    return Ok((Vec::new(), Vec::new()));
+                }
+                let gaps = |reason: &'static str, detail: Option<String>| -> Vec<PriceGap> {
+                    ids.iter().map(|(asset, _)| PriceGap { asset: asset.clone(), reason, detail: detail.clone() }).collect()
+                };
+                let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+                let lag = now.saturating_sub(block.timestamp.as_u64());
+                if lag > self.max_rest_lag_secs {
+                    return Ok((Vec::new(), gaps("stale", Some(format!("block is {}s old", lag)))));
+                }
+                let joined = ids.iter().map(|(_, id)| id.as_str()).collect::<Vec<_>>().join(",");
+                let mut request = self.client.get(&self.rest_url).query(&[("ids", joined.as_str()), ("vs_currencies", "usd")]);
+                if let Some(api_key) = &self.api_key {
+                    request = request.header("x-cg-pro-api-key", api_key);
+                }
+                let body = match request.send().await.and_then(|response| response.error_for_status()) {
+                    Ok(response) => response.json::<Value>().await,
+                    Err(e) => Err(e),
+                };
+                let body = match body {
+                    Ok(body) => body,
+                    Err(e) => return Ok((Vec::new(), gaps("request_failed", Some(e.to_string())))),
+                };
+                let (mut prices, mut missing) = (Vec::new(), Vec::new());
+                for (asset, id) in ids {
+                    match body[id.as_str()]["usd"].as_f64() {
+                        Some(price) => prices.push((asset, price.to_string())),
+                        None => missing.push(PriceGap { asset, reason: "not_returned", detail: Some(format!("id {}", id)) }),
+                    }
+                }
+                Ok((prices, missing))
+            }
+        }
+    }
+
+    async fn chainlink_price(&self, feed: Address, block_number: u64) -> Result<String> {
+        let decimals = self.feed_decimals(feed, block_number).await?;
+        let output = self.adapter.call(feed, Bytes::from(LATEST_ROUND_DATA_SELECTOR.to_vec()), block_number).await?;
+        if output.len() < 64 {
+            return Err(anyhow!("Unexpected latestRoundData output: {}", output));
+        }
+        // (roundId, answer, startedAt, updatedAt, answeredInRound); answer is a signed int256.
+        let answer = I256::from_raw(U256::from_big_endian(&output[32..64]));
+        if answer <= I256::zero() {
+            return Err(anyhow!("non-positive answer {}", answer));
+        }
+        Ok(scale_down(&answer.to_string(), decimals))
+    }
+
+    async fn feed_decimals(&self, feed: Address, block_number: u64) -> Result<u32> {
+        if let Some(decimals) = self.feed_decimals.lock().unwrap().get(&feed) {
+            return Ok(*decimals);
+        }
+        let output = self.adapter.call(feed, Bytes::from(DECIMALS_SELECTOR.to_vec()), block_number).await?;
+        if output.len() < 32 {
+            return Err(anyhow!("Unexpected decimals output: {}", output));
+        }
+        let decimals = U256::from_big_endian(&output[..32]).as_u32();
+        self.feed_decimals.lock().unwrap().insert(feed, decimals);
+        Ok(decimals)
+    }
+
+    /// Records the assets of the window at `block_number` that have no price, and clears the
+    /// gaps of assets that now have one, e.g. when the window is redelivered.
+    async fn record_gaps(&self, chain_name: &str, block_number: u64, priced: &[(String, String)], gaps: &[PriceGap]) -> Result<()> {
+        for gap in gaps {
+            warn!(
+                "No {} price for {} at block {} ({}): {}",
+                chain_name,
+                gap.asset,
+                block_number,
+                gap.reason,
+                gap.detail.as_deref().unwrap_or("")
+            );
+            metrics::increment_counter("price_gaps_total", &[("chain", chain_name), ("reason", gap.reason)], 1);
+        }
+        sqlx::query("DELETE FROM price_gaps WHERE chain_name = $1 AND block_number = $2 AND asset = ANY($3)")
+            .bind(chain_name)
+            .bind(block_number as i64)
+            .bind(priced.iter().map(|(asset, _)| asset.clone()).collect::<Vec<_>>())
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        if gaps.is_empty() {
+            return Ok(());
+        }
+        sqlx::query(
+            "INSERT INTO price_gaps (chain_name, block_number, asset, reason, detail)
+            SELECT $1, $2, asset, reason, detail
+            FROM UNNEST($3::text[], $4::text[], $5::text[]) AS g(asset, reason, detail)
+            ON CONFLICT (chain_name, asset, block_number) DO UPDATE SET
+                reason = EXCLUDED.reason,
+                detail = EXCLUDED.detail,
+                recorded_at = EXCLUDED.recorded_at",
+        )
+        .bind(chain_name)
+        .bind(block_number as i64)
+        .bind(gaps.iter().map(|gap| gap.asset.clone()).collect::<Vec<_>>())
+        .bind(gaps.iter().map(|gap| gap.reason.to_string()).collect::<Vec<_>>())
+        .bind(gaps.iter().map(|gap| gap.detail.clone()).collect::<Vec<_>>())
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    /// Values the block's transfers of priced tokens at the latest price recorded at or before it.
+    async fn annotate_transfers(&self, chain_name: &str, block_number: u64) -> Result<()> {
+        if self.tokens.is_empty() {
+            return Ok(());
+        }
+        sqlx::query(
+            "UPDATE token_transfers t SET value_usd = t.value / power(10::numeric, d.decimals) * p.price_usd
+            FROM UNNEST($3::text[], $4::int[]) AS d(token, decimals),
+            LATERAL (
+                SELECT price_usd FROM prices
+                WHERE chain_name = $1 AND asset = d.token AND block_number <= $2
+                ORDER BY block_number DESC
+                LIMIT 1
+            ) p
+            WHERE t.chain_name = $1 AND t.block_number = $2 AND t.token = d.token AND t.value_usd IS NULL",
+        )
+        .bind(chain_name)
+        .bind(block_number as i64)
+        .bind(self.tokens.iter().map(|token| format!("{:?}", token.address)).collect::<Vec<_>>())
+        .bind(self.tokens.iter().map(|token| token.decimals as i32).collect::<Vec<_>>())
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+}
+
+/// Formats an integer string divided by `10^decimals` as a decimal string.
+fn scale_down(digits: &str, decimals: u32) -> String {
+    let decimals = decimals as usize;
+    if decimals == 0 {
+        return digits.to_string();
+    }
+    let padded = format!("{:0>width$}", digits, width = decimals + 1);
+    let (whole, fraction) = padded.split_at(padded.len() - decimals);
+    format!("{}.{}", whole, fraction)
+}
+
+#[async_trait]
+impl ConsumerHook for PriceRecorder {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        if block_number % self.window_blocks == 0 {
+            let (prices, gaps) = self.fetch_prices(block).await?;
+            self.record_gaps(chain_name, block_number, &prices, &gaps).await?;
+            if !prices.is_empty() {
+                let source = match self.source {
+                    PriceSource::Chainlink => "chainlink",
+                    PriceSource::Rest => "rest",
+                };
+                sqlx::query(
+                    "INSERT INTO prices (chain_name, block_number, timestamp, asset, price_usd, source)
+                    SELECT $1, $2, to_timestamp($3) AT TIME ZONE 'UTC', asset, price_usd::numeric, $4
+                    FROM UNNEST($5::text[], $6::text[]) AS p(asset, price_usd)
+                    ON CONFLICT (chain_name, asset, block_number) DO UPDATE SET
+                        price_usd = EXCLUDED.price_usd,
+                        source = EXCLUDED.source",
+                )
+                .bind(chain_name)
+                .bind(block_number as i64)
+                .bind(block.timestamp.as_u64() as f64)
+                .bind(source)
+                .bind(prices.iter().map(|(asset, _)| asset.clone()).collect::<Vec<_>>())
+                .bind(prices.iter().map(|(_, price)| price.clone()).collect::<Vec<_>>())
+                .execute(self.pg_pool.as_ref())
+                .await?;
+            }
+        }
+
+        if self.annotate_transfers {
+            self.annotate_transfers(chain_name, block_number).await?;
+        }
+        Ok(())
+    }
+}
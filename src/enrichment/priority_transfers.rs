@@ -0,0 +1,145 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Transaction};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::enrichment::transfers::decode_erc20_transfer;
+use crate::metrics;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+use crate::streams::message_queue::queue::{MessageQueue, QueuePublisher};
+use crate::streams::schemas::priority::PriorityTransfer;
+use crate::streams::schemas::schema::{MessageSchema, WireFormat};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PriorityTransfersConfig {
+    pub tokens: Vec<String>, // token contract addresses, e.g. stablecoins
+}
+
+/// Decodes transfers of allowlisted tokens straight from the realtime producer, publishes them to
+/// `{chain}-priority-transfers` and stores them in `priority_transfers`. This skips the block topic
+/// and the consumer's decoding hooks, so payment monitors see them while the block is still
+/// queued. Rows can belong to blocks later orphaned; `PriorityTransferPruner` removes those.
+pub struct PriorityTransferPublisher {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    publisher: Box<dyn QueuePublisher>,
+    chain_name: String,
+    wire_format: WireFormat,
+    tokens: HashSet<Address>,
+}
+
+impl PriorityTransferPublisher {
+    pub async fn new(
+        adapter: Arc<dyn BlockchainAdapter>,
+        pg_pool: Arc<PgPool>,
+        queue: Arc<dyn MessageQueue>,
+        topic: &str,
+        chain_name: &str,
+        wire_format: WireFormat,
+        config: &PriorityTransfersConfig,
+    ) -> Result<Self> {
+        let tokens = config
+            .tokens
+            .iter()
+            .map(|token| {
+                token
+                    .parse::<Address>()
+                    .with_context(|| format!("Invalid priority token address `{}` for {}", token, chain_name))
+            })
+            .collect::<Result<HashSet<_>>>()?;
+        Ok(Self {
+            adapter,
+            pg_pool,
+            publisher: queue.publisher(topic).await?,
+            chain_name: chain_name.to_string(),
+            wire_format,
+            tokens,
+        })
+    }
+
+    /// Publishes and stores the allowlisted transfers of one block.
+    pub async fn process(&self, block_number: u64) -> Result<()> {
+        let logs = self.adapter.get_logs(block_number).await?;
+        let transfers: Vec<PriorityTransfer> = logs
+            .iter()
+            .filter(|log| self.tokens.contains(&log.address))
+            .filter_map(|log| {
+                let transfer = decode_erc20_transfer(log)?;
+                Some(PriorityTransfer {
+                    chain_name: self.chain_name.clone(),
+                    block_number: transfer.block_number,
+                    block_hash: log.block_hash?,
+                    tx_hash: transfer.tx_hash,
+                    log_index: transfer.log_index,
+                    token: transfer.token,
+                    from: transfer.from,
+                    to: transfer.to,
+                    value: transfer.value,
+                })
+            })
+            .collect();
+        if transfers.is_empty() {
+            return Ok(());
+        }
+
+        for transfer in &transfers {
+            self.publisher.publish(transfer.encode(self.wire_format)?).await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO priority_transfers (chain_name, block_number, block_hash, tx_hash, log_index, token, from_address, to_address, value)
+            SELECT $1, $2, block_hash, tx_hash, log_index, token, from_address, to_address, value::numeric
+            FROM UNNEST($3::text[], $4::text[], $5::bigint[], $6::text[], $7::text[], $8::text[], $9::text[])
+                AS t(block_hash, tx_hash, log_index, token, from_address, to_address, value)
+            ON CONFLICT (chain_name, tx_hash, log_index) DO UPDATE SET
+                block_number = EXCLUDED.block_number,
+                block_hash = EXCLUDED.block_hash",
+        )
+        .bind(&self.chain_name)
+        .bind(block_number as i64)
+        .bind(transfers.iter().map(|t| format!("{:?}", t.block_hash)).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| format!("{:?}", t.tx_hash)).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| t.log_index as i64).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| format!("{:?}", t.token)).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| format!("{:?}", t.from)).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| format!("{:?}", t.to)).collect::<Vec<_>>())
+        .bind(transfers.iter().map(|t| t.value.to_string()).collect::<Vec<_>>())
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        metrics::increment_counter("priority_transfers_total", &[("chain", &self.chain_name)], transfers.len() as u64);
+        Ok(())
+    }
+}
+
+/// Deletes `priority_transfers` rows of blocks orphaned by a reorg.
+pub struct PriorityTransferPruner {
+    pg_pool: Arc<PgPool>,
+}
+
+impl PriorityTransferPruner {
+    pub fn new(pg_pool: Arc<PgPool>) -> Self {
+        Self { pg_pool }
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for PriorityTransferPruner {
+    async fn on_block_committed(&self, _chain_name: &str, _block: &Block<Transaction>) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        sqlx::query("DELETE FROM priority_transfers WHERE chain_name = $1 AND block_hash = ANY($2)")
+            .bind(chain_name)
+            .bind(&hashes)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        Ok(())
+    }
+}
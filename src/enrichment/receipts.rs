@@ -0,0 +1,135 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction, TransactionRequest, H256};
+use ethers::utils::hex;
+use log::warn;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::enrichment::abi::AbiRegistry;
+use crate::metrics;
+use crate::streams::consumers::hooks::ConsumerHook;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ReceiptStatusConfig {
+    pub enabled: bool,
+    /// Replay failed transactions with `eth_call` to recover why they reverted.
+    pub revert_reasons: bool,
+}
+
+impl Default for ReceiptStatusConfig {
+    fn default() -> Self {
+        Self { enabled: false, revert_reasons: true }
+    }
+}
+
+/// Records each transaction's receipt status, and for failed ones the revert reason, on the
+/// `transactions` rows of every committed block.
+///
+/// Reasons are best-effort: they come from replaying the transaction, with its sender, value and
+/// gas limit, against the parent block's state. Transactions that depend on earlier transactions
+/// in the same block may replay successfully and get no reason, or revert for another one.
+pub struct ReceiptStatusTracker {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    abis: Arc<AbiRegistry>,
+    revert_reasons: bool,
+}
+
+impl ReceiptStatusTracker {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, abis: Arc<AbiRegistry>, config: &ReceiptStatusConfig) -> Self {
+        Self { adapter, pg_pool, abis, revert_reasons: config.revert_reasons }
+    }
+
+    async fn revert_reason(&self, tx: &Transaction, block_number: u64) -> Option<String> {
+        // Contract creations have no target to call.
+        let to = tx.to?;
+        let request = TransactionRequest::new()
+            .from(tx.from)
+            .to(to)
+            .value(tx.value)
+            .gas(tx.gas)
+            .data(tx.input.clone());
+        match self.adapter.call_request(request, block_number.saturating_sub(1)).await {
+            Ok(_) => None,
+            Err(e) => reason_from_error(&e.to_string(), &self.abis),
+        }
+    }
+}
+
+/// Decodes the revert data embedded in a node's error message, falling back to the message's
+/// own `execution reverted: <reason>` text.
+fn reason_from_error(message: &str, abis: &AbiRegistry) -> Option<String> {
+    let mut rest = message;
+    while let Some(start) = rest.find("0x") {
+        let digits: String = rest[start + 2..].chars().take_while(|c| c.is_ascii_hexdigit()).collect();
+        // At least a selector.
+        if digits.len() >= 8 && digits.len() % 2 == 0 {
+            if let Some(reason) = hex::decode(&digits).ok().and_then(|data| abis.decode_revert(&data)) {
+                return Some(reason);
+            }
+        }
+        rest = &rest[start + 2 + digits.len()..];
+    }
+
+    let (_, reason) = message.split_once("execution reverted: ")?;
+    let reason = reason.trim().trim_matches('"');
+    (!reason.is_empty()).then(|| reason.to_string())
+}
+
+#[async_trait]
+impl ConsumerHook for ReceiptStatusTracker {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        if block.transactions.is_empty() {
+            return Ok(());
+        }
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let receipts = self.adapter.get_block_receipts(block_number).await?;
+        let transactions: HashMap<H256, &Transaction> = block.transactions.iter().map(|tx| (tx.hash, tx)).collect();
+
+        let mut hashes = Vec::with_capacity(receipts.len());
+        let mut statuses = Vec::with_capacity(receipts.len());
+        let mut reasons = Vec::with_capacity(receipts.len());
+        let mut failed = 0;
+        for receipt in &receipts {
+            // Pre-Byzantium receipts carry a state root instead of a status.
+            let status = receipt.status.map(|status| status.as_u64() as i16);
+            let mut reason = None;
+            if status == Some(0) {
+                failed += 1;
+                if let (true, Some(tx)) = (self.revert_reasons, transactions.get(&receipt.transaction_hash)) {
+                    reason = self.revert_reason(tx, block_number).await;
+                }
+            }
+            hashes.push(format!("{:?}", receipt.transaction_hash));
+            statuses.push(status);
+            reasons.push(reason);
+        }
+        if receipts.len() != block.transactions.len() {
+            warn!(
+                "{} block {} has {} transactions but {} receipts",
+                chain_name, block_number, block.transactions.len(), receipts.len()
+            );
+        }
+
+        sqlx::query(
+            "UPDATE transactions t SET status = r.status, revert_reason = r.revert_reason
+            FROM UNNEST($3::text[], $4::smallint[], $5::text[]) AS r(tx_hash, status, revert_reason)
+            WHERE t.chain_name = $1 AND t.block_number = $2 AND t.tx_hash = r.tx_hash AND t.canonical",
+        )
+        .bind(chain_name)
+        .bind(block_number as i64)
+        .bind(&hashes)
+        .bind(&statuses)
+        .bind(&reasons)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        metrics::increment_counter("failed_transactions_total", &[("chain", chain_name)], failed);
+        Ok(())
+    }
+}
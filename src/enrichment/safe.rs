@@ -0,0 +1,236 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::abi::{decode, ParamType, Token};
+use ethers::types::{Address, Block, Bytes, Log, Transaction, H256, U256};
+use ethers::utils::keccak256;
+use log::warn;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+/// `execTransaction(address,uint256,bytes,uint8,uint256,uint256,uint256,address,address,bytes)` selector.
+const EXEC_TRANSACTION_SELECTOR: [u8; 4] = [0x6a, 0x76, 0x12, 0x02];
+/// `nonce()` selector.
+const NONCE_SELECTOR: [u8; 4] = [0xaf, 0xfe, 0xd0, 0xe0];
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SafeDecodingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// The arguments of a decoded `execTransaction` call.
+struct ExecCall {
+    to: Address,
+    value: U256,
+    operation: u8,
+    signers: usize,
+}
+
+/// One Safe transaction execution found in a block.
+struct SafeExecution {
+    tx_hash: H256,
+    tx_index: u64,
+    safe: Address,
+    safe_tx_hash: Option<H256>,
+    call: Option<ExecCall>,
+    success: bool,
+}
+
+fn decode_exec_call(input: &[u8]) -> Option<ExecCall> {
+    if input.get(..4)? != EXEC_TRANSACTION_SELECTOR {
+        return None;
+    }
+    let tokens = decode(
+        &[
+            ParamType::Address,
+            ParamType::Uint(256),
+            ParamType::Bytes,
+            ParamType::Uint(8),
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+            ParamType::Uint(256),
+            ParamType::Address,
+            ParamType::Address,
+            ParamType::Bytes,
+        ],
+        &input[4..],
+    )
+    .ok()?;
+    match (&tokens[0], &tokens[1], &tokens[3], &tokens[9]) {
+        (Token::Address(to), Token::Uint(value), Token::Uint(operation), Token::Bytes(signatures)) => Some(ExecCall {
+            to: *to,
+            value: *value,
+            operation: operation.low_u32() as u8,
+            signers: count_signers(signatures),
+        }),
+        _ => None,
+    }
+}
+
+/// Counts the 65-byte signatures in Safe's packed encoding. Contract signatures (`v == 0`) point
+/// at dynamic data appended after the static part, which ends where the first of them points.
+fn count_signers(signatures: &[u8]) -> usize {
+    let mut count = signatures.len() / 65;
+    let mut i = 0;
+    while i < count {
+        let signature = &signatures[i * 65..(i + 1) * 65];
+        if signature[64] == 0 {
+            let offset = U256::from_big_endian(&signature[32..64]);
+            if offset < U256::from(signatures.len()) {
+                count = count.min(offset.as_usize() / 65);
+            }
+        }
+        i += 1;
+    }
+    count
+}
+
+/// Decodes Gnosis Safe executions in committed blocks into `safe_transactions`: top-level
+/// `execTransaction` calls (target, value, operation, signer count) and the
+/// `ExecutionSuccess`/`ExecutionFailure` events Safes emit, which also catch executions relayed
+/// through other contracts. A call whose transaction reverted has no event and counts as failed.
+/// Nonces are read from the Safe as of the previous block and advanced per execution in the block.
+pub struct SafeTransactionDecoder {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    success_topic: H256,
+    failure_topic: H256,
+}
+
+impl SafeTransactionDecoder {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>) -> Self {
+        Self {
+            adapter,
+            pg_pool,
+            success_topic: H256::from(keccak256("ExecutionSuccess(bytes32,uint256)")),
+            failure_topic: H256::from(keccak256("ExecutionFailure(bytes32,uint256)")),
+        }
+    }
+
+    fn executions(&self, block: &Block<Transaction>, logs: &[Log]) -> Vec<SafeExecution> {
+        let mut executions = Vec::new();
+        for (tx_index, transaction) in block.transactions.iter().enumerate() {
+            let events: Vec<&Log> = logs
+                .iter()
+                .filter(|log| log.transaction_hash == Some(transaction.hash))
+                .filter(|log| log.topics.first().map_or(false, |topic| *topic == self.success_topic || *topic == self.failure_topic))
+                .filter(|log| log.data.len() >= 32)
+                .collect();
+            let mut call = transaction.to.and_then(|to| decode_exec_call(&transaction.input).map(|call| (to, call)));
+
+            for event in &events {
+                // The top-level call belongs to the event its Safe emitted; other events were relayed.
+                let owns_call = call.as_ref().map_or(false, |(safe, _)| *safe == event.address);
+                let event_call = if owns_call { call.take().map(|(_, call)| call) } else { None };
+                executions.push(SafeExecution {
+                    tx_hash: transaction.hash,
+                    tx_index: tx_index as u64,
+                    safe: event.address,
+                    safe_tx_hash: Some(H256::from_slice(&event.data[..32])),
+                    call: event_call,
+                    success: event.topics[0] == self.success_topic,
+                });
+            }
+            if let Some((safe, call)) = call {
+                executions.push(SafeExecution {
+                    tx_hash: transaction.hash,
+                    tx_index: tx_index as u64,
+                    safe,
+                    safe_tx_hash: None,
+                    call: Some(call),
+                    success: false,
+                });
+            }
+        }
+        executions
+    }
+
+    async fn nonce_before(&self, safe: Address, block_number: u64) -> Option<u64> {
+        let output = match self.adapter.call(safe, Bytes::from(NONCE_SELECTOR.to_vec()), block_number.saturating_sub(1)).await {
+            Ok(output) if output.len() >= 32 => output,
+            Ok(output) => {
+                warn!("Unexpected nonce() output from Safe {:?}: {}", safe, output);
+                return None;
+            }
+            Err(e) => {
+                warn!("Failed to read nonce of Safe {:?} at block {}: {}", safe, block_number.saturating_sub(1), e);
+                return None;
+            }
+        };
+        Some(U256::from_big_endian(&output[..32]).as_u64())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for SafeTransactionDecoder {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let logs = self.adapter.get_logs(block_number).await?;
+        let executions = self.executions(block, &logs);
+        if executions.is_empty() {
+            return Ok(());
+        }
+
+        // Executions that emitted an event consumed a nonce; reverted calls did not.
+        let mut nonces: HashMap<Address, Option<u64>> = HashMap::new();
+        let mut assigned = Vec::with_capacity(executions.len());
+        for execution in &executions {
+            if !nonces.contains_key(&execution.safe) {
+                let nonce = self.nonce_before(execution.safe, block_number).await;
+                nonces.insert(execution.safe, nonce);
+            }
+            let next = nonces.get_mut(&execution.safe).unwrap();
+            assigned.push(next.map(|nonce| nonce as i64));
+            if execution.safe_tx_hash.is_some() {
+                *next = next.map(|nonce| nonce + 1);
+            }
+        }
+
+        // A redelivered block replaces its earlier rows.
+        let mut db_tx = self.pg_pool.begin().await?;
+        sqlx::query("DELETE FROM safe_transactions WHERE chain_name = $1 AND block_number = $2")
+            .bind(chain_name)
+            .bind(block_number as i64)
+            .execute(&mut db_tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO safe_transactions (chain_name, block_number, block_hash, tx_hash, tx_index, safe_address, nonce, safe_tx_hash, to_address, value, operation, signers_count, success)
+            SELECT $1, $2, $3, tx_hash, tx_index, safe_address, nonce, safe_tx_hash, to_address, value::numeric, operation, signers_count, success
+            FROM UNNEST($4::text[], $5::bigint[], $6::text[], $7::bigint[], $8::text[], $9::text[], $10::text[], $11::smallint[], $12::int[], $13::boolean[])
+                AS t(tx_hash, tx_index, safe_address, nonce, safe_tx_hash, to_address, value, operation, signers_count, success)",
+        )
+        .bind(chain_name)
+        .bind(block_number as i64)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(executions.iter().map(|e| format!("{:?}", e.tx_hash)).collect::<Vec<_>>())
+        .bind(executions.iter().map(|e| e.tx_index as i64).collect::<Vec<_>>())
+        .bind(executions.iter().map(|e| format!("{:?}", e.safe)).collect::<Vec<_>>())
+        .bind(&assigned)
+        .bind(executions.iter().map(|e| e.safe_tx_hash.map(|hash| format!("{:?}", hash))).collect::<Vec<_>>())
+        .bind(executions.iter().map(|e| e.call.as_ref().map(|call| format!("{:?}", call.to))).collect::<Vec<_>>())
+        .bind(executions.iter().map(|e| e.call.as_ref().map(|call| call.value.to_string())).collect::<Vec<_>>())
+        .bind(executions.iter().map(|e| e.call.as_ref().map(|call| call.operation as i16)).collect::<Vec<_>>())
+        .bind(executions.iter().map(|e| e.call.as_ref().map(|call| call.signers as i32)).collect::<Vec<_>>())
+        .bind(executions.iter().map(|e| e.success).collect::<Vec<_>>())
+        .execute(&mut db_tx)
+        .await?;
+
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        sqlx::query("DELETE FROM safe_transactions WHERE chain_name = $1 AND block_hash = ANY($2)")
+            .bind(chain_name)
+            .bind(&hashes)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        Ok(())
+    }
+}
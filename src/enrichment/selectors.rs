@@ -0,0 +1,140 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use ethers::utils::{hex, keccak256};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::enrichment::abi::AbiRegistry;
+use crate::streams::consumers::hooks::ConsumerHook;
+
+const BUNDLED_SIGNATURES: &str = include_str!("../../data/function_signatures.txt");
+
+#[derive(Debug, Default, Deserialize)]
+pub struct MethodDecodingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Signature dump (e.g. from openchain or 4byte) loaded on top of the bundled signatures.
+    pub signatures_file: Option<PathBuf>,
+}
+
+/// Function signatures by 4-byte selector.
+pub struct SignatureDatabase {
+    signatures: HashMap<[u8; 4], String>,
+}
+
+impl SignatureDatabase {
+    /// The bundled signatures plus those in `signatures_file`, if configured.
+    pub fn load(config: &MethodDecodingConfig) -> Result<Self> {
+        let mut database = Self { signatures: HashMap::new() };
+        database.add_lines(BUNDLED_SIGNATURES);
+        if let Some(path) = &config.signatures_file {
+            database.add_file(path)?;
+        }
+        Ok(database)
+    }
+
+    fn add_file(&mut self, path: &Path) -> Result<()> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read signatures from {}", path.display()))?;
+        self.add_lines(&contents);
+        Ok(())
+    }
+
+    /// Accepts `signature` lines, whose selector is derived, and `0xselector<sep>signature` lines
+    /// as found in dumps, separated by a comma, tab or space. The first signature of a selector wins.
+    fn add_lines(&mut self, contents: &str) {
+        for line in contents.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parsed = match line.strip_prefix("0x") {
+                Some(rest) => rest
+                    .split_once(|c: char| c == ',' || c == '\t' || c == ' ')
+                    .and_then(|(selector, signature)| Some((parse_selector(selector)?, signature.trim().to_string()))),
+                None => Some((selector_of(line), line.to_string())),
+            };
+            if let Some((selector, signature)) = parsed {
+                self.signatures.entry(selector).or_insert(signature);
+            }
+        }
+    }
+
+    pub fn get(&self, selector: [u8; 4]) -> Option<&str> {
+        self.signatures.get(&selector).map(String::as_str)
+    }
+}
+
+fn selector_of(signature: &str) -> [u8; 4] {
+    let mut selector = [0u8; 4];
+    selector.copy_from_slice(&keccak256(signature.as_bytes())[..4]);
+    selector
+}
+
+fn parse_selector(hex_selector: &str) -> Option<[u8; 4]> {
+    hex::decode(hex_selector).ok()?.try_into().ok()
+}
+
+/// Sets `method_selector` and `method_name` on the `transactions` rows of every committed block.
+/// Registered ABIs take precedence over the signature database, whose selectors can collide.
+pub struct MethodDecoder {
+    pg_pool: Arc<PgPool>,
+    signatures: Arc<SignatureDatabase>,
+    abis: Arc<AbiRegistry>,
+}
+
+impl MethodDecoder {
+    pub fn new(pg_pool: Arc<PgPool>, signatures: Arc<SignatureDatabase>, abis: Arc<AbiRegistry>) -> Self {
+        Self { pg_pool, signatures, abis }
+    }
+
+    fn method_name(&self, selector: [u8; 4]) -> Option<String> {
+        method_name(&self.abis, Some(&self.signatures), selector)
+    }
+}
+
+/// Name of the function `selector` calls, from the registered ABIs or else the signature
+/// database.
+pub fn method_name(abis: &AbiRegistry, signatures: Option<&SignatureDatabase>, selector: [u8; 4]) -> Option<String> {
+    let signature = abis
+        .function_signature(selector)
+        .or_else(|| signatures?.get(selector).map(str::to_string))?;
+    signature.split('(').next().map(str::to_string)
+}
+
+#[async_trait]
+impl ConsumerHook for MethodDecoder {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let mut hashes = Vec::new();
+        let mut selectors = Vec::new();
+        let mut names = Vec::new();
+        // Plain transfers carry no calldata and keep NULL columns.
+        for tx in block.transactions.iter().filter(|tx| tx.input.len() >= 4) {
+            let mut selector = [0u8; 4];
+            selector.copy_from_slice(&tx.input[..4]);
+            hashes.push(format!("{:?}", tx.hash));
+            selectors.push(format!("0x{}", hex::encode(selector)));
+            names.push(self.method_name(selector));
+        }
+        if hashes.is_empty() {
+            return Ok(());
+        }
+
+        sqlx::query(
+            "UPDATE transactions t SET method_selector = m.method_selector, method_name = m.method_name
+            FROM UNNEST($3::text[], $4::text[], $5::text[]) AS m(tx_hash, method_selector, method_name)
+            WHERE t.chain_name = $1 AND t.block_number = $2 AND t.tx_hash = m.tx_hash AND t.canonical",
+        )
+        .bind(chain_name)
+        .bind(block.number.unwrap_or_default().as_u64() as i64)
+        .bind(&hashes)
+        .bind(&selectors)
+        .bind(&names)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+}
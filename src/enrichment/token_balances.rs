@@ -0,0 +1,253 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Bytes, Transaction, U256};
+use log::{error, warn};
+use serde::Deserialize;
+use sqlx::{PgPool, Postgres, Row, Transaction as DbTransaction};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::blockchain::adapters::{logs_of_block, BlockchainAdapter};
+use crate::enrichment::transfers::{decode_erc20_transfers, insert_token_transfer, TokenTransfer};
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+/// `balanceOf(address)` selector.
+const BALANCE_OF_SELECTOR: [u8; 4] = [0x70, 0xa0, 0x82, 0x31];
+
+#[derive(Debug, Deserialize)]
+pub struct TokenBalancesConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_reconcile_interval_secs")]
+    pub reconcile_interval_secs: u64,
+    #[serde(default = "default_reconcile_batch_size")]
+    pub reconcile_batch_size: i64,
+}
+
+fn default_reconcile_interval_secs() -> u64 {
+    300
+}
+
+fn default_reconcile_batch_size() -> i64 {
+    100
+}
+
+impl Default for TokenBalancesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            reconcile_interval_secs: default_reconcile_interval_secs(),
+            reconcile_batch_size: default_reconcile_batch_size(),
+        }
+    }
+}
+
+/// Decodes ERC-20 transfers from each committed block into `token_transfers` and applies their
+/// deltas to `token_balances`, once per block hash. Orphaned blocks' transfers are deleted and
+/// their deltas taken back out. A background `reconcile` loop periodically overwrites balances
+/// with on-chain `balanceOf` results, correcting drift from history skipped; transfers up to the
+/// reconciled block, arriving later, are stored without moving the balance again.
+pub struct TokenBalanceTracker {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+    reconcile_interval: Duration,
+    reconcile_batch_size: i64,
+}
+
+impl TokenBalanceTracker {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>, config: &TokenBalancesConfig) -> Self {
+        Self {
+            adapter,
+            pg_pool,
+            reconcile_interval: Duration::from_secs(config.reconcile_interval_secs),
+            reconcile_batch_size: config.reconcile_batch_size,
+        }
+    }
+
+    /// Periodically reconciles the least recently reconciled balances of a chain. Runs forever.
+    pub async fn reconcile(&self, chain_name: &str) -> Result<()> {
+        let mut ticker = tokio::time::interval(self.reconcile_interval);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.reconcile_batch(chain_name).await {
+                error!("Token balance reconciliation failed for {}: {}", chain_name, e);
+            }
+        }
+    }
+
+    async fn reconcile_batch(&self, chain_name: &str) -> Result<()> {
+        let rows = sqlx::query(
+            "SELECT token, holder, balance::text AS balance, last_updated_block FROM token_balances
+            WHERE chain_name = $1
+            ORDER BY reconciled_at NULLS FIRST
+            LIMIT $2",
+        )
+        .bind(chain_name)
+        .bind(self.reconcile_batch_size)
+        .fetch_all(self.pg_pool.as_ref())
+        .await?;
+
+        for row in rows {
+            let token: String = row.try_get("token")?;
+            let holder: String = row.try_get("holder")?;
+            let stored: String = row.try_get("balance")?;
+            let block_number: i64 = row.try_get("last_updated_block")?;
+
+            let on_chain = self.balance_of(token.parse()?, holder.parse()?, block_number as u64).await?;
+            if on_chain.to_string() != stored {
+                warn!(
+                    "Token balance drift on {} for {} holder {} at block {}: stored {}, on-chain {}",
+                    chain_name, token, holder, block_number, stored, on_chain
+                );
+            }
+
+            // Skip the write if a newer transfer moved the balance while we were calling the node.
+            sqlx::query(
+                "UPDATE token_balances SET balance = $1::numeric, reconciled_at = NOW(), reconciled_block = $5
+                WHERE chain_name = $2 AND token = $3 AND holder = $4 AND last_updated_block = $5",
+            )
+            .bind(on_chain.to_string())
+            .bind(chain_name)
+            .bind(&token)
+            .bind(&holder)
+            .bind(block_number)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn balance_of(&self, token: Address, holder: Address, block_number: u64) -> Result<U256> {
+        let mut data = BALANCE_OF_SELECTOR.to_vec();
+        data.extend_from_slice(&[0u8; 12]);
+        data.extend_from_slice(holder.as_bytes());
+
+        let output = self.adapter.call(token, Bytes::from(data), block_number).await?;
+        if output.len() < 32 {
+            return Err(anyhow!("Unexpected balanceOf output from {:?}: {}", token, output));
+        }
+        Ok(U256::from_big_endian(&output[..32]))
+    }
+}
+
+/// Inserts `transfers` into `token_transfers` and applies the deltas of the ones not stored before
+/// to `token_balances`, so transfers reaching the table from another source, like the Alchemy
+/// backfill, still move balances exactly once. Transfers at or before the block a balance was
+/// reconciled at are already in it and leave it as it is. Returns how many transfers were new.
+pub async fn store_transfers(
+    tx: &mut DbTransaction<'_, Postgres>,
+    chain_name: &str,
+    transfers: &[TokenTransfer],
+) -> Result<usize> {
+    // Net (token, holder, block) deltas; the zero address is mint/burn.
+    let mut deltas: HashMap<(Address, Address, u64), (U256, U256)> = HashMap::new();
+    let mut inserted = 0;
+    for transfer in transfers {
+        if !insert_token_transfer(tx, chain_name, transfer).await? {
+            continue;
+        }
+        inserted += 1;
+        if !transfer.from.is_zero() {
+            let entry = deltas.entry((transfer.token, transfer.from, transfer.block_number)).or_default();
+            entry.1 = entry.1.saturating_add(transfer.value);
+        }
+        if !transfer.to.is_zero() {
+            let entry = deltas.entry((transfer.token, transfer.to, transfer.block_number)).or_default();
+            entry.0 = entry.0.saturating_add(transfer.value);
+        }
+    }
+
+    for ((token, holder, block_number), (incoming, outgoing)) in deltas {
+        sqlx::query(
+            "INSERT INTO token_balances (chain_name, token, holder, balance, last_updated_block)
+            VALUES ($1, $2, $3, $4::numeric - $5::numeric, $6)
+            ON CONFLICT (chain_name, token, holder) DO UPDATE SET
+                balance = token_balances.balance
+                    + CASE WHEN token_balances.reconciled_block >= EXCLUDED.last_updated_block THEN 0 ELSE EXCLUDED.balance END,
+                last_updated_block = GREATEST(token_balances.last_updated_block, EXCLUDED.last_updated_block)",
+        )
+        .bind(chain_name)
+        .bind(format!("{:?}", token))
+        .bind(format!("{:?}", holder))
+        .bind(incoming.to_string())
+        .bind(outgoing.to_string())
+        .bind(block_number as i64)
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    Ok(inserted)
+}
+
+#[async_trait]
+impl ConsumerHook for TokenBalanceTracker {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        // Fetched by height, so the logs of a block that has since replaced this one are dropped.
+        let logs = logs_of_block(self.adapter.get_logs(block_number).await?, block.hash);
+        let transfers = decode_erc20_transfers(&logs);
+        if transfers.is_empty() {
+            return Ok(());
+        }
+
+        let mut tx = self.pg_pool.begin().await?;
+        let first = sqlx::query(
+            "INSERT INTO token_balance_blocks (chain_name, block_hash, block_number) VALUES ($1, $2, $3)
+            ON CONFLICT (chain_name, block_hash) DO NOTHING",
+        )
+        .bind(chain_name)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(block_number as i64)
+        .execute(&mut tx)
+        .await?
+        .rows_affected()
+            == 1;
+        if first {
+            store_transfers(&mut tx, chain_name, &transfers).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Deletes the orphaned blocks' transfers and subtracts their deltas, except from balances
+    /// reconciled at or after the block, which the node already gave without them.
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        let mut tx = self.pg_pool.begin().await?;
+        sqlx::query(
+            "WITH removed AS (
+                DELETE FROM token_transfers WHERE chain_name = $1 AND block_hash = ANY($2)
+                RETURNING token, from_address, to_address, value, block_number
+            ),
+            applied AS (
+                SELECT token, to_address AS holder, value AS delta, block_number FROM removed WHERE to_address <> $3
+                UNION ALL
+                SELECT token, from_address AS holder, -value AS delta, block_number FROM removed WHERE from_address <> $3
+            ),
+            totals AS (
+                SELECT a.token, a.holder, SUM(a.delta) AS delta
+                FROM applied a
+                JOIN token_balances b ON b.chain_name = $1 AND b.token = a.token AND b.holder = a.holder
+                WHERE b.reconciled_block IS NULL OR b.reconciled_block < a.block_number
+                GROUP BY a.token, a.holder
+            )
+            UPDATE token_balances b SET balance = b.balance - t.delta
+            FROM totals t
+            WHERE b.chain_name = $1 AND b.token = t.token AND b.holder = t.holder",
+        )
+        .bind(chain_name)
+        .bind(&hashes)
+        .bind(format!("{:?}", Address::zero()))
+        .execute(&mut tx)
+        .await?;
+        sqlx::query("DELETE FROM token_balance_blocks WHERE chain_name = $1 AND block_hash = ANY($2)")
+            .bind(chain_name)
+            .bind(&hashes)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+}
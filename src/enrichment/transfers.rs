@@ -0,0 +1,78 @@
+use anyhow::Result;
+use ethers::types::{Address, Log, H256, U256};
+use ethers::utils::keccak256;
+use serde::{Deserialize, Serialize};
+use sqlx::{Postgres, Transaction as DbTransaction};
+
+/// `keccak256("Transfer(address,address,uint256)")`, shared by ERC-20 and ERC-721.
+pub fn transfer_topic() -> H256 {
+    H256::from(keccak256("Transfer(address,address,uint256)"))
+}
+
+// A decoded ERC-20 `Transfer` event.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct TokenTransfer {
+    pub token: Address,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub block_number: u64,
+    /// `None` for sources that don't say, such as Alchemy's transfer API.
+    pub block_hash: Option<H256>,
+    pub tx_hash: H256,
+    pub log_index: u64,
+}
+
+/// Decodes an ERC-20 `Transfer` log. ERC-721 transfers share the signature but index the token id
+/// as a fourth topic, so they are skipped here.
+pub fn decode_erc20_transfer(log: &Log) -> Option<TokenTransfer> {
+    if log.topics.len() != 3 || log.topics[0] != transfer_topic() || log.data.len() != 32 {
+        return None;
+    }
+    if log.removed == Some(true) {
+        return None;
+    }
+
+    Some(TokenTransfer {
+        token: log.address,
+        from: Address::from(log.topics[1]),
+        to: Address::from(log.topics[2]),
+        value: U256::from_big_endian(&log.data),
+        block_number: log.block_number?.as_u64(),
+        block_hash: log.block_hash,
+        tx_hash: log.transaction_hash?,
+        log_index: log.log_index?.as_u64(),
+    })
+}
+
+/// Decodes every ERC-20 transfer in a block's logs.
+pub fn decode_erc20_transfers(logs: &[Log]) -> Vec<TokenTransfer> {
+    logs.iter().filter_map(decode_erc20_transfer).collect()
+}
+
+/// Inserts a transfer into `token_transfers`. Returns `false` if it was already stored, so callers
+/// applying derived state (e.g. balance deltas) can stay idempotent across redeliveries.
+pub async fn insert_token_transfer(
+    tx: &mut DbTransaction<'_, Postgres>,
+    chain_name: &str,
+    transfer: &TokenTransfer,
+) -> Result<bool> {
+    let result = sqlx::query(
+        "INSERT INTO token_transfers (chain_name, block_number, tx_hash, log_index, token, from_address, to_address, value, block_hash)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8::numeric, $9)
+        ON CONFLICT (chain_name, tx_hash, log_index) DO NOTHING",
+    )
+    .bind(chain_name)
+    .bind(transfer.block_number as i64)
+    .bind(format!("{:?}", transfer.tx_hash))
+    .bind(transfer.log_index as i64)
+    .bind(format!("{:?}", transfer.token))
+    .bind(format!("{:?}", transfer.from))
+    .bind(format!("{:?}", transfer.to))
+    .bind(transfer.value.to_string())
+    .bind(transfer.block_hash.map(|hash| format!("{:?}", hash)))
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
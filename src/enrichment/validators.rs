@@ -0,0 +1,216 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::blockchain::beacon::BeaconClient;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+#[derive(Debug, Deserialize)]
+pub struct ValidatorRewardsConfig {
+    pub beacon_url: String, // env var holding the Beacon node REST URL
+    /// Validator indices to sample balances for. Withdrawals are recorded for every validator.
+    #[serde(default)]
+    pub validators: Vec<u64>,
+    #[serde(default = "default_every_n_blocks")]
+    pub every_n_blocks: u64,
+    #[serde(default = "default_seconds_per_slot")]
+    pub seconds_per_slot: u64,
+}
+
+fn default_every_n_blocks() -> u64 {
+    // Once a day on 12-second slots.
+    7200
+}
+
+fn default_seconds_per_slot() -> u64 {
+    12
+}
+
+/// Records each committed block's execution-layer withdrawals in `validator_withdrawals` and, every
+/// `every_n_blocks`, samples the configured validators' beacon balances into `validator_rewards`.
+/// A sample's reward is the balance change since the previous sample plus what was withdrawn in
+/// between, so withdrawals sweeping rewards out don't show up as losses.
+///
+/// Blocks may arrive out of order (newest-first or parallel backfills), so a reward is only
+/// settled once both samples and every block between them are stored. A sample or withdrawal
+/// arriving later unsettles the sample after it, which is then settled again.
+pub struct ValidatorRewardTracker {
+    pg_pool: Arc<PgPool>,
+    beacon: BeaconClient,
+    validators: Vec<u64>,
+    every_n_blocks: u64,
+}
+
+impl ValidatorRewardTracker {
+    pub fn new(pg_pool: Arc<PgPool>, config: &ValidatorRewardsConfig) -> Self {
+        Self {
+            pg_pool,
+            beacon: BeaconClient::new(&config.beacon_url, config.seconds_per_slot),
+            validators: config.validators.clone(),
+            every_n_blocks: config.every_n_blocks.max(1),
+        }
+    }
+
+    async fn record_withdrawals(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let Some(withdrawals) = block.withdrawals.as_ref().filter(|withdrawals| !withdrawals.is_empty()) else {
+            return Ok(());
+        };
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let validator_indices: Vec<i64> = withdrawals.iter().map(|w| w.validator_index.as_u64() as i64).collect();
+        let mut db_tx = self.pg_pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO validator_withdrawals (chain_name, block_number, block_hash, withdrawal_index, validator_index, address, amount_gwei)
+            SELECT $1, $2, $3, withdrawal_index, validator_index, address, amount_gwei
+            FROM UNNEST($4::bigint[], $5::bigint[], $6::text[], $7::bigint[]) AS w(withdrawal_index, validator_index, address, amount_gwei)
+            ON CONFLICT (chain_name, withdrawal_index) DO UPDATE SET
+                block_number = EXCLUDED.block_number,
+                block_hash = EXCLUDED.block_hash",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(withdrawals.iter().map(|w| w.index.as_u64() as i64).collect::<Vec<_>>())
+        .bind(&validator_indices)
+        .bind(withdrawals.iter().map(|w| format!("{:?}", w.address)).collect::<Vec<_>>())
+        .bind(withdrawals.iter().map(|w| w.amount.as_u64() as i64).collect::<Vec<_>>())
+        .execute(&mut db_tx)
+        .await?;
+        unsettle_next_samples(&mut db_tx, chain_name, &validator_indices, block_number - 1).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn sample_balances(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let slot = self.beacon.slot_at(block.timestamp.as_u64()).await?;
+        let balances = self.beacon.validator_balances(slot, &self.validators).await?;
+        if balances.is_empty() {
+            return Ok(());
+        }
+        let indices: Vec<i64> = balances.keys().map(|index| *index as i64).collect();
+
+        let mut db_tx = self.pg_pool.begin().await?;
+        sqlx::query(
+            "INSERT INTO validator_rewards (chain_name, validator_index, block_number, block_hash, slot, timestamp, balance_gwei, withdrawn_gwei, reward_gwei)
+            SELECT $1, validator_index, $2, $3, $4, to_timestamp($5) AT TIME ZONE 'UTC', balance_gwei, NULL, NULL
+            FROM UNNEST($6::bigint[], $7::bigint[]) AS r(validator_index, balance_gwei)
+            ON CONFLICT (chain_name, validator_index, block_number) DO UPDATE SET
+                block_hash = EXCLUDED.block_hash,
+                balance_gwei = EXCLUDED.balance_gwei,
+                withdrawn_gwei = NULL,
+                reward_gwei = NULL",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(slot as i64)
+        .bind(block.timestamp.as_u64() as f64)
+        .bind(&indices)
+        .bind(indices.iter().map(|index| balances[&(*index as u64)] as i64).collect::<Vec<_>>())
+        .execute(&mut db_tx)
+        .await?;
+        unsettle_next_samples(&mut db_tx, chain_name, &indices, block_number).await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    /// Settles the rewards of samples whose previous sample, and every block in between, are
+    /// stored.
+    async fn settle_rewards(&self, chain_name: &str) -> Result<()> {
+        sqlx::query(
+            "UPDATE validator_rewards r SET withdrawn_gwei = s.withdrawn_gwei, reward_gwei = r.balance_gwei - s.previous_balance + s.withdrawn_gwei
+            FROM (
+                SELECT r.validator_index, r.block_number, prev.balance_gwei AS previous_balance,
+                    COALESCE((SELECT SUM(w.amount_gwei) FROM validator_withdrawals w
+                        WHERE w.chain_name = $1 AND w.validator_index = r.validator_index
+                            AND w.block_number > prev.block_number AND w.block_number <= r.block_number), 0)::bigint AS withdrawn_gwei
+                FROM validator_rewards r
+                JOIN LATERAL (
+                    SELECT block_number, balance_gwei FROM validator_rewards p
+                    WHERE p.chain_name = $1 AND p.validator_index = r.validator_index AND p.block_number < r.block_number
+                    ORDER BY p.block_number DESC
+                    LIMIT 1
+                ) prev ON TRUE
+                WHERE r.chain_name = $1 AND r.reward_gwei IS NULL
+                    AND (SELECT COUNT(*) FROM blocks b
+                        WHERE b.chain_name = $1 AND b.block_number > prev.block_number AND b.block_number <= r.block_number AND b.canonical)
+                        = r.block_number - prev.block_number
+            ) s
+            WHERE r.chain_name = $1 AND r.validator_index = s.validator_index AND r.block_number = s.block_number",
+        )
+        .bind(chain_name)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+}
+
+/// Clears the reward of each validator's first sample after `block_number`, whose previous
+/// sample or withdrawals just changed.
+async fn unsettle_next_samples(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    chain_name: &str,
+    validator_indices: &[i64],
+    block_number: i64,
+) -> Result<()> {
+    sqlx::query(
+        "UPDATE validator_rewards r SET withdrawn_gwei = NULL, reward_gwei = NULL
+        FROM (
+            SELECT validator_index, MIN(block_number) AS block_number FROM validator_rewards
+            WHERE chain_name = $1 AND validator_index = ANY($2) AND block_number > $3
+            GROUP BY validator_index
+        ) next
+        WHERE r.chain_name = $1 AND r.validator_index = next.validator_index AND r.block_number = next.block_number",
+    )
+    .bind(chain_name)
+    .bind(validator_indices)
+    .bind(block_number)
+    .execute(&mut *db_tx)
+    .await?;
+    Ok(())
+}
+
+#[async_trait]
+impl ConsumerHook for ValidatorRewardTracker {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        self.record_withdrawals(chain_name, block).await?;
+        let block_number = block.number.unwrap_or_default().as_u64();
+        if !self.validators.is_empty() && block_number % self.every_n_blocks == 0 {
+            self.sample_balances(chain_name, block).await?;
+        }
+        if !self.validators.is_empty() {
+            self.settle_rewards(chain_name).await?;
+        }
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        let mut db_tx = self.pg_pool.begin().await?;
+        sqlx::query("DELETE FROM validator_withdrawals WHERE chain_name = $1 AND block_hash = ANY($2)")
+            .bind(chain_name)
+            .bind(&hashes)
+            .execute(&mut db_tx)
+            .await?;
+        sqlx::query("DELETE FROM validator_rewards WHERE chain_name = $1 AND block_hash = ANY($2)")
+            .bind(chain_name)
+            .bind(&hashes)
+            .execute(&mut db_tx)
+            .await?;
+        // Later samples were settled against the orphaned samples and withdrawals.
+        let first_orphaned = orphaned.iter().map(|block| block.block_number).min().unwrap_or_default();
+        sqlx::query(
+            "UPDATE validator_rewards SET withdrawn_gwei = NULL, reward_gwei = NULL
+            WHERE chain_name = $1 AND block_number >= $2",
+        )
+        .bind(chain_name)
+        .bind(first_orphaned)
+        .execute(&mut db_tx)
+        .await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+}
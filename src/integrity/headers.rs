@@ -0,0 +1,165 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction, H256};
+use ethers::utils::keccak256;
+use ethers::utils::rlp::RlpStream;
+use log::warn;
+use serde::Deserialize;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+use crate::streams::consumers::hooks::ConsumerHook;
+
+/// A block trusted out of band (e.g. from a block explorer or a synced light client).
+#[derive(Debug, Clone, Deserialize)]
+pub struct VerificationCheckpoint {
+    pub block_number: u64,
+    pub hash: String,
+}
+
+/// Recomputes a block hash as `keccak256(rlp(header))`. Returns `None` if the provider omitted a
+/// field the hash depends on.
+///
+/// Header fields newer than the ones `ethers` models make the computed hash differ, so such
+/// blocks stay unverified rather than being trusted.
+pub fn compute_header_hash(block: &Block<Transaction>) -> Option<H256> {
+    let mut stream = RlpStream::new();
+    stream.begin_unbounded_list();
+    stream.append(&block.parent_hash);
+    stream.append(&block.uncles_hash);
+    stream.append(&block.author?);
+    stream.append(&block.state_root);
+    stream.append(&block.transactions_root);
+    stream.append(&block.receipts_root);
+    stream.append(&block.logs_bloom?);
+    stream.append(&block.difficulty);
+    stream.append(&block.number?);
+    stream.append(&block.gas_limit);
+    stream.append(&block.gas_used);
+    stream.append(&block.timestamp);
+    stream.append(&block.extra_data);
+    stream.append(&block.mix_hash?);
+    stream.append(&block.nonce?);
+    // Optional fields were appended by successive hard forks, in this order.
+    if let Some(base_fee) = block.base_fee_per_gas {
+        stream.append(&base_fee);
+    }
+    if let Some(withdrawals_root) = block.withdrawals_root {
+        stream.append(&withdrawals_root);
+    }
+    if let Some(blob_gas_used) = block.blob_gas_used {
+        stream.append(&blob_gas_used);
+    }
+    if let Some(excess_blob_gas) = block.excess_blob_gas {
+        stream.append(&excess_blob_gas);
+    }
+    if let Some(parent_beacon_block_root) = block.parent_beacon_block_root {
+        stream.append(&parent_beacon_block_root);
+    }
+    stream.finalize_unbounded_list();
+    Some(H256::from(keccak256(stream.out())))
+}
+
+/// Verifies ingested headers instead of trusting the RPC.
+///
+/// A block is `verified` when its header hashes to the hash the provider claimed and it is linked
+/// by parent hashes to the checkpoint through other verified blocks. Verification propagates in
+/// both directions, so blocks ingested out of order are verified once the gap is filled.
+pub struct HeaderVerifier {
+    pg_pool: Arc<PgPool>,
+    checkpoint_number: i64,
+    checkpoint_hash: H256,
+}
+
+impl HeaderVerifier {
+    pub fn new(pg_pool: Arc<PgPool>, checkpoint: &VerificationCheckpoint) -> Result<Self> {
+        Ok(Self {
+            pg_pool,
+            checkpoint_number: checkpoint.block_number as i64,
+            checkpoint_hash: checkpoint
+                .hash
+                .parse()
+                .with_context(|| format!("Invalid checkpoint hash `{}`", checkpoint.hash))?,
+        })
+    }
+
+    /// Whether the block is the checkpoint or adjacent to a verified block it links to.
+    async fn is_anchored(&self, chain_name: &str, block_number: i64, hash: &str, parent_hash: &str) -> Result<bool> {
+        if block_number == self.checkpoint_number {
+            return Ok(hash == format!("{:?}", self.checkpoint_hash));
+        }
+
+        let row = sqlx::query(
+            "SELECT EXISTS (
+                SELECT 1 FROM blocks
+                WHERE chain_name = $1 AND verified
+                  AND ((block_number = $2 - 1 AND hash = $4) OR (block_number = $2 + 1 AND parent_hash = $3))
+            ) AS anchored",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(hash)
+        .bind(parent_hash)
+        .fetch_one(self.pg_pool.as_ref())
+        .await?;
+        Ok(row.try_get("anchored")?)
+    }
+
+    /// Marks every header-valid block reachable by parent links from `hash` as verified.
+    async fn propagate(&self, chain_name: &str, hash: &str) -> Result<()> {
+        sqlx::query(
+            "WITH RECURSIVE descendants AS (
+                SELECT block_number, hash FROM blocks WHERE chain_name = $1 AND hash = $2
+                UNION ALL
+                SELECT b.block_number, b.hash FROM blocks b
+                JOIN descendants d ON b.parent_hash = d.hash AND b.block_number = d.block_number + 1
+                WHERE b.chain_name = $1 AND b.header_valid
+            ),
+            ancestors AS (
+                SELECT block_number, parent_hash FROM blocks WHERE chain_name = $1 AND hash = $2
+                UNION ALL
+                SELECT b.block_number, b.parent_hash FROM blocks b
+                JOIN ancestors a ON b.hash = a.parent_hash AND b.block_number = a.block_number - 1
+                WHERE b.chain_name = $1 AND b.header_valid
+            )
+            UPDATE blocks SET verified = TRUE
+            WHERE chain_name = $1 AND NOT verified
+              AND (hash IN (SELECT hash FROM descendants)
+                   OR (header_valid AND (block_number, hash) IN (SELECT block_number - 1, parent_hash FROM ancestors)))",
+        )
+        .bind(chain_name)
+        .bind(hash)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for HeaderVerifier {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let claimed_hash = block.hash.unwrap_or_default();
+        let hash = format!("{:?}", claimed_hash);
+        let parent_hash = format!("{:?}", block.parent_hash);
+
+        let header_valid = compute_header_hash(block) == Some(claimed_hash);
+        if !header_valid {
+            warn!("Header hash mismatch on {} block {} ({})", chain_name, block_number, hash);
+        }
+        let verified = header_valid && self.is_anchored(chain_name, block_number, &hash, &parent_hash).await?;
+
+        sqlx::query("UPDATE blocks SET header_valid = $3, verified = $4 WHERE chain_name = $1 AND hash = $2")
+            .bind(chain_name)
+            .bind(&hash)
+            .bind(header_valid)
+            .bind(verified)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+
+        if verified {
+            self.propagate(chain_name, &hash).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+pub mod headers;
+pub mod roots;
+pub mod timestamps;
+pub mod writes;
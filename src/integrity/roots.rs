@@ -0,0 +1,328 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction, TransactionReceipt, H256, U256};
+use ethers::utils::keccak256;
+use ethers::utils::rlp::RlpStream;
+use log::warn;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::metrics;
+use crate::streams::consumers::hooks::ConsumerHook;
+use crate::streams::sharding;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct IntegrityConfig {
+    pub enabled: bool,
+    /// Chains to verify; all of them when empty.
+    pub chains: Vec<String>,
+}
+
+impl Default for IntegrityConfig {
+    fn default() -> Self {
+        Self { enabled: false, chains: Vec::new() }
+    }
+}
+
+impl IntegrityConfig {
+    /// Whether blocks of `chain_name` are verified.
+    pub fn enabled_for(&self, chain_name: &str) -> bool {
+        self.enabled && (self.chains.is_empty() || self.chains.iter().any(|chain| chain == chain_name))
+    }
+}
+
+/// Transaction type of OP-stack deposits.
+const DEPOSIT_TX_TYPE: u64 = 0x7e;
+
+/// Canonical encoding of a transaction as it appears in the transactions trie. `ethers` covers
+/// legacy, EIP-2930 and EIP-1559 transactions, and blob transactions are encoded here; other
+/// (chain-specific) types have none.
+fn encode_transaction(tx: &Transaction) -> Option<Vec<u8>> {
+    match tx.transaction_type.map_or(0, |kind| kind.as_u64()) {
+        0..=2 => Some(tx.rlp().to_vec()),
+        3 => encode_blob_transaction(tx),
+        _ => None,
+    }
+}
+
+/// EIP-4844 encoding: `0x03 || rlp([chain_id, nonce, max_priority_fee_per_gas, max_fee_per_gas,
+/// gas_limit, to, value, data, access_list, max_fee_per_blob_gas, blob_versioned_hashes,
+/// y_parity, r, s])`. The blob fields are among the transaction's extra fields.
+fn encode_blob_transaction(tx: &Transaction) -> Option<Vec<u8>> {
+    let max_fee_per_blob_gas: U256 = tx.other.get_deserialized("maxFeePerBlobGas")?.ok()?;
+    let blob_versioned_hashes: Vec<H256> = tx.other.get_deserialized("blobVersionedHashes")?.ok()?;
+    let mut stream = RlpStream::new_list(14);
+    stream.append(&tx.chain_id?);
+    stream.append(&tx.nonce);
+    stream.append(&tx.max_priority_fee_per_gas?);
+    stream.append(&tx.max_fee_per_gas?);
+    stream.append(&tx.gas);
+    stream.append(&tx.to?);
+    stream.append(&tx.value);
+    stream.append(&tx.input.to_vec());
+    stream.append(&tx.access_list.clone().unwrap_or_default());
+    stream.append(&max_fee_per_blob_gas);
+    stream.append_list(&blob_versioned_hashes);
+    stream.append(&tx.v);
+    stream.append(&tx.r);
+    stream.append(&tx.s);
+
+    let mut encoded = vec![3];
+    encoded.extend_from_slice(&stream.out());
+    Some(encoded)
+}
+
+/// Canonical receipt encoding: `rlp([status, cumulative_gas, bloom, logs])`, prefixed with the
+/// transaction type for typed receipts. OP-stack deposit receipts append `depositNonce` (since
+/// Regolith) and `depositReceiptVersion` (since Canyon) when the node returns them.
+fn encode_receipt(receipt: &TransactionReceipt) -> Vec<u8> {
+    let kind = receipt.transaction_type.map_or(0, |kind| kind.as_u64());
+    let deposit_fields: Vec<u64> = if kind == DEPOSIT_TX_TYPE {
+        ["depositNonce", "depositReceiptVersion"]
+            .iter()
+            .map_while(|key| receipt.other.get_deserialized::<U256>(key)?.ok())
+            .map(|value| value.as_u64())
+            .collect()
+    } else {
+        Vec::new()
+    };
+
+    let mut stream = RlpStream::new_list(4 + deposit_fields.len());
+    match (receipt.status, receipt.root) {
+        (Some(status), _) => stream.append(&status),
+        // Pre-Byzantium receipts carry the post-transaction state root instead of a status.
+        (None, Some(root)) => stream.append(&root),
+        (None, None) => stream.append_empty_data(),
+    };
+    stream.append(&receipt.cumulative_gas_used);
+    stream.append(&receipt.logs_bloom);
+    stream.begin_list(receipt.logs.len());
+    for log in &receipt.logs {
+        stream.begin_list(3);
+        stream.append(&log.address);
+        stream.append_list(&log.topics);
+        stream.append(&log.data.to_vec());
+    }
+    for value in &deposit_fields {
+        stream.append(value);
+    }
+
+    let mut encoded = Vec::new();
+    if kind != 0 {
+        encoded.push(kind as u8);
+    }
+    encoded.extend_from_slice(&stream.out());
+    encoded
+}
+
+/// Converts bytes to nibbles, most significant first.
+fn to_nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+/// Hex-prefix encoding of a nibble path, as used in leaf and extension nodes.
+fn hex_prefix(nibbles: &[u8], is_leaf: bool) -> Vec<u8> {
+    let flag = if is_leaf { 2 } else { 0 };
+    let mut encoded = Vec::with_capacity(nibbles.len() / 2 + 1);
+    let rest = if nibbles.len() % 2 == 1 {
+        encoded.push(((flag + 1) << 4) | nibbles[0]);
+        &nibbles[1..]
+    } else {
+        encoded.push(flag << 4);
+        nibbles
+    };
+    for pair in rest.chunks(2) {
+        encoded.push((pair[0] << 4) | pair[1]);
+    }
+    encoded
+}
+
+/// Appends a reference to a child node: inlined if shorter than 32 bytes, hashed otherwise.
+fn append_child(stream: &mut RlpStream, node: &[u8]) {
+    if node.len() < 32 {
+        stream.append_raw(node, 1);
+    } else {
+        stream.append(&H256::from(keccak256(node)));
+    }
+}
+
+/// RLP-encodes the trie node holding `entries`, sorted by key and non-empty.
+fn encode_node(entries: &[(Vec<u8>, &[u8])], depth: usize) -> Vec<u8> {
+    if let [(key, value)] = entries {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(&key[depth..], true));
+        stream.append(&value.to_vec());
+        return stream.out().to_vec();
+    }
+
+    let first = &entries[0].0;
+    let last = &entries[entries.len() - 1].0;
+    let shared = first[depth..]
+        .iter()
+        .zip(&last[depth..])
+        .take_while(|(a, b)| a == b)
+        .count();
+    if shared > 0 {
+        let mut stream = RlpStream::new_list(2);
+        stream.append(&hex_prefix(&first[depth..depth + shared], false));
+        append_child(&mut stream, &encode_node(entries, depth + shared));
+        return stream.out().to_vec();
+    }
+
+    let mut stream = RlpStream::new_list(17);
+    let mut branch_value = None;
+    let mut start = 0;
+    if entries[0].0.len() == depth {
+        branch_value = Some(entries[0].1);
+        start = 1;
+    }
+    for nibble in 0..16u8 {
+        let end = start + entries[start..].iter().take_while(|(key, _)| key[depth] == nibble).count();
+        if end > start {
+            append_child(&mut stream, &encode_node(&entries[start..end], depth + 1));
+        } else {
+            stream.append_empty_data();
+        }
+        start = end;
+    }
+    match branch_value {
+        Some(value) => stream.append(&value.to_vec()),
+        None => stream.append_empty_data(),
+    };
+    stream.out().to_vec()
+}
+
+/// Root of the Merkle-Patricia trie mapping `rlp(index)` to each item, as used for the
+/// transactions and receipts roots.
+pub fn ordered_trie_root(items: &[Vec<u8>]) -> H256 {
+    if items.is_empty() {
+        // keccak256(rlp(""))
+        return H256::from(keccak256([0x80]));
+    }
+
+    let mut entries: Vec<(Vec<u8>, &[u8])> = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| {
+            let mut key = RlpStream::new();
+            key.append(&(index as u64));
+            (to_nibbles(&key.out()), item.as_slice())
+        })
+        .collect();
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+
+    H256::from(keccak256(encode_node(&entries, 0)))
+}
+
+/// Transactions root of a block's transactions, or `None` if one of them has no canonical
+/// encoding.
+pub fn transactions_root(transactions: &[Transaction]) -> Option<H256> {
+    let encoded: Option<Vec<Vec<u8>>> = transactions.iter().map(encode_transaction).collect();
+    Some(ordered_trie_root(&encoded?))
+}
+
+/// Receipts root of a block's receipts, in transaction order.
+pub fn receipts_root(receipts: &[TransactionReceipt]) -> H256 {
+    let encoded: Vec<Vec<u8>> = receipts.iter().map(encode_receipt).collect();
+    ordered_trie_root(&encoded)
+}
+
+/// Recomputes each block's transactions and receipts roots from the fetched data and compares
+/// them with the header. Blocks that don't match are flagged `quarantined` and recorded in
+/// `quarantined_blocks` so they can be re-fetched from another provider.
+pub struct RootVerifier {
+    adapter: Arc<dyn BlockchainAdapter>,
+    pg_pool: Arc<PgPool>,
+}
+
+impl RootVerifier {
+    pub fn new(adapter: Arc<dyn BlockchainAdapter>, pg_pool: Arc<PgPool>) -> Self {
+        Self { adapter, pg_pool }
+    }
+
+    async fn quarantine(
+        &self,
+        chain_name: &str,
+        block: &Block<Transaction>,
+        reason: &str,
+        expected_root: H256,
+        computed_root: H256,
+    ) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let hash = format!("{:?}", block.hash.unwrap_or_default());
+        warn!(
+            "Quarantining {} block {} ({}): {} expected {:?}, computed {:?}",
+            chain_name, block_number, hash, reason, expected_root, computed_root
+        );
+
+        let mut tx = self.pg_pool.begin().await?;
+        sqlx::query("UPDATE blocks SET quarantined = TRUE WHERE chain_name = $1 AND hash = $2")
+            .bind(chain_name)
+            .bind(&hash)
+            .execute(&mut tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO quarantined_blocks (chain_name, block_number, hash, reason, expected_root, computed_root)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (chain_name, hash, reason) DO UPDATE SET computed_root = EXCLUDED.computed_root, detected_at = NOW()",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(&hash)
+        .bind(reason)
+        .bind(format!("{:?}", expected_root))
+        .bind(format!("{:?}", computed_root))
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+
+        metrics::increment_counter("integrity_quarantined_blocks_total", &[("chain", chain_name), ("reason", reason)], 1);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for RootVerifier {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block
+            .number
+            .ok_or_else(|| anyhow!("Block without a number cannot be verified"))?
+            .as_u64();
+
+        // Chain-specific transaction types have no canonical encoding here, so only the
+        // receipts root can be checked for blocks containing them. The same goes for hash-only
+        // blocks, which carry none of their transactions.
+        let complete = sharding::tx_count(block) == block.transactions.len();
+        if let Some(computed_root) = transactions_root(&block.transactions).filter(|_| complete) {
+            if computed_root != block.transactions_root {
+                self.quarantine(chain_name, block, "transactions_root", block.transactions_root, computed_root)
+                    .await?;
+            }
+        }
+
+        // Receipts are fetched by height, so after a reorg they may be those of the block that
+        // replaced this one. Comparing them would quarantine a block that is fine.
+        let receipts = self.adapter.get_block_receipts(block_number).await?;
+        let other_block = receipts
+            .iter()
+            .find_map(|receipt| receipt.block_hash.filter(|hash| Some(*hash) != block.hash));
+        if let Some(other) = other_block {
+            warn!(
+                "Skipping receipts root of {} block {} ({:?}): receipts are of block {:?}",
+                chain_name, block_number, block.hash.unwrap_or_default(), other
+            );
+            metrics::increment_counter("integrity_skipped_blocks_total", &[("chain", chain_name), ("reason", "receipts_block_hash")], 1);
+            return Ok(());
+        }
+        let computed_root = receipts_root(&receipts);
+        if computed_root != block.receipts_root {
+            self.quarantine(chain_name, block, "receipts_root", block.receipts_root, computed_root)
+                .await?;
+        }
+
+        Ok(())
+    }
+}
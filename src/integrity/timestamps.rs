@@ -0,0 +1,125 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use log::warn;
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::metrics;
+use crate::streams::consumers::hooks::ConsumerHook;
+use crate::streams::schemas::evm::received_at;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct TimestampChecksConfig {
+    pub enabled: bool,
+    /// How far ahead of the ingesting host's clock a block's timestamp may be.
+    pub max_future_secs: i64,
+    /// How far behind the time the realtime producer received it a block's timestamp may be.
+    /// Backfilled blocks aren't held to this.
+    pub max_past_secs: i64,
+}
+
+impl Default for TimestampChecksConfig {
+    fn default() -> Self {
+        Self { enabled: false, max_future_secs: 15, max_past_secs: 600 }
+    }
+}
+
+/// Compares each committed block's timestamp with the ingesting host's clock and the previous
+/// block's, since a node serving bad data can otherwise skew every time-series built on the
+/// blocks. Skew is the block timestamp minus the time the realtime producer received the block,
+/// so a consumer catching up on a backlog doesn't look like a node serving stale blocks. The
+/// skew of the newest block is exported as `block_timestamp_skew_seconds`. Blocks too far in the
+/// future, realtime blocks too far in the past, and blocks older than their parent are recorded
+/// in `timestamp_anomalies`.
+pub struct TimestampChecker {
+    pg_pool: Arc<PgPool>,
+    max_future_secs: i64,
+    max_past_secs: i64,
+    /// Newest (block number, timestamp) per chain.
+    newest: Mutex<HashMap<String, (u64, i64)>>,
+}
+
+impl TimestampChecker {
+    pub fn new(pg_pool: Arc<PgPool>, config: &TimestampChecksConfig) -> Self {
+        Self {
+            pg_pool,
+            max_future_secs: config.max_future_secs,
+            max_past_secs: config.max_past_secs,
+            newest: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn record_anomaly(&self, chain_name: &str, block: &Block<Transaction>, kind: &str, skew_secs: i64, detail: String) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        warn!("{} block {} has a suspicious timestamp ({}): {}", chain_name, block_number, kind, detail);
+        sqlx::query(
+            "INSERT INTO timestamp_anomalies (chain_name, block_number, hash, block_timestamp, skew_secs, kind, detail)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (chain_name, hash, kind) DO NOTHING",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(block.timestamp.as_u64() as i64)
+        .bind(skew_secs)
+        .bind(kind)
+        .bind(detail)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        metrics::increment_counter("timestamp_anomalies_total", &[("chain", chain_name), ("kind", kind)], 1);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for TimestampChecker {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let timestamp = block.timestamp.as_u64() as i64;
+        // Backfilled blocks were received at some point before now. A timestamp ahead of now
+        // was ahead of that too, but one behind now says nothing about the node.
+        let received = received_at(block);
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+        let skew_secs = timestamp - received.unwrap_or(now);
+
+        let (is_newest, parent_timestamp) = {
+            let mut newest = self.newest.lock().unwrap_or_else(|e| e.into_inner());
+            let previous = newest.get(chain_name).copied();
+            let is_newest = previous.map_or(true, |(number, _)| block_number >= number);
+            if is_newest {
+                newest.insert(chain_name.to_string(), (block_number, timestamp));
+            }
+            // Only a parent seen right before can be compared without a lookup.
+            let parent_timestamp = previous.filter(|(number, _)| number + 1 == block_number).map(|(_, timestamp)| timestamp);
+            (is_newest, parent_timestamp)
+        };
+
+        if skew_secs > self.max_future_secs {
+            self.record_anomaly(chain_name, block, "future", skew_secs, format!("{}s ahead of the local clock", skew_secs)).await?;
+        }
+        if is_newest {
+            metrics::set_gauge("block_timestamp_skew_seconds", &[("chain", chain_name)], skew_secs as f64);
+        }
+        if received.is_some() && -skew_secs > self.max_past_secs {
+            self.record_anomaly(chain_name, block, "past", skew_secs, format!("{}s behind the time it was received", -skew_secs)).await?;
+        }
+        if let Some(parent_timestamp) = parent_timestamp {
+            if timestamp < parent_timestamp {
+                self.record_anomaly(
+                    chain_name,
+                    block,
+                    "before_parent",
+                    skew_secs,
+                    format!("{}s before its parent's timestamp", parent_timestamp - timestamp),
+                )
+                .await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,129 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use log::warn;
+use serde::Deserialize;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+use crate::metrics;
+use crate::streams::consumers::hooks::ConsumerHook;
+use crate::streams::sharding;
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct WriteVerificationConfig {
+    pub enabled: bool,
+    /// Verifies every n-th block by number; 1 verifies all of them.
+    pub sample_every: u64,
+}
+
+impl Default for WriteVerificationConfig {
+    fn default() -> Self {
+        Self { enabled: false, sample_every: 1 }
+    }
+}
+
+/// Reads each committed block back from Postgres and compares it with the message it was written
+/// from: the canonical row at its height must have its hash and `tx_count`, and every one of its
+/// transactions must have a canonical row. Differences are recorded in `write_mismatches`, so
+/// silent truncation or encoding loss between the consumer and the database shows up.
+pub struct WriteVerifier {
+    pg_pool: Arc<PgPool>,
+    sample_every: u64,
+}
+
+impl WriteVerifier {
+    pub fn new(pg_pool: Arc<PgPool>, config: &WriteVerificationConfig) -> Self {
+        Self { pg_pool, sample_every: config.sample_every.max(1) }
+    }
+
+    async fn record_mismatch(
+        &self,
+        chain_name: &str,
+        block_number: i64,
+        hash: &str,
+        field: &str,
+        expected: &str,
+        stored: &str,
+    ) -> Result<()> {
+        warn!(
+            "Read-back of {} block {} ({}) differs in {}: wrote {}, stored {}",
+            chain_name, block_number, hash, field, expected, stored
+        );
+        sqlx::query(
+            "INSERT INTO write_mismatches (chain_name, block_number, hash, field, expected, stored)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            ON CONFLICT (chain_name, hash, field) DO UPDATE SET stored = EXCLUDED.stored, detected_at = NOW()",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(hash)
+        .bind(field)
+        .bind(expected)
+        .bind(stored)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        metrics::increment_counter("write_verification_mismatches_total", &[("chain", chain_name), ("field", field)], 1);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for WriteVerifier {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block
+            .number
+            .ok_or_else(|| anyhow!("Block without a number cannot be verified"))?
+            .as_u64();
+        if block_number % self.sample_every != 0 {
+            return Ok(());
+        }
+        let block_number = block_number as i64;
+        let hash = format!("{:?}", block.hash.unwrap_or_default());
+        let tx_hashes: Vec<String> = block.transactions.iter().map(|transaction| format!("{:?}", transaction.hash)).collect();
+
+        // Looked up by height rather than hash, so a mangled hash still finds its row.
+        let row = sqlx::query(
+            "SELECT hash, tx_count,
+                (SELECT COUNT(DISTINCT tx_hash) FROM transactions
+                 WHERE chain_name = $1 AND block_number = $2 AND canonical AND tx_hash = ANY($3)) AS stored_transactions
+            FROM blocks WHERE chain_name = $1 AND block_number = $2 AND canonical",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(&tx_hashes)
+        .fetch_optional(self.pg_pool.as_ref())
+        .await?;
+
+        let Some(row) = row else {
+            return self.record_mismatch(chain_name, block_number, &hash, "row", "present", "missing").await;
+        };
+        let stored_hash: String = row.try_get("hash")?;
+        if stored_hash != hash {
+            self.record_mismatch(chain_name, block_number, &hash, "hash", &hash, &stored_hash).await?;
+        }
+        // Hash-only blocks carry none of their transactions, only their count.
+        let expected_tx_count = sharding::tx_count(block);
+        let tx_count: i64 = row.try_get("tx_count")?;
+        if tx_count != expected_tx_count as i64 {
+            self.record_mismatch(chain_name, block_number, &hash, "tx_count", &expected_tx_count.to_string(), &tx_count.to_string())
+                .await?;
+        }
+        let stored_transactions: i64 = row.try_get("stored_transactions")?;
+        if stored_transactions != tx_hashes.len() as i64 {
+            self.record_mismatch(
+                chain_name,
+                block_number,
+                &hash,
+                "transactions",
+                &tx_hashes.len().to_string(),
+                &stored_transactions.to_string(),
+            )
+            .await?;
+        }
+        metrics::increment_counter("write_verification_blocks_total", &[("chain", chain_name)], 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,244 @@
+use anyhow::Result;
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use crate::metrics;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LeaderElectionConfig {
+    pub enabled: bool,
+    /// Namespace of the Lease objects; the pod's own namespace when unset.
+    pub namespace: Option<String>,
+    /// Each chain's Lease is named `{lease_prefix}{chain}`, lowercased.
+    pub lease_prefix: String,
+    /// How long a Lease stays held without renewal before a standby may take it over.
+    pub lease_duration_secs: u64,
+    pub renew_interval_secs: u64,
+}
+
+impl Default for LeaderElectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            namespace: None,
+            lease_prefix: "ingestion-".to_string(),
+            lease_duration_secs: 15,
+            renew_interval_secs: 5,
+        }
+    }
+}
+
+/// Leadership of one chain, held through a Kubernetes Lease. Every replica builds the chain's
+/// adapters, sinks and subscriptions, but its producers and consumers only start once `wait`
+/// returns, so standbys are warm and take over within a lease duration of the leader going away.
+pub struct ChainLeader {
+    chain_name: String,
+    leading: watch::Sender<bool>,
+    #[cfg(feature = "k8s")]
+    lease: k8s::LeaseLock,
+}
+
+impl ChainLeader {
+    #[cfg(feature = "k8s")]
+    pub async fn new(chain_name: &str, config: &LeaderElectionConfig) -> Result<Arc<Self>> {
+        metrics::set_gauge("chain_leader", &[("chain", chain_name)], 0.0);
+        Ok(Arc::new(Self {
+            chain_name: chain_name.to_string(),
+            leading: watch::channel(false).0,
+            lease: k8s::LeaseLock::new(chain_name, config).await?,
+        }))
+    }
+
+    #[cfg(not(feature = "k8s"))]
+    pub async fn new(chain_name: &str, _config: &LeaderElectionConfig) -> Result<Arc<Self>> {
+        Err(anyhow::anyhow!("Leader election of {} needs the service built with the `k8s` feature", chain_name))
+    }
+
+    /// Waits until this replica leads the chain.
+    pub async fn wait(&self) {
+        let mut leading = self.leading.subscribe();
+        // The sender lives as long as `self`, so this only returns once leading.
+        let _ = leading.wait_for(|leading| *leading).await;
+    }
+
+    /// Waits until this replica no longer leads the chain.
+    pub async fn lost(&self) {
+        let mut leading = self.leading.subscribe();
+        let _ = leading.wait_for(|leading| !*leading).await;
+    }
+
+    /// Runs `task` while this replica leads the chain. If leadership is lost the task is stopped,
+    /// and started afresh once the replica leads again. Returns when the task ends by itself.
+    pub async fn while_leading<F, Fut>(&self, mut task: F) -> Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        loop {
+            self.wait().await;
+            tokio::select! {
+                result = task() => return result,
+                _ = self.lost() => {}
+            }
+        }
+    }
+
+    /// Acquires the chain's Lease and keeps renewing it. If leadership is lost the chain's tasks
+    /// stop and the replica stands by for the Lease again, rather than ingesting alongside the
+    /// new leader. Other chains are left running.
+    #[cfg(feature = "k8s")]
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        loop {
+            self.lease.acquire().await;
+            log::info!("Leading {}", self.chain_name);
+            metrics::set_gauge("chain_leader", &[("chain", &self.chain_name)], 1.0);
+            self.leading.send_replace(true);
+            self.lease.renew_until_lost().await;
+            log::error!("Lost leadership of {}, stopping its tasks and standing by", self.chain_name);
+            metrics::set_gauge("chain_leader", &[("chain", &self.chain_name)], 0.0);
+            metrics::increment_counter("chain_leadership_lost_total", &[("chain", &self.chain_name)], 1);
+            self.leading.send_replace(false);
+        }
+    }
+
+    #[cfg(not(feature = "k8s"))]
+    pub async fn run(self: Arc<Self>) -> Result<()> {
+        Err(anyhow::anyhow!("Leader election of {} needs the service built with the `k8s` feature", self.chain_name))
+    }
+}
+
+/// Runs `task` while this replica leads its chain, or straight away without leader election.
+pub async fn while_leading<F, Fut>(leader: Option<&ChainLeader>, mut task: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    match leader {
+        Some(leader) => leader.while_leading(task).await,
+        None => task().await,
+    }
+}
+
+#[cfg(feature = "k8s")]
+mod k8s {
+    use anyhow::{Context, Result};
+    use chrono::Utc;
+    use k8s_openapi::api::coordination::v1::{Lease, LeaseSpec};
+    use k8s_openapi::apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta};
+    use kube::api::{Api, PostParams};
+    use kube::{Client, Error};
+    use log::{info, warn};
+    use std::env;
+    use std::time::Duration;
+    use tokio::time::Instant;
+
+    use super::LeaderElectionConfig;
+
+    pub struct LeaseLock {
+        api: Api<Lease>,
+        name: String,
+        identity: String,
+        lease_duration: Duration,
+        renew_interval: Duration,
+    }
+
+    impl LeaseLock {
+        pub async fn new(chain_name: &str, config: &LeaderElectionConfig) -> Result<Self> {
+            let client = Client::try_default().await.context("Failed to create Kubernetes client")?;
+            let api = match &config.namespace {
+                Some(namespace) => Api::namespaced(client, namespace),
+                None => Api::default_namespaced(client),
+            };
+            // The pod name, which Kubernetes also sets as the hostname.
+            let identity = env::var("POD_NAME")
+                .or_else(|_| env::var("HOSTNAME"))
+                .context("Failed to get the pod name from environment for key `POD_NAME` or `HOSTNAME`")?;
+            Ok(Self {
+                api,
+                name: format!("{}{}", config.lease_prefix, chain_name).to_lowercase(),
+                identity,
+                lease_duration: Duration::from_secs(config.lease_duration_secs),
+                renew_interval: Duration::from_secs(config.renew_interval_secs.max(1)),
+            })
+        }
+
+        /// Takes the Lease if it is free, expired or already ours, and renews it. `Ok(false)` if
+        /// another replica holds it. Writes carry the read's resource version, so of two replicas
+        /// racing for an expired Lease only one succeeds.
+        async fn try_acquire(&self) -> Result<bool> {
+            let now = MicroTime(Utc::now());
+            let Some(mut lease) = self.api.get_opt(&self.name).await? else {
+                let lease = Lease {
+                    metadata: ObjectMeta { name: Some(self.name.clone()), ..Default::default() },
+                    spec: Some(LeaseSpec {
+                        holder_identity: Some(self.identity.clone()),
+                        lease_duration_seconds: Some(self.lease_duration.as_secs() as i32),
+                        acquire_time: Some(now.clone()),
+                        renew_time: Some(now),
+                        lease_transitions: Some(0),
+                        ..Default::default()
+                    }),
+                };
+                return match self.api.create(&PostParams::default(), &lease).await {
+                    Ok(_) => Ok(true),
+                    Err(Error::Api(response)) if response.code == 409 => Ok(false),
+                    Err(e) => Err(e.into()),
+                };
+            };
+
+            let spec = lease.spec.get_or_insert_with(LeaseSpec::default);
+            if spec.holder_identity.as_deref() != Some(self.identity.as_str()) {
+                let duration = chrono::Duration::seconds(spec.lease_duration_seconds.unwrap_or(0) as i64);
+                let expired = spec.renew_time.as_ref().map_or(true, |renewed| renewed.0 + duration < now.0);
+                if !expired {
+                    return Ok(false);
+                }
+                spec.holder_identity = Some(self.identity.clone());
+                spec.acquire_time = Some(now.clone());
+                spec.lease_transitions = Some(spec.lease_transitions.unwrap_or(0) + 1);
+            }
+            spec.lease_duration_seconds = Some(self.lease_duration.as_secs() as i32);
+            spec.renew_time = Some(now);
+            match self.api.replace(&self.name, &PostParams::default(), &lease).await {
+                Ok(_) => Ok(true),
+                Err(Error::Api(response)) if response.code == 409 => Ok(false),
+                Err(e) => Err(e.into()),
+            }
+        }
+
+        /// Waits until the Lease is ours.
+        pub async fn acquire(&self) {
+            info!("Waiting for Lease {} as {}", self.name, self.identity);
+            loop {
+                match self.try_acquire().await {
+                    Ok(true) => return,
+                    Ok(false) => {}
+                    Err(e) => warn!("Failed to acquire Lease {}: {}", self.name, e),
+                }
+                tokio::time::sleep(self.renew_interval).await;
+            }
+        }
+
+        /// Renews the Lease until another replica holds it, or until renewals have failed for
+        /// so long that one may have taken it over.
+        pub async fn renew_until_lost(&self) {
+            let mut last_renewed = Instant::now();
+            loop {
+                tokio::time::sleep(self.renew_interval).await;
+                match self.try_acquire().await {
+                    Ok(true) => last_renewed = Instant::now(),
+                    Ok(false) => return,
+                    Err(e) => {
+                        warn!("Failed to renew Lease {}: {}", self.name, e);
+                        if last_renewed.elapsed() + self.renew_interval >= self.lease_duration {
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
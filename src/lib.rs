@@ -1,132 +1,1489 @@
 pub mod streams;
 pub mod blockchain;
 pub mod storage;
+pub mod server;
+pub mod aggregation;
+pub mod enrichment;
+pub mod metrics;
+pub mod integrity;
+pub mod alerting;
+pub mod quotas;
+pub mod leader;
+pub mod startup;
+pub mod shutdown;
+pub mod recovery;
+pub mod logging;
+pub mod secrets;
 
-use anyhow::Context;
+use anyhow::{anyhow, Context};
 use tokio::runtime::Builder;
 use tokio::task;
 use anyhow::Result;
 use std::sync::Arc;
 use tokio::sync::Mutex;
-use log::error;
-use futures_util::future;
+use log::{error, info, warn};
 use std::env;
 use dotenv::dotenv;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use serde::Deserialize;
 use sqlx::PgPool;
 
+use crate::streams::message_queue::fanout::{Destination, FanOutQueue, MirrorQueueConfig};
+use crate::streams::message_queue::kafka::KafkaConfig;
+#[cfg(feature = "kafka")]
+use crate::streams::message_queue::kafka::KafkaQueue;
+use crate::streams::message_queue::kinesis::KinesisConfig;
+#[cfg(feature = "kinesis")]
+use crate::streams::message_queue::kinesis::KinesisQueue;
+use crate::streams::message_queue::pubsub::{PubSubConfig, PubSubQueue};
 use crate::streams::message_queue::pulsar::PulsarClient;
-use crate::blockchain::evm_adapter::EVMAdapter;
+use crate::streams::message_queue::pulsar_admin::{PulsarAdmin, PulsarConfig};
+use crate::streams::message_queue::rabbitmq::{RabbitMqConfig, RabbitMqQueue};
+use crate::streams::message_queue::encryption::{EncryptedQueue, EncryptionConfig, PayloadCipher};
+use crate::streams::message_queue::queue::MessageQueue;
+use crate::blockchain::adaptive_throttle::{AdaptiveThrottleAdapter, AdaptiveThrottleConfig};
+use crate::blockchain::archive_adapter::{RawArchiveAdapter, RawArchiveConfig};
+use crate::blockchain::etherscan_adapter::EtherscanAdapter;
+use crate::blockchain::fallback_adapter::{HistoryFallbackAdapter, HistoryFallbackConfig};
+use crate::blockchain::recording_adapter::{RecordReplayAdapter, RecordingMode, RpcRecordingConfig};
+use crate::blockchain::registry::{AdapterContext, AdapterRegistry};
+use crate::blockchain::time_search::{first_block_at_or_after, unix_seconds};
+use crate::storage::sinks::{for_consumer, Sink, SinkContext, SinkRegistry};
+use crate::storage::canonical::{CanonicalMapperRegistry, CanonicalSchemaConfig, CanonicalTableWriter};
+use crate::storage::db::{builtin_table_schema, run_table_migrations, stored_blocks_table, sync_indexes, IndexConfig, TableSchema};
 
-use crate::streams::producers::evm_producer::EVMProducer;
+use crate::streams::producers::evm_producer::{EVMProducer, HeadTopicConfig, TxDetail};
+use alloy_network_primitives::BlockTransactionsKind;
+use crate::streams::consumers::dedup::{DedupConfig, DedupWindow};
 use crate::streams::consumers::evm_consumer::EVMConsumer;
 use crate::streams::producers::producer::StreamProducer;
 use crate::streams::consumers::consumer::StreamConsumer;
+use crate::streams::consumers::hooks::ConsumerHook;
+use crate::streams::consumers::projections::{Projection, ProjectionHook};
+use crate::streams::consumers::redelivery::RedeliveryConfig;
+use crate::storage::sinks::circuit_breaker::{CircuitBreakerConfig, CircuitBreakerSink};
+use crate::startup::{wait_for, StartupConfig};
+use crate::shutdown::{Shutdown, ShutdownConfig};
+use crate::recovery::{ChainRecovery, RecoveryConfig};
+use crate::leader::{while_leading, ChainLeader, LeaderElectionConfig};
+use crate::server::admin::{self, AdminApiConfig, AdminState};
+use crate::server::dashboard::{self, DashboardConfig, DashboardState};
+use crate::server::push::{self, PushHook, PushHub, PushServerConfig};
+use crate::storage::notify::{NotifyConfig, NotifyHook};
+use crate::storage::search::{SearchConfig, SearchIndex};
+use crate::storage::overflow::{OverflowConfig, OverflowStore};
+use crate::storage::projection::ProjectionConfig;
+use crate::streams::redaction::{Redaction, RedactionConfig};
+use crate::streams::sampling::Sampler;
+use crate::streams::delivery::DeliveryMode;
+use crate::streams::lanes::{Lane, LaneMonitor, LanesConfig};
+use crate::streams::sharding::{consumer_topics, ShardConfig, ShardKey};
+use crate::streams::producers::cdc_producer::{CdcConfig, CdcProducer};
+use crate::streams::control::StreamControl;
+use crate::streams::producers::backfill_jobs::{BackfillConfig, BackfillJobRunner, BackfillOrder};
+use crate::streams::producers::watchdog::{Heartbeat, Watchdog, WatchdogConfig};
+use crate::streams::schemas::schema::{WireFormat, WireFormatConfig};
+use crate::streams::producers::routing_producer::{RouteConfig, RoutingProducer};
+use crate::aggregation::daily_stats::{AggregationConfig, DailyStatsAggregator};
+use crate::aggregation::address_activity::{AddressActivityConfig, AddressActivityIndexer};
+use crate::aggregation::block_flows::{BlockFlowAggregator, BlockFlowsConfig};
+use crate::alerting::{AlertHook, Alerter, AlertingConfig};
+use crate::alerting::slo::{LatencyTracker, SloConfig};
+use crate::alerting::stuck_transactions::{StuckTransactionMonitor, StuckTransactionsConfig};
+use crate::enrichment::balances::BalanceTracker;
+use crate::enrichment::token_balances::{TokenBalanceTracker, TokenBalancesConfig};
+use crate::enrichment::asset_transfers::AlchemyTransferBackfill;
+use crate::enrichment::abi::{AbiConfig, AbiRegistry};
+use crate::enrichment::bridges::{BridgeConfig, BridgeDecoder};
+use crate::enrichment::contract_metadata::{ContractCreationTracker, ContractMetadataConfig, ContractMetadataFetcher};
+use crate::enrichment::logs::{LogWriter, LogsConfig};
+use crate::enrichment::mempool::{MempoolConfig, PendingTransactionTracker};
+use crate::enrichment::mev::{MevDetectionConfig, MevDetector};
+use crate::enrichment::prices::{PriceFeedConfig, PriceRecorder};
+use crate::enrichment::priority_transfers::{PriorityTransferPruner, PriorityTransferPublisher, PriorityTransfersConfig};
+use crate::enrichment::receipts::{ReceiptStatusConfig, ReceiptStatusTracker};
+use crate::enrichment::fees::{FeeModelKind, FeeRecorder, FeesConfig};
+use crate::enrichment::validators::{ValidatorRewardTracker, ValidatorRewardsConfig};
+use crate::enrichment::safe::{SafeDecodingConfig, SafeTransactionDecoder};
+use crate::enrichment::selectors::{MethodDecoder, MethodDecodingConfig, SignatureDatabase};
+use crate::storage::retention::{RetentionConfig, RetentionPruner};
+use crate::storage::pipeline_metrics::{PipelineMetricsConfig, PipelineMetricsRecorder};
+use crate::secrets::{self, SecretRedactionConfig};
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::rpc_usage::{MeteredAdapter, RpcCostConfig, RpcUsageMeter};
+use crate::blockchain::rpc_quota::{PrioritizedAdapter, Priority, PriorityRateLimiter, RpcQuotaConfig};
+use crate::integrity::headers::{HeaderVerifier, VerificationCheckpoint};
+use crate::integrity::roots::{IntegrityConfig, RootVerifier};
+use crate::integrity::timestamps::{TimestampChecker, TimestampChecksConfig};
+use crate::integrity::writes::{WriteVerificationConfig, WriteVerifier};
+use crate::quotas::{QuotaAdapter, QuotaConfig, QuotaQueue, QuotaSink, QuotaTracker};
 
 #[derive(Debug, Deserialize)]
 pub struct BlockchainConfig {
     pub adapter_type: String,
+    pub chain_id: Option<u64>, // needed for canonical_schema, contract_metadata and bridges
     pub schemas: Vec<String>,
     pub http_url: String,
+    #[serde(default)]
     pub ws_url: String,
+    pub api_token: Option<String>, // env var holding the source API token (Firehose)
+    #[serde(default)]
+    pub adapter_options: toml::Table, // passed as-is to custom adapter types
     pub start_block: Option<u64>,
     pub end_block: Option<u64>,
+    pub start_time: Option<toml::value::Datetime>, // instead of start_block, the first block at or after this time
+    pub end_time: Option<toml::value::Datetime>, // instead of end_block, the last block before this time
+    #[serde(default)]
+    pub backfill_order: BackfillOrder, // "oldest_first" (default) or "newest_first"
+    #[serde(default)]
+    pub watched_addresses: Vec<String>,
+    pub balance_every_n_blocks: Option<u64>, // adding this turns on balance tracking for watched_addresses
+    pub transfers_source: Option<String>, // "alchemy" backfills token_transfers over start_block..end_block
+    pub history_fallback: Option<HistoryFallbackConfig>, // Etherscan-family source for pruned history
+    pub verification_checkpoint: Option<VerificationCheckpoint>, // adding this turns on header verification
+    pub rpc_recording: Option<RpcRecordingConfig>, // record source responses to disk, or replay them offline
+    pub raw_archive: Option<RawArchiveConfig>, // also land raw block responses in object storage as gzipped JSON
+    pub rpc_quota: Option<RpcQuotaConfig>, // shared request budget, with realtime ahead of backfill
+    pub adaptive_throttle: Option<AdaptiveThrottleConfig>, // backfill rate that backs off on 429s and timeouts
+    pub rpc_cost: Option<RpcCostConfig>, // adding this turns on provider credit accounting in rpc_usage
+    #[serde(default = "default_sink")]
+    pub sink: String, // "postgres", "clickhouse", "parquet", "delta", "mongodb", "scylla", "sqlite" or a registered "custom:<name>"
+    #[serde(default)]
+    pub projection: ProjectionConfig, // columns the sink doesn't persist, per table
+    pub overflow: Option<OverflowConfig>, // calldata and log data over a size cap go to object storage (postgres sink)
+    #[serde(default)]
+    pub routes: Vec<RouteConfig>, // matching transactions are also published to `{chain}-{name}` topics
+    #[serde(default)]
+    pub mirror_queues: Vec<MirrorQueueConfig>, // brokers every topic is also published to, e.g. for readers outside the pipeline
+    pub consumer_workers: Option<usize>, // adding this writes blocks from N parallel workers per consumer
+    #[serde(default)]
+    pub tx_detail: HashMap<String, TxDetail>, // per schema, "full" (default) or "hashes"
+    #[serde(default)]
+    pub delivery: HashMap<String, DeliveryMode>, // per schema, "at_least_once" (default) or "at_most_once"
+    #[serde(default)]
+    pub lanes: HashMap<String, Lane>, // per schema, "critical", "standard" (default) or "best_effort"
+    #[serde(default)]
+    pub shards: HashMap<String, ShardConfig>, // per schema, spread over `{topic}-shard-{n}` topics (or partitions) with a consumer each
+    #[serde(default)]
+    pub redaction: HashMap<String, RedactionConfig>, // per schema, address fields hashed or truncated before publishing
+    #[serde(default)]
+    pub sample_rate: HashMap<String, f64>, // per schema (or mempool), share of blocks published (all by default), stamped on each message
+    pub mev_detection: Option<MevDetectionConfig>, // adding this flags sandwiches, backruns and builder tags
+    pub prices: Option<PriceFeedConfig>, // adding this records USD prices per block window
+    pub priority_transfers: Option<PriorityTransfersConfig>, // allowlisted tokens for the low-latency transfer path
+    pub mempool: Option<MempoolConfig>, // adding this tracks pending transactions until mined, replaced or dropped
+    pub bridges: Option<BridgeConfig>, // bridge contracts whose transfers are decoded into bridge_transfers
+    pub validator_rewards: Option<ValidatorRewardsConfig>, // pairs the chain with its Beacon node for staking analytics
+    pub stuck_transactions: Option<StuckTransactionsConfig>, // alerts on stuck and nonce-gapped watched_addresses (needs mempool)
+    pub slo: Option<SloConfig>, // records block latency into latency_samples and alerts on latency SLO breaches
+    #[serde(default)]
+    pub fee_model: FeeModelKind, // "eip1559" (default), "op_stack" or "arbitrum", for the [fees] columns
+}
+
+fn default_sink() -> String {
+    "postgres".to_string()
+}
+
+/// Extension points for crates that embed the pipeline. `Registries::default()` holds the
+/// built-in implementations.
+#[derive(Clone, Default)]
+pub struct Registries {
+    pub adapters: AdapterRegistry,
+    pub sinks: SinkRegistry,
+    pub canonical_mappers: CanonicalMapperRegistry,
+    pub alert_hooks: Vec<Arc<dyn AlertHook>>, // alert destinations on top of [alerting] webhooks
+    pub projections: Vec<Arc<dyn Projection>>, // derived tables folded from the consumed blocks
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ConfigToml {
     pub blockchains: HashMap<String, BlockchainConfig>,
+    #[serde(default)]
+    pub push_server: PushServerConfig,
+    #[serde(default)]
+    pub admin_api: AdminApiConfig,
+    #[serde(default)]
+    pub dashboard: DashboardConfig,
+    #[serde(default)]
+    pub watchdog: WatchdogConfig,
+    #[serde(default)]
+    pub lanes: LanesConfig,
+    #[serde(default)]
+    pub shutdown: ShutdownConfig,
+    #[serde(default)]
+    pub recovery: RecoveryConfig,
+    #[serde(default)]
+    pub pipeline_metrics: PipelineMetricsConfig,
+    #[serde(default)]
+    pub secret_redaction: SecretRedactionConfig,
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    #[serde(default)]
+    pub search: SearchConfig,
+    #[serde(default)]
+    pub cdc: CdcConfig,
+    #[serde(default)]
+    pub head_topic: HeadTopicConfig,
+    #[serde(default)]
+    pub canonical_schema: CanonicalSchemaConfig,
+    #[serde(default)]
+    pub aggregation: AggregationConfig,
+    #[serde(default)]
+    pub address_activity: AddressActivityConfig,
+    #[serde(default)]
+    pub block_flows: BlockFlowsConfig,
+    #[serde(default)]
+    pub token_balances: TokenBalancesConfig,
+    #[serde(default)]
+    pub retention: RetentionConfig,
+    #[serde(default)]
+    pub integrity: IntegrityConfig,
+    #[serde(default)]
+    pub timestamp_checks: TimestampChecksConfig,
+    #[serde(default)]
+    pub write_verification: WriteVerificationConfig,
+    #[serde(default)]
+    pub receipts: ReceiptStatusConfig,
+    #[serde(default)]
+    pub fees: FeesConfig,
+    #[serde(default)]
+    pub method_decoding: MethodDecodingConfig,
+    #[serde(default)]
+    pub contract_metadata: ContractMetadataConfig,
+    #[serde(default)]
+    pub safe_transactions: SafeDecodingConfig,
+    #[serde(default)]
+    pub logs: LogsConfig,
+    #[serde(default)]
+    pub abis: AbiConfig,
+    #[serde(default)]
+    pub alerting: AlertingConfig,
+    #[serde(default)]
+    pub backfill: BackfillConfig,
+    #[serde(default)]
+    pub dedup: DedupConfig,
+    #[serde(default)]
+    pub redelivery: RedeliveryConfig,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    #[serde(default)]
+    pub startup: StartupConfig,
+    #[serde(default)]
+    pub leader_election: LeaderElectionConfig,
+    #[serde(default)]
+    pub quotas: HashMap<String, QuotaConfig>, // per tenant usage limits over its chains, e.g. [quotas.analytics]
+    #[serde(default)]
+    pub encryption: EncryptionConfig,
+    #[serde(default)]
+    pub sinks: HashMap<String, toml::Table>, // per sink type options, e.g. [sinks.clickhouse]
+    #[serde(default)]
+    pub wire_formats: WireFormatConfig,
+    #[serde(default)]
+    pub tables: HashMap<String, TableSchema>, // generated tables for schema streams, e.g. [tables.erc20_transfers]
+    #[serde(default)]
+    pub indexes: IndexConfig,
+    #[serde(default)]
+    pub queue_type: QueueType,
+    #[serde(default)]
+    pub pulsar: PulsarConfig,
+    #[serde(default)]
+    pub pubsub: PubSubConfig,
+    #[serde(default)]
+    pub rabbitmq: RabbitMqConfig,
+    #[serde(default)]
+    pub kafka: KafkaConfig,
+    #[serde(default)]
+    pub kinesis: KinesisConfig,
 }
 
-pub async fn run_ingestion(pool: Arc<PgPool>, pulsar: Arc<PulsarClient>) -> Result<()> {
+/// The broker blocks are published through.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "snake_case")]
+pub enum QueueType {
+    #[default]
+    Pulsar,
+    Pubsub,
+    Rabbitmq,
+    /// Needs the service built with the `kafka` feature.
+    Kafka,
+    /// Publish-only, so only usable for mirrors and bridge targets. Needs the `kinesis` feature.
+    Kinesis,
+}
+
+impl QueueType {
+    pub fn name(self) -> &'static str {
+        match self {
+            QueueType::Pulsar => "pulsar",
+            QueueType::Pubsub => "pubsub",
+            QueueType::Rabbitmq => "rabbitmq",
+            QueueType::Kafka => "kafka",
+            QueueType::Kinesis => "kinesis",
+        }
+    }
+}
+
+pub async fn run_ingestion(pool: Arc<PgPool>, queue: Arc<dyn MessageQueue>, registries: Registries) -> Result<()> {
+    let config = read_config()?;
+    run_pipeline(config, pool, queue, registries).await
+}
+
+/// Connects to a broker of `queue_type`, waiting out the startup grace period. On Pulsar the
+/// tenant, namespace, partitioned topics and topic policies are set up first where configured.
+pub async fn connect_queue(config: &ConfigToml, queue_type: QueueType) -> Result<Arc<dyn MessageQueue>> {
+    let queue: Arc<dyn MessageQueue> = match queue_type {
+        QueueType::Pulsar => {
+            let partitioned_topics = config.partitioned_topics();
+            if config.pulsar.provision || !config.pulsar.topics.is_empty() || !partitioned_topics.is_empty() {
+                let admin = PulsarAdmin::new(&config.pulsar)?;
+                if config.pulsar.provision {
+                    wait_for("Pulsar admin API", &config.startup, || admin.provision(&config.pulsar)).await?;
+                }
+                // Partitioned topics first, so per-topic policies don't create them unpartitioned.
+                for (topic, partitions) in partitioned_topics {
+                    admin.ensure_partitioned_topic(&config.pulsar, &topic, partitions).await?;
+                }
+                admin.apply_topic_policies(&config.pulsar).await?;
+            }
+            let pulsar_url = env::var("PULSAR_URL").unwrap_or_else(|_| "pulsar://127.0.0.1:6650".to_string());
+            let mut pulsar = wait_for("Pulsar", &config.startup, || PulsarClient::new(&pulsar_url)).await?;
+            // Set on each instance of an active-active pair, which need `[dedup] peers = true`.
+            if let Ok(instance_id) = env::var("INSTANCE_ID") {
+                pulsar = pulsar.with_instance_id(instance_id);
+            }
+            Arc::new(pulsar)
+        }
+        QueueType::Pubsub => Arc::new(wait_for("Pub/Sub", &config.startup, || PubSubQueue::new(&config.pubsub)).await?),
+        QueueType::Rabbitmq => {
+            let chains: Vec<String> = config.blockchains.keys().cloned().collect();
+            let primary = config.queue_type == QueueType::Rabbitmq;
+            Arc::new(wait_for("RabbitMQ", &config.startup, || RabbitMqQueue::new(&config.rabbitmq, &chains, primary)).await?)
+        }
+        #[cfg(feature = "kafka")]
+        QueueType::Kafka => Arc::new(wait_for("Kafka", &config.startup, || KafkaQueue::new(&config.kafka)).await?),
+        #[cfg(feature = "kinesis")]
+        QueueType::Kinesis => Arc::new(wait_for("Kinesis", &config.startup, || KinesisQueue::new(&config.kinesis)).await?),
+        #[cfg(not(all(feature = "kafka", feature = "kinesis")))]
+        queue_type => {
+            let name = queue_type.name();
+            return Err(anyhow!("`{}` queues need the service built with the `{}` feature", name, name));
+        }
+    };
+    Ok(queue)
+}
+
+impl ConfigToml {
+    /// Topic names (without prefix) of the schemas sharded over Pulsar partitions, with their
+    /// partition count.
+    pub fn partitioned_topics(&self) -> Vec<(String, usize)> {
+        let mut topics = Vec::new();
+        for (chain_name, chain_cfg) in &self.blockchains {
+            for (schema, sharding) in chain_cfg.shards.iter().filter(|(_, sharding)| sharding.partitioned) {
+                let topic = format!("{}-{}", chain_name, schema);
+                topics.push((format!("{}-historical", topic), sharding.count));
+                topics.push((topic, sharding.count));
+            }
+        }
+        topics
+    }
+}
+
+/// Loads the configuration from `blockchains.toml`.
+pub fn read_config() -> Result<ConfigToml> {
     // Load environment variables from the .env file.
     dotenv().ok();
 
     // 1) Load the configuration from `blockchains.toml`.
     let config_str = std::fs::read_to_string("blockchains.toml")
         .context("Failed to read blockchains.toml")?;
-    let mut config: ConfigToml = toml::from_str(&config_str)
+    load_config(&config_str)
+}
+
+/// Parses a `blockchains.toml` document and resolves the environment variables it names.
+pub fn load_config(config_str: &str) -> Result<ConfigToml> {
+    let mut config: ConfigToml = toml::from_str(config_str)
         .context("Failed to parse blockchains.toml")?;
+    // Before anything below can log or fail with a secret.
+    secrets::configure(&config.secret_redaction)?;
 
     // 2) Substitute placeholders with actual values from environment variables.
     for (_, chain_cfg) in config.blockchains.iter_mut() {
+        // Replayed chains never contact their endpoints.
+        if chain_cfg.rpc_recording.as_ref().map_or(false, |recording| recording.mode == RecordingMode::Replay) {
+            continue;
+        }
         chain_cfg.http_url = env::var(&chain_cfg.http_url)
             .with_context(|| format!("Failed to get HTTP URL from environment for key `{}`", &chain_cfg.http_url))?;
-        chain_cfg.ws_url = env::var(&chain_cfg.ws_url)
-            .with_context(|| format!("Failed to get WebSocket URL from environment for key `{}`", &chain_cfg.ws_url))?;
+        // Sources without a WebSocket endpoint (e.g. Firehose) leave `ws_url` unset.
+        if !chain_cfg.ws_url.is_empty() {
+            chain_cfg.ws_url = env::var(&chain_cfg.ws_url)
+                .with_context(|| format!("Failed to get WebSocket URL from environment for key `{}`", &chain_cfg.ws_url))?;
+        }
+        if let Some(api_token) = &chain_cfg.api_token {
+            chain_cfg.api_token = Some(env::var(api_token)
+                .with_context(|| format!("Failed to get API token from environment for key `{}`", api_token))?);
+        }
+        if let Some(fallback) = chain_cfg.history_fallback.as_mut() {
+            fallback.etherscan.api_url = env::var(&fallback.etherscan.api_url)
+                .with_context(|| format!("Failed to get fallback API URL from environment for key `{}`", &fallback.etherscan.api_url))?;
+            fallback.etherscan.api_key = env::var(&fallback.etherscan.api_key)
+                .with_context(|| format!("Failed to get fallback API key from environment for key `{}`", &fallback.etherscan.api_key))?;
+        }
+        if let Some(validator_rewards) = chain_cfg.validator_rewards.as_mut() {
+            validator_rewards.beacon_url = env::var(&validator_rewards.beacon_url)
+                .with_context(|| format!("Failed to get Beacon URL from environment for key `{}`", &validator_rewards.beacon_url))?;
+        }
+        if let Some(prices) = chain_cfg.prices.as_mut() {
+            if let Some(api_key) = &prices.api_key {
+                prices.api_key = Some(env::var(api_key)
+                    .with_context(|| format!("Failed to get price API key from environment for key `{}`", api_key))?);
+            }
+        }
+    }
+
+    if config.contract_metadata.enabled {
+        if let Some(api_key) = &config.contract_metadata.etherscan_api_key {
+            config.contract_metadata.etherscan_api_key = Some(env::var(api_key)
+                .with_context(|| format!("Failed to get Etherscan API key from environment for key `{}`", api_key))?);
+        }
+    }
+
+    for webhook in config.alerting.webhooks.iter_mut() {
+        *webhook = env::var(&*webhook)
+            .with_context(|| format!("Failed to get alert webhook URL from environment for key `{}`", webhook))?;
+    }
+
+    // Blocks and transactions carry flattened fields that bincode cannot decode; only the CDC
+    // envelopes can use it.
+    let wire_formats = &config.wire_formats;
+    if let Some((topic, _)) = wire_formats.topics.iter().find(|(topic, format)| **format == WireFormat::Bincode && !topic.ends_with("-cdc")) {
+        return Err(anyhow!("bincode can only be used for -cdc topics, not `{}`", topic));
+    }
+    if wire_formats.default == WireFormat::Bincode {
+        return Err(anyhow!("bincode can only be used for -cdc topics, not as the default wire format"));
+    }
+    if config.queue_type == QueueType::Kinesis {
+        return Err(anyhow!("Kinesis is publish-only, so it can be a mirror queue or bridge target but not the queue_type"));
+    }
+    for (chain_name, chain_cfg) in &config.blockchains {
+        if chain_cfg.start_time.is_some() && chain_cfg.start_block.is_some() {
+            return Err(anyhow!("{} sets both start_time and start_block", chain_name));
+        }
+        if chain_cfg.end_time.is_some() && chain_cfg.end_block.is_some() {
+            return Err(anyhow!("{} sets both end_time and end_block", chain_name));
+        }
+        // The mempool isn't published as a schema stream, so it has no delivery mode either.
+        if let Some(schema) = chain_cfg.delivery.keys().find(|schema| !chain_cfg.schemas.contains(schema)) {
+            return Err(anyhow!("delivery of {} names `{}`, which is not one of its schemas", chain_name, schema));
+        }
+        if chain_cfg.mirror_queues.iter().any(|mirror| mirror.queue_type == config.queue_type) {
+            return Err(anyhow!("Mirror queue of {} is `{}`, which it already publishes to", chain_name, config.queue_type.name()));
+        }
+    }
+
+    Ok(config)
+}
+
+/// Runs producers and consumers for every configured chain until they all exit.
+pub async fn run_pipeline(config: ConfigToml, pool: Arc<PgPool>, queue: Arc<dyn MessageQueue>, registries: Registries) -> Result<()> {
+    // Encrypt the configured topics' payloads for every producer and consumer below.
+    let queue: Arc<dyn MessageQueue> = match PayloadCipher::new(&config.encryption).context("Failed to load payload encryption keys")? {
+        Some(cipher) => Arc::new(EncryptedQueue::new(queue, cipher)),
+        None => queue,
+    };
+    // Connect the brokers chains mirror their topics to, encrypted the same way.
+    let mut mirror_queues: HashMap<QueueType, Arc<dyn MessageQueue>> = HashMap::new();
+    for mirror in config.blockchains.values().flat_map(|chain_cfg| &chain_cfg.mirror_queues) {
+        if mirror_queues.contains_key(&mirror.queue_type) {
+            continue;
+        }
+        let mirror_queue = connect_queue(&config, mirror.queue_type).await?;
+        let mirror_queue: Arc<dyn MessageQueue> = match PayloadCipher::new(&config.encryption)? {
+            Some(cipher) => Arc::new(EncryptedQueue::new(mirror_queue, cipher)),
+            None => mirror_queue,
+        };
+        mirror_queues.insert(mirror.queue_type, mirror_queue);
     }
 
     // 3) Prepare the topic prefix for producers.
-    let producer_topic_prefix = "persistent://public/default/".to_string();
+    let producer_topic_prefix = config.pulsar.topic_prefix();
 
     // 4) Prepare tasks for producing messages.
     let mut tasks = Vec::new();
+    // Kept apart so shutdown can stop producers before draining consumers.
+    let mut producer_tasks = Vec::new();
+    let mut consumer_tasks = Vec::new();
+    let shutdown = Shutdown::new();
     let mut consumers_vec = Vec::new();
+    // Per-chain hooks, attached to the consumers of the chain's primary schema so they run once per block.
+    let mut chain_hooks: HashMap<String, (String, Vec<Arc<dyn ConsumerHook>>)> = HashMap::new();
+    // One sink per chain, shared by all of the chain's consumers.
+    let mut chain_sinks: HashMap<String, Arc<dyn Sink>> = HashMap::new();
+    let mut chain_adapters: HashMap<String, Arc<dyn BlockchainAdapter>> = HashMap::new();
+    // Realtime producers' heartbeats by chain, which give the dashboard each chain's head.
+    let mut chain_heartbeats: HashMap<String, Vec<Arc<Heartbeat>>> = HashMap::new();
+    let mut chain_recoveries: Vec<ChainRecovery> = Vec::new();
+    let mut chain_consumer_workers: HashMap<String, usize> = HashMap::new();
+    let mut schema_tx_detail: HashMap<(String, String), BlockTransactionsKind> = HashMap::new();
+    let mut schema_delivery: HashMap<(String, String), DeliveryMode> = HashMap::new();
+    let mut schema_lanes: HashMap<(String, String), Lane> = HashMap::new();
+    // Shard topics of address-sharded streams whose consumers leave block rows to shard 0.
+    let mut transactions_only_topics: HashSet<String> = HashSet::new();
+    // Shard topics of address-sharded streams, whose parts wait for their fan-out commit marker.
+    let mut staged_topics: HashSet<String> = HashSet::new();
+    // First shard topics of address-sharded streams, whose consumers get whole blocks, store the
+    // block row with shard 0's transactions and run the hooks.
+    let mut owner_topics: HashMap<String, ShardConfig> = HashMap::new();
+    // Backfill ranges written to Postgres, which deferred index creation waits for.
+    let mut postgres_backfills: Vec<(String, &'static str, u64, u64)> = Vec::new();
+    let mut chain_leaders: HashMap<String, Arc<ChainLeader>> = HashMap::new();
+    // Chains with the postgres sink, whose `blocks` and `transactions` rows hooks may read.
+    let mut postgres_chains: HashSet<String> = HashSet::new();
+
+    // Start the optional push server that re-broadcasts consumed data to WebSocket/SSE subscribers.
+    let push_hub = if config.push_server.enabled {
+        let hub = Arc::new(PushHub::new(config.push_server.channel_capacity));
+        let hub_clone = Arc::clone(&hub);
+        let push_config = config.push_server;
+        tasks.push(task::spawn(async move {
+            push::serve(&push_config, hub_clone).await
+        }));
+        Some(hub)
+    } else {
+        None
+    };
+
+    // Start the optional admin API for status, stream pauses and backfill retries.
+    if config.admin_api.enabled {
+        let admin_state = Arc::new(AdminState::new(Arc::clone(&pool), &config.admin_api).context("Failed to set up admin API")?);
+        let admin_config = config.admin_api;
+        tasks.push(task::spawn(async move {
+            admin::serve(&admin_config, admin_state).await
+        }));
+    }
+
+    // Start the retention pruner if any policy is configured.
+    if !config.retention.policies.is_empty() {
+        let chains: Vec<String> = config.blockchains.keys().cloned().collect();
+        let pruner = RetentionPruner::new(Arc::clone(&pool), &config.retention, chains)
+            .context("Failed to create RetentionPruner")?;
+        tasks.push(task::spawn(async move {
+            pruner.run().await
+        }));
+    }
+
+    let alerter = Alerter::new(&config.alerting, &registries.alert_hooks);
+
+    // Streams paused through the CLI, picked up by the producers while they run.
+    let stream_control = Arc::new(StreamControl::load(Arc::clone(&pool)).await.context("Failed to load stream pauses")?);
+    let stream_control_clone = Arc::clone(&stream_control);
+    tasks.push(task::spawn(async move {
+        stream_control_clone.run().await
+    }));
+
+    // Critical streams start first and best-effort ones shed load while they fall behind.
+    let lane_monitor = Arc::new(LaneMonitor::new(&config.lanes));
+
+    // ABIs for decoding, shared by every chain.
+    let abi_registry = Arc::new(AbiRegistry::load(&config.abis).context("Failed to load ABIs")?);
+    let signatures = if config.method_decoding.enabled {
+        Some(Arc::new(SignatureDatabase::load(&config.method_decoding)?))
+    } else {
+        None
+    };
+    let search_index = if config.search.enabled {
+        Some(SearchIndex::connect(&config.search).await.context("Failed to set up search index")?)
+    } else {
+        None
+    };
+
+    // Schemas without a hand-written migration get a generated table when a Postgres chain enables them.
+    let mut generated_tables: Vec<(String, TableSchema)> = Vec::new();
+    for chain_cfg in config.blockchains.values().filter(|chain_cfg| chain_cfg.sink == "postgres") {
+        for schema in &chain_cfg.schemas {
+            if schema == "blocks" || schema == "transactions" || schema == "logs" || schema == "headers" || generated_tables.iter().any(|(table, _)| table == schema) {
+                continue;
+            }
+            if let Some(table) = config.tables.get(schema).cloned().or_else(|| builtin_table_schema(schema)) {
+                generated_tables.push((schema.clone(), table));
+            }
+        }
+    }
+    run_table_migrations(&pool, &generated_tables)
+        .await
+        .context("Failed to create generated schema tables")?;
+
+    // One tracker per tenant, shared by the tenant's chains.
+    let mut chain_quotas: HashMap<String, Arc<QuotaTracker>> = HashMap::new();
+    for (tenant, quota) in &config.quotas {
+        let mut streams = Vec::new();
+        for chain_name in &quota.chains {
+            let chain_cfg = config
+                .blockchains
+                .get(chain_name)
+                .ok_or_else(|| anyhow!("Quota {} covers unknown chain {}", tenant, chain_name))?;
+            streams.extend(chain_cfg.schemas.iter().map(|schema| (chain_name.clone(), schema.clone())));
+        }
+        let tracker = Arc::new(QuotaTracker::new(tenant, quota.clone(), streams, Arc::clone(&pool), alerter.clone()));
+        for chain_name in &quota.chains {
+            if chain_quotas.insert(chain_name.clone(), Arc::clone(&tracker)).is_some() {
+                return Err(anyhow!("Chain {} is covered by more than one quota", chain_name));
+            }
+        }
+        tasks.push(task::spawn(async move {
+            tracker.run().await
+        }));
+    }
 
     // For each blockchain in the configuration.
-    for (chain_name, chain_cfg) in config.blockchains {
-        match chain_cfg.adapter_type.as_str() {
-            "EVM" => {
-                // Create an EVM-based adapter.
-                let adapter = EVMAdapter::new(
-                    &chain_name,
-                    &chain_cfg.http_url,
-                    &chain_cfg.ws_url,
+    for (chain_name, mut chain_cfg) in config.blockchains {
+        // Publish to the chain's mirror brokers too.
+        let queue: Arc<dyn MessageQueue> = if chain_cfg.mirror_queues.is_empty() {
+            Arc::clone(&queue)
+        } else {
+            let mirrors = chain_cfg
+                .mirror_queues
+                .iter()
+                .map(|mirror| Destination {
+                    name: mirror.queue_type.name().to_string(),
+                    queue: Arc::clone(&mirror_queues[&mirror.queue_type]),
+                    required: mirror.required,
+                })
+                .collect();
+            let primary = Destination { name: config.queue_type.name().to_string(), queue: Arc::clone(&queue), required: true };
+            Arc::new(FanOutQueue::new(primary, mirrors))
+        };
+        let quota = chain_quotas.get(&chain_name).cloned();
+        // Count what the chain publishes against its quota.
+        let queue: Arc<dyn MessageQueue> = match &quota {
+            Some(tracker) => Arc::new(QuotaQueue::new(Arc::clone(&queue), Arc::clone(tracker))),
+            None => Arc::clone(&queue),
+        };
+
+        // Create the chain's source adapter.
+        let adapter: Arc<dyn BlockchainAdapter> = match &chain_cfg.rpc_recording {
+            Some(recording) if recording.mode == RecordingMode::Replay => Arc::new(
+                RecordReplayAdapter::replay(&recording.dir)
+                    .context(format!("Failed to open RPC recording for {}", chain_name))?,
+            ),
+            _ => {
+                let context = AdapterContext {
+                    chain_name: chain_name.clone(),
+                    http_url: chain_cfg.http_url.clone(),
+                    ws_url: chain_cfg.ws_url.clone(),
+                    api_token: chain_cfg.api_token.clone(),
+                    options: chain_cfg.adapter_options.clone(),
+                    // Full blocks unless every schema of the chain only needs hashes.
+                    tx_detail: if !chain_cfg.schemas.is_empty()
+                        && chain_cfg.schemas.iter().all(|schema| chain_cfg.tx_detail.get(schema) == Some(&TxDetail::Hashes))
+                    {
+                        BlockTransactionsKind::Hashes
+                    } else {
+                        BlockTransactionsKind::Full
+                    },
+                };
+                // The endpoint may still be starting, so it gets the startup grace period.
+                let created = wait_for(&format!("RPC endpoint of {}", chain_name), &config.startup, || async {
+                    match registries.adapters.create(&chain_cfg.adapter_type, context.clone()).await {
+                        Some(adapter) => {
+                            let adapter = adapter?;
+                            adapter.get_latest_block_number().await?;
+                            Ok(Some(adapter))
+                        }
+                        None => Ok(None),
+                    }
+                })
+                .await?;
+                match created {
+                    Some(adapter) => adapter,
+                    None => {
+                        error!("Unknown adapter_type `{}` for chain `{}`. Skipping.", chain_cfg.adapter_type, chain_name);
+                        continue;
+                    }
+                }
+            }
+        };
+
+        // Count requests against the provider's credit budget. Replayed chains make no billed calls.
+        let replaying = matches!(&chain_cfg.rpc_recording, Some(recording) if recording.mode == RecordingMode::Replay);
+        let adapter: Arc<dyn BlockchainAdapter> = match &chain_cfg.rpc_cost {
+            Some(cost) if !replaying => {
+                let meter = Arc::new(RpcUsageMeter::new(Arc::clone(&pool), &chain_name, cost.clone()));
+                let meter_clone = Arc::clone(&meter);
+                tasks.push(task::spawn(async move {
+                    meter_clone.run().await
+                }));
+                Arc::new(MeteredAdapter::new(adapter, meter))
+            }
+            _ => adapter,
+        };
+
+        // Count requests against the chain's quota, and hold them back while it throttles.
+        let adapter: Arc<dyn BlockchainAdapter> = match &quota {
+            Some(tracker) => Arc::new(QuotaAdapter::new(adapter, Arc::clone(tracker))),
+            None => adapter,
+        };
+
+        // Record everything the source returns, so it can be replayed elsewhere.
+        let adapter: Arc<dyn BlockchainAdapter> = match &chain_cfg.rpc_recording {
+            Some(recording) if recording.mode == RecordingMode::Record => {
+                Arc::new(RecordReplayAdapter::record(adapter, &recording.dir))
+            }
+            _ => adapter,
+        };
+
+        // Archive raw block responses, so schemas can be re-derived without the node.
+        let adapter: Arc<dyn BlockchainAdapter> = match &chain_cfg.raw_archive {
+            Some(archive) => Arc::new(
+                RawArchiveAdapter::new(adapter, &chain_name, archive)
+                    .context(format!("Failed to open raw archive for {}", chain_name))?,
+            ),
+            None => adapter,
+        };
+
+        // Split the provider's request quota so backfill cannot starve the head stream.
+        let (adapter, backfill_adapter): (Arc<dyn BlockchainAdapter>, Arc<dyn BlockchainAdapter>) = match &chain_cfg.rpc_quota {
+            Some(quota) => {
+                let limiter = Arc::new(PriorityRateLimiter::new(quota));
+                (
+                    Arc::new(PrioritizedAdapter::new(Arc::clone(&adapter), Arc::clone(&limiter), Priority::Realtime)),
+                    Arc::new(PrioritizedAdapter::new(adapter, limiter, Priority::Backfill)),
                 )
+            }
+            None => (Arc::clone(&adapter), adapter),
+        };
+
+        // Back off the backfill request rate when the provider starts throttling.
+        let backfill_adapter: Arc<dyn BlockchainAdapter> = match &chain_cfg.adaptive_throttle {
+            Some(throttle) => Arc::new(AdaptiveThrottleAdapter::new(backfill_adapter, &chain_name, throttle)),
+            None => backfill_adapter,
+        };
+        // Best-effort realtime streams share backfill's side of the quota, leaving realtime's to the rest.
+        let best_effort_adapter = Arc::clone(&backfill_adapter);
+
+        chain_cfg
+            .projection
+            .validate()
+            .with_context(|| format!("Invalid projection for {}", chain_name))?;
+        for (schema, sharding) in &chain_cfg.shards {
+            sharding.validate().with_context(|| format!("Invalid shards for {} {}", chain_name, schema))?;
+            // Senders are only known from full transactions.
+            if sharding.key == ShardKey::Address && chain_cfg.tx_detail.get(schema) == Some(&TxDetail::Hashes) {
+                return Err(anyhow!("Address sharding of {} {} needs full tx_detail", chain_name, schema));
+            }
+            if sharding.key == ShardKey::Address && schema == "headers" {
+                return Err(anyhow!("Headers of {} have no transactions to shard by address", chain_name));
+            }
+            if sharding.partitioned && config.queue_type != QueueType::Pulsar {
+                return Err(anyhow!("Partitioned shards of {} {} need the Pulsar queue", chain_name, schema));
+            }
+        }
+        let overflow = chain_cfg
+            .overflow
+            .as_ref()
+            .map(|overflow| OverflowStore::new(&chain_name, overflow).map(Arc::new))
+            .transpose()
+            .with_context(|| format!("Failed to open overflow storage for {}", chain_name))?;
+        let sink_context = SinkContext {
+            chain_name: chain_name.clone(),
+            pg_pool: Arc::clone(&pool),
+            options: config.sinks.get(&chain_cfg.sink).cloned().unwrap_or_default(),
+            projection: chain_cfg.projection.clone(),
+            overflow: overflow.clone(),
+            adapter: Arc::clone(&best_effort_adapter),
+        };
+        let sink = match registries.sinks.create(&chain_cfg.sink, sink_context).await {
+            Some(sink) => sink.context(format!("Failed to create {} sink for {}", chain_cfg.sink, chain_name))?,
+            None => {
+                error!("Unknown sink `{}` for chain `{}`. Skipping.", chain_cfg.sink, chain_name);
+                continue;
+            }
+        };
+
+        // Backfill windows given as times become block numbers, searched for on the chain itself.
+        if let Some(start_time) = &chain_cfg.start_time {
+            let start_block = first_block_at_or_after(adapter.as_ref(), unix_seconds(start_time)?)
+                .await
+                .with_context(|| format!("Failed to find the first block of {} at {}", chain_name, start_time))?
+                .ok_or_else(|| anyhow!("start_time {} of {} is after its head", start_time, chain_name))?;
+            info!("{} reached {} at block {}", chain_name, start_time, start_block);
+            chain_cfg.start_block = Some(start_block);
+        }
+        if let Some(end_time) = &chain_cfg.end_time {
+            // A window reaching past the head ends at the head at startup, as without an end_block.
+            let end_block = first_block_at_or_after(adapter.as_ref(), unix_seconds(end_time)?)
                 .await
-                .context(format!("Failed to create EVMAdapter for {}", chain_name))?;
-
-                // For each schema in the chain_cfg.schemas create a producer for each schema.
-                for schema in chain_cfg.schemas {
-                    // Create a producer for each schema.
-                    let producer_topic = format!("{}{}-{}", &producer_topic_prefix, &chain_name, &schema);
-
-                    // Add the producer_topic to the consumers_vec.
-                    consumers_vec.push((chain_name.clone(), producer_topic.clone()));
-
-                    // Clone the adapter for different tasks.
-                    let adapter_clone_rt = Arc::new(Mutex::new(adapter.clone()));
-
-                    // Historical ingestion task (if a start_block is provided).
-                    if let Some(start_block) = chain_cfg.start_block {
-                        let producer_topic_hist = producer_topic.clone() + "-historical";
-                        consumers_vec.push((chain_name.clone(), producer_topic_hist.clone()));
-
-                        let adapter_clone_hist = Arc::new(Mutex::new(adapter.clone()));
-                        let pulsar_clone_hist = Arc::clone(&pulsar);
-
-                        let end_block = chain_cfg.end_block.unwrap_or(u64::MAX);
-                        tasks.push(task::spawn_blocking(move || {
-                            let rt = Builder::new_multi_thread().enable_all().build().unwrap();
-                            rt.block_on(async move {
-                                // Create an EVMProducer for historical production.
-                                let evm_producer = EVMProducer::new(adapter_clone_hist, pulsar_clone_hist, producer_topic_hist).await?;
-                                evm_producer.produce_historical(start_block, end_block).await?;
-                                Ok::<(), anyhow::Error>(())
-                            })
-                        }));
-                    }
-
-                    // Real-time ingestion task.
-                    let pulsar_clone_rt = Arc::clone(&pulsar);
-                    tasks.push(task::spawn_blocking(move || {
-                        let rt = Builder::new_multi_thread().enable_all().build().unwrap();
-                        rt.block_on(async move {
-                            // Create an EVMProducer for real-time production.
-                            let evm_producer = EVMProducer::new(adapter_clone_rt, pulsar_clone_rt, producer_topic).await?;
-                            evm_producer.produce_realtime().await?;
-                            Ok::<(), anyhow::Error>(())
-                        })
+                .with_context(|| format!("Failed to find the last block of {} before {}", chain_name, end_time))?
+                .map(|block| block.checked_sub(1).ok_or_else(|| anyhow!("end_time {} of {} is before its first block", end_time, chain_name)))
+                .transpose()?;
+            if let Some(end_block) = end_block {
+                info!("{} ended {} at block {}", chain_name, end_time, end_block);
+            }
+            chain_cfg.end_block = end_block;
+        }
+        if let (Some(start_block), Some(end_block)) = (chain_cfg.start_block, chain_cfg.end_block) {
+            if start_block > end_block {
+                return Err(anyhow!("{} has an empty backfill window, blocks {} to {}", chain_name, start_block, end_block));
+            }
+        }
+
+        // Without a start_block, continue from whatever the sink already holds up to the current
+        // head, so a restart leaves no gap before realtime ingestion picks up.
+        let historical_range = match chain_cfg.start_block {
+            Some(start_block) => Some((start_block, chain_cfg.end_block.unwrap_or(u64::MAX))),
+            None => match sink.last_block(&chain_name).await? {
+                Some(last_block) => {
+                    let head = adapter.get_latest_block_number().await?;
+                    let end_block = chain_cfg.end_block.unwrap_or(head);
+                    if last_block < end_block {
+                        info!("Resuming {} from block {} to {}", chain_name, last_block + 1, end_block);
+                    }
+                    (last_block < end_block).then_some((last_block + 1, end_block))
+                }
+                None => None,
+            },
+        };
+        let blocks_table = stored_blocks_table(&chain_cfg.schemas);
+        // Only configured backfills defer the managed indexes; resuming after a restart only
+        // catches up on the blocks missed meanwhile.
+        let backfill_range = historical_range.filter(|_| chain_cfg.start_block.is_some());
+        if let (Some((start_block, end_block)), "postgres") = (backfill_range, chain_cfg.sink.as_str()) {
+            postgres_backfills.push((chain_name.clone(), blocks_table, start_block, end_block));
+        }
+        // Gaps below the last stored block, produced by the primary schema's historical producer.
+        // The report is diagnostic, so failing to build it doesn't stop the chain from starting.
+        let gap_repairs = if config.recovery.enabled {
+            let recovery = ChainRecovery::inspect(
+                &pool,
+                &chain_name,
+                &chain_cfg.sink,
+                blocks_table,
+                sink.as_ref(),
+                adapter.as_ref(),
+                historical_range,
+                &config.recovery,
+            )
+            .await;
+            match recovery {
+                Ok(recovery) => {
+                    let gap_repairs = recovery.planned_repairs.clone();
+                    chain_recoveries.push(recovery);
+                    gap_repairs
+                }
+                Err(e) => {
+                    warn!("Failed to inspect {} for recovery, starting without a report: {:#}", chain_name, e);
+                    Vec::new()
+                }
+            }
+        } else {
+            Vec::new()
+        };
+        let sink: Arc<dyn Sink> = match &quota {
+            Some(tracker) => Arc::new(QuotaSink::new(sink, Arc::clone(tracker))),
+            None => sink,
+        };
+        let sink: Arc<dyn Sink> = if config.circuit_breaker.enabled {
+            Arc::new(CircuitBreakerSink::new(sink, &chain_name, &config.circuit_breaker))
+        } else {
+            sink
+        };
+        chain_sinks.insert(chain_name.clone(), sink);
+        chain_adapters.insert(chain_name.clone(), Arc::clone(&adapter));
+        // With leader election, one replica per chain produces and consumes; the others wait.
+        let leader = if config.leader_election.enabled {
+            let leader = ChainLeader::new(&chain_name, &config.leader_election).await?;
+            tasks.push(task::spawn(Arc::clone(&leader).run()));
+            chain_leaders.insert(chain_name.clone(), Arc::clone(&leader));
+            Some(leader)
+        } else {
+            None
+        };
+        chain_consumer_workers.insert(chain_name.clone(), chain_cfg.consumer_workers.unwrap_or(1));
+
+        // Header-only blocks are a last resort, as the per-chain hooks expect full blocks.
+        let primary_schema = if chain_cfg.schemas.iter().any(|schema| schema == "blocks") {
+            "blocks".to_string()
+        } else {
+            chain_cfg.schemas
+                .iter()
+                .find(|schema| *schema != "headers")
+                .or(chain_cfg.schemas.first())
+                .cloned()
+                .unwrap_or_default()
+        };
+        for (schema, rate) in &chain_cfg.sample_rate {
+            if schema != "mempool" && !chain_cfg.schemas.contains(schema) {
+                return Err(anyhow!("sample_rate of {} names `{}`, which is neither one of its schemas nor mempool", chain_name, schema));
+            }
+            // Its consumers run the chain's hooks and its producer repairs gaps, which need every block.
+            if *schema == primary_schema && *rate < 1.0 {
+                return Err(anyhow!("{} {} is the chain's primary schema, which can't be sampled", chain_name, schema));
+            }
+        }
+        let mut hooks: Vec<Arc<dyn ConsumerHook>> = Vec::new();
+        // Hooks that read or update the `blocks` and `transactions` rows only run on chains whose
+        // sink writes them.
+        let postgres_sink = chain_cfg.sink == "postgres";
+        if postgres_sink {
+            postgres_chains.insert(chain_name.clone());
+        }
+
+        // First, so the latency isn't inflated by the other hooks.
+        if let Some(slo) = &chain_cfg.slo {
+            hooks.push(Arc::new(LatencyTracker::new(Arc::clone(&pool), alerter.clone(), slo)));
+        }
+
+        if let Some(every_n_blocks) = chain_cfg.balance_every_n_blocks {
+            let tracker = BalanceTracker::new(
+                Arc::clone(&adapter),
+                Arc::clone(&pool),
+                &chain_cfg.watched_addresses,
+                every_n_blocks,
+            )
+            .context(format!("Failed to create BalanceTracker for {}", chain_name))?;
+            hooks.push(Arc::new(tracker));
+        }
+
+        if config.token_balances.enabled {
+            let tracker = Arc::new(TokenBalanceTracker::new(
+                Arc::clone(&adapter),
+                Arc::clone(&pool),
+                &config.token_balances,
+            ));
+            let tracker_clone = Arc::clone(&tracker);
+            let chain_name_clone = chain_name.clone();
+            let leader_clone = leader.clone();
+            // Like the chain's other background tasks, only run by its leader.
+            tasks.push(task::spawn(async move {
+                while_leading(leader_clone.as_deref(), || tracker_clone.reconcile(&chain_name_clone)).await
+            }));
+            hooks.push(tracker);
+        }
+
+        if config.block_flows.enabled {
+            hooks.push(Arc::new(BlockFlowAggregator::new(Arc::clone(&adapter), Arc::clone(&pool), &config.block_flows)));
+        }
+
+        if chain_cfg.verification_checkpoint.is_some() && !postgres_sink {
+            error!("Chain `{}` has a verification_checkpoint, which needs the postgres sink. Skipping.", chain_name);
+        }
+        if let Some(checkpoint) = chain_cfg.verification_checkpoint.as_ref().filter(|_| postgres_sink) {
+            let verifier = HeaderVerifier::new(Arc::clone(&pool), checkpoint)
+                .context(format!("Failed to create HeaderVerifier for {}", chain_name))?;
+            hooks.push(Arc::new(verifier));
+        }
+
+        if config.integrity.enabled_for(&chain_name) {
+            hooks.push(Arc::new(RootVerifier::new(Arc::clone(&adapter), Arc::clone(&pool))));
+        }
+
+        if config.timestamp_checks.enabled {
+            hooks.push(Arc::new(TimestampChecker::new(Arc::clone(&pool), &config.timestamp_checks)));
+        }
+
+        if config.write_verification.enabled && postgres_sink {
+            hooks.push(Arc::new(WriteVerifier::new(Arc::clone(&pool), &config.write_verification)));
+        }
+
+        if config.receipts.enabled && postgres_sink {
+            hooks.push(Arc::new(ReceiptStatusTracker::new(
+                Arc::clone(&adapter),
+                Arc::clone(&pool),
+                Arc::clone(&abi_registry),
+                &config.receipts,
+            )));
+        }
+
+        if config.fees.enabled && postgres_sink {
+            hooks.push(Arc::new(FeeRecorder::new(Arc::clone(&adapter), Arc::clone(&pool), chain_cfg.fee_model.model())));
+        }
+
+        if let Some(signatures) = signatures.as_ref().filter(|_| postgres_sink) {
+            hooks.push(Arc::new(MethodDecoder::new(Arc::clone(&pool), Arc::clone(signatures), Arc::clone(&abi_registry))));
+        }
+
+        if config.logs.enabled {
+            let mut writer = LogWriter::new(Arc::clone(&adapter), Arc::clone(&pool), Arc::clone(&abi_registry), &config.logs);
+            if let Some(overflow) = &overflow {
+                writer = writer.with_overflow(Arc::clone(overflow));
+            }
+            hooks.push(Arc::new(writer));
+        }
+
+        if let Some(search_index) = &search_index {
+            if config.search.chains.is_empty() || config.search.chains.contains(&chain_name) {
+                hooks.push(Arc::new(search_index.indexer(Arc::clone(&adapter), Arc::clone(&abi_registry), signatures.clone())));
+            }
+        }
+
+        if config.safe_transactions.enabled {
+            hooks.push(Arc::new(SafeTransactionDecoder::new(Arc::clone(&adapter), Arc::clone(&pool))));
+        }
+
+        if config.contract_metadata.enabled {
+            match chain_cfg.chain_id {
+                Some(chain_id) => {
+                    hooks.push(Arc::new(ContractCreationTracker::new(Arc::clone(&adapter), Arc::clone(&pool))));
+                    let fetcher = ContractMetadataFetcher::new(
+                        Arc::clone(&pool),
+                        &chain_name,
+                        chain_id,
+                        Arc::clone(&abi_registry),
+                        &config.contract_metadata,
+                    );
+                    let leader_clone = leader.clone();
+                    tasks.push(task::spawn(async move {
+                        while_leading(leader_clone.as_deref(), || fetcher.run()).await
                     }));
                 }
+                None => error!("Chain `{}` has no chain_id, so its contract metadata is not fetched.", chain_name),
             }
-            // Handle other adapter types if needed.
-            _ => {
-                error!("Unknown adapter_type `{}` for chain `{}`. Skipping.", chain_cfg.adapter_type, chain_name);
-                continue;
+        }
+
+        if let Some(mev_detection) = &chain_cfg.mev_detection {
+            hooks.push(Arc::new(MevDetector::new(Arc::clone(&adapter), Arc::clone(&pool), mev_detection)));
+        }
+
+        if let Some(mempool) = &chain_cfg.mempool {
+            let mut tracker = PendingTransactionTracker::new(Arc::clone(&adapter), Arc::clone(&pool), &chain_name, mempool);
+            if let Some(rate) = chain_cfg.sample_rate.get("mempool") {
+                let sampler = Sampler::new(*rate).with_context(|| format!("Invalid sample_rate for {} mempool", chain_name))?;
+                tracker = tracker.with_sampling(sampler);
+            }
+            let tracker = Arc::new(tracker);
+            let tracker_clone = Arc::clone(&tracker);
+            let leader_clone = leader.clone();
+            tasks.push(task::spawn(async move {
+                while_leading(leader_clone.as_deref(), || tracker_clone.run()).await
+            }));
+            hooks.push(tracker);
+        }
+
+        if let Some(bridges) = &chain_cfg.bridges {
+            match chain_cfg.chain_id {
+                Some(chain_id) => {
+                    let decoder = BridgeDecoder::new(Arc::clone(&adapter), Arc::clone(&pool), chain_id, bridges)
+                        .context(format!("Failed to create BridgeDecoder for {}", chain_name))?;
+                    hooks.push(Arc::new(decoder));
+                }
+                None => error!("Chain `{}` has no chain_id, so its bridge transfers are not decoded.", chain_name),
             }
         }
+
+        if let Some(validator_rewards) = &chain_cfg.validator_rewards {
+            hooks.push(Arc::new(ValidatorRewardTracker::new(Arc::clone(&pool), validator_rewards)));
+        }
+
+        // Confirmed nonces are read from `transactions`.
+        if chain_cfg.stuck_transactions.is_some() && !postgres_sink {
+            error!("Chain `{}` has stuck_transactions, which needs the postgres sink. Skipping.", chain_name);
+        }
+        if let Some(stuck_transactions) = chain_cfg.stuck_transactions.as_ref().filter(|_| postgres_sink) {
+            if chain_cfg.mempool.is_none() {
+                error!("Chain `{}` has stuck_transactions without mempool, so nothing is pending to check.", chain_name);
+            }
+            let monitor = StuckTransactionMonitor::new(
+                Arc::clone(&pool),
+                &chain_name,
+                &chain_cfg.watched_addresses,
+                alerter.clone(),
+                stuck_transactions,
+            )
+            .context(format!("Failed to create StuckTransactionMonitor for {}", chain_name))?;
+            let leader_clone = leader.clone();
+            tasks.push(task::spawn(async move {
+                while_leading(leader_clone.as_deref(), || monitor.run()).await
+            }));
+        }
+
+        if chain_cfg.priority_transfers.is_some() {
+            hooks.push(Arc::new(PriorityTransferPruner::new(Arc::clone(&pool))));
+        }
+
+        // After the token balance tracker, so the block's transfers are stored before being valued.
+        if let Some(prices) = &chain_cfg.prices {
+            hooks.push(Arc::new(PriceRecorder::new(Arc::clone(&adapter), Arc::clone(&pool), prices)));
+        }
+
+        if config.canonical_schema.enabled {
+            match (chain_cfg.chain_id, registries.canonical_mappers.get(&chain_cfg.adapter_type)) {
+                (Some(chain_id), Some(mapper)) => {
+                    hooks.push(Arc::new(CanonicalTableWriter::new(Arc::clone(&pool), chain_id, mapper)));
+                }
+                (None, _) => {
+                    error!("Chain `{}` has no chain_id, so it is left out of the canonical tables.", chain_name);
+                }
+                (_, None) => {
+                    error!("No canonical mapper for adapter_type `{}` of chain `{}`. Skipping.", chain_cfg.adapter_type, chain_name);
+                }
+            }
+        }
+
+        if !chain_cfg.routes.is_empty() {
+            let router = RoutingProducer::new(
+                Arc::clone(&queue),
+                &producer_topic_prefix,
+                &chain_name,
+                &chain_cfg.routes,
+                &config.wire_formats,
+            )
+                .await
+                .context(format!("Failed to create routing producer for {}", chain_name))?;
+            hooks.push(Arc::new(router));
+        }
+
+        chain_hooks.insert(chain_name.clone(), (primary_schema.clone(), hooks));
+
+        // Backfills read pruned history from the fallback source, if one is configured.
+        let history_adapter: Arc<dyn BlockchainAdapter> = match &chain_cfg.history_fallback {
+            Some(fallback) => Arc::new(HistoryFallbackAdapter::new(
+                backfill_adapter,
+                Arc::new(EtherscanAdapter::new(
+                    &fallback.etherscan.api_url,
+                    &fallback.etherscan.api_key,
+                    fallback.etherscan.requests_per_second,
+                )),
+                fallback.pruning_horizon_blocks,
+            )),
+            None => backfill_adapter,
+        };
+
+        // Token transfer backfill through a provider's enhanced API.
+        match chain_cfg.transfers_source.as_deref() {
+            Some("alchemy") => {
+                let start_block = chain_cfg.start_block
+                    .with_context(|| format!("transfers_source for {} requires start_block", chain_name))?;
+                let end_block = match chain_cfg.end_block {
+                    Some(end_block) => end_block,
+                    None => adapter.get_latest_block_number().await?,
+                };
+                let backfill = AlchemyTransferBackfill::new(
+                    &chain_cfg.http_url,
+                    Arc::clone(&pool),
+                    config.token_balances.enabled,
+                );
+                let chain_name_clone = chain_name.clone();
+                let leader_clone = leader.clone();
+                tasks.push(task::spawn(async move {
+                    while_leading(leader_clone.as_deref(), || backfill.run(&chain_name_clone, start_block, end_block)).await
+                }));
+            }
+            Some(other) => {
+                error!("Unknown transfers_source `{}` for chain `{}`. Skipping.", other, chain_name);
+            }
+            None => {}
+        }
+
+        // Known before any producer starts, so the chain's best-effort streams wait for all of them.
+        for schema in &chain_cfg.schemas {
+            if chain_cfg.lanes.get(schema) == Some(&Lane::Critical) {
+                lane_monitor.register_critical(&chain_name, schema);
+            }
+        }
+
+        // For each schema in the chain_cfg.schemas create a producer for each schema.
+        for schema in chain_cfg.schemas {
+            // Create a producer for each schema.
+            let producer_topic = format!("{}{}-{}", &producer_topic_prefix, &chain_name, &schema);
+            let wire_format_rt = config.wire_formats.for_topic(&format!("{}-{}", &chain_name, &schema));
+            let wire_format_hist = config.wire_formats.for_topic(&format!("{}-{}-historical", &chain_name, &schema));
+            let tx_detail: BlockTransactionsKind = chain_cfg.tx_detail.get(&schema).copied().unwrap_or_default().into();
+            schema_tx_detail.insert((chain_name.clone(), schema.clone()), tx_detail);
+            let delivery = chain_cfg.delivery.get(&schema).copied().unwrap_or_default();
+            schema_delivery.insert((chain_name.clone(), schema.clone()), delivery);
+            let lane = chain_cfg.lanes.get(&schema).copied().unwrap_or_default();
+            schema_lanes.insert((chain_name.clone(), schema.clone()), lane);
+
+            let sharding = chain_cfg.shards.get(&schema).copied();
+            let staged = sharding.is_some_and(|sharding| sharding.key == ShardKey::Address);
+            let redaction = chain_cfg
+                .redaction
+                .get(&schema)
+                .map(Redaction::new)
+                .transpose()
+                .with_context(|| format!("Invalid redaction for {} {}", chain_name, schema))?;
+            let sampler = chain_cfg
+                .sample_rate
+                .get(&schema)
+                .map(|rate| Sampler::new(*rate))
+                .transpose()
+                .with_context(|| format!("Invalid sample_rate for {} {}", chain_name, schema))?;
+
+            // Add the producer_topic, or its shard topics, to the consumers_vec.
+            for (topic, transactions_only) in consumer_topics(&producer_topic, sharding.as_ref()) {
+                if transactions_only {
+                    transactions_only_topics.insert(topic.clone());
+                }
+                if staged {
+                    staged_topics.insert(topic.clone());
+                    if !transactions_only {
+                        owner_topics.extend(sharding.map(|sharding| (topic.clone(), sharding)));
+                    }
+                }
+                consumers_vec.push((chain_name.clone(), schema.clone(), topic));
+            }
+
+            // Ranges the realtime producer skips while the stream is paused, produced by the
+            // historical task's job runner so they don't hold up new heads.
+            let (gap_sender, gap_receiver) = tokio::sync::mpsc::unbounded_channel();
+            let gap_receiver = Arc::new(Mutex::new(gap_receiver));
+
+            // Clone the adapter for different tasks.
+            let adapter_clone_rt = match lane {
+                Lane::BestEffort => Arc::clone(&best_effort_adapter),
+                _ => Arc::clone(&adapter),
+            };
+
+            // Historical ingestion task, run as chunks in backfill_chunks. It plans the range (if a
+            // start_block is provided or the sink has earlier blocks) and produces whatever chunks
+            // are pending, including ones left over from earlier runs.
+            {
+                let producer_topic_hist = producer_topic.clone() + "-historical";
+                for (topic, transactions_only) in consumer_topics(&producer_topic_hist, sharding.as_ref()) {
+                    if transactions_only {
+                        transactions_only_topics.insert(topic.clone());
+                    }
+                    if staged {
+                        staged_topics.insert(topic.clone());
+                        if !transactions_only {
+                            owner_topics.extend(sharding.map(|sharding| (topic.clone(), sharding)));
+                        }
+                    }
+                    consumers_vec.push((chain_name.clone(), schema.clone(), topic));
+                }
+
+                let adapter_clone_hist = Arc::clone(&history_adapter);
+                let queue_clone_hist = Arc::clone(&queue);
+                let stream_control_hist = Arc::clone(&stream_control);
+                let pool_hist = Arc::clone(&pool);
+                let alerter_hist = alerter.clone();
+                let backfill_config = config.backfill.clone();
+                let chain_name_hist = chain_name.clone();
+                let schema_hist = schema.clone();
+                let backfill_order = chain_cfg.backfill_order;
+                let redaction_hist = redaction.clone();
+                let gap_repairs_hist = if schema == primary_schema { gap_repairs.clone() } else { Vec::new() };
+                let leader_hist = leader.clone();
+                let lane_monitor_hist = Arc::clone(&lane_monitor);
+                let task_name = format!("historical producer {}/{}", chain_name, schema);
+                let shutdown_hist = Arc::clone(&shutdown);
+                let gaps_hist = Arc::clone(&gap_receiver);
+
+                producer_tasks.push(task::spawn_blocking(move || {
+                    let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+                    // Started afresh each time the replica leads the chain again.
+                    let result = rt.block_on(shutdown_hist.until_producers_stop(while_leading(leader_hist.as_deref(), || async {
+                        if lane == Lane::BestEffort {
+                            lane_monitor_hist.wait_for_critical(&chain_name_hist).await;
+                        }
+                        dashboard::task_started(&task_name);
+                        // Create an EVMProducer for historical production.
+                        let mut evm_producer = EVMProducer::new(Arc::clone(&adapter_clone_hist), Arc::clone(&queue_clone_hist), producer_topic_hist.clone())
+                            .await?;
+                        if let Some(sharding) = sharding {
+                            evm_producer = evm_producer.with_shards(Arc::clone(&queue_clone_hist), sharding).await?;
+                        }
+                        let mut evm_producer = evm_producer
+                            .with_wire_format(wire_format_hist)
+                            .with_tx_detail(tx_detail)
+                            .with_stream_control(Arc::clone(&stream_control_hist), &chain_name_hist, &schema_hist)
+                            .with_backfill_order(backfill_order)
+                            .with_delivery(delivery)
+                            .with_lane(Arc::clone(&lane_monitor_hist), lane, &chain_name_hist, &schema_hist);
+                        if schema_hist == "headers" {
+                            evm_producer = evm_producer.with_headers_only();
+                        }
+                        if let Some(redaction) = redaction_hist.clone() {
+                            evm_producer = evm_producer.with_redaction(redaction);
+                        }
+                        if let Some(sampler) = sampler {
+                            evm_producer = evm_producer.with_sampling(sampler);
+                        }
+                        let runner = BackfillJobRunner::new(
+                            Arc::clone(&pool_hist),
+                            Arc::new(evm_producer),
+                            &chain_name_hist,
+                            &schema_hist,
+                            alerter_hist.clone(),
+                            &backfill_config,
+                        )
+                        .with_order(backfill_order);
+                        if let Some((start_block, end_block)) = historical_range {
+                            // Chunks need an end, so an open-ended range stops at the current head,
+                            // where realtime ingestion takes over.
+                            let end_block = match end_block {
+                                u64::MAX => adapter_clone_hist.get_latest_block_number().await?,
+                                end_block => end_block,
+                            };
+                            runner.plan(start_block, end_block).await?;
+                        }
+                        for &(start_block, end_block) in &gap_repairs_hist {
+                            runner.plan(start_block, end_block).await?;
+                        }
+                        runner.run().await?;
+                        runner.run_handoffs(&mut *gaps_hist.lock().await).await?;
+                        Ok::<(), anyhow::Error>(())
+                    })));
+                    dashboard::task_finished(&task_name, &result);
+                    result
+                }));
+            }
+
+            // Only the primary schema's realtime producer publishes heads, so there is one per block.
+            let head_topic = (config.head_topic.enabled && schema == primary_schema)
+                .then(|| format!("{}{}-head", &producer_topic_prefix, &chain_name));
+            let wire_format_head = config.wire_formats.for_topic(&format!("{}-head", &chain_name));
+            let chain_name_rt = chain_name.clone();
+            // Likewise for the priority transfer fast path.
+            let priority_transfers = chain_cfg.priority_transfers.as_ref()
+                .filter(|_| schema == primary_schema)
+                .map(|priority| (format!("{}{}-priority-transfers", &producer_topic_prefix, &chain_name), priority.clone()));
+            let wire_format_priority = config.wire_formats.for_topic(&format!("{}-priority-transfers", &chain_name));
+            let pool_rt = Arc::clone(&pool);
+            let stream_control_rt = Arc::clone(&stream_control);
+            let schema_rt = schema.clone();
+
+            // Real-time ingestion task.
+            let queue_clone_rt = Arc::clone(&queue);
+            let leader_rt = leader.clone();
+            let task_name = format!("realtime producer {}/{}", chain_name, schema);
+            let watchdog = config.watchdog.enabled.then(|| {
+                let heartbeat = Arc::new(Heartbeat::default());
+                let watchdog = Watchdog::new(
+                    Arc::clone(&adapter_clone_rt),
+                    Arc::clone(&heartbeat),
+                    alerter.clone(),
+                    &chain_name,
+                    &task_name,
+                    &config.watchdog,
+                );
+                (heartbeat, watchdog)
+            });
+            let shutdown_rt = Arc::clone(&shutdown);
+            let lane_monitor_rt = Arc::clone(&lane_monitor);
+            let lanes_config = config.lanes.clone();
+            let restart_heartbeat = (lane != Lane::BestEffort).then(|| Arc::new(Heartbeat::default()));
+            if let Some(heartbeat) = watchdog.as_ref().map(|(heartbeat, _)| heartbeat).or(restart_heartbeat.as_ref()) {
+                chain_heartbeats.entry(chain_name.clone()).or_default().push(Arc::clone(heartbeat));
+            }
+            producer_tasks.push(task::spawn_blocking(move || {
+                let rt = Builder::new_multi_thread().enable_all().build().unwrap();
+                // Started afresh each time the replica leads the chain again, resuming after the
+                // heartbeat's last block like a restart.
+                let result = rt.block_on(shutdown_rt.until_producers_stop(while_leading(leader_rt.as_deref(), || async {
+                    if lane == Lane::BestEffort {
+                        lane_monitor_rt.wait_for_critical(&chain_name_rt).await;
+                    }
+                    dashboard::task_started(&task_name);
+                    // Create an EVMProducer for real-time production.
+                    let mut evm_producer = EVMProducer::new(Arc::clone(&adapter_clone_rt), Arc::clone(&queue_clone_rt), producer_topic.clone())
+                        .await?
+                        .with_wire_format(wire_format_rt)
+                        .with_tx_detail(tx_detail)
+                        .with_stream_control(Arc::clone(&stream_control_rt), &chain_name_rt, &schema_rt)
+                        .with_gap_handoff(gap_sender.clone())
+                        .with_delivery(delivery)
+                        .with_lane(Arc::clone(&lane_monitor_rt), lane, &chain_name_rt, &schema_rt);
+                    if schema_rt == "headers" {
+                        evm_producer = evm_producer.with_headers_only();
+                    }
+                    if let Some(redaction) = redaction.clone() {
+                        evm_producer = evm_producer.with_redaction(redaction);
+                    }
+                    if let Some(sampler) = sampler {
+                        evm_producer = evm_producer.with_sampling(sampler);
+                    }
+                    if let Some(sharding) = sharding {
+                        evm_producer = evm_producer.with_shards(Arc::clone(&queue_clone_rt), sharding).await?;
+                    }
+                    if let Some((topic, priority_config)) = &priority_transfers {
+                        let publisher = PriorityTransferPublisher::new(
+                            Arc::clone(&adapter_clone_rt),
+                            Arc::clone(&pool_rt),
+                            Arc::clone(&queue_clone_rt),
+                            topic,
+                            &chain_name_rt,
+                            wire_format_priority,
+                            priority_config,
+                        )
+                        .await?;
+                        evm_producer = evm_producer.with_priority_transfers(publisher);
+                    }
+                    if let Some(head_topic) = &head_topic {
+                        evm_producer = evm_producer
+                            .with_head_topic(Arc::clone(&queue_clone_rt), head_topic, &chain_name_rt, wire_format_head)
+                            .await?;
+                    }
+                    // Restarted producers always get a heartbeat, so a restart picks up after the
+                    // last block they published.
+                    let (heartbeat, watchdog) = match &watchdog {
+                        Some((heartbeat, watchdog)) => (Some(Arc::clone(heartbeat)), Some(watchdog)),
+                        None => (restart_heartbeat.clone(), None),
+                    };
+                    if let Some(heartbeat) = heartbeat {
+                        evm_producer = evm_producer.with_heartbeat(heartbeat);
+                    }
+                    if lane == Lane::Critical {
+                        lane_monitor_rt.critical_started(&chain_name_rt, &schema_rt);
+                    }
+                    let mut attempt = 0;
+                    loop {
+                        let result = match &watchdog {
+                            Some(watchdog) => watchdog.supervise(|| evm_producer.produce_realtime()).await,
+                            None => evm_producer.produce_realtime().await,
+                        };
+                        if lane == Lane::BestEffort {
+                            result?;
+                            break;
+                        }
+                        // Restarted whether it failed or its subscription ended.
+                        attempt += 1;
+                        let delay = lanes_config.restart_delay(attempt);
+                        let message = match result {
+                            Ok(()) => format!("{} stopped, restarting it in {:?}", task_name, delay),
+                            Err(e) => format!("{} failed, restarting it in {:?}: {}", task_name, delay, e),
+                        };
+                        error!("{}", message);
+                        dashboard::record_error(&task_name, &message);
+                        metrics::increment_counter("lane_restarts_total", &[("task", &task_name)], 1);
+                        tokio::time::sleep(delay).await;
+                    }
+                    Ok::<(), anyhow::Error>(())
+                })));
+                dashboard::task_finished(&task_name, &result);
+                result
+            }));
+        }
+    }
+
+    if !config.indexes.managed.is_empty() {
+        let pool_clone = Arc::clone(&pool);
+        let index_config = config.indexes;
+        tasks.push(task::spawn(async move {
+            if let Err(e) = sync_indexes(&pool_clone, &index_config, &postgres_backfills).await {
+                error!("Failed to sync managed indexes: {}", e);
+                dashboard::record_error("index sync", &e.to_string());
+            }
+            Ok(())
+        }));
+    }
+
+    // Start the optional dashboard, now that every chain's adapter and sink exist.
+    if config.dashboard.enabled {
+        let dashboard_state = Arc::new(
+            DashboardState::new(Arc::clone(&pool), chain_adapters.clone(), chain_sinks.clone()).with_heartbeats(chain_heartbeats),
+        );
+        let dashboard_config = config.dashboard;
+        tasks.push(task::spawn(async move {
+            dashboard::serve(&dashboard_config, dashboard_state).await
+        }));
+    }
+
+    // Persist metrics to Postgres for dashboards without a metrics stack.
+    if config.pipeline_metrics.enabled {
+        let recorder = PipelineMetricsRecorder::new(Arc::clone(&pool), chain_adapters, chain_sinks.clone(), &config.pipeline_metrics);
+        tasks.push(task::spawn(async move {
+            recorder.run().await
+        }));
     }
 
     // 5) Spawn a consumer task.
@@ -134,34 +1491,211 @@ pub async fn run_ingestion(pool: Arc<PgPool>, pulsar: Arc<PulsarClient>) -> Resu
     // by concating the topic with "-subscription"
     let consumer_subscription_vec = consumers_vec
                                     .iter()
-                                    .map(|consumer| (consumer.0.clone(), consumer.1.clone(), consumer.1.clone() + "-subscription"))
-                                    .collect::<Vec<(String, String, String)>>();
+                                    .map(|consumer| (consumer.0.clone(), consumer.1.clone(), consumer.2.clone(), consumer.2.clone() + "-subscription"))
+                                    .collect::<Vec<(String, String, String, String)>>();
+
+    // Record what each chain resumes and repairs, with the backlog its consumers are about to read.
+    if !chain_recoveries.is_empty() {
+        let admin = (config.queue_type == QueueType::Pulsar).then(|| PulsarAdmin::new(&config.pulsar).ok()).flatten();
+        for mut recovery in chain_recoveries {
+            if let Some(admin) = &admin {
+                let subscriptions: Vec<(String, String)> = consumer_subscription_vec
+                    .iter()
+                    .filter(|(chain_name, _, _, _)| *chain_name == recovery.chain_name)
+                    .map(|(_, _, topic, subscription)| (topic.clone(), subscription.clone()))
+                    .collect();
+                recovery.read_backlog(admin, &subscriptions).await;
+            }
+            if let Err(e) = recovery.record(&pool).await {
+                error!("Failed to record the recovery report of {}: {}", recovery.chain_name, e);
+            }
+        }
+    }
+
+
+    // Daily stats are refreshed by one scheduled job, fed by a hook on every consumer.
+    let daily_stats = if config.aggregation.enabled {
+        let aggregator = Arc::new(DailyStatsAggregator::new(Arc::clone(&pool), &config.aggregation));
+        let aggregator_clone = Arc::clone(&aggregator);
+        tasks.push(task::spawn(async move {
+            aggregator_clone.run().await
+        }));
+        Some(aggregator)
+    } else {
+        None
+    };
 
+    // One dedup window per stream, shared by its realtime, historical and shard consumers.
+    let mut dedup_windows: HashMap<(String, String), Arc<DedupWindow>> = HashMap::new();
+    // One CDC producer per chain, shared by the consumers of all its schemas.
+    let mut cdc_producers: HashMap<String, Arc<CdcProducer>> = HashMap::new();
 
-    for (chain_name, consumer_topic, consumer_subscription) in consumer_subscription_vec {
-        let pulsar_clone_consumer = Arc::clone(&pulsar);
-        let pg_pool_clone = Arc::clone(&pool);
+    for (chain_name, schema, consumer_topic, consumer_subscription) in consumer_subscription_vec {
+        let queue_clone_consumer = Arc::clone(&queue);
+        let sink = Arc::clone(&chain_sinks[&chain_name]);
+        let workers = chain_consumer_workers[&chain_name];
+        let tx_detail = schema_tx_detail[&(chain_name.clone(), schema.clone())];
+        let delivery = schema_delivery[&(chain_name.clone(), schema.clone())];
+        let lane = schema_lanes[&(chain_name.clone(), schema.clone())];
+        let lanes_config = config.lanes.clone();
+        let transactions_only = transactions_only_topics.contains(&consumer_topic);
+        let staged_fanout = staged_topics.contains(&consumer_topic);
+        let owned_part = owner_topics.get(&consumer_topic).copied();
+        let redelivery = config.redelivery.clone();
+        let leader = chain_leaders.get(&chain_name).cloned();
+        let dedup = if config.dedup.enabled {
+            let key = (chain_name.clone(), schema.clone());
+            if !dedup_windows.contains_key(&key) {
+                let window = DedupWindow::load(Arc::clone(&pool), &chain_name, &schema, &config.dedup)
+                    .await
+                    .context(format!("Failed to load dedup window for {} {}", chain_name, schema))?;
+                dedup_windows.insert(key.clone(), Arc::new(window));
+            }
+            Some(Arc::clone(&dedup_windows[&key]))
+        } else {
+            None
+        };
+
+        let mut hooks: Vec<Arc<dyn ConsumerHook>> = Vec::new();
+        if let Some(hub) = &push_hub {
+            hooks.push(Arc::new(PushHook::new(Arc::clone(hub), &schema)));
+        }
+        // Only the chain's primary stream notifies, like it runs the per-chain hooks, so listeners
+        // get one event per committed block whichever schemas the chain has.
+        let primary_schema = chain_hooks.get(&chain_name).map(|(primary_schema, _)| primary_schema);
+        if config.notify.enabled && primary_schema == Some(&schema) {
+            hooks.push(Arc::new(NotifyHook::new(Arc::clone(&pool), &config.notify.channel)));
+        }
+        // Every schema's consumer writes `blocks`, and whichever stores a replacement first flips
+        // the old block's `canonical` and gets it back as orphaned, so each flip is seen once.
+        if config.cdc.enabled && schema != "headers" {
+            if !cdc_producers.contains_key(&chain_name) {
+                let cdc_topic = format!("{}{}-cdc", &producer_topic_prefix, &chain_name);
+                let cdc_producer = CdcProducer::new(Arc::clone(&queue), cdc_topic)
+                    .await
+                    .context(format!("Failed to create CDC producer for {}", chain_name))?
+                    .with_wire_format(config.wire_formats.for_topic(&format!("{}-cdc", &chain_name)));
+                cdc_producers.insert(chain_name.clone(), Arc::new(cdc_producer));
+            }
+            hooks.push(Arc::clone(&cdc_producers[&chain_name]) as Arc<dyn ConsumerHook>);
+        }
+        // Aggregated from the rows the postgres sink writes.
+        if let Some(aggregator) = daily_stats.as_ref().filter(|_| postgres_chains.contains(&chain_name)) {
+            hooks.push(Arc::clone(aggregator) as Arc<dyn ConsumerHook>);
+        }
+        // Indexed from the transactions stream only, so each transaction is counted once.
+        if config.address_activity.enabled && schema == "transactions" {
+            hooks.push(Arc::new(AddressActivityIndexer::new(Arc::clone(&pool))));
+        }
+        for projection in registries.projections.iter().filter(|projection| projection.schemas().contains(&schema)) {
+            hooks.push(Arc::new(ProjectionHook::new(Arc::clone(projection), Arc::clone(&pool), &schema)?));
+        }
+        if let Some((primary_schema, per_chain_hooks)) = chain_hooks.get(&chain_name) {
+            if &schema == primary_schema && schema != "headers" {
+                hooks.extend(per_chain_hooks.iter().cloned());
+            }
+        }
+        // Shard 0's consumer runs the hooks of an address-sharded stream, once per whole block.
+        if transactions_only {
+            hooks.clear();
+        }
 
-        tasks.push(task::spawn_blocking(move || -> Result<()> {
+        let task_name = format!("consumer {}", consumer_topic.trim_start_matches(producer_topic_prefix.as_str()));
+        let shutdown_consumer = Arc::clone(&shutdown);
+        consumer_tasks.push(task::spawn_blocking(move || -> Result<()> {
             let rt = Builder::new_multi_thread().enable_all().build().unwrap();
             rt.block_on(async move {
-                let mut evm_consumer = EVMConsumer::new(
-                    pulsar_clone_consumer,
-                    consumer_topic.clone(),
-                    consumer_subscription.clone()
-                ).await;
-
-                if let Err(e) = evm_consumer.postgres_consume(pg_pool_clone, &chain_name).await {
-                    error!("Consumer error: {}", e);
+                loop {
+                    // A standby that never became leader has nothing to drain.
+                    if let Some(leader) = &leader {
+                        tokio::select! {
+                            _ = leader.wait() => {}
+                            _ = shutdown_consumer.consumers_draining() => return,
+                        }
+                    }
+                    dashboard::task_started(&task_name);
+                    let consumed = async {
+                        let mut attempt = 0;
+                        loop {
+                            let mut evm_consumer = EVMConsumer::new(
+                                Arc::clone(&queue_clone_consumer),
+                                consumer_topic.clone(),
+                                consumer_subscription.clone(),
+                                hooks.clone()
+                            ).await
+                            .with_workers(workers)
+                            .with_tx_detail(tx_detail)
+                            .with_redelivery(redelivery.clone())
+                            .with_delivery(delivery)
+                            .with_shutdown(Arc::clone(&shutdown_consumer));
+                            if transactions_only {
+                                evm_consumer = evm_consumer.with_transactions_only();
+                            }
+                            if staged_fanout {
+                                evm_consumer = evm_consumer.with_staged_fanout();
+                            }
+                            if let Some(sharding) = owned_part {
+                                evm_consumer = evm_consumer.with_owned_part(sharding);
+                            }
+                            if schema == "headers" {
+                                evm_consumer = evm_consumer.with_headers();
+                            }
+                            if let Some(dedup) = &dedup {
+                                evm_consumer = evm_consumer.with_dedup(Arc::clone(dedup));
+                            }
+
+                            // A fresh buffer each run, as a failed run's blocks are redelivered.
+                            let result = evm_consumer.consume(for_consumer(&sink), &chain_name).await;
+                            // A failed critical consumer resumes from its subscription after a backoff.
+                            match result {
+                                Err(e) if lane == Lane::Critical => {
+                                    attempt += 1;
+                                    let delay = lanes_config.restart_delay(attempt);
+                                    let message = format!("{} failed, restarting it in {:?}: {}", task_name, delay, e);
+                                    error!("{}", message);
+                                    dashboard::record_error(&task_name, &message);
+                                    metrics::increment_counter("lane_restarts_total", &[("task", &task_name)], 1);
+                                    tokio::select! {
+                                        _ = tokio::time::sleep(delay) => {}
+                                        _ = shutdown_consumer.consumers_draining() => break Ok(()),
+                                    }
+                                }
+                                result => break result,
+                            }
+                        }
+                    };
+                    // Stopped when leadership is lost, leaving what it read unacknowledged for the
+                    // new leader, and started afresh once the replica leads again.
+                    let result = tokio::select! {
+                        result = consumed => result,
+                        _ = async {
+                            match &leader {
+                                Some(leader) => leader.lost().await,
+                                None => std::future::pending().await,
+                            }
+                        } => continue,
+                    };
+                    if let Err(e) = &result {
+                        error!("Consumer error: {}", e);
+                    }
+                    dashboard::task_finished(&task_name, &result);
+                    break;
                 }
             });
             Ok(())
         }));
     }
 
-    // 6) Wait for all tasks to complete.
-    // Since producer and consumer tasks run indefinitely, join_all will keep the process alive.
-    future::join_all(tasks).await;
+    // 6) Run until a shutdown signal, then stop producers, drain consumers and record what was
+    // left behind. Producer and consumer tasks run indefinitely, so this keeps the process alive.
+    if let Some(mut summary) = shutdown.run(&config.shutdown, &mut producer_tasks, &mut consumer_tasks, &mut tasks).await? {
+        summary.read_cursors(&chain_sinks).await;
+        summary.record(&pool).await;
+        // Background tasks, and consumers that didn't drain in time, run on blocking threads the
+        // runtime would wait for on drop, holding the pod until it is killed. Whatever they hold
+        // is redelivered after the restart.
+        std::process::exit(if summary.timed_out { 1 } else { 0 });
+    }
 
     Ok(())
 }
\ No newline at end of file
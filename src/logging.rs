@@ -0,0 +1,145 @@
+use anyhow::{anyhow, bail, Result};
+use log::{LevelFilter, Log, Metadata, Record};
+use std::env;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{OnceLock, RwLock};
+
+use crate::secrets;
+
+/// Which log levels are enabled, in `RUST_LOG` syntax: a global level followed by per-module ones,
+/// e.g. `info,evm_adapter=debug`. A module is matched by any segment of the log target's path, so
+/// `evm_adapter` covers `blockchain_data_ingestion::blockchain::evm_adapter` and `blockchain`
+/// every module below it. The longest matching module wins.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LogFilter {
+    level: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl FromStr for LogFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(spec: &str) -> Result<Self> {
+        let mut filter = LogFilter { level: LevelFilter::Error, modules: Vec::new() };
+        for directive in spec.split(',').map(str::trim).filter(|directive| !directive.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    let Ok(level) = level.trim().parse() else {
+                        bail!("Invalid log level `{}` for `{}`", level, module);
+                    };
+                    let module = module.trim().trim_matches(':').to_string();
+                    filter.modules.retain(|(name, _)| *name != module);
+                    filter.modules.push((module, level));
+                }
+                None => match directive.parse() {
+                    Ok(level) => filter.level = level,
+                    // A bare module name enables all of its logs, as with `RUST_LOG`.
+                    Err(_) => filter.modules.push((directive.trim_matches(':').to_string(), LevelFilter::Trace)),
+                },
+            }
+        }
+        Ok(filter)
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.level.as_str().to_lowercase())?;
+        for (module, level) in &self.modules {
+            write!(f, ",{}={}", module, level.as_str().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+impl LogFilter {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| matches_module(target, module))
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.level, |(_, level)| *level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|(_, level)| *level).fold(self.level, LevelFilter::max)
+    }
+}
+
+/// Whether `module` is the target or a run of whole segments of its path.
+fn matches_module(target: &str, module: &str) -> bool {
+    target.match_indices(module).any(|(start, _)| {
+        let end = start + module.len();
+        (start == 0 || target[..start].ends_with("::")) && (end == target.len() || target[end..].starts_with("::"))
+    })
+}
+
+/// Formats records as `env_logger` does, behind a filter that can be replaced while running, with
+/// secrets redacted from every message.
+struct ReloadableLogger {
+    format: env_logger::Logger,
+    filter: RwLock<LogFilter>,
+    startup_filter: LogFilter,
+}
+
+impl Log for ReloadableLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.read().unwrap_or_else(|e| e.into_inner()).level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = secrets::redact(&record.args().to_string());
+        self.format.log(
+            &Record::builder()
+                .args(format_args!("{}", message))
+                .metadata(record.metadata().clone())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn flush(&self) {
+        self.format.flush();
+    }
+}
+
+static LOGGER: OnceLock<ReloadableLogger> = OnceLock::new();
+
+/// Installs the logger with the filter from `RUST_LOG`, `info` by default.
+pub fn init() -> Result<()> {
+    let startup_filter = match env::var("RUST_LOG") {
+        Ok(spec) => spec.parse()?,
+        Err(_) => LogFilter { level: LevelFilter::Info, modules: Vec::new() },
+    };
+    let logger = LOGGER.get_or_init(|| ReloadableLogger {
+        format: env_logger::Builder::new().filter_level(LevelFilter::Trace).build(),
+        filter: RwLock::new(startup_filter.clone()),
+        startup_filter,
+    });
+    log::set_logger(logger).map_err(|e| anyhow!("Failed to install logger: {}", e))?;
+    log::set_max_level(logger.filter.read().unwrap_or_else(|e| e.into_inner()).max_level());
+    Ok(())
+}
+
+/// The filter in effect, `None` before `init`.
+pub fn current_filter() -> Option<LogFilter> {
+    LOGGER.get().map(|logger| logger.filter.read().unwrap_or_else(|e| e.into_inner()).clone())
+}
+
+/// Replaces the filter, or restores the one from startup with `None`. Returns the filter now in
+/// effect.
+pub fn set_filter(filter: Option<LogFilter>) -> Result<LogFilter> {
+    let Some(logger) = LOGGER.get() else {
+        bail!("Logging isn't initialized");
+    };
+    let filter = filter.unwrap_or_else(|| logger.startup_filter.clone());
+    log::set_max_level(filter.max_level());
+    *logger.filter.write().unwrap_or_else(|e| e.into_inner()) = filter.clone();
+    Ok(filter)
+}
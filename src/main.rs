@@ -1,32 +1,245 @@
+use std::env;
+use std::path::PathBuf;
+use std::sync::Arc;
+use clap::{Parser, Subcommand};
 use dotenv::dotenv;
-use env_logger;
 use log::info;
-use blockchain_data_ingestion::run_ingestion;
+use blockchain_data_ingestion::{connect_queue, logging, read_config, run_pipeline, QueueType, Registries};
+use blockchain_data_ingestion::startup::wait_for;
+use blockchain_data_ingestion::storage::snapshot::{export_snapshot, import_snapshot};
+use blockchain_data_ingestion::streams::producers::backfill_jobs::{list_failed_chunks, retry_failed_chunks};
+use blockchain_data_ingestion::streams::control::{list_paused_streams, pause_stream, resume_stream};
+use blockchain_data_ingestion::streams::message_queue::bridge::bridge;
+use blockchain_data_ingestion::streams::message_queue::pulsar_admin::PulsarAdmin;
+use blockchain_data_ingestion::streams::schemas::json_schema::topic_schemas;
 use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
 
-#[tokio::main]
-async fn main() -> anyhow::Result<()> {
-    // Load environment variables
-    dotenv().ok();
+#[derive(Parser)]
+#[command(about = "Ingests blockchain data into Pulsar and Postgres")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    // Initialize the logger
-    env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("info")).init();
+#[derive(Subcommand)]
+enum Command {
+    /// Run the ingestion service (the default when no command is given).
+    Run,
+    /// Export or import snapshot bundles of ingested data.
+    Snapshot {
+        #[command(subcommand)]
+        action: SnapshotCommand,
+    },
+    /// Inspect and retry backfill chunks that ran out of attempts.
+    Backfill {
+        #[command(subcommand)]
+        action: BackfillCommand,
+    },
+    /// Pause, resume or list individual schema streams of the running service.
+    Streams {
+        #[command(subcommand)]
+        action: StreamsCommand,
+    },
+    /// Write the JSON Schema of every published message type to a directory.
+    Schemas {
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Report Pulsar retention and TTL policies that drifted from the config.
+    Status,
+    /// Copy topics from `queue_type` to another broker as they are published, e.g. to migrate
+    /// brokers without re-ingesting from the chain.
+    Bridge {
+        /// Topic without the tenant and namespace, e.g. `ETH-blocks`. Repeat for more topics.
+        #[arg(long, required = true)]
+        topic: Vec<String>,
+        #[arg(long, value_enum)]
+        to: QueueType,
+        #[arg(long, default_value = "bridge")]
+        subscription: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    /// Dump a chain's ingested block range to compressed Parquet files.
+    Export {
+        #[arg(long)]
+        chain: String,
+        #[arg(long)]
+        start_block: i64,
+        #[arg(long)]
+        end_block: i64,
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Restore a snapshot bundle into the configured database.
+    Import {
+        #[arg(long)]
+        dir: PathBuf,
+    },
+}
+
+#[derive(Subcommand)]
+enum BackfillCommand {
+    /// List the failed chunks with their last error.
+    Failed,
+    /// Reset a chain's failed chunks so the next start produces them again.
+    Retry {
+        #[arg(long)]
+        chain: String,
+    },
+}
 
-    info!("Starting the ingestion service...");
+#[derive(Subcommand)]
+enum StreamsCommand {
+    /// Stop producing one schema stream of a chain until it is resumed, across restarts.
+    Pause {
+        #[arg(long)]
+        chain: String,
+        #[arg(long)]
+        schema: String,
+        #[arg(long)]
+        reason: Option<String>,
+    },
+    /// Resume a paused stream. Blocks skipped while it was paused are backfilled.
+    Resume {
+        #[arg(long)]
+        chain: String,
+        #[arg(long)]
+        schema: String,
+    },
+    /// List the paused streams.
+    List,
+}
 
+async fn connect_postgres() -> anyhow::Result<PgPool> {
     let database_url = env::var("DATABASE_URL")?;
     let pool = PgPoolOptions::new()
         .max_connections(5)
         .connect(&database_url)
         .await?;
-    let pg_pool = Arc::new(pool);
+    Ok(pool)
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    // Load environment variables
+    dotenv().ok();
+
+    // Initialize the logger
+    logging::init()?;
+
+    let cli = Cli::parse();
+
+    match cli.command.unwrap_or(Command::Run) {
+        Command::Run => {
+            info!("Starting the ingestion service...");
 
-    let pulsar_url = env::var("PULSAR_URL").unwrap_or_else(|_| "pulsar://127.0.0.1:6650".to_string());
-    let pulsar = Arc::new(PulsarClient::new(&pulsar_url).await?);
+            let config = read_config()?;
+            // Dependencies started alongside the service (docker-compose, Kubernetes) get the
+            // startup grace period to come up.
+            let pg_pool = Arc::new(wait_for("Postgres", &config.startup, connect_postgres).await?);
 
+            let queue = connect_queue(&config, config.queue_type).await?;
 
-    // Start the ingestion process
-    run_ingestion(pg_pool, pulsar).await?;
+            // Start the ingestion process
+            run_pipeline(config, pg_pool, queue, Registries::default()).await?;
+        }
+        Command::Snapshot { action: SnapshotCommand::Export { chain, start_block, end_block, out } } => {
+            let pg_pool = connect_postgres().await?;
+            let manifest = export_snapshot(&pg_pool, &chain, start_block, end_block, &out).await?;
+            info!("Exported {} chunks of {} to {}", manifest.chunks.len(), chain, out.display());
+        }
+        Command::Snapshot { action: SnapshotCommand::Import { dir } } => {
+            let pg_pool = connect_postgres().await?;
+            let manifest = import_snapshot(&pg_pool, &dir).await?;
+            info!(
+                "Imported {} blocks {}..={} from {}",
+                manifest.chain_name, manifest.start_block, manifest.end_block, dir.display()
+            );
+        }
+        Command::Backfill { action: BackfillCommand::Failed } => {
+            let pg_pool = connect_postgres().await?;
+            for chunk in list_failed_chunks(&pg_pool).await? {
+                println!(
+                    "{}\t{}\t{}..={}\t{} attempts\t{}",
+                    chunk.chain_name,
+                    chunk.schema,
+                    chunk.start_block,
+                    chunk.end_block,
+                    chunk.attempts,
+                    chunk.last_error.unwrap_or_default()
+                );
+            }
+        }
+        Command::Backfill { action: BackfillCommand::Retry { chain } } => {
+            let pg_pool = connect_postgres().await?;
+            let reset = retry_failed_chunks(&pg_pool, &chain).await?;
+            info!("Reset {} failed backfill chunks of {}", reset, chain);
+        }
+        Command::Streams { action: StreamsCommand::Pause { chain, schema, reason } } => {
+            let pg_pool = connect_postgres().await?;
+            pause_stream(&pg_pool, &chain, &schema, reason.as_deref()).await?;
+            info!("Paused stream {} of {}", schema, chain);
+        }
+        Command::Streams { action: StreamsCommand::Resume { chain, schema } } => {
+            let pg_pool = connect_postgres().await?;
+            if resume_stream(&pg_pool, &chain, &schema).await? {
+                info!("Resumed stream {} of {}", schema, chain);
+            } else {
+                info!("Stream {} of {} was not paused", schema, chain);
+            }
+        }
+        Command::Streams { action: StreamsCommand::List } => {
+            let pg_pool = connect_postgres().await?;
+            for stream in list_paused_streams(&pg_pool).await? {
+                println!(
+                    "{}\t{}\tpaused at {}\t{}",
+                    stream.chain_name,
+                    stream.schema,
+                    stream.paused_at,
+                    stream.reason.unwrap_or_default()
+                );
+            }
+        }
+        Command::Schemas { out } => {
+            std::fs::create_dir_all(&out)?;
+            for topic_schema in topic_schemas() {
+                let path = out.join(format!("{}.schema.json", topic_schema.name));
+                std::fs::write(&path, serde_json::to_vec_pretty(&topic_schema.schema)?)?;
+                info!("Wrote {} (topics {})", path.display(), topic_schema.topics.join(", "));
+            }
+        }
+        Command::Status => {
+            let config = read_config()?;
+            if config.queue_type != QueueType::Pulsar {
+                println!("Policy drift is only checked for Pulsar");
+                return Ok(());
+            }
+            let drift = PulsarAdmin::new(&config.pulsar)?.policy_drift(&config.pulsar).await?;
+            if drift.is_empty() {
+                println!("Pulsar policies match the config");
+            }
+            for policy in drift {
+                println!("{}\t{}\texpected {}\tactual {}", policy.resource, policy.policy, policy.expected, policy.actual);
+            }
+        }
+        Command::Bridge { topic, to, subscription } => {
+            let config = read_config()?;
+            if to == config.queue_type {
+                return Err(anyhow::anyhow!("`--to {}` is the broker it would read from", to.name()));
+            }
+            let source = connect_queue(&config, config.queue_type).await?;
+            let target = connect_queue(&config, to).await?;
+            let topics: Vec<String> = topic.iter().map(|topic| format!("{}{}", config.pulsar.topic_prefix(), topic)).collect();
+            tokio::select! {
+                result = bridge(source, target, &topics, &subscription) => result?,
+                _ = tokio::signal::ctrl_c() => info!("Stopped bridging"),
+            }
+        }
+    }
 
     Ok(())
 }
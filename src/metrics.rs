@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::{Mutex, OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+/// A point-in-time reading of one metric series.
+#[derive(Debug, Clone)]
+pub struct MetricSample {
+    pub name: String,
+    pub labels: Vec<(String, String)>,
+    pub kind: MetricKind,
+    pub value: f64,
+}
+
+type SeriesKey = (String, Vec<(String, String)>);
+
+// Process-wide registry of metric series, keyed by name and sorted labels.
+fn registry() -> &'static Mutex<BTreeMap<SeriesKey, (MetricKind, f64)>> {
+    static REGISTRY: OnceLock<Mutex<BTreeMap<SeriesKey, (MetricKind, f64)>>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(BTreeMap::new()))
+}
+
+fn series_key(name: &str, labels: &[(&str, &str)]) -> SeriesKey {
+    let mut labels: Vec<(String, String)> = labels
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect();
+    labels.sort();
+    (name.to_string(), labels)
+}
+
+/// Adds `value` to a counter series, creating it at zero if needed.
+pub fn increment_counter(name: &str, labels: &[(&str, &str)], value: u64) {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    let entry = registry
+        .entry(series_key(name, labels))
+        .or_insert((MetricKind::Counter, 0.0));
+    entry.1 += value as f64;
+}
+
+/// Sets a gauge series to `value`.
+pub fn set_gauge(name: &str, labels: &[(&str, &str)], value: f64) {
+    let mut registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    registry.insert(series_key(name, labels), (MetricKind::Gauge, value));
+}
+
+/// Returns every registered series.
+pub fn snapshot() -> Vec<MetricSample> {
+    let registry = registry().lock().unwrap_or_else(|e| e.into_inner());
+    registry
+        .iter()
+        .map(|((name, labels), (kind, value))| MetricSample {
+            name: name.clone(),
+            labels: labels.clone(),
+            kind: *kind,
+            value: *value,
+        })
+        .collect()
+}
+
+/// Renders every registered series in the Prometheus text exposition format.
+pub fn render_prometheus() -> String {
+    let mut out = String::new();
+    let mut last_name = String::new();
+    for sample in snapshot() {
+        if sample.name != last_name {
+            let kind = match sample.kind {
+                MetricKind::Counter => "counter",
+                MetricKind::Gauge => "gauge",
+            };
+            let _ = writeln!(out, "# TYPE {} {}", sample.name, kind);
+            last_name = sample.name.clone();
+        }
+        let labels = sample
+            .labels
+            .iter()
+            .map(|(k, v)| format!("{}=\"{}\"", k, v.replace('\\', "\\\\").replace('"', "\\\"")))
+            .collect::<Vec<_>>()
+            .join(",");
+        if labels.is_empty() {
+            let _ = writeln!(out, "{} {}", sample.name, sample.value);
+        } else {
+            let _ = writeln!(out, "{}{{{}}} {}", sample.name, labels, sample.value);
+        }
+    }
+    out
+}
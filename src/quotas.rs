@@ -0,0 +1,502 @@
+use anyhow::Result as AnyResult;
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, TransactionRequest, H256, U256};
+use futures_core::{Future, Stream};
+use futures_util::StreamExt;
+use log::{error, info};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::HashSet;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::alerting::{Alert, Alerter};
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::blockchain::rate_limit::RateLimiter;
+use crate::metrics;
+use crate::storage::sinks::{Sink, WriteOutcome};
+use crate::streams::control::{list_paused_streams, pause_stream, resume_stream};
+use crate::streams::message_queue::queue::{MessageQueue, QueuePublisher, QueueSubscriber};
+
+/// How often usage is checked against the limits and windows are rolled over.
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// What a tenant does once one of its quotas is used up.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum QuotaAction {
+    /// Only alert.
+    #[default]
+    Alert,
+    /// Alert and slow every RPC call of the tenant's chains to `throttled_requests_per_second`.
+    Throttle,
+    /// Alert and pause every stream of the tenant's chains until the window rolls over.
+    Pause,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuotaConfig {
+    /// Chains whose usage counts against this quota.
+    pub chains: Vec<String>,
+    /// Rows the chains' sinks may write per UTC day.
+    pub rows_per_day: Option<u64>,
+    /// RPC calls per hour, subscription events and streamed blocks included.
+    pub rpc_calls_per_hour: Option<u64>,
+    /// Payload bytes the chains' producers may publish per UTC day.
+    pub topic_bytes_per_day: Option<u64>,
+    #[serde(default)]
+    pub action: QuotaAction,
+    /// Request rate allowed while throttled.
+    #[serde(default = "default_throttled_requests_per_second")]
+    pub throttled_requests_per_second: f64,
+}
+
+fn default_throttled_requests_per_second() -> f64 {
+    1.0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Resource {
+    Rows,
+    RpcCalls,
+    TopicBytes,
+}
+
+impl Resource {
+    const ALL: [Resource; 3] = [Resource::Rows, Resource::RpcCalls, Resource::TopicBytes];
+
+    fn name(self) -> &'static str {
+        match self {
+            Resource::Rows => "rows",
+            Resource::RpcCalls => "rpc_calls",
+            Resource::TopicBytes => "topic_bytes",
+        }
+    }
+
+    fn period_secs(self) -> u64 {
+        match self {
+            Resource::RpcCalls => 3600,
+            Resource::Rows | Resource::TopicBytes => 86_400,
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or_default()
+}
+
+#[derive(Default, Clone, Copy)]
+struct Window {
+    /// Start of the window, in Unix seconds.
+    start: u64,
+    used: u64,
+}
+
+/// Usage of one tenant's quotas, counted in calendar windows (UTC days, hours) in memory, so a
+/// restart starts the current window over. Sinks, adapters and publishers of the tenant's chains
+/// report what they use; `run` compares it with the limits and enforces the configured action.
+pub struct QuotaTracker {
+    tenant: String,
+    config: QuotaConfig,
+    /// Every (chain, schema) stream of the tenant, for the `pause` action.
+    streams: Vec<(String, String)>,
+    pg_pool: Arc<PgPool>,
+    alerter: Alerter,
+    windows: Mutex<[Window; 3]>,
+    exceeded: Mutex<HashSet<Resource>>,
+    throttled: AtomicBool,
+    throttle: RateLimiter,
+}
+
+impl QuotaTracker {
+    pub fn new(tenant: &str, config: QuotaConfig, streams: Vec<(String, String)>, pg_pool: Arc<PgPool>, alerter: Alerter) -> Self {
+        let throttle = RateLimiter::new(config.throttled_requests_per_second);
+        for resource in Resource::ALL {
+            if let Some(limit) = limit(&config, resource) {
+                metrics::set_gauge("quota_limit", &[("tenant", tenant), ("resource", resource.name())], limit as f64);
+            }
+        }
+        Self {
+            tenant: tenant.to_string(),
+            config,
+            streams,
+            pg_pool,
+            alerter,
+            windows: Mutex::new([Window::default(); 3]),
+            exceeded: Mutex::new(HashSet::new()),
+            throttled: AtomicBool::new(false),
+            throttle,
+        }
+    }
+
+    /// Adds `amount` to the current window of `resource`.
+    pub fn record(&self, resource: Resource, amount: u64) {
+        let used = {
+            let mut windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+            let window = &mut windows[resource.index()];
+            let start = now_secs() / resource.period_secs() * resource.period_secs();
+            if window.start != start {
+                *window = Window { start, used: 0 };
+            }
+            window.used += amount;
+            window.used
+        };
+        metrics::set_gauge("quota_used", &[("tenant", &self.tenant), ("resource", resource.name())], used as f64);
+    }
+
+    /// Slows the caller down while the tenant is throttled.
+    pub async fn before_call(&self) {
+        if self.throttled.load(Ordering::Relaxed) {
+            self.throttle.acquire().await;
+        }
+    }
+
+    /// Usage of `resource` in its current window, 0 once the window has rolled over.
+    fn used(&self, resource: Resource) -> u64 {
+        let windows = self.windows.lock().unwrap_or_else(|e| e.into_inner());
+        let window = windows[resource.index()];
+        let start = now_secs() / resource.period_secs() * resource.period_secs();
+        if window.start == start {
+            window.used
+        } else {
+            0
+        }
+    }
+
+    fn pause_reason(&self) -> String {
+        format!("Quota {} exceeded", self.tenant)
+    }
+
+    /// Checks usage every `CHECK_INTERVAL`. Runs forever.
+    pub async fn run(&self) -> AnyResult<()> {
+        let mut ticker = tokio::time::interval(CHECK_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.check().await {
+                error!("Failed to enforce quota {}: {}", self.tenant, e);
+            }
+        }
+    }
+
+    async fn check(&self) -> AnyResult<()> {
+        for resource in Resource::ALL {
+            let Some(limit) = limit(&self.config, resource) else {
+                continue;
+            };
+            let used = self.used(resource);
+            let over = used >= limit;
+            let changed = {
+                let mut exceeded = self.exceeded.lock().unwrap_or_else(|e| e.into_inner());
+                if over {
+                    exceeded.insert(resource)
+                } else {
+                    exceeded.remove(&resource)
+                }
+            };
+            metrics::set_gauge(
+                "quota_exceeded",
+                &[("tenant", &self.tenant), ("resource", resource.name())],
+                if over { 1.0 } else { 0.0 },
+            );
+            if !changed {
+                continue;
+            }
+            let (kind, message) = if over {
+                ("quota_exceeded", format!("Quota {} used {} of {} {}", self.tenant, used, limit, resource.name()))
+            } else {
+                ("quota_recovered", format!("Quota {} of {} is available again", self.tenant, resource.name()))
+            };
+            self.alerter
+                .fire(&Alert {
+                    chain_name: self.config.chains.join(","),
+                    kind: kind.to_string(),
+                    message,
+                    details: json!({
+                        "tenant": self.tenant,
+                        "resource": resource.name(),
+                        "used": used,
+                        "limit": limit,
+                        "action": format!("{:?}", self.config.action).to_lowercase(),
+                    }),
+                })
+                .await;
+        }
+
+        let enforcing = !self.exceeded.lock().unwrap_or_else(|e| e.into_inner()).is_empty();
+        match self.config.action {
+            QuotaAction::Alert => {}
+            QuotaAction::Throttle => {
+                if self.throttled.swap(enforcing, Ordering::Relaxed) != enforcing {
+                    info!("Quota {} throttling {}", self.tenant, if enforcing { "started" } else { "lifted" });
+                }
+            }
+            QuotaAction::Pause => self.enforce_pause(enforcing).await?,
+        }
+        Ok(())
+    }
+
+    /// Pauses the tenant's streams, or resumes the ones this quota paused. Streams paused for
+    /// another reason are left alone.
+    async fn enforce_pause(&self, enforcing: bool) -> AnyResult<()> {
+        let reason = self.pause_reason();
+        let paused = list_paused_streams(&self.pg_pool).await?;
+        for (chain_name, schema) in &self.streams {
+            let pause = paused.iter().find(|stream| &stream.chain_name == chain_name && &stream.schema == schema);
+            match (enforcing, pause) {
+                (true, None) => pause_stream(&self.pg_pool, chain_name, schema, Some(&reason)).await?,
+                (false, Some(pause)) if pause.reason.as_deref() == Some(reason.as_str()) => {
+                    resume_stream(&self.pg_pool, chain_name, schema).await?;
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+fn limit(config: &QuotaConfig, resource: Resource) -> Option<u64> {
+    match resource {
+        Resource::Rows => config.rows_per_day,
+        Resource::RpcCalls => config.rpc_calls_per_hour,
+        Resource::TopicBytes => config.topic_bytes_per_day,
+    }
+}
+
+/// Counts the rows the wrapped sink writes against a quota.
+pub struct QuotaSink {
+    inner: Arc<dyn Sink>,
+    tracker: Arc<QuotaTracker>,
+}
+
+impl QuotaSink {
+    pub fn new(inner: Arc<dyn Sink>, tracker: Arc<QuotaTracker>) -> Self {
+        Self { inner, tracker }
+    }
+}
+
+#[async_trait]
+impl Sink for QuotaSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> AnyResult<WriteOutcome> {
+        let outcome = self.inner.write_block(chain_name, block).await?;
+        self.tracker.record(Resource::Rows, 1 + block.transactions.len() as u64);
+        Ok(outcome)
+    }
+
+    async fn write_transactions(&self, chain_name: &str, block: &Block<Transaction>) -> AnyResult<WriteOutcome> {
+        let outcome = self.inner.write_transactions(chain_name, block).await?;
+        self.tracker.record(Resource::Rows, block.transactions.len() as u64);
+        Ok(outcome)
+    }
+
+    async fn write_header(&self, chain_name: &str, block: &Block<Transaction>) -> AnyResult<WriteOutcome> {
+        let outcome = self.inner.write_header(chain_name, block).await?;
+        self.tracker.record(Resource::Rows, 1);
+        Ok(outcome)
+    }
+
+    async fn last_block(&self, chain_name: &str) -> AnyResult<Option<u64>> {
+        self.inner.last_block(chain_name).await
+    }
+
+    async fn flush(&self, chain_name: &str) -> AnyResult<Option<WriteOutcome>> {
+        self.inner.flush(chain_name).await
+    }
+
+    fn flush_interval(&self) -> Option<Duration> {
+        self.inner.flush_interval()
+    }
+
+    fn with_own_buffer(&self) -> Option<Arc<dyn Sink>> {
+        let inner = self.inner.with_own_buffer()?;
+        Some(Arc::new(Self::new(inner, Arc::clone(&self.tracker))))
+    }
+}
+
+/// Counts the payload bytes published through the wrapped queue against a quota.
+pub struct QuotaQueue {
+    inner: Arc<dyn MessageQueue>,
+    tracker: Arc<QuotaTracker>,
+}
+
+impl QuotaQueue {
+    pub fn new(inner: Arc<dyn MessageQueue>, tracker: Arc<QuotaTracker>) -> Self {
+        Self { inner, tracker }
+    }
+}
+
+#[async_trait]
+impl MessageQueue for QuotaQueue {
+    async fn publisher(&self, topic: &str) -> AnyResult<Box<dyn QueuePublisher>> {
+        Ok(Box::new(QuotaPublisher { inner: self.inner.publisher(topic).await?, tracker: Arc::clone(&self.tracker) }))
+    }
+
+    async fn subscriber(&self, topic: &str, subscription: &str) -> AnyResult<Box<dyn QueueSubscriber>> {
+        self.inner.subscriber(topic, subscription).await
+    }
+}
+
+struct QuotaPublisher {
+    inner: Box<dyn QueuePublisher>,
+    tracker: Arc<QuotaTracker>,
+}
+
+#[async_trait]
+impl QueuePublisher for QuotaPublisher {
+    async fn publish(&self, payload: Vec<u8>) -> AnyResult<()> {
+        let bytes = payload.len() as u64;
+        self.inner.publish(payload).await?;
+        self.tracker.record(Resource::TopicBytes, bytes);
+        Ok(())
+    }
+
+    async fn publish_block(&self, block_number: u64, payload: Vec<u8>) -> AnyResult<()> {
+        let bytes = payload.len() as u64;
+        self.inner.publish_block(block_number, payload).await?;
+        self.tracker.record(Resource::TopicBytes, bytes);
+        Ok(())
+    }
+}
+
+/// Counts the wrapped adapter's requests against a quota, and holds them back while the quota
+/// throttles.
+pub struct QuotaAdapter {
+    inner: Arc<dyn BlockchainAdapter>,
+    tracker: Arc<QuotaTracker>,
+}
+
+impl QuotaAdapter {
+    pub fn new(inner: Arc<dyn BlockchainAdapter>, tracker: Arc<QuotaTracker>) -> Self {
+        Self { inner, tracker }
+    }
+
+    fn counted<T, F, Fut>(&self, call: F) -> Pin<Box<dyn Future<Output = AnyResult<T>> + Send>>
+    where
+        T: Send + 'static,
+        F: FnOnce(Arc<dyn BlockchainAdapter>) -> Fut + Send + 'static,
+        Fut: Future<Output = AnyResult<T>> + Send + 'static,
+    {
+        let inner = Arc::clone(&self.inner);
+        let tracker = Arc::clone(&self.tracker);
+        Box::pin(async move {
+            tracker.before_call().await;
+            tracker.record(Resource::RpcCalls, 1);
+            call(inner).await
+        })
+    }
+}
+
+impl BlockchainAdapter for QuotaAdapter {
+
+    fn get_block_by_number(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<Transaction>>>> + Send>> {
+        self.counted(move |inner| inner.get_block_by_number(block_number))
+    }
+
+    fn get_block_with_hashes(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Option<Block<H256>>>> + Send>> {
+        self.counted(move |inner| inner.get_block_with_hashes(block_number))
+    }
+
+    // Subscription events are counted, but can't be throttled.
+    fn subscribe_new_blocks(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>> {
+        let tracker = Arc::clone(&self.tracker);
+        let mut stream = self.inner.subscribe_new_blocks();
+        Box::pin(async_stream::stream! {
+            while let Some(block) = stream.next().await {
+                tracker.record(Resource::RpcCalls, 1);
+                yield block;
+            }
+        })
+    }
+
+    fn subscribe_pending_transactions(
+        &self,
+    ) -> Pin<Box<dyn Stream<Item = AnyResult<Transaction>> + Send>> {
+        let tracker = Arc::clone(&self.tracker);
+        let mut stream = self.inner.subscribe_pending_transactions();
+        Box::pin(async_stream::stream! {
+            while let Some(transaction) = stream.next().await {
+                tracker.record(Resource::RpcCalls, 1);
+                yield transaction;
+            }
+        })
+    }
+
+    fn get_latest_block_number(
+        &self,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<u64>> + Send>> {
+        self.counted(|inner| inner.get_latest_block_number())
+    }
+
+    // Each streamed block counts as a call, and the next one isn't pulled while throttled.
+    fn stream_blocks(
+        &self,
+        start_block: u64,
+        end_block: u64,
+    ) -> Option<Pin<Box<dyn Stream<Item = AnyResult<Block<Transaction>>> + Send>>> {
+        let tracker = Arc::clone(&self.tracker);
+        let mut stream = self.inner.stream_blocks(start_block, end_block)?;
+        Some(Box::pin(async_stream::stream! {
+            loop {
+                tracker.before_call().await;
+                let Some(block) = stream.next().await else {
+                    break;
+                };
+                tracker.record(Resource::RpcCalls, 1);
+                yield block;
+            }
+        }))
+    }
+
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<U256>> + Send>> {
+        self.counted(move |inner| inner.get_balance(address, block_number))
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<Log>>> + Send>> {
+        self.counted(move |inner| inner.get_logs(block_number))
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.counted(move |inner| inner.call(to, data, block_number))
+    }
+
+    fn call_request(
+        &self,
+        request: TransactionRequest,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Bytes>> + Send>> {
+        self.counted(move |inner| inner.call_request(request, block_number))
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = AnyResult<Vec<TransactionReceipt>>> + Send>> {
+        self.counted(move |inner| inner.get_block_receipts(block_number))
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Row};
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::shutdown::instance_name;
+use crate::storage::sinks::Sink;
+use crate::streams::message_queue::pulsar_admin::PulsarAdmin;
+
+/// Most gaps listed per chain, oldest first.
+const MAX_GAPS: i64 = 100;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RecoveryConfig {
+    pub enabled: bool,
+    /// How many blocks below the last stored one are checked for gaps, on the Postgres sink.
+    pub gap_scan_blocks: u64,
+    /// Also plan the gaps found as backfill chunks of the chain's primary schema.
+    pub repair_gaps: bool,
+}
+
+impl Default for RecoveryConfig {
+    fn default() -> Self {
+        Self { enabled: false, gap_scan_blocks: 100_000, repair_gaps: false }
+    }
+}
+
+/// What the service found for a chain at startup and is about to do about it.
+#[derive(Debug, Default, Serialize)]
+pub struct ChainRecovery {
+    pub chain_name: String,
+    pub instance: String,
+    pub head_block: Option<u64>,
+    pub sink_last_block: Option<u64>,
+    /// The chain's last stored block as recorded by the latest graceful shutdown.
+    pub shutdown_cursor: Option<u64>,
+    /// Whether this instance's run of the chain before this one ended without a graceful shutdown.
+    pub unclean_shutdown: bool,
+    /// The range the historical producer will publish first, as `[start, end]`.
+    pub resume_range: Option<(u64, u64)>,
+    /// Missing block ranges below the last stored block, as `[start, end]`.
+    pub gaps: Vec<(u64, u64)>,
+    /// Gap ranges planned as backfill chunks.
+    pub planned_repairs: Vec<(u64, u64)>,
+    pub pending_chunks: i64,
+    pub failed_chunks: i64,
+    /// Messages waiting on the chain's subscriptions, on Pulsar with the admin API.
+    pub queue_backlog: Option<u64>,
+    pub warnings: Vec<String>,
+}
+
+impl ChainRecovery {
+    /// Compares the sink with the chain head, the latest shutdown summary and the backfill
+    /// chunks. `resume_range` is what ingestion already decided to resume, and on the Postgres
+    /// sink `blocks_table` is where gaps are looked for (see `stored_blocks_table`).
+    pub async fn inspect(
+        pg_pool: &PgPool,
+        chain_name: &str,
+        sink_type: &str,
+        blocks_table: &str,
+        sink: &dyn Sink,
+        adapter: &dyn BlockchainAdapter,
+        resume_range: Option<(u64, u64)>,
+        config: &RecoveryConfig,
+    ) -> Result<Self> {
+        let mut recovery = Self {
+            chain_name: chain_name.to_string(),
+            instance: instance_name(),
+            head_block: adapter.get_latest_block_number().await.ok(),
+            sink_last_block: sink.last_block(chain_name).await?,
+            ..Default::default()
+        };
+        // An open-ended range runs up to the head.
+        recovery.resume_range = resume_range.map(|(start_block, end_block)| match end_block {
+            u64::MAX => (start_block, recovery.head_block.unwrap_or(end_block)),
+            end_block => (start_block, end_block),
+        });
+
+        // Each start records a report and each graceful shutdown a summary, so a report of this
+        // instance newer than its latest summary covering the chain means the last run was killed.
+        let previous = sqlx::query(
+            "SELECT
+                (SELECT cursors -> $1 FROM shutdown_summaries WHERE cursors ? $1 ORDER BY at DESC LIMIT 1) AS cursor,
+                (SELECT MAX(at) FROM recovery_reports WHERE chain_name = $1 AND instance = $2)
+                    > COALESCE(
+                        (SELECT MAX(at) FROM shutdown_summaries WHERE instance = $2 AND cursors ? $1),
+                        '-infinity'
+                    ) AS unclean",
+        )
+        .bind(chain_name)
+        .bind(&recovery.instance)
+        .fetch_one(pg_pool)
+        .await?;
+        recovery.shutdown_cursor = previous.get::<Option<serde_json::Value>, _>("cursor").and_then(|cursor| cursor.as_u64());
+        recovery.unclean_shutdown = previous.get::<Option<bool>, _>("unclean").unwrap_or(false);
+        if recovery.unclean_shutdown {
+            recovery.warnings.push("The previous run ended without a graceful shutdown; unacknowledged messages will be redelivered".to_string());
+        }
+        if let (Some(cursor), sink_last_block) = (recovery.shutdown_cursor, recovery.sink_last_block) {
+            if sink_last_block.map_or(true, |last_block| last_block < cursor) {
+                recovery.warnings.push(format!(
+                    "The sink holds blocks up to {:?}, behind block {} recorded at the last shutdown",
+                    sink_last_block, cursor
+                ));
+            }
+        }
+
+        if let (Some(last_block), "postgres") = (recovery.sink_last_block, sink_type) {
+            recovery.gaps =
+                find_gaps(pg_pool, chain_name, blocks_table, last_block.saturating_sub(config.gap_scan_blocks), last_block).await?;
+            if config.repair_gaps {
+                recovery.planned_repairs = recovery.gaps.clone();
+            }
+        }
+
+        let chunks = sqlx::query(
+            "SELECT COUNT(*) FILTER (WHERE status IN ('pending', 'running')) AS pending_chunks,
+                COUNT(*) FILTER (WHERE status = 'failed') AS failed_chunks
+            FROM backfill_chunks WHERE chain_name = $1",
+        )
+        .bind(chain_name)
+        .fetch_one(pg_pool)
+        .await?;
+        recovery.pending_chunks = chunks.get("pending_chunks");
+        recovery.failed_chunks = chunks.get("failed_chunks");
+        if recovery.failed_chunks > 0 {
+            recovery.warnings.push(format!("{} backfill chunks failed and won't be retried until reset", recovery.failed_chunks));
+        }
+        Ok(recovery)
+    }
+
+    /// Sums the backlog of the chain's subscriptions, given as (topic, subscription).
+    pub async fn read_backlog(&mut self, admin: &PulsarAdmin, subscriptions: &[(String, String)]) {
+        let mut backlog = 0;
+        for (topic, subscription) in subscriptions {
+            match admin.subscription_backlog(topic, subscription).await {
+                Ok(messages) => backlog += messages.unwrap_or(0),
+                Err(e) => {
+                    warn!("Failed to read the backlog of {}: {}", subscription, e);
+                    return;
+                }
+            }
+        }
+        self.queue_backlog = Some(backlog);
+    }
+
+    /// Logs the report and records it in `recovery_reports`.
+    pub async fn record(&self, pg_pool: &PgPool) -> Result<()> {
+        info!(
+            "Recovery of {}: head {:?}, sink at {:?}, last shutdown at {:?}, resuming {:?}, {} gaps ({} planned for repair), {} pending and {} failed backfill chunks, queue backlog {:?}",
+            self.chain_name,
+            self.head_block,
+            self.sink_last_block,
+            self.shutdown_cursor,
+            self.resume_range,
+            self.gaps.len(),
+            self.planned_repairs.len(),
+            self.pending_chunks,
+            self.failed_chunks,
+            self.queue_backlog
+        );
+        for warning in &self.warnings {
+            warn!("Recovery of {}: {}", self.chain_name, warning);
+        }
+        sqlx::query("INSERT INTO recovery_reports (chain_name, instance, report) VALUES ($1, $2, $3)")
+            .bind(&self.chain_name)
+            .bind(&self.instance)
+            .bind(json!(self))
+            .execute(pg_pool)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Ranges of canonical blocks missing from `table` of the Postgres sink in `from..=to`.
+async fn find_gaps(pg_pool: &PgPool, chain_name: &str, table: &str, from: u64, to: u64) -> Result<Vec<(u64, u64)>> {
+    let rows = sqlx::query(&format!(
+        "SELECT previous + 1 AS gap_start, block_number - 1 AS gap_end FROM (
+            SELECT block_number, LAG(block_number) OVER (ORDER BY block_number) AS previous
+            FROM {} WHERE chain_name = $1 AND canonical AND block_number BETWEEN $2 AND $3
+        ) numbered
+        WHERE block_number > previous + 1
+        ORDER BY gap_start LIMIT $4",
+        table
+    ))
+    .bind(chain_name)
+    .bind(from as i64)
+    .bind(to as i64)
+    .bind(MAX_GAPS)
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| (row.get::<i64, _>("gap_start") as u64, row.get::<i64, _>("gap_end") as u64))
+        .collect())
+}
@@ -0,0 +1,158 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use serde_json::Value;
+use std::env;
+use std::sync::{OnceLock, RwLock};
+
+const MASK: &str = "***";
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SecretRedactionConfig {
+    pub enabled: bool,
+    /// Query parameters of URLs whose values are masked, compared case-insensitively.
+    pub query_params: Vec<String>,
+    /// URL path segments at least this long that mix letters and digits are taken for API keys,
+    /// as in `https://eth-mainnet.g.alchemy.com/v2/<key>`. 0 leaves paths alone.
+    pub min_key_length: usize,
+    /// Env vars whose values are masked wherever they appear, e.g. tokens that aren't in a URL.
+    pub secret_envs: Vec<String>,
+}
+
+impl Default for SecretRedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            query_params: ["key", "apikey", "api_key", "api-key", "token", "access_token", "auth", "secret", "password"]
+                .map(String::from)
+                .to_vec(),
+            min_key_length: 20,
+            secret_envs: Vec::new(),
+        }
+    }
+}
+
+/// Masks secrets in text before it leaves the process: credentials, API key query parameters and
+/// key-like path segments of URLs, and the values of configured env vars.
+struct Redactor {
+    config: SecretRedactionConfig,
+    secrets: Vec<String>,
+}
+
+fn redactor() -> &'static RwLock<Redactor> {
+    static REDACTOR: OnceLock<RwLock<Redactor>> = OnceLock::new();
+    REDACTOR.get_or_init(|| RwLock::new(Redactor { config: SecretRedactionConfig::default(), secrets: Vec::new() }))
+}
+
+/// Applies the config. Until then the defaults are used, so logs from startup are covered too.
+pub fn configure(config: &SecretRedactionConfig) -> Result<()> {
+    let secrets = config
+        .secret_envs
+        .iter()
+        .map(|key| env::var(key).with_context(|| format!("Failed to get secret to redact from environment for key `{}`", key)))
+        .collect::<Result<Vec<_>>>()?;
+    *redactor().write().unwrap_or_else(|e| e.into_inner()) = Redactor {
+        config: config.clone(),
+        // Short values would mask unrelated text.
+        secrets: secrets.into_iter().filter(|secret| secret.len() >= 8).collect(),
+    };
+    Ok(())
+}
+
+/// `text` with its secrets masked.
+pub fn redact(text: &str) -> String {
+    let redactor = redactor().read().unwrap_or_else(|e| e.into_inner());
+    if !redactor.config.enabled {
+        return text.to_string();
+    }
+    let mut redacted = redact_urls(text, &redactor.config);
+    for secret in &redactor.secrets {
+        redacted = redacted.replace(secret.as_str(), MASK);
+    }
+    redacted
+}
+
+/// Redacts every string in `value`, e.g. the details of an alert.
+pub fn redact_json(value: &Value) -> Value {
+    match value {
+        Value::String(text) => Value::String(redact(text)),
+        Value::Array(values) => Value::Array(values.iter().map(redact_json).collect()),
+        Value::Object(fields) => Value::Object(fields.iter().map(|(key, value)| (key.clone(), redact_json(value))).collect()),
+        value => value.clone(),
+    }
+}
+
+fn redact_urls(text: &str, config: &SecretRedactionConfig) -> String {
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(separator) = rest.find("://") {
+        let scheme_start = rest[..separator]
+            .rfind(|c: char| !(c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.')))
+            .map_or(0, |i| i + 1);
+        let url_end = rest[separator..]
+            .find(|c: char| c.is_whitespace() || matches!(c, '"' | '\'' | '<' | '>' | '`' | ')' | ']' | '}' | ','))
+            .map_or(rest.len(), |i| separator + i);
+        redacted.push_str(&rest[..scheme_start]);
+        if scheme_start == separator {
+            // No scheme, so not a URL.
+            redacted.push_str("://");
+            rest = &rest[separator + 3..];
+            continue;
+        }
+        redacted.push_str(&redact_url(&rest[scheme_start..url_end], config));
+        rest = &rest[url_end..];
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+/// `url` with its credentials, API key query parameters and key-like path segments masked.
+pub fn redact_url(url: &str, config: &SecretRedactionConfig) -> String {
+    let (scheme, rest) = url.split_once("://").unwrap_or(("", url));
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let (authority, rest) = rest.split_at(authority_end);
+    let authority = match authority.rsplit_once('@') {
+        Some((credentials, host)) => match credentials.split_once(':') {
+            Some((user, _)) => format!("{}:{}@{}", user, MASK, host),
+            None => format!("{}@{}", MASK, host),
+        },
+        None => authority.to_string(),
+    };
+
+    let (rest, fragment) = rest.split_once('#').map_or((rest, None), |(rest, fragment)| (rest, Some(fragment)));
+    let (path, query) = rest.split_once('?').map_or((rest, None), |(path, query)| (path, Some(query)));
+    let path = path
+        .split('/')
+        .map(|segment| if looks_like_key(segment, config.min_key_length) { MASK } else { segment })
+        .collect::<Vec<_>>()
+        .join("/");
+
+    let mut redacted = format!("{}://{}{}", scheme, authority, path);
+    if let Some(query) = query {
+        let query = query
+            .split('&')
+            .map(|pair| match pair.split_once('=') {
+                Some((name, _)) if config.query_params.iter().any(|param| param.eq_ignore_ascii_case(name)) => format!("{}={}", name, MASK),
+                _ => pair.to_string(),
+            })
+            .collect::<Vec<_>>()
+            .join("&");
+        redacted.push('?');
+        redacted.push_str(&query);
+    }
+    if let Some(fragment) = fragment {
+        redacted.push('#');
+        redacted.push_str(fragment);
+    }
+    redacted
+}
+
+/// Whether a URL path segment is taken for an API key: at least `min_length` long, made of
+/// letters, digits, `-` and `_`, with both letters and digits.
+pub fn looks_like_key(segment: &str, min_length: usize) -> bool {
+    min_length > 0
+        && segment.len() >= min_length
+        && segment.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        && segment.chars().any(|c| c.is_ascii_digit())
+        && segment.chars().any(|c| c.is_ascii_alphabetic())
+}
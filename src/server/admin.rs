@@ -0,0 +1,290 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use axum::{
+    extract::{FromRequestParts, Path, State},
+    http::{header::AUTHORIZATION, request::Parts, StatusCode},
+    routing::{get, post, put},
+    Json, Router,
+};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::PgPool;
+use std::env;
+use std::sync::Arc;
+
+use crate::logging::{self, LogFilter};
+use crate::metrics;
+use crate::streams::control::{list_paused_streams, pause_stream, resume_stream, PausedStream};
+use crate::streams::producers::backfill_jobs::{list_failed_chunks, retry_failed_chunks, FailedChunk};
+
+/// What a caller of the admin API may do. Each role may do everything the ones before it may.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    /// Read status and metrics.
+    Viewer,
+    /// Also pause and resume streams, and change log levels.
+    Operator,
+    /// Also retry failed backfills.
+    Admin,
+}
+
+impl Role {
+    fn name(self) -> &'static str {
+        match self {
+            Role::Viewer => "viewer",
+            Role::Operator => "operator",
+            Role::Admin => "admin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ApiToken {
+    /// Who holds the token, as recorded in the audit log.
+    pub name: String,
+    /// Env var holding the token.
+    pub token_env: String,
+    pub role: Role,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminApiConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_bind_addr")]
+    pub bind_addr: String,
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+    /// Role of requests without a token, e.g. `viewer` to leave status open. Without it they are
+    /// rejected.
+    pub anonymous_role: Option<Role>,
+}
+
+fn default_bind_addr() -> String {
+    "0.0.0.0:8081".to_string()
+}
+
+impl Default for AdminApiConfig {
+    fn default() -> Self {
+        Self { enabled: false, bind_addr: default_bind_addr(), tokens: Vec::new(), anonymous_role: None }
+    }
+}
+
+pub struct AdminState {
+    pg_pool: Arc<PgPool>,
+    /// (token, holder, role)
+    tokens: Vec<(String, String, Role)>,
+    anonymous_role: Option<Role>,
+}
+
+impl AdminState {
+    /// Resolves the configured token env vars.
+    pub fn new(pg_pool: Arc<PgPool>, config: &AdminApiConfig) -> Result<Self> {
+        let tokens = config
+            .tokens
+            .iter()
+            .map(|token| {
+                let value = env::var(&token.token_env)
+                    .with_context(|| format!("Failed to get admin API token from environment for key `{}`", token.token_env))?;
+                Ok((value, token.name.clone(), token.role))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { pg_pool, tokens, anonymous_role: config.anonymous_role })
+    }
+
+    /// Appends to `admin_audit_log`. A failed write is logged rather than failing the request.
+    async fn audit(&self, caller: &Caller, action: &str, target: &str, allowed: bool, details: Value) {
+        info!("Admin API: {} ({}) {} {} {}", caller.name, caller.role.name(), action, target, if allowed { "allowed" } else { "denied" });
+        let result = sqlx::query(
+            "INSERT INTO admin_audit_log (actor, role, action, target, allowed, details) VALUES ($1, $2, $3, $4, $5, $6)",
+        )
+        .bind(&caller.name)
+        .bind(caller.role.name())
+        .bind(action)
+        .bind(target)
+        .bind(allowed)
+        .bind(details)
+        .execute(self.pg_pool.as_ref())
+        .await;
+        if let Err(e) = result {
+            error!("Failed to write admin audit log: {}", e);
+        }
+    }
+}
+
+/// The authenticated caller of a request.
+pub struct Caller {
+    name: String,
+    role: Role,
+}
+
+impl Caller {
+    /// Rejects callers below `role`, recording the attempt when it changes something.
+    async fn require(&self, state: &AdminState, role: Role, action: &str, target: &str) -> Result<(), StatusCode> {
+        if self.role >= role {
+            return Ok(());
+        }
+        metrics::increment_counter("admin_api_denied_total", &[("action", action)], 1);
+        if role > Role::Viewer {
+            state.audit(self, action, target, false, json!({ "required_role": role.name() })).await;
+        }
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+#[async_trait]
+impl FromRequestParts<Arc<AdminState>> for Caller {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &Arc<AdminState>) -> Result<Self, Self::Rejection> {
+        let token = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "));
+        let Some(token) = token else {
+            return state
+                .anonymous_role
+                .map(|role| Caller { name: "anonymous".to_string(), role })
+                .ok_or(StatusCode::UNAUTHORIZED);
+        };
+        match state.tokens.iter().find(|(value, _, _)| constant_time_eq(value.as_bytes(), token.as_bytes())) {
+            Some((_, name, role)) => Ok(Caller { name: name.clone(), role: *role }),
+            None => {
+                warn!("Admin API request with an unknown token");
+                Err(StatusCode::UNAUTHORIZED)
+            }
+        }
+    }
+}
+
+/// Compares without stopping at the first differing byte, so response times don't leak how much
+/// of a guessed token is right.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// Serves the admin API until the listener fails: status for viewers, stream pauses and log levels
+/// for operators, backfill retries for admins. Every change is recorded in `admin_audit_log`.
+pub async fn serve(config: &AdminApiConfig, state: Arc<AdminState>) -> Result<()> {
+    let app = Router::new()
+        .route("/status", get(status_handler))
+        .route("/metrics", get(metrics_handler))
+        .route("/streams/:chain/:schema/pause", post(pause_handler))
+        .route("/streams/:chain/:schema/resume", post(resume_handler))
+        .route("/backfills/:chain/retry", post(retry_handler))
+        .route("/log-level", put(set_log_level_handler).get(log_level_handler).delete(reset_log_level_handler))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&config.bind_addr)
+        .await
+        .with_context(|| format!("Failed to bind admin API to {}", config.bind_addr))?;
+    info!("Admin API listening on {}", config.bind_addr);
+
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+fn internal_error(e: anyhow::Error) -> StatusCode {
+    error!("Admin API request failed: {}", e);
+    StatusCode::INTERNAL_SERVER_ERROR
+}
+
+#[derive(Serialize)]
+struct Status {
+    paused_streams: Vec<PausedStream>,
+    failed_backfill_chunks: Vec<FailedChunk>,
+}
+
+async fn status_handler(caller: Caller, State(state): State<Arc<AdminState>>) -> Result<Json<Status>, StatusCode> {
+    caller.require(&state, Role::Viewer, "status", "").await?;
+    Ok(Json(Status {
+        paused_streams: list_paused_streams(&state.pg_pool).await.map_err(internal_error)?,
+        failed_backfill_chunks: list_failed_chunks(&state.pg_pool).await.map_err(internal_error)?,
+    }))
+}
+
+async fn metrics_handler(caller: Caller, State(state): State<Arc<AdminState>>) -> Result<String, StatusCode> {
+    caller.require(&state, Role::Viewer, "metrics", "").await?;
+    Ok(metrics::render_prometheus())
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct PauseRequest {
+    reason: Option<String>,
+}
+
+async fn pause_handler(
+    caller: Caller,
+    State(state): State<Arc<AdminState>>,
+    Path((chain, schema)): Path<(String, String)>,
+    request: Option<Json<PauseRequest>>,
+) -> Result<StatusCode, StatusCode> {
+    let target = format!("{}/{}", chain, schema);
+    caller.require(&state, Role::Operator, "pause_stream", &target).await?;
+    let reason = request.map(|Json(request)| request).unwrap_or_default().reason;
+    pause_stream(&state.pg_pool, &chain, &schema, reason.as_deref()).await.map_err(internal_error)?;
+    state.audit(&caller, "pause_stream", &target, true, json!({ "reason": reason })).await;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn resume_handler(
+    caller: Caller,
+    State(state): State<Arc<AdminState>>,
+    Path((chain, schema)): Path<(String, String)>,
+) -> Result<StatusCode, StatusCode> {
+    let target = format!("{}/{}", chain, schema);
+    caller.require(&state, Role::Operator, "resume_stream", &target).await?;
+    let resumed = resume_stream(&state.pg_pool, &chain, &schema).await.map_err(internal_error)?;
+    state.audit(&caller, "resume_stream", &target, true, json!({ "was_paused": resumed })).await;
+    Ok(if resumed { StatusCode::NO_CONTENT } else { StatusCode::NOT_FOUND })
+}
+
+async fn retry_handler(
+    caller: Caller,
+    State(state): State<Arc<AdminState>>,
+    Path(chain): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    caller.require(&state, Role::Admin, "retry_backfill", &chain).await?;
+    let reset = retry_failed_chunks(&state.pg_pool, &chain).await.map_err(internal_error)?;
+    state.audit(&caller, "retry_backfill", &chain, true, json!({ "chunks": reset })).await;
+    Ok(Json(json!({ "reset_chunks": reset })))
+}
+
+#[derive(Serialize)]
+struct LogLevel {
+    filter: Option<String>,
+}
+
+async fn log_level_handler(caller: Caller, State(state): State<Arc<AdminState>>) -> Result<Json<LogLevel>, StatusCode> {
+    caller.require(&state, Role::Viewer, "log_level", "").await?;
+    Ok(Json(LogLevel { filter: logging::current_filter().map(|filter| filter.to_string()) }))
+}
+
+#[derive(Debug, Deserialize)]
+struct SetLogLevelRequest {
+    /// In `RUST_LOG` syntax, e.g. `info,evm_adapter=debug`.
+    filter: String,
+}
+
+async fn set_log_level_handler(
+    caller: Caller,
+    State(state): State<Arc<AdminState>>,
+    Json(request): Json<SetLogLevelRequest>,
+) -> Result<Json<LogLevel>, (StatusCode, String)> {
+    caller.require(&state, Role::Operator, "set_log_level", "").await.map_err(|status| (status, String::new()))?;
+    let filter = request.filter.parse::<LogFilter>().map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))?;
+    let filter = logging::set_filter(Some(filter)).map_err(|e| (internal_error(e), String::new()))?;
+    state.audit(&caller, "set_log_level", "", true, json!({ "filter": filter.to_string() })).await;
+    Ok(Json(LogLevel { filter: Some(filter.to_string()) }))
+}
+
+/// Restores the filter the service started with.
+async fn reset_log_level_handler(caller: Caller, State(state): State<Arc<AdminState>>) -> Result<Json<LogLevel>, StatusCode> {
+    caller.require(&state, Role::Operator, "reset_log_level", "").await?;
+    let filter = logging::set_filter(None).map_err(internal_error)?;
+    state.audit(&caller, "reset_log_level", "", true, json!({ "filter": filter.to_string() })).await;
+    Ok(Json(LogLevel { filter: Some(filter.to_string()) }))
+}
@@ -0,0 +1,270 @@
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Html,
+    routing::get,
+    Json, Router,
+};
+use futures::future;
+use log::{error, info};
+use serde::{Deserialize, Serialize};
+use sqlx::{PgPool, Row};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::secrets;
+use crate::storage::sinks::Sink;
+use crate::streams::producers::watchdog::Heartbeat;
+
+/// Errors kept for the dashboard; older ones are dropped.
+const RECENT_ERRORS: usize = 50;
+/// How long a chain's head and ingested block lookups get before the chain is shown without them.
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long a head looked up from the node is reused, across refreshes and open tabs.
+const HEAD_CACHE_TTL: Duration = Duration::from_secs(30);
+/// A producer heartbeat older than this doesn't give the head, e.g. on a standby that led before.
+const HEARTBEAT_MAX_AGE: Duration = Duration::from_secs(60);
+
+const INDEX_HTML: &str = include_str!("dashboard.html");
+
+#[derive(Debug, Deserialize)]
+pub struct DashboardConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_bind_addr")]
+    pub bind_addr: String,
+}
+
+// Unauthenticated, so only local by default.
+fn default_bind_addr() -> String {
+    "127.0.0.1:8082".to_string()
+}
+
+impl Default for DashboardConfig {
+    fn default() -> Self {
+        Self { enabled: false, bind_addr: default_bind_addr() }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskState {
+    Running,
+    Finished,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskHealth {
+    pub name: String,
+    pub state: TaskState,
+    pub since: u64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RecentError {
+    pub source: String,
+    pub message: String,
+    pub at: u64,
+}
+
+#[derive(Default)]
+struct Health {
+    tasks: BTreeMap<String, TaskHealth>,
+    errors: VecDeque<RecentError>,
+}
+
+// Process-wide, like the metrics registry, so tasks on any runtime can report to it.
+fn health() -> &'static Mutex<Health> {
+    static HEALTH: OnceLock<Mutex<Health>> = OnceLock::new();
+    HEALTH.get_or_init(|| Mutex::new(Health::default()))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|now| now.as_secs()).unwrap_or_default()
+}
+
+/// Marks a long-running task as started.
+pub fn task_started(name: &str) {
+    let mut health = health().lock().unwrap_or_else(|e| e.into_inner());
+    health.tasks.insert(name.to_string(), TaskHealth { name: name.to_string(), state: TaskState::Running, since: unix_now() });
+}
+
+/// Marks a task as ended, recording its error if it failed.
+pub fn task_finished(name: &str, result: &Result<()>) {
+    let state = match result {
+        Ok(()) => TaskState::Finished,
+        Err(e) => {
+            record_error(name, &format!("{:#}", e));
+            TaskState::Failed
+        }
+    };
+    let mut health = health().lock().unwrap_or_else(|e| e.into_inner());
+    health.tasks.insert(name.to_string(), TaskHealth { name: name.to_string(), state, since: unix_now() });
+}
+
+/// Adds an error to the dashboard's recent errors.
+pub fn record_error(source: &str, message: &str) {
+    let mut health = health().lock().unwrap_or_else(|e| e.into_inner());
+    health.errors.push_back(RecentError { source: source.to_string(), message: secrets::redact(message), at: unix_now() });
+    while health.errors.len() > RECENT_ERRORS {
+        health.errors.pop_front();
+    }
+}
+
+pub struct DashboardState {
+    pg_pool: Arc<PgPool>,
+    adapters: HashMap<String, Arc<dyn BlockchainAdapter>>,
+    sinks: HashMap<String, Arc<dyn Sink>>,
+    heartbeats: HashMap<String, Vec<Arc<Heartbeat>>>,
+    /// Heads looked up from the node, for chains without a current heartbeat.
+    heads: Mutex<HashMap<String, (Instant, Option<u64>)>>,
+}
+
+impl DashboardState {
+    pub fn new(
+        pg_pool: Arc<PgPool>,
+        adapters: HashMap<String, Arc<dyn BlockchainAdapter>>,
+        sinks: HashMap<String, Arc<dyn Sink>>,
+    ) -> Self {
+        Self { pg_pool, adapters, sinks, heartbeats: HashMap::new(), heads: Mutex::new(HashMap::new()) }
+    }
+
+    /// Shows each chain's head as the last block its realtime producers published, so refreshes
+    /// don't spend RPC quota. Chains without a recent heartbeat are looked up from the node.
+    pub fn with_heartbeats(mut self, heartbeats: HashMap<String, Vec<Arc<Heartbeat>>>) -> Self {
+        self.heartbeats = heartbeats;
+        self
+    }
+
+    async fn head(&self, chain_name: &str) -> Option<u64> {
+        let published = self
+            .heartbeats
+            .get(chain_name)
+            .into_iter()
+            .flatten()
+            .filter(|heartbeat| heartbeat.since_last_beat() < HEARTBEAT_MAX_AGE)
+            .filter_map(|heartbeat| heartbeat.last_block())
+            .max();
+        if published.is_some() {
+            return published;
+        }
+        if let Some((at, head)) = self.heads.lock().unwrap_or_else(|e| e.into_inner()).get(chain_name) {
+            if at.elapsed() < HEAD_CACHE_TTL {
+                return *head;
+            }
+        }
+        let adapter = self.adapters.get(chain_name)?;
+        let head = tokio::time::timeout(LOOKUP_TIMEOUT, adapter.get_latest_block_number()).await.ok().and_then(Result::ok);
+        self.heads.lock().unwrap_or_else(|e| e.into_inner()).insert(chain_name.to_string(), (Instant::now(), head));
+        head
+    }
+
+    async fn chain(&self, chain_name: &str) -> ChainStatus {
+        let head = self.head(chain_name).await;
+        let ingested = match self.sinks.get(chain_name) {
+            Some(sink) => tokio::time::timeout(LOOKUP_TIMEOUT, sink.last_block(chain_name)).await.ok().and_then(Result::ok).flatten(),
+            None => None,
+        };
+        ChainStatus {
+            chain_name: chain_name.to_string(),
+            head_block: head,
+            ingested_block: ingested,
+            backlog_blocks: head.zip(ingested).map(|(head, ingested)| head.saturating_sub(ingested)),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ChainStatus {
+    chain_name: String,
+    head_block: Option<u64>,
+    ingested_block: Option<u64>,
+    /// Blocks between the head and the last ingested one.
+    backlog_blocks: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+struct BackfillProgress {
+    chain_name: String,
+    schema: String,
+    done_blocks: i64,
+    total_blocks: i64,
+    pending_chunks: i64,
+    running_chunks: i64,
+    failed_chunks: i64,
+}
+
+#[derive(Debug, Serialize)]
+struct DashboardSnapshot {
+    generated_at: u64,
+    chains: Vec<ChainStatus>,
+    backfills: Vec<BackfillProgress>,
+    tasks: Vec<TaskHealth>,
+    recent_errors: Vec<RecentError>,
+}
+
+async fn backfill_progress(pg_pool: &PgPool) -> Result<Vec<BackfillProgress>> {
+    let rows = sqlx::query(
+        "SELECT chain_name, schema_name,
+            COALESCE(SUM(end_block - start_block + 1) FILTER (WHERE status = 'done'), 0)::bigint AS done_blocks,
+            SUM(end_block - start_block + 1)::bigint AS total_blocks,
+            COUNT(*) FILTER (WHERE status = 'pending') AS pending_chunks,
+            COUNT(*) FILTER (WHERE status = 'running') AS running_chunks,
+            COUNT(*) FILTER (WHERE status = 'failed') AS failed_chunks
+        FROM backfill_chunks GROUP BY chain_name, schema_name ORDER BY chain_name, schema_name",
+    )
+    .fetch_all(pg_pool)
+    .await?;
+    Ok(rows
+        .iter()
+        .map(|row| BackfillProgress {
+            chain_name: row.get("chain_name"),
+            schema: row.get("schema_name"),
+            done_blocks: row.get("done_blocks"),
+            total_blocks: row.get("total_blocks"),
+            pending_chunks: row.get("pending_chunks"),
+            running_chunks: row.get("running_chunks"),
+            failed_chunks: row.get("failed_chunks"),
+        })
+        .collect())
+}
+
+/// Serves a read-only dashboard of the pipeline at `/`, with the JSON it renders at `/api/state`,
+/// until the listener fails. It has no authentication, so keep it on a private address.
+pub async fn serve(config: &DashboardConfig, state: Arc<DashboardState>) -> Result<()> {
+    let app = Router::new()
+        .route("/", get(index_handler))
+        .route("/api/state", get(state_handler))
+        .with_state(state);
+
+    let listener = tokio::net::TcpListener::bind(&config.bind_addr)
+        .await
+        .with_context(|| format!("Failed to bind dashboard to {}", config.bind_addr))?;
+    info!("Dashboard listening on {}", config.bind_addr);
+
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn index_handler() -> Html<&'static str> {
+    Html(INDEX_HTML)
+}
+
+async fn state_handler(State(state): State<Arc<DashboardState>>) -> Result<Json<DashboardSnapshot>, StatusCode> {
+    let mut chain_names: Vec<&String> = state.adapters.keys().collect();
+    chain_names.sort();
+    let chains = future::join_all(chain_names.into_iter().map(|chain_name| state.chain(chain_name))).await;
+    let backfills = backfill_progress(&state.pg_pool).await.map_err(|e| {
+        error!("Dashboard request failed: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let (tasks, recent_errors) = {
+        let health = health().lock().unwrap_or_else(|e| e.into_inner());
+        (health.tasks.values().cloned().collect(), health.errors.iter().rev().cloned().collect())
+    };
+    Ok(Json(DashboardSnapshot { generated_at: unix_now(), chains, backfills, tasks, recent_errors }))
+}
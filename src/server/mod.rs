@@ -0,0 +1,3 @@
+pub mod admin;
+pub mod dashboard;
+pub mod push;
@@ -0,0 +1,210 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use axum::{
+    extract::{ws::{Message, WebSocket, WebSocketUpgrade}, Path, State},
+    response::sse::{Event, KeepAlive, Sse},
+    response::IntoResponse,
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use ethers::types::{Block, Transaction};
+use futures_core::Stream;
+use futures_util::StreamExt;
+use log::{info, warn};
+use schemars::schema::RootSchema;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+use crate::streams::consumers::hooks::ConsumerHook;
+use crate::streams::schemas::json_schema::{topic_schemas, TopicSchema};
+
+/// Channel segment that matches every chain or schema.
+const WILDCARD: &str = "*";
+
+#[derive(Debug, Deserialize)]
+pub struct PushServerConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_bind_addr")]
+    pub bind_addr: String,
+    #[serde(default = "default_channel_capacity")]
+    pub channel_capacity: usize,
+}
+
+fn default_bind_addr() -> String {
+    "0.0.0.0:8080".to_string()
+}
+
+fn default_channel_capacity() -> usize {
+    1024
+}
+
+impl Default for PushServerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            bind_addr: default_bind_addr(),
+            channel_capacity: default_channel_capacity(),
+        }
+    }
+}
+
+/// A single message pushed to subscribers of a `{chain}/{schema}` channel.
+#[derive(Debug, Clone, Serialize)]
+pub struct PushEvent {
+    pub chain: String,
+    pub schema: String,
+    pub payload: Value,
+}
+
+impl PushEvent {
+    fn matches(&self, chain: &str, schema: &str) -> bool {
+        (chain == WILDCARD || chain == self.chain) && (schema == WILDCARD || schema == self.schema)
+    }
+}
+
+/// Fans consumed data out to every connected WebSocket/SSE subscriber.
+#[derive(Clone)]
+pub struct PushHub {
+    sender: broadcast::Sender<Arc<PushEvent>>,
+}
+
+impl PushHub {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Publishes an event. Having no subscribers is not an error.
+    pub fn publish(&self, event: PushEvent) {
+        let _ = self.sender.send(Arc::new(event));
+    }
+
+    /// Returns a stream of events for the given chain/schema channel (`*` matches any).
+    pub fn subscribe(&self, chain: String, schema: String) -> impl Stream<Item = Arc<PushEvent>> + Send {
+        BroadcastStream::new(self.sender.subscribe()).filter_map(move |res| {
+            let item = match res {
+                Ok(event) if event.matches(&chain, &schema) => Some(event),
+                Ok(_) => None,
+                Err(e) => {
+                    // A slow subscriber lagged behind the channel capacity; skip what it missed.
+                    warn!("Push subscriber on {}/{} lagged: {}", chain, schema, e);
+                    None
+                }
+            };
+            futures_util::future::ready(item)
+        })
+    }
+}
+
+/// A consumer hook that re-broadcasts committed blocks (or their transactions) to the hub.
+pub struct PushHook {
+    hub: Arc<PushHub>,
+    schema: String,
+}
+
+impl PushHook {
+    pub fn new(hub: Arc<PushHub>, schema: &str) -> Self {
+        Self {
+            hub,
+            schema: schema.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for PushHook {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        if self.schema == "transactions" {
+            for transaction in &block.transactions {
+                self.hub.publish(PushEvent {
+                    chain: chain_name.to_string(),
+                    schema: self.schema.clone(),
+                    payload: serde_json::to_value(transaction)?,
+                });
+            }
+        } else {
+            self.hub.publish(PushEvent {
+                chain: chain_name.to_string(),
+                schema: self.schema.clone(),
+                payload: serde_json::to_value(block)?,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Serves `/ws/{chain}/{schema}`, `/sse/{chain}/{schema}` and the topic message schemas under
+/// `/schemas` until the listener fails.
+pub async fn serve(config: &PushServerConfig, hub: Arc<PushHub>) -> Result<()> {
+    let app = Router::new()
+        .route("/ws/:chain/:schema", get(ws_handler))
+        .route("/sse/:chain/:schema", get(sse_handler))
+        .route("/schemas", get(schemas_handler))
+        .route("/schemas/:name", get(schema_handler))
+        .with_state(hub);
+
+    let listener = tokio::net::TcpListener::bind(&config.bind_addr)
+        .await
+        .with_context(|| format!("Failed to bind push server to {}", config.bind_addr))?;
+    info!("Push server listening on {}", config.bind_addr);
+
+    axum::serve(listener, app).await?;
+    Ok(())
+}
+
+async fn ws_handler(
+    ws: WebSocketUpgrade,
+    Path((chain, schema)): Path<(String, String)>,
+    State(hub): State<Arc<PushHub>>,
+) -> impl IntoResponse {
+    ws.on_upgrade(move |socket| forward_to_socket(socket, hub, chain, schema))
+}
+
+async fn forward_to_socket(mut socket: WebSocket, hub: Arc<PushHub>, chain: String, schema: String) {
+    let mut events = Box::pin(hub.subscribe(chain, schema));
+    while let Some(event) = events.next().await {
+        let text = match serde_json::to_string(event.as_ref()) {
+            Ok(text) => text,
+            Err(e) => {
+                warn!("Failed to serialize push event: {}", e);
+                continue;
+            }
+        };
+        if socket.send(Message::Text(text)).await.is_err() {
+            // The client went away.
+            break;
+        }
+    }
+}
+
+async fn sse_handler(
+    Path((chain, schema)): Path<(String, String)>,
+    State(hub): State<Arc<PushHub>>,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>> {
+    let events = hub.subscribe(chain, schema).map(|event| {
+        let sse_event = Event::default()
+            .event(format!("{}-{}", event.chain, event.schema))
+            .json_data(event.as_ref())
+            .unwrap_or_else(|_| Event::default().comment("serialization error"));
+        Ok(sse_event)
+    });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn schemas_handler() -> Json<Vec<TopicSchema>> {
+    Json(topic_schemas())
+}
+
+async fn schema_handler(Path(name): Path<String>) -> Result<Json<RootSchema>, StatusCode> {
+    topic_schemas()
+        .into_iter()
+        .find(|topic_schema| topic_schema.name == name)
+        .map(|topic_schema| Json(topic_schema.schema))
+        .ok_or(StatusCode::NOT_FOUND)
+}
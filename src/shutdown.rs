@@ -0,0 +1,255 @@
+use anyhow::Result;
+use futures::future;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::PgPool;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+
+use crate::storage::sinks::Sink;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ShutdownConfig {
+    /// How long producers get to stop before consumers are drained anyway.
+    pub producer_timeout_secs: u64,
+    /// How long consumers get to finish, flush and acknowledge what they read.
+    pub drain_timeout_secs: u64,
+}
+
+impl Default for ShutdownConfig {
+    fn default() -> Self {
+        Self { producer_timeout_secs: 10, drain_timeout_secs: 30 }
+    }
+}
+
+/// What a consumer had in flight when it stopped reading, and what became of it.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DrainReport {
+    pub consumer: String,
+    pub chain_name: String,
+    /// Messages read but not yet acknowledged.
+    pub in_flight: u64,
+    pub acked: u64,
+    /// In-flight messages left unacknowledged, which the broker redelivers after the restart.
+    pub unacked: u64,
+    /// Buffered sink batches written out while draining.
+    pub flushed_batches: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+enum Phase {
+    Running,
+    StoppingProducers,
+    DrainingConsumers,
+}
+
+/// Stops the pipeline in a fixed order on SIGTERM or Ctrl-C: producers first, so nothing new is
+/// published, then consumers, which stop reading, store and acknowledge what they hold, and
+/// report it. Tasks on any runtime can wait for their phase.
+pub struct Shutdown {
+    phase: watch::Sender<Phase>,
+    reports: Mutex<Vec<DrainReport>>,
+}
+
+impl Shutdown {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self { phase: watch::channel(Phase::Running).0, reports: Mutex::new(Vec::new()) })
+    }
+
+    async fn reached(&self, phase: Phase) {
+        let mut current = self.phase.subscribe();
+        // The sender lives as long as `self`, so this only returns once the phase is reached.
+        let _ = current.wait_for(|current| *current >= phase).await;
+    }
+
+    /// Returns once producers have to stop.
+    pub async fn producers_stopping(&self) {
+        self.reached(Phase::StoppingProducers).await
+    }
+
+    /// Returns once consumers have to stop reading and drain.
+    pub async fn consumers_draining(&self) {
+        self.reached(Phase::DrainingConsumers).await
+    }
+
+    /// Runs a producer until it ends or producers have to stop.
+    pub async fn until_producers_stop(&self, producer: impl Future<Output = Result<()>>) -> Result<()> {
+        tokio::select! {
+            result = producer => result,
+            _ = self.producers_stopping() => Ok(()),
+        }
+    }
+
+    /// Tells producers to stop.
+    pub fn stop_producers(&self) {
+        self.phase.send_if_modified(|phase| {
+            if *phase >= Phase::StoppingProducers {
+                return false;
+            }
+            *phase = Phase::StoppingProducers;
+            true
+        });
+    }
+
+    /// Tells consumers to stop reading and drain, and producers to stop if they haven't yet.
+    pub fn drain_consumers(&self) {
+        self.drain_consumers();
+    }
+
+    /// Takes the drain reports recorded so far.
+    pub fn take_reports(&self) -> Vec<DrainReport> {
+        std::mem::take(&mut *self.reports.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Records how a consumer drained, for the shutdown summary.
+    pub fn report(&self, report: DrainReport) {
+        self.reports.lock().unwrap_or_else(|e| e.into_inner()).push(report);
+    }
+
+    /// Waits for a shutdown signal, then stops the producer and consumer tasks in order. Returns
+    /// early if every task ends by itself.
+    pub async fn run(
+        &self,
+        config: &ShutdownConfig,
+        producers: &mut [JoinHandle<Result<()>>],
+        consumers: &mut [JoinHandle<Result<()>>],
+        others: &mut [JoinHandle<Result<()>>],
+    ) -> Result<Option<ShutdownSummary>> {
+        let all_tasks = future::join_all(producers.iter_mut().chain(consumers.iter_mut()).chain(others.iter_mut()));
+        tokio::select! {
+            signal = signal() => signal?,
+            _ = all_tasks => return Ok(None),
+        }
+        let started = Instant::now();
+
+        info!("Shutting down: stopping producers");
+        self.stop_producers();
+        let producers_stopped =
+            tokio::time::timeout(Duration::from_secs(config.producer_timeout_secs), future::join_all(unfinished(producers))).await.is_ok();
+        if !producers_stopped {
+            warn!("Producers didn't stop within {}s, draining consumers anyway", config.producer_timeout_secs);
+        }
+
+        info!("Shutting down: draining consumers");
+        self.drain_consumers();
+        let consumers_drained =
+            tokio::time::timeout(Duration::from_secs(config.drain_timeout_secs), future::join_all(unfinished(consumers))).await.is_ok();
+        if !consumers_drained {
+            warn!("Consumers didn't drain within {}s", config.drain_timeout_secs);
+        }
+
+        let reports = self.take_reports();
+        Ok(Some(ShutdownSummary {
+            instance: instance_name(),
+            duration: started.elapsed(),
+            timed_out: !producers_stopped || !consumers_drained,
+            in_flight: reports.iter().map(|report| report.in_flight).sum(),
+            acked: reports.iter().map(|report| report.acked).sum(),
+            unacked: reports.iter().map(|report| report.unacked).sum(),
+            flushed_batches: reports.iter().map(|report| report.flushed_batches).sum(),
+            cursors: BTreeMap::new(),
+            consumers: reports,
+        }))
+    }
+}
+
+/// Tasks still running. Finished ones are left out, as their handles may already have been
+/// polled to completion.
+fn unfinished(tasks: &mut [JoinHandle<Result<()>>]) -> impl Iterator<Item = &mut JoinHandle<Result<()>>> {
+    tasks.iter_mut().filter(|task| !task.is_finished())
+}
+
+/// Resolves on SIGTERM, as sent by Kubernetes and docker, or Ctrl-C.
+async fn signal() -> Result<()> {
+    #[cfg(unix)]
+    {
+        let mut terminate = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())?;
+        tokio::select! {
+            ctrl_c = tokio::signal::ctrl_c() => ctrl_c?,
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    tokio::signal::ctrl_c().await?;
+    Ok(())
+}
+
+/// What a shutdown left behind, so operators can check nothing was lost across a deploy.
+#[derive(Debug, Serialize)]
+pub struct ShutdownSummary {
+    pub instance: String,
+    #[serde(skip)]
+    pub duration: Duration,
+    /// Whether producers or consumers were still running when their timeout ran out.
+    pub timed_out: bool,
+    pub in_flight: u64,
+    pub acked: u64,
+    pub unacked: u64,
+    pub flushed_batches: u64,
+    /// Last block each chain's sink holds, `None` if it can't tell.
+    pub cursors: BTreeMap<String, Option<u64>>,
+    pub consumers: Vec<DrainReport>,
+}
+
+/// Names this instance in shutdown summaries and recovery reports: `POD_NAME`, else `HOSTNAME`.
+pub fn instance_name() -> String {
+    env::var("POD_NAME").or_else(|_| env::var("HOSTNAME")).unwrap_or_default()
+}
+
+impl ShutdownSummary {
+    /// Reads each chain's final cursor from its sink.
+    pub async fn read_cursors(&mut self, sinks: &HashMap<String, Arc<dyn Sink>>) {
+        for (chain_name, sink) in sinks {
+            let cursor = match sink.last_block(chain_name).await {
+                Ok(cursor) => cursor,
+                Err(e) => {
+                    warn!("Failed to read the final cursor of {}: {}", chain_name, e);
+                    None
+                }
+            };
+            self.cursors.insert(chain_name.clone(), cursor);
+        }
+    }
+
+    /// Logs the summary and records it in `shutdown_summaries`.
+    pub async fn record(&self, pg_pool: &PgPool) {
+        info!(
+            "Shut down in {:?}: {} messages in flight, {} acknowledged, {} left for redelivery, {} batches flushed{}",
+            self.duration,
+            self.in_flight,
+            self.acked,
+            self.unacked,
+            self.flushed_batches,
+            if self.timed_out { ", timed out" } else { "" }
+        );
+        for (chain_name, cursor) in &self.cursors {
+            info!("Final cursor of {}: {:?}", chain_name, cursor);
+        }
+        let result = sqlx::query(
+            "INSERT INTO shutdown_summaries
+                (instance, duration_ms, timed_out, in_flight_messages, acked_messages, unacked_messages, flushed_batches, cursors, consumers)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)",
+        )
+        .bind(&self.instance)
+        .bind(self.duration.as_millis() as i64)
+        .bind(self.timed_out)
+        .bind(self.in_flight as i64)
+        .bind(self.acked as i64)
+        .bind(self.unacked as i64)
+        .bind(self.flushed_batches as i64)
+        .bind(json!(self.cursors))
+        .bind(json!(self.consumers))
+        .execute(pg_pool)
+        .await;
+        if let Err(e) = result {
+            error!("Failed to record shutdown summary: {}", e);
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use anyhow::Result;
+use log::{info, warn};
+use serde::Deserialize;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+/// How long the service waits at startup for its dependencies (Postgres, the message broker,
+/// each chain's RPC endpoint) to become reachable, for deployments that start them together.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StartupConfig {
+    /// Time a dependency gets before startup fails; 0 fails on the first error.
+    pub grace_period_secs: u64,
+    /// Wait after the first failed check, doubled after each further one.
+    pub initial_backoff_ms: u64,
+    pub max_backoff_secs: u64,
+}
+
+impl Default for StartupConfig {
+    fn default() -> Self {
+        Self { grace_period_secs: 120, initial_backoff_ms: 500, max_backoff_secs: 10 }
+    }
+}
+
+/// Runs `check` until it succeeds, retrying with backoff for the grace period.
+pub async fn wait_for<T, F, Fut>(dependency: &str, config: &StartupConfig, mut check: F) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let deadline = Instant::now() + Duration::from_secs(config.grace_period_secs);
+    let max_backoff = Duration::from_secs(config.max_backoff_secs);
+    let mut backoff = Duration::from_millis(config.initial_backoff_ms).min(max_backoff);
+    let mut failed = false;
+    loop {
+        match check().await {
+            Ok(value) => {
+                if failed {
+                    info!("{} is reachable", dependency);
+                }
+                return Ok(value);
+            }
+            Err(e) if Instant::now() + backoff < deadline => {
+                warn!("{} is not reachable yet, retrying in {:?}: {}", dependency, backoff, e);
+                failed = true;
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(max_backoff);
+            }
+            Err(e) => {
+                return Err(e.context(format!("{} is not reachable after {}s", dependency, config.grace_period_secs)));
+            }
+        }
+    }
+}
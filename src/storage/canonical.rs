@@ -0,0 +1,261 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use serde::Deserialize;
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+use crate::streams::sharding;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CanonicalSchemaConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// A block in the chain-agnostic `canonical_blocks` shape. EVM-specific fields are `None` for
+/// chains that don't have them.
+#[derive(Debug, Clone, Default)]
+pub struct CanonicalBlock {
+    pub block_number: i64,
+    pub hash: String,
+    pub parent_hash: String,
+    pub timestamp: i64,
+    pub tx_count: i64,
+    pub miner: Option<String>,
+    pub gas_used: Option<i64>,
+    pub gas_limit: Option<i64>,
+    pub base_fee_per_gas: Option<String>,
+}
+
+/// A transaction in the chain-agnostic `canonical_transactions` shape.
+#[derive(Debug, Clone, Default)]
+pub struct CanonicalTransaction {
+    pub tx_hash: String,
+    pub tx_index: i64,
+    pub from_address: String,
+    pub to_address: Option<String>,
+    pub value: String,
+    pub nonce: Option<i64>,
+    pub gas: Option<String>,
+    pub gas_price: Option<String>,
+    pub max_fee_per_gas: Option<String>,
+    pub max_priority_fee_per_gas: Option<String>,
+    pub tx_type: Option<i64>,
+    pub input: Option<String>,
+}
+
+/// Maps an adapter's blocks into the canonical shape. Adapter types register one mapper each,
+/// since they differ in which fields their source actually carries.
+pub trait CanonicalMapper: Send + Sync {
+    fn map_block(&self, block: &Block<Transaction>) -> (CanonicalBlock, Vec<CanonicalTransaction>);
+}
+
+/// Mapper for JSON-RPC sources, which return every EVM field.
+pub struct EvmMapper;
+
+impl CanonicalMapper for EvmMapper {
+    fn map_block(&self, block: &Block<Transaction>) -> (CanonicalBlock, Vec<CanonicalTransaction>) {
+        let canonical_block = CanonicalBlock {
+            miner: block.author.map(|author| format!("{:?}", author)),
+            gas_used: Some(block.gas_used.as_u64() as i64),
+            gas_limit: Some(block.gas_limit.as_u64() as i64),
+            base_fee_per_gas: block.base_fee_per_gas.map(|fee| fee.to_string()),
+            ..common_block(block)
+        };
+        let transactions = block
+            .transactions
+            .iter()
+            .map(|transaction| CanonicalTransaction {
+                nonce: Some(transaction.nonce.as_u64() as i64),
+                gas: Some(transaction.gas.to_string()),
+                gas_price: transaction.gas_price.map(|price| price.to_string()),
+                max_fee_per_gas: transaction.max_fee_per_gas.map(|fee| fee.to_string()),
+                max_priority_fee_per_gas: transaction.max_priority_fee_per_gas.map(|fee| fee.to_string()),
+                tx_type: transaction.transaction_type.map(|tx_type| tx_type.as_u64() as i64),
+                input: Some(transaction.input.to_string()),
+                ..common_transaction(transaction)
+            })
+            .collect();
+        (canonical_block, transactions)
+    }
+}
+
+/// Mapper for Firehose sources. Firehose traces carry no transaction type or EIP-1559 fee caps,
+/// so those stay NULL rather than holding the zero defaults of the converted block.
+pub struct FirehoseMapper;
+
+impl CanonicalMapper for FirehoseMapper {
+    fn map_block(&self, block: &Block<Transaction>) -> (CanonicalBlock, Vec<CanonicalTransaction>) {
+        let (canonical_block, mut transactions) = EvmMapper.map_block(block);
+        for transaction in &mut transactions {
+            transaction.tx_type = None;
+            transaction.max_fee_per_gas = None;
+            transaction.max_priority_fee_per_gas = None;
+        }
+        (canonical_block, transactions)
+    }
+}
+
+fn common_block(block: &Block<Transaction>) -> CanonicalBlock {
+    CanonicalBlock {
+        block_number: block.number.unwrap_or_default().as_u64() as i64,
+        hash: format!("{:?}", block.hash.unwrap_or_default()),
+        parent_hash: format!("{:?}", block.parent_hash),
+        timestamp: block.timestamp.as_u64() as i64,
+        tx_count: sharding::tx_count(block) as i64,
+        ..Default::default()
+    }
+}
+
+fn common_transaction(transaction: &Transaction) -> CanonicalTransaction {
+    CanonicalTransaction {
+        tx_hash: format!("{:?}", transaction.hash),
+        tx_index: transaction.transaction_index.unwrap_or_default().as_u64() as i64,
+        from_address: format!("{:?}", transaction.from),
+        to_address: transaction.to.map(|to| format!("{:?}", to)),
+        value: transaction.value.to_string(),
+        ..Default::default()
+    }
+}
+
+/// Maps `adapter_type` config strings to canonical mappers, alongside the adapter registry.
+#[derive(Clone)]
+pub struct CanonicalMapperRegistry {
+    mappers: HashMap<String, Arc<dyn CanonicalMapper>>,
+}
+
+impl CanonicalMapperRegistry {
+    /// A registry with no mappers.
+    pub fn empty() -> Self {
+        Self { mappers: HashMap::new() }
+    }
+
+    /// Registers `mapper` for `adapter_type`, replacing any earlier registration.
+    pub fn register(&mut self, adapter_type: &str, mapper: Arc<dyn CanonicalMapper>) -> &mut Self {
+        self.mappers.insert(adapter_type.to_string(), mapper);
+        self
+    }
+
+    pub fn get(&self, adapter_type: &str) -> Option<Arc<dyn CanonicalMapper>> {
+        self.mappers.get(adapter_type).cloned()
+    }
+}
+
+impl Default for CanonicalMapperRegistry {
+    /// A registry with mappers for the built-in `EVM` and `FIREHOSE` adapter types.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("EVM", Arc::new(EvmMapper));
+        registry.register("FIREHOSE", Arc::new(FirehoseMapper));
+        registry
+    }
+}
+
+/// Writes each committed block into `canonical_blocks` and `canonical_transactions`, keyed by
+/// `chain_id`, so all chains can be queried from one pair of tables. Only canonical blocks are
+/// kept: a reorg's replacement overwrites the height and orphaned blocks are deleted.
+pub struct CanonicalTableWriter {
+    pg_pool: Arc<PgPool>,
+    chain_id: i64,
+    mapper: Arc<dyn CanonicalMapper>,
+}
+
+impl CanonicalTableWriter {
+    pub fn new(pg_pool: Arc<PgPool>, chain_id: u64, mapper: Arc<dyn CanonicalMapper>) -> Self {
+        Self { pg_pool, chain_id: chain_id as i64, mapper }
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for CanonicalTableWriter {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let (canonical_block, transactions) = self.mapper.map_block(block);
+        let mut db_tx = self.pg_pool.begin().await?;
+
+        sqlx::query(
+            "INSERT INTO canonical_blocks (chain_id, chain_name, block_number, hash, parent_hash, timestamp, tx_count, miner, gas_used, gas_limit, base_fee_per_gas)
+            VALUES ($1, $2, $3, $4, $5, to_timestamp($6) AT TIME ZONE 'UTC', $7, $8, $9, $10, $11::numeric)
+            ON CONFLICT (chain_id, block_number) DO UPDATE SET
+                chain_name = EXCLUDED.chain_name,
+                hash = EXCLUDED.hash,
+                parent_hash = EXCLUDED.parent_hash,
+                timestamp = EXCLUDED.timestamp,
+                tx_count = EXCLUDED.tx_count,
+                miner = EXCLUDED.miner,
+                gas_used = EXCLUDED.gas_used,
+                gas_limit = EXCLUDED.gas_limit,
+                base_fee_per_gas = EXCLUDED.base_fee_per_gas",
+        )
+        .bind(self.chain_id)
+        .bind(chain_name)
+        .bind(canonical_block.block_number)
+        .bind(&canonical_block.hash)
+        .bind(&canonical_block.parent_hash)
+        .bind(canonical_block.timestamp as f64)
+        .bind(canonical_block.tx_count)
+        .bind(&canonical_block.miner)
+        .bind(canonical_block.gas_used)
+        .bind(canonical_block.gas_limit)
+        .bind(&canonical_block.base_fee_per_gas)
+        .execute(&mut db_tx)
+        .await?;
+
+        // A replacement block at this height brings its own transactions.
+        sqlx::query("DELETE FROM canonical_transactions WHERE chain_id = $1 AND block_number = $2")
+            .bind(self.chain_id)
+            .bind(canonical_block.block_number)
+            .execute(&mut db_tx)
+            .await?;
+
+        if !transactions.is_empty() {
+            let column = |f: fn(&CanonicalTransaction) -> Option<String>| transactions.iter().map(f).collect::<Vec<_>>();
+            sqlx::query(
+                "INSERT INTO canonical_transactions (chain_id, chain_name, block_number, block_hash, tx_hash, tx_index, from_address, to_address, value, nonce, gas, gas_price, max_fee_per_gas, max_priority_fee_per_gas, tx_type, input)
+                SELECT $1, $2, $3, $4, tx_hash, tx_index, from_address, to_address, value::numeric, nonce, gas::numeric, gas_price::numeric, max_fee_per_gas::numeric, max_priority_fee_per_gas::numeric, tx_type, input
+                FROM UNNEST($5::text[], $6::bigint[], $7::text[], $8::text[], $9::text[], $10::bigint[], $11::text[], $12::text[], $13::text[], $14::text[], $15::bigint[], $16::text[])
+                    AS t(tx_hash, tx_index, from_address, to_address, value, nonce, gas, gas_price, max_fee_per_gas, max_priority_fee_per_gas, tx_type, input)",
+            )
+            .bind(self.chain_id)
+            .bind(chain_name)
+            .bind(canonical_block.block_number)
+            .bind(&canonical_block.hash)
+            .bind(transactions.iter().map(|t| t.tx_hash.clone()).collect::<Vec<_>>())
+            .bind(transactions.iter().map(|t| t.tx_index).collect::<Vec<_>>())
+            .bind(transactions.iter().map(|t| t.from_address.clone()).collect::<Vec<_>>())
+            .bind(column(|t| t.to_address.clone()))
+            .bind(transactions.iter().map(|t| t.value.clone()).collect::<Vec<_>>())
+            .bind(transactions.iter().map(|t| t.nonce).collect::<Vec<_>>())
+            .bind(column(|t| t.gas.clone()))
+            .bind(column(|t| t.gas_price.clone()))
+            .bind(column(|t| t.max_fee_per_gas.clone()))
+            .bind(column(|t| t.max_priority_fee_per_gas.clone()))
+            .bind(transactions.iter().map(|t| t.tx_type).collect::<Vec<_>>())
+            .bind(column(|t| t.input.clone()))
+            .execute(&mut db_tx)
+            .await?;
+        }
+
+        db_tx.commit().await?;
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, _chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        let mut db_tx = self.pg_pool.begin().await?;
+        sqlx::query("DELETE FROM canonical_transactions WHERE chain_id = $1 AND block_hash = ANY($2)")
+            .bind(self.chain_id)
+            .bind(&hashes)
+            .execute(&mut db_tx)
+            .await?;
+        sqlx::query("DELETE FROM canonical_blocks WHERE chain_id = $1 AND hash = ANY($2)")
+            .bind(self.chain_id)
+            .bind(&hashes)
+            .execute(&mut db_tx)
+            .await?;
+        db_tx.commit().await?;
+        Ok(())
+    }
+}
@@ -1,10 +1,412 @@
+use std::time::Duration;
 use sqlx::migrate::Migrator;
-use sqlx::Pool;
-use anyhow::Result;
+use sqlx::{Pool, Row};
+use anyhow::{anyhow, Result};
+use log::{info, warn};
+use serde::Deserialize;
 
 static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
 
+// Serializes generated DDL across instances starting at the same time.
+const TABLE_MIGRATION_LOCK: i64 = 0x7461_626c_6573;
+
+// How often deferred index creation checks whether the backfills have landed.
+const BACKFILL_POLL_INTERVAL: Duration = Duration::from_secs(60);
+
+// Postgres truncates longer identifiers.
+const MAX_IDENTIFIER_LEN: usize = 63;
+
+// Prefix of every managed index, so one can never share a name with an index from the migrations.
+const MANAGED_INDEX_PREFIX: &str = "managed_";
+
 pub async fn run_migrations(pg_pool: &Pool<sqlx::Postgres>) -> Result<()> {
     MIGRATOR.run(pg_pool).await?;
     Ok(())
 }
+
+/// Table backing a schema stream that has no hand-written migration. Every generated table gets
+/// leading `chain_name` and `block_number` columns and an index on them, so it can be pruned and
+/// rolled back like the built-in tables.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TableSchema {
+    pub columns: Vec<ColumnSchema>,
+    /// Columns of the primary key, e.g. `["chain_name", "tx_hash", "log_index"]`.
+    #[serde(default)]
+    pub primary_key: Vec<String>,
+    /// Extra indexes, one column list each.
+    #[serde(default)]
+    pub indexes: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ColumnSchema {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub sql_type: String,
+    #[serde(default)]
+    pub nullable: bool,
+}
+
+impl ColumnSchema {
+    fn new(name: &str, sql_type: &str) -> Self {
+        Self { name: name.to_string(), sql_type: sql_type.to_string(), nullable: false }
+    }
+}
+
+/// Definitions for schema streams the pipeline knows about but that have no migration of their own.
+pub fn builtin_table_schema(schema: &str) -> Option<TableSchema> {
+    match schema {
+        "erc20_transfers" => Some(TableSchema {
+            columns: vec![
+                ColumnSchema::new("tx_hash", "TEXT"),
+                ColumnSchema::new("log_index", "BIGINT"),
+                ColumnSchema::new("token", "TEXT"),
+                ColumnSchema::new("from_address", "TEXT"),
+                ColumnSchema::new("to_address", "TEXT"),
+                ColumnSchema::new("value", "NUMERIC"),
+            ],
+            primary_key: vec!["chain_name".to_string(), "tx_hash".to_string(), "log_index".to_string()],
+            indexes: vec![vec!["chain_name".to_string(), "token".to_string(), "block_number".to_string()]],
+        }),
+        _ => None,
+    }
+}
+
+impl TableSchema {
+    /// Idempotent DDL creating the table and its indexes. Columns added to the definition later
+    /// are added to an existing table; removed or retyped columns are left alone.
+    pub fn migration_sql(&self, table: &str) -> Result<Vec<String>> {
+        validate_identifier(table)?;
+        let mut columns = vec![ColumnSchema::new("chain_name", "TEXT"), ColumnSchema::new("block_number", "BIGINT")];
+        columns.extend(self.columns.iter().cloned());
+        for column in &columns {
+            validate_identifier(&column.name)?;
+            if column.sql_type.is_empty()
+                || !column.sql_type.chars().all(|c| c.is_ascii_alphanumeric() || " _(),[]".contains(c))
+            {
+                return Err(anyhow!("Invalid type `{}` for column `{}.{}`", column.sql_type, table, column.name));
+            }
+        }
+        let known = |name: &String| columns.iter().any(|column| &column.name == name);
+        for name in self.primary_key.iter().chain(self.indexes.iter().flatten()) {
+            if !known(name) {
+                return Err(anyhow!("Unknown column `{}` in key or index of `{}`", name, table));
+            }
+        }
+
+        let column_sql = |column: &ColumnSchema| {
+            format!("{} {}{}", column.name, column.sql_type, if column.nullable { "" } else { " NOT NULL" })
+        };
+        let mut definitions: Vec<String> = columns.iter().map(column_sql).collect();
+        if !self.primary_key.is_empty() {
+            definitions.push(format!("PRIMARY KEY ({})", self.primary_key.join(", ")));
+        }
+
+        let mut statements = vec![format!("CREATE TABLE IF NOT EXISTS {} ({})", table, definitions.join(", "))];
+        for column in &self.columns {
+            // Existing rows can't satisfy NOT NULL for a column added later.
+            statements.push(format!("ALTER TABLE {} ADD COLUMN IF NOT EXISTS {} {}", table, column.name, column.sql_type));
+        }
+        let block_index = vec!["chain_name".to_string(), "block_number".to_string()];
+        for index in std::iter::once(&block_index).chain(&self.indexes) {
+            statements.push(format!(
+                "CREATE INDEX IF NOT EXISTS {}_{}_idx ON {} ({})",
+                table,
+                index.join("_"),
+                table,
+                index.join(", ")
+            ));
+        }
+        Ok(statements)
+    }
+}
+
+/// Creates or extends the tables of generated schemas, in one transaction.
+pub async fn run_table_migrations(pg_pool: &Pool<sqlx::Postgres>, tables: &[(String, TableSchema)]) -> Result<()> {
+    let mut statements = Vec::new();
+    for (table, schema) in tables {
+        statements.extend(schema.migration_sql(table)?);
+    }
+    if statements.is_empty() {
+        return Ok(());
+    }
+
+    let mut tx = pg_pool.begin().await?;
+    sqlx::query("SELECT pg_advisory_xact_lock($1)")
+        .bind(TABLE_MIGRATION_LOCK)
+        .execute(&mut tx)
+        .await?;
+    for statement in &statements {
+        sqlx::query(statement).execute(&mut tx).await?;
+    }
+    tx.commit().await?;
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct IndexConfig {
+    /// Drop the managed indexes while a bounded backfill into Postgres is running, and build them
+    /// once every backfilled block is stored.
+    #[serde(default)]
+    pub defer_until_backfilled: bool,
+    /// How long deferred indexes wait for the backfilled blocks. After that they are built
+    /// anyway, so a block that is never stored doesn't leave the tables without them.
+    #[serde(default = "default_backfill_timeout_secs")]
+    pub backfill_timeout_secs: u64,
+    #[serde(default)]
+    pub managed: Vec<ManagedIndex>,
+}
+
+fn default_backfill_timeout_secs() -> u64 {
+    24 * 60 * 60
+}
+
+impl Default for IndexConfig {
+    fn default() -> Self {
+        Self {
+            defer_until_backfilled: false,
+            backfill_timeout_secs: default_backfill_timeout_secs(),
+            managed: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IndexMethod {
+    #[default]
+    Btree,
+    /// Tiny and cheap to maintain; suits columns that grow with insertion order, like block_number.
+    Brin,
+    Hash,
+    Gin,
+}
+
+impl IndexMethod {
+    fn as_sql(self) -> &'static str {
+        match self {
+            IndexMethod::Btree => "btree",
+            IndexMethod::Brin => "brin",
+            IndexMethod::Hash => "hash",
+            IndexMethod::Gin => "gin",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ManagedIndex {
+    pub table: String,
+    pub columns: Vec<String>,
+    #[serde(default)]
+    pub method: IndexMethod,
+    /// Defaults to `{table}_{columns}_idx`, with the method before `idx` unless it is btree.
+    /// Either way the index is created as `managed_{name}`.
+    pub name: Option<String>,
+}
+
+impl ManagedIndex {
+    fn name(&self) -> String {
+        let name = self.name.clone().unwrap_or_else(|| match self.method {
+            IndexMethod::Btree => format!("{}_{}_idx", self.table, self.columns.join("_")),
+            method => format!("{}_{}_{}_idx", self.table, self.columns.join("_"), method.as_sql()),
+        });
+        format!("{}{}", MANAGED_INDEX_PREFIX, name)
+    }
+
+    fn definition(&self) -> String {
+        format!("ON {} USING {} ({})", self.table, self.method.as_sql(), self.columns.join(", "))
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_identifier(&self.table)?;
+        for column in &self.columns {
+            validate_identifier(column)?;
+        }
+        if self.columns.is_empty() {
+            return Err(anyhow!("Managed index on `{}` has no columns", self.table));
+        }
+        let name = self.name();
+        validate_identifier(&name)?;
+        if name.len() > MAX_IDENTIFIER_LEN {
+            return Err(anyhow!("Index name `{}` is longer than {} characters; set `name`", name, MAX_IDENTIFIER_LEN));
+        }
+        Ok(())
+    }
+}
+
+/// Table a chain's stored blocks are counted in: `blocks`, or `block_headers` for a chain whose
+/// only schema is `headers`.
+pub fn stored_blocks_table(schemas: &[String]) -> &'static str {
+    if !schemas.is_empty() && schemas.iter().all(|schema| schema == "headers") {
+        "block_headers"
+    } else {
+        "blocks"
+    }
+}
+
+/// Brings the managed indexes in line with the config. Indexes dropped from the config are
+/// dropped from the database; the rest are built, after `backfills` (chain, table from
+/// `stored_blocks_table`, first and last block) have been stored or `backfill_timeout_secs`
+/// has passed if creation is deferred. Indexes are only dropped for a backfill that isn't
+/// stored yet. They are built and dropped concurrently, so ingestion keeps writing meanwhile.
+pub async fn sync_indexes(
+    pg_pool: &Pool<sqlx::Postgres>,
+    config: &IndexConfig,
+    backfills: &[(String, &'static str, u64, u64)],
+) -> Result<()> {
+    for index in &config.managed {
+        index.validate()?;
+    }
+
+    let rows = sqlx::query("SELECT name, definition FROM managed_indexes")
+        .fetch_all(pg_pool)
+        .await?;
+    for row in rows {
+        let name: String = row.try_get("name")?;
+        let definition: String = row.try_get("definition")?;
+        if !name.starts_with(MANAGED_INDEX_PREFIX) {
+            // Recorded before managed indexes were namespaced; it may be a migration's index.
+            warn!("Forgetting unprefixed managed index {} without dropping it; drop it by hand if it was only configured", name);
+            forget_index(pg_pool, &name).await?;
+            continue;
+        }
+        let current = config.managed.iter().any(|index| index.name() == name && index.definition() == definition);
+        if !current {
+            info!("Dropping index {}, no longer configured", name);
+            drop_index(pg_pool, &name).await?;
+        }
+    }
+
+    if config.defer_until_backfilled && !backfills.is_empty() {
+        if backfills.iter().any(|(_, _, _, last_block)| *last_block == u64::MAX) {
+            warn!("A backfill has no end_block, so index creation is not deferred");
+        } else {
+            let mut pending = false;
+            for (chain_name, table, first_block, last_block) in backfills {
+                let stored = stored_blocks(pg_pool, chain_name, table, *first_block, *last_block).await?;
+                pending |= stored < (last_block - first_block + 1) as i64;
+            }
+            if pending {
+                for index in &config.managed {
+                    drop_index(pg_pool, &index.name()).await?;
+                }
+            } else {
+                info!("Backfills are already stored, keeping the managed indexes");
+            }
+            let deadline = tokio::time::Instant::now() + Duration::from_secs(config.backfill_timeout_secs);
+            for (chain_name, table, first_block, last_block) in backfills {
+                if !wait_for_backfill(pg_pool, chain_name, table, *first_block, *last_block, deadline).await? {
+                    warn!(
+                        "Backfill of {} blocks {}..={} not stored after {}s, building the managed indexes anyway",
+                        chain_name, first_block, last_block, config.backfill_timeout_secs
+                    );
+                    break;
+                }
+            }
+        }
+    }
+
+    for index in &config.managed {
+        create_index(pg_pool, index).await?;
+    }
+    Ok(())
+}
+
+async fn create_index(pg_pool: &Pool<sqlx::Postgres>, index: &ManagedIndex) -> Result<()> {
+    let name = index.name();
+    // A failed concurrent build leaves an invalid index behind that IF NOT EXISTS would keep.
+    let invalid = sqlx::query("SELECT NOT indisvalid AS invalid FROM pg_index WHERE indexrelid = to_regclass($1)")
+        .bind(&name)
+        .fetch_optional(pg_pool)
+        .await?;
+    if let Some(row) = invalid {
+        if row.try_get::<bool, _>("invalid")? {
+            sqlx::query(&format!("DROP INDEX CONCURRENTLY IF EXISTS {}", name)).execute(pg_pool).await?;
+        }
+    }
+
+    info!("Creating index {}", name);
+    sqlx::query(&format!("CREATE INDEX CONCURRENTLY IF NOT EXISTS {} {}", name, index.definition()))
+        .execute(pg_pool)
+        .await?;
+    sqlx::query(
+        "INSERT INTO managed_indexes (name, table_name, definition) VALUES ($1, $2, $3)
+        ON CONFLICT (name) DO UPDATE SET table_name = EXCLUDED.table_name, definition = EXCLUDED.definition, created_at = NOW()",
+    )
+    .bind(&name)
+    .bind(&index.table)
+    .bind(index.definition())
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+async fn drop_index(pg_pool: &Pool<sqlx::Postgres>, name: &str) -> Result<()> {
+    validate_identifier(name)?;
+    sqlx::query(&format!("DROP INDEX CONCURRENTLY IF EXISTS {}", name))
+        .execute(pg_pool)
+        .await?;
+    forget_index(pg_pool, name).await
+}
+
+async fn forget_index(pg_pool: &Pool<sqlx::Postgres>, name: &str) -> Result<()> {
+    sqlx::query("DELETE FROM managed_indexes WHERE name = $1")
+        .bind(name)
+        .execute(pg_pool)
+        .await?;
+    Ok(())
+}
+
+/// Number of canonical blocks of the range stored in `table`.
+async fn stored_blocks(
+    pg_pool: &Pool<sqlx::Postgres>,
+    chain_name: &str,
+    table: &str,
+    first_block: u64,
+    last_block: u64,
+) -> Result<i64> {
+    let row = sqlx::query(&format!(
+        "SELECT COUNT(*) AS stored FROM {}
+        WHERE chain_name = $1 AND block_number BETWEEN $2 AND $3 AND canonical",
+        table
+    ))
+    .bind(chain_name)
+    .bind(first_block as i64)
+    .bind(last_block as i64)
+    .fetch_one(pg_pool)
+    .await?;
+    Ok(row.try_get("stored")?)
+}
+
+/// Waits until every block of the range is stored in `table`, or returns false at `deadline`.
+async fn wait_for_backfill(
+    pg_pool: &Pool<sqlx::Postgres>,
+    chain_name: &str,
+    table: &str,
+    first_block: u64,
+    last_block: u64,
+    deadline: tokio::time::Instant,
+) -> Result<bool> {
+    let expected = (last_block - first_block + 1) as i64;
+    loop {
+        if stored_blocks(pg_pool, chain_name, table, first_block, last_block).await? >= expected {
+            info!("Backfill of {} blocks {}..={} is stored", chain_name, first_block, last_block);
+            return Ok(true);
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Ok(false);
+        }
+        tokio::time::sleep_until(deadline.min(tokio::time::Instant::now() + BACKFILL_POLL_INTERVAL)).await;
+    }
+}
+
+fn validate_identifier(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit());
+    if valid {
+        Ok(())
+    } else {
+        Err(anyhow!("Invalid identifier `{}`", name))
+    }
+}
@@ -1 +1,11 @@
+pub mod canonical;
 pub mod db;
+pub mod notify;
+pub mod objects;
+pub mod overflow;
+pub mod pipeline_metrics;
+pub mod projection;
+pub mod retention;
+pub mod search;
+pub mod sinks;
+pub mod snapshot;
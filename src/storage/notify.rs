@@ -0,0 +1,65 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+use crate::streams::consumers::hooks::ConsumerHook;
+
+#[derive(Debug, Deserialize)]
+pub struct NotifyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_channel")]
+    pub channel: String,
+}
+
+fn default_channel() -> String {
+    "new_block".to_string()
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            channel: default_channel(),
+        }
+    }
+}
+
+/// Issues `NOTIFY <channel>, '{"chain": ..., "number": ...}'` once a block has been committed,
+/// so services using `LISTEN` can react without polling.
+pub struct NotifyHook {
+    pg_pool: Arc<PgPool>,
+    channel: String,
+}
+
+impl NotifyHook {
+    pub fn new(pg_pool: Arc<PgPool>, channel: &str) -> Self {
+        Self {
+            pg_pool,
+            channel: channel.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for NotifyHook {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let payload = json!({
+            "chain": chain_name,
+            "number": block.number.unwrap_or_default().as_u64(),
+        });
+
+        // `NOTIFY` does not accept bind parameters, `pg_notify` does.
+        sqlx::query("SELECT pg_notify($1, $2)")
+            .bind(&self.channel)
+            .bind(payload.to_string())
+            .execute(self.pg_pool.as_ref())
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,29 @@
+use anyhow::{anyhow, Context, Result};
+use object_store::aws::AmazonS3Builder;
+use object_store::gcp::GoogleCloudStorageBuilder;
+use object_store::local::LocalFileSystem;
+use object_store::ObjectStore;
+use std::sync::Arc;
+use url::Url;
+
+/// Opens the object store behind `url` (`s3://bucket/prefix`, `gs://bucket/prefix` or
+/// `file:///path`) and returns it with the key prefix, empty or ending in `/`, that objects go
+/// under. Cloud credentials come from the usual `AWS_*`/`GOOGLE_*` env vars.
+pub fn open_store(url: &str) -> Result<(Arc<dyn ObjectStore>, String)> {
+    let parsed = Url::parse(url).with_context(|| format!("Invalid object storage URL `{}`", url))?;
+    let mut prefix = parsed.path().trim_matches('/').to_string();
+    let store: Arc<dyn ObjectStore> = match parsed.scheme() {
+        "s3" => Arc::new(AmazonS3Builder::from_env().with_url(url).build()?),
+        "gs" => Arc::new(GoogleCloudStorageBuilder::from_env().with_url(url).build()?),
+        "file" => {
+            std::fs::create_dir_all(parsed.path())?;
+            prefix.clear();
+            Arc::new(LocalFileSystem::new_with_prefix(parsed.path())?)
+        }
+        scheme => return Err(anyhow!("Unsupported object storage scheme `{}`", scheme)),
+    };
+    if !prefix.is_empty() {
+        prefix.push('/');
+    }
+    Ok((store, prefix))
+}
@@ -0,0 +1,62 @@
+use anyhow::{Context, Result};
+use ethers::types::Bytes;
+use object_store::path::Path as ObjectPath;
+use object_store::ObjectStore;
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::metrics;
+use crate::storage::objects::open_store;
+
+#[derive(Debug, Deserialize)]
+pub struct OverflowConfig {
+    /// Where oversized payloads go: `s3://bucket/prefix`, `gs://bucket/prefix` or `file:///path`.
+    pub url: String,
+    /// Largest calldata or log data, in bytes, stored in the row itself.
+    #[serde(default = "default_max_inline_bytes")]
+    pub max_inline_bytes: usize,
+}
+
+fn default_max_inline_bytes() -> usize {
+    4096
+}
+
+/// Keeps Postgres rows small by moving calldata and log data over `max_inline_bytes` to object
+/// storage. The row then holds the object's URI instead of the data; the object holds the raw
+/// bytes.
+pub struct OverflowStore {
+    store: Arc<dyn ObjectStore>,
+    prefix: String,
+    url: String,
+    chain_name: String,
+    max_inline_bytes: usize,
+}
+
+impl OverflowStore {
+    pub fn new(chain_name: &str, config: &OverflowConfig) -> Result<Self> {
+        let (store, prefix) = open_store(&config.url)?;
+        Ok(Self {
+            store,
+            prefix,
+            url: config.url.trim_end_matches('/').to_string(),
+            chain_name: chain_name.to_string(),
+            max_inline_bytes: config.max_inline_bytes,
+        })
+    }
+
+    /// Writes `data` to `{url}/{chain}/{kind}/{key}` if it is too large to store inline and
+    /// returns the URI, or returns `None` if it fits in the row. The object is written before
+    /// the row, so a stored URI always resolves.
+    pub async fn offload(&self, kind: &str, key: &str, data: &Bytes) -> Result<Option<String>> {
+        if data.len() <= self.max_inline_bytes {
+            return Ok(None);
+        }
+        let relative = format!("{}/{}/{}", self.chain_name, kind, key);
+        self.store
+            .put(&ObjectPath::from(format!("{}{}", self.prefix, relative)), data.to_vec().into())
+            .await
+            .with_context(|| format!("Failed to store {} {} of {} in overflow storage", kind, key, self.chain_name))?;
+        metrics::increment_counter("overflow_payloads_total", &[("chain", &self.chain_name), ("kind", kind)], 1);
+        Ok(Some(format!("{}/{}", self.url, relative)))
+    }
+}
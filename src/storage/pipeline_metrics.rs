@@ -0,0 +1,150 @@
+use anyhow::Result;
+use log::{error, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::metrics::{self, MetricKind};
+use crate::shutdown::instance_name;
+use crate::storage::sinks::Sink;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PipelineMetricsConfig {
+    pub enabled: bool,
+    pub interval_secs: u64,
+    /// Samples older than this are deleted.
+    pub keep_days: u32,
+}
+
+impl Default for PipelineMetricsConfig {
+    fn default() -> Self {
+        Self { enabled: false, interval_secs: 60, keep_days: 30 }
+    }
+}
+
+/// One row of `pipeline_metrics`.
+#[derive(Serialize)]
+struct Sample {
+    metric: String,
+    labels: Value,
+    value: f64,
+}
+
+fn labels(pairs: &[(String, String)]) -> Value {
+    Value::Object(pairs.iter().map(|(k, v)| (k.clone(), Value::String(v.clone()))).collect::<Map<_, _>>())
+}
+
+fn chain_labels(chain_name: &str) -> Value {
+    labels(&[("chain".to_string(), chain_name.to_string())])
+}
+
+/// Persists the pipeline's state into `pipeline_metrics` every `interval_secs`, for dashboards
+/// built on the database instead of a metrics stack. Each chain gets `chain_head_block`,
+/// `chain_ingested_block`, `chain_lag_blocks` and `chain_blocks_per_second`. Every series of the
+/// metrics registry is added too: gauges as they are, counters as their increase over the
+/// interval, so error counts can be summed over any time range. Every sample is labelled with
+/// the `instance` that wrote it, so standbys' samples stay apart from the leader's.
+pub struct PipelineMetricsRecorder {
+    pg_pool: Arc<PgPool>,
+    instance: String,
+    adapters: HashMap<String, Arc<dyn BlockchainAdapter>>,
+    sinks: HashMap<String, Arc<dyn Sink>>,
+    interval: Duration,
+    keep_days: u32,
+}
+
+impl PipelineMetricsRecorder {
+    pub fn new(
+        pg_pool: Arc<PgPool>,
+        adapters: HashMap<String, Arc<dyn BlockchainAdapter>>,
+        sinks: HashMap<String, Arc<dyn Sink>>,
+        config: &PipelineMetricsConfig,
+    ) -> Self {
+        Self {
+            pg_pool,
+            instance: instance_name(),
+            adapters,
+            sinks,
+            interval: Duration::from_secs(config.interval_secs.max(1)),
+            keep_days: config.keep_days,
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        // Previous counter values and ingested blocks, to turn them into rates.
+        let mut counters: HashMap<(String, Vec<(String, String)>), f64> = HashMap::new();
+        let mut ingested: HashMap<String, (u64, Instant)> = HashMap::new();
+        loop {
+            let mut samples = Vec::new();
+            for (chain_name, adapter) in &self.adapters {
+                let head = match adapter.get_latest_block_number().await {
+                    Ok(head) => Some(head),
+                    Err(e) => {
+                        warn!("Failed to get the head of {} for pipeline metrics: {}", chain_name, e);
+                        None
+                    }
+                };
+                let last_block = match self.sinks.get(chain_name).map(|sink| sink.last_block(chain_name)) {
+                    Some(last_block) => last_block.await.unwrap_or_else(|e| {
+                        warn!("Failed to get the last block of {} for pipeline metrics: {}", chain_name, e);
+                        None
+                    }),
+                    None => None,
+                };
+                let mut add = |metric: &str, value: f64| {
+                    samples.push(Sample { metric: metric.to_string(), labels: chain_labels(chain_name), value })
+                };
+                if let Some(head) = head {
+                    add("chain_head_block", head as f64);
+                }
+                if let Some(last_block) = last_block {
+                    add("chain_ingested_block", last_block as f64);
+                    if let Some((previous, at)) = ingested.insert(chain_name.clone(), (last_block, Instant::now())) {
+                        add("chain_blocks_per_second", last_block.saturating_sub(previous) as f64 / at.elapsed().as_secs_f64());
+                    }
+                }
+                if let (Some(head), Some(last_block)) = (head, last_block) {
+                    add("chain_lag_blocks", head.saturating_sub(last_block) as f64);
+                }
+            }
+
+            for sample in metrics::snapshot() {
+                let value = match sample.kind {
+                    MetricKind::Gauge => sample.value,
+                    MetricKind::Counter => {
+                        let previous = counters.insert((sample.name.clone(), sample.labels.clone()), sample.value);
+                        sample.value - previous.unwrap_or(0.0)
+                    }
+                };
+                samples.push(Sample { metric: sample.name, labels: labels(&sample.labels), value });
+            }
+
+            if let Err(e) = self.record(&samples).await {
+                error!("Failed to record pipeline metrics: {}", e);
+            }
+            tokio::time::sleep(self.interval).await;
+        }
+    }
+
+    async fn record(&self, samples: &[Sample]) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO pipeline_metrics (metric, labels, value)
+            SELECT metric, labels || jsonb_build_object('instance', $2::text), value
+            FROM jsonb_to_recordset($1) AS s(metric TEXT, labels JSONB, value DOUBLE PRECISION)",
+        )
+        .bind(json!(samples))
+        .bind(&self.instance)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        sqlx::query("DELETE FROM pipeline_metrics WHERE at < NOW() - make_interval(days => $1)")
+            .bind(self.keep_days as i32)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        Ok(())
+    }
+}
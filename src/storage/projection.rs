@@ -0,0 +1,52 @@
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// Columns of the built-in tables that can be left out. Keys, and columns the pipeline itself
+/// reads back (e.g. `transactions.gas_price` for daily statistics), always stay.
+const PROJECTABLE_COLUMNS: [(&str, &[&str]); 2] = [
+    ("blocks", &["miner", "difficulty", "total_difficulty", "receipts_root", "transactions"]),
+    ("transactions", &["gas", "input"]),
+];
+
+/// Which columns a chain's sink persists. Excluded columns are written as NULL (left at their
+/// default in ClickHouse), e.g. dropping `transactions.input` calldata, which is most of the
+/// storage on some chains.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProjectionConfig {
+    /// Columns not to persist, per table, e.g. `{ transactions = ["input"] }`.
+    #[serde(default)]
+    pub exclude: HashMap<String, Vec<String>>,
+}
+
+impl ProjectionConfig {
+    /// Rejects tables and columns that can't be excluded.
+    pub fn validate(&self) -> Result<()> {
+        for (table, columns) in &self.exclude {
+            let projectable = PROJECTABLE_COLUMNS
+                .iter()
+                .find(|(name, _)| name == table)
+                .map(|(_, columns)| *columns)
+                .ok_or_else(|| anyhow!("Columns of `{}` can't be excluded", table))?;
+            if let Some(column) = columns.iter().find(|column| !projectable.contains(&column.as_str())) {
+                return Err(anyhow!(
+                    "Column `{}.{}` can't be excluded; excludable columns are {}",
+                    table,
+                    column,
+                    projectable.join(", ")
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether `column` of `table` is persisted.
+    pub fn includes(&self, table: &str, column: &str) -> bool {
+        self.exclude.get(table).map_or(true, |columns| !columns.iter().any(|excluded| excluded == column))
+    }
+
+    /// `value()` if the column is persisted, otherwise `None`.
+    pub fn project<T>(&self, table: &str, column: &str, value: impl FnOnce() -> T) -> Option<T> {
+        self.includes(table, column).then(value)
+    }
+}
@@ -0,0 +1,320 @@
+use anyhow::{anyhow, Result};
+use log::{error, info};
+use serde::Deserialize;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::metrics;
+
+#[derive(Debug, Deserialize)]
+pub struct RetentionConfig {
+    #[serde(default = "default_interval_secs")]
+    pub interval_secs: u64,
+    #[serde(default = "default_batch_size")]
+    pub batch_size: i64,
+    #[serde(default)]
+    pub policies: Vec<RetentionPolicy>,
+}
+
+fn default_interval_secs() -> u64 {
+    3600
+}
+
+fn default_batch_size() -> i64 {
+    10_000
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            interval_secs: default_interval_secs(),
+            batch_size: default_batch_size(),
+            policies: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PruneMode {
+    /// Delete expired rows in batches of `batch_size`.
+    #[default]
+    Delete,
+    /// Drop whole partitions of a table range-partitioned by `block_number`.
+    DropPartitions,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetentionPolicy {
+    /// Table to prune. It must have `chain_name` and `block_number` columns, unless
+    /// `time_column` is set.
+    pub table: String,
+    /// Chain to prune. Every chain is pruned if omitted. Not allowed with `drop_partitions`, as
+    /// partitions hold the rows of every chain.
+    pub chain: Option<String>,
+    pub keep_days: u32,
+    #[serde(default)]
+    pub mode: PruneMode,
+    /// Timestamp column to measure age by instead of the rows' blocks, for tables whose rows
+    /// have no block, e.g. `first_seen` of `pending_transactions`. Rows where it is NULL are kept.
+    /// Not allowed with `drop_partitions`.
+    pub time_column: Option<String>,
+}
+
+/// Background task that enforces retention policies.
+///
+/// Age is measured through the `blocks` table: for each chain, every row at or below the newest
+/// block older than `keep_days` is expired. That lets any table keyed by (chain_name, block_number)
+/// be pruned, whether or not it stores its own timestamp. Policies with a `time_column` expire
+/// rows by that column instead.
+pub struct RetentionPruner {
+    pg_pool: Arc<PgPool>,
+    interval: Duration,
+    batch_size: i64,
+    policies: Vec<RetentionPolicy>,
+    /// The configured chains, pruned by policies that don't name one.
+    chains: Vec<String>,
+}
+
+impl RetentionPruner {
+    pub fn new(pg_pool: Arc<PgPool>, config: &RetentionConfig, chains: Vec<String>) -> Result<Self> {
+        for policy in &config.policies {
+            if !is_valid_identifier(&policy.table) {
+                return Err(anyhow!("Invalid table name `{}` in retention policy", policy.table));
+            }
+            if let Some(column) = &policy.time_column {
+                if !is_valid_identifier(column) {
+                    return Err(anyhow!("Invalid time_column `{}` in retention policy for `{}`", column, policy.table));
+                }
+                if policy.mode == PruneMode::DropPartitions {
+                    return Err(anyhow!(
+                        "Retention policy for `{}` can't drop partitions by time_column, as they are ranges of block_number",
+                        policy.table
+                    ));
+                }
+            }
+            if policy.mode == PruneMode::DropPartitions && policy.chain.is_some() {
+                return Err(anyhow!(
+                    "Retention policy for `{}` can't drop partitions for one chain, as they hold every chain's rows",
+                    policy.table
+                ));
+            }
+        }
+        Ok(Self {
+            pg_pool,
+            interval: Duration::from_secs(config.interval_secs),
+            batch_size: config.batch_size.max(1),
+            policies: config.policies.clone(),
+            chains,
+        })
+    }
+
+    /// Applies every policy once per interval. Runs forever.
+    pub async fn run(&self) -> Result<()> {
+        let mut ticker = tokio::time::interval(self.interval);
+        loop {
+            ticker.tick().await;
+            for policy in &self.policies {
+                if let Err(e) = self.apply(policy).await {
+                    error!("Retention policy for `{}` failed: {}", policy.table, e);
+                }
+            }
+        }
+    }
+
+    async fn apply(&self, policy: &RetentionPolicy) -> Result<()> {
+        let chains: Vec<String> = match &policy.chain {
+            Some(chain) => vec![chain.clone()],
+            None => self.chains.clone(),
+        };
+
+        if let Some(column) = &policy.time_column {
+            for chain_name in chains {
+                let reclaimed = self.delete_expired(&policy.table, column, &chain_name, policy.keep_days).await?;
+                if reclaimed > 0 {
+                    info!(
+                        "Retention reclaimed {} rows from `{}` for {} ({} older than {} days)",
+                        reclaimed, policy.table, chain_name, column, policy.keep_days
+                    );
+                }
+                metrics::increment_counter(
+                    "retention_reclaimed_rows_total",
+                    &[("table", policy.table.as_str()), ("chain", chain_name.as_str())],
+                    reclaimed,
+                );
+            }
+            return Ok(());
+        }
+
+        match policy.mode {
+            PruneMode::Delete => {
+                for chain_name in chains {
+                    let cutoff = match self.cutoff_block(&chain_name, policy.keep_days).await? {
+                        Some(cutoff) => cutoff,
+                        None => continue,
+                    };
+                    let reclaimed = self.delete_batched(&policy.table, &chain_name, cutoff).await?;
+                    self.record_reclaimed(&policy.table, &chain_name, cutoff, reclaimed);
+                }
+            }
+            PruneMode::DropPartitions => {
+                // Partitions are shared by every chain in the table, so only drop what has expired
+                // for all of them.
+                let mut cutoff = i64::MAX;
+                for chain_name in &chains {
+                    match self.cutoff_block(chain_name, policy.keep_days).await? {
+                        Some(chain_cutoff) => cutoff = cutoff.min(chain_cutoff),
+                        None => return Ok(()),
+                    }
+                }
+                if cutoff == i64::MAX {
+                    return Ok(());
+                }
+                let reclaimed = self.drop_partitions(&policy.table, cutoff).await?;
+                self.record_reclaimed(&policy.table, "*", cutoff, reclaimed);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn record_reclaimed(&self, table: &str, chain_name: &str, cutoff: i64, reclaimed: u64) {
+        if reclaimed > 0 {
+            info!(
+                "Retention reclaimed {} rows from `{}` for {} (block_number <= {})",
+                reclaimed, table, chain_name, cutoff
+            );
+        }
+        metrics::increment_counter(
+            "retention_reclaimed_rows_total",
+            &[("table", table), ("chain", chain_name)],
+            reclaimed,
+        );
+    }
+
+    /// The newest block of `chain_name` that is older than `keep_days`, if any.
+    async fn cutoff_block(&self, chain_name: &str, keep_days: u32) -> Result<Option<i64>> {
+        let row = sqlx::query(
+            "SELECT MAX(block_number) AS cutoff FROM blocks
+            WHERE chain_name = $1 AND timestamp < NOW() - make_interval(days => $2)",
+        )
+        .bind(chain_name)
+        .bind(keep_days as i32)
+        .fetch_one(self.pg_pool.as_ref())
+        .await?;
+        Ok(row.try_get("cutoff")?)
+    }
+
+    async fn delete_batched(&self, table: &str, chain_name: &str, cutoff: i64) -> Result<u64> {
+        // Table names cannot be bound, they are validated in `new` instead.
+        let statement = format!(
+            "DELETE FROM {table} WHERE ctid IN (
+                SELECT ctid FROM {table} WHERE chain_name = $1 AND block_number <= $2 LIMIT $3
+            )",
+            table = table
+        );
+
+        let mut reclaimed = 0;
+        loop {
+            let deleted = sqlx::query(&statement)
+                .bind(chain_name)
+                .bind(cutoff)
+                .bind(self.batch_size)
+                .execute(self.pg_pool.as_ref())
+                .await?
+                .rows_affected();
+            reclaimed += deleted;
+            if deleted < self.batch_size as u64 {
+                return Ok(reclaimed);
+            }
+        }
+    }
+
+    /// Deletes rows of `chain_name` whose `column` is older than `keep_days`, in batches.
+    async fn delete_expired(&self, table: &str, column: &str, chain_name: &str, keep_days: u32) -> Result<u64> {
+        // Like table names, columns are validated in `new`. Timestamps are stored in UTC.
+        let statement = format!(
+            "DELETE FROM {table} WHERE ctid IN (
+                SELECT ctid FROM {table}
+                WHERE chain_name = $1 AND {column} < (NOW() AT TIME ZONE 'UTC') - make_interval(days => $2)
+                LIMIT $3
+            )",
+            table = table,
+            column = column
+        );
+
+        let mut reclaimed = 0;
+        loop {
+            let deleted = sqlx::query(&statement)
+                .bind(chain_name)
+                .bind(keep_days as i32)
+                .bind(self.batch_size)
+                .execute(self.pg_pool.as_ref())
+                .await?
+                .rows_affected();
+            reclaimed += deleted;
+            if deleted < self.batch_size as u64 {
+                return Ok(reclaimed);
+            }
+        }
+    }
+
+    /// Drops child partitions whose whole `block_number` range is at or below the cutoff.
+    async fn drop_partitions(&self, table: &str, cutoff: i64) -> Result<u64> {
+        let partitions = sqlx::query(
+            "SELECT c.oid::regclass::text AS partition, pg_get_expr(c.relpartbound, c.oid) AS bound
+            FROM pg_inherits i
+            JOIN pg_class c ON c.oid = i.inhrelid
+            JOIN pg_class p ON p.oid = i.inhparent
+            WHERE p.relname = $1",
+        )
+        .bind(table)
+        .fetch_all(self.pg_pool.as_ref())
+        .await?;
+
+        let mut reclaimed = 0;
+        for row in partitions {
+            // Quoted and schema-qualified as needed, so it can be used as is.
+            let partition: String = row.try_get("partition")?;
+            let bound: String = row.try_get("bound")?;
+            let upper = match partition_upper_bound(&bound) {
+                Some(upper) => upper,
+                None => continue,
+            };
+            // The upper bound of a range partition is exclusive.
+            if upper > cutoff + 1 {
+                continue;
+            }
+
+            let count_row = sqlx::query(&format!("SELECT COUNT(*) AS rows FROM {}", partition))
+                .fetch_one(self.pg_pool.as_ref())
+                .await?;
+            let rows: i64 = count_row.try_get("rows")?;
+
+            sqlx::query(&format!("DROP TABLE {}", partition))
+                .execute(self.pg_pool.as_ref())
+                .await?;
+            info!("Retention dropped partition `{}` of `{}`", partition, table);
+            metrics::increment_counter("retention_dropped_partitions_total", &[("table", table)], 1);
+            reclaimed += rows as u64;
+        }
+
+        Ok(reclaimed)
+    }
+}
+
+/// Parses the exclusive upper bound out of `FOR VALUES FROM ('0') TO ('1000000')`.
+fn partition_upper_bound(bound: &str) -> Option<i64> {
+    let upper = bound.split(" TO ").nth(1)?;
+    upper
+        .trim_matches(|c: char| c == '(' || c == ')' || c == '\'' || c.is_whitespace())
+        .parse()
+        .ok()
+}
+
+fn is_valid_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+}
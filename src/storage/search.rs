@@ -0,0 +1,275 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Log, Transaction, H256};
+use ethers::utils::hex;
+use reqwest::Client;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashMap};
+use std::env;
+use std::sync::Arc;
+
+use crate::blockchain::adapters::{logs_of_block, BlockchainAdapter};
+use crate::enrichment::abi::AbiRegistry;
+use crate::enrichment::selectors::{method_name, SignatureDatabase};
+use crate::enrichment::transfers::decode_erc20_transfer;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct SearchConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    pub url: Option<String>,      // env var holding the OpenSearch/Elasticsearch URL, e.g. http://localhost:9200
+    pub user: Option<String>,     // env var holding the user name
+    pub password: Option<String>, // env var holding the password
+    #[serde(default = "default_index")]
+    pub index: String,
+    /// Chains to index; all of them when empty.
+    #[serde(default)]
+    pub chains: Vec<String>,
+    /// Token symbols by contract address, e.g. `"0xa0b8...eb48" = "USDC"`, attached to the
+    /// transfers and events of those contracts.
+    #[serde(default)]
+    pub token_symbols: HashMap<String, String>,
+}
+
+fn default_index() -> String {
+    "chain-activity".to_string()
+}
+
+// Keywords for exact matches and facets; `text` holds everything worth free-text search.
+const INDEX_MAPPINGS: &str = r#"{
+    "mappings": {
+        "properties": {
+            "chain": {"type": "keyword"},
+            "block_number": {"type": "long"},
+            "block_hash": {"type": "keyword"},
+            "timestamp": {"type": "date", "format": "epoch_second"},
+            "tx_hash": {"type": "keyword"},
+            "from": {"type": "keyword"},
+            "to": {"type": "keyword"},
+            "value": {"type": "keyword"},
+            "method_selector": {"type": "keyword"},
+            "method_name": {"type": "keyword"},
+            "addresses": {"type": "keyword"},
+            "event_names": {"type": "keyword"},
+            "token_symbols": {"type": "keyword"},
+            "events": {"type": "object", "enabled": false},
+            "text": {"type": "text"}
+        }
+    }
+}"#;
+
+/// Indexes each committed block's transactions in OpenSearch (or Elasticsearch), one document
+/// per transaction keyed by `{chain}:{tx_hash}`, with the method name, the events its logs
+/// decode to against the registered ABIs, ERC-20 transfers, and every address and token symbol
+/// involved. Documents of orphaned blocks are deleted.
+pub struct SearchIndexer {
+    client: Client,
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+    index: String,
+    adapter: Arc<dyn BlockchainAdapter>,
+    abis: Arc<AbiRegistry>,
+    signatures: Option<Arc<SignatureDatabase>>,
+    token_symbols: Arc<HashMap<String, String>>,
+}
+
+/// The index connection shared by every chain's indexer.
+pub struct SearchIndex {
+    client: Client,
+    url: String,
+    user: Option<String>,
+    password: Option<String>,
+    index: String,
+    token_symbols: Arc<HashMap<String, String>>,
+}
+
+impl SearchIndex {
+    /// Resolves the configured environment variables and creates the index if needed.
+    pub async fn connect(config: &SearchConfig) -> Result<Self> {
+        let resolve = |key: &str| {
+            env::var(key).with_context(|| format!("Failed to get search setting from environment for key `{}`", key))
+        };
+        let url = config.url.as_deref().ok_or_else(|| anyhow!("[search] needs a url"))?;
+        let index = Self {
+            client: Client::new(),
+            url: resolve(url)?.trim_end_matches('/').to_string(),
+            user: config.user.as_deref().map(resolve).transpose()?,
+            password: config.password.as_deref().map(resolve).transpose()?,
+            index: config.index.clone(),
+            token_symbols: Arc::new(
+                config.token_symbols.iter().map(|(address, symbol)| (address.to_lowercase(), symbol.clone())).collect(),
+            ),
+        };
+
+        let mut request = index.client.put(format!("{}/{}", index.url, index.index)).header("Content-Type", "application/json");
+        if let Some(user) = &index.user {
+            request = request.basic_auth(user, index.password.as_ref());
+        }
+        let response = request.body(INDEX_MAPPINGS).send().await?;
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if !status.is_success() && !body.contains("resource_already_exists_exception") {
+            return Err(anyhow!("Failed to create search index {} ({}): {}", index.index, status, body.trim()));
+        }
+        Ok(index)
+    }
+
+    pub fn indexer(
+        &self,
+        adapter: Arc<dyn BlockchainAdapter>,
+        abis: Arc<AbiRegistry>,
+        signatures: Option<Arc<SignatureDatabase>>,
+    ) -> SearchIndexer {
+        SearchIndexer {
+            client: self.client.clone(),
+            url: self.url.clone(),
+            user: self.user.clone(),
+            password: self.password.clone(),
+            index: self.index.clone(),
+            adapter,
+            abis,
+            signatures,
+            token_symbols: Arc::clone(&self.token_symbols),
+        }
+    }
+}
+
+impl SearchIndexer {
+    async fn post(&self, path: &str, content_type: &str, body: String) -> Result<Value> {
+        let mut request = self.client.post(format!("{}/{}", self.url, path)).header("Content-Type", content_type);
+        if let Some(user) = &self.user {
+            request = request.basic_auth(user, self.password.as_ref());
+        }
+        let response = request.body(body).send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response.text().await.unwrap_or_default();
+            return Err(anyhow!("Search request failed ({}): {}", status, message.trim()));
+        }
+        Ok(response.json().await?)
+    }
+
+    fn symbol(&self, address: &str) -> Option<&String> {
+        self.token_symbols.get(address)
+    }
+
+    /// The searchable document of one transaction and its logs.
+    fn document(&self, chain_name: &str, block: &Block<Transaction>, transaction: &Transaction, logs: &[&Log]) -> Value {
+        let from = format!("{:?}", transaction.from);
+        let to = transaction.to.map(|to| format!("{:?}", to));
+        let mut addresses: BTreeSet<String> = std::iter::once(from.clone()).chain(to.clone()).collect();
+        let mut symbols = BTreeSet::new();
+        let mut event_names = BTreeSet::new();
+        let mut events = Vec::new();
+
+        let (method_selector, method) = match transaction.input.get(..4) {
+            Some(selector) => {
+                let mut bytes = [0u8; 4];
+                bytes.copy_from_slice(selector);
+                (Some(format!("0x{}", hex::encode(bytes))), method_name(&self.abis, self.signatures.as_deref(), bytes))
+            }
+            None => (None, None),
+        };
+        if let Some(symbol) = to.as_deref().and_then(|to| self.symbol(to)) {
+            symbols.insert(symbol.clone());
+        }
+
+        for log in logs {
+            let contract = format!("{:?}", log.address);
+            let symbol = self.symbol(&contract).cloned();
+            addresses.insert(contract.clone());
+            symbols.extend(symbol.clone());
+            if let Some(transfer) = decode_erc20_transfer(log) {
+                addresses.insert(format!("{:?}", transfer.from));
+                addresses.insert(format!("{:?}", transfer.to));
+            }
+            if let Some(event) = self.abis.decode_log(log) {
+                event_names.insert(event.name.clone());
+                let params = event.params_json();
+                // Address parameters are worth finding the transaction by, e.g. a swap's recipient.
+                if let Value::Object(params) = &params {
+                    for value in params.values() {
+                        if let Some(address) = value.as_str().filter(|value| value.len() == 42 && value.starts_with("0x")) {
+                            addresses.insert(address.to_string());
+                        }
+                    }
+                }
+                events.push(json!({ "name": event.name, "contract": contract, "token_symbol": symbol, "params": params }));
+            }
+        }
+
+        let text = method
+            .iter()
+            .chain(&event_names)
+            .chain(&symbols)
+            .chain(&addresses)
+            .cloned()
+            .collect::<Vec<_>>()
+            .join(" ");
+        json!({
+            "chain": chain_name,
+            "block_number": block.number.unwrap_or_default().as_u64(),
+            "block_hash": format!("{:?}", block.hash.unwrap_or_default()),
+            "timestamp": block.timestamp.as_u64(),
+            "tx_hash": format!("{:?}", transaction.hash),
+            "from": from,
+            "to": to,
+            "value": transaction.value.to_string(),
+            "method_selector": method_selector,
+            "method_name": method,
+            "addresses": addresses,
+            "event_names": event_names,
+            "token_symbols": symbols,
+            "events": events,
+            "text": text,
+        })
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for SearchIndexer {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        if block.transactions.is_empty() {
+            return Ok(());
+        }
+        let logs = self.adapter.get_logs(block.number.unwrap_or_default().as_u64()).await?;
+        let logs = logs_of_block(logs, block.hash);
+        let mut logs_by_tx: HashMap<H256, Vec<&Log>> = HashMap::new();
+        for log in &logs {
+            if let Some(tx_hash) = log.transaction_hash {
+                logs_by_tx.entry(tx_hash).or_default().push(log);
+            }
+        }
+
+        let mut body = String::new();
+        for transaction in &block.transactions {
+            let logs = logs_by_tx.get(&transaction.hash).map(Vec::as_slice).unwrap_or_default();
+            let id = format!("{}:{:?}", chain_name, transaction.hash);
+            body.push_str(&json!({ "index": { "_index": self.index, "_id": id } }).to_string());
+            body.push('\n');
+            body.push_str(&self.document(chain_name, block, transaction, logs).to_string());
+            body.push('\n');
+        }
+        let response = self.post("_bulk", "application/x-ndjson", body).await?;
+        // The bulk API reports per-document failures in a successful response.
+        if response["errors"].as_bool().unwrap_or(false) {
+            return Err(anyhow!("Search bulk index of {} block {:?} had failures", chain_name, block.number));
+        }
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let hashes: Vec<&str> = orphaned.iter().map(|block| block.hash.as_str()).collect();
+        let query = json!({
+            "query": { "bool": { "filter": [
+                { "term": { "chain": chain_name } },
+                { "terms": { "block_hash": hashes } },
+            ] } }
+        });
+        self.post(&format!("{}/_delete_by_query", self.index), "application/json", query.to_string()).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use log::{info, warn};
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::metrics;
+use crate::storage::sinks::{Sink, WriteOutcome};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    pub enabled: bool,
+    /// Consecutive failed writes that open the circuit.
+    pub failure_threshold: u32,
+    /// How often a write is tried while the circuit is open.
+    pub probe_interval_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self { enabled: false, failure_threshold: 5, probe_interval_secs: 10 }
+    }
+}
+
+#[derive(Default)]
+struct BreakerState {
+    consecutive_failures: u32,
+    open: bool,
+}
+
+/// Stops a chain's consumers from hammering a sink that is down. After `failure_threshold`
+/// failed writes in a row the circuit opens: every write then waits `probe_interval_secs` before
+/// it is tried, so consumers slow to one probe per interval and the broker buffers their backlog.
+/// Failed probes still fail, and the consumer writes its unacked blocks again. The first write that
+/// succeeds closes the circuit, and every consumer carries on at full speed.
+pub struct CircuitBreakerSink {
+    inner: Arc<dyn Sink>,
+    chain_name: String,
+    failure_threshold: u32,
+    probe_interval: Duration,
+    /// Shared with the copies made for other consumers, so the chain has one circuit.
+    state: Arc<Mutex<BreakerState>>,
+}
+
+impl CircuitBreakerSink {
+    pub fn new(inner: Arc<dyn Sink>, chain_name: &str, config: &CircuitBreakerConfig) -> Self {
+        metrics::set_gauge("sink_circuit_open", &[("chain", chain_name)], 0.0);
+        Self {
+            inner,
+            chain_name: chain_name.to_string(),
+            failure_threshold: config.failure_threshold.max(1),
+            probe_interval: Duration::from_secs(config.probe_interval_secs.max(1)),
+            state: Arc::new(Mutex::new(BreakerState::default())),
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).open
+    }
+
+    fn record_success(&self) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.consecutive_failures = 0;
+        if state.open {
+            state.open = false;
+            info!("Sink of {} recovered, closing its circuit", self.chain_name);
+            metrics::set_gauge("sink_circuit_open", &[("chain", &self.chain_name)], 0.0);
+        }
+    }
+
+    /// Counts a failed write, opening the circuit at the threshold.
+    fn record_failure(&self, e: &anyhow::Error) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        state.consecutive_failures = state.consecutive_failures.saturating_add(1);
+        if !state.open && state.consecutive_failures >= self.failure_threshold {
+            state.open = true;
+            warn!(
+                "Sink of {} failed {} writes in a row, opening its circuit until a probe succeeds: {}",
+                self.chain_name, state.consecutive_failures, e
+            );
+            metrics::set_gauge("sink_circuit_open", &[("chain", &self.chain_name)], 1.0);
+            metrics::increment_counter("sink_circuit_opened_total", &[("chain", &self.chain_name)], 1);
+        }
+    }
+
+    /// Runs `write`. While the circuit is open it is delayed by `probe_interval` and counted as a
+    /// probe. A failed write is always returned to the consumer, never retried here: a buffering
+    /// sink may have taken its whole batch along with it, and only the consumer, which writes
+    /// every unacked block again, gets that batch back.
+    async fn guarded<Fut>(&self, write: Fut) -> Result<WriteOutcome>
+    where
+        Fut: Future<Output = Result<WriteOutcome>> + Send,
+    {
+        let probe = self.is_open();
+        if probe {
+            tokio::time::sleep(self.probe_interval).await;
+        }
+        let result = write.await;
+        if probe {
+            let result_label = if result.is_ok() { "success" } else { "failure" };
+            metrics::increment_counter(
+                "sink_circuit_probes_total",
+                &[("chain", &self.chain_name), ("result", result_label)],
+                1,
+            );
+        }
+        match &result {
+            Ok(_) => self.record_success(),
+            Err(e) => self.record_failure(e),
+        }
+        result
+    }
+}
+
+#[async_trait]
+impl Sink for CircuitBreakerSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.guarded(self.inner.write_block(chain_name, block)).await
+    }
+
+    async fn write_transactions(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.guarded(self.inner.write_transactions(chain_name, block)).await
+    }
+
+    async fn write_header(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.guarded(self.inner.write_header(chain_name, block)).await
+    }
+
+    async fn last_block(&self, chain_name: &str) -> Result<Option<u64>> {
+        self.inner.last_block(chain_name).await
+    }
+
+    // Not guarded, so shutdown isn't held up by a sink that is down.
+    async fn flush(&self, chain_name: &str) -> Result<Option<WriteOutcome>> {
+        self.inner.flush(chain_name).await
+    }
+
+    fn flush_interval(&self) -> Option<Duration> {
+        self.inner.flush_interval()
+    }
+
+    fn with_own_buffer(&self) -> Option<Arc<dyn Sink>> {
+        Some(Arc::new(Self {
+            inner: self.inner.with_own_buffer()?,
+            chain_name: self.chain_name.clone(),
+            failure_threshold: self.failure_threshold,
+            probe_interval: self.probe_interval,
+            state: Arc::clone(&self.state),
+        }))
+    }
+}
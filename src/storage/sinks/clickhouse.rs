@@ -0,0 +1,434 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+use crate::storage::projection::ProjectionConfig;
+use crate::storage::sinks::{Sink, WriteOutcome};
+use crate::streams::sharding;
+
+#[derive(Debug, Deserialize)]
+pub struct ClickHouseSinkConfig {
+    pub url: String, // env var holding the HTTP interface URL, e.g. http://localhost:8123
+    #[serde(default = "default_database")]
+    pub database: String,
+    pub user: Option<String>,     // env var holding the user name
+    pub password: Option<String>, // env var holding the password
+    /// Lets the server batch inserts from many writers (`async_insert`), waiting for each to be
+    /// flushed before it counts as written.
+    #[serde(default)]
+    pub async_insert: bool,
+    /// Rows buffered in the sink before they are inserted together. Blocks are acknowledged once
+    /// their rows are inserted.
+    #[serde(default = "default_insert_block_size")]
+    pub insert_block_size: usize,
+    /// Seconds pending rows may wait before they are inserted even though fewer than
+    /// `insert_block_size` have come in, so a quiet chain's blocks are still acknowledged.
+    #[serde(default = "default_flush_interval_secs")]
+    pub flush_interval_secs: u64,
+    /// Inserts into `Buffer` tables in front of the tables, which the server flushes to them in
+    /// the background. Blocks are acknowledged once the sink has the `Buffer` tables flushed,
+    /// every `flush_interval_secs`.
+    #[serde(default)]
+    pub buffer_tables: bool,
+    /// Creates the tables `ON CLUSTER` this cluster, on every node, with `Distributed` tables
+    /// `{table}_all` over them that rows are inserted into and read from.
+    pub cluster: Option<String>,
+}
+
+fn default_database() -> String {
+    "default".to_string()
+}
+
+fn default_insert_block_size() -> usize {
+    1
+}
+
+fn default_flush_interval_secs() -> u64 {
+    5
+}
+
+#[derive(Default)]
+struct PendingRows {
+    blocks: Vec<BlockRow>,
+    transactions: Vec<TransactionRow>,
+    headers: Vec<HeaderRow>,
+    /// Rows were inserted into the `Buffer` tables since they were last flushed.
+    in_buffer_tables: bool,
+}
+
+impl PendingRows {
+    fn len(&self) -> usize {
+        self.blocks.len() + self.transactions.len() + self.headers.len()
+    }
+}
+
+#[derive(Serialize)]
+struct BlockRow {
+    chain_name: String,
+    block_number: u64,
+    hash: String,
+    parent_hash: String,
+    timestamp: u64,
+    // Columns left out by the projection are omitted, so ClickHouse stores their default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    miner: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    difficulty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total_difficulty: Option<String>,
+    gas_used: u64,
+    gas_limit: u64,
+    size: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    receipts_root: Option<String>,
+    tx_count: u64,
+}
+
+#[derive(Serialize)]
+struct HeaderRow {
+    chain_name: String,
+    block_number: u64,
+    hash: String,
+    parent_hash: String,
+    timestamp: u64,
+    miner: Option<String>,
+    gas_used: u64,
+    gas_limit: u64,
+    base_fee_per_gas: Option<String>,
+}
+
+#[derive(Serialize)]
+struct TransactionRow {
+    chain_name: String,
+    block_number: u64,
+    block_hash: String,
+    tx_hash: String,
+    from_address: String,
+    to_address: Option<String>,
+    value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gas_price: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    gas: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    input: Option<String>,
+    nonce: u64,
+}
+
+// ReplacingMergeTree keyed by hash, so redelivered blocks collapse on merge. Blocks orphaned by a
+// reorg stay in place; readers pick one block per height, e.g. by following parent hashes.
+const CREATE_TABLES: [&str; 3] = [
+    "CREATE TABLE IF NOT EXISTS {db}.blocks{on_cluster} (
+        chain_name LowCardinality(String), block_number UInt64, hash String, parent_hash String,
+        timestamp DateTime, miner String, difficulty String, total_difficulty String,
+        gas_used UInt64, gas_limit UInt64, size UInt64, receipts_root String, tx_count UInt64
+    ) ENGINE = ReplacingMergeTree ORDER BY (chain_name, block_number, hash)",
+    "CREATE TABLE IF NOT EXISTS {db}.transactions{on_cluster} (
+        chain_name LowCardinality(String), block_number UInt64, block_hash String, tx_hash String,
+        from_address String, to_address Nullable(String), value String, gas_price String,
+        gas String, input String, nonce UInt64
+    ) ENGINE = ReplacingMergeTree ORDER BY (chain_name, block_number, block_hash, tx_hash)",
+    "CREATE TABLE IF NOT EXISTS {db}.block_headers{on_cluster} (
+        chain_name LowCardinality(String), block_number UInt64, hash String, parent_hash String,
+        timestamp DateTime, miner Nullable(String), gas_used UInt64, gas_limit UInt64,
+        base_fee_per_gas Nullable(String)
+    ) ENGINE = ReplacingMergeTree ORDER BY (chain_name, block_number, hash)",
+];
+
+const TABLES: [&str; 3] = ["blocks", "transactions", "block_headers"];
+
+// Sharded by height, so a redelivered row lands on the shard holding the row it replaces.
+const CREATE_DISTRIBUTED_TABLE: &str = "CREATE TABLE IF NOT EXISTS {db}.{table}_all{on_cluster} AS {db}.{table}
+    ENGINE = Distributed({cluster}, {db}, {table}, cityHash64(chain_name, block_number))";
+
+// Flushed to `{table}` once 10s and 10k rows, or 100s, 1M rows or 100 MB are reached in any of
+// the 16 layers, or when the sink flushes it.
+const CREATE_BUFFER_TABLE: &str = "CREATE TABLE IF NOT EXISTS {db}.{table}_buffer{on_cluster} AS {db}.{table}
+    ENGINE = Buffer({db}, {table}, 16, 10, 100, 10000, 1000000, 10000000, 100000000)";
+
+/// Writes blocks and transactions to ClickHouse over its HTTP interface, one `JSONEachRow` insert
+/// per table and `insert_block_size` rows. Canonicality is not tracked. Each consumer keeps its
+/// own pending rows, which consumers insert after `flush_interval_secs` at the latest.
+pub struct ClickHouseSink {
+    client: Client,
+    url: String,
+    database: String,
+    user: Option<String>,
+    password: Option<String>,
+    projection: ProjectionConfig,
+    async_insert: bool,
+    insert_block_size: usize,
+    flush_interval: Duration,
+    buffer_tables: bool,
+    cluster: Option<String>,
+    pending: Mutex<PendingRows>,
+}
+
+impl ClickHouseSink {
+    /// Resolves the configured environment variables and creates the tables if needed.
+    pub async fn new(config: &ClickHouseSinkConfig) -> Result<Self> {
+        if config.buffer_tables && config.cluster.is_some() {
+            // Rows a Buffer table flushes to a Distributed one reach the shards asynchronously,
+            // so there is no point at which they are known to be stored.
+            return Err(anyhow!("ClickHouse buffer_tables can't be combined with cluster"));
+        }
+        let resolve = |key: &str| {
+            env::var(key).with_context(|| format!("Failed to get ClickHouse setting from environment for key `{}`", key))
+        };
+        let sink = Self {
+            client: Client::new(),
+            url: resolve(&config.url)?,
+            database: config.database.clone(),
+            user: config.user.as_deref().map(resolve).transpose()?,
+            password: config.password.as_deref().map(resolve).transpose()?,
+            projection: ProjectionConfig::default(),
+            async_insert: config.async_insert,
+            insert_block_size: config.insert_block_size.max(1),
+            flush_interval: Duration::from_secs(config.flush_interval_secs.max(1)),
+            buffer_tables: config.buffer_tables,
+            cluster: config.cluster.clone(),
+            pending: Mutex::new(PendingRows::default()),
+        };
+
+        let on_cluster = config.cluster.as_ref().map(|cluster| format!(" ON CLUSTER {}", cluster)).unwrap_or_default();
+        let ddl = |statement: &str| statement.replace("{db}", &sink.database).replace("{on_cluster}", &on_cluster);
+        for statement in CREATE_TABLES {
+            sink.execute(&ddl(statement), String::new()).await?;
+        }
+        if let Some(cluster) = &config.cluster {
+            for table in TABLES {
+                let statement = ddl(CREATE_DISTRIBUTED_TABLE).replace("{cluster}", cluster).replace("{table}", table);
+                sink.execute(&statement, String::new()).await?;
+            }
+        }
+        if sink.buffer_tables {
+            for table in TABLES {
+                sink.execute(&ddl(CREATE_BUFFER_TABLE).replace("{table}", table), String::new()).await?;
+            }
+        }
+        Ok(sink)
+    }
+
+    /// Leaves the columns the projection excludes at their defaults.
+    pub fn with_projection(mut self, projection: ProjectionConfig) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Runs `query` with `body` as its input data and returns the response body.
+    async fn execute_with_params(&self, query: &str, params: &[(&str, &str)], body: String) -> Result<String> {
+        let mut request = self.client.post(&self.url).query(&[("query", query)]).query(params).body(body);
+        if let Some(user) = &self.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let message = response.text().await.unwrap_or_default();
+            return Err(anyhow!("ClickHouse query failed ({}): {}", status, message.trim()));
+        }
+        Ok(response.text().await?)
+    }
+
+    async fn execute(&self, query: &str, body: String) -> Result<()> {
+        self.execute_with_params(query, &[], body).await?;
+        Ok(())
+    }
+
+    /// The table inserts and reads of `table` go to: its `Buffer` table if there is one, which
+    /// reads see through to the table, or on a cluster its `Distributed` table.
+    fn table(&self, table: &str) -> String {
+        if self.buffer_tables {
+            format!("{}.{}_buffer", self.database, table)
+        } else if self.cluster.is_some() {
+            format!("{}.{}_all", self.database, table)
+        } else {
+            format!("{}.{}", self.database, table)
+        }
+    }
+
+    async fn insert<T: Serialize>(&self, table: &str, rows: &[T]) -> Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut body = String::new();
+        for row in rows {
+            body.push_str(&serde_json::to_string(row)?);
+            body.push('\n');
+        }
+        let query = format!("INSERT INTO {} FORMAT JSONEachRow", self.table(table));
+        // Waiting for the flush, and on a cluster for the shards, keeps the write durable once
+        // it returns.
+        let mut params = Vec::new();
+        if self.async_insert {
+            params.extend([("async_insert", "1"), ("wait_for_async_insert", "1")]);
+        }
+        if self.cluster.is_some() {
+            params.push(("insert_distributed_sync", "1"));
+        }
+        self.execute_with_params(&query, &params, body).await?;
+        Ok(())
+    }
+
+    /// Adds a block's rows to the pending ones, and inserts them all once there are
+    /// `insert_block_size`. The write is durable only if they were inserted into the tables
+    /// themselves; rows in `Buffer` tables wait for the next flush.
+    async fn stage(&self, add: impl FnOnce(&mut PendingRows)) -> Result<WriteOutcome> {
+        let mut pending = self.pending.lock().await;
+        add(&mut pending);
+        if pending.len() < self.insert_block_size {
+            return Ok(WriteOutcome { orphaned: Vec::new(), durable: false });
+        }
+        self.insert_rows(&mut pending).await?;
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: !self.buffer_tables })
+    }
+
+    // Called with the pending rows' lock held, so later rows can't be inserted ahead of these.
+    async fn insert_rows(&self, pending: &mut PendingRows) -> Result<()> {
+        let rows = std::mem::take(pending);
+        pending.in_buffer_tables = rows.in_buffer_tables;
+        // Transactions first, so a block row never exists without its transactions.
+        self.insert("transactions", &rows.transactions).await?;
+        self.insert("blocks", &rows.blocks).await?;
+        self.insert("block_headers", &rows.headers).await?;
+        pending.in_buffer_tables |= self.buffer_tables;
+        Ok(())
+    }
+
+    /// Has the server write out its `Buffer` tables, this consumer's rows among them.
+    async fn flush_buffer_tables(&self) -> Result<()> {
+        for table in TABLES {
+            self.execute(&format!("OPTIMIZE TABLE {}", self.table(table)), String::new()).await?;
+        }
+        Ok(())
+    }
+
+    fn transaction_rows(&self, chain_name: &str, block: &Block<Transaction>) -> Vec<TransactionRow> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let block_hash = format!("{:?}", block.hash.unwrap_or_default());
+        block
+            .transactions
+            .iter()
+            .map(|transaction| TransactionRow {
+                chain_name: chain_name.to_string(),
+                block_number,
+                block_hash: block_hash.clone(),
+                tx_hash: format!("{:?}", transaction.hash),
+                from_address: format!("{:?}", transaction.from),
+                to_address: transaction.to.map(|to| format!("{:?}", to)),
+                value: transaction.value.to_string(),
+                gas_price: self.projection.project("transactions", "gas_price", || transaction.gas_price.unwrap_or_default().to_string()),
+                gas: self.projection.project("transactions", "gas", || transaction.gas.to_string()),
+                input: self.projection.project("transactions", "input", || transaction.input.to_string()),
+                nonce: transaction.nonce.as_u64(),
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl Sink for ClickHouseSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let transactions = self.transaction_rows(chain_name, block);
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let block_hash = format!("{:?}", block.hash.unwrap_or_default());
+
+        let row = BlockRow {
+            chain_name: chain_name.to_string(),
+            block_number,
+            hash: block_hash,
+            parent_hash: format!("{:?}", block.parent_hash),
+            timestamp: block.timestamp.as_u64(),
+            miner: self.projection.project("blocks", "miner", || format!("{:?}", block.author.unwrap_or_default())),
+            difficulty: self.projection.project("blocks", "difficulty", || block.difficulty.to_string()),
+            total_difficulty: self.projection.project("blocks", "total_difficulty", || block.total_difficulty.unwrap_or_default().to_string()),
+            gas_used: block.gas_used.as_u64(),
+            gas_limit: block.gas_limit.as_u64(),
+            size: block.size.unwrap_or_default().as_u64(),
+            receipts_root: self.projection.project("blocks", "receipts_root", || format!("{:?}", block.receipts_root)),
+            tx_count: sharding::tx_count(block) as u64,
+        };
+        self.stage(|pending| {
+            pending.transactions.extend(transactions);
+            pending.blocks.push(row);
+        })
+        .await
+    }
+
+    async fn write_transactions(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let transactions = self.transaction_rows(chain_name, block);
+        self.stage(|pending| pending.transactions.extend(transactions)).await
+    }
+
+    async fn write_header(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let row = HeaderRow {
+            chain_name: chain_name.to_string(),
+            block_number: block.number.unwrap_or_default().as_u64(),
+            hash: format!("{:?}", block.hash.unwrap_or_default()),
+            parent_hash: format!("{:?}", block.parent_hash),
+            timestamp: block.timestamp.as_u64(),
+            miner: block.author.map(|author| format!("{:?}", author)),
+            gas_used: block.gas_used.as_u64(),
+            gas_limit: block.gas_limit.as_u64(),
+            base_fee_per_gas: block.base_fee_per_gas.map(|fee| fee.to_string()),
+        };
+        self.stage(|pending| pending.headers.push(row)).await
+    }
+
+    async fn last_block(&self, chain_name: &str) -> Result<Option<u64>> {
+        let query = format!(
+            "SELECT max(block_number), count() FROM {} WHERE chain_name = {{chain:String}} FORMAT TSV",
+            self.table("blocks")
+        );
+        let response = self.execute_with_params(&query, &[("param_chain", chain_name)], String::new()).await?;
+        let mut fields = response.trim().split('\t');
+        let last_block: u64 = fields.next().unwrap_or("0").parse()?;
+        let count: u64 = fields.next().unwrap_or("0").parse()?;
+        // max() of no rows is 0, not NULL.
+        Ok((count > 0).then_some(last_block))
+    }
+
+    async fn flush(&self, _chain_name: &str) -> Result<Option<WriteOutcome>> {
+        let mut pending = self.pending.lock().await;
+        if pending.len() == 0 && !pending.in_buffer_tables {
+            return Ok(None);
+        }
+        self.insert_rows(&mut pending).await?;
+        if pending.in_buffer_tables {
+            self.flush_buffer_tables().await?;
+            pending.in_buffer_tables = false;
+        }
+        Ok(Some(WriteOutcome { orphaned: Vec::new(), durable: true }))
+    }
+
+    fn flush_interval(&self) -> Option<Duration> {
+        Some(self.flush_interval)
+    }
+
+    fn with_own_buffer(&self) -> Option<Arc<dyn Sink>> {
+        Some(Arc::new(Self {
+            client: self.client.clone(),
+            url: self.url.clone(),
+            database: self.database.clone(),
+            user: self.user.clone(),
+            password: self.password.clone(),
+            projection: self.projection.clone(),
+            async_insert: self.async_insert,
+            insert_block_size: self.insert_block_size,
+            flush_interval: self.flush_interval,
+            buffer_tables: self.buffer_tables,
+            cluster: self.cluster.clone(),
+            pending: Mutex::new(PendingRows::default()),
+        }))
+    }
+}
@@ -0,0 +1,239 @@
+use anyhow::{Context, Result};
+use arrow::array::{ArrayRef, Int64Array, StringArray};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use deltalake::kernel::Transaction as AppTransaction;
+use deltalake::operations::optimize::OptimizeType;
+use deltalake::operations::transaction::CommitProperties;
+use deltalake::protocol::SaveMode;
+use deltalake::{DeltaOps, DeltaTable};
+use ethers::types::{Block, Log, Transaction};
+use futures_util::future::try_join_all;
+use log::{info, warn};
+use serde::Deserialize;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::storage::projection::ProjectionConfig;
+use crate::storage::sinks::parquet::{blocks_batch, transactions_batch};
+use crate::storage::sinks::{Sink, WriteOutcome};
+
+#[derive(Debug, Deserialize)]
+pub struct DeltaSinkConfig {
+    /// Directory or `s3://` URI the tables are created under, as `{uri}/{chain}/blocks`,
+    /// `{uri}/{chain}/transactions` and `{uri}/{chain}/logs`.
+    pub uri: String,
+    #[serde(default = "default_blocks_per_commit")]
+    pub blocks_per_commit: usize,
+    /// Z-orders the tables by `block_number` after every this many commits.
+    pub zorder_every: Option<u64>,
+    /// Fetches each block's logs from the node and appends them to the `logs` table.
+    #[serde(default = "default_logs")]
+    pub logs: bool,
+}
+
+fn default_blocks_per_commit() -> usize {
+    1000
+}
+
+fn default_logs() -> bool {
+    true
+}
+
+/// Buffers blocks and appends them to Delta tables, one commit per table and batch, with the
+/// same columns as the Parquet sink, plus the blocks' logs. Each commit records two application
+/// transactions per chain: `{chain}` at the batch's last block, so a batch redelivered after its
+/// commit is not appended again, and `{chain}:high` at the highest block committed so far, which
+/// is where ingestion resumes. Blocks are only acknowledged once every commit is in.
+pub struct DeltaSink {
+    uri: String,
+    blocks_per_commit: usize,
+    zorder_every: Option<u64>,
+    projection: ProjectionConfig,
+    adapter: Option<Arc<dyn BlockchainAdapter>>,
+    buffer: Mutex<Vec<Block<Transaction>>>,
+    commits: AtomicU64,
+    /// Set while a z-order runs in the background, so runs never pile up behind a slow one.
+    optimizing: Arc<AtomicBool>,
+}
+
+impl DeltaSink {
+    pub fn new(config: &DeltaSinkConfig) -> Self {
+        if config.uri.starts_with("s3://") {
+            deltalake::aws::register_handlers(None);
+        }
+        Self {
+            uri: config.uri.trim_end_matches('/').to_string(),
+            blocks_per_commit: config.blocks_per_commit.max(1),
+            zorder_every: config.zorder_every.filter(|every| *every > 0),
+            projection: ProjectionConfig::default(),
+            adapter: None,
+            buffer: Mutex::new(Vec::new()),
+            commits: AtomicU64::new(0),
+            optimizing: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Writes nulls instead of the columns the projection excludes.
+    pub fn with_projection(mut self, projection: ProjectionConfig) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Fetches the logs of every committed block from `adapter` for the `logs` table.
+    pub fn with_logs(mut self, adapter: Arc<dyn BlockchainAdapter>) -> Self {
+        self.adapter = Some(adapter);
+        self
+    }
+
+    fn table_uri(&self, chain_name: &str, table: &str) -> String {
+        format!("{}/{}/{}", self.uri, chain_name, table)
+    }
+
+    /// Commits a batch of blocks to the logs, transactions and blocks tables.
+    async fn commit_blocks(&self, chain_name: &str, blocks: &[Block<Transaction>]) -> Result<()> {
+        let last = blocks.iter().map(|block| block.number.unwrap_or_default().as_u64()).max().unwrap_or_default();
+        // Logs and transactions first, so a block row never exists without them.
+        if let Some(adapter) = &self.adapter {
+            let logs = try_join_all(blocks.iter().map(|block| adapter.get_logs(block.number.unwrap_or_default().as_u64())))
+                .await
+                .context("Failed to fetch logs for Delta")?;
+            if logs.iter().any(|logs| !logs.is_empty()) {
+                self.commit(chain_name, "logs", logs_batch(chain_name, blocks, &logs)?, last).await?;
+            }
+        }
+        self.commit(chain_name, "transactions", transactions_batch(chain_name, blocks, &self.projection)?, last)
+            .await?;
+        self.commit(chain_name, "blocks", blocks_batch(chain_name, blocks, &self.projection)?, last).await
+    }
+
+    /// Appends `batch` to the table, unless the table's last commit was this batch's, i.e. the
+    /// batch was redelivered after its commit. The table is created by its first commit.
+    async fn commit(&self, chain_name: &str, table: &str, batch: RecordBatch, last: u64) -> Result<()> {
+        let uri = self.table_uri(chain_name, table);
+        let ops = DeltaOps::try_from_uri(&uri).await.with_context(|| format!("Failed to open Delta table {}", uri))?;
+        let versions = ops.0.get_app_transaction_version();
+        let version = |app_id: &str| versions.get(app_id).map(|txn| txn.version);
+        if version(chain_name) == Some(last as i64) {
+            info!("Delta table {} already has the batch ending at block {}, skipping it", uri, last);
+            return Ok(());
+        }
+        let high = version(&high_app_id(chain_name)).unwrap_or(-1).max(last as i64);
+
+        let commit_properties = CommitProperties::default()
+            .with_application_transaction(AppTransaction::new(chain_name, last as i64))
+            .with_application_transaction(AppTransaction::new(high_app_id(chain_name), high));
+        let written = ops
+            .write(vec![batch])
+            .with_save_mode(SaveMode::Append)
+            .with_commit_properties(commit_properties)
+            .await
+            .with_context(|| format!("Failed to commit to Delta table {}", uri))?;
+
+        let commits = self.commits.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.zorder_every.is_some_and(|every| commits % every == 0) {
+            self.spawn_zorder(written, uri);
+        }
+        Ok(())
+    }
+
+    /// Z-orders the table in the background. Its failure is only logged: the data is committed
+    /// either way, and the next run picks up the files this one left.
+    fn spawn_zorder(&self, table: DeltaTable, uri: String) {
+        if self.optimizing.swap(true, Ordering::AcqRel) {
+            info!("Z-order of a Delta table still running, skipping {}", uri);
+            return;
+        }
+        let optimizing = Arc::clone(&self.optimizing);
+        tokio::spawn(async move {
+            let result = DeltaOps(table).optimize().with_type(OptimizeType::ZOrder(vec!["block_number".to_string()])).await;
+            if let Err(e) = result {
+                warn!("Failed to z-order Delta table {}: {}", uri, e);
+            }
+            optimizing.store(false, Ordering::Release);
+        });
+    }
+}
+
+/// App id of the highest block committed for the chain.
+fn high_app_id(chain_name: &str) -> String {
+    format!("{}:high", chain_name)
+}
+
+fn logs_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("chain_name", DataType::Utf8, false),
+        Field::new("block_number", DataType::Int64, false),
+        Field::new("block_hash", DataType::Utf8, false),
+        Field::new("tx_hash", DataType::Utf8, false),
+        Field::new("tx_index", DataType::Int64, false),
+        Field::new("log_index", DataType::Int64, false),
+        Field::new("address", DataType::Utf8, false),
+        Field::new("topic0", DataType::Utf8, true),
+        Field::new("topic1", DataType::Utf8, true),
+        Field::new("topic2", DataType::Utf8, true),
+        Field::new("topic3", DataType::Utf8, true),
+        Field::new("data", DataType::Utf8, false),
+    ]))
+}
+
+/// The logs of `blocks`, `logs[i]` being those of `blocks[i]`.
+fn logs_batch(chain_name: &str, blocks: &[Block<Transaction>], logs: &[Vec<Log>]) -> Result<RecordBatch> {
+    let rows: Vec<(&Block<Transaction>, &Log)> =
+        blocks.iter().zip(logs).flat_map(|(block, logs)| logs.iter().map(move |log| (block, log))).collect();
+    let topic = |index: usize| -> ArrayRef {
+        Arc::new(StringArray::from(
+            rows.iter().map(|(_, log)| log.topics.get(index).map(|topic| format!("{:?}", topic))).collect::<Vec<_>>(),
+        ))
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|_| chain_name))),
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|(block, _)| block.number.unwrap_or_default().as_u64() as i64))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(block, _)| format!("{:?}", block.hash.unwrap_or_default())))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(_, log)| format!("{:?}", log.transaction_hash.unwrap_or_default())))),
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|(_, log)| log.transaction_index.unwrap_or_default().as_u64() as i64))),
+        Arc::new(Int64Array::from_iter_values(rows.iter().map(|(_, log)| log.log_index.unwrap_or_default().as_u64() as i64))),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(_, log)| format!("{:?}", log.address)))),
+        topic(0),
+        topic(1),
+        topic(2),
+        topic(3),
+        Arc::new(StringArray::from_iter_values(rows.iter().map(|(_, log)| log.data.to_string()))),
+    ];
+    Ok(RecordBatch::try_new(logs_schema(), columns)?)
+}
+
+#[async_trait]
+impl Sink for DeltaSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let mut buffer = self.buffer.lock().await;
+        buffer.push(block.clone());
+        if buffer.len() < self.blocks_per_commit {
+            return Ok(WriteOutcome { orphaned: Vec::new(), durable: false });
+        }
+
+        let blocks = std::mem::take(&mut *buffer);
+        self.commit_blocks(chain_name, &blocks).await?;
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: true })
+    }
+
+    async fn flush(&self, chain_name: &str) -> Result<Option<WriteOutcome>> {
+        let mut buffer = self.buffer.lock().await;
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+        let blocks = std::mem::take(&mut *buffer);
+        self.commit_blocks(chain_name, &blocks).await?;
+        Ok(Some(WriteOutcome { orphaned: Vec::new(), durable: true }))
+    }
+
+    async fn last_block(&self, chain_name: &str) -> Result<Option<u64>> {
+        let uri = self.table_uri(chain_name, "blocks");
+        let ops = DeltaOps::try_from_uri(&uri).await.with_context(|| format!("Failed to open Delta table {}", uri))?;
+        let last_block = ops.0.get_app_transaction_version().get(&high_app_id(chain_name)).map(|txn| txn.version as u64);
+        Ok(last_block)
+    }
+}
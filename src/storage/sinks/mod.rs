@@ -0,0 +1,187 @@
+pub mod circuit_breaker;
+pub mod clickhouse;
+pub mod delta;
+pub mod mongo;
+pub mod parquet;
+pub mod postgres;
+pub mod scylla;
+pub mod sqlite;
+
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use sqlx::PgPool;
+use std::collections::HashMap;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::storage::overflow::OverflowStore;
+use crate::storage::projection::ProjectionConfig;
+use crate::storage::sinks::clickhouse::{ClickHouseSink, ClickHouseSinkConfig};
+use crate::storage::sinks::delta::{DeltaSink, DeltaSinkConfig};
+use crate::storage::sinks::mongo::{MongoSink, MongoSinkConfig};
+use crate::storage::sinks::parquet::{ParquetSink, ParquetSinkConfig};
+use crate::storage::sinks::postgres::PostgresSink;
+use crate::storage::sinks::scylla::{ScyllaSink, ScyllaSinkConfig};
+use crate::storage::sinks::sqlite::{SqliteSink, SqliteSinkConfig};
+use crate::streams::consumers::hooks::OrphanedBlock;
+
+/// Result of handing a block to a sink.
+#[derive(Debug, Default)]
+pub struct WriteOutcome {
+    /// Blocks this write displaced from the canonical chain, for sinks that track canonicality.
+    pub orphaned: Vec<OrphanedBlock>,
+    /// Whether this and every earlier block are now durably stored. Consumers hold back their
+    /// acknowledgements until a write reports `true`.
+    pub durable: bool,
+}
+
+/// Where consumers store blocks and their transactions.
+#[async_trait]
+pub trait Sink: Send + Sync {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome>;
+
+    /// Stores only the block's transactions, leaving its block row to another writer. Consumers
+    /// of address-sharded streams use this for every shard but the first.
+    async fn write_transactions(&self, _chain_name: &str, _block: &Block<Transaction>) -> Result<WriteOutcome> {
+        Err(anyhow!("This sink can't store address-sharded streams"))
+    }
+
+    /// Stores only the block's header fields, apart from the block rows, for the `headers`
+    /// schema. `block` carries no transactions.
+    async fn write_header(&self, _chain_name: &str, _block: &Block<Transaction>) -> Result<WriteOutcome> {
+        Err(anyhow!("This sink can't store the headers schema"))
+    }
+
+    /// Highest block number stored for the chain, used to resume ingestion after a restart.
+    /// `None` if nothing is stored or the sink cannot tell.
+    async fn last_block(&self, _chain_name: &str) -> Result<Option<u64>> {
+        Ok(None)
+    }
+
+    /// Writes out whatever the sink has buffered, so every earlier write is durable, e.g. before
+    /// shutting down. Returns the outcome of the batch it wrote, `None` if there was none.
+    async fn flush(&self, _chain_name: &str) -> Result<Option<WriteOutcome>> {
+        Ok(None)
+    }
+
+    /// How long a consumer may leave blocks buffered in the sink before it flushes them, so a
+    /// quiet chain's blocks don't wait for a batch to fill. `None` to only flush full batches.
+    fn flush_interval(&self) -> Option<Duration> {
+        None
+    }
+
+    /// A copy of a buffering sink with a buffer of its own, for one consumer. `None` for sinks
+    /// that don't buffer, which every consumer shares.
+    fn with_own_buffer(&self) -> Option<Arc<dyn Sink>> {
+        None
+    }
+}
+
+/// The sink one consumer of a chain writes through. Each gets its own buffer, so a consumer's
+/// write never makes durable, or loses, blocks another consumer still holds unacknowledged.
+pub fn for_consumer(sink: &Arc<dyn Sink>) -> Arc<dyn Sink> {
+    sink.with_own_buffer().unwrap_or_else(|| Arc::clone(sink))
+}
+
+/// What a sink constructor gets to build a chain's sink.
+#[derive(Clone)]
+pub struct SinkContext {
+    pub chain_name: String,
+    pub pg_pool: Arc<PgPool>,
+    /// The `[sinks.<sink_type>]` table from the config, empty if absent.
+    pub options: toml::Table,
+    /// Columns the chain doesn't persist. Sinks leave them out of what they write.
+    pub projection: ProjectionConfig,
+    /// Where oversized calldata goes, if the chain has `overflow` configured.
+    pub overflow: Option<Arc<OverflowStore>>,
+    /// The chain's node, for sinks that fetch more than the consumed blocks carry, e.g. logs.
+    pub adapter: Arc<dyn BlockchainAdapter>,
+}
+
+type SinkFuture = Pin<Box<dyn Future<Output = Result<Arc<dyn Sink>>> + Send>>;
+type SinkConstructor = Arc<dyn Fn(SinkContext) -> SinkFuture + Send + Sync>;
+
+/// Maps `sink` config strings to sink constructors. Sinks from other crates are registered under
+/// `custom:<name>`.
+#[derive(Clone)]
+pub struct SinkRegistry {
+    constructors: HashMap<String, SinkConstructor>,
+}
+
+impl SinkRegistry {
+    /// A registry with no sink types.
+    pub fn empty() -> Self {
+        Self { constructors: HashMap::new() }
+    }
+
+    /// Registers `constructor` for `sink_type`, replacing any earlier registration.
+    pub fn register<F, Fut>(&mut self, sink_type: &str, constructor: F) -> &mut Self
+    where
+        F: Fn(SinkContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Arc<dyn Sink>>> + Send + 'static,
+    {
+        self.constructors.insert(
+            sink_type.to_string(),
+            Arc::new(move |context| Box::pin(constructor(context)) as SinkFuture),
+        );
+        self
+    }
+
+    pub fn contains(&self, sink_type: &str) -> bool {
+        self.constructors.contains_key(sink_type)
+    }
+
+    /// Builds a sink of `sink_type`, or returns `None` if the type is not registered.
+    pub async fn create(&self, sink_type: &str, context: SinkContext) -> Option<Result<Arc<dyn Sink>>> {
+        let constructor = self.constructors.get(sink_type)?;
+        Some(constructor(context).await)
+    }
+}
+
+impl Default for SinkRegistry {
+    /// A registry with the built-in `postgres`, `clickhouse`, `parquet`, `delta`, `mongodb`,
+    /// `scylla` and `sqlite` sinks.
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        registry.register("postgres", |context: SinkContext| async move {
+            let mut sink = PostgresSink::new(context.pg_pool).with_projection(context.projection);
+            if let Some(overflow) = context.overflow {
+                sink = sink.with_overflow(overflow);
+            }
+            Ok(Arc::new(sink) as Arc<dyn Sink>)
+        });
+        registry.register("clickhouse", |context: SinkContext| async move {
+            let config: ClickHouseSinkConfig = context.options.try_into().context("Invalid [sinks.clickhouse] config")?;
+            Ok(Arc::new(ClickHouseSink::new(&config).await?.with_projection(context.projection)) as Arc<dyn Sink>)
+        });
+        registry.register("parquet", |context: SinkContext| async move {
+            let config: ParquetSinkConfig = context.options.try_into().context("Invalid [sinks.parquet] config")?;
+            Ok(Arc::new(ParquetSink::new(&config).with_projection(context.projection)) as Arc<dyn Sink>)
+        });
+        registry.register("delta", |context: SinkContext| async move {
+            let config: DeltaSinkConfig = context.options.try_into().context("Invalid [sinks.delta] config")?;
+            let mut sink = DeltaSink::new(&config).with_projection(context.projection);
+            if config.logs {
+                sink = sink.with_logs(context.adapter);
+            }
+            Ok(Arc::new(sink) as Arc<dyn Sink>)
+        });
+        registry.register("mongodb", |context: SinkContext| async move {
+            let config: MongoSinkConfig = context.options.try_into().context("Invalid [sinks.mongodb] config")?;
+            Ok(Arc::new(MongoSink::new(&config).await?.with_projection(context.projection)) as Arc<dyn Sink>)
+        });
+        registry.register("scylla", |context: SinkContext| async move {
+            let config: ScyllaSinkConfig = context.options.try_into().context("Invalid [sinks.scylla] config")?;
+            Ok(Arc::new(ScyllaSink::new(&config).await?.with_projection(context.projection)) as Arc<dyn Sink>)
+        });
+        registry.register("sqlite", |context: SinkContext| async move {
+            let config: SqliteSinkConfig = context.options.try_into().context("Invalid [sinks.sqlite] config")?;
+            Ok(Arc::new(SqliteSink::new(&config).await?.with_projection(context.projection)) as Arc<dyn Sink>)
+        });
+        registry
+    }
+}
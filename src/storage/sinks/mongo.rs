@@ -0,0 +1,112 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use mongodb::bson::{self, doc, Bson, Document};
+use mongodb::{Client, Collection, IndexModel};
+use serde::Deserialize;
+use std::env;
+
+use crate::storage::projection::ProjectionConfig;
+use crate::storage::sinks::{Sink, WriteOutcome};
+
+#[derive(Debug, Deserialize)]
+pub struct MongoSinkConfig {
+    pub uri: String, // env var holding the connection string, e.g. mongodb://localhost:27017
+    #[serde(default = "default_database")]
+    pub database: String,
+    #[serde(default = "default_collection")]
+    pub collection: String,
+}
+
+fn default_database() -> String {
+    "ingestion".to_string()
+}
+
+fn default_collection() -> String {
+    "blocks".to_string()
+}
+
+/// Stores each block as one document with its transactions embedded, as the node returns them,
+/// upserted by `_id: {chain, number}`. A block that replaces a height after a reorg overwrites
+/// the document, so the collection only holds the latest block seen at each height.
+pub struct MongoSink {
+    collection: Collection<Document>,
+    projection: ProjectionConfig,
+}
+
+impl MongoSink {
+    /// Connects and creates the `(chain_name, number)` index if needed.
+    pub async fn new(config: &MongoSinkConfig) -> Result<Self> {
+        let uri = env::var(&config.uri)
+            .with_context(|| format!("Failed to get MongoDB URI from environment for key `{}`", config.uri))?;
+        let client = Client::with_uri_str(&uri).await.context("Failed to connect to MongoDB")?;
+        let collection = client.database(&config.database).collection::<Document>(&config.collection);
+        collection
+            .create_index(IndexModel::builder().keys(doc! { "chain_name": 1, "number": -1 }).build())
+            .await
+            .context("Failed to create MongoDB index")?;
+        Ok(Self { collection, projection: ProjectionConfig::default() })
+    }
+
+    /// Stores null instead of the fields the projection excludes.
+    pub fn with_projection(mut self, projection: ProjectionConfig) -> Self {
+        self.projection = projection;
+        self
+    }
+}
+
+/// Fields of the block and transaction documents behind each projectable column.
+const PROJECTED_FIELDS: [(&str, &str, &str); 8] = [
+    ("blocks", "miner", "miner"),
+    ("blocks", "difficulty", "difficulty"),
+    ("blocks", "total_difficulty", "totalDifficulty"),
+    ("blocks", "receipts_root", "receiptsRoot"),
+    ("blocks", "transactions", "transactions"),
+    ("transactions", "gas_price", "gasPrice"),
+    ("transactions", "gas", "gas"),
+    ("transactions", "input", "input"),
+];
+
+/// The document `block` is stored as, keyed by `_id: {chain, number}`, with the fields
+/// `projection` excludes set to null.
+pub fn block_document(chain_name: &str, block: &Block<Transaction>, projection: &ProjectionConfig) -> Result<Document> {
+    let number = block.number.unwrap_or_default().as_u64() as i64;
+    let mut document = bson::to_document(block)?;
+    for (table, column, field) in PROJECTED_FIELDS {
+        if projection.includes(table, column) {
+            continue;
+        }
+        if table == "blocks" {
+            document.insert(field, Bson::Null);
+        } else if let Ok(transactions) = document.get_array_mut("transactions") {
+            for transaction in transactions.iter_mut().filter_map(Bson::as_document_mut) {
+                transaction.insert(field, Bson::Null);
+            }
+        }
+    }
+    // Quantities are hex strings in the block; the number is also kept as an integer to sort on.
+    document.insert("_id", doc! { "chain": chain_name, "number": number });
+    document.insert("chain_name", chain_name);
+    document.insert("number", number);
+    Ok(document)
+}
+
+#[async_trait]
+impl Sink for MongoSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let document = block_document(chain_name, block, &self.projection)?;
+        let id = document.get("_id").cloned().unwrap_or(Bson::Null);
+        self.collection.replace_one(doc! { "_id": id }, document).upsert(true).await?;
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: true })
+    }
+
+    async fn last_block(&self, chain_name: &str) -> Result<Option<u64>> {
+        let last = self
+            .collection
+            .find_one(doc! { "chain_name": chain_name })
+            .sort(doc! { "number": -1 })
+            .projection(doc! { "number": 1 })
+            .await?;
+        Ok(last.and_then(|document| document.get_i64("number").ok()).map(|number| number as u64))
+    }
+}
@@ -0,0 +1,178 @@
+use anyhow::{Context, Result};
+use arrow::array::{ArrayRef, BooleanArray, Int64Array, StringArray};
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+use crate::storage::projection::ProjectionConfig;
+use crate::storage::sinks::{Sink, WriteOutcome};
+use crate::storage::snapshot::{blocks_schema, transactions_schema, write_parquet};
+use crate::streams::sharding;
+
+#[derive(Debug, Deserialize)]
+pub struct ParquetSinkConfig {
+    pub dir: PathBuf,
+    #[serde(default = "default_blocks_per_file")]
+    pub blocks_per_file: usize,
+}
+
+fn default_blocks_per_file() -> usize {
+    1000
+}
+
+/// Buffers blocks and writes them as zstd-compressed Parquet files under `{dir}/{chain}/`, using
+/// the same columns as snapshot bundles. Blocks are only acknowledged once their file is written.
+/// Each consumer buffers on its own, so a file holds the blocks of one consumer.
+pub struct ParquetSink {
+    dir: PathBuf,
+    blocks_per_file: usize,
+    projection: ProjectionConfig,
+    buffer: Mutex<Vec<Block<Transaction>>>,
+}
+
+impl ParquetSink {
+    pub fn new(config: &ParquetSinkConfig) -> Self {
+        Self {
+            dir: config.dir.clone(),
+            blocks_per_file: config.blocks_per_file.max(1),
+            projection: ProjectionConfig::default(),
+            buffer: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Writes nulls instead of the columns the projection excludes.
+    pub fn with_projection(mut self, projection: ProjectionConfig) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    fn write_files(&self, chain_name: &str, blocks: &[Block<Transaction>]) -> Result<()> {
+        let dir = self.dir.join(chain_name);
+        std::fs::create_dir_all(&dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+
+        let numbers = blocks.iter().map(|block| block.number.unwrap_or_default().as_u64());
+        let first = numbers.clone().min().unwrap_or_default();
+        let last = numbers.max().unwrap_or_default();
+        // Realtime and backfill consumers share the sink, so ranges alone may collide.
+        let written_at = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_millis()).unwrap_or_default();
+
+        write_parquet(
+            &dir.join(format!("blocks-{}-{}-{}.parquet", first, last, written_at)),
+            &blocks_batch(chain_name, blocks, &self.projection)?,
+        )?;
+        write_parquet(
+            &dir.join(format!("transactions-{}-{}-{}.parquet", first, last, written_at)),
+            &transactions_batch(chain_name, blocks, &self.projection)?,
+        )?;
+        Ok(())
+    }
+}
+
+pub(super) fn blocks_batch(chain_name: &str, blocks: &[Block<Transaction>], projection: &ProjectionConfig) -> Result<RecordBatch> {
+    let project = |column: &str, value: fn(&Block<Transaction>) -> String| -> ArrayRef {
+        Arc::new(StringArray::from(blocks.iter().map(|b| projection.project("blocks", column, || value(b))).collect::<Vec<_>>()))
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(Int64Array::from_iter_values(blocks.iter().map(|b| b.number.unwrap_or_default().as_u64() as i64))),
+        Arc::new(StringArray::from_iter_values(blocks.iter().map(|_| chain_name))),
+        Arc::new(StringArray::from_iter_values(blocks.iter().map(|b| format!("{:?}", b.hash.unwrap_or_default())))),
+        Arc::new(StringArray::from_iter_values(blocks.iter().map(|b| format!("{:?}", b.parent_hash)))),
+        Arc::new(Int64Array::from_iter_values(blocks.iter().map(|b| b.timestamp.as_u64() as i64))),
+        project("miner", |b| format!("{:?}", b.author.unwrap_or_default())),
+        project("difficulty", |b| b.difficulty.to_string()),
+        project("total_difficulty", |b| b.total_difficulty.unwrap_or_default().to_string()),
+        Arc::new(Int64Array::from_iter_values(blocks.iter().map(|b| b.gas_used.as_u64() as i64))),
+        Arc::new(Int64Array::from_iter_values(blocks.iter().map(|b| b.gas_limit.as_u64() as i64))),
+        Arc::new(Int64Array::from_iter_values(blocks.iter().map(|b| b.size.unwrap_or_default().as_u64() as i64))),
+        project("receipts_root", |b| format!("{:?}", b.receipts_root)),
+        Arc::new(StringArray::from(
+            blocks
+                .iter()
+                .map(|b| projection.project("blocks", "transactions", || serde_json::to_string(&b.transactions)).transpose())
+                .collect::<Result<Vec<_>, _>>()?,
+        )),
+        Arc::new(Int64Array::from_iter_values(blocks.iter().map(|b| sharding::tx_count(b) as i64))),
+        // Every block is written as received; readers resolve reorgs by parent hash.
+        Arc::new(BooleanArray::from(vec![true; blocks.len()])),
+    ];
+    Ok(RecordBatch::try_new(blocks_schema(), columns)?)
+}
+
+pub(super) fn transactions_batch(chain_name: &str, blocks: &[Block<Transaction>], projection: &ProjectionConfig) -> Result<RecordBatch> {
+    let transactions: Vec<&Transaction> = blocks.iter().flat_map(|b| &b.transactions).collect();
+    let project = |column: &str, value: fn(&Transaction) -> String| -> ArrayRef {
+        Arc::new(StringArray::from(transactions.iter().map(|t| projection.project("transactions", column, || value(t))).collect::<Vec<_>>()))
+    };
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(transactions.iter().map(|_| chain_name))),
+        Arc::new(Int64Array::from_iter_values(transactions.iter().map(|t| t.block_number.unwrap_or_default().as_u64() as i64))),
+        Arc::new(StringArray::from_iter_values(transactions.iter().map(|t| format!("{:?}", t.hash)))),
+        Arc::new(StringArray::from_iter_values(transactions.iter().map(|t| format!("{:?}", t.from)))),
+        Arc::new(StringArray::from(transactions.iter().map(|t| t.to.map(|to| format!("{:?}", to))).collect::<Vec<_>>())),
+        Arc::new(StringArray::from_iter_values(transactions.iter().map(|t| t.value.to_string()))),
+        project("gas_price", |t| t.gas_price.unwrap_or_default().to_string()),
+        project("gas", |t| t.gas.to_string()),
+        project("input", |t| t.input.to_string()),
+        Arc::new(Int64Array::from_iter_values(transactions.iter().map(|t| t.nonce.as_u64() as i64))),
+        Arc::new(BooleanArray::from(vec![true; transactions.len()])),
+    ];
+    Ok(RecordBatch::try_new(transactions_schema(), columns)?)
+}
+
+#[async_trait]
+impl Sink for ParquetSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let mut buffer = self.buffer.lock().await;
+        buffer.push(block.clone());
+        if buffer.len() < self.blocks_per_file {
+            return Ok(WriteOutcome { orphaned: Vec::new(), durable: false });
+        }
+
+        let blocks = std::mem::take(&mut *buffer);
+        self.write_files(chain_name, &blocks)?;
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: true })
+    }
+
+    async fn flush(&self, chain_name: &str) -> Result<Option<WriteOutcome>> {
+        let mut buffer = self.buffer.lock().await;
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+        let blocks = std::mem::take(&mut *buffer);
+        self.write_files(chain_name, &blocks)?;
+        Ok(Some(WriteOutcome { orphaned: Vec::new(), durable: true }))
+    }
+
+    fn with_own_buffer(&self) -> Option<Arc<dyn Sink>> {
+        Some(Arc::new(Self {
+            dir: self.dir.clone(),
+            blocks_per_file: self.blocks_per_file,
+            projection: self.projection.clone(),
+            buffer: Mutex::new(Vec::new()),
+        }))
+    }
+
+    async fn last_block(&self, chain_name: &str) -> Result<Option<u64>> {
+        let dir = self.dir.join(chain_name);
+        if !dir.is_dir() {
+            return Ok(None);
+        }
+        // File names are `blocks-{first}-{last}-{written_at}.parquet`.
+        let mut last_block = None;
+        for entry in std::fs::read_dir(&dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            let Some(range) = name.strip_prefix("blocks-").and_then(|rest| rest.strip_suffix(".parquet")) else {
+                continue;
+            };
+            if let Some(Ok(last)) = range.split('-').nth(1).map(str::parse::<u64>) {
+                last_block = last_block.max(Some(last));
+            }
+        }
+        Ok(last_block)
+    }
+}
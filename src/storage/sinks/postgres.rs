@@ -0,0 +1,294 @@
+use anyhow::Result;
+use futures_util::future::try_join_all;
+use async_trait::async_trait;
+use log::error;
+use sqlx::{PgPool, Postgres, Row};
+use std::sync::Arc;
+use ethers::types::{Block, Transaction};
+
+use crate::storage::overflow::OverflowStore;
+use crate::storage::projection::ProjectionConfig;
+use crate::storage::sinks::{Sink, WriteOutcome};
+use crate::streams::consumers::hooks::OrphanedBlock;
+use crate::streams::sharding;
+
+/// Writes blocks and transactions to the Postgres `blocks` and `transactions` tables, tracking
+/// which block is canonical at each height.
+pub struct PostgresSink {
+    pg_pool: Arc<PgPool>,
+    projection: ProjectionConfig,
+    overflow: Option<Arc<OverflowStore>>,
+}
+
+impl PostgresSink {
+    pub fn new(pg_pool: Arc<PgPool>) -> Self {
+        Self { pg_pool, projection: ProjectionConfig::default(), overflow: None }
+    }
+
+    /// Writes NULL instead of the columns the projection excludes.
+    pub fn with_projection(mut self, projection: ProjectionConfig) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Moves oversized calldata to `overflow` and stores its URI in `input_uri` instead.
+    pub fn with_overflow(mut self, overflow: Arc<OverflowStore>) -> Self {
+        self.overflow = Some(overflow);
+        self
+    }
+
+    /// Overflow URIs of the block's transaction inputs, in transaction order; `None` for inputs
+    /// stored inline.
+    async fn offload_inputs(&self, block: &Block<Transaction>) -> Result<Vec<Option<String>>> {
+        match &self.overflow {
+            Some(overflow) if self.projection.includes("transactions", "input") => {
+                try_join_all(block.transactions.iter().map(|transaction| {
+                    overflow.offload("calldata", &format!("{:?}", transaction.hash), &transaction.input)
+                }))
+                .await
+            }
+            _ => Ok(vec![None; block.transactions.len()]),
+        }
+    }
+
+    /// Inserts all of a block's transactions with one statement, binding each column as an array.
+    async fn insert_transactions(
+        &self,
+        db_tx: &mut sqlx::Transaction<'_, Postgres>,
+        chain_name: &str,
+        block: &Block<Transaction>,
+        input_uris: Vec<Option<String>>,
+    ) -> Result<()> {
+        if block.transactions.is_empty() {
+            return Ok(());
+        }
+        let count = block.transactions.len();
+        let mut block_numbers = Vec::with_capacity(count);
+        let mut block_hashes = Vec::with_capacity(count);
+        let mut hashes = Vec::with_capacity(count);
+        let mut from_addresses = Vec::with_capacity(count);
+        // Empty for contract creations, turned back into NULL by the query.
+        let mut to_addresses = Vec::with_capacity(count);
+        let mut values = Vec::with_capacity(count);
+        let mut gas_prices = Vec::with_capacity(count);
+        let mut gas = Vec::with_capacity(count);
+        let mut inputs = Vec::with_capacity(count);
+        let mut nonces = Vec::with_capacity(count);
+        let keep_gas_price = self.projection.includes("transactions", "gas_price");
+        let keep_gas = self.projection.includes("transactions", "gas");
+        let keep_input = self.projection.includes("transactions", "input");
+        for (transaction, input_uri) in block.transactions.iter().zip(&input_uris) {
+            block_numbers.push(transaction.block_number.unwrap_or_default().as_u64() as i64);
+            block_hashes.push(format!("{:?}", transaction.block_hash.or(block.hash).unwrap_or_default()));
+            hashes.push(format!("{:?}", transaction.hash));
+            from_addresses.push(format!("{:?}", transaction.from));
+            to_addresses.push(transaction.to.map(|to| format!("{:?}", to)).unwrap_or_default());
+            values.push(transaction.value.to_string());
+            gas_prices.push(keep_gas_price.then(|| transaction.gas_price.unwrap_or_default().to_string()));
+            gas.push(keep_gas.then(|| transaction.gas.to_string()));
+            inputs.push((keep_input && input_uri.is_none()).then(|| transaction.input.to_string()));
+            nonces.push(transaction.nonce.as_u64() as i64);
+        }
+
+        sqlx::query(
+            "INSERT INTO transactions (block_number, chain_name, tx_hash, from_address, to_address, value, gas_price, gas, input, input_uri, nonce, block_hash)
+            SELECT t.block_number, $2, t.tx_hash, t.from_address, NULLIF(t.to_address, ''), t.value, t.gas_price, t.gas, t.input, t.input_uri, t.nonce, t.block_hash
+            FROM UNNEST($1::bigint[], $3::text[], $4::text[], $5::text[], $6::text[], $7::text[], $8::text[], $9::text[], $10::text[], $11::bigint[], $12::text[])
+                AS t(block_number, tx_hash, from_address, to_address, value, gas_price, gas, input, input_uri, nonce, block_hash)",
+        )
+        .bind(block_numbers)
+        .bind(chain_name)
+        .bind(hashes)
+        .bind(from_addresses)
+        .bind(to_addresses)
+        .bind(values)
+        .bind(gas_prices)
+        .bind(gas)
+        .bind(inputs)
+        .bind(input_uris)
+        .bind(nonces)
+        .bind(block_hashes)
+        .execute(&mut *db_tx)
+        .await
+        .map_err(|e: sqlx::Error| {
+            error!("Failed to insert transaction data into PostgreSQL: {}", e);
+            anyhow::anyhow!(e)
+        })?;
+
+        Ok(())
+    }
+
+    /// Inserts a block as the canonical block at its height. Any other canonical block already stored
+    /// at that height (and its transactions) is marked orphaned and returned. A block stored before,
+    /// e.g. written by another schema's consumer or orphaned and now canonical again, is kept.
+    async fn insert_block_data(
+        &self,
+        db_tx: &mut sqlx::Transaction<'_, Postgres>,
+        chain_name: &str,
+        block: &Block<Transaction>,
+    ) -> Result<Vec<OrphanedBlock>> {
+        let block_number_i64 = block.number.unwrap_or_default().as_u64() as i64;
+        let gas_used_i64 = block.gas_used.as_u64() as i64;
+        let gas_limit_i64 = block.gas_limit.as_u64() as i64;
+        let size_i64 = block.size.unwrap_or_default().as_u64() as i64;
+        let timestamp_i64 = block.timestamp.as_u64() as i64;
+        let timestamp: PrimitiveDateTime = PrimitiveDateTime::from_unix_timestamp(timestamp_i64).unwrap();
+        // Shard 0 of an address-sharded stream only stores some of the transactions.
+        let tx_count_i64 = sharding::tx_count(block) as i64;
+        // Serialized straight to text: building a `Value` tree allocates per field.
+        let transactions_json = self
+            .projection
+            .project("blocks", "transactions", || serde_json::value::to_raw_value(&block.transactions))
+            .transpose()?;
+
+        let block_hash = format!("{:?}", block.hash.unwrap_or_default());
+
+        let orphaned: Vec<OrphanedBlock> = sqlx::query!(
+            "UPDATE blocks SET canonical = FALSE WHERE chain_name = $1 AND block_number = $2 AND hash <> $3 AND canonical RETURNING hash",
+            chain_name,
+            block_number_i64,
+            block_hash
+        )
+        .fetch_all(&mut *db_tx)
+        .await?
+        .into_iter()
+        .map(|row| OrphanedBlock {
+            block_number: block_number_i64,
+            hash: row.hash,
+            replaced_by: block_hash.clone(),
+        })
+        .collect();
+
+        // By block, not height, so transactions other shards stored for this block stay
+        // canonical. Rows without a block hash predate the column and go by height.
+        if !orphaned.is_empty() {
+            sqlx::query!(
+                "UPDATE transactions SET canonical = FALSE WHERE chain_name = $1 AND block_number = $2 AND block_hash IS DISTINCT FROM $3",
+                chain_name,
+                block_number_i64,
+                block_hash
+            )
+            .execute(&mut *db_tx)
+            .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO blocks (block_number, chain_name, hash, parent_hash, timestamp, miner, difficulty, total_difficulty, gas_used, gas_limit, size, receipts_root, tx_count, transactions) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
+            ON CONFLICT (block_number, hash) DO UPDATE SET canonical = TRUE",
+        )
+        .bind(block_number_i64)
+        .bind(chain_name)
+        .bind(&block_hash)
+        .bind(format!("{:?}", block.parent_hash))
+        .bind(timestamp)
+        .bind(self.projection.project("blocks", "miner", || format!("{:?}", block.author.unwrap_or_default())))
+        .bind(self.projection.project("blocks", "difficulty", || block.difficulty.to_string()))
+        .bind(self.projection.project("blocks", "total_difficulty", || block.total_difficulty.unwrap_or_default().to_string()))
+        .bind(gas_used_i64)
+        .bind(gas_limit_i64)
+        .bind(size_i64)
+        .bind(self.projection.project("blocks", "receipts_root", || format!("{:?}", block.receipts_root)))
+        .bind(tx_count_i64)
+        .bind(transactions_json.as_deref())
+        .execute(&mut *db_tx)
+        .await
+        .map_err(|e: sqlx::Error| {
+            error!("Failed to insert block data into PostgreSQL: {}", e);
+            anyhow::anyhow!(e)
+        })?;
+
+        Ok(orphaned)
+    }
+}
+
+#[async_trait]
+impl Sink for PostgresSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        // Overflow objects are written before the rows that reference them.
+        let input_uris = self.offload_inputs(block).await?;
+
+        // One database transaction per block. The block goes first so a reorg orphans the old
+        // transactions before the new ones land.
+        let mut db_tx = self.pg_pool.begin().await?;
+        let orphaned = self.insert_block_data(&mut db_tx, chain_name, block).await?;
+        self.insert_transactions(&mut db_tx, chain_name, block, input_uris).await?;
+        db_tx.commit().await?;
+
+        Ok(WriteOutcome { orphaned, durable: true })
+    }
+
+    async fn write_transactions(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let input_uris = self.offload_inputs(block).await?;
+        let mut db_tx = self.pg_pool.begin().await?;
+        self.insert_transactions(&mut db_tx, chain_name, block, input_uris).await?;
+        // Shard 0 may have orphaned the block already, before this shard's part arrived.
+        sqlx::query(
+            "UPDATE transactions SET canonical = FALSE WHERE chain_name = $1 AND block_hash = $2
+            AND EXISTS (SELECT 1 FROM blocks WHERE chain_name = $1 AND hash = $2 AND NOT canonical)",
+        )
+        .bind(chain_name)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .execute(&mut db_tx)
+        .await?;
+        db_tx.commit().await?;
+
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: true })
+    }
+
+    async fn write_header(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let block_hash = format!("{:?}", block.hash.unwrap_or_default());
+        let timestamp = PrimitiveDateTime::from_unix_timestamp(block.timestamp.as_u64() as i64).unwrap();
+
+        let mut db_tx = self.pg_pool.begin().await?;
+        let orphaned: Vec<OrphanedBlock> = sqlx::query(
+            "UPDATE block_headers SET canonical = FALSE WHERE chain_name = $1 AND block_number = $2 AND hash <> $3 AND canonical RETURNING hash",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(&block_hash)
+        .fetch_all(&mut db_tx)
+        .await?
+        .into_iter()
+        .map(|row| -> Result<OrphanedBlock> {
+            Ok(OrphanedBlock { block_number, hash: row.try_get("hash")?, replaced_by: block_hash.clone() })
+        })
+        .collect::<Result<_>>()?;
+
+        // A redelivered header becomes canonical again rather than failing on its key.
+        sqlx::query(
+            "INSERT INTO block_headers (chain_name, block_number, hash, parent_hash, timestamp, miner, gas_used, gas_limit, base_fee_per_gas)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+            ON CONFLICT (chain_name, hash) DO UPDATE SET canonical = TRUE",
+        )
+        .bind(chain_name)
+        .bind(block_number)
+        .bind(&block_hash)
+        .bind(format!("{:?}", block.parent_hash))
+        .bind(timestamp)
+        .bind(block.author.map(|author| format!("{:?}", author)))
+        .bind(block.gas_used.as_u64() as i64)
+        .bind(block.gas_limit.as_u64() as i64)
+        .bind(block.base_fee_per_gas.map(|fee| fee.to_string()))
+        .execute(&mut db_tx)
+        .await?;
+        db_tx.commit().await?;
+
+        Ok(WriteOutcome { orphaned, durable: true })
+    }
+
+    async fn last_block(&self, chain_name: &str) -> Result<Option<u64>> {
+        // Chains that only ingest the `headers` schema have no `blocks` rows.
+        let row = sqlx::query(
+            "SELECT COALESCE(
+                (SELECT MAX(block_number) FROM blocks WHERE chain_name = $1 AND canonical),
+                (SELECT MAX(block_number) FROM block_headers WHERE chain_name = $1 AND canonical)
+            ) AS last_block",
+        )
+        .bind(chain_name)
+        .fetch_one(self.pg_pool.as_ref())
+        .await?;
+        let last_block: Option<i64> = row.try_get("last_block")?;
+        Ok(last_block.map(|n| n as u64))
+    }
+}
@@ -0,0 +1,365 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use futures_util::future::try_join_all;
+use futures_util::TryStreamExt;
+use scylla::batch::{Batch, BatchType};
+use scylla::prepared_statement::PreparedStatement;
+use scylla::{Session, SessionBuilder};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+
+use crate::storage::projection::ProjectionConfig;
+use crate::storage::sinks::{Sink, WriteOutcome};
+use crate::streams::sharding;
+
+/// Tables a Scylla sink can write.
+const SCYLLA_TABLES: [&str; 2] = ["blocks", "address_activity"];
+
+#[derive(Debug, Deserialize)]
+pub struct ScyllaSinkConfig {
+    pub nodes: String, // env var holding comma-separated contact points, e.g. 10.0.0.1:9042,10.0.0.2:9042
+    #[serde(default = "default_keyspace")]
+    pub keyspace: String,
+    #[serde(default = "default_replication_factor")]
+    pub replication_factor: u32,
+    pub user: Option<String>,     // env var holding the user name
+    pub password: Option<String>, // env var holding the password
+    /// Blocks per `blocks` and `transactions` partition.
+    #[serde(default = "default_block_bucket_size")]
+    pub block_bucket_size: u64,
+    /// `blocks` (blocks and transactions by block bucket) and/or `address_activity`
+    /// (transactions by sender and recipient).
+    #[serde(default = "default_tables")]
+    pub tables: Vec<String>,
+    /// Statements per batch. Each batch only holds rows of one partition.
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+}
+
+fn default_keyspace() -> String {
+    "ingestion".to_string()
+}
+
+fn default_replication_factor() -> u32 {
+    3
+}
+
+fn default_block_bucket_size() -> u64 {
+    10_000
+}
+
+fn default_tables() -> Vec<String> {
+    vec!["blocks".to_string()]
+}
+
+fn default_batch_size() -> usize {
+    100
+}
+
+const CREATE_TABLES: [&str; 4] = [
+    "CREATE TABLE IF NOT EXISTS {ks}.blocks (
+        chain_name text, block_bucket bigint, block_number bigint, hash text, parent_hash text,
+        timestamp bigint, miner text, gas_used bigint, gas_limit bigint, tx_count int,
+        PRIMARY KEY ((chain_name, block_bucket), block_number, hash)
+    ) WITH CLUSTERING ORDER BY (block_number DESC, hash ASC)",
+    "CREATE TABLE IF NOT EXISTS {ks}.transactions (
+        chain_name text, block_bucket bigint, block_number bigint, block_hash text, tx_hash text,
+        from_address text, to_address text, value text, gas_price text, gas text, input text, nonce bigint,
+        PRIMARY KEY ((chain_name, block_bucket), block_number, block_hash, tx_hash)
+    ) WITH CLUSTERING ORDER BY (block_number DESC, block_hash ASC, tx_hash ASC)",
+    "CREATE TABLE IF NOT EXISTS {ks}.address_activity (
+        chain_name text, address text, block_number bigint, tx_hash text, counterparty text,
+        outgoing boolean, value text,
+        PRIMARY KEY ((chain_name, address), block_number, tx_hash, outgoing)
+    ) WITH CLUSTERING ORDER BY (block_number DESC, tx_hash ASC, outgoing ASC)",
+    // Written with the block number as the write timestamp, so the highest block stored wins
+    // whatever order blocks are written in, without a lightweight transaction per block.
+    "CREATE TABLE IF NOT EXISTS {ks}.chain_heads (
+        chain_name text PRIMARY KEY, last_block bigint
+    )",
+];
+
+type BlockValues = (String, i64, i64, String, String, i64, Option<String>, i64, i64, i32);
+type TransactionValues =
+    (String, i64, i64, String, String, String, Option<String>, String, Option<String>, Option<String>, Option<String>, i64);
+type ActivityValues = (String, String, i64, String, Option<String>, bool, String);
+
+/// Writes blocks to Cassandra or ScyllaDB through prepared statements. Rows are batched per
+/// partition, so each unlogged batch goes straight to the replicas owning its token. Blocks and
+/// their transactions are partitioned by `(chain_name, block_bucket)`; the optional
+/// `address_activity` table holds each transaction under its sender and its recipient. Like the
+/// ClickHouse sink, every block seen is kept and canonicality is not tracked. The highest block
+/// stored per chain is kept in `chain_heads`, so finding it doesn't scan the partitions.
+pub struct ScyllaSink {
+    session: Session,
+    keyspace: String,
+    block_bucket_size: u64,
+    batch_size: usize,
+    projection: ProjectionConfig,
+    insert_block: Option<PreparedStatement>,
+    update_head: Option<PreparedStatement>,
+    insert_transaction: Option<PreparedStatement>,
+    insert_activity: Option<PreparedStatement>,
+}
+
+impl ScyllaSink {
+    /// Connects, then creates the keyspace and the configured tables if needed.
+    pub async fn new(config: &ScyllaSinkConfig) -> Result<Self> {
+        if let Some(table) = config.tables.iter().find(|table| !SCYLLA_TABLES.contains(&table.as_str())) {
+            return Err(anyhow!("Unknown Scylla table `{}`; tables are {}", table, SCYLLA_TABLES.join(", ")));
+        }
+        let resolve = |key: &str| {
+            env::var(key).with_context(|| format!("Failed to get Scylla setting from environment for key `{}`", key))
+        };
+        let nodes = resolve(&config.nodes)?;
+        let mut builder = SessionBuilder::new().known_nodes(nodes.split(',').map(str::trim));
+        if let (Some(user), Some(password)) = (&config.user, &config.password) {
+            builder = builder.user(resolve(user)?, resolve(password)?);
+        }
+        let session = builder.build().await.context("Failed to connect to Scylla")?;
+
+        let keyspace = config.keyspace.clone();
+        session
+            .query_unpaged(
+                format!(
+                    "CREATE KEYSPACE IF NOT EXISTS {} WITH replication = {{'class': 'NetworkTopologyStrategy', 'replication_factor': {}}}",
+                    keyspace, config.replication_factor
+                ),
+                (),
+            )
+            .await?;
+
+        let blocks = config.tables.iter().any(|table| table == "blocks");
+        let activity = config.tables.iter().any(|table| table == "address_activity");
+        for (statement, wanted) in CREATE_TABLES.iter().zip([blocks, blocks, activity, blocks]) {
+            if wanted {
+                session.query_unpaged(statement.replace("{ks}", &keyspace), ()).await?;
+            }
+        }
+
+        let prepare = |wanted: bool, statement: String| {
+            let session = &session;
+            async move {
+                match wanted {
+                    true => Ok::<_, anyhow::Error>(Some(session.prepare(statement).await?)),
+                    false => Ok(None),
+                }
+            }
+        };
+        let insert_block = prepare(
+            blocks,
+            format!(
+                "INSERT INTO {}.blocks (chain_name, block_bucket, block_number, hash, parent_hash, timestamp, miner, gas_used, gas_limit, tx_count)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                keyspace
+            ),
+        )
+        .await?;
+        let insert_transaction = prepare(
+            blocks,
+            format!(
+                "INSERT INTO {}.transactions (chain_name, block_bucket, block_number, block_hash, tx_hash, from_address, to_address, value, gas_price, gas, input, nonce)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+                keyspace
+            ),
+        )
+        .await?;
+        let update_head = prepare(
+            blocks,
+            format!("INSERT INTO {}.chain_heads (chain_name, last_block) VALUES (?, ?) USING TIMESTAMP ?", keyspace),
+        )
+        .await?;
+        let insert_activity = prepare(
+            activity,
+            format!(
+                "INSERT INTO {}.address_activity (chain_name, address, block_number, tx_hash, counterparty, outgoing, value)
+                VALUES (?, ?, ?, ?, ?, ?, ?)",
+                keyspace
+            ),
+        )
+        .await?;
+
+        Ok(Self {
+            session,
+            keyspace,
+            block_bucket_size: config.block_bucket_size.max(1),
+            batch_size: config.batch_size.max(1),
+            projection: ProjectionConfig::default(),
+            insert_block,
+            update_head,
+            insert_transaction,
+            insert_activity,
+        })
+    }
+
+    /// Writes NULL instead of the columns the projection excludes.
+    pub fn with_projection(mut self, projection: ProjectionConfig) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    /// Sends `values` of one partition in unlogged batches of at most `batch_size` statements.
+    async fn batch<V: scylla::serialize::row::SerializeRow>(&self, statement: &PreparedStatement, values: Vec<V>) -> Result<()> {
+        let mut values = values.into_iter().peekable();
+        while values.peek().is_some() {
+            let chunk: Vec<V> = values.by_ref().take(self.batch_size).collect();
+            let mut batch = Batch::new(BatchType::Unlogged);
+            for _ in 0..chunk.len() {
+                batch.append_statement(statement.clone());
+            }
+            self.session.batch(&batch, chunk).await?;
+        }
+        Ok(())
+    }
+
+    fn transaction_values(&self, chain_name: &str, bucket: i64, block: &Block<Transaction>) -> Vec<TransactionValues> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let block_hash = format!("{:?}", block.hash.unwrap_or_default());
+        block
+            .transactions
+            .iter()
+            .map(|transaction| {
+                (
+                    chain_name.to_string(),
+                    bucket,
+                    block_number,
+                    block_hash.clone(),
+                    format!("{:?}", transaction.hash),
+                    format!("{:?}", transaction.from),
+                    transaction.to.map(|to| format!("{:?}", to)),
+                    transaction.value.to_string(),
+                    self.projection.project("transactions", "gas_price", || transaction.gas_price.unwrap_or_default().to_string()),
+                    self.projection.project("transactions", "gas", || transaction.gas.to_string()),
+                    self.projection.project("transactions", "input", || transaction.input.to_string()),
+                    transaction.nonce.as_u64() as i64,
+                )
+            })
+            .collect()
+    }
+
+    /// Activity rows grouped by address, i.e. by partition.
+    fn activity_values(&self, chain_name: &str, block: &Block<Transaction>) -> HashMap<String, Vec<ActivityValues>> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let mut by_address: HashMap<String, Vec<ActivityValues>> = HashMap::new();
+        for transaction in &block.transactions {
+            let tx_hash = format!("{:?}", transaction.hash);
+            let from = format!("{:?}", transaction.from);
+            let to = transaction.to.map(|to| format!("{:?}", to));
+            let value = transaction.value.to_string();
+            by_address.entry(from.clone()).or_default().push((
+                chain_name.to_string(),
+                from.clone(),
+                block_number,
+                tx_hash.clone(),
+                to.clone(),
+                true,
+                value.clone(),
+            ));
+            if let Some(to) = to {
+                by_address.entry(to.clone()).or_default().push((chain_name.to_string(), to, block_number, tx_hash, Some(from), false, value));
+            }
+        }
+        by_address
+    }
+
+    /// Transactions first, so a block row never exists without its transactions.
+    async fn write_rows(&self, chain_name: &str, block: &Block<Transaction>, with_block: bool) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64();
+        let bucket = (block_number / self.block_bucket_size) as i64;
+
+        if let Some(insert_activity) = &self.insert_activity {
+            try_join_all(
+                self.activity_values(chain_name, block)
+                    .into_values()
+                    .map(|values| self.batch(insert_activity, values)),
+            )
+            .await?;
+        }
+        if let Some(insert_transaction) = &self.insert_transaction {
+            self.batch(insert_transaction, self.transaction_values(chain_name, bucket, block)).await?;
+        }
+        if let (true, Some(insert_block)) = (with_block, &self.insert_block) {
+            let values: BlockValues = (
+                chain_name.to_string(),
+                bucket,
+                block_number as i64,
+                format!("{:?}", block.hash.unwrap_or_default()),
+                format!("{:?}", block.parent_hash),
+                block.timestamp.as_u64() as i64,
+                self.projection.project("blocks", "miner", || format!("{:?}", block.author.unwrap_or_default())),
+                block.gas_used.as_u64() as i64,
+                block.gas_limit.as_u64() as i64,
+                sharding::tx_count(block) as i32,
+            );
+            self.session.execute_unpaged(insert_block, values).await?;
+            if let Some(update_head) = &self.update_head {
+                self.record_head(update_head, chain_name, block_number as i64).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn record_head(&self, update_head: &PreparedStatement, chain_name: &str, block_number: i64) -> Result<()> {
+        self.session.execute_unpaged(update_head, (chain_name, block_number, block_number)).await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Sink for ScyllaSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.write_rows(chain_name, block, true).await?;
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: true })
+    }
+
+    async fn write_transactions(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.write_rows(chain_name, block, false).await?;
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: true })
+    }
+
+    async fn last_block(&self, chain_name: &str) -> Result<Option<u64>> {
+        let Some(update_head) = &self.update_head else {
+            return Ok(None);
+        };
+        let head = self
+            .session
+            .query_unpaged(format!("SELECT last_block FROM {}.chain_heads WHERE chain_name = ?", self.keyspace), (chain_name,))
+            .await?
+            .maybe_first_row_typed::<(i64,)>()?;
+        if let Some((last_block,)) = head {
+            return Ok(Some(last_block as u64));
+        }
+
+        // Blocks stored before `chain_heads` existed: found once from the highest bucket, paging
+        // through the partition keys, and recorded.
+        let mut buckets = self
+            .session
+            .query_iter(format!("SELECT DISTINCT chain_name, block_bucket FROM {}.blocks", self.keyspace), ())
+            .await?
+            .into_typed::<(String, i64)>();
+        let mut highest = None;
+        while let Some((chain, bucket)) = buckets.try_next().await? {
+            if chain == chain_name {
+                highest = highest.max(Some(bucket));
+            }
+        }
+        let Some(bucket) = highest else {
+            return Ok(None);
+        };
+        // Clustered by block number descending, so the first row is the highest.
+        let last = self
+            .session
+            .query_unpaged(
+                format!("SELECT block_number FROM {}.blocks WHERE chain_name = ? AND block_bucket = ? LIMIT 1", self.keyspace),
+                (chain_name, bucket),
+            )
+            .await?
+            .maybe_first_row_typed::<(i64,)>()?;
+        if let Some((number,)) = last {
+            self.record_head(update_head, chain_name, number).await?;
+        }
+        Ok(last.map(|(number,)| number as u64))
+    }
+}
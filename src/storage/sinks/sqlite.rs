@@ -0,0 +1,245 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use serde::Deserialize;
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous};
+use sqlx::{Row, Sqlite};
+use std::str::FromStr;
+use tokio::sync::Mutex;
+
+use crate::storage::projection::ProjectionConfig;
+use crate::storage::sinks::{Sink, WriteOutcome};
+use crate::streams::consumers::hooks::OrphanedBlock;
+use crate::streams::sharding;
+
+#[derive(Debug, Deserialize)]
+pub struct SqliteSinkConfig {
+    /// Database file, created if missing, e.g. `data/ingestion.db`.
+    pub path: String,
+    /// Blocks written per database transaction.
+    #[serde(default = "default_blocks_per_transaction")]
+    pub blocks_per_transaction: usize,
+}
+
+fn default_blocks_per_transaction() -> usize {
+    100
+}
+
+// The Postgres `blocks` and `transactions` columns, with timestamps as Unix seconds.
+const CREATE_TABLES: [&str; 4] = [
+    "CREATE TABLE IF NOT EXISTS blocks (
+        id INTEGER PRIMARY KEY,
+        block_number INTEGER NOT NULL,
+        chain_name TEXT NOT NULL,
+        hash TEXT NOT NULL,
+        parent_hash TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        miner TEXT,
+        difficulty TEXT,
+        total_difficulty TEXT,
+        gas_used INTEGER NOT NULL,
+        gas_limit INTEGER NOT NULL,
+        size INTEGER NOT NULL,
+        receipts_root TEXT,
+        tx_count INTEGER NOT NULL,
+        transactions TEXT,
+        canonical INTEGER NOT NULL DEFAULT 1,
+        UNIQUE (chain_name, hash)
+    )",
+    "CREATE UNIQUE INDEX IF NOT EXISTS blocks_canonical_number_idx ON blocks (chain_name, block_number) WHERE canonical",
+    "CREATE TABLE IF NOT EXISTS transactions (
+        id INTEGER PRIMARY KEY,
+        block_number INTEGER NOT NULL,
+        chain_name TEXT NOT NULL,
+        tx_hash TEXT NOT NULL,
+        from_address TEXT NOT NULL,
+        to_address TEXT,
+        value TEXT NOT NULL,
+        gas_price TEXT,
+        gas TEXT,
+        input TEXT,
+        nonce INTEGER NOT NULL,
+        canonical INTEGER NOT NULL DEFAULT 1,
+        UNIQUE (chain_name, block_number, tx_hash)
+    )",
+    "CREATE INDEX IF NOT EXISTS transactions_tx_hash_idx ON transactions (tx_hash)",
+];
+
+/// Writes blocks and transactions to a SQLite file with the Postgres table layout, tracking
+/// which block is canonical at each height. The database runs in WAL mode, and blocks are
+/// buffered and written `blocks_per_transaction` at a time in one database transaction; they
+/// are only acknowledged once it commits. Needs no infrastructure, for edge boxes and tests.
+pub struct SqliteSink {
+    pool: SqlitePool,
+    blocks_per_transaction: usize,
+    projection: ProjectionConfig,
+    buffer: Mutex<Vec<(String, Block<Transaction>)>>,
+}
+
+/// How the sink's connections to the database at `path` are opened. `Full` syncs the WAL on
+/// every commit: with `Normal` a power loss can undo commits whose blocks were already
+/// acknowledged to the broker.
+pub fn connect_options(path: &str) -> Result<SqliteConnectOptions> {
+    Ok(SqliteConnectOptions::from_str(&format!("sqlite://{}", path))?
+        .create_if_missing(true)
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Full))
+}
+
+impl SqliteSink {
+    /// Opens the database, creating it and its tables if needed.
+    pub async fn new(config: &SqliteSinkConfig) -> Result<Self> {
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(connect_options(&config.path)?)
+            .await
+            .with_context(|| format!("Failed to open SQLite database {}", config.path))?;
+        for statement in CREATE_TABLES {
+            sqlx::query(statement).execute(&pool).await?;
+        }
+        Ok(Self {
+            pool,
+            blocks_per_transaction: config.blocks_per_transaction.max(1),
+            projection: ProjectionConfig::default(),
+            buffer: Mutex::new(Vec::new()),
+        })
+    }
+
+    /// Writes NULL instead of the columns the projection excludes.
+    pub fn with_projection(mut self, projection: ProjectionConfig) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    async fn insert_transactions(&self, db_tx: &mut sqlx::Transaction<'_, Sqlite>, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        for transaction in &block.transactions {
+            // A redelivered transaction becomes canonical again rather than failing on its key.
+            sqlx::query(
+                "INSERT INTO transactions (block_number, chain_name, tx_hash, from_address, to_address, value, gas_price, gas, input, nonce)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+                ON CONFLICT (chain_name, block_number, tx_hash) DO UPDATE SET canonical = 1",
+            )
+            .bind(transaction.block_number.unwrap_or_default().as_u64() as i64)
+            .bind(chain_name)
+            .bind(format!("{:?}", transaction.hash))
+            .bind(format!("{:?}", transaction.from))
+            .bind(transaction.to.map(|to| format!("{:?}", to)))
+            .bind(transaction.value.to_string())
+            .bind(self.projection.project("transactions", "gas_price", || transaction.gas_price.unwrap_or_default().to_string()))
+            .bind(self.projection.project("transactions", "gas", || transaction.gas.to_string()))
+            .bind(self.projection.project("transactions", "input", || transaction.input.to_string()))
+            .bind(transaction.nonce.as_u64() as i64)
+            .execute(&mut *db_tx)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Inserts a batch of buffered blocks in one transaction.
+    async fn insert_blocks(&self, blocks: &[(String, Block<Transaction>)]) -> Result<Vec<OrphanedBlock>> {
+        // The block goes first so a reorg orphans the old transactions before the new ones land.
+        let mut db_tx = self.pool.begin().await?;
+        let mut orphaned = Vec::new();
+        for (chain_name, block) in blocks {
+            orphaned.extend(self.insert_block(&mut db_tx, chain_name, block).await?);
+            self.insert_transactions(&mut db_tx, chain_name, block).await?;
+        }
+        db_tx.commit().await?;
+        Ok(orphaned)
+    }
+
+    /// Inserts a block as the canonical block at its height, orphaning any other canonical block
+    /// there and its transactions.
+    async fn insert_block(&self, db_tx: &mut sqlx::Transaction<'_, Sqlite>, chain_name: &str, block: &Block<Transaction>) -> Result<Vec<OrphanedBlock>> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let block_hash = format!("{:?}", block.hash.unwrap_or_default());
+
+        let orphaned: Vec<OrphanedBlock> =
+            sqlx::query("SELECT hash FROM blocks WHERE chain_name = ? AND block_number = ? AND hash <> ? AND canonical")
+                .bind(chain_name)
+                .bind(block_number)
+                .bind(&block_hash)
+                .fetch_all(&mut *db_tx)
+                .await?
+                .into_iter()
+                .map(|row| -> Result<OrphanedBlock> {
+                    Ok(OrphanedBlock { block_number, hash: row.try_get("hash")?, replaced_by: block_hash.clone() })
+                })
+                .collect::<Result<_>>()?;
+        if !orphaned.is_empty() {
+            sqlx::query("UPDATE blocks SET canonical = 0 WHERE chain_name = ? AND block_number = ? AND hash <> ?")
+                .bind(chain_name)
+                .bind(block_number)
+                .bind(&block_hash)
+                .execute(&mut *db_tx)
+                .await?;
+            sqlx::query("UPDATE transactions SET canonical = 0 WHERE chain_name = ? AND block_number = ?")
+                .bind(chain_name)
+                .bind(block_number)
+                .execute(&mut *db_tx)
+                .await?;
+        }
+
+        let transactions_json = self
+            .projection
+            .project("blocks", "transactions", || serde_json::to_string(&block.transactions))
+            .transpose()?;
+        sqlx::query(
+            "INSERT INTO blocks (block_number, chain_name, hash, parent_hash, timestamp, miner, difficulty, total_difficulty, gas_used, gas_limit, size, receipts_root, tx_count, transactions)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (chain_name, hash) DO UPDATE SET canonical = 1",
+        )
+        .bind(block_number)
+        .bind(chain_name)
+        .bind(&block_hash)
+        .bind(format!("{:?}", block.parent_hash))
+        .bind(block.timestamp.as_u64() as i64)
+        .bind(self.projection.project("blocks", "miner", || format!("{:?}", block.author.unwrap_or_default())))
+        .bind(self.projection.project("blocks", "difficulty", || block.difficulty.to_string()))
+        .bind(self.projection.project("blocks", "total_difficulty", || block.total_difficulty.unwrap_or_default().to_string()))
+        .bind(block.gas_used.as_u64() as i64)
+        .bind(block.gas_limit.as_u64() as i64)
+        .bind(block.size.unwrap_or_default().as_u64() as i64)
+        .bind(self.projection.project("blocks", "receipts_root", || format!("{:?}", block.receipts_root)))
+        .bind(sharding::tx_count(block) as i64)
+        .bind(transactions_json)
+        .execute(&mut *db_tx)
+        .await?;
+
+        Ok(orphaned)
+    }
+}
+
+#[async_trait]
+impl Sink for SqliteSink {
+    async fn write_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let mut buffer = self.buffer.lock().await;
+        buffer.push((chain_name.to_string(), block.clone()));
+        if buffer.len() < self.blocks_per_transaction {
+            return Ok(WriteOutcome { orphaned: Vec::new(), durable: false });
+        }
+
+        let blocks = std::mem::take(&mut *buffer);
+        let orphaned = self.insert_blocks(&blocks).await?;
+        Ok(WriteOutcome { orphaned, durable: true })
+    }
+
+    async fn flush(&self, _chain_name: &str) -> Result<Option<WriteOutcome>> {
+        let mut buffer = self.buffer.lock().await;
+        if buffer.is_empty() {
+            return Ok(None);
+        }
+        let blocks = std::mem::take(&mut *buffer);
+        let orphaned = self.insert_blocks(&blocks).await?;
+        Ok(Some(WriteOutcome { orphaned, durable: true }))
+    }
+
+    async fn last_block(&self, chain_name: &str) -> Result<Option<u64>> {
+        let row = sqlx::query("SELECT MAX(block_number) AS last_block FROM blocks WHERE chain_name = ? AND canonical")
+            .bind(chain_name)
+            .fetch_one(&self.pool)
+            .await?;
+        let last_block: Option<i64> = row.try_get("last_block")?;
+        Ok(last_block.map(|n| n as u64))
+    }
+}
@@ -0,0 +1,334 @@
+use anyhow::{anyhow, Context, Result};
+use arrow::array::{ArrayRef, BooleanArray, BooleanBuilder, Int64Array, Int64Builder, StringArray, StringBuilder};
+use arrow::datatypes::{DataType, Field, Schema};
+use arrow::record_batch::RecordBatch;
+use log::info;
+use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+use parquet::arrow::ArrowWriter;
+use parquet::basic::{Compression, ZstdLevel};
+use parquet::file::properties::WriterProperties;
+use serde::{Deserialize, Serialize};
+use sqlx::postgres::PgRow;
+use sqlx::{PgPool, Row};
+use std::fs::{self, File};
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::storage::db::run_migrations;
+
+/// Number of blocks written per Parquet file.
+const CHUNK_BLOCKS: i64 = 10_000;
+const MANIFEST_FILE: &str = "manifest.json";
+
+/// Describes a snapshot bundle: one directory holding a manifest and per-chunk Parquet files.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotManifest {
+    pub chain_name: String,
+    pub start_block: i64,
+    pub end_block: i64,
+    pub chunks: Vec<SnapshotChunk>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SnapshotChunk {
+    pub start_block: i64,
+    pub end_block: i64,
+    pub blocks_file: String,
+    pub transactions_file: String,
+    pub block_rows: usize,
+    pub transaction_rows: usize,
+}
+
+pub(crate) fn blocks_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("block_number", DataType::Int64, false),
+        Field::new("chain_name", DataType::Utf8, false),
+        Field::new("hash", DataType::Utf8, false),
+        Field::new("parent_hash", DataType::Utf8, false),
+        Field::new("timestamp", DataType::Int64, false),
+        Field::new("miner", DataType::Utf8, true),
+        Field::new("difficulty", DataType::Utf8, true),
+        Field::new("total_difficulty", DataType::Utf8, true),
+        Field::new("gas_used", DataType::Int64, false),
+        Field::new("gas_limit", DataType::Int64, false),
+        Field::new("size", DataType::Int64, false),
+        Field::new("receipts_root", DataType::Utf8, true),
+        Field::new("transactions", DataType::Utf8, true),
+        Field::new("tx_count", DataType::Int64, false),
+        Field::new("canonical", DataType::Boolean, false),
+    ]))
+}
+
+pub(crate) fn transactions_schema() -> Arc<Schema> {
+    Arc::new(Schema::new(vec![
+        Field::new("chain_name", DataType::Utf8, false),
+        Field::new("block_number", DataType::Int64, false),
+        Field::new("tx_hash", DataType::Utf8, false),
+        Field::new("from_address", DataType::Utf8, false),
+        Field::new("to_address", DataType::Utf8, true),
+        Field::new("value", DataType::Utf8, false),
+        Field::new("gas_price", DataType::Utf8, true),
+        Field::new("gas", DataType::Utf8, true),
+        Field::new("input", DataType::Utf8, true),
+        Field::new("nonce", DataType::Int64, false),
+        Field::new("canonical", DataType::Boolean, false),
+    ]))
+}
+
+/// The snapshot's transactions: the sinks' columns plus the block each copy of a transaction is
+/// in, so a transaction's canonical and orphaned copies stay apart, and where overflowed
+/// calldata was stored instead of `input`.
+fn snapshot_transactions_schema() -> Arc<Schema> {
+    let mut fields: Vec<Field> = transactions_schema().fields().iter().map(|field| field.as_ref().clone()).collect();
+    fields.push(Field::new("block_hash", DataType::Utf8, true));
+    fields.push(Field::new("input_uri", DataType::Utf8, true));
+    Arc::new(Schema::new(fields))
+}
+
+fn writer_properties() -> Result<WriterProperties> {
+    Ok(WriterProperties::builder()
+        .set_compression(Compression::ZSTD(ZstdLevel::try_new(3)?))
+        .build())
+}
+
+pub(crate) fn write_parquet(path: &Path, batch: &RecordBatch) -> Result<()> {
+    let file = File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let mut writer = ArrowWriter::try_new(file, batch.schema(), Some(writer_properties()?))?;
+    writer.write(batch)?;
+    writer.close()?;
+    Ok(())
+}
+
+fn read_parquet(path: &Path) -> Result<Vec<RecordBatch>> {
+    let file = File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)?.build()?;
+    Ok(reader.collect::<Result<Vec<_>, _>>()?)
+}
+
+/// Converts Postgres rows into a record batch, reading each schema field from the column of the same name.
+fn rows_to_batch(schema: Arc<Schema>, rows: &[PgRow]) -> Result<RecordBatch> {
+    let mut columns: Vec<ArrayRef> = Vec::with_capacity(schema.fields().len());
+    for field in schema.fields() {
+        let column: ArrayRef = match field.data_type() {
+            DataType::Int64 => {
+                let mut builder = Int64Builder::with_capacity(rows.len());
+                for row in rows {
+                    builder.append_value(row.try_get::<i64, _>(field.name().as_str())?);
+                }
+                Arc::new(builder.finish())
+            }
+            DataType::Boolean => {
+                let mut builder = BooleanBuilder::with_capacity(rows.len());
+                for row in rows {
+                    builder.append_value(row.try_get::<bool, _>(field.name().as_str())?);
+                }
+                Arc::new(builder.finish())
+            }
+            _ => {
+                let mut builder = StringBuilder::new();
+                for row in rows {
+                    builder.append_option(row.try_get::<Option<String>, _>(field.name().as_str())?);
+                }
+                Arc::new(builder.finish())
+            }
+        };
+        columns.push(column);
+    }
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+/// Dumps `start_block..=end_block` of a chain into a snapshot bundle at `out_dir`.
+pub async fn export_snapshot(pg_pool: &PgPool, chain_name: &str, start_block: i64, end_block: i64, out_dir: &Path) -> Result<SnapshotManifest> {
+    fs::create_dir_all(out_dir).with_context(|| format!("Failed to create {}", out_dir.display()))?;
+
+    let mut chunks = Vec::new();
+    let mut chunk_start = start_block;
+    while chunk_start <= end_block {
+        let chunk_end = (chunk_start + CHUNK_BLOCKS - 1).min(end_block);
+
+        let blocks = export_blocks(pg_pool, chain_name, chunk_start, chunk_end).await?;
+        let transactions = export_transactions(pg_pool, chain_name, chunk_start, chunk_end).await?;
+
+        let blocks_file = format!("blocks-{}-{}.parquet", chunk_start, chunk_end);
+        let transactions_file = format!("transactions-{}-{}.parquet", chunk_start, chunk_end);
+        write_parquet(&out_dir.join(&blocks_file), &blocks)?;
+        write_parquet(&out_dir.join(&transactions_file), &transactions)?;
+
+        info!(
+            "Exported {} blocks {}..={} ({} blocks, {} transactions)",
+            chain_name, chunk_start, chunk_end, blocks.num_rows(), transactions.num_rows()
+        );
+        chunks.push(SnapshotChunk {
+            start_block: chunk_start,
+            end_block: chunk_end,
+            blocks_file,
+            transactions_file,
+            block_rows: blocks.num_rows(),
+            transaction_rows: transactions.num_rows(),
+        });
+        chunk_start = chunk_end + 1;
+    }
+
+    let manifest = SnapshotManifest {
+        chain_name: chain_name.to_string(),
+        start_block,
+        end_block,
+        chunks,
+    };
+    fs::write(out_dir.join(MANIFEST_FILE), serde_json::to_vec_pretty(&manifest)?)?;
+    Ok(manifest)
+}
+
+async fn export_blocks(pg_pool: &PgPool, chain_name: &str, start_block: i64, end_block: i64) -> Result<RecordBatch> {
+    let rows = sqlx::query(
+        "SELECT block_number, chain_name, hash, parent_hash, EXTRACT(EPOCH FROM timestamp)::bigint AS timestamp,
+            miner, difficulty, total_difficulty, gas_used, gas_limit, size, receipts_root,
+            transactions::text AS transactions, tx_count, canonical
+        FROM blocks
+        WHERE chain_name = $1 AND block_number BETWEEN $2 AND $3
+        ORDER BY block_number",
+    )
+    .bind(chain_name)
+    .bind(start_block)
+    .bind(end_block)
+    .fetch_all(pg_pool)
+    .await?;
+
+    rows_to_batch(blocks_schema(), &rows)
+}
+
+async fn export_transactions(pg_pool: &PgPool, chain_name: &str, start_block: i64, end_block: i64) -> Result<RecordBatch> {
+    let rows = sqlx::query(
+        "SELECT chain_name, block_number, tx_hash, from_address, to_address, value, gas_price, gas, input, nonce, canonical, block_hash, input_uri
+        FROM transactions
+        WHERE chain_name = $1 AND block_number BETWEEN $2 AND $3
+        ORDER BY block_number, id",
+    )
+    .bind(chain_name)
+    .bind(start_block)
+    .bind(end_block)
+    .fetch_all(pg_pool)
+    .await?;
+
+    rows_to_batch(snapshot_transactions_schema(), &rows)
+}
+
+fn string_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a StringArray> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+        .ok_or_else(|| anyhow!("Snapshot column `{}` is missing or not a string", name))
+}
+
+fn int64_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a Int64Array> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<Int64Array>())
+        .ok_or_else(|| anyhow!("Snapshot column `{}` is missing or not an int64", name))
+}
+
+fn bool_column<'a>(batch: &'a RecordBatch, name: &str) -> Result<&'a BooleanArray> {
+    batch
+        .column_by_name(name)
+        .and_then(|column| column.as_any().downcast_ref::<BooleanArray>())
+        .ok_or_else(|| anyhow!("Snapshot column `{}` is missing or not a boolean", name))
+}
+
+fn strings(column: &StringArray) -> Vec<Option<String>> {
+    column.iter().map(|value| value.map(str::to_string)).collect()
+}
+
+/// A string column bundles from before it was exported don't have, as NULLs.
+fn optional_strings(batch: &RecordBatch, name: &str) -> Result<Vec<Option<String>>> {
+    match batch.column_by_name(name) {
+        Some(_) => Ok(strings(string_column(batch, name)?)),
+        None => Ok(vec![None; batch.num_rows()]),
+    }
+}
+
+/// Restores a snapshot bundle from `dir` into the sink, running migrations first so a fresh
+/// database can be bootstrapped directly.
+pub async fn import_snapshot(pg_pool: &PgPool, dir: &Path) -> Result<SnapshotManifest> {
+    let manifest_bytes = fs::read(dir.join(MANIFEST_FILE))
+        .with_context(|| format!("Failed to read snapshot manifest in {}", dir.display()))?;
+    let manifest: SnapshotManifest = serde_json::from_slice(&manifest_bytes)?;
+
+    run_migrations(pg_pool).await?;
+
+    for chunk in &manifest.chunks {
+        let mut tx = pg_pool.begin().await?;
+
+        for batch in read_parquet(&dir.join(&chunk.blocks_file))? {
+            sqlx::query(
+                "INSERT INTO blocks (block_number, chain_name, hash, parent_hash, timestamp, miner, difficulty, total_difficulty,
+                    gas_used, gas_limit, size, receipts_root, transactions, tx_count, canonical)
+                SELECT block_number, chain_name, hash, parent_hash, to_timestamp(ts) AT TIME ZONE 'UTC', miner, difficulty, total_difficulty,
+                    gas_used, gas_limit, size, receipts_root, transactions::jsonb, tx_count, canonical
+                FROM UNNEST($1::bigint[], $2::text[], $3::text[], $4::text[], $5::bigint[], $6::text[], $7::text[], $8::text[],
+                    $9::bigint[], $10::bigint[], $11::bigint[], $12::text[], $13::text[], $14::bigint[], $15::bool[])
+                    AS b(block_number, chain_name, hash, parent_hash, ts, miner, difficulty, total_difficulty,
+                        gas_used, gas_limit, size, receipts_root, transactions, tx_count, canonical)
+                ON CONFLICT DO NOTHING",
+            )
+            .bind(int64_column(&batch, "block_number")?.values().to_vec())
+            .bind(strings(string_column(&batch, "chain_name")?))
+            .bind(strings(string_column(&batch, "hash")?))
+            .bind(strings(string_column(&batch, "parent_hash")?))
+            .bind(int64_column(&batch, "timestamp")?.values().to_vec())
+            .bind(strings(string_column(&batch, "miner")?))
+            .bind(strings(string_column(&batch, "difficulty")?))
+            .bind(strings(string_column(&batch, "total_difficulty")?))
+            .bind(int64_column(&batch, "gas_used")?.values().to_vec())
+            .bind(int64_column(&batch, "gas_limit")?.values().to_vec())
+            .bind(int64_column(&batch, "size")?.values().to_vec())
+            .bind(strings(string_column(&batch, "receipts_root")?))
+            .bind(strings(string_column(&batch, "transactions")?))
+            .bind(int64_column(&batch, "tx_count")?.values().to_vec())
+            .bind(bool_column(&batch, "canonical")?.iter().map(|v| v.unwrap_or(true)).collect::<Vec<bool>>())
+            .execute(&mut tx)
+            .await?;
+        }
+
+        // `transactions` has no unique key for ON CONFLICT, so rows already stored (by an earlier
+        // import of the bundle, or by ingestion) are skipped explicitly. By block hash, so the
+        // copies of a transaction in a canonical and an orphaned block are both kept; rows from
+        // bundles without block hashes fall back to the height.
+        for batch in read_parquet(&dir.join(&chunk.transactions_file))? {
+            sqlx::query(
+                "INSERT INTO transactions (chain_name, block_number, tx_hash, from_address, to_address, value, gas_price, gas, input, nonce, canonical, block_hash, input_uri)
+                SELECT DISTINCT ON (t.chain_name, t.block_number, t.block_hash, t.tx_hash) t.*
+                FROM UNNEST($1::text[], $2::bigint[], $3::text[], $4::text[], $5::text[], $6::text[], $7::text[], $8::text[],
+                    $9::text[], $10::bigint[], $11::bool[], $12::text[], $13::text[])
+                    AS t(chain_name, block_number, tx_hash, from_address, to_address, value, gas_price, gas, input, nonce, canonical, block_hash, input_uri)
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM transactions existing
+                    WHERE existing.chain_name = t.chain_name AND existing.block_number = t.block_number AND existing.tx_hash = t.tx_hash
+                        AND (t.block_hash IS NULL OR existing.block_hash IS NULL OR existing.block_hash = t.block_hash)
+                )",
+            )
+            .bind(strings(string_column(&batch, "chain_name")?))
+            .bind(int64_column(&batch, "block_number")?.values().to_vec())
+            .bind(strings(string_column(&batch, "tx_hash")?))
+            .bind(strings(string_column(&batch, "from_address")?))
+            .bind(strings(string_column(&batch, "to_address")?))
+            .bind(strings(string_column(&batch, "value")?))
+            .bind(strings(string_column(&batch, "gas_price")?))
+            .bind(strings(string_column(&batch, "gas")?))
+            .bind(strings(string_column(&batch, "input")?))
+            .bind(int64_column(&batch, "nonce")?.values().to_vec())
+            .bind(bool_column(&batch, "canonical")?.iter().map(|v| v.unwrap_or(true)).collect::<Vec<bool>>())
+            .bind(optional_strings(&batch, "block_hash")?)
+            .bind(optional_strings(&batch, "input_uri")?)
+            .execute(&mut tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        info!(
+            "Imported {} blocks {}..={} ({} blocks, {} transactions)",
+            manifest.chain_name, chunk.start_block, chunk.end_block, chunk.block_rows, chunk.transaction_rows
+        );
+    }
+
+    Ok(manifest)
+}
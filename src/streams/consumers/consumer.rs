@@ -3,9 +3,17 @@ use async_trait::async_trait;
 use sqlx::postgres::PgPool;
 use std::sync::Arc;
 
+use crate::storage::sinks::postgres::PostgresSink;
+use crate::storage::sinks::Sink;
+
 /// A trait representing a stream consumer that can consume messages and store them.
 #[async_trait]
-pub trait StreamConsumer {
-    /// Consume messages from a stream (e.g. Pulsar) and store them (e.g. in PostgreSQL).
-    async fn postgres_consume(&mut self, pg_pool: Arc<PgPool>, chain: &str) -> Result<()>;
+pub trait StreamConsumer: Send {
+    /// Consume messages from a stream (e.g. Pulsar) and store them in `sink`.
+    async fn consume(&mut self, sink: Arc<dyn Sink>, chain: &str) -> Result<()>;
+
+    /// Consume messages from a stream and store them in PostgreSQL.
+    async fn postgres_consume(&mut self, pg_pool: Arc<PgPool>, chain: &str) -> Result<()> {
+        self.consume(Arc::new(PostgresSink::new(pg_pool)), chain).await
+    }
 }
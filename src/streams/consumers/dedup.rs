@@ -0,0 +1,292 @@
+use anyhow::Result;
+use ethers::types::{Block, Transaction};
+use log::warn;
+use serde::Deserialize;
+use sqlx::{PgPool, Row};
+use std::collections::{HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+use crate::metrics;
+use crate::streams::consumers::hooks::OrphanedBlock;
+
+/// Recorded blocks between prunes of keys that fell out of the window.
+const PRUNE_EVERY: u64 = 1000;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DedupConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Blocks behind the newest recorded one whose keys are kept in Postgres.
+    #[serde(default = "default_window_blocks")]
+    pub window_blocks: u64,
+    /// Keys held in memory per stream; the oldest are evicted first.
+    #[serde(default = "default_max_keys")]
+    pub max_keys: usize,
+    /// Another instance publishes and consumes the same chains (active-active). Keys missing from
+    /// memory are then looked up in Postgres, where the peer records what it stored.
+    #[serde(default)]
+    pub peers: bool,
+}
+
+fn default_window_blocks() -> u64 {
+    10_000
+}
+
+fn default_max_keys() -> usize {
+    200_000
+}
+
+impl Default for DedupConfig {
+    fn default() -> Self {
+        Self { enabled: false, window_blocks: default_window_blocks(), max_keys: default_max_keys(), peers: false }
+    }
+}
+
+struct RecentKeys {
+    keys: HashSet<String>,
+    order: VecDeque<String>,
+    newest_block: u64,
+    recorded_since_prune: u64,
+}
+
+impl RecentKeys {
+    fn insert(&mut self, key: String, max_keys: usize) {
+        if self.keys.insert(key.clone()) {
+            self.order.push_back(key);
+        }
+        while self.order.len() > max_keys {
+            if let Some(evicted) = self.order.pop_front() {
+                self.keys.remove(&evicted);
+            }
+        }
+    }
+}
+
+/// Keys of what a stream's consumers recently stored: the block hash of every block written,
+/// and `{block_hash}:{tx_hash}` of transactions written without their block. Redeliveries and
+/// blocks both the historical and realtime topics carry are then stored once, even in sinks
+/// without unique constraints. Keys of blocks a reorg orphans are forgotten, so a block that
+/// becomes canonical again after a reorg back is stored again. Lookups only touch memory;
+/// Postgres keeps the window across restarts.
+pub struct DedupWindow {
+    pg_pool: Arc<PgPool>,
+    chain_name: String,
+    stream: String,
+    window_blocks: u64,
+    max_keys: usize,
+    peers: bool,
+    recent: Mutex<RecentKeys>,
+}
+
+/// What is left of a block once its already stored parts are dropped, with the keys to record
+/// once it is stored.
+pub struct Unseen {
+    pub block: Block<Transaction>,
+    pub keys: Vec<String>,
+}
+
+impl DedupWindow {
+    /// Loads the newest `max_keys` keys of the `stream` schema of `chain_name`.
+    pub async fn load(pg_pool: Arc<PgPool>, chain_name: &str, stream: &str, config: &DedupConfig) -> Result<Self> {
+        let rows = sqlx::query(
+            "SELECT key, block_number FROM consumer_dedup WHERE chain_name = $1 AND stream = $2
+            ORDER BY block_number DESC LIMIT $3",
+        )
+        .bind(chain_name)
+        .bind(stream)
+        .bind(config.max_keys as i64)
+        .fetch_all(pg_pool.as_ref())
+        .await?;
+
+        let mut recent = RecentKeys {
+            keys: HashSet::new(),
+            order: VecDeque::new(),
+            newest_block: 0,
+            recorded_since_prune: 0,
+        };
+        for row in rows.iter().rev() {
+            recent.insert(row.try_get("key")?, config.max_keys);
+            recent.newest_block = recent.newest_block.max(row.try_get::<i64, _>("block_number")? as u64);
+        }
+        Ok(Self {
+            pg_pool,
+            chain_name: chain_name.to_string(),
+            stream: stream.to_string(),
+            window_blocks: config.window_blocks,
+            max_keys: config.max_keys,
+            peers: config.peers,
+            recent: Mutex::new(recent),
+        })
+    }
+
+    /// What of `block` isn't stored yet, or `None` if all of it is. With `transactions_only`
+    /// transactions are checked one by one, otherwise the block as a whole.
+    ///
+    /// With peers, the returned keys are claimed in memory right away: both instances publish
+    /// every block, so its second copy usually arrives before the first is durable. A claim is
+    /// only lost if this consumer stops, and its peer then takes over the subscription.
+    pub async fn unseen(&self, mut block: Block<Transaction>, transactions_only: bool) -> Result<Option<Unseen>> {
+        let block_hash = format!("{:?}", block.hash.unwrap_or_default());
+        let candidates: Vec<String> = if transactions_only {
+            block.transactions.iter().map(|transaction| format!("{}:{:?}", block_hash, transaction.hash)).collect()
+        } else {
+            vec![block_hash]
+        };
+
+        let mut seen: HashSet<String> = {
+            let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+            candidates.iter().filter(|key| recent.keys.contains(*key)).cloned().collect()
+        };
+        if self.peers {
+            let missing: Vec<&str> = candidates.iter().filter(|key| !seen.contains(*key)).map(String::as_str).collect();
+            if !missing.is_empty() {
+                // Failing to look is no reason to stop consuming: the keys are taken as unseen, and
+                // at worst the block is stored twice.
+                match self.stored_by_peer(&missing).await {
+                    Ok(stored) => seen.extend(stored),
+                    Err(e) => {
+                        warn!(
+                            "Failed to look up dedup keys of {} {} stored by a peer, storing the block anyway: {}",
+                            self.chain_name, self.stream, e
+                        );
+                        let labels = [("chain", self.chain_name.as_str()), ("schema", self.stream.as_str())];
+                        metrics::increment_counter("dedup_peer_lookup_failures_total", &labels, 1);
+                    }
+                }
+            }
+            let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+            for key in &candidates {
+                // A key claimed since the lookup belongs to a copy already on its way to the sink.
+                if seen.contains(key) || recent.keys.contains(key) {
+                    seen.insert(key.clone());
+                }
+                recent.insert(key.clone(), self.max_keys);
+            }
+        }
+
+        let (unseen, skipped) = if transactions_only {
+            let count = block.transactions.len();
+            let mut candidates = candidates.into_iter();
+            let mut keys = Vec::with_capacity(count);
+            block.transactions.retain(|_| {
+                let key = candidates.next().unwrap_or_default();
+                let seen = seen.contains(&key);
+                if !seen {
+                    keys.push(key);
+                }
+                !seen
+            });
+            let skipped = count - block.transactions.len();
+            ((skipped == 0 || !block.transactions.is_empty()).then_some(Unseen { block, keys }), skipped)
+        } else if !seen.is_empty() {
+            (None, 1)
+        } else {
+            (Some(Unseen { block, keys: candidates }), 0)
+        };
+
+        if skipped > 0 {
+            let labels = [("chain", self.chain_name.as_str()), ("schema", self.stream.as_str())];
+            metrics::increment_counter("dedup_skipped_total", &labels, skipped as u64);
+        }
+        Ok(unseen)
+    }
+
+    /// Drops the claims of `keys` whose write failed, so their redelivery is stored.
+    pub fn release(&self, keys: &[String]) {
+        if !self.peers || keys.is_empty() {
+            return;
+        }
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        for key in keys {
+            recent.keys.remove(key);
+        }
+        let RecentKeys { keys: held, order, .. } = &mut *recent;
+        order.retain(|key| held.contains(key));
+    }
+
+    /// Forgets the keys of `orphaned` blocks.
+    pub async fn forget(&self, orphaned: &[OrphanedBlock]) -> Result<()> {
+        if orphaned.is_empty() {
+            return Ok(());
+        }
+        let hashes: Vec<&str> = orphaned.iter().map(|block| block.hash.as_str()).collect();
+        {
+            let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+            let RecentKeys { keys, order, .. } = &mut *recent;
+            keys.retain(|key| !hashes.contains(&block_hash_of(key)));
+            order.retain(|key| keys.contains(key));
+        }
+        sqlx::query(
+            "DELETE FROM consumer_dedup WHERE chain_name = $1 AND stream = $2 AND split_part(key, ':', 1) = ANY($3)",
+        )
+        .bind(&self.chain_name)
+        .bind(&self.stream)
+        .bind(&hashes)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        Ok(())
+    }
+
+    /// Which of `keys` a peer recorded since this window was loaded.
+    async fn stored_by_peer(&self, keys: &[&str]) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT key FROM consumer_dedup WHERE chain_name = $1 AND stream = $2 AND key = ANY($3)")
+            .bind(&self.chain_name)
+            .bind(&self.stream)
+            .bind(keys)
+            .fetch_all(self.pg_pool.as_ref())
+            .await?;
+        rows.iter().map(|row| Ok(row.try_get("key")?)).collect()
+    }
+
+    /// Records `keys` of a stored block, and every `PRUNE_EVERY` blocks drops persisted keys of
+    /// blocks that fell out of the window.
+    pub async fn record(&self, block_number: u64, keys: Vec<String>) -> Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let prune_below = {
+            let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+            for key in &keys {
+                recent.insert(key.clone(), self.max_keys);
+            }
+            recent.newest_block = recent.newest_block.max(block_number);
+            recent.recorded_since_prune += 1;
+            (recent.recorded_since_prune >= PRUNE_EVERY).then(|| {
+                recent.recorded_since_prune = 0;
+                recent.newest_block.saturating_sub(self.window_blocks)
+            })
+        };
+
+        sqlx::query(
+            "INSERT INTO consumer_dedup (chain_name, stream, key, block_number)
+            SELECT $1, $2, key, $4 FROM UNNEST($3::text[]) AS k(key)
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(&self.chain_name)
+        .bind(&self.stream)
+        .bind(&keys)
+        .bind(block_number as i64)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        if let Some(prune_below) = prune_below {
+            sqlx::query("DELETE FROM consumer_dedup WHERE chain_name = $1 AND stream = $2 AND block_number < $3")
+                .bind(&self.chain_name)
+                .bind(&self.stream)
+                .bind(prune_below as i64)
+                .execute(self.pg_pool.as_ref())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+/// The block hash a key was derived from.
+fn block_hash_of(key: &str) -> &str {
+    key.split(':').next().unwrap_or(key)
+}
+
+/// Drops from `keys`, waiting to be recorded, those of `orphaned` blocks.
+pub fn drop_orphaned(keys: &mut Vec<String>, orphaned: &[OrphanedBlock]) {
+    keys.retain(|key| !orphaned.iter().any(|block| block.hash == block_hash_of(key)));
+}
@@ -1,136 +1,798 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
 use log::error;
-use sqlx::PgPool;
-use serde_json::{json, Value};
-use futures_util::StreamExt;
-use pulsar::message;
-use pulsar::DeserializeMessage;
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::Mutex;
-use alloy_primitives::{U256, Address, B256};
-use alloy_network_primitives::{BlockResponse, TransactionResponse, BlockTransactions};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+use alloy_network_primitives::BlockTransactionsKind;
+use ethers::types::{Block, Transaction, H256};
 
-use crate::streams::message_queue::pulsar::{create_consumer, PulsarClient};
+use crate::metrics;
+use crate::server::dashboard;
+use crate::shutdown::{DrainReport, Shutdown};
+use crate::storage::sinks::{Sink, WriteOutcome};
+use crate::streams::message_queue::queue::{MessageQueue, QueueMessage, QueueSubscriber};
 use crate::streams::consumers::consumer::StreamConsumer;
+use crate::streams::consumers::dedup::{drop_orphaned, DedupWindow};
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+use crate::streams::consumers::redelivery::{RedeliveryBackoff, RedeliveryConfig};
+use crate::streams::delivery::DeliveryMode;
+use crate::streams::schemas::evm::without_transactions;
+use crate::streams::schemas::header::BlockHeader;
+use crate::streams::schemas::schema::decode;
+use crate::streams::schemas::versioning::decode_versioned;
+use crate::streams::sharding::{FanoutCommit, ShardConfig};
+
+/// Blocks queued per insert worker before the consumer stops reading ahead.
+const WORKER_QUEUE_DEPTH: usize = 64;
+
+/// What of each block a consumer stores.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stored {
+    Block,
+    /// Transactions only, for shards of an address-sharded stream but the first.
+    Transactions,
+    /// The block row and the transactions of shard 0, for the first shard of an address-sharded
+    /// stream. Hooks still get the whole block.
+    OwnedPart(ShardConfig),
+    /// Header fields only, for the `headers` schema.
+    Header,
+}
 
 pub struct EVMConsumer {
-    pulsar: Arc<PulsarClient>,
+    queue: Arc<dyn MessageQueue>,
     consumer_topic: String,
     consumer_subscription: String,
+    hooks: Vec<Arc<dyn ConsumerHook>>,
+    workers: usize,
+    tx_detail: BlockTransactionsKind,
+    stored: Stored,
+    dedup: Option<Arc<DedupWindow>>,
+    staged_fanout: bool,
+    redelivery: RedeliveryConfig,
+    delivery: DeliveryMode,
+    shutdown: Option<Arc<Shutdown>>,
 }
 
+/// Block parts read from a staged topic whose `FanoutCommit` hasn't arrived yet, in read order.
+type StagedParts = Vec<(QueueMessage, Block<Transaction>)>;
+
 impl EVMConsumer {
     pub async fn new(
-        pulsar: Arc<PulsarClient>,
+        queue: Arc<dyn MessageQueue>,
         consumer_topic: String,
-        consumer_subscription: String
+        consumer_subscription: String,
+        hooks: Vec<Arc<dyn ConsumerHook>>,
     ) -> Self {
         Self {
-            pulsar,
+            queue,
             consumer_topic,
-            consumer_subscription
-        }
-    }
-
-    pub async fn insert_transaction_data(&self, pg_pool: &PgPool, block_number: i64, chain_name: &str, transaction: &impl TransactionResponse) -> Result<()> {
-        let mut tx = pg_pool.begin().await?;
-
-        sqlx::query!(
-            "INSERT INTO transactions (block_number, chain_name, tx_hash, from_address, to_address, value, gas_price, gas, input, nonce) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)",
-            block_number,
-            chain_name,
-            transaction.tx_hash().to_string(),
-            transaction.from().to_string(),
-            transaction.to().map(|to| to.to_string()),
-            transaction.value().unwrap_or_default().to_string(),
-            transaction.gas_price().unwrap_or_default().to_string(),
-            transaction.gas().to_string(),
-            transaction.input().to_string(),
-            transaction.nonce().unwrap_or_default().as_u64() as i64
-        )
-        .execute(&mut tx)
-        .await
-        .map_err(|e: sqlx::Error| {
-            error!("Failed to insert transaction data into PostgreSQL: {}", e);
-            anyhow::anyhow!(e)
-        })?;
-
-        tx.commit().await?;
+            consumer_subscription,
+            hooks,
+            workers: 1,
+            tx_detail: BlockTransactionsKind::Full,
+            stored: Stored::Block,
+            dedup: None,
+            staged_fanout: false,
+            redelivery: RedeliveryConfig::default(),
+            delivery: DeliveryMode::AtLeastOnce,
+            shutdown: None,
+        }
+    }
+
+    /// Stops reading once `shutdown` drains consumers, stores and acknowledges what was read,
+    /// and reports it.
+    pub fn with_shutdown(mut self, shutdown: Arc<Shutdown>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
+    /// Returns once the consumer has to stop reading; never without a shutdown.
+    async fn draining(&self) {
+        draining(&self.shutdown).await
+    }
+
+    fn drain_report(&self, chain_name: &str, in_flight: usize) -> DrainReport {
+        DrainReport {
+            consumer: self.consumer_topic.clone(),
+            chain_name: chain_name.to_string(),
+            in_flight: in_flight as u64,
+            ..Default::default()
+        }
+    }
+
+    /// With at-most-once delivery messages are acknowledged before their block is stored, and a
+    /// failed write drops the block instead of having it redelivered.
+    pub fn with_delivery(mut self, delivery: DeliveryMode) -> Self {
+        self.delivery = delivery;
+        self
+    }
+
+    /// Sets the backoff failed writes are retried with.
+    pub fn with_redelivery(mut self, redelivery: RedeliveryConfig) -> Self {
+        self.redelivery = redelivery;
+        self
+    }
+
+    /// Holds each block part back until the producer's `FanoutCommit` for its block arrives, for
+    /// the shards of an address-sharded stream.
+    pub fn with_staged_fanout(mut self) -> Self {
+        self.staged_fanout = true;
+        self
+    }
+
+    /// Stores only the transactions of each block, for the shards of an address-sharded stream
+    /// whose blocks are written by the first shard's consumer.
+    pub fn with_transactions_only(mut self) -> Self {
+        self.stored = Stored::Transactions;
+        self
+    }
+
+    /// Stores the block row and only shard 0's transactions of each whole block, for the first
+    /// shard of an address-sharded stream.
+    pub fn with_owned_part(mut self, sharding: ShardConfig) -> Self {
+        self.stored = Stored::OwnedPart(sharding);
+        self
+    }
+
+    /// Reads the `BlockHeader` messages of the `headers` schema and stores header fields only.
+    pub fn with_headers(mut self) -> Self {
+        self.stored = Stored::Header;
+        self
+    }
+
+    /// Skips blocks and transactions `dedup` has seen stored, and records the ones this consumer
+    /// stores once they are durable.
+    pub fn with_dedup(mut self, dedup: Arc<DedupWindow>) -> Self {
+        self.dedup = Some(dedup);
+        self
+    }
+
+    /// Drops what the dedup window has seen stored. `None` if nothing is left to store; the
+    /// keys are what to record once the rest is.
+    async fn deduplicate(&self, block: Block<Transaction>) -> Result<Option<(Block<Transaction>, Vec<String>)>> {
+        match &self.dedup {
+            Some(dedup) => Ok(dedup
+                .unseen(block, self.stored == Stored::Transactions)
+                .await?
+                .map(|unseen| (unseen.block, unseen.keys))),
+            None => Ok(Some((block, Vec::new()))),
+        }
+    }
+
+    /// Reads blocks published with transaction hashes only. They are stored without transactions.
+    pub fn with_tx_detail(mut self, tx_detail: BlockTransactionsKind) -> Self {
+        self.tx_detail = tx_detail;
+        self
+    }
+
+    fn decode_block(&self, payload: &[u8]) -> Result<Block<Transaction>> {
+        if self.stored == Stored::Header {
+            return Ok(decode_versioned::<BlockHeader>(payload)?.into_block());
+        }
+        match self.tx_detail {
+            BlockTransactionsKind::Full => decode_versioned(payload),
+            BlockTransactionsKind::Hashes => without_transactions(&decode_versioned::<Block<H256>>(payload)?),
+        }
+    }
+
+    /// The block `msg` carries, with the messages to acknowledge once it is stored. On a staged
+    /// topic parts wait in `staged` and come out when their marker is read, together with the
+    /// marker; `None` means there is nothing to store yet, and `settled` gets the messages that
+    /// can be acknowledged right away: markers without a part, and parts of fan-outs that were
+    /// cut short and published again.
+    fn read_block(
+        &self,
+        mut msg: QueueMessage,
+        staged: &mut StagedParts,
+        settled: &mut Vec<QueueMessage>,
+    ) -> Result<Option<(Block<Transaction>, Vec<QueueMessage>)>> {
+        // Acknowledging only needs the id, so the payload is dropped once decoded instead of
+        // being held until the sink reports durability.
+        let payload = std::mem::take(&mut msg.payload);
+        if !self.staged_fanout {
+            return Ok(Some((self.decode_block(&payload)?, vec![msg])));
+        }
+        let Ok(commit) = decode::<FanoutCommit>(&payload) else {
+            staged.push((msg, self.decode_block(&payload)?));
+            return Ok(None);
+        };
+        let Some(index) = staged.iter().rposition(|(_, block)| block.hash == Some(commit.fanout_commit)) else {
+            settled.push(msg);
+            return Ok(None);
+        };
+        let mut parts = staged.drain(..=index);
+        let (part, block) = parts.next_back().expect("committed part");
+        settled.extend(parts.map(|(msg, _)| msg));
+        Ok(Some((block, vec![part, msg])))
+    }
 
+    /// Writes blocks from `workers` parallel tasks instead of one at a time. Blocks are assigned
+    /// to workers by block number, so blocks at the same height are still written in order.
+    pub fn with_workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    /// Fans messages out to the insert workers. Workers only write; once a block and every block
+    /// read before it are written, its hooks run and its messages are acknowledged here, one
+    /// block at a time in the order they were read, so hooks see blocks in stream order.
+    async fn consume_parallel(&mut self, sink: Arc<dyn Sink>, chain_name: &str) -> Result<()> {
+        let mut subscriber = self.queue.subscriber(&self.consumer_topic, &self.consumer_subscription).await?;
+        let (done_sender, mut done) = mpsc::unbounded_channel::<Finished>();
+        let mut backoff = RedeliveryBackoff::new(self.redelivery.clone());
+        let mut staged = StagedParts::new();
+        let mut settled = Vec::new();
+        // Messages handed to the workers and not yet acknowledged.
+        let mut in_workers = 0;
+        // Blocks handed to the workers, numbered in read order, and the next one to settle.
+        let mut dispatched = 0u64;
+        let mut next_to_settle = 0u64;
+        let mut finished = BTreeMap::new();
+
+        let mut workers = Vec::with_capacity(self.workers);
+        for _ in 0..self.workers {
+            let (sender, mut blocks) = mpsc::channel::<(u64, Vec<QueueMessage>, Block<Transaction>, Vec<String>)>(WORKER_QUEUE_DEPTH);
+            let sink = Arc::clone(&sink);
+            let chain_name = chain_name.to_string();
+            let done_sender = done_sender.clone();
+            let stored = self.stored;
+            let dedup = self.dedup.clone();
+            let mut backoff = RedeliveryBackoff::new(self.redelivery.clone());
+            let retries = self.delivery != DeliveryMode::AtMostOnce;
+            let shutdown = self.shutdown.clone();
+            tokio::spawn(async move {
+                while let Some((seq, msgs, block, keys)) = blocks.recv().await {
+                    let mut written = write(sink.as_ref(), &chain_name, &block, stored).await;
+                    // Retried here rather than redelivered, so the blocks read after it can't
+                    // settle first. Given up once the consumer drains, and redelivered then.
+                    while retries {
+                        let Err(e) = &written else {
+                            backoff.reset();
+                            break;
+                        };
+                        // Writing it again would fail the same way: settling redelivers it.
+                        if is_permanent(e) {
+                            break;
+                        }
+                        let delay = backoff.next_delay();
+                        error!("Failed to store {} block {:?}, writing it again in {:?}: {}", chain_name, block.number, delay, e);
+                        dashboard::record_error(&format!("{} sink", chain_name), &e.to_string());
+                        metrics::increment_counter("consumer_write_retries_total", &[("chain", &chain_name)], 1);
+                        tokio::select! {
+                            _ = tokio::time::sleep(delay) => {}
+                            _ = draining(&shutdown) => break,
+                        }
+                        written = write(sink.as_ref(), &chain_name, &block, stored).await;
+                    }
+                    let result = match written {
+                        Ok(outcome) => async {
+                            // Another worker's flush may not cover this block, so buffered writes can't be acked.
+                            if !outcome.durable {
+                                return Err(anyhow!("Parallel consumer workers need a sink that makes every write durable"));
+                            }
+                            if let Some(dedup) = &dedup {
+                                dedup.record(block.number.unwrap_or_default().as_u64(), keys).await?;
+                                dedup.forget(&outcome.orphaned).await?;
+                            }
+                            Ok(outcome.orphaned)
+                        }
+                        .await
+                        .map_err(WorkerError::Fatal),
+                        Err(e) => {
+                            if let Some(dedup) = &dedup {
+                                dedup.release(&keys);
+                            }
+                            Err(WorkerError::Write(e))
+                        }
+                    };
+                    if done_sender.send(Finished { seq, msgs, block, result }).is_err() {
+                        break;
+                    }
+                }
+            });
+            workers.push(sender);
+        }
+        drop(done_sender);
+
+        loop {
+            tokio::select! {
+                msg_res = subscriber.next() => match msg_res {
+                    Some(Ok(msg)) => {
+                        let read = self.read_block(msg, &mut staged, &mut settled);
+                        for msg in settled.drain(..) {
+                            subscriber.ack(&msg).await?;
+                        }
+                        let (block, msgs) = match read {
+                            Ok(Some(read)) => read,
+                            Ok(None) => continue,
+                            Err(e) => {
+                                error!("Failed to deserialize message: {:?}", e);
+                                break;
+                            }
+                        };
+                        let Some((block, keys)) = self.deduplicate(block).await? else {
+                            for msg in &msgs {
+                                subscriber.ack(msg).await?;
+                            }
+                            continue;
+                        };
+                        let msgs = if self.delivery == DeliveryMode::AtMostOnce {
+                            for msg in &msgs {
+                                subscriber.ack(msg).await?;
+                            }
+                            Vec::new()
+                        } else {
+                            msgs
+                        };
+                        let worker = block.number.unwrap_or_default().as_u64() as usize % workers.len();
+                        in_workers += msgs.len();
+                        workers[worker]
+                            .send((dispatched, msgs, block, keys))
+                            .await
+                            .map_err(|_| anyhow!("Consumer worker for {} stopped", chain_name))?;
+                        dispatched += 1;
+                    }
+                    Some(Err(e)) => error!("Failed to receive message: {}", e),
+                    None => break,
+                },
+                Some(block) = done.recv() => {
+                    finished.insert(block.seq, block);
+                    let (settled_msgs, _) = self
+                        .settle_in_order(subscriber.as_mut(), chain_name, &mut finished, &mut next_to_settle, &mut backoff)
+                        .await?;
+                    in_workers -= settled_msgs;
+                }
+                _ = self.draining() => break,
+            }
+        }
+
+        // Let the workers finish what they were given.
+        let mut report = self.drain_report(chain_name, in_workers + staged.len());
+        drop(workers);
+        while let Some(block) = done.recv().await {
+            finished.insert(block.seq, block);
+            let (_, acked) = self
+                .settle_in_order(subscriber.as_mut(), chain_name, &mut finished, &mut next_to_settle, &mut backoff)
+                .await?;
+            report.acked += acked as u64;
+        }
+        // Staged parts and redelivered blocks are left to the broker.
+        if let Some(shutdown) = &self.shutdown {
+            report.unacked = report.in_flight.saturating_sub(report.acked);
+            shutdown.report(report);
+        }
         Ok(())
     }
 
-    pub async fn insert_block_data(&self, pg_pool: &PgPool, chain_name: &str, block: &impl BlockResponse) -> Result<()> {
-        let header = block.header();
-        let block_number_i64 = header.number().as_u64() as i64;
-        let gas_used_i64 = header.gas_used().as_u64() as i64;
-        let gas_limit_i64 = header.gas_limit().as_u64() as i64;
-        let size_i64 = header.size().unwrap_or_default().as_u64() as i64;
-        let timestamp_i64 = header.timestamp().as_u64() as i64;
-        let timestamp: PrimitiveDateTime = PrimitiveDateTime::from_unix_timestamp(timestamp_i64).unwrap();
-        let tx_count_i64 = block.transactions().len() as i64;
-        let tx_count_value: Value = tx_count_i64.into();
-        let transactions_json: Value = serde_json::to_value(&block.transactions()).unwrap();
-
-        let mut tx = pg_pool.begin().await?;
-
-        sqlx::query!(
-            "INSERT INTO blocks (block_number, chain_name, hash, parent_hash, timestamp, miner, difficulty, total_difficulty, gas_used, gas_limit, size, receipts_root, tx_count, transactions) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)",
-            block_number_i64,
-            chain_name,
-            header.hash().to_string(),
-            header.parent_hash().to_string(),
-            timestamp,
-            header.miner().to_string(),
-            header.difficulty().to_string(),
-            header.total_difficulty().to_string(),
-            gas_used_i64,
-            gas_limit_i64,
-            size_i64,
-            header.receipts_root().to_string(),
-            tx_count_value,
-            transactions_json
-        )
-        .execute(&mut tx)
-        .await
-        .map_err(|e: sqlx::Error| {
-            error!("Failed to insert block data into PostgreSQL: {}", e);
-            anyhow::anyhow!(e)
-        })?;
-
-        tx.commit().await?;
+    /// Writes every block of `unacked` again, then `block`, or flushes the sink if there is no
+    /// `block`, after a write failed with `e`. Retries with backoff until all of it succeeds.
+    /// The failed write may have taken the blocks the sink buffered with it, so they are all
+    /// written again, in order, and no later block overtakes them. Returns the outcome of the
+    /// last write, with the blocks every write orphaned. Errors that writing again can't fix,
+    /// such as constraint violations, are returned instead of retried.
+    async fn rewrite(
+        &self,
+        sink: &dyn Sink,
+        chain_name: &str,
+        unacked: &[Unacked],
+        block: Option<&Block<Transaction>>,
+        mut e: anyhow::Error,
+        backoff: &mut RedeliveryBackoff,
+    ) -> Rewrite {
+        loop {
+            if is_permanent(&e) {
+                return Rewrite::Rejected(e);
+            }
+            let delay = backoff.next_delay();
+            error!("Failed to store a block of {}, writing it and {} buffered blocks again in {:?}: {}", chain_name, unacked.len(), delay, e);
+            dashboard::record_error(&format!("{} sink", chain_name), &e.to_string());
+            metrics::increment_counter("consumer_write_retries_total", &[("chain", chain_name)], 1);
+            tokio::select! {
+                _ = tokio::time::sleep(delay) => {}
+                _ = self.draining() => return Rewrite::Draining,
+            }
 
+            let rewritten = async {
+                let mut orphaned = Vec::new();
+                for unacked in unacked {
+                    orphaned.extend(write(sink, chain_name, &unacked.block, self.stored).await?.orphaned);
+                }
+                let mut outcome = match block {
+                    Some(block) => write(sink, chain_name, block, self.stored).await?,
+                    None => sink.flush(chain_name).await?.unwrap_or(WriteOutcome { orphaned: Vec::new(), durable: true }),
+                };
+                orphaned.append(&mut outcome.orphaned);
+                outcome.orphaned = orphaned;
+                Ok::<_, anyhow::Error>(outcome)
+            };
+            let rewritten = tokio::select! {
+                rewritten = rewritten => rewritten,
+                _ = self.draining() => return Rewrite::Draining,
+            };
+            match rewritten {
+                Ok(outcome) => {
+                    backoff.reset();
+                    return Rewrite::Written(outcome);
+                }
+                Err(err) => e = err,
+            }
+        }
+    }
+
+    /// Redelivers the blocks of `unacked`, after a write failed in a way writing them again
+    /// right away can't fix, so the stream moves on. Returns the messages redelivered.
+    async fn reject(
+        &self,
+        subscriber: &mut dyn QueueSubscriber,
+        chain_name: &str,
+        unacked: &mut Vec<Unacked>,
+        delay: Duration,
+        e: &anyhow::Error,
+    ) -> Result<usize> {
+        let mut rejected = 0;
+        for unacked in unacked.drain(..) {
+            if let Some(dedup) = &self.dedup {
+                dedup.release(&unacked.keys);
+            }
+            redeliver(subscriber, chain_name, &unacked.msgs, delay, e).await?;
+            rejected += unacked.msgs.len();
+        }
+        Ok(rejected)
+    }
+
+    /// Gives up on the blocks of `unacked`, which a failed write may have lost from the sink, so
+    /// the shutdown doesn't acknowledge them. Returns their messages, left to the broker.
+    fn abandon(&self, unacked: &mut Vec<Unacked>) -> usize {
+        let mut abandoned = 0;
+        for unacked in unacked.drain(..) {
+            if let Some(dedup) = &self.dedup {
+                dedup.release(&unacked.keys);
+            }
+            abandoned += unacked.msgs.len();
+        }
+        abandoned
+    }
+
+    /// Drops the keys of blocks a write orphaned from the dedup window and from `unacked`.
+    async fn forget_orphaned(&self, unacked: &mut [Unacked], orphaned: &[OrphanedBlock]) -> Result<()> {
+        if let Some(dedup) = &self.dedup {
+            for unacked in unacked.iter_mut() {
+                drop_orphaned(&mut unacked.keys, orphaned);
+            }
+            dedup.forget(orphaned).await?;
+        }
         Ok(())
     }
+
+    /// Runs the hooks of the blocks in `unacked`, which the sink now holds durably, and
+    /// acknowledges them, in the order they were read. Returns the messages acknowledged.
+    async fn settle(&self, subscriber: &mut dyn QueueSubscriber, chain_name: &str, unacked: &mut Vec<Unacked>) -> Result<usize> {
+        let mut acked = 0;
+        for Unacked { msgs, block, keys, orphaned } in unacked.drain(..) {
+            if let Some(dedup) = &self.dedup {
+                dedup.record(block.number.unwrap_or_default().as_u64(), keys).await?;
+            }
+            run_hooks(&self.hooks, chain_name, &block, &orphaned).await;
+            for msg in &msgs {
+                subscriber.ack(msg).await.map_err(|e| {
+                    error!("Failed to ACK message: {}", e);
+                    e
+                })?;
+            }
+            acked += msgs.len();
+        }
+        Ok(acked)
+    }
+
+    /// Settles the finished blocks from `next` on, up to the first block read that a worker still
+    /// holds: runs the hooks of each stored block and acknowledges it, or redelivers (or, at most
+    /// once, drops) a block whose write failed. Returns the messages settled and those acknowledged.
+    async fn settle_in_order(
+        &self,
+        subscriber: &mut dyn QueueSubscriber,
+        chain_name: &str,
+        finished: &mut BTreeMap<u64, Finished>,
+        next: &mut u64,
+        backoff: &mut RedeliveryBackoff,
+    ) -> Result<(usize, usize)> {
+        let (mut settled, mut acked) = (0, 0);
+        while let Some(Finished { msgs, block, result, .. }) = finished.remove(next) {
+            *next += 1;
+            settled += msgs.len();
+            match result {
+                Ok(orphaned) => {
+                    backoff.reset();
+                    run_hooks(&self.hooks, chain_name, &block, &orphaned).await;
+                }
+                Err(WorkerError::Write(e)) if self.delivery == DeliveryMode::AtMostOnce => {
+                    drop_block(chain_name, &e);
+                    continue;
+                }
+                Err(WorkerError::Write(e)) => {
+                    redeliver(subscriber, chain_name, &msgs, backoff.next_delay(), &e).await?;
+                    continue;
+                }
+                Err(WorkerError::Fatal(e)) => return Err(e),
+            }
+            for msg in &msgs {
+                subscriber.ack(msg).await.map_err(|e| {
+                    error!("Failed to ACK message: {}", e);
+                    e
+                })?;
+            }
+            acked += msgs.len();
+        }
+        Ok((settled, acked))
+    }
+}
+
+/// A block the sink holds but hasn't made durable yet, with its messages and dedup keys.
+struct Unacked {
+    msgs: Vec<QueueMessage>,
+    block: Block<Transaction>,
+    keys: Vec<String>,
+    /// The blocks its write orphaned, for its hooks.
+    orphaned: Vec<OrphanedBlock>,
+}
+
+/// A block an insert worker is done with, numbered in the order the consumer read it.
+struct Finished {
+    seq: u64,
+    msgs: Vec<QueueMessage>,
+    block: Block<Transaction>,
+    /// The blocks the write orphaned.
+    result: Result<Vec<OrphanedBlock>, WorkerError>,
+}
+
+/// Why an insert worker couldn't store a block.
+enum WorkerError {
+    /// The sink write failed, e.g. during a database failover; the block is redelivered.
+    Write(anyhow::Error),
+    Fatal(anyhow::Error),
+}
+
+/// How `EVMConsumer::rewrite` ended.
+enum Rewrite {
+    Written(WriteOutcome),
+    /// The write failed in a way writing it again can't fix.
+    Rejected(anyhow::Error),
+    /// The consumer drains; the blocks are left to the broker.
+    Draining,
+}
+
+/// Whether a failed write would fail again however often it is retried: a constraint violation
+/// (SQLSTATE class 23) or a value that can't be decoded or encoded.
+fn is_permanent(e: &anyhow::Error) -> bool {
+    e.chain().any(|cause| match cause.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(db)) => db.code().map_or(false, |code| code.starts_with("23")),
+        Some(sqlx::Error::Decode(_) | sqlx::Error::ColumnDecode { .. }) => true,
+        Some(_) => false,
+        None => cause.is::<serde_json::Error>(),
+    })
+}
+
+/// Negatively acknowledges the messages of a block whose write failed, for redelivery after
+/// `delay`.
+async fn redeliver(
+    subscriber: &mut dyn QueueSubscriber,
+    chain_name: &str,
+    msgs: &[QueueMessage],
+    delay: Duration,
+    e: &anyhow::Error,
+) -> Result<()> {
+    error!("Failed to store {} messages of {}, redelivering in {:?}: {}", msgs.len(), chain_name, delay, e);
+    dashboard::record_error(&format!("{} sink", chain_name), &e.to_string());
+    metrics::increment_counter("consumer_redeliveries_total", &[("chain", chain_name)], msgs.len() as u64);
+    subscriber.nack(msgs, delay).await
+}
+
+/// Logs and counts a block of an at-most-once stream whose write failed.
+fn drop_block(chain_name: &str, e: &anyhow::Error) {
+    error!("Dropped a block of {} after a failed write: {}", chain_name, e);
+    dashboard::record_error(&format!("{} sink", chain_name), &e.to_string());
+    metrics::increment_counter("consumer_dropped_blocks_total", &[("chain", chain_name)], 1);
+}
+
+async fn write(sink: &dyn Sink, chain_name: &str, block: &Block<Transaction>, stored: Stored) -> Result<WriteOutcome> {
+    match stored {
+        Stored::Block => sink.write_block(chain_name, block).await,
+        Stored::Transactions => sink.write_transactions(chain_name, block).await,
+        Stored::OwnedPart(sharding) => sink.write_block(chain_name, &sharding.owned_part(block)).await,
+        Stored::Header => sink.write_header(chain_name, block).await,
+    }
+}
+
+/// Returns once consumers have to stop reading; never without a shutdown.
+async fn draining(shutdown: &Option<Arc<Shutdown>>) {
+    match shutdown {
+        Some(shutdown) => shutdown.consumers_draining().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Resolves once the oldest block the sink buffers, buffered `since`, has waited `interval`.
+/// Never resolves while the sink buffers nothing or has no interval.
+async fn flush_due(interval: Option<Duration>, since: Option<Instant>) {
+    match (interval, since) {
+        (Some(interval), Some(since)) => tokio::time::sleep_until((since + interval).into()).await,
+        _ => std::future::pending().await,
+    }
+}
+
+/// Runs the orphan hooks for blocks a flush replaced, which have no committed block to go with.
+async fn run_orphan_hooks(hooks: &[Arc<dyn ConsumerHook>], chain_name: &str, orphaned: &[OrphanedBlock]) {
+    if orphaned.is_empty() {
+        return;
+    }
+    for hook in hooks {
+        if let Err(e) = hook.on_blocks_orphaned(chain_name, orphaned).await {
+            error!("Consumer hook failed on orphaned {} blocks: {}", chain_name, e);
+        }
+    }
+}
+
+/// Runs every registered hook for a committed block. Hook failures are logged, not propagated,
+/// so a broken side effect never stalls ingestion.
+async fn run_hooks(hooks: &[Arc<dyn ConsumerHook>], chain_name: &str, block: &Block<Transaction>, orphaned: &[OrphanedBlock]) {
+    for hook in hooks {
+        if !orphaned.is_empty() {
+            if let Err(e) = hook.on_blocks_orphaned(chain_name, orphaned).await {
+                error!("Consumer hook failed on orphaned {} blocks: {}", chain_name, e);
+            }
+        }
+        if let Err(e) = hook.on_block_committed(chain_name, block).await {
+            error!("Consumer hook failed for {} block {:?}: {}", chain_name, block.number, e);
+        }
+    }
 }
 
 #[async_trait]
 impl StreamConsumer for EVMConsumer {
-    async fn postgres_consume(&mut self, pg_pool: Arc<PgPool>, chain_name: &str) -> Result<()> {
-        let mut consumer = create_consumer(&self.pulsar, &self.consumer_topic, &self.consumer_subscription).await?;
-        
-        while let Some(msg_res) = consumer.next().await {
+    async fn consume(&mut self, sink: Arc<dyn Sink>, chain_name: &str) -> Result<()> {
+        if self.workers > 1 {
+            return self.consume_parallel(sink, chain_name).await;
+        }
+
+        let mut subscriber = self.queue.subscriber(&self.consumer_topic, &self.consumer_subscription).await?;
+        // Blocks the sink has buffered but not yet made durable, in the order they were read.
+        let mut unacked: Vec<Unacked> = Vec::new();
+        let mut staged = StagedParts::new();
+        let mut settled = Vec::new();
+        let mut backoff = RedeliveryBackoff::new(self.redelivery.clone());
+        // Messages of a write cut short by the shutdown.
+        let mut interrupted = 0;
+        let flush_interval = sink.flush_interval();
+        // When the oldest of `unacked` was written.
+        let mut unacked_since: Option<Instant> = None;
+
+        loop {
+            let msg_res = tokio::select! {
+                msg_res = subscriber.next() => match msg_res {
+                    Some(msg_res) => msg_res,
+                    None => break,
+                },
+                // A quiet chain's buffered blocks are written out instead of waiting for a full batch.
+                _ = flush_due(flush_interval, unacked_since) => {
+                    unacked_since = None;
+                    let flushed = match sink.flush(chain_name).await {
+                        Ok(flushed) => flushed,
+                        Err(e) => match self.rewrite(sink.as_ref(), chain_name, &unacked, None, e, &mut backoff).await {
+                            Rewrite::Written(outcome) => Some(outcome),
+                            Rewrite::Rejected(e) => {
+                                self.reject(subscriber.as_mut(), chain_name, &mut unacked, backoff.next_delay(), &e).await?;
+                                continue;
+                            }
+                            // Left unacknowledged for the broker to redeliver after the restart.
+                            Rewrite::Draining => {
+                                interrupted = self.abandon(&mut unacked);
+                                break;
+                            }
+                        },
+                    };
+                    if let Some(outcome) = flushed {
+                        self.forget_orphaned(&mut unacked, &outcome.orphaned).await?;
+                        run_orphan_hooks(&self.hooks, chain_name, &outcome.orphaned).await;
+                    }
+                    self.settle(subscriber.as_mut(), chain_name, &mut unacked).await?;
+                    continue;
+                }
+                _ = self.draining() => break,
+            };
             match msg_res {
                 Ok(msg) => {
-                    let block_message: BlockTransactions<impl TransactionResponse> = match msg.deserialize() {
-                        Ok(data) => data,
+                    let read = self.read_block(msg, &mut staged, &mut settled);
+                    for msg in settled.drain(..) {
+                        subscriber.ack(&msg).await?;
+                    }
+                    let (block_message, msgs) = match read {
+                        Ok(Some(read)) => read,
+                        Ok(None) => continue,
                         Err(e) => {
                             error!("Failed to deserialize message: {:?}", e);
                             break;
                         }
                     };
-                    
-                    for transaction in block_message.transactions() {
-                        self.insert_transaction_data(&pg_pool, transaction.block_number().as_u64() as i64, chain_name, transaction).await?;
+                    let Some((block_message, keys)) = self.deduplicate(block_message).await? else {
+                        // Already stored, and acknowledging out of order is fine.
+                        for msg in &msgs {
+                            subscriber.ack(msg).await?;
+                        }
+                        continue;
+                    };
+
+                    let block_number = block_message.number.unwrap_or_default().as_u64();
+                    if self.delivery == DeliveryMode::AtMostOnce {
+                        // Acknowledged first, so a failed write loses the block rather than
+                        // having it redelivered.
+                        for msg in &msgs {
+                            subscriber.ack(msg).await?;
+                        }
+                        match write(sink.as_ref(), chain_name, &block_message, self.stored).await {
+                            Ok(outcome) => {
+                                if let Some(dedup) = &self.dedup {
+                                    dedup.record(block_number, keys).await?;
+                                    dedup.forget(&outcome.orphaned).await?;
+                                }
+                                run_hooks(&self.hooks, chain_name, &block_message, &outcome.orphaned).await;
+                            }
+                            Err(e) => {
+                                if let Some(dedup) = &self.dedup {
+                                    dedup.release(&keys);
+                                }
+                                drop_block(chain_name, &e);
+                            }
+                        }
+                        continue;
+                    }
+
+                    // A write held up by a sink that is down mustn't hold up the shutdown. The
+                    // block is left unacknowledged and redelivered after the restart.
+                    let written = tokio::select! {
+                        written = write(sink.as_ref(), chain_name, &block_message, self.stored) => written,
+                        _ = self.draining() => {
+                            if let Some(dedup) = &self.dedup {
+                                dedup.release(&keys);
+                            }
+                            interrupted = msgs.len();
+                            break;
+                        }
+                    };
+                    let outcome = match written {
+                        Ok(outcome) => {
+                            backoff.reset();
+                            outcome
+                        }
+                        // Retried here rather than redelivered, so no later block overtakes it.
+                        Err(e) => match self.rewrite(sink.as_ref(), chain_name, &unacked, Some(&block_message), e, &mut backoff).await {
+                            Rewrite::Written(outcome) => outcome,
+                            Rewrite::Rejected(e) => {
+                                let delay = backoff.next_delay();
+                                unacked.push(Unacked { msgs, block: block_message, keys, orphaned: Vec::new() });
+                                self.reject(subscriber.as_mut(), chain_name, &mut unacked, delay, &e).await?;
+                                unacked_since = None;
+                                continue;
+                            }
+                            Rewrite::Draining => {
+                                if let Some(dedup) = &self.dedup {
+                                    dedup.release(&keys);
+                                }
+                                interrupted = msgs.len() + self.abandon(&mut unacked);
+                                break;
+                            }
+                        },
+                    };
+
+                    self.forget_orphaned(&mut unacked, &outcome.orphaned).await?;
+                    // Hooks run once the block is durable, so a block redelivered after a
+                    // restart has never been through them.
+                    unacked.push(Unacked { msgs, block: block_message, keys, orphaned: outcome.orphaned });
+                    unacked_since.get_or_insert_with(Instant::now);
+                    if outcome.durable {
+                        unacked_since = None;
+                        self.settle(subscriber.as_mut(), chain_name, &mut unacked).await?;
                     }
-                    
-                    self.insert_block_data(&pg_pool, chain_name, &block_message).await?;
-                    
-                    consumer.ack(&msg).await.map_err(|e| {
-                        error!("Failed to ACK message: {}", e);
-                        anyhow::anyhow!(e)
-                    })?;
                 }
                 Err(e) => {
                     error!("Failed to receive message: {}", e);
@@ -138,6 +800,27 @@ impl StreamConsumer for EVMConsumer {
             }
         }
 
+        // Blocks the sink still buffers are written out so their messages can be acknowledged.
+        // Staged parts are left to the broker.
+        if let Some(shutdown) = &self.shutdown {
+            let unacked_msgs: usize = unacked.iter().map(|unacked| unacked.msgs.len()).sum();
+            let mut report = self.drain_report(chain_name, unacked_msgs + staged.len() + interrupted);
+            if !unacked.is_empty() {
+                match sink.flush(chain_name).await {
+                    Ok(flushed) => {
+                        if let Some(outcome) = flushed {
+                            report.flushed_batches += 1;
+                            self.forget_orphaned(&mut unacked, &outcome.orphaned).await?;
+                            run_orphan_hooks(&self.hooks, chain_name, &outcome.orphaned).await;
+                        }
+                        report.acked += self.settle(subscriber.as_mut(), chain_name, &mut unacked).await? as u64;
+                    }
+                    Err(e) => error!("Failed to flush the sink of {} while shutting down: {}", chain_name, e),
+                }
+            }
+            report.unacked = report.in_flight - report.acked;
+            shutdown.report(report);
+        }
         Ok(())
     }
 }
@@ -0,0 +1,24 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+
+/// A previously canonical block that was replaced by a reorg.
+#[derive(Debug, Clone)]
+pub struct OrphanedBlock {
+    pub block_number: i64,
+    pub hash: String,
+    /// Hash of the block that replaced it at the same height.
+    pub replaced_by: String,
+}
+
+/// A trait for side effects that run after a consumed block has been committed to the sink.
+#[async_trait]
+pub trait ConsumerHook: Send + Sync {
+    /// Called once per block, after the block and its transactions have been committed.
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()>;
+
+    /// Called when committing a block marked previously canonical blocks as orphaned.
+    async fn on_blocks_orphaned(&self, _chain_name: &str, _orphaned: &[OrphanedBlock]) -> Result<()> {
+        Ok(())
+    }
+}
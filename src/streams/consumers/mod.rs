@@ -1,2 +1,6 @@
 pub mod consumer;
+pub mod dedup;
 pub mod evm_consumer;
+pub mod hooks;
+pub mod projections;
+pub mod redelivery;
@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use log::warn;
+use sqlx::types::Json;
+use sqlx::{PgPool, Postgres, Transaction as DbTransaction};
+use std::sync::Arc;
+
+use crate::metrics;
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+
+/// Failed blocks a projection folds again before each new block.
+const RETRY_BATCH: i64 = 10;
+
+/// A derived table maintained by folding consumed blocks into it, registered through
+/// `Registries::projections`. The pipeline runs it on the consumers of its schemas and keeps its
+/// checkpoint, so it only has to say how a block changes its state.
+#[async_trait]
+pub trait Projection: Send + Sync {
+    /// Unique name, under which its checkpoints are kept.
+    fn name(&self) -> &str;
+
+    /// Schema streams whose blocks it folds, e.g. `transactions`.
+    fn schemas(&self) -> Vec<String>;
+
+    /// The table it maintains.
+    fn output_table(&self) -> &str;
+
+    /// Folds a block of `schema` into the output table. Runs in the transaction that records the
+    /// block as folded, so each block is folded exactly once per schema, in any order.
+    async fn fold(&self, tx: &mut DbTransaction<'_, Postgres>, chain_name: &str, schema: &str, block: &Block<Transaction>) -> Result<()>;
+
+    /// Undoes blocks a reorg replaced, before their replacements are folded. By default deletes
+    /// the output rows with their `chain_name` and `block_number`; projections keeping running
+    /// state, such as totals, must subtract the blocks' contributions instead.
+    async fn revert(&self, tx: &mut DbTransaction<'_, Postgres>, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let block_numbers: Vec<i64> = orphaned.iter().map(|block| block.block_number).collect();
+        sqlx::query(&format!("DELETE FROM {} WHERE chain_name = $1 AND block_number = ANY($2)", self.output_table()))
+            .bind(chain_name)
+            .bind(block_numbers)
+            .execute(&mut *tx)
+            .await?;
+        Ok(())
+    }
+}
+
+/// Runs a projection on one schema stream, checkpointing in `projection_checkpoints`, recording
+/// folded blocks in `projection_blocks` and keeping blocks it failed to fold in
+/// `projection_failures` until a retry folds them.
+pub struct ProjectionHook {
+    projection: Arc<dyn Projection>,
+    pg_pool: Arc<PgPool>,
+    schema: String,
+}
+
+impl ProjectionHook {
+    pub fn new(projection: Arc<dyn Projection>, pg_pool: Arc<PgPool>, schema: &str) -> Result<Self> {
+        let table = projection.output_table();
+        // The table name is interpolated into the default revert.
+        if table.is_empty() || !table.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.') {
+            return Err(anyhow!("Invalid output table `{}` of projection {}", table, projection.name()));
+        }
+        Ok(Self { projection, pg_pool, schema: schema.to_string() })
+    }
+
+    /// Folds `block` and records it as folded, unless it already is.
+    async fn fold_block(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        let block_number = block.number.unwrap_or_default().as_u64() as i64;
+        let block_hash = format!("{:?}", block.hash.unwrap_or_default());
+        let mut tx = self.pg_pool.begin().await?;
+
+        // A redelivered block is already folded.
+        let recorded = sqlx::query(
+            "INSERT INTO projection_blocks (projection, chain_name, schema_name, block_hash, block_number)
+            VALUES ($1, $2, $3, $4, $5)
+            ON CONFLICT DO NOTHING",
+        )
+        .bind(self.projection.name())
+        .bind(chain_name)
+        .bind(&self.schema)
+        .bind(&block_hash)
+        .bind(block_number)
+        .execute(&mut tx)
+        .await?;
+        if recorded.rows_affected() == 0 {
+            return Ok(());
+        }
+
+        self.projection.fold(&mut tx, chain_name, &self.schema, block).await?;
+        sqlx::query(
+            "INSERT INTO projection_checkpoints (projection, chain_name, schema_name, last_block, last_block_hash, blocks_folded)
+            VALUES ($1, $2, $3, $4, $5, 1)
+            ON CONFLICT (projection, chain_name, schema_name) DO UPDATE SET
+                last_block = GREATEST(projection_checkpoints.last_block, EXCLUDED.last_block),
+                last_block_hash = CASE WHEN EXCLUDED.last_block >= projection_checkpoints.last_block
+                    THEN EXCLUDED.last_block_hash ELSE projection_checkpoints.last_block_hash END,
+                blocks_folded = projection_checkpoints.blocks_folded + 1,
+                updated_at = NOW()",
+        )
+        .bind(self.projection.name())
+        .bind(chain_name)
+        .bind(&self.schema)
+        .bind(block_number)
+        .bind(&block_hash)
+        .execute(&mut tx)
+        .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Keeps a block whose fold failed in `projection_failures`, to be folded again later.
+    async fn record_failure(&self, chain_name: &str, block: &Block<Transaction>, e: &anyhow::Error) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO projection_failures (projection, chain_name, schema_name, block_hash, block_number, block, error)
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (projection, chain_name, schema_name, block_hash) DO UPDATE SET
+                error = EXCLUDED.error, attempts = projection_failures.attempts + 1, failed_at = NOW()",
+        )
+        .bind(self.projection.name())
+        .bind(chain_name)
+        .bind(&self.schema)
+        .bind(format!("{:?}", block.hash.unwrap_or_default()))
+        .bind(block.number.unwrap_or_default().as_u64() as i64)
+        .bind(serde_json::to_value(block)?)
+        .bind(e.to_string())
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        metrics::increment_counter(
+            "projection_fold_failures_total",
+            &[("projection", self.projection.name()), ("chain", chain_name)],
+            1,
+        );
+        Ok(())
+    }
+
+    /// Folds again the oldest blocks whose fold failed, up to `RETRY_BATCH` at a time.
+    async fn retry_failures(&self, chain_name: &str) -> Result<()> {
+        let failed: Vec<(String, Json<Block<Transaction>>)> = sqlx::query_as(
+            "SELECT block_hash, block FROM projection_failures
+            WHERE projection = $1 AND chain_name = $2 AND schema_name = $3
+            ORDER BY block_number
+            LIMIT $4",
+        )
+        .bind(self.projection.name())
+        .bind(chain_name)
+        .bind(&self.schema)
+        .bind(RETRY_BATCH)
+        .fetch_all(self.pg_pool.as_ref())
+        .await?;
+        for (block_hash, Json(block)) in failed {
+            if let Err(e) = self.fold_block(chain_name, &block).await {
+                warn!(
+                    "Projection {} failed again to fold {} block {:?}: {}",
+                    self.projection.name(), chain_name, block.number, e
+                );
+                self.record_failure(chain_name, &block, &e).await?;
+                continue;
+            }
+            sqlx::query(
+                "DELETE FROM projection_failures
+                WHERE projection = $1 AND chain_name = $2 AND schema_name = $3 AND block_hash = $4",
+            )
+            .bind(self.projection.name())
+            .bind(chain_name)
+            .bind(&self.schema)
+            .bind(&block_hash)
+            .execute(self.pg_pool.as_ref())
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for ProjectionHook {
+    /// A failed fold is returned, and the block kept in `projection_failures`. Failed blocks are
+    /// folded again before each later block of the chain.
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        if let Err(e) = self.retry_failures(chain_name).await {
+            warn!("Failed to retry failed folds of projection {} for {}: {}", self.projection.name(), chain_name, e);
+        }
+        let Err(e) = self.fold_block(chain_name, block).await else {
+            return Ok(());
+        };
+        self.record_failure(chain_name, block, &e).await?;
+        Err(e.context(format!("Projection {} failed to fold the block, keeping it for a retry", self.projection.name())))
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        let mut tx = self.pg_pool.begin().await?;
+        // Only the blocks this projection folded have anything to undo. Failed ones are
+        // dropped instead of retried.
+        let hashes: Vec<String> = orphaned.iter().map(|block| block.hash.clone()).collect();
+        sqlx::query(
+            "DELETE FROM projection_failures
+            WHERE projection = $1 AND chain_name = $2 AND schema_name = $3 AND block_hash = ANY($4)",
+        )
+        .bind(self.projection.name())
+        .bind(chain_name)
+        .bind(&self.schema)
+        .bind(&hashes)
+        .execute(&mut tx)
+        .await?;
+        let folded: Vec<String> = sqlx::query_scalar(
+            "DELETE FROM projection_blocks
+            WHERE projection = $1 AND chain_name = $2 AND schema_name = $3 AND block_hash = ANY($4)
+            RETURNING block_hash",
+        )
+        .bind(self.projection.name())
+        .bind(chain_name)
+        .bind(&self.schema)
+        .bind(hashes)
+        .fetch_all(&mut tx)
+        .await?;
+        let folded: Vec<OrphanedBlock> = orphaned.iter().filter(|block| folded.contains(&block.hash)).cloned().collect();
+        if !folded.is_empty() {
+            self.projection.revert(&mut tx, chain_name, &folded).await?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,43 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+/// How long a consumer has a failed block redelivered after, e.g. while Postgres fails over.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RedeliveryConfig {
+    /// Delay after the first failed write.
+    pub initial_delay_ms: u64,
+    /// Factor the delay grows by with each further failure in a row.
+    pub multiplier: f64,
+    pub max_delay_ms: u64,
+}
+
+impl Default for RedeliveryConfig {
+    fn default() -> Self {
+        Self { initial_delay_ms: 500, multiplier: 2.0, max_delay_ms: 60_000 }
+    }
+}
+
+/// Exponential backoff over consecutive failed writes, reset by a successful one.
+#[derive(Debug, Clone)]
+pub struct RedeliveryBackoff {
+    config: RedeliveryConfig,
+    failures: u32,
+}
+
+impl RedeliveryBackoff {
+    pub fn new(config: RedeliveryConfig) -> Self {
+        Self { config, failures: 0 }
+    }
+
+    /// The redelivery delay for another failure.
+    pub fn next_delay(&mut self) -> Duration {
+        let delay = self.config.initial_delay_ms as f64 * self.config.multiplier.max(1.0).powi(self.failures as i32);
+        self.failures = self.failures.saturating_add(1);
+        Duration::from_millis(delay.min(self.config.max_delay_ms as f64) as u64)
+    }
+
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+}
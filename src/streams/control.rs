@@ -0,0 +1,119 @@
+use anyhow::Result;
+use log::{error, info};
+use serde::Serialize;
+use sqlx::{PgPool, Row};
+use std::collections::HashSet;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::metrics;
+
+/// How often running producers pick up pauses and resumes from `stream_pauses`.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// A (chain, schema) stream an operator has paused.
+#[derive(Debug, Clone, Serialize)]
+pub struct PausedStream {
+    pub chain_name: String,
+    pub schema: String,
+    pub reason: Option<String>,
+    pub paused_at: String,
+}
+
+/// Pauses production of one schema stream of a chain, e.g. traces during an RPC provider incident,
+/// while the chain's other streams keep going. Persisted, so it holds across restarts.
+pub async fn pause_stream(pg_pool: &PgPool, chain_name: &str, schema: &str, reason: Option<&str>) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO stream_pauses (chain_name, schema_name, reason) VALUES ($1, $2, $3)
+        ON CONFLICT (chain_name, schema_name) DO UPDATE SET reason = EXCLUDED.reason",
+    )
+    .bind(chain_name)
+    .bind(schema)
+    .bind(reason)
+    .execute(pg_pool)
+    .await?;
+    Ok(())
+}
+
+/// Resumes a paused stream. Returns whether it was paused.
+pub async fn resume_stream(pg_pool: &PgPool, chain_name: &str, schema: &str) -> Result<bool> {
+    let deleted = sqlx::query("DELETE FROM stream_pauses WHERE chain_name = $1 AND schema_name = $2")
+        .bind(chain_name)
+        .bind(schema)
+        .execute(pg_pool)
+        .await?;
+    Ok(deleted.rows_affected() > 0)
+}
+
+pub async fn list_paused_streams(pg_pool: &PgPool) -> Result<Vec<PausedStream>> {
+    sqlx::query(
+        "SELECT chain_name, schema_name, reason, paused_at::text AS paused_at
+        FROM stream_pauses ORDER BY chain_name, schema_name",
+    )
+    .fetch_all(pg_pool)
+    .await?
+    .iter()
+    .map(|row| {
+        Ok(PausedStream {
+            chain_name: row.try_get("chain_name")?,
+            schema: row.try_get("schema_name")?,
+            reason: row.try_get("reason")?,
+            paused_at: row.try_get("paused_at")?,
+        })
+    })
+    .collect()
+}
+
+/// The running service's view of `stream_pauses`, refreshed in the background so pauses and
+/// resumes made through the CLI take effect without a restart.
+pub struct StreamControl {
+    pg_pool: Arc<PgPool>,
+    paused: RwLock<HashSet<(String, String)>>,
+}
+
+impl StreamControl {
+    /// Loads the current pauses, so streams paused before a restart start out paused.
+    pub async fn load(pg_pool: Arc<PgPool>) -> Result<Self> {
+        let control = Self {
+            pg_pool,
+            paused: RwLock::new(HashSet::new()),
+        };
+        control.refresh().await?;
+        Ok(control)
+    }
+
+    pub fn is_paused(&self, chain_name: &str, schema: &str) -> bool {
+        let paused = self.paused.read().unwrap_or_else(|e| e.into_inner());
+        paused.contains(&(chain_name.to_string(), schema.to_string()))
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let current: HashSet<(String, String)> = list_paused_streams(&self.pg_pool)
+            .await?
+            .into_iter()
+            .map(|stream| (stream.chain_name, stream.schema))
+            .collect();
+        let mut paused = self.paused.write().unwrap_or_else(|e| e.into_inner());
+        for (chain_name, schema) in current.difference(&paused) {
+            info!("Stream {} of {} paused", schema, chain_name);
+            metrics::set_gauge("stream_paused", &[("chain", chain_name), ("schema", schema)], 1.0);
+        }
+        for (chain_name, schema) in paused.difference(&current) {
+            info!("Stream {} of {} resumed", schema, chain_name);
+            metrics::set_gauge("stream_paused", &[("chain", chain_name), ("schema", schema)], 0.0);
+        }
+        *paused = current;
+        Ok(())
+    }
+
+    /// Refreshes the pauses every few seconds. Runs forever.
+    pub async fn run(&self) -> Result<()> {
+        let mut ticker = tokio::time::interval(REFRESH_INTERVAL);
+        loop {
+            ticker.tick().await;
+            if let Err(e) = self.refresh().await {
+                error!("Failed to refresh stream pauses: {}", e);
+            }
+        }
+    }
+}
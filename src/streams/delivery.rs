@@ -0,0 +1,20 @@
+use serde::Deserialize;
+use std::time::Duration;
+
+/// Sends of a block an at-least-once producer attempts before giving up on the stream.
+pub const PUBLISH_ATTEMPTS: u32 = 5;
+/// Wait after the first failed send, doubled after each further one.
+pub const PUBLISH_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// What a schema stream guarantees about each block reaching the sink.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DeliveryMode {
+    /// Producers retry failed sends and consumers acknowledge once the block is stored, so no
+    /// block is lost but some may be stored twice.
+    #[default]
+    AtLeastOnce,
+    /// Producers send once and consumers acknowledge before storing, so a failure drops the
+    /// block instead of retrying it. For low-value streams like the mempool.
+    AtMostOnce,
+}
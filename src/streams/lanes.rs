@@ -0,0 +1,143 @@
+use log::{info, warn};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::metrics;
+
+/// How a schema stream is treated when its chain's streams compete for the node and the pipeline.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Lane {
+    /// Starts before the chain's best-effort streams, keeps the realtime share of `rpc_quota`,
+    /// and its realtime producer and consumers are restarted when they fail.
+    Critical,
+    /// Its realtime producer is restarted when it fails, like a critical one.
+    #[default]
+    Standard,
+    /// Starts after the chain's critical streams, draws on the backfill share of `rpc_quota`, and
+    /// drops realtime blocks while a critical stream of the chain falls behind.
+    BestEffort,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LanesConfig {
+    /// A critical stream taking this long from receiving a realtime block to publishing it puts
+    /// its chain under pressure, until it catches up again.
+    pub pressure_delay_secs: u64,
+    /// Longest wait between restarts of a failed producer or critical consumer.
+    pub max_restart_delay_secs: u64,
+    /// Longest wait of best-effort streams for the chain's critical streams to start. They start
+    /// anyway after it, so a critical stream that never comes up doesn't hold them back for good.
+    pub critical_start_timeout_secs: u64,
+}
+
+impl Default for LanesConfig {
+    fn default() -> Self {
+        Self { pressure_delay_secs: 30, max_restart_delay_secs: 60, critical_start_timeout_secs: 120 }
+    }
+}
+
+impl LanesConfig {
+    /// Waits before restart `attempt` (from 1), doubling up to `max_restart_delay_secs`.
+    pub fn restart_delay(&self, attempt: u32) -> Duration {
+        Duration::from_secs((1u64 << attempt.min(16).saturating_sub(1)).min(self.max_restart_delay_secs.max(1)))
+    }
+}
+
+#[derive(Default)]
+struct ChainLanes {
+    critical_streams: HashSet<String>,
+    started: HashSet<String>,
+    /// Critical streams currently behind.
+    pressured: HashSet<String>,
+}
+
+/// Tracks every chain's critical streams, for best-effort streams to start after them and to
+/// shed load while they fall behind. Shared by all producers, across their runtimes.
+pub struct LaneMonitor {
+    pressure_delay: Duration,
+    critical_start_timeout: Duration,
+    chains: Mutex<HashMap<String, ChainLanes>>,
+}
+
+impl LaneMonitor {
+    pub fn new(config: &LanesConfig) -> Self {
+        Self {
+            pressure_delay: Duration::from_secs(config.pressure_delay_secs),
+            critical_start_timeout: Duration::from_secs(config.critical_start_timeout_secs),
+            chains: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Declares a critical stream before any producer starts, so best-effort ones wait for it.
+    pub fn register_critical(&self, chain_name: &str, schema: &str) {
+        let mut chains = self.chains.lock().unwrap_or_else(|e| e.into_inner());
+        chains.entry(chain_name.to_string()).or_default().critical_streams.insert(schema.to_string());
+    }
+
+    /// Marks a critical stream's realtime producer as running.
+    pub fn critical_started(&self, chain_name: &str, schema: &str) {
+        let mut chains = self.chains.lock().unwrap_or_else(|e| e.into_inner());
+        chains.entry(chain_name.to_string()).or_default().started.insert(schema.to_string());
+    }
+
+    /// Waits until every critical stream of `chain_name` is running, or `critical_start_timeout`
+    /// has passed.
+    pub async fn wait_for_critical(&self, chain_name: &str) {
+        let deadline = Instant::now() + self.critical_start_timeout;
+        loop {
+            let missing: Vec<String> = {
+                let chains = self.chains.lock().unwrap_or_else(|e| e.into_inner());
+                chains
+                    .get(chain_name)
+                    .map(|lanes| lanes.critical_streams.difference(&lanes.started).cloned().collect())
+                    .unwrap_or_default()
+            };
+            if missing.is_empty() {
+                return;
+            }
+            if Instant::now() >= deadline {
+                warn!(
+                    "Critical streams {} of {} didn't start within {:?}, starting its best-effort streams anyway",
+                    missing.join(", "),
+                    chain_name,
+                    self.critical_start_timeout
+                );
+                metrics::increment_counter("lane_critical_start_timeouts_total", &[("chain", chain_name)], 1);
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+    }
+
+    /// Records how long a critical stream took to publish a realtime block it received.
+    pub fn report_delay(&self, chain_name: &str, schema: &str, delay: Duration) {
+        let behind = delay > self.pressure_delay;
+        let mut chains = self.chains.lock().unwrap_or_else(|e| e.into_inner());
+        let lanes = chains.entry(chain_name.to_string()).or_default();
+        let was_shedding = !lanes.pressured.is_empty();
+        if behind {
+            lanes.pressured.insert(schema.to_string());
+        } else {
+            lanes.pressured.remove(schema);
+        }
+        let shedding = !lanes.pressured.is_empty();
+        if shedding != was_shedding {
+            if shedding {
+                warn!("{} {} took {}s to publish a block, shedding best-effort streams of {}", chain_name, schema, delay.as_secs(), chain_name);
+            } else {
+                info!("Critical streams of {} caught up, resuming best-effort streams", chain_name);
+            }
+            metrics::set_gauge("lane_shedding", &[("chain", chain_name)], if shedding { 1.0 } else { 0.0 });
+        }
+    }
+
+    /// Whether best-effort streams of `chain_name` should shed load.
+    pub fn is_shedding(&self, chain_name: &str) -> bool {
+        let chains = self.chains.lock().unwrap_or_else(|e| e.into_inner());
+        chains.get(chain_name).map_or(false, |lanes| !lanes.pressured.is_empty())
+    }
+}
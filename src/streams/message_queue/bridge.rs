@@ -0,0 +1,55 @@
+use anyhow::{anyhow, Result};
+use futures::future;
+use log::{info, warn};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::metrics;
+use crate::streams::message_queue::queue::MessageQueue;
+
+/// How often progress is logged, in messages per topic.
+const LOG_EVERY: u64 = 10_000;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(30);
+
+/// Republishes `topics` from `source` to `target` through `subscription`, until the subscriptions
+/// close. Payloads are copied byte for byte, so envelopes, wire formats and encryption carry over
+/// and consumers can switch brokers without re-ingesting from the chain. Each message is
+/// acknowledged only once `target` has it, and each topic is copied in order. Messages published
+/// through `publish_block` are republished with the same key, so a deduplicating target drops
+/// the copies a restart sends again after a failed acknowledgement.
+pub async fn bridge(source: Arc<dyn MessageQueue>, target: Arc<dyn MessageQueue>, topics: &[String], subscription: &str) -> Result<()> {
+    let copies = topics.iter().map(|topic| bridge_topic(source.as_ref(), target.as_ref(), topic, subscription));
+    future::try_join_all(copies).await?;
+    Ok(())
+}
+
+async fn bridge_topic(source: &dyn MessageQueue, target: &dyn MessageQueue, topic: &str, subscription: &str) -> Result<()> {
+    let mut subscriber = source.subscriber(topic, subscription).await?;
+    let publisher = target.publisher(topic).await?;
+    info!("Bridging {} through subscription {}", topic, subscription);
+
+    let mut copied = 0u64;
+    while let Some(message) = subscriber.next().await {
+        let message = message?;
+        let mut delay = Duration::from_secs(1);
+        // Retried in place rather than nacked, so the topic stays in order.
+        loop {
+            let published = match message.block_key {
+                Some(key) => publisher.publish_block(key, message.payload.clone()).await,
+                None => publisher.publish(message.payload.clone()).await,
+            };
+            let Err(e) = published else { break };
+            warn!("Failed to republish a message of {}, retrying in {:?}: {}", topic, delay, e);
+            metrics::increment_counter("bridge_publish_failures_total", &[("topic", topic)], 1);
+            tokio::time::sleep(delay).await;
+            delay = (delay * 2).min(MAX_RETRY_DELAY);
+        }
+        subscriber.ack(&message).await?;
+        metrics::increment_counter("bridge_messages_total", &[("topic", topic)], 1);
+        copied += 1;
+        if copied % LOG_EVERY == 0 {
+            info!("Bridged {} messages of {}", copied, topic);
+        }
+    }
+    Err(anyhow!("Subscription {} to {} closed after {} messages", subscription, topic, copied))
+}
@@ -0,0 +1,198 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::utils::{hex, keccak256};
+use serde::Deserialize;
+use std::env;
+use std::time::Duration;
+use std::sync::Arc;
+
+use crate::streams::message_queue::queue::{MessageQueue, QueueMessage, QueuePublisher, QueueSubscriber};
+
+/// First byte of an encrypted payload. No wire format starts with it.
+const ENCRYPTED_FORMAT_BYTE: u8 = 0xEC;
+const KEY_ID_LEN: usize = 4;
+const NONCE_LEN: usize = 12;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct EncryptionConfig {
+    /// Topics whose payloads are encrypted, without the `persistent://public/default/` prefix,
+    /// e.g. `ETH-watchlist`. Their `-historical` and shard topics are encrypted too.
+    #[serde(default)]
+    pub topics: Vec<String>,
+    /// Env var holding the hex-encoded 256-bit key new payloads are encrypted with.
+    pub key_env: Option<String>,
+    /// Env vars holding retired keys, still used to decrypt payloads written before a rotation.
+    #[serde(default)]
+    pub previous_key_envs: Vec<String>,
+    /// Read unencrypted payloads of encrypted topics as they are, while draining what was
+    /// published before the topics were encrypted. Otherwise they are rejected, so nobody with
+    /// broker access can slip plaintext blocks in.
+    #[serde(default)]
+    pub accept_plaintext: bool,
+}
+
+struct PayloadKey {
+    /// Leading bytes of the key's hash, written in front of each payload so readers pick the
+    /// right key after a rotation.
+    id: [u8; KEY_ID_LEN],
+    cipher: Aes256Gcm,
+}
+
+impl PayloadKey {
+    fn from_env(key_env: &str) -> Result<Self> {
+        let encoded = env::var(key_env).with_context(|| format!("Failed to get payload key from environment for key `{}`", key_env))?;
+        let bytes = hex::decode(encoded.trim().trim_start_matches("0x"))
+            .with_context(|| format!("Payload key in `{}` is not hex", key_env))?;
+        if bytes.len() != 32 {
+            return Err(anyhow!("Payload key in `{}` must be 32 bytes, not {}", key_env, bytes.len()));
+        }
+        let mut id = [0u8; KEY_ID_LEN];
+        id.copy_from_slice(&keccak256(&bytes)[..KEY_ID_LEN]);
+        Ok(Self { id, cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)) })
+    }
+}
+
+/// AES-256-GCM keys of the encrypted topics: the current one and the retired ones.
+pub struct PayloadCipher {
+    topics: Vec<String>,
+    current: PayloadKey,
+    previous: Vec<PayloadKey>,
+    accept_plaintext: bool,
+}
+
+impl PayloadCipher {
+    /// `None` when no topic is encrypted.
+    pub fn new(config: &EncryptionConfig) -> Result<Option<Self>> {
+        if config.topics.is_empty() {
+            return Ok(None);
+        }
+        let key_env = config.key_env.as_deref().ok_or_else(|| anyhow!("Encrypted topics need a key_env"))?;
+        Ok(Some(Self {
+            topics: config.topics.clone(),
+            current: PayloadKey::from_env(key_env)?,
+            previous: config.previous_key_envs.iter().map(|key_env| PayloadKey::from_env(key_env)).collect::<Result<_>>()?,
+            accept_plaintext: config.accept_plaintext,
+        }))
+    }
+
+    fn covers(&self, topic: &str) -> bool {
+        let name = topic.rsplit('/').next().unwrap_or(topic);
+        self.topics
+            .iter()
+            .any(|encrypted| name == encrypted || name.strip_prefix(encrypted.as_str()).is_some_and(|rest| rest.starts_with('-')))
+    }
+
+    /// `[format byte][key id][nonce][ciphertext and tag]`, with the key id authenticated too.
+    fn encrypt(&self, payload: &[u8]) -> Result<Vec<u8>> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .current
+            .cipher
+            .encrypt(&nonce, Payload { msg: payload, aad: &self.current.id })
+            .map_err(|_| anyhow!("Failed to encrypt payload"))?;
+        let mut encrypted = Vec::with_capacity(1 + KEY_ID_LEN + NONCE_LEN + ciphertext.len());
+        encrypted.push(ENCRYPTED_FORMAT_BYTE);
+        encrypted.extend_from_slice(&self.current.id);
+        encrypted.extend_from_slice(&nonce);
+        encrypted.extend_from_slice(&ciphertext);
+        Ok(encrypted)
+    }
+
+    /// Decrypts a payload written by `encrypt`. Unencrypted payloads are only passed through as
+    /// they are with `accept_plaintext`.
+    fn decrypt(&self, payload: Vec<u8>) -> Result<Vec<u8>> {
+        if payload.first() != Some(&ENCRYPTED_FORMAT_BYTE) {
+            if self.accept_plaintext {
+                return Ok(payload);
+            }
+            return Err(anyhow!("Payload of an encrypted topic isn't encrypted; set accept_plaintext to read it"));
+        }
+        if payload.len() < 1 + KEY_ID_LEN + NONCE_LEN {
+            return Err(anyhow!("Encrypted payload is truncated"));
+        }
+        let (id, rest) = payload[1..].split_at(KEY_ID_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+        let key = std::iter::once(&self.current)
+            .chain(&self.previous)
+            .find(|key| key.id == id)
+            .ok_or_else(|| anyhow!("Payload was encrypted with unknown key {}", hex::encode(id)))?;
+        key.cipher
+            .decrypt(Nonce::from_slice(nonce), Payload { msg: ciphertext, aad: id })
+            .map_err(|_| anyhow!("Failed to decrypt payload with key {}", hex::encode(id)))
+    }
+}
+
+/// Encrypts payloads published to the configured topics and decrypts them on the way out, so
+/// their contents at rest in the broker are unreadable without the key. Other topics pass
+/// through untouched.
+pub struct EncryptedQueue {
+    inner: Arc<dyn MessageQueue>,
+    cipher: Arc<PayloadCipher>,
+}
+
+impl EncryptedQueue {
+    pub fn new(inner: Arc<dyn MessageQueue>, cipher: PayloadCipher) -> Self {
+        Self { inner, cipher: Arc::new(cipher) }
+    }
+}
+
+#[async_trait]
+impl MessageQueue for EncryptedQueue {
+    async fn publisher(&self, topic: &str) -> Result<Box<dyn QueuePublisher>> {
+        let publisher = self.inner.publisher(topic).await?;
+        if !self.cipher.covers(topic) {
+            return Ok(publisher);
+        }
+        Ok(Box::new(EncryptingPublisher { inner: publisher, cipher: Arc::clone(&self.cipher) }))
+    }
+
+    async fn subscriber(&self, topic: &str, subscription: &str) -> Result<Box<dyn QueueSubscriber>> {
+        let subscriber = self.inner.subscriber(topic, subscription).await?;
+        if !self.cipher.covers(topic) {
+            return Ok(subscriber);
+        }
+        Ok(Box::new(DecryptingSubscriber { inner: subscriber, cipher: Arc::clone(&self.cipher) }))
+    }
+}
+
+struct EncryptingPublisher {
+    inner: Box<dyn QueuePublisher>,
+    cipher: Arc<PayloadCipher>,
+}
+
+#[async_trait]
+impl QueuePublisher for EncryptingPublisher {
+    async fn publish(&self, payload: Vec<u8>) -> Result<()> {
+        self.inner.publish(self.cipher.encrypt(&payload)?).await
+    }
+
+    async fn publish_block(&self, block_number: u64, payload: Vec<u8>) -> Result<()> {
+        self.inner.publish_block(block_number, self.cipher.encrypt(&payload)?).await
+    }
+}
+
+struct DecryptingSubscriber {
+    inner: Box<dyn QueueSubscriber>,
+    cipher: Arc<PayloadCipher>,
+}
+
+#[async_trait]
+impl QueueSubscriber for DecryptingSubscriber {
+    async fn next(&mut self) -> Option<Result<QueueMessage>> {
+        let message = match self.inner.next().await? {
+            Ok(message) => message,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(self.cipher.decrypt(message.payload).map(|payload| QueueMessage { id: message.id, payload, block_key: message.block_key }))
+    }
+
+    async fn ack(&mut self, message: &QueueMessage) -> Result<()> {
+        self.inner.ack(message).await
+    }
+
+    async fn nack(&mut self, messages: &[QueueMessage], delay: Duration) -> Result<()> {
+        self.inner.nack(messages, delay).await
+    }
+}
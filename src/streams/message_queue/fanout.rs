@@ -0,0 +1,184 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures::future;
+use log::warn;
+use serde::Deserialize;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Instant;
+use tokio::sync::{Mutex, OnceCell};
+
+use crate::metrics;
+use crate::server::dashboard;
+use crate::streams::consumers::redelivery::{RedeliveryBackoff, RedeliveryConfig};
+use crate::streams::message_queue::queue::{MessageQueue, QueuePublisher, QueueSubscriber};
+use crate::QueueType;
+
+/// A broker a chain also publishes to, next to `queue_type`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MirrorQueueConfig {
+    pub queue_type: QueueType,
+    /// Fail the publish, so the producer retries it, when this broker rejects it. Otherwise the
+    /// failure is counted and the message is missing from this broker only.
+    #[serde(default)]
+    pub required: bool,
+}
+
+/// A broker a `FanOutQueue` publishes to.
+pub struct Destination {
+    pub name: String,
+    pub queue: Arc<dyn MessageQueue>,
+    pub required: bool,
+}
+
+/// Publishes every message to the primary broker and each mirror at once, tracking each one's
+/// successes and failures separately in `queue_publishes_total` and
+/// `queue_publish_failures_total`. Subscribers read from the primary broker only, so the
+/// pipeline's consumers are unaffected by the mirrors.
+pub struct FanOutQueue {
+    primary: Destination,
+    mirrors: Vec<Destination>,
+}
+
+impl FanOutQueue {
+    pub fn new(primary: Destination, mirrors: Vec<Destination>) -> Self {
+        Self { primary, mirrors }
+    }
+}
+
+#[async_trait]
+impl MessageQueue for FanOutQueue {
+    async fn publisher(&self, topic: &str) -> Result<Box<dyn QueuePublisher>> {
+        let mut targets = vec![Target::connected(&self.primary, self.primary.queue.publisher(topic).await?)];
+        for mirror in &self.mirrors {
+            match mirror.queue.publisher(topic).await {
+                Ok(publisher) => targets.push(Target::connected(mirror, publisher)),
+                Err(e) if mirror.required => return Err(e.context(format!("Failed to create publisher for {} on {}", topic, mirror.name))),
+                Err(e) => {
+                    warn!("Failed to create publisher for {} on {}, retrying with backoff as it's published to: {}", topic, mirror.name, e);
+                    metrics::increment_counter("queue_publish_failures_total", &[("queue", &mirror.name), ("topic", topic)], 1);
+                    targets.push(Target::disconnected(mirror));
+                }
+            }
+        }
+        Ok(Box::new(FanOutPublisher { topic: topic.to_string(), targets }))
+    }
+
+    async fn subscriber(&self, topic: &str, subscription: &str) -> Result<Box<dyn QueueSubscriber>> {
+        self.primary.queue.subscriber(topic, subscription).await
+    }
+}
+
+/// How long a mirror whose publisher couldn't be created waits before it is created again.
+fn reconnect_backoff() -> RedeliveryBackoff {
+    RedeliveryBackoff::new(RedeliveryConfig { initial_delay_ms: 1_000, multiplier: 2.0, max_delay_ms: 60_000 })
+}
+
+/// A broker's publisher, or, for a mirror whose publisher couldn't be created, when to try again.
+struct Target {
+    name: String,
+    required: bool,
+    queue: Arc<dyn MessageQueue>,
+    publisher: OnceCell<Box<dyn QueuePublisher>>,
+    retry: Mutex<(RedeliveryBackoff, Instant)>,
+}
+
+impl Target {
+    fn connected(destination: &Destination, publisher: Box<dyn QueuePublisher>) -> Self {
+        Self {
+            name: destination.name.clone(),
+            required: destination.required,
+            queue: Arc::clone(&destination.queue),
+            publisher: OnceCell::from(publisher),
+            retry: Mutex::new((reconnect_backoff(), Instant::now())),
+        }
+    }
+
+    fn disconnected(destination: &Destination) -> Self {
+        let mut backoff = reconnect_backoff();
+        let retry_at = Instant::now() + backoff.next_delay();
+        Self {
+            name: destination.name.clone(),
+            required: destination.required,
+            queue: Arc::clone(&destination.queue),
+            publisher: OnceCell::new(),
+            retry: Mutex::new((backoff, retry_at)),
+        }
+    }
+
+    /// The publisher, created again once the backoff since the last failed attempt has passed.
+    /// Until then, and while another publish is creating it, publishing to this broker fails.
+    async fn publisher(&self, topic: &str) -> Result<&dyn QueuePublisher> {
+        if let Some(publisher) = self.publisher.get() {
+            return Ok(publisher.as_ref());
+        }
+        let mut retry = self.retry.try_lock().map_err(|_| anyhow!("No publisher yet, one is being created"))?;
+        if let Some(publisher) = self.publisher.get() {
+            return Ok(publisher.as_ref());
+        }
+        let (backoff, retry_at) = &mut *retry;
+        let now = Instant::now();
+        if now < *retry_at {
+            return Err(anyhow!("No publisher, creating one again in {:?}", *retry_at - now));
+        }
+        match self.queue.publisher(topic).await {
+            Ok(publisher) => {
+                backoff.reset();
+                Ok(self.publisher.get_or_init(|| async move { publisher }).await.as_ref())
+            }
+            Err(e) => {
+                let delay = backoff.next_delay();
+                *retry_at = Instant::now() + delay;
+                Err(e.context(format!("Failed to create publisher, retrying in {:?}", delay)))
+            }
+        }
+    }
+}
+
+struct FanOutPublisher {
+    topic: String,
+    targets: Vec<Target>,
+}
+
+impl FanOutPublisher {
+    /// Runs `send` on every broker concurrently. Fails if a required one failed.
+    async fn publish_all<'a, F, Fut>(&'a self, send: F) -> Result<()>
+    where
+        F: Fn(&'a dyn QueuePublisher) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let send = &send;
+        let results = future::join_all(self.targets.iter().map(|target| async move { send(target.publisher(&self.topic).await?).await })).await;
+        let mut failed = Vec::new();
+        for (target, result) in self.targets.iter().zip(results) {
+            let queue = &target.name;
+            match result {
+                Ok(()) => metrics::increment_counter("queue_publishes_total", &[("queue", queue), ("topic", &self.topic)], 1),
+                Err(e) => {
+                    metrics::increment_counter("queue_publish_failures_total", &[("queue", queue), ("topic", &self.topic)], 1);
+                    let message = format!("Failed to publish to {} on {}: {}", self.topic, queue, e);
+                    warn!("{}", message);
+                    dashboard::record_error(queue, &message);
+                    if target.required {
+                        failed.push(queue.as_str());
+                    }
+                }
+            }
+        }
+        if !failed.is_empty() {
+            return Err(anyhow!("Failed to publish to {} on {}", self.topic, failed.join(", ")));
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl QueuePublisher for FanOutPublisher {
+    async fn publish(&self, payload: Vec<u8>) -> Result<()> {
+        self.publish_all(|publisher| publisher.publish(payload.clone())).await
+    }
+
+    async fn publish_block(&self, block_number: u64, payload: Vec<u8>) -> Result<()> {
+        self.publish_all(|publisher| publisher.publish_block(block_number, payload.clone())).await
+    }
+}
@@ -0,0 +1,230 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+#[derive(Debug, Deserialize)]
+pub struct KafkaConfig {
+    /// Env var holding the bootstrap servers, e.g. `localhost:9092`.
+    #[serde(default = "default_brokers_env")]
+    pub brokers_env: String,
+    /// Further librdkafka properties for producers and consumers, e.g. `security.protocol`.
+    #[serde(default)]
+    pub options: HashMap<String, String>,
+}
+
+fn default_brokers_env() -> String {
+    "KAFKA_BROKERS".to_string()
+}
+
+impl Default for KafkaConfig {
+    fn default() -> Self {
+        Self { brokers_env: default_brokers_env(), options: HashMap::new() }
+    }
+}
+
+/// Kafka topic names only allow letters, digits and `._-`, so Pulsar-style names are cut to their
+/// last path segment: `persistent://public/default/ETH-blocks` becomes `ETH-blocks`.
+#[cfg(feature = "kafka")]
+fn topic_name(topic: &str) -> &str {
+    topic.rsplit('/').next().unwrap_or(topic)
+}
+
+#[cfg(feature = "kafka")]
+pub use client::KafkaQueue;
+
+#[cfg(feature = "kafka")]
+mod client {
+    use anyhow::{anyhow, Context, Result};
+    use async_trait::async_trait;
+    use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
+    use rdkafka::message::{Header, Headers, OwnedHeaders};
+    use rdkafka::producer::{FutureProducer, FutureRecord};
+    use rdkafka::util::Timeout;
+    use rdkafka::{ClientConfig, Message, Offset, TopicPartitionList};
+    use std::collections::{BTreeSet, HashMap};
+    use std::env;
+    use std::time::Duration;
+
+    use super::{topic_name, KafkaConfig};
+    use crate::streams::message_queue::queue::{MessageQueue, QueueMessage, QueuePublisher, QueueSubscriber, BLOCK_KEY_HEADER};
+
+    const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// A `MessageQueue` over Kafka. Every message of a topic is keyed by the topic name, so it
+    /// lands on a single partition and stays in order. Producers are idempotent, so the broker
+    /// drops sends the client retries itself; `publish_block` keys travel in a header. Each
+    /// subscription is a consumer group that commits offsets itself, up to the oldest message
+    /// still unacknowledged.
+    pub struct KafkaQueue {
+        config: ClientConfig,
+    }
+
+    impl KafkaQueue {
+        pub async fn new(config: &KafkaConfig) -> Result<Self> {
+            let brokers = env::var(&config.brokers_env)
+                .with_context(|| format!("Failed to get Kafka brokers from environment for key `{}`", config.brokers_env))?;
+            let mut client_config = ClientConfig::new();
+            client_config.set("bootstrap.servers", brokers);
+            for (key, value) in &config.options {
+                client_config.set(key, value);
+            }
+            // Clients connect lazily, so the cluster is asked for its metadata to fail here rather
+            // than on the first publish.
+            let producer: FutureProducer = client_config.create().context("Failed to create Kafka client")?;
+            tokio::task::spawn_blocking(move || producer.client().fetch_metadata(None, Timeout::After(SEND_TIMEOUT)))
+                .await?
+                .context("Failed to connect to Kafka")?;
+            Ok(Self { config: client_config })
+        }
+    }
+
+    struct KafkaPublisher {
+        producer: FutureProducer,
+        topic: String,
+    }
+
+    impl KafkaPublisher {
+        async fn send(&self, payload: &[u8], headers: Option<OwnedHeaders>) -> Result<()> {
+            let mut record = FutureRecord::to(&self.topic).key(&self.topic).payload(payload);
+            if let Some(headers) = headers {
+                record = record.headers(headers);
+            }
+            self.producer
+                .send(record, Timeout::After(SEND_TIMEOUT))
+                .await
+                .map_err(|(e, _)| anyhow!("Failed to publish to Kafka topic {}: {}", self.topic, e))?;
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl QueuePublisher for KafkaPublisher {
+        async fn publish(&self, payload: Vec<u8>) -> Result<()> {
+            self.send(&payload, None).await
+        }
+
+        async fn publish_block(&self, block_number: u64, payload: Vec<u8>) -> Result<()> {
+            let key = block_number.to_string();
+            let headers = OwnedHeaders::new().insert(Header { key: BLOCK_KEY_HEADER, value: Some(key.as_bytes()) });
+            self.send(&payload, Some(headers)).await
+        }
+    }
+
+    struct KafkaSubscriber {
+        consumer: StreamConsumer,
+        topic: String,
+        // Delivered but not yet acknowledged messages, keyed by `QueueMessage::id`.
+        pending: HashMap<u64, (i32, i64)>,
+        // The same messages as (partition, offset), so commits stop short of the oldest.
+        unacked: BTreeSet<(i32, i64)>,
+        // Highest offset delivered per partition since it was last rewound.
+        delivered: HashMap<i32, i64>,
+        next_id: u64,
+    }
+
+    #[async_trait]
+    impl QueueSubscriber for KafkaSubscriber {
+        async fn next(&mut self) -> Option<Result<QueueMessage>> {
+            let message = match self.consumer.recv().await {
+                Ok(message) => message,
+                Err(e) => return Some(Err(anyhow!(e))),
+            };
+            let id = self.next_id;
+            self.next_id += 1;
+            let payload = message.payload().unwrap_or_default().to_vec();
+            let block_key = message.headers().and_then(|headers| {
+                headers
+                    .iter()
+                    .find(|header| header.key == BLOCK_KEY_HEADER)
+                    .and_then(|header| header.value)
+                    .and_then(|value| std::str::from_utf8(value).ok()?.parse().ok())
+            });
+            let position = (message.partition(), message.offset());
+            self.pending.insert(id, position);
+            self.unacked.insert(position);
+            self.delivered.insert(position.0, position.1);
+            Some(Ok(QueueMessage { id, payload, block_key }))
+        }
+
+        async fn ack(&mut self, message: &QueueMessage) -> Result<()> {
+            let (partition, offset) = self
+                .pending
+                .remove(&message.id)
+                .ok_or_else(|| anyhow!("Message {} was not delivered by this subscriber", message.id))?;
+            self.unacked.remove(&(partition, offset));
+            // A committed offset is the next message to read, so it can only move up to the
+            // oldest message of the partition that is still unacknowledged.
+            let commit = match self.unacked.range((partition, i64::MIN)..=(partition, i64::MAX)).next() {
+                Some((_, oldest)) => *oldest,
+                None => self.delivered.get(&partition).map_or(offset, |last| *last) + 1,
+            };
+            let mut offsets = TopicPartitionList::new();
+            offsets.add_partition_offset(&self.topic, partition, Offset::Offset(commit))?;
+            self.consumer.commit(&offsets, CommitMode::Async)?;
+            Ok(())
+        }
+
+        /// Seeks each partition back to the first of `messages`, so it and every message after
+        /// it are delivered again.
+        async fn nack(&mut self, messages: &[QueueMessage], delay: Duration) -> Result<()> {
+            let mut rewind: HashMap<i32, i64> = HashMap::new();
+            for message in messages {
+                let (partition, offset) = self
+                    .pending
+                    .remove(&message.id)
+                    .ok_or_else(|| anyhow!("Message {} was not delivered by this subscriber", message.id))?;
+                self.unacked.remove(&(partition, offset));
+                let first = rewind.entry(partition).or_insert(offset);
+                *first = (*first).min(offset);
+            }
+            // Everything from the first nacked message on is delivered again, so none of it may
+            // be committed until then.
+            for (partition, offset) in &rewind {
+                self.unacked.retain(|(p, o)| p != partition || o < offset);
+                self.delivered.insert(*partition, offset - 1);
+            }
+            tokio::time::sleep(delay).await;
+            for (partition, offset) in rewind {
+                self.consumer.seek(&self.topic, partition, Offset::Offset(offset), Timeout::After(SEND_TIMEOUT))?;
+            }
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl MessageQueue for KafkaQueue {
+        async fn publisher(&self, topic: &str) -> Result<Box<dyn QueuePublisher>> {
+            let producer = self
+                .config
+                .clone()
+                .set("enable.idempotence", "true")
+                .create()
+                .context("Failed to create Kafka producer")?;
+            Ok(Box::new(KafkaPublisher { producer, topic: topic_name(topic).to_string() }))
+        }
+
+        async fn subscriber(&self, topic: &str, subscription: &str) -> Result<Box<dyn QueueSubscriber>> {
+            let topic = topic_name(topic);
+            // Group ids are cluster-wide, so they are prefixed with the topic like Pulsar's
+            // subscriptions are scoped to it.
+            let consumer: StreamConsumer = self
+                .config
+                .clone()
+                .set("group.id", format!("{}-{}", topic, subscription))
+                .set("enable.auto.commit", "false")
+                .set("auto.offset.reset", "earliest")
+                .create()
+                .context("Failed to create Kafka consumer")?;
+            consumer
+                .subscribe(&[topic])
+                .with_context(|| format!("Failed to subscribe to Kafka topic {}", topic))?;
+            Ok(Box::new(KafkaSubscriber {
+                consumer,
+                topic: topic.to_string(),
+                pending: HashMap::new(),
+                unacked: BTreeSet::new(),
+                delivered: HashMap::new(),
+                next_id: 0,
+            }))
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use serde::Deserialize;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct KinesisConfig {
+    /// AWS region of the streams. Defaults to the region of the environment's AWS config.
+    pub region: Option<String>,
+}
+
+/// Kinesis stream names only allow letters, digits and `_.-`, so Pulsar-style names are cut to
+/// their last path segment: `persistent://public/default/ETH-blocks` becomes `ETH-blocks`.
+#[cfg(feature = "kinesis")]
+fn stream_name(topic: &str) -> &str {
+    topic.rsplit('/').next().unwrap_or(topic)
+}
+
+#[cfg(feature = "kinesis")]
+pub use client::KinesisQueue;
+
+#[cfg(feature = "kinesis")]
+mod client {
+    use anyhow::{anyhow, Context, Result};
+    use async_trait::async_trait;
+    use aws_config::{BehaviorVersion, Region};
+    use aws_sdk_kinesis::primitives::Blob;
+    use aws_sdk_kinesis::Client;
+    use tokio::sync::Mutex;
+
+    use super::{stream_name, KinesisConfig};
+    use crate::streams::message_queue::queue::{MessageQueue, QueuePublisher, QueueSubscriber};
+
+    /// Every record is sent with this explicit hash key, which lies in the first shard's range,
+    /// so a stream's records keep the order they were published in whatever their partition key.
+    const FIRST_SHARD_HASH_KEY: &str = "0";
+
+    /// A `MessageQueue` that publishes to Kinesis data streams, for mirroring and bridging topics
+    /// to consumers on AWS. Streams are not created, so each topic needs one of the same name.
+    /// Records of `publish_block` carry the key as their partition key; the rest carry the stream
+    /// name. Kinesis doesn't deduplicate, so a retried publish may be stored twice. This service
+    /// doesn't read from Kinesis, so it can't be the `queue_type`.
+    pub struct KinesisQueue {
+        client: Client,
+    }
+
+    impl KinesisQueue {
+        pub async fn new(config: &KinesisConfig) -> Result<Self> {
+            let mut loader = aws_config::defaults(BehaviorVersion::latest());
+            if let Some(region) = &config.region {
+                loader = loader.region(Region::new(region.clone()));
+            }
+            let client = Client::new(&loader.load().await);
+            client.list_streams().limit(1).send().await.context("Failed to connect to Kinesis")?;
+            Ok(Self { client })
+        }
+    }
+
+    struct KinesisPublisher {
+        client: Client,
+        stream: String,
+        // Sequence number of the last record sent, so the next one is ordered after it.
+        last_sequence_number: Mutex<Option<String>>,
+    }
+
+    impl KinesisPublisher {
+        async fn send(&self, partition_key: String, payload: Vec<u8>) -> Result<()> {
+            let mut last_sequence_number = self.last_sequence_number.lock().await;
+            let output = self
+                .client
+                .put_record()
+                .stream_name(&self.stream)
+                .data(Blob::new(payload))
+                .partition_key(partition_key)
+                .explicit_hash_key(FIRST_SHARD_HASH_KEY)
+                .set_sequence_number_for_ordering(last_sequence_number.clone())
+                .send()
+                .await
+                .with_context(|| format!("Failed to publish to Kinesis stream {}", self.stream))?;
+            *last_sequence_number = Some(output.sequence_number().to_string());
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl QueuePublisher for KinesisPublisher {
+        async fn publish(&self, payload: Vec<u8>) -> Result<()> {
+            self.send(self.stream.clone(), payload).await
+        }
+
+        async fn publish_block(&self, block_number: u64, payload: Vec<u8>) -> Result<()> {
+            self.send(block_number.to_string(), payload).await
+        }
+    }
+
+    #[async_trait]
+    impl MessageQueue for KinesisQueue {
+        async fn publisher(&self, topic: &str) -> Result<Box<dyn QueuePublisher>> {
+            Ok(Box::new(KinesisPublisher {
+                client: self.client.clone(),
+                stream: stream_name(topic).to_string(),
+                last_sequence_number: Mutex::new(None),
+            }))
+        }
+
+        async fn subscriber(&self, topic: &str, _subscription: &str) -> Result<Box<dyn QueueSubscriber>> {
+            Err(anyhow!("Kinesis is only published to, so {} can't be read from it", topic))
+        }
+    }
+}
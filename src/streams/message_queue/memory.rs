@@ -0,0 +1,136 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+use crate::streams::message_queue::queue::{MessageQueue, QueueMessage, QueuePublisher, QueueSubscriber};
+
+#[derive(Default)]
+struct Topic {
+    // Payloads with the key they were published with through `publish_block`.
+    messages: Mutex<Vec<(Vec<u8>, Option<u64>)>>,
+    // Next unacknowledged offset per subscription name.
+    acked: Mutex<HashMap<String, u64>>,
+    published: Notify,
+}
+
+/// A process-local `MessageQueue`. Topics are append-only logs that are never trimmed, and new
+/// subscriptions start from the earliest message, like the Pulsar consumers do.
+#[derive(Default)]
+pub struct InMemoryQueue {
+    topics: Mutex<HashMap<String, Arc<Topic>>>,
+}
+
+impl InMemoryQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn topic(&self, name: &str) -> Arc<Topic> {
+        let mut topics = self.topics.lock().unwrap_or_else(|e| e.into_inner());
+        Arc::clone(topics.entry(name.to_string()).or_default())
+    }
+
+    /// Number of messages ever published to `topic`.
+    pub fn len(&self, topic: &str) -> usize {
+        self.topic(topic).messages.lock().unwrap_or_else(|e| e.into_inner()).len()
+    }
+}
+
+struct InMemoryPublisher {
+    topic: Arc<Topic>,
+}
+
+#[async_trait]
+impl QueuePublisher for InMemoryPublisher {
+    async fn publish(&self, payload: Vec<u8>) -> Result<()> {
+        self.push(payload, None);
+        Ok(())
+    }
+
+    async fn publish_block(&self, block_number: u64, payload: Vec<u8>) -> Result<()> {
+        self.push(payload, Some(block_number));
+        Ok(())
+    }
+}
+
+impl InMemoryPublisher {
+    fn push(&self, payload: Vec<u8>, block_key: Option<u64>) {
+        self.topic.messages.lock().unwrap_or_else(|e| e.into_inner()).push((payload, block_key));
+        self.topic.published.notify_waiters();
+    }
+}
+
+struct InMemorySubscriber {
+    topic: Arc<Topic>,
+    subscription: String,
+    position: u64,
+}
+
+#[async_trait]
+impl QueueSubscriber for InMemorySubscriber {
+    async fn next(&mut self) -> Option<Result<QueueMessage>> {
+        loop {
+            // Register for the wakeup before checking, so a publish in between is not missed.
+            let published = self.topic.published.notified();
+            tokio::pin!(published);
+            published.as_mut().enable();
+
+            let message = self
+                .topic
+                .messages
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .get(self.position as usize)
+                .cloned();
+            if let Some((payload, block_key)) = message {
+                let id = self.position;
+                self.position += 1;
+                return Some(Ok(QueueMessage { id, payload, block_key }));
+            }
+
+            published.await;
+        }
+    }
+
+    async fn ack(&mut self, message: &QueueMessage) -> Result<()> {
+        let mut acked = self.topic.acked.lock().unwrap_or_else(|e| e.into_inner());
+        let offset = acked.entry(self.subscription.clone()).or_default();
+        *offset = (*offset).max(message.id + 1);
+        Ok(())
+    }
+
+    /// Rewinds to the first of `messages`, so it and every message after it are delivered again.
+    async fn nack(&mut self, messages: &[QueueMessage], delay: Duration) -> Result<()> {
+        tokio::time::sleep(delay).await;
+        if let Some(first) = messages.iter().map(|message| message.id).min() {
+            self.position = self.position.min(first);
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageQueue for InMemoryQueue {
+    async fn publisher(&self, topic: &str) -> Result<Box<dyn QueuePublisher>> {
+        Ok(Box::new(InMemoryPublisher { topic: self.topic(topic) }))
+    }
+
+    async fn subscriber(&self, topic: &str, subscription: &str) -> Result<Box<dyn QueueSubscriber>> {
+        let topic = self.topic(topic);
+        let position = topic
+            .acked
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(subscription)
+            .copied()
+            .unwrap_or_default();
+        Ok(Box::new(InMemorySubscriber {
+            topic,
+            subscription: subscription.to_string(),
+            position,
+        }))
+    }
+}
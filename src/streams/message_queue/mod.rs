@@ -1 +1,11 @@
+pub mod queue;
 pub mod pulsar;
+pub mod pulsar_admin;
+pub mod pubsub;
+pub mod rabbitmq;
+pub mod kafka;
+pub mod kinesis;
+pub mod memory;
+pub mod encryption;
+pub mod fanout;
+pub mod bridge;
@@ -0,0 +1,231 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use google_cloud_googleapis::pubsub::v1::PubsubMessage;
+use google_cloud_pubsub::client::{Client, ClientConfig};
+use google_cloud_pubsub::publisher::Publisher;
+use google_cloud_pubsub::subscriber::ReceivedMessage;
+use google_cloud_pubsub::subscription::{MessageStream, SubscriptionConfig};
+use log::warn;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use crate::streams::message_queue::queue::{MessageQueue, QueueMessage, QueuePublisher, QueueSubscriber, BLOCK_KEY_HEADER};
+
+#[derive(Debug, Deserialize)]
+pub struct PubSubConfig {
+    /// GCP project of the topics. Defaults to the project of the credentials in
+    /// `GOOGLE_APPLICATION_CREDENTIALS`.
+    pub project_id: Option<String>,
+    /// Ack deadline of created subscriptions. Messages still unacknowledged after half of it
+    /// get their deadline extended, so slow sinks don't cause redeliveries.
+    #[serde(default = "default_ack_deadline_secs")]
+    pub ack_deadline_secs: u32,
+}
+
+fn default_ack_deadline_secs() -> u32 {
+    60
+}
+
+impl Default for PubSubConfig {
+    fn default() -> Self {
+        Self { project_id: None, ack_deadline_secs: default_ack_deadline_secs() }
+    }
+}
+
+/// Pub/Sub topic ids only allow letters, digits and `-_.~+%`, so Pulsar-style names are cut to
+/// their last path segment: `persistent://public/default/ETH-blocks` becomes `ETH-blocks`.
+fn topic_id(topic: &str) -> &str {
+    topic.rsplit('/').next().unwrap_or(topic)
+}
+
+/// A `MessageQueue` over Google Cloud Pub/Sub. Topics and subscriptions are created on first
+/// use. Every message is published with its topic as ordering key and subscriptions have
+/// message ordering enabled, so each chain's blocks arrive in the order they were published.
+pub struct PubSubQueue {
+    client: Client,
+    ack_deadline_secs: u32,
+}
+
+impl PubSubQueue {
+    pub async fn new(config: &PubSubConfig) -> Result<Self> {
+        let mut client_config = ClientConfig::default().with_auth().await.context("Failed to authenticate to Pub/Sub")?;
+        if let Some(project_id) = &config.project_id {
+            client_config.project_id = Some(project_id.clone());
+        }
+        let client = Client::new(client_config).await.context("Failed to create Pub/Sub client")?;
+        Ok(Self { client, ack_deadline_secs: config.ack_deadline_secs.clamp(10, 600) })
+    }
+}
+
+struct PubSubPublisher {
+    publisher: Publisher,
+    ordering_key: String,
+}
+
+#[async_trait]
+impl QueuePublisher for PubSubPublisher {
+    async fn publish(&self, payload: Vec<u8>) -> Result<()> {
+        self.send(PubsubMessage { data: payload, ordering_key: self.ordering_key.clone(), ..Default::default() }).await
+    }
+
+    async fn publish_block(&self, block_number: u64, payload: Vec<u8>) -> Result<()> {
+        let attributes = HashMap::from([(BLOCK_KEY_HEADER.to_string(), block_number.to_string())]);
+        self.send(PubsubMessage { data: payload, ordering_key: self.ordering_key.clone(), attributes, ..Default::default() }).await
+    }
+}
+
+impl PubSubPublisher {
+    async fn send(&self, message: PubsubMessage) -> Result<()> {
+        let result = self.publisher.publish(message).await.get().await;
+        if let Err(e) = result {
+            // A failed publish pauses its ordering key; later messages may go through once it is
+            // resumed, while the caller retries this one.
+            self.publisher.resume_publish(&self.ordering_key);
+            return Err(anyhow!("Failed to publish to Pub/Sub: {}", e));
+        }
+        Ok(())
+    }
+}
+
+/// Delivered but not yet acknowledged messages, keyed by `QueueMessage::id`, with when their
+/// deadline was last set.
+type Pending = Arc<Mutex<HashMap<u64, (ReceivedMessage, Instant)>>>;
+
+struct PubSubSubscriber {
+    stream: MessageStream,
+    pending: Pending,
+    next_id: u64,
+    extender: tokio::task::JoinHandle<()>,
+}
+
+impl Drop for PubSubSubscriber {
+    fn drop(&mut self) {
+        self.extender.abort();
+    }
+}
+
+/// Extends the deadline of messages held for more than half of it, until the subscriber is
+/// dropped.
+fn extend_deadlines(pending: Pending, ack_deadline_secs: u32) -> tokio::task::JoinHandle<()> {
+    let deadline = Duration::from_secs(ack_deadline_secs as u64);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(deadline / 4);
+        loop {
+            interval.tick().await;
+            // Snapshotted so acks aren't held up behind the extension calls.
+            let due: Vec<(u64, ReceivedMessage)> = pending
+                .lock()
+                .await
+                .iter()
+                .filter(|(_, (_, deadline_set))| deadline_set.elapsed() >= deadline / 2)
+                .map(|(id, (message, _))| (*id, message.clone()))
+                .collect();
+            for (id, message) in due {
+                let extended_at = Instant::now();
+                match message.modify_ack_deadline(ack_deadline_secs as i32).await {
+                    // Messages acked or nacked in the meantime are gone from `pending`.
+                    Ok(()) => {
+                        if let Some((_, deadline_set)) = pending.lock().await.get_mut(&id) {
+                            *deadline_set = extended_at;
+                        }
+                    }
+                    Err(e) => warn!("Failed to extend Pub/Sub ack deadline: {}", e),
+                }
+            }
+        }
+    })
+}
+
+#[async_trait]
+impl QueueSubscriber for PubSubSubscriber {
+    async fn next(&mut self) -> Option<Result<QueueMessage>> {
+        let message = self.stream.next().await?;
+        let id = self.next_id;
+        self.next_id += 1;
+        let payload = message.message.data.clone();
+        let block_key = message.message.attributes.get(BLOCK_KEY_HEADER).and_then(|key| key.parse().ok());
+        self.pending.lock().await.insert(id, (message, Instant::now()));
+        Some(Ok(QueueMessage { id, payload, block_key }))
+    }
+
+    async fn ack(&mut self, message: &QueueMessage) -> Result<()> {
+        let (delivered, _) = self
+            .pending
+            .lock()
+            .await
+            .remove(&message.id)
+            .ok_or_else(|| anyhow!("Message {} was not delivered by this subscriber", message.id))?;
+        delivered.ack().await?;
+        Ok(())
+    }
+
+    async fn nack(&mut self, messages: &[QueueMessage], delay: Duration) -> Result<()> {
+        let delivered = {
+            let mut pending = self.pending.lock().await;
+            messages
+                .iter()
+                .map(|message| {
+                    pending
+                        .remove(&message.id)
+                        .map(|(delivered, _)| delivered)
+                        .ok_or_else(|| anyhow!("Message {} was not delivered by this subscriber", message.id))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        // Out of `pending`, deadlines are no longer extended and the messages are redelivered
+        // once they pass.
+        // Deadlines are in whole seconds; rounding down would redeliver sub-second delays at once.
+        let deadline_secs = delay.as_millis().div_ceil(1000).min(600) as i32;
+        for delivered in delivered {
+            delivered.modify_ack_deadline(deadline_secs).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageQueue for PubSubQueue {
+    async fn publisher(&self, topic: &str) -> Result<Box<dyn QueuePublisher>> {
+        let topic_id = topic_id(topic);
+        let topic = self.client.topic(topic_id);
+        if !topic.exists(None).await? {
+            topic.create(None, None).await.with_context(|| format!("Failed to create Pub/Sub topic {}", topic_id))?;
+        }
+        Ok(Box::new(PubSubPublisher { publisher: topic.new_publisher(None), ordering_key: topic_id.to_string() }))
+    }
+
+    async fn subscriber(&self, topic: &str, subscription: &str) -> Result<Box<dyn QueueSubscriber>> {
+        let topic = self.client.topic(topic_id(topic));
+        if !topic.exists(None).await? {
+            topic.create(None, None).await?;
+        }
+        // Subscription ids are project-wide, so they are prefixed with the topic like Pulsar's
+        // are scoped to it.
+        let subscription_id = format!("{}-{}", topic.id(), subscription.replace(['/', ':'], "-"));
+        let subscription = self.client.subscription(&subscription_id);
+        if !subscription.exists(None).await? {
+            let config = SubscriptionConfig {
+                enable_message_ordering: true,
+                ack_deadline_seconds: self.ack_deadline_secs as i32,
+                ..Default::default()
+            };
+            subscription
+                .create(topic.fully_qualified_name(), config, None)
+                .await
+                .with_context(|| format!("Failed to create Pub/Sub subscription {}", subscription_id))?;
+        }
+
+        let stream = subscription.subscribe(None).await?;
+        let pending = Pending::default();
+        Ok(Box::new(PubSubSubscriber {
+            stream,
+            extender: extend_deadlines(Arc::clone(&pending), self.ack_deadline_secs),
+            pending,
+            next_id: 0,
+        }))
+    }
+}
@@ -1,31 +1,82 @@
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use futures_util::StreamExt;
+use std::collections::HashMap;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 use pulsar::{Pulsar, Producer, Consumer, ConsumerOptions, SubType, TokioExecutor};
 use pulsar::consumer::InitialPosition;
 use pulsar::DeserializeMessage;
 use pulsar::message::Message;
+use pulsar::producer;
+use tokio::sync::Mutex;
+
+use crate::streams::message_queue::queue::{MessageQueue, QueueMessage, QueuePublisher, QueueSubscriber, BLOCK_KEY_HEADER};
+
+// A producer and the last sequence ID it sent.
+type SharedProducer = Mutex<(Producer<TokioExecutor>, Option<u64>)>;
 
 #[derive(Clone)]
 pub struct PulsarClient {
     client: Pulsar<TokioExecutor>,
+    instance_id: Option<String>,
+    /// Producers of this client's publishers, by topic. The broker refuses a second producer
+    /// under a name already connected to the topic, so publishers of a topic share one.
+    producers: Arc<Mutex<HashMap<String, Weak<SharedProducer>>>>,
 }
 
 impl PulsarClient {
     pub async fn new(url: &str) -> Result<Self> {
         let client = Pulsar::builder(url, TokioExecutor).build().await?;
-        Ok(PulsarClient { client })
+        Ok(PulsarClient { client, instance_id: None, producers: Arc::new(Mutex::new(HashMap::new())) })
+    }
+
+    /// Lets several instances publish and consume the same topics (active-active): producer
+    /// names get the instance id appended, so the broker accepts a producer per instance, and
+    /// subscriptions fail over to a peer's consumer when the active one disconnects.
+    pub fn with_instance_id(mut self, instance_id: String) -> Self {
+        self.instance_id = Some(instance_id);
+        self
     }
 }
 
-pub async fn create_producer(client: &PulsarClient, topic: String) -> Result<Producer<TokioExecutor>> {
-    let producer = client.client.producer().with_topic(topic).build().await?;
+impl PulsarClient {
+    /// A stable name lets a deduplicating broker recognise the producer again after a restart,
+    /// so it only depends on the topic, which names the shard for sharded topics.
+    fn producer_name(&self, topic: &str) -> String {
+        match &self.instance_id {
+            Some(instance_id) => format!("{}-producer-{}", topic, instance_id),
+            None => format!("{}-producer", topic),
+        }
+    }
+
+    /// The topic's producer, opened if no publisher of the topic holds it anymore.
+    async fn shared_producer(&self, topic: &str) -> Result<Arc<SharedProducer>> {
+        let mut producers = self.producers.lock().await;
+        if let Some(producer) = producers.get(topic).and_then(Weak::upgrade) {
+            return Ok(producer);
+        }
+        let producer = create_producer(self, topic.to_string(), &self.producer_name(topic)).await?;
+        let producer = Arc::new(Mutex::new((producer, None)));
+        producers.insert(topic.to_string(), Arc::downgrade(&producer));
+        Ok(producer)
+    }
+}
+
+pub async fn create_producer(client: &PulsarClient, topic: String, name: &str) -> Result<Producer<TokioExecutor>> {
+    let producer = client.client.producer().with_name(name).with_topic(topic).build().await?;
     Ok(producer)
 }
 
+// Sequence IDs are `block_number * SEQUENCE_IDS_PER_BLOCK`, leaving room for blocks that replace
+// an already published height after a reorg.
+const SEQUENCE_IDS_PER_BLOCK: u64 = 1000;
+
 pub async fn create_consumer<T: DeserializeMessage>(client: &PulsarClient, topic: String, subscription: &str) -> Result<Consumer<T, TokioExecutor>> {
     let consumer = client.client
         .consumer()
         .with_topic(topic)
-        .with_subscription_type(SubType::Exclusive)
+        .with_subscription_type(if client.instance_id.is_some() { SubType::Failover } else { SubType::Exclusive })
         .with_subscription(subscription)
         .with_options(ConsumerOptions {
             initial_position: InitialPosition::Earliest,
@@ -34,4 +85,113 @@ pub async fn create_consumer<T: DeserializeMessage>(client: &PulsarClient, topic
         .build()
         .await?;
     Ok(consumer)
-}
\ No newline at end of file
+}
+
+struct PulsarPublisher {
+    producer: Arc<SharedProducer>,
+}
+
+#[async_trait]
+impl QueuePublisher for PulsarPublisher {
+    async fn publish(&self, payload: Vec<u8>) -> Result<()> {
+        let mut producer = self.producer.lock().await;
+        producer.0.send(payload).await?;
+        Ok(())
+    }
+
+    async fn publish_block(&self, block_number: u64, payload: Vec<u8>) -> Result<()> {
+        let mut producer = self.producer.lock().await;
+        let (producer, last_sequence_id) = &mut *producer;
+        // With deduplication on, the broker drops any sequence ID at or below the highest it has
+        // stored for this producer name, so blocks re-sent after a restart are dropped. Blocks that
+        // arrive for a height this publisher already sent are reorg replacements and must go
+        // through, so they take the next free ID instead. Publishers sharing the producer share
+        // the last ID too, as the broker tracks it per producer.
+        let mut sequence_id = block_number * SEQUENCE_IDS_PER_BLOCK;
+        if let Some(last) = *last_sequence_id {
+            if sequence_id <= last {
+                sequence_id = last + 1;
+            }
+        }
+        let message = producer::Message {
+            payload,
+            sequence_id: Some(sequence_id),
+            properties: HashMap::from([(BLOCK_KEY_HEADER.to_string(), block_number.to_string())]),
+            ..Default::default()
+        };
+        producer.send(message).await?;
+        *last_sequence_id = Some(sequence_id);
+        Ok(())
+    }
+}
+
+struct PulsarSubscriber {
+    consumer: Consumer<Vec<u8>, TokioExecutor>,
+    // Delivered but not yet acknowledged messages, keyed by `QueueMessage::id`.
+    pending: HashMap<u64, Message<Vec<u8>>>,
+    next_id: u64,
+}
+
+#[async_trait]
+impl QueueSubscriber for PulsarSubscriber {
+    async fn next(&mut self) -> Option<Result<QueueMessage>> {
+        let message = match self.consumer.next().await? {
+            Ok(message) => message,
+            Err(e) => return Some(Err(anyhow!(e))),
+        };
+        let id = self.next_id;
+        self.next_id += 1;
+        let payload = message.payload.data.clone();
+        let block_key = message
+            .metadata()
+            .properties
+            .iter()
+            .find(|property| property.key == BLOCK_KEY_HEADER)
+            .and_then(|property| property.value.parse().ok());
+        self.pending.insert(id, message);
+        Some(Ok(QueueMessage { id, payload, block_key }))
+    }
+
+    async fn ack(&mut self, message: &QueueMessage) -> Result<()> {
+        let delivered = self
+            .pending
+            .remove(&message.id)
+            .ok_or_else(|| anyhow!("Message {} was not delivered by this subscriber", message.id))?;
+        self.consumer.ack(&delivered).await?;
+        Ok(())
+    }
+
+    async fn nack(&mut self, messages: &[QueueMessage], delay: Duration) -> Result<()> {
+        let delivered = messages
+            .iter()
+            .map(|message| {
+                self.pending
+                    .remove(&message.id)
+                    .ok_or_else(|| anyhow!("Message {} was not delivered by this subscriber", message.id))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        // The client has no per-message redelivery delay; a nack is redelivered right away.
+        tokio::time::sleep(delay).await;
+        for delivered in &delivered {
+            self.consumer.nack(delivered).await?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl MessageQueue for PulsarClient {
+    async fn publisher(&self, topic: &str) -> Result<Box<dyn QueuePublisher>> {
+        let producer = self.shared_producer(topic).await?;
+        Ok(Box::new(PulsarPublisher { producer }))
+    }
+
+    async fn subscriber(&self, topic: &str, subscription: &str) -> Result<Box<dyn QueueSubscriber>> {
+        let consumer = create_consumer(self, topic.to_string(), subscription).await?;
+        Ok(Box::new(PulsarSubscriber {
+            consumer,
+            pending: HashMap::new(),
+            next_id: 0,
+        }))
+    }
+}
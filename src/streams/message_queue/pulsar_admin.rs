@@ -0,0 +1,313 @@
+use anyhow::{anyhow, Context, Result};
+use log::info;
+use reqwest::{Client, RequestBuilder, StatusCode};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+
+#[derive(Debug, Deserialize)]
+pub struct PulsarConfig {
+    /// Tenant and namespace the topics live in, `persistent://{tenant}/{namespace}/...`.
+    #[serde(default = "default_tenant")]
+    pub tenant: String,
+    #[serde(default = "default_namespace")]
+    pub namespace: String,
+    /// Env var holding the admin API URL, e.g. http://localhost:8080
+    #[serde(default = "default_admin_url_env")]
+    pub admin_url_env: String,
+    /// Env var holding a token for the admin API, if it needs one.
+    pub admin_token_env: Option<String>,
+    /// Creates the tenant and namespace at startup if missing, and applies the policies below.
+    #[serde(default)]
+    pub provision: bool,
+    /// Clusters a created tenant is allowed on.
+    #[serde(default = "default_clusters")]
+    pub clusters: Vec<String>,
+    /// Namespace retention and TTL, applied when provisioning.
+    #[serde(flatten)]
+    pub policies: TopicPolicies,
+    /// Namespace broker deduplication (see Pulsar deduplication below).
+    pub deduplication: Option<bool>,
+    /// Per-topic retention and TTL overriding the namespace's, applied at startup.
+    #[serde(default)]
+    pub topics: Vec<TopicPolicyConfig>,
+}
+
+/// Retention and TTL policies of a namespace or topic; unset ones are left alone.
+#[derive(Debug, Default, Clone, PartialEq, Deserialize)]
+pub struct TopicPolicies {
+    /// Retention of acknowledged messages; -1 keeps them forever.
+    pub retention_minutes: Option<i64>,
+    pub retention_size_mb: Option<i64>,
+    /// Time after which unacknowledged messages expire.
+    pub message_ttl_secs: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopicPolicyConfig {
+    /// Topic name without the tenant and namespace, e.g. `ETH-blocks`.
+    pub topic: String,
+    #[serde(flatten)]
+    pub policies: TopicPolicies,
+}
+
+/// A policy whose value on the cluster differs from the config, e.g. after a manual
+/// `pulsar-admin` change.
+#[derive(Debug)]
+pub struct PolicyDrift {
+    /// `{tenant}/{namespace}` or the topic name.
+    pub resource: String,
+    pub policy: &'static str,
+    pub expected: String,
+    pub actual: String,
+}
+
+fn default_tenant() -> String {
+    "public".to_string()
+}
+
+fn default_namespace() -> String {
+    "default".to_string()
+}
+
+fn default_admin_url_env() -> String {
+    "PULSAR_ADMIN_URL".to_string()
+}
+
+fn default_clusters() -> Vec<String> {
+    vec!["standalone".to_string()]
+}
+
+impl Default for PulsarConfig {
+    fn default() -> Self {
+        Self {
+            tenant: default_tenant(),
+            namespace: default_namespace(),
+            admin_url_env: default_admin_url_env(),
+            admin_token_env: None,
+            provision: false,
+            clusters: default_clusters(),
+            policies: TopicPolicies::default(),
+            deduplication: None,
+            topics: Vec::new(),
+        }
+    }
+}
+
+impl PulsarConfig {
+    /// Prefix of every topic name.
+    pub fn topic_prefix(&self) -> String {
+        format!("persistent://{}/{}/", self.tenant, self.namespace)
+    }
+}
+
+/// A client for the parts of the Pulsar admin REST API (`/admin/v2`) the pipeline manages.
+pub struct PulsarAdmin {
+    client: Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl PulsarAdmin {
+    pub fn new(config: &PulsarConfig) -> Result<Self> {
+        let url = env::var(&config.admin_url_env)
+            .with_context(|| format!("Failed to get Pulsar admin URL from environment for key `{}`", config.admin_url_env))?;
+        let token = config
+            .admin_token_env
+            .as_deref()
+            .map(|key| env::var(key).with_context(|| format!("Failed to get Pulsar admin token from environment for key `{}`", key)))
+            .transpose()?;
+        Ok(Self { client: Client::new(), url: url.trim_end_matches('/').to_string(), token })
+    }
+
+    fn request(&self, request: RequestBuilder) -> RequestBuilder {
+        match &self.token {
+            Some(token) => request.bearer_auth(token),
+            None => request,
+        }
+    }
+
+    /// Sends `request`; `Ok(false)` if the resource already exists (409).
+    async fn send(&self, request: RequestBuilder, what: &str) -> Result<bool> {
+        let response = self.request(request).send().await?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::CONFLICT => Ok(false),
+            status => {
+                let message = response.text().await.unwrap_or_default();
+                Err(anyhow!("Pulsar admin request to {} failed ({}): {}", what, status, message.trim()))
+            }
+        }
+    }
+
+    pub async fn get(&self, path: &str) -> Result<Option<Value>> {
+        let response = self.request(self.client.get(format!("{}/admin/v2/{}", self.url, path))).send().await?;
+        match response.status() {
+            status if status.is_success() => {
+                let body = response.text().await?;
+                // Unset policies come back as an empty body.
+                Ok(if body.trim().is_empty() { None } else { Some(serde_json::from_str(&body)?) })
+            }
+            StatusCode::NOT_FOUND => Ok(None),
+            status => {
+                let message = response.text().await.unwrap_or_default();
+                Err(anyhow!("Pulsar admin request to {} failed ({}): {}", path, status, message.trim()))
+            }
+        }
+    }
+
+    pub async fn put(&self, path: &str, body: Value) -> Result<bool> {
+        self.send(self.client.put(format!("{}/admin/v2/{}", self.url, path)).json(&body), path).await
+    }
+
+    pub async fn post(&self, path: &str, body: Value) -> Result<bool> {
+        self.send(self.client.post(format!("{}/admin/v2/{}", self.url, path)).json(&body), path).await
+    }
+
+    /// Sets the policies of a namespace (`namespaces/{tenant}/{namespace}`) or topic
+    /// (`persistent/{tenant}/{namespace}/{topic}`).
+    async fn set_policies(&self, path: &str, policies: &TopicPolicies) -> Result<()> {
+        if policies.retention_minutes.is_some() || policies.retention_size_mb.is_some() {
+            // Both limits are set in one call, so the one not configured keeps its current value.
+            let current = self.get(&format!("{}/retention", path)).await?;
+            let current = |field: &str| current.as_ref().and_then(|r| r[field].as_i64()).unwrap_or(0);
+            let retention = json!({
+                "retentionTimeInMinutes": policies.retention_minutes.unwrap_or_else(|| current("retentionTimeInMinutes")),
+                "retentionSizeInMB": policies.retention_size_mb.unwrap_or_else(|| current("retentionSizeInMB")),
+            });
+            self.post(&format!("{}/retention", path), retention).await?;
+        }
+        if let Some(message_ttl_secs) = policies.message_ttl_secs {
+            if path.starts_with("namespaces/") {
+                self.post(&format!("{}/messageTTL", path), json!(message_ttl_secs)).await?;
+            } else {
+                // The topic endpoint takes the TTL as a query parameter rather than a body.
+                let url = format!("{}/admin/v2/{}/messageTTL?messageTTL={}", self.url, path, message_ttl_secs);
+                self.send(self.client.post(url), path).await?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The policies set on a namespace or topic, `None` where only inherited.
+    async fn policies(&self, path: &str) -> Result<TopicPolicies> {
+        let retention = self.get(&format!("{}/retention", path)).await?;
+        let message_ttl = self.get(&format!("{}/messageTTL", path)).await?;
+        Ok(TopicPolicies {
+            retention_minutes: retention.as_ref().and_then(|r| r["retentionTimeInMinutes"].as_i64()),
+            retention_size_mb: retention.as_ref().and_then(|r| r["retentionSizeInMB"].as_i64()),
+            message_ttl_secs: message_ttl.as_ref().and_then(Value::as_u64),
+        })
+    }
+
+    /// Creates each configured topic if missing and sets its policies. Topic policies need
+    /// `topicLevelPoliciesEnabled` on the brokers, the default since Pulsar 3.0.
+    pub async fn apply_topic_policies(&self, config: &PulsarConfig) -> Result<()> {
+        for topic in &config.topics {
+            let path = format!("persistent/{}/{}/{}", config.tenant, config.namespace, topic.topic);
+            if self.put(&path, json!({})).await? {
+                info!("Created Pulsar topic {}", topic.topic);
+            }
+            self.set_policies(&path, &topic.policies).await?;
+            info!("Applied policies to Pulsar topic {}", topic.topic);
+        }
+        Ok(())
+    }
+
+    /// Messages waiting on `subscription` of `topic`, a full `persistent://` name. `None` if
+    /// either doesn't exist yet.
+    pub async fn subscription_backlog(&self, topic: &str, subscription: &str) -> Result<Option<u64>> {
+        let stats = self.get(&format!("{}/stats", topic.replacen("://", "/", 1))).await?;
+        Ok(stats.and_then(|stats| stats["subscriptions"][subscription]["msgBacklog"].as_u64()))
+    }
+
+    /// Creates `topic` as a partitioned topic with `partitions` partitions, or grows an existing
+    /// one to that many. Pulsar can't remove partitions, so a topic with more is an error.
+    pub async fn ensure_partitioned_topic(&self, config: &PulsarConfig, topic: &str, partitions: usize) -> Result<()> {
+        let path = format!("persistent/{}/{}/{}/partitions", config.tenant, config.namespace, topic);
+        // A missing or non-partitioned topic reports 0 partitions.
+        let existing = self
+            .get(&path)
+            .await?
+            .and_then(|metadata| metadata["partitions"].as_u64())
+            .unwrap_or(0) as usize;
+        match existing {
+            0 => {
+                if !self.put(&path, json!(partitions)).await? {
+                    return Err(anyhow!("Pulsar topic {} already exists without partitions", topic));
+                }
+                info!("Created Pulsar topic {} with {} partitions", topic, partitions);
+            }
+            existing if existing < partitions => {
+                self.post(&path, json!(partitions)).await?;
+                info!("Grew Pulsar topic {} from {} to {} partitions", topic, existing, partitions);
+            }
+            existing if existing > partitions => {
+                return Err(anyhow!("Pulsar topic {} has {} partitions, more than the {} configured", topic, existing, partitions));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Compares the configured namespace and topic policies, and namespace deduplication, with
+    /// those on the cluster.
+    pub async fn policy_drift(&self, config: &PulsarConfig) -> Result<Vec<PolicyDrift>> {
+        let namespace = format!("{}/{}", config.tenant, config.namespace);
+        let mut expected = vec![(namespace.clone(), format!("namespaces/{}", namespace), &config.policies)];
+        for topic in &config.topics {
+            expected.push((topic.topic.clone(), format!("persistent/{}/{}", namespace, topic.topic), &topic.policies));
+        }
+
+        let mut drift = Vec::new();
+        for (resource, path, wanted) in expected {
+            let actual = self.policies(&path).await?;
+            let mut compare = |policy, wanted: Option<String>, actual: Option<String>| {
+                if wanted.is_some() && wanted != actual {
+                    drift.push(PolicyDrift {
+                        resource: resource.clone(),
+                        policy,
+                        expected: wanted.unwrap_or_default(),
+                        actual: actual.unwrap_or_else(|| "unset".to_string()),
+                    });
+                }
+            };
+            compare("retention_minutes", wanted.retention_minutes.map(|v| v.to_string()), actual.retention_minutes.map(|v| v.to_string()));
+            compare("retention_size_mb", wanted.retention_size_mb.map(|v| v.to_string()), actual.retention_size_mb.map(|v| v.to_string()));
+            compare("message_ttl_secs", wanted.message_ttl_secs.map(|v| v.to_string()), actual.message_ttl_secs.map(|v| v.to_string()));
+        }
+
+        if let Some(wanted) = config.deduplication {
+            let actual = self.get(&format!("namespaces/{}/deduplication", namespace)).await?.and_then(|v| v.as_bool());
+            if actual != Some(wanted) {
+                drift.push(PolicyDrift {
+                    resource: namespace,
+                    policy: "deduplication",
+                    expected: wanted.to_string(),
+                    actual: actual.map_or_else(|| "unset".to_string(), |v| v.to_string()),
+                });
+            }
+        }
+        Ok(drift)
+    }
+
+    /// Creates the tenant and namespace if missing and sets the namespace policies, so a fresh
+    /// cluster needs no `pulsar-admin` steps. Policies are set on every start, so config changes
+    /// take effect.
+    pub async fn provision(&self, config: &PulsarConfig) -> Result<()> {
+        let tenant = &config.tenant;
+        let namespace = format!("{}/{}", config.tenant, config.namespace);
+        if self.put(&format!("tenants/{}", tenant), json!({ "allowedClusters": config.clusters, "adminRoles": [] })).await? {
+            info!("Created Pulsar tenant {}", tenant);
+        }
+        if self.put(&format!("namespaces/{}", namespace), json!({})).await? {
+            info!("Created Pulsar namespace {}", namespace);
+        }
+
+        self.set_policies(&format!("namespaces/{}", namespace), &config.policies).await?;
+        if let Some(deduplication) = config.deduplication {
+            self.post(&format!("namespaces/{}/deduplication", namespace), json!(deduplication)).await?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+
+/// A message delivered by a `QueueSubscriber`. `id` is only meaningful to the subscriber that
+/// delivered it, which uses it to acknowledge the message.
+#[derive(Debug, Clone)]
+pub struct QueueMessage {
+    pub id: u64,
+    pub payload: Vec<u8>,
+    /// The key the message was published with through `publish_block`, on brokers that carry it
+    /// (as the `BLOCK_KEY_HEADER` property, attribute or header). `None` for plain publishes.
+    pub block_key: Option<u64>,
+}
+
+/// Name of the message property carrying the `publish_block` key, so a copy of the message can
+/// be republished under the same key.
+pub const BLOCK_KEY_HEADER: &str = "block_key";
+
+/// Above any real block number, and low enough for brokers to scale keys into sequence IDs.
+const DESCENDING_KEY_BASE: u64 = 1 << 44;
+
+/// The key a topic published newest block first passes to `publish_block` in place of the block
+/// number, so keys still rise as blocks fall and deduplicating brokers don't drop every block
+/// below the first one, or below the last one published before a restart.
+pub fn descending_key(block_number: u64) -> u64 {
+    DESCENDING_KEY_BASE.saturating_sub(block_number)
+}
+
+/// Publishes raw payloads to a single topic.
+#[async_trait]
+pub trait QueuePublisher: Send + Sync {
+    async fn publish(&self, payload: Vec<u8>) -> Result<()>;
+
+    /// Publishes a payload for block `block_number`. Brokers that deduplicate by sequence ID
+    /// use it to drop repeated sends of the same block; the rest publish as usual.
+    async fn publish_block(&self, _block_number: u64, payload: Vec<u8>) -> Result<()> {
+        self.publish(payload).await
+    }
+}
+
+/// Reads a single topic through a named subscription. Unacknowledged messages are redelivered
+/// to the next subscriber with the same subscription name.
+#[async_trait]
+pub trait QueueSubscriber: Send {
+    /// Waits for the next message. `None` means the subscription was closed.
+    async fn next(&mut self) -> Option<Result<QueueMessage>>;
+
+    async fn ack(&mut self, message: &QueueMessage) -> Result<()>;
+
+    /// Negatively acknowledges `messages`, asking for them to be redelivered after `delay`.
+    /// Brokers without per-message redelivery delays wait out `delay` before asking, which holds
+    /// back this subscriber too.
+    async fn nack(&mut self, messages: &[QueueMessage], delay: Duration) -> Result<()>;
+}
+
+/// A message broker the pipeline publishes blocks through (Pulsar in production, in-memory in
+/// tests).
+#[async_trait]
+pub trait MessageQueue: Send + Sync {
+    async fn publisher(&self, topic: &str) -> Result<Box<dyn QueuePublisher>>;
+
+    async fn subscriber(&self, topic: &str, subscription: &str) -> Result<Box<dyn QueueSubscriber>>;
+}
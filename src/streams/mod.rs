@@ -1,5 +1,11 @@
+pub mod control;
+pub mod delivery;
+pub mod lanes;
 pub mod producers;
 pub mod consumers;
 pub mod message_queue;
 pub mod schemas;
+pub mod redaction;
+pub mod sampling;
+pub mod sharding;
 
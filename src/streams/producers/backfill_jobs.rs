@@ -0,0 +1,367 @@
+use anyhow::Result;
+use futures::future;
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use crate::alerting::{Alert, Alerter};
+use crate::metrics;
+use crate::secrets;
+use crate::streams::producers::producer::StreamProducer;
+
+/// How long idle workers wait before looking for chunks whose retry backoff has passed.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BackfillConfig {
+    /// Blocks per chunk. Chunks are cells of a fixed grid of this many blocks, so a later run
+    /// extends the partial chunk at the end of a range instead of planning an overlapping one.
+    pub chunk_size: u64,
+    /// Chunks of one stream produced concurrently. With more than one, the historical topic is
+    /// no longer in block order.
+    pub workers: usize,
+    /// Attempts before a chunk is marked `failed` and alerted.
+    pub max_attempts: i32,
+    /// Backoff after the first failed attempt, doubled after each further one.
+    pub retry_backoff_secs: u64,
+}
+
+impl Default for BackfillConfig {
+    fn default() -> Self {
+        Self {
+            chunk_size: 10_000,
+            workers: 1,
+            max_attempts: 5,
+            retry_backoff_secs: 30,
+        }
+    }
+}
+
+/// Which end of a historical range is produced first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BackfillOrder {
+    #[default]
+    OldestFirst,
+    /// From `end_block` down toward `start_block`, so recent data is usable while history fills in.
+    NewestFirst,
+}
+
+/// A chunk claimed by a worker.
+struct Chunk {
+    id: i64,
+    start_block: u64,
+    /// First block to produce: after the blocks a previous run of the chunk already produced.
+    from_block: u64,
+    end_block: u64,
+    attempts: i32,
+}
+
+/// A chunk that ran out of attempts, as listed for operators.
+#[derive(Debug, Clone, Serialize)]
+pub struct FailedChunk {
+    pub chain_name: String,
+    pub schema: String,
+    pub start_block: i64,
+    pub end_block: i64,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+}
+
+/// Runs the historical backfill of one (chain, schema) stream as chunk records in
+/// `backfill_chunks`. Workers claim pending chunks in block order, or newest first with
+/// `BackfillOrder::NewestFirst`, and produce them; a failed chunk is retried with exponential
+/// backoff and, after `max_attempts`, marked `failed` and alerted while the other chunks carry on.
+pub struct BackfillJobRunner {
+    pg_pool: Arc<PgPool>,
+    producer: Arc<dyn StreamProducer + Send + Sync>,
+    chain_name: String,
+    schema: String,
+    alerter: Alerter,
+    config: BackfillConfig,
+    order: BackfillOrder,
+}
+
+impl BackfillJobRunner {
+    pub fn new(
+        pg_pool: Arc<PgPool>,
+        producer: Arc<dyn StreamProducer + Send + Sync>,
+        chain_name: &str,
+        schema: &str,
+        alerter: Alerter,
+        config: &BackfillConfig,
+    ) -> Self {
+        Self {
+            pg_pool,
+            producer,
+            chain_name: chain_name.to_string(),
+            schema: schema.to_string(),
+            alerter,
+            config: config.clone(),
+            order: BackfillOrder::OldestFirst,
+        }
+    }
+
+    /// Claims chunks in `order` instead of oldest first. The producer should walk each chunk the
+    /// same way.
+    pub fn with_order(mut self, order: BackfillOrder) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Records the chunks of `start_block..=end_block`, one per cell of the `chunk_size` grid.
+    /// A cell that already has a chunk gets it extended to cover the range: a done chunk goes
+    /// back to pending for the new blocks only, while a failed one waits for a retry. Extending a
+    /// produced chunk to earlier blocks produces it again from its new start.
+    pub async fn plan(&self, start_block: u64, end_block: u64) -> Result<()> {
+        let chunk_size = self.config.chunk_size.max(1);
+        let mut grid_starts = Vec::new();
+        let mut starts = Vec::new();
+        let mut ends = Vec::new();
+        let mut chunk_start = start_block;
+        while chunk_start <= end_block {
+            let grid_start = chunk_start / chunk_size * chunk_size;
+            let chunk_end = (grid_start + chunk_size - 1).min(end_block);
+            grid_starts.push(grid_start as i64);
+            starts.push(chunk_start as i64);
+            ends.push(chunk_end as i64);
+            chunk_start = chunk_end + 1;
+        }
+
+        let planned = sqlx::query(
+            "INSERT INTO backfill_chunks AS c (chain_name, schema_name, grid_start, start_block, end_block)
+            SELECT $1, $2, grid_start, start_block, end_block
+            FROM UNNEST($3::bigint[], $4::bigint[], $5::bigint[]) AS p(grid_start, start_block, end_block)
+            ON CONFLICT (chain_name, schema_name, grid_start) DO UPDATE SET
+                start_block = LEAST(c.start_block, EXCLUDED.start_block),
+                end_block = GREATEST(c.end_block, EXCLUDED.end_block),
+                produced_through = CASE WHEN EXCLUDED.start_block < c.start_block THEN NULL ELSE c.produced_through END,
+                status = CASE WHEN c.status = 'done' THEN 'pending' ELSE c.status END,
+                attempts = CASE WHEN c.status = 'done' THEN 0 ELSE c.attempts END,
+                next_attempt_at = CASE WHEN c.status = 'done' THEN NOW() ELSE c.next_attempt_at END,
+                updated_at = NOW()
+            WHERE EXCLUDED.start_block < c.start_block OR EXCLUDED.end_block > c.end_block",
+        )
+        .bind(&self.chain_name)
+        .bind(&self.schema)
+        .bind(&grid_starts)
+        .bind(&starts)
+        .bind(&ends)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        info!(
+            "Planned or extended {} backfill chunks of {} for {} ({}..={})",
+            planned.rows_affected(), self.schema, self.chain_name, start_block, end_block
+        );
+        Ok(())
+    }
+
+    /// Runs the workers until no chunk is pending or waiting for a retry. Chunks left running by
+    /// a previous process, and failed chunks reset for retry, are picked up too.
+    pub async fn run(&self) -> Result<()> {
+        sqlx::query(
+            "UPDATE backfill_chunks SET status = 'pending', updated_at = NOW()
+            WHERE chain_name = $1 AND schema_name = $2 AND status = 'running'",
+        )
+        .bind(&self.chain_name)
+        .bind(&self.schema)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        let workers = (0..self.config.workers.max(1)).map(|_| self.work());
+        for result in future::join_all(workers).await {
+            result?;
+        }
+        Ok(())
+    }
+
+    /// Plans and runs each range handed off by the stream's realtime producer, until the
+    /// producer is gone.
+    pub async fn run_handoffs(&self, gaps: &mut UnboundedReceiver<(u64, u64)>) -> Result<()> {
+        while let Some((start_block, end_block)) = gaps.recv().await {
+            self.plan(start_block, end_block).await?;
+            self.run().await?;
+        }
+        Ok(())
+    }
+
+    async fn work(&self) -> Result<()> {
+        loop {
+            let Some(chunk) = self.claim().await? else {
+                if self.remaining().await? == 0 {
+                    return Ok(());
+                }
+                tokio::time::sleep(POLL_INTERVAL).await;
+                continue;
+            };
+            match self.producer.produce_historical(chunk.from_block, chunk.end_block).await {
+                Ok(()) => self.complete(&chunk).await?,
+                Err(e) => self.fail(&chunk, &e.to_string()).await?,
+            }
+        }
+    }
+
+    async fn claim(&self) -> Result<Option<Chunk>> {
+        let direction = match self.order {
+            BackfillOrder::OldestFirst => "ASC",
+            BackfillOrder::NewestFirst => "DESC",
+        };
+        let query = format!(
+            "UPDATE backfill_chunks SET status = 'running', attempts = attempts + 1, updated_at = NOW()
+            WHERE id = (
+                SELECT id FROM backfill_chunks
+                WHERE chain_name = $1 AND schema_name = $2 AND status = 'pending' AND next_attempt_at <= NOW()
+                ORDER BY start_block {}
+                LIMIT 1
+                FOR UPDATE SKIP LOCKED
+            )
+            RETURNING id, start_block, end_block, produced_through, attempts",
+            direction
+        );
+        let row = sqlx::query(&query)
+            .bind(&self.chain_name)
+            .bind(&self.schema)
+            .fetch_optional(self.pg_pool.as_ref())
+            .await?;
+        row.map(|row| {
+            let start_block = row.try_get::<i64, _>("start_block")? as u64;
+            let produced_through = row.try_get::<Option<i64>, _>("produced_through")?;
+            Ok(Chunk {
+                id: row.try_get("id")?,
+                start_block,
+                from_block: produced_through.map_or(start_block, |through| (through as u64 + 1).max(start_block)),
+                end_block: row.try_get::<i64, _>("end_block")? as u64,
+                attempts: row.try_get("attempts")?,
+            })
+        })
+        .transpose()
+    }
+
+    /// Chunks still to be produced, including those waiting for a retry.
+    async fn remaining(&self) -> Result<i64> {
+        let row = sqlx::query(
+            "SELECT COUNT(*) AS remaining FROM backfill_chunks
+            WHERE chain_name = $1 AND schema_name = $2 AND status IN ('pending', 'running')",
+        )
+        .bind(&self.chain_name)
+        .bind(&self.schema)
+        .fetch_one(self.pg_pool.as_ref())
+        .await?;
+        Ok(row.try_get("remaining")?)
+    }
+
+    /// Records the claimed blocks as produced. A chunk a planner extended while it ran goes back
+    /// to pending for the rest.
+    async fn complete(&self, chunk: &Chunk) -> Result<()> {
+        sqlx::query(
+            "UPDATE backfill_chunks SET
+                produced_through = CASE WHEN start_block = $2 THEN $3 ELSE produced_through END,
+                status = CASE WHEN start_block = $2 AND end_block = $3 THEN 'done' ELSE 'pending' END,
+                attempts = CASE WHEN start_block = $2 AND end_block = $3 THEN attempts ELSE 0 END,
+                last_error = NULL,
+                updated_at = NOW()
+            WHERE id = $1",
+        )
+        .bind(chunk.id)
+        .bind(chunk.start_block as i64)
+        .bind(chunk.end_block as i64)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+        metrics::increment_counter(
+            "backfill_chunk_blocks_total",
+            &[("chain", &self.chain_name), ("schema", &self.schema)],
+            chunk.end_block - chunk.from_block + 1,
+        );
+        Ok(())
+    }
+
+    async fn fail(&self, chunk: &Chunk, message: &str) -> Result<()> {
+        let exhausted = chunk.attempts >= self.config.max_attempts;
+        let backoff_secs = self.config.retry_backoff_secs.saturating_mul(1u64 << (chunk.attempts - 1).clamp(0, 16));
+        sqlx::query(
+            "UPDATE backfill_chunks SET
+                status = $2,
+                last_error = $3,
+                next_attempt_at = NOW() + make_interval(secs => $4),
+                updated_at = NOW()
+            WHERE id = $1",
+        )
+        .bind(chunk.id)
+        .bind(if exhausted { "failed" } else { "pending" })
+        .bind(secrets::redact(message))
+        .bind(backoff_secs as f64)
+        .execute(self.pg_pool.as_ref())
+        .await?;
+
+        if !exhausted {
+            warn!(
+                "Backfill chunk {}..={} of {} for {} failed (attempt {}), retrying in {}s: {}",
+                chunk.from_block, chunk.end_block, self.schema, self.chain_name, chunk.attempts, backoff_secs, message
+            );
+            return Ok(());
+        }
+        error!(
+            "Backfill chunk {}..={} of {} for {} failed {} times, giving up: {}",
+            chunk.from_block, chunk.end_block, self.schema, self.chain_name, chunk.attempts, message
+        );
+        metrics::increment_counter("backfill_chunks_failed_total", &[("chain", &self.chain_name), ("schema", &self.schema)], 1);
+        self.alerter
+            .fire(&Alert {
+                chain_name: self.chain_name.clone(),
+                kind: "backfill_chunk_failed".to_string(),
+                message: format!(
+                    "Backfill of {} blocks {}..={} failed {} times: {}",
+                    self.schema, chunk.from_block, chunk.end_block, chunk.attempts, message
+                ),
+                details: json!({
+                    "schema": self.schema,
+                    "start_block": chunk.from_block,
+                    "end_block": chunk.end_block,
+                    "attempts": chunk.attempts,
+                    "last_error": message,
+                }),
+            })
+            .await;
+        Ok(())
+    }
+}
+
+/// Lists the chunks that ran out of attempts.
+pub async fn list_failed_chunks(pg_pool: &PgPool) -> Result<Vec<FailedChunk>> {
+    sqlx::query(
+        "SELECT chain_name, schema_name, start_block, end_block, attempts, last_error
+        FROM backfill_chunks WHERE status = 'failed' ORDER BY chain_name, schema_name, start_block",
+    )
+    .fetch_all(pg_pool)
+    .await?
+    .iter()
+    .map(|row| {
+        Ok(FailedChunk {
+            chain_name: row.try_get("chain_name")?,
+            schema: row.try_get("schema_name")?,
+            start_block: row.try_get("start_block")?,
+            end_block: row.try_get("end_block")?,
+            attempts: row.try_get("attempts")?,
+            last_error: row.try_get("last_error")?,
+        })
+    })
+    .collect()
+}
+
+/// Hands a chain's failed chunks back to the workers with fresh attempts. They are produced
+/// on the next start, whether or not that start has a historical range. Returns how many were reset.
+pub async fn retry_failed_chunks(pg_pool: &PgPool, chain_name: &str) -> Result<u64> {
+    let reset = sqlx::query(
+        "UPDATE backfill_chunks SET status = 'pending', attempts = 0, next_attempt_at = NOW(), updated_at = NOW()
+        WHERE chain_name = $1 AND status = 'failed'",
+    )
+    .bind(chain_name)
+    .execute(pg_pool)
+    .await?;
+    Ok(reset.rows_affected())
+}
@@ -0,0 +1,88 @@
+use anyhow::Result;
+use async_trait::async_trait;
+use ethers::types::{Block, Transaction};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+use crate::streams::message_queue::queue::{MessageQueue, QueuePublisher};
+use crate::streams::schemas::cdc::{BlockRowImage, ChangeEnvelope, ChangeSource, CDC_CONNECTOR};
+use crate::streams::schemas::schema::{MessageSchema, WireFormat};
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CdcConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// Publishes Debezium-style change events to `{chain}-cdc` whenever a reorg flips a block's
+/// canonicality, so downstream caches can react to corrections and not just appends.
+pub struct CdcProducer {
+    publisher: Box<dyn QueuePublisher>,
+    wire_format: WireFormat,
+}
+
+impl CdcProducer {
+    pub async fn new(queue: Arc<dyn MessageQueue>, producer_topic: String) -> Result<Self> {
+        let publisher = queue.publisher(&producer_topic).await?;
+        Ok(Self { publisher, wire_format: WireFormat::Json })
+    }
+
+    /// Encodes change events in `wire_format` instead of JSON.
+    pub fn with_wire_format(mut self, wire_format: WireFormat) -> Self {
+        self.wire_format = wire_format;
+        self
+    }
+
+    async fn send(&self, envelope: ChangeEnvelope<BlockRowImage>) -> Result<()> {
+        self.publisher.publish(envelope.encode(self.wire_format)?).await?;
+        Ok(())
+    }
+}
+
+fn now_ms() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or_default()
+}
+
+#[async_trait]
+impl ConsumerHook for CdcProducer {
+    async fn on_block_committed(&self, _chain_name: &str, _block: &Block<Transaction>) -> Result<()> {
+        // Plain appends are already visible on the block topics.
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        for block in orphaned {
+            let before = BlockRowImage {
+                chain_name: chain_name.to_string(),
+                block_number: block.block_number,
+                hash: block.hash.clone(),
+                canonical: true,
+            };
+            let after = BlockRowImage {
+                canonical: false,
+                ..before.clone()
+            };
+            let ts_ms = now_ms();
+
+            self.send(ChangeEnvelope {
+                before: Some(before),
+                after: Some(after),
+                source: ChangeSource {
+                    connector: CDC_CONNECTOR.to_string(),
+                    name: chain_name.to_string(),
+                    table: "blocks".to_string(),
+                    ts_ms,
+                },
+                op: "u".to_string(),
+                ts_ms,
+            })
+            .await?;
+        }
+        Ok(())
+    }
+}
@@ -1,54 +1,561 @@
 use async_trait::async_trait;
 use anyhow::Result;
+use log::{error, info, warn};
+use serde::Deserialize;
 use futures_util::StreamExt;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
+use tokio::sync::mpsc::UnboundedSender;
 use std::future::Future;
-use pulsar::{Producer, TokioExecutor};
-use pulsar::message::{Message, Payload};
 use crate::blockchain::adapters::BlockchainAdapter;
+use crate::enrichment::priority_transfers::PriorityTransferPublisher;
+use crate::metrics;
+use crate::streams::control::StreamControl;
+use crate::streams::delivery::{DeliveryMode, PUBLISH_ATTEMPTS, PUBLISH_RETRY_BACKOFF};
+use crate::streams::lanes::{Lane, LaneMonitor};
+use crate::streams::producers::backfill_jobs::BackfillOrder;
 use futures_core::Stream;
 use std::pin::Pin;
 use crate::streams::producers::producer::StreamProducer;
-use crate::streams::message_queue::pulsar::{create_producer, PulsarClient};
+use crate::streams::producers::watchdog::Heartbeat;
+use crate::streams::message_queue::queue::{descending_key, MessageQueue, QueuePublisher};
+use crate::streams::schemas::evm::{stamp_received_at, with_transaction_hashes};
+use crate::streams::schemas::head::ChainHead;
+use crate::streams::schemas::header::BlockHeader;
+use crate::streams::schemas::schema::{encode, MessageSchema, WireFormat};
+use crate::streams::sampling::Sampler;
+use crate::streams::schemas::versioning::{encode_sampled, Versioned};
+use crate::streams::redaction::Redaction;
+use crate::streams::sharding::{FanoutCommit, ShardConfig, ShardKey};
 use alloy_network_primitives::{BlockResponse, BlockTransactions, BlockTransactionsKind};
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, U256, U64};
+
+/// Fetches of a skipped block a realtime producer attempts before giving up on it.
+const GAP_FETCH_ATTEMPTS: u32 = 8;
+/// Wait after the first failed fetch of a skipped block, doubled after each further one.
+const GAP_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+const GAP_MAX_RETRY_BACKOFF: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HeadTopicConfig {
+    #[serde(default)]
+    pub enabled: bool,
+}
+
+/// How much of each transaction a schema's blocks carry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TxDetail {
+    #[default]
+    Full,
+    /// Transaction hashes only. Sources answer these requests far more cheaply.
+    Hashes,
+}
+
+impl From<TxDetail> for BlockTransactionsKind {
+    fn from(tx_detail: TxDetail) -> Self {
+        match tx_detail {
+            TxDetail::Full => BlockTransactionsKind::Full,
+            TxDetail::Hashes => BlockTransactionsKind::Hashes,
+        }
+    }
+}
+
+struct HeadPublisher {
+    publisher: Box<dyn QueuePublisher>,
+    chain_name: String,
+    wire_format: WireFormat,
+}
+
+struct PauseCheck {
+    control: Arc<StreamControl>,
+    chain_name: String,
+    schema: String,
+}
+
+struct LaneCheck {
+    monitor: Arc<LaneMonitor>,
+    lane: Lane,
+    chain_name: String,
+    schema: String,
+}
 
 pub struct EVMProducer {
-    adapter: Arc<Mutex<dyn BlockchainAdapter>>,
-    producer: Arc<Mutex<Producer<TokioExecutor>>>,
+    adapter: Arc<dyn BlockchainAdapter>,
+    /// One per shard topic, or just the producer topic's if the schema isn't sharded.
+    publishers: Vec<Box<dyn QueuePublisher>>,
+    sharding: Option<ShardConfig>,
     producer_topic: String,
+    wire_format: WireFormat,
+    tx_detail: BlockTransactionsKind,
+    headers_only: bool,
+    head: Option<HeadPublisher>,
+    priority_transfers: Option<PriorityTransferPublisher>,
+    redaction: Option<Redaction>,
+    sampler: Option<Sampler>,
+    pause: Option<PauseCheck>,
+    lane: Option<LaneCheck>,
+    backfill_order: BackfillOrder,
+    delivery: DeliveryMode,
+    heartbeat: Option<Arc<Heartbeat>>,
+    gap_handoff: Option<UnboundedSender<(u64, u64)>>,
 }
 
 impl EVMProducer {
     pub async fn new(
-        adapter: Arc<Mutex<dyn BlockchainAdapter>>,
-        pulsar: Arc<PulsarClient>,
+        adapter: Arc<dyn BlockchainAdapter>,
+        queue: Arc<dyn MessageQueue>,
         producer_topic: String,
     ) -> Result<Self> {
-        let producer = create_producer(&pulsar, producer_topic.clone()).await?;
+        let publisher = queue.publisher(&producer_topic).await?;
         Ok(Self {
             adapter,
-            producer: Arc::new(Mutex::new(producer)),
+            publishers: vec![publisher],
+            sharding: None,
             producer_topic,
+            wire_format: WireFormat::Json,
+            tx_detail: BlockTransactionsKind::Full,
+            headers_only: false,
+            head: None,
+            priority_transfers: None,
+            redaction: None,
+            sampler: None,
+            pause: None,
+            lane: None,
+            backfill_order: BackfillOrder::OldestFirst,
+            delivery: DeliveryMode::AtLeastOnce,
+            heartbeat: None,
+            gap_handoff: None,
         })
     }
+
+    /// Reports realtime progress to `heartbeat` for a watchdog. A restarted `produce_realtime`
+    /// picks up after the heartbeat's last block, backfilling what it missed.
+    pub fn with_heartbeat(mut self, heartbeat: Arc<Heartbeat>) -> Self {
+        self.heartbeat = Some(heartbeat);
+        self
+    }
+
+    /// With at-most-once delivery a failed send is dropped rather than retried.
+    pub fn with_delivery(mut self, delivery: DeliveryMode) -> Self {
+        self.delivery = delivery;
+        self
+    }
+
+    /// Also publishes a `ChainHead` to `head_topic` for every new realtime block.
+    pub async fn with_head_topic(
+        mut self,
+        queue: Arc<dyn MessageQueue>,
+        head_topic: &str,
+        chain_name: &str,
+        wire_format: WireFormat,
+    ) -> Result<Self> {
+        self.head = Some(HeadPublisher {
+            publisher: queue.publisher(head_topic).await?,
+            chain_name: chain_name.to_string(),
+            wire_format,
+        });
+        Ok(self)
+    }
+
+    /// Also runs the allowlisted token transfer fast path for every new realtime block.
+    pub fn with_priority_transfers(mut self, priority_transfers: PriorityTransferPublisher) -> Self {
+        self.priority_transfers = Some(priority_transfers);
+        self
+    }
+
+    /// Stops publishing while the `schema` stream of `chain_name` is paused in `control`.
+    pub fn with_stream_control(mut self, control: Arc<StreamControl>, chain_name: &str, schema: &str) -> Self {
+        self.pause = Some(PauseCheck {
+            control,
+            chain_name: chain_name.to_string(),
+            schema: schema.to_string(),
+        });
+        self
+    }
+
+    /// Hands the blocks skipped while the stream was paused to `gap_handoff`, i.e. the stream's
+    /// backfill job runner, instead of fetching them in the realtime loop ahead of new heads.
+    pub fn with_gap_handoff(mut self, gap_handoff: UnboundedSender<(u64, u64)>) -> Self {
+        self.gap_handoff = Some(gap_handoff);
+        self
+    }
+
+    /// Places the `schema` stream of `chain_name` in `lane`. Critical streams report how far
+    /// their realtime blocks lag to `monitor`, and best-effort ones shed load while any lags.
+    pub fn with_lane(mut self, monitor: Arc<LaneMonitor>, lane: Lane, chain_name: &str, schema: &str) -> Self {
+        self.lane = Some(LaneCheck {
+            monitor,
+            lane,
+            chain_name: chain_name.to_string(),
+            schema: schema.to_string(),
+        });
+        self
+    }
+
+    /// Rewrites the configured address fields of every block before it is published.
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.redaction = Some(redaction);
+        self
+    }
+
+    /// Publishes only the blocks `sampler` keeps, stamping its rate on every message.
+    pub fn with_sampling(mut self, sampler: Sampler) -> Self {
+        self.sampler = Some(sampler);
+        self
+    }
+
+    /// Walks historical ranges in `order`. Native range streams are always read oldest first.
+    pub fn with_backfill_order(mut self, order: BackfillOrder) -> Self {
+        self.backfill_order = order;
+        self
+    }
+
+    /// Publishes to the shard topics of the producer topic instead of the topic itself.
+    pub async fn with_shards(mut self, queue: Arc<dyn MessageQueue>, sharding: ShardConfig) -> Result<Self> {
+        let mut publishers = Vec::with_capacity(sharding.count);
+        for topic in sharding.topics(&self.producer_topic) {
+            publishers.push(queue.publisher(&topic).await?);
+        }
+        self.publishers = publishers;
+        self.sharding = Some(sharding);
+        Ok(self)
+    }
+
+    /// Encodes published blocks in `wire_format` instead of JSON.
+    pub fn with_wire_format(mut self, wire_format: WireFormat) -> Self {
+        self.wire_format = wire_format;
+        self
+    }
+
+    /// Publishes blocks with transaction hashes instead of full transactions when `tx_detail`
+    /// is `Hashes`, and fetches them that way too.
+    pub fn with_tx_detail(mut self, tx_detail: BlockTransactionsKind) -> Self {
+        self.tx_detail = tx_detail;
+        self
+    }
+
+    /// Publishes a `BlockHeader` per block instead of the block, for the `headers` schema. Blocks
+    /// are fetched with transaction hashes only, which is all a header needs.
+    pub fn with_headers_only(mut self) -> Self {
+        self.headers_only = true;
+        self.tx_detail = BlockTransactionsKind::Hashes;
+        self
+    }
+
+    fn is_paused(&self) -> bool {
+        self.pause.as_ref().map_or(false, |pause| pause.control.is_paused(&pause.chain_name, &pause.schema))
+    }
+
+    /// Whether this is a best-effort stream whose chain is under pressure.
+    fn is_shedding(&self) -> bool {
+        self.lane.as_ref().map_or(false, |lane| lane.lane == Lane::BestEffort && lane.monitor.is_shedding(&lane.chain_name))
+    }
+
+    /// Tells the lane monitor how long a critical stream took from receiving a realtime block to
+    /// publishing it, backfilling any gap before it included. Unlike the block's timestamp this
+    /// doesn't depend on the chain's clock or block time.
+    fn report_delay(&self, received: Instant) {
+        let Some(lane) = self.lane.as_ref().filter(|lane| lane.lane == Lane::Critical) else {
+            return;
+        };
+        lane.monitor.report_delay(&lane.chain_name, &lane.schema, received.elapsed());
+    }
+
+    async fn wait_while_paused(&self) {
+        while self.is_paused() || self.is_shedding() {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    /// Whether block `block_number` is published, counting the ones sampled out.
+    fn samples(&self, block_number: u64) -> bool {
+        let keeps = self.sampler.map_or(true, |sampler| sampler.keeps(block_number));
+        if !keeps {
+            metrics::increment_counter("producer_sampled_out_blocks_total", &[("topic", &self.producer_topic)], 1);
+        }
+        keeps
+    }
+
+    /// Shard a message of block `number` goes to when blocks aren't split by address.
+    fn shard(&self, number: Option<U64>) -> usize {
+        match (&self.sharding, number) {
+            (Some(sharding), Some(number)) => sharding.block_shard(number.as_u64()),
+            _ => 0,
+        }
+    }
+
+    async fn publish<T: Versioned>(&self, number: Option<U64>, message: &T) -> Result<()> {
+        self.publish_to(self.shard(number), number, message).await
+    }
+
+    async fn publish_to<T: Versioned>(&self, shard: usize, number: Option<U64>, message: &T) -> Result<()> {
+        let serialized_block = encode_sampled(self.wire_format, message, self.sampler.map(|sampler| sampler.rate()))?;
+        self.send(shard, number.map(|number| number.as_u64()), serialized_block).await
+    }
+
+    /// What `block_number` is published under, rising in the order this producer publishes.
+    fn sequence_key(&self, block_number: u64) -> u64 {
+        match self.backfill_order {
+            BackfillOrder::OldestFirst => block_number,
+            BackfillOrder::NewestFirst => descending_key(block_number),
+        }
+    }
+
+    /// Sends a payload to a shard's topic. At least once, a failed send is retried with backoff
+    /// and fails the stream after `PUBLISH_ATTEMPTS`; at most once, it is logged and dropped.
+    async fn send(&self, shard: usize, block_number: Option<u64>, payload: Vec<u8>) -> Result<()> {
+        let publisher = &self.publishers[shard];
+        let mut backoff = PUBLISH_RETRY_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            let result = match block_number {
+                Some(block_number) => publisher.publish_block(self.sequence_key(block_number), payload.clone()).await,
+                None => publisher.publish(payload.clone()).await,
+            };
+            let Err(e) = result else {
+                return Ok(());
+            };
+            if self.delivery == DeliveryMode::AtMostOnce {
+                warn!("Dropped block {:?} for {} after a failed send: {}", block_number, self.producer_topic, e);
+                metrics::increment_counter("producer_dropped_messages_total", &[("topic", &self.producer_topic)], 1);
+                return Ok(());
+            }
+            if attempt >= PUBLISH_ATTEMPTS {
+                return Err(e);
+            }
+            warn!("Send of block {:?} for {} failed, retrying in {:?}: {}", block_number, self.producer_topic, backoff, e);
+            tokio::time::sleep(backoff).await;
+            backoff *= 2;
+            attempt += 1;
+        }
+    }
+
+    async fn publish_block(&self, block: &Block<Transaction>) -> Result<()> {
+        if !self.samples(block.number.unwrap_or_default().as_u64()) {
+            return Ok(());
+        }
+        let redacted;
+        let block = match &self.redaction {
+            Some(redaction) => {
+                redacted = redaction.redact(block.clone());
+                &redacted
+            }
+            None => block,
+        };
+        if self.headers_only {
+            return self.publish(block.number, &BlockHeader::from(block)).await;
+        }
+        if let Some(sharding) = self.sharding.filter(|sharding| sharding.key == ShardKey::Address) {
+            let parts = sharding.split(block);
+            for (shard, part) in &parts {
+                self.publish_to(*shard, block.number, part).await?;
+            }
+            // Only now is every part out, so consumers may store them.
+            let commit = encode(self.wire_format, &FanoutCommit { fanout_commit: block.hash.unwrap_or_default() })?;
+            let block_number = block.number.unwrap_or_default().as_u64();
+            for (shard, _) in &parts {
+                self.send(*shard, Some(block_number), commit.clone()).await?;
+            }
+            return Ok(());
+        }
+        match self.tx_detail {
+            BlockTransactionsKind::Full => self.publish(block.number, block).await,
+            BlockTransactionsKind::Hashes => self.publish(block.number, &with_transaction_hashes(block)?).await,
+        }
+    }
+
+    /// Fetches one block at the configured detail and publishes it. Returns whether it existed.
+    /// Blocks sampled out aren't fetched at all and count as existing.
+    async fn fetch_and_publish(&self, block_number: u64) -> Result<bool> {
+        if !self.samples(block_number) {
+            return Ok(true);
+        }
+        match self.tx_detail {
+            BlockTransactionsKind::Full => match self.adapter.get_block_by_number(block_number).await? {
+                Some(block) => self.publish_block(&block).await.map(|_| true),
+                None => Ok(false),
+            },
+            BlockTransactionsKind::Hashes => match self.adapter.get_block_with_hashes(block_number).await? {
+                Some(mut block) => {
+                    if let Some(redaction) = &self.redaction {
+                        redaction.redact_header(&mut block);
+                    }
+                    if self.headers_only {
+                        self.publish(block.number, &BlockHeader::from(&block)).await?;
+                    } else {
+                        self.publish(block.number, &block).await?;
+                    }
+                    Ok(true)
+                }
+                None => Ok(false),
+            },
+        }
+    }
+
+    async fn publish_head(&self, block: &Block<Transaction>) -> Result<()> {
+        let (Some(head), Some(number)) = (&self.head, block.number) else {
+            return Ok(());
+        };
+        let chain_head = ChainHead {
+            chain_name: head.chain_name.clone(),
+            block_number: number.as_u64(),
+            hash: block.hash.unwrap_or_default(),
+            timestamp: block.timestamp.as_u64(),
+        };
+        head.publisher.publish(chain_head.encode(head.wire_format)?).await
+    }
+
+    async fn publish_priority_transfers(&self, block_number: u64) {
+        let Some(priority_transfers) = &self.priority_transfers else {
+            return;
+        };
+        // The block itself is already published, so a failed fast path must not stop the stream.
+        if let Err(e) = priority_transfers.process(block_number).await {
+            warn!("Priority transfers of block {} for {} failed: {}", block_number, self.producer_topic, e);
+        }
+    }
+
+    /// Fetches and publishes a block of a gap, retrying failed fetches with backoff. A block the
+    /// node doesn't have yet is retried too: the subscription already announced a later one, so
+    /// it is usually a load-balanced node lagging behind. Returns `false` if it never turned up,
+    /// and fails once every attempt failed, so the restarted producer backfills from its
+    /// heartbeat's last block.
+    async fn fetch_gap_block(&self, block_number: u64) -> Result<bool> {
+        let mut backoff = GAP_RETRY_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            let error = match self.fetch_and_publish(block_number).await {
+                Ok(true) => return Ok(true),
+                Ok(false) if attempt >= GAP_FETCH_ATTEMPTS => return Ok(false),
+                Ok(false) => format!("block {} not found", block_number),
+                Err(e) if attempt >= GAP_FETCH_ATTEMPTS => return Err(e),
+                Err(e) => e.to_string(),
+            };
+            warn!(
+                "Gap backfill of block {} for {} failed, retrying in {:?}: {}",
+                block_number, self.producer_topic, backoff, error
+            );
+            metrics::increment_counter("realtime_gap_fetch_retries_total", &[("topic", &self.producer_topic)], 1);
+            if let Some(heartbeat) = &self.heartbeat {
+                heartbeat.touch();
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(GAP_MAX_RETRY_BACKOFF);
+            attempt += 1;
+        }
+    }
+
+    /// Hands the blocks between `last_block_number` and `block_number`, skipped while the stream
+    /// was paused, to the backfill job runner, and moves `last_block_number` past them. Without a
+    /// handoff they are left to the gap backfill.
+    fn hand_off_paused(&self, last_block_number: &mut Option<u64>, block_number: Option<u64>) {
+        let (Some(gap_handoff), Some(last), Some(number)) = (&self.gap_handoff, *last_block_number, block_number) else {
+            return;
+        };
+        if number <= last + 1 || gap_handoff.send((last + 1, number - 1)).is_err() {
+            return;
+        }
+        info!(
+            "Stream {} resumed, handing blocks {}..={} skipped while paused to the backfill",
+            self.producer_topic, last + 1, number - 1
+        );
+        metrics::increment_counter("paused_blocks_handed_off_total", &[("topic", &self.producer_topic)], number - 1 - last);
+        *last_block_number = Some(number - 1);
+    }
+
+    /// Fetches and publishes `start_block..=end_block`, which the realtime subscription skipped.
+    async fn backfill_gap(&self, start_block: u64, end_block: u64) -> Result<()> {
+        warn!(
+            "Realtime stream for {} skipped blocks {}..={}, backfilling",
+            self.producer_topic, start_block, end_block
+        );
+        for block_number in start_block..=end_block {
+            if !self.fetch_gap_block(block_number).await? {
+                warn!(
+                    "Block {} for {} still not found after {} attempts, skipping it in the gap backfill",
+                    block_number, self.producer_topic, GAP_FETCH_ATTEMPTS
+                );
+                metrics::increment_counter("realtime_gap_missing_blocks_total", &[("topic", &self.producer_topic)], 1);
+                continue;
+            }
+            // A long gap takes longer than the watchdog's stall timeout, so every block beats.
+            if let Some(heartbeat) = &self.heartbeat {
+                heartbeat.beat(block_number);
+            }
+            self.publish_priority_transfers(block_number).await;
+        }
+        metrics::increment_counter(
+            "realtime_gap_backfilled_blocks_total",
+            &[("topic", &self.producer_topic)],
+            end_block - start_block + 1,
+        );
+        Ok(())
+    }
 }
 
 #[async_trait]
 impl StreamProducer for EVMProducer {
     async fn produce_realtime(&self) -> Result<()> {
-        let mut stream = self.adapter.lock().await.subscribe_new_blocks();
+        let mut stream = self.adapter.subscribe_new_blocks();
+        let mut last_block_number = self.heartbeat.as_ref().and_then(|heartbeat| heartbeat.last_block());
+        let mut skipped_while_paused = false;
         while let Some(block_result) = stream.next().await {
             match block_result {
-                Ok(block) => {
-                    // Produce block to Pulsar
-                    let mut producer = self.producer.lock().await;
-                    let serialized_block = serde_json::to_string(&block)?;
-                    producer.send(serialized_block).await?;
+                Ok(mut block) => {
+                    let received = Instant::now();
+                    // Lets consumers tell the block's own lag from the pipeline's.
+                    stamp_received_at(&mut block);
+                    let block_number = block.number.map(|number| number.as_u64());
+                    // Blocks arriving while paused are dropped. The last published block stays
+                    // behind, so they are handed off, or backfilled below, once the stream resumes.
+                    if self.is_paused() {
+                        if let Some(heartbeat) = &self.heartbeat {
+                            heartbeat.touch();
+                        }
+                        skipped_while_paused = true;
+                        if last_block_number.is_none() {
+                            last_block_number = block_number.map(|number| number.saturating_sub(1));
+                        }
+                        continue;
+                    }
+                    // Blocks shed under pressure are dropped for good, so the gap backfill below
+                    // doesn't bring the load back once the chain catches up.
+                    if self.is_shedding() {
+                        if let Some(heartbeat) = &self.heartbeat {
+                            heartbeat.touch();
+                        }
+                        last_block_number = last_block_number.max(block_number);
+                        metrics::increment_counter("lane_shed_blocks_total", &[("topic", &self.producer_topic)], 1);
+                        continue;
+                    }
+
+                    if std::mem::take(&mut skipped_while_paused) {
+                        self.hand_off_paused(&mut last_block_number, block_number);
+                    }
+                    // Subscriptions skip blocks across reconnects. Publish the missing ones first so
+                    // the topic stays in block order.
+                    if let (Some(last), Some(number)) = (last_block_number, block_number) {
+                        if number > last + 1 {
+                            self.backfill_gap(last + 1, number - 1).await?;
+                        }
+                    }
+                    last_block_number = last_block_number.max(block_number);
+
+                    // Produce block to the queue
+                    self.publish_block(&block).await?;
+                    self.publish_head(&block).await?;
+                    self.report_delay(received);
+                    if let Some(number) = block_number {
+                        if let Some(heartbeat) = &self.heartbeat {
+                            heartbeat.beat(number);
+                        }
+                        self.publish_priority_transfers(number).await;
+                    }
                 }
                 Err(e) => {
-                    // Handle error
-                    eprintln!("Error processing block: {:?}", e);
+                    error!("Error processing block for {}: {:?}", self.producer_topic, e);
                 }
             }
         }
@@ -56,14 +563,31 @@ impl StreamProducer for EVMProducer {
     }
 
     async fn produce_historical(&self, start_block: u64, end_block: u64) -> Result<()> {
-        for block_number in start_block..=end_block {
-            let block = self.adapter.lock().await.get_block_by_number(block_number).await?;
-            if let Some(block) = block {
-                // Produce block to Pulsar
-                let mut producer = self.producer.lock().await;
-                let serialized_block = serde_json::to_string(&block)?;
-                producer.send(serialized_block).await?;
+        // Prefer the adapter's native range stream (e.g. Firehose) over one request per block,
+        // even though it always carries full transactions. Range streams run oldest first, which
+        // would publish newest-first chunks against their sequence keys.
+        let range_stream = match self.backfill_order {
+            BackfillOrder::OldestFirst => self.adapter.stream_blocks(start_block, end_block),
+            BackfillOrder::NewestFirst => None,
+        };
+        if let Some(mut stream) = range_stream {
+            while let Some(block) = stream.next().await {
+                let block = block?;
+                self.wait_while_paused().await;
+                // Produce block to the queue
+                self.publish_block(&block).await?;
             }
+            return Ok(());
+        }
+
+        let block_numbers: Box<dyn Iterator<Item = u64> + Send> = match self.backfill_order {
+            BackfillOrder::OldestFirst => Box::new(start_block..=end_block),
+            BackfillOrder::NewestFirst => Box::new((start_block..=end_block).rev()),
+        };
+        for block_number in block_numbers {
+            self.wait_while_paused().await;
+            // Produce block to the queue
+            self.fetch_and_publish(block_number).await?;
         }
         Ok(())
     }
@@ -107,4 +631,47 @@ impl<A: BlockchainAdapter + Send + Sync + 'static> BlockchainAdapter for Arc<Mut
             adapter.lock().await.get_latest_block_number().await
         })
     }
+
+    fn get_balance(
+        &self,
+        address: Address,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = Result<U256>> + Send>> {
+        let adapter = self.clone();
+        Box::pin(async move {
+            adapter.lock().await.get_balance(address, block_number).await
+        })
+    }
+
+    fn get_logs(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<Log>>> + Send>> {
+        let adapter = self.clone();
+        Box::pin(async move {
+            adapter.lock().await.get_logs(block_number).await
+        })
+    }
+
+    fn call(
+        &self,
+        to: Address,
+        data: Bytes,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = Result<Bytes>> + Send>> {
+        let adapter = self.clone();
+        Box::pin(async move {
+            adapter.lock().await.call(to, data, block_number).await
+        })
+    }
+
+    fn get_block_receipts(
+        &self,
+        block_number: u64,
+    ) -> Pin<Box<dyn Future<Output = Result<Vec<TransactionReceipt>>> + Send>> {
+        let adapter = self.clone();
+        Box::pin(async move {
+            adapter.lock().await.get_block_receipts(block_number).await
+        })
+    }
 }
\ No newline at end of file
@@ -1,2 +1,6 @@
 pub mod producer;
+pub mod backfill_jobs;
 pub mod evm_producer;
+pub mod cdc_producer;
+pub mod routing_producer;
+pub mod watchdog;
@@ -0,0 +1,125 @@
+use anyhow::{anyhow, Context, Result};
+use async_trait::async_trait;
+use ethers::types::{Address, Block, Bytes, Transaction};
+use serde::Deserialize;
+use std::sync::Arc;
+
+use crate::streams::consumers::hooks::ConsumerHook;
+use crate::streams::message_queue::queue::{MessageQueue, QueuePublisher};
+use crate::streams::schemas::routing::RoutedTransaction;
+use crate::streams::schemas::schema::{MessageSchema, WireFormat, WireFormatConfig};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RouteConfig {
+    pub name: String, // matching transactions are published to `{chain}-{name}`
+    #[serde(default)]
+    pub to: Vec<String>,
+    #[serde(default)]
+    pub from: Vec<String>,
+    #[serde(default)]
+    pub selectors: Vec<String>, // 4-byte method selectors, e.g. "0xa9059cbb"
+}
+
+/// A transaction matches when every non-empty criterion has a match.
+struct TransactionFilter {
+    to: Vec<Address>,
+    from: Vec<Address>,
+    selectors: Vec<[u8; 4]>,
+}
+
+impl TransactionFilter {
+    fn new(route: &RouteConfig) -> Result<Self> {
+        let parse_addresses = |addresses: &[String]| {
+            addresses
+                .iter()
+                .map(|address| {
+                    address
+                        .parse::<Address>()
+                        .with_context(|| format!("Invalid address `{}` in route `{}`", address, route.name))
+                })
+                .collect::<Result<Vec<_>>>()
+        };
+        let selectors = route
+            .selectors
+            .iter()
+            .map(|selector| {
+                let bytes = selector
+                    .parse::<Bytes>()
+                    .with_context(|| format!("Invalid selector `{}` in route `{}`", selector, route.name))?;
+                <[u8; 4]>::try_from(bytes.as_ref())
+                    .map_err(|_| anyhow!("Selector `{}` in route `{}` is not 4 bytes", selector, route.name))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let filter = Self {
+            to: parse_addresses(&route.to)?,
+            from: parse_addresses(&route.from)?,
+            selectors,
+        };
+        if filter.to.is_empty() && filter.from.is_empty() && filter.selectors.is_empty() {
+            return Err(anyhow!("Route `{}` has no to, from or selectors criteria", route.name));
+        }
+        Ok(filter)
+    }
+
+    fn matches(&self, transaction: &Transaction) -> bool {
+        (self.to.is_empty() || transaction.to.map_or(false, |to| self.to.contains(&to)))
+            && (self.from.is_empty() || self.from.contains(&transaction.from))
+            && (self.selectors.is_empty()
+                || transaction.input.get(..4).map_or(false, |selector| {
+                    self.selectors.iter().any(|candidate| candidate.as_slice() == selector)
+                }))
+    }
+}
+
+struct Route {
+    name: String,
+    filter: TransactionFilter,
+    publisher: Box<dyn QueuePublisher>,
+    wire_format: WireFormat,
+}
+
+/// Publishes committed transactions that match a chain's routing rules to dedicated topics, so
+/// consumers interested in a few contracts don't have to read the full transaction stream.
+pub struct RoutingProducer {
+    routes: Vec<Route>,
+}
+
+impl RoutingProducer {
+    pub async fn new(
+        queue: Arc<dyn MessageQueue>,
+        topic_prefix: &str,
+        chain_name: &str,
+        routes: &[RouteConfig],
+        wire_formats: &WireFormatConfig,
+    ) -> Result<Self> {
+        let mut built = Vec::with_capacity(routes.len());
+        for route in routes {
+            let filter = TransactionFilter::new(route)?;
+            let topic = format!("{}-{}", chain_name, route.name);
+            let publisher = queue.publisher(&format!("{}{}", topic_prefix, topic)).await?;
+            let wire_format = wire_formats.for_topic(&topic);
+            built.push(Route { name: route.name.clone(), filter, publisher, wire_format });
+        }
+        Ok(Self { routes: built })
+    }
+}
+
+#[async_trait]
+impl ConsumerHook for RoutingProducer {
+    async fn on_block_committed(&self, chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        for route in &self.routes {
+            for transaction in block.transactions.iter().filter(|transaction| route.filter.matches(transaction)) {
+                let message = RoutedTransaction {
+                    chain_name: chain_name.to_string(),
+                    route: route.name.clone(),
+                    block_number: block.number.unwrap_or_default().as_u64(),
+                    block_hash: block.hash.unwrap_or_default(),
+                    transaction: transaction.clone(),
+                };
+                route.publisher.publish(message.encode(route.wire_format)?).await?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,147 @@
+use anyhow::Result;
+use log::warn;
+use serde::Deserialize;
+use serde_json::json;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
+
+use crate::alerting::{Alert, Alerter};
+use crate::blockchain::adapters::BlockchainAdapter;
+use crate::metrics;
+use crate::server::dashboard;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct WatchdogConfig {
+    pub enabled: bool,
+    /// How long a realtime producer may go without publishing while the chain advances before
+    /// it counts as stalled.
+    pub stall_secs: u64,
+    pub check_interval_secs: u64,
+}
+
+impl Default for WatchdogConfig {
+    fn default() -> Self {
+        Self { enabled: false, stall_secs: 120, check_interval_secs: 15 }
+    }
+}
+
+/// Signs of life of a realtime producer, updated as it publishes.
+pub struct Heartbeat {
+    /// Last published block number plus one, so 0 means none yet.
+    last_block: AtomicU64,
+    last_beat: Mutex<Instant>,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self { last_block: AtomicU64::new(0), last_beat: Mutex::new(Instant::now()) }
+    }
+}
+
+impl Heartbeat {
+    /// Records that `block_number` was published.
+    pub fn beat(&self, block_number: u64) {
+        self.last_block.fetch_max(block_number + 1, Ordering::Relaxed);
+        self.touch();
+    }
+
+    /// Records that the producer is alive without publishing, e.g. while its stream is paused.
+    pub fn touch(&self) {
+        *self.last_beat.lock().unwrap_or_else(|e| e.into_inner()) = Instant::now();
+    }
+
+    pub fn last_block(&self) -> Option<u64> {
+        self.last_block.load(Ordering::Relaxed).checked_sub(1)
+    }
+
+    pub fn since_last_beat(&self) -> Duration {
+        self.last_beat.lock().unwrap_or_else(|e| e.into_inner()).elapsed()
+    }
+}
+
+/// Restarts a realtime producer that is still running but has stopped publishing, e.g. because
+/// its WebSocket subscription died without an error. A producer counts as stalled once it hasn't
+/// published for `stall_secs` while the chain's head is past its last published block.
+pub struct Watchdog {
+    adapter: Arc<dyn BlockchainAdapter>,
+    heartbeat: Arc<Heartbeat>,
+    alerter: Alerter,
+    chain_name: String,
+    task_name: String,
+    stall: Duration,
+    check_interval: Duration,
+}
+
+impl Watchdog {
+    pub fn new(
+        adapter: Arc<dyn BlockchainAdapter>,
+        heartbeat: Arc<Heartbeat>,
+        alerter: Alerter,
+        chain_name: &str,
+        task_name: &str,
+        config: &WatchdogConfig,
+    ) -> Self {
+        Self {
+            adapter,
+            heartbeat,
+            alerter,
+            chain_name: chain_name.to_string(),
+            task_name: task_name.to_string(),
+            stall: Duration::from_secs(config.stall_secs.max(1)),
+            check_interval: Duration::from_secs(config.check_interval_secs.max(1)),
+        }
+    }
+
+    /// Returns once the producer has stalled, with the chain's head.
+    async fn stalled(&self) -> u64 {
+        loop {
+            tokio::time::sleep(self.check_interval).await;
+            if self.heartbeat.since_last_beat() < self.stall {
+                continue;
+            }
+            match self.adapter.get_latest_block_number().await {
+                Ok(head) if self.heartbeat.last_block().map_or(true, |last_block| head > last_block) => return head,
+                Ok(_) => {}
+                // The chain can't be checked, so neither can the producer.
+                Err(e) => warn!("Watchdog of {} failed to get the head of {}: {}", self.task_name, self.chain_name, e),
+            }
+        }
+    }
+
+    /// Runs the producer, restarting it whenever it stalls. Returns when it ends on its own.
+    pub async fn supervise<F, Fut>(&self, mut run: F) -> Result<()>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        loop {
+            tokio::select! {
+                result = run() => return result,
+                head = self.stalled() => {
+                    let silent_secs = self.heartbeat.since_last_beat().as_secs();
+                    let last_block = self.heartbeat.last_block();
+                    let message = format!(
+                        "{} published nothing for {}s while {} reached block {}, restarting it",
+                        self.task_name, silent_secs, self.chain_name, head
+                    );
+                    metrics::increment_counter("producer_stalls_total", &[("task", &self.task_name)], 1);
+                    dashboard::record_error(&self.task_name, &message);
+                    self.alerter
+                        .fire(&Alert {
+                            chain_name: self.chain_name.clone(),
+                            kind: "producer_stalled".to_string(),
+                            message,
+                            details: json!({ "task": self.task_name, "silent_secs": silent_secs, "head_block": head, "last_published_block": last_block }),
+                        })
+                        .await;
+                    // The restarted producer gets a full stall period before it is checked again.
+                    self.heartbeat.touch();
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{anyhow, Context, Result};
+use ethers::types::{Address, Block, Transaction, U256, U64};
+use ethers::utils::keccak256;
+use serde::Deserialize;
+use std::env;
+
+/// Address fields a schema's blocks can be redacted in.
+const REDACTABLE_FIELDS: [&str; 3] = ["miner", "from", "to"];
+
+/// How a redacted address is rewritten.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionMode {
+    /// Replaced by a keyed hash, so the same address still maps to the same value.
+    #[default]
+    Hash,
+    /// Only the first `keep_bytes` bytes are kept; the rest are zeroed.
+    Truncate,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct RedactionConfig {
+    /// Fields to rewrite: `miner` of blocks, `from` and `to` of transactions.
+    pub fields: Vec<String>,
+    #[serde(default)]
+    pub mode: RedactionMode,
+    /// Env var holding the salt hashed in with each address, so hashes can't be matched against
+    /// the hashes of known addresses.
+    pub salt_env: Option<String>,
+    #[serde(default = "default_keep_bytes")]
+    pub keep_bytes: usize,
+}
+
+fn default_keep_bytes() -> usize {
+    4
+}
+
+/// Rewrites configured address fields of a schema's blocks before they are published, so
+/// neither the topics nor the sinks ever hold them. Redacting `from` also clears transaction
+/// signatures, from which the sender could be recovered.
+#[derive(Debug, Clone)]
+pub struct Redaction {
+    mode: RedactionMode,
+    salt: Vec<u8>,
+    keep_bytes: usize,
+    miner: bool,
+    from: bool,
+    to: bool,
+}
+
+impl Redaction {
+    pub fn new(config: &RedactionConfig) -> Result<Self> {
+        if let Some(field) = config.fields.iter().find(|field| !REDACTABLE_FIELDS.contains(&field.as_str())) {
+            return Err(anyhow!("Field `{}` can't be redacted; redactable fields are {}", field, REDACTABLE_FIELDS.join(", ")));
+        }
+        let salt = match &config.salt_env {
+            Some(salt_env) => env::var(salt_env)
+                .with_context(|| format!("Failed to get redaction salt from environment for key `{}`", salt_env))?
+                .into_bytes(),
+            None => Vec::new(),
+        };
+        let redacts = |field: &str| config.fields.iter().any(|configured| configured == field);
+        Ok(Self {
+            mode: config.mode,
+            salt,
+            keep_bytes: config.keep_bytes.min(Address::len_bytes()),
+            miner: redacts("miner"),
+            from: redacts("from"),
+            to: redacts("to"),
+        })
+    }
+
+    fn address(&self, address: Address) -> Address {
+        match self.mode {
+            RedactionMode::Hash => {
+                let mut input = self.salt.clone();
+                input.extend_from_slice(address.as_bytes());
+                Address::from_slice(&keccak256(input)[12..])
+            }
+            RedactionMode::Truncate => {
+                let mut bytes = [0u8; 20];
+                bytes[..self.keep_bytes].copy_from_slice(&address.as_bytes()[..self.keep_bytes]);
+                Address::from(bytes)
+            }
+        }
+    }
+
+    /// Redacts the block's own fields, whatever its transactions are.
+    pub fn redact_header<TX>(&self, block: &mut Block<TX>) {
+        if self.miner {
+            block.author = block.author.map(|miner| self.address(miner));
+        }
+    }
+
+    pub fn redact(&self, mut block: Block<Transaction>) -> Block<Transaction> {
+        self.redact_header(&mut block);
+        for transaction in &mut block.transactions {
+            if self.from {
+                transaction.from = self.address(transaction.from);
+                transaction.v = U64::zero();
+                transaction.r = U256::zero();
+                transaction.s = U256::zero();
+            }
+            if self.to {
+                transaction.to = transaction.to.map(|to| self.address(to));
+            }
+        }
+        block
+    }
+}
@@ -0,0 +1,44 @@
+use anyhow::{anyhow, Result};
+use ethers::types::H256;
+
+/// Keeps a fixed share of a stream's blocks, for high-volume streams like traces whose full
+/// volume is too costly to collect. The decision depends on the block number alone, so the
+/// realtime and historical producers, every shard and every rerun keep the same blocks. Streams
+/// of a chain with the same rate keep the same blocks too, and their samples can be joined.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sampler {
+    rate: f64,
+}
+
+impl Sampler {
+    pub fn new(rate: f64) -> Result<Self> {
+        if !(rate > 0.0 && rate <= 1.0) {
+            return Err(anyhow!("sample_rate must be greater than 0 and at most 1, got {}", rate));
+        }
+        Ok(Self { rate })
+    }
+
+    /// Share of blocks kept. Consumers weigh each kept block by its inverse to estimate totals.
+    pub fn rate(&self) -> f64 {
+        self.rate
+    }
+
+    pub fn keeps(&self, block_number: u64) -> bool {
+        unit_interval(block_number) < self.rate
+    }
+
+    /// Like `keeps`, for what has no block number yet, like mempool transactions.
+    pub fn keeps_hash(&self, hash: &H256) -> bool {
+        self.keeps(hash.to_low_u64_be())
+    }
+}
+
+/// Maps `x` to a uniformly spread value in [0, 1), with splitmix64's finalizer so consecutive
+/// block numbers don't land in runs.
+fn unit_interval(x: u64) -> f64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    (z >> 11) as f64 / (1u64 << 53) as f64
+}
@@ -0,0 +1,45 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::schema::MessageSchema;
+
+/// Name reported in `source.connector` of every change event.
+pub const CDC_CONNECTOR: &str = "blockchain-data-ingestion";
+
+// Row image of a block as seen by change-event consumers.
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone)]
+pub struct BlockRowImage {
+    pub chain_name: String,
+    pub block_number: i64,
+    pub hash: String,
+    pub canonical: bool,
+}
+
+// Debezium-style `source` block describing where the change happened.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct ChangeSource {
+    pub connector: String,
+    pub name: String,
+    pub table: String,
+    pub ts_ms: i64,
+}
+
+// Debezium-compatible change envelope. `op` follows Debezium: "c" create, "u" update, "d" delete.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct ChangeEnvelope<T> {
+    pub before: Option<T>,
+    pub after: Option<T>,
+    pub source: ChangeSource,
+    pub op: String,
+    pub ts_ms: i64,
+}
+
+impl<T: Serialize + for<'de> Deserialize<'de>> MessageSchema for ChangeEnvelope<T> {
+    fn serialize(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("Failed to serialize ChangeEnvelope")
+    }
+
+    fn deserialize(data: &[u8]) -> Self {
+        serde_json::from_slice(data).expect("Failed to deserialize ChangeEnvelope")
+    }
+}
@@ -1,8 +1,12 @@
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use ethers::types::{H256, U256, Address, Bytes};
+use serde_json::Value;
+use ethers::types::{Block, Transaction, H256, U256, Address, Bytes};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 use super::schema::MessageSchema;
+use crate::streams::sharding::TX_COUNT_FIELD;
 
 // Define the schema for a block
 #[derive(Serialize, Deserialize, Debug)]
@@ -65,4 +69,34 @@ impl MessageSchema for TransactionSchema {
     fn deserialize(data: &[u8]) -> Self {
         serde_json::from_slice(data).expect("Failed to deserialize BlockSchema")
     }
-}
\ No newline at end of file
+}
+/// The block as `eth_getBlockByNumber(n, false)` returns it, with transaction hashes only.
+pub fn with_transaction_hashes(block: &Block<Transaction>) -> Result<Block<H256>> {
+    let mut value = serde_json::to_value(block)?;
+    value["transactions"] = Value::from(block.transactions.iter().map(|tx| format!("{:?}", tx.hash)).collect::<Vec<_>>());
+    Ok(serde_json::from_value(value)?)
+}
+
+/// A hash-only block with its transaction list emptied, in the shape sinks and hooks take. Its
+/// transaction count is kept under `TX_COUNT_FIELD`.
+pub fn without_transactions(block: &Block<H256>) -> Result<Block<Transaction>> {
+    let mut value = serde_json::to_value(block)?;
+    value["transactions"] = Value::Array(Vec::new());
+    value[TX_COUNT_FIELD] = Value::from(block.transactions.len());
+    Ok(serde_json::from_value(value)?)
+}
+
+/// Unix time, in seconds, at which the realtime producer received a block from its subscription.
+/// Backfilled blocks don't carry it.
+pub const RECEIVED_AT_FIELD: &str = "receivedAt";
+
+/// Stamps the current time on `block` under `RECEIVED_AT_FIELD`.
+pub fn stamp_received_at(block: &mut Block<Transaction>) {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+    block.other.insert(RECEIVED_AT_FIELD.to_string(), Value::from(now));
+}
+
+/// When the realtime producer received `block`, if it did.
+pub fn received_at(block: &Block<Transaction>) -> Option<i64> {
+    block.other.get_deserialized::<i64>(RECEIVED_AT_FIELD).and_then(|received_at| received_at.ok())
+}
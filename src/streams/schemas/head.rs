@@ -0,0 +1,26 @@
+use ethers::types::H256;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::schema::MessageSchema;
+
+// The tip of a chain, published to `{chain}-head` for services that don't need block bodies.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct ChainHead {
+    pub chain_name: String,
+    pub block_number: u64,
+    #[schemars(with = "String")]
+    pub hash: H256,
+    /// Unix seconds.
+    pub timestamp: u64,
+}
+
+impl MessageSchema for ChainHead {
+    fn serialize(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("Failed to serialize ChainHead")
+    }
+
+    fn deserialize(data: &[u8]) -> Self {
+        serde_json::from_slice(data).expect("Failed to deserialize ChainHead")
+    }
+}
@@ -0,0 +1,56 @@
+use ethers::types::{Address, Block, Transaction, H256, U256, U64};
+use serde::{Deserialize, Serialize};
+
+use super::versioning::Versioned;
+
+/// A block without its transactions, published by the `headers` schema for chains that only need
+/// chain-tip and timestamp tracking.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct BlockHeader {
+    pub number: U64,
+    pub hash: H256,
+    pub parent_hash: H256,
+    /// Unix seconds.
+    pub timestamp: U256,
+    pub miner: Option<Address>,
+    pub gas_used: U256,
+    pub gas_limit: U256,
+    pub base_fee_per_gas: Option<U256>,
+}
+
+impl Versioned for BlockHeader {
+    const SCHEMA_VERSION: u32 = 1;
+}
+
+impl<TX> From<&Block<TX>> for BlockHeader {
+    fn from(block: &Block<TX>) -> Self {
+        Self {
+            number: block.number.unwrap_or_default(),
+            hash: block.hash.unwrap_or_default(),
+            parent_hash: block.parent_hash,
+            timestamp: block.timestamp,
+            miner: block.author,
+            gas_used: block.gas_used,
+            gas_limit: block.gas_limit,
+            base_fee_per_gas: block.base_fee_per_gas,
+        }
+    }
+}
+
+impl BlockHeader {
+    /// The header as a block without transactions, in the shape sinks and hooks take. Fields the
+    /// header doesn't carry are left at their defaults.
+    pub fn into_block(self) -> Block<Transaction> {
+        Block {
+            number: Some(self.number),
+            hash: Some(self.hash),
+            parent_hash: self.parent_hash,
+            timestamp: self.timestamp,
+            author: self.miner,
+            gas_used: self.gas_used,
+            gas_limit: self.gas_limit,
+            base_fee_per_gas: self.base_fee_per_gas,
+            ..Default::default()
+        }
+    }
+}
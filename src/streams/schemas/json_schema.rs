@@ -0,0 +1,168 @@
+use schemars::schema::RootSchema;
+use schemars::{schema_for, JsonSchema};
+use serde::Serialize;
+
+use super::cdc::{BlockRowImage, ChangeEnvelope};
+use super::head::ChainHead;
+use super::priority::PriorityTransfer;
+use super::routing::RoutedTransaction;
+
+// Shapes of ethers' JSON encoding of blocks and transactions, for schema generation only.
+// Quantities and hashes are 0x-prefixed hex strings. tests/json_schema.rs checks them against
+// encoded fixtures, so a field ethers adds or renames fails there rather than in a consumer.
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct EthTransaction {
+    hash: String,
+    nonce: String,
+    block_hash: Option<String>,
+    block_number: Option<String>,
+    transaction_index: Option<String>,
+    from: String,
+    to: Option<String>,
+    value: String,
+    gas_price: Option<String>,
+    gas: String,
+    input: String,
+    v: String,
+    r: String,
+    s: String,
+    #[serde(rename = "type")]
+    transaction_type: Option<String>,
+    max_priority_fee_per_gas: Option<String>,
+    max_fee_per_gas: Option<String>,
+    chain_id: Option<String>,
+    access_list: Option<Vec<EthAccessListItem>>,
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct EthAccessListItem {
+    address: String,
+    storage_keys: Vec<String>,
+}
+
+/// Full transactions, or only their hashes on `tx_detail = "hashes"` topics.
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(untagged)]
+enum EthBlockTransaction {
+    Full(EthTransaction),
+    Hash(String),
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct EthWithdrawal {
+    index: String,
+    validator_index: String,
+    address: String,
+    amount: String,
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+#[serde(rename_all = "camelCase")]
+struct EthBlock {
+    /// Message schema version; absent on payloads written before versioning, which are version 1.
+    #[serde(rename = "schema_version")]
+    schema_version: Option<u32>,
+    /// Share of blocks a sampled stream publishes.
+    #[serde(rename = "sample_rate")]
+    sample_rate: Option<f64>,
+    hash: Option<String>,
+    parent_hash: String,
+    sha3_uncles: String,
+    miner: Option<String>,
+    state_root: String,
+    transactions_root: String,
+    receipts_root: String,
+    number: Option<String>,
+    gas_used: String,
+    gas_limit: String,
+    extra_data: String,
+    logs_bloom: Option<String>,
+    timestamp: String,
+    difficulty: String,
+    total_difficulty: Option<String>,
+    seal_fields: Option<Vec<String>>,
+    uncles: Vec<String>,
+    transactions: Vec<EthBlockTransaction>,
+    size: Option<String>,
+    mix_hash: Option<String>,
+    nonce: Option<String>,
+    base_fee_per_gas: Option<String>,
+    blob_gas_used: Option<String>,
+    excess_blob_gas: Option<String>,
+    withdrawals_root: Option<String>,
+    withdrawals: Option<Vec<EthWithdrawal>>,
+    parent_beacon_block_root: Option<String>,
+    /// Unix seconds at which the realtime producer received the block. Backfilled blocks don't
+    /// carry it.
+    received_at: Option<u64>,
+}
+
+#[allow(dead_code)]
+#[derive(JsonSchema)]
+struct EthBlockHeader {
+    schema_version: u32,
+    sample_rate: Option<f64>,
+    number: String,
+    hash: String,
+    parent_hash: String,
+    timestamp: String,
+    miner: Option<String>,
+    gas_used: String,
+    gas_limit: String,
+    base_fee_per_gas: Option<String>,
+}
+
+/// The JSON Schema of the messages on one family of topics.
+#[derive(Debug, Serialize)]
+pub struct TopicSchema {
+    pub name: &'static str,
+    /// Topic name patterns under `persistent://public/default/`.
+    pub topics: Vec<&'static str>,
+    pub schema: RootSchema,
+}
+
+/// Schemas of every message type the pipeline publishes, for consumers in other languages to
+/// validate against or generate code from.
+pub fn topic_schemas() -> Vec<TopicSchema> {
+    vec![
+        TopicSchema {
+            name: "block",
+            topics: vec!["{chain}-{schema}", "{chain}-{schema}-historical"],
+            schema: schema_for!(EthBlock),
+        },
+        TopicSchema {
+            name: "block_header",
+            topics: vec!["{chain}-headers", "{chain}-headers-historical"],
+            schema: schema_for!(EthBlockHeader),
+        },
+        TopicSchema {
+            name: "cdc_change",
+            topics: vec!["{chain}-cdc"],
+            schema: schema_for!(ChangeEnvelope<BlockRowImage>),
+        },
+        TopicSchema {
+            name: "routed_transaction",
+            topics: vec!["{chain}-{route}"],
+            schema: schema_for!(RoutedTransaction),
+        },
+        TopicSchema {
+            name: "chain_head",
+            topics: vec!["{chain}-head"],
+            schema: schema_for!(ChainHead),
+        },
+        TopicSchema {
+            name: "priority_transfer",
+            topics: vec!["{chain}-priority-transfers"],
+            schema: schema_for!(PriorityTransfer),
+        },
+    ]
+}
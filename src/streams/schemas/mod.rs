@@ -1,2 +1,9 @@
+pub mod cdc;
 pub mod evm;
 pub mod schema;
+pub mod routing;
+pub mod json_schema;
+pub mod head;
+pub mod header;
+pub mod priority;
+pub mod versioning;
@@ -0,0 +1,37 @@
+use ethers::types::{Address, H256, U256};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::schema::MessageSchema;
+
+// A transfer of an allowlisted token, published to `{chain}-priority-transfers` as soon as its
+// block is seen, ahead of the block's own consumption.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct PriorityTransfer {
+    pub chain_name: String,
+    pub block_number: u64,
+    #[schemars(with = "String")]
+    pub block_hash: H256,
+    #[schemars(with = "String")]
+    pub tx_hash: H256,
+    pub log_index: u64,
+    #[schemars(with = "String")]
+    pub token: Address,
+    #[schemars(with = "String")]
+    pub from: Address,
+    #[schemars(with = "String")]
+    pub to: Address,
+    /// Raw token amount, as a 0x-prefixed hex quantity.
+    #[schemars(with = "String")]
+    pub value: U256,
+}
+
+impl MessageSchema for PriorityTransfer {
+    fn serialize(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("Failed to serialize PriorityTransfer")
+    }
+
+    fn deserialize(data: &[u8]) -> Self {
+        serde_json::from_slice(data).expect("Failed to deserialize PriorityTransfer")
+    }
+}
@@ -0,0 +1,28 @@
+use ethers::types::{Transaction, H256};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::json_schema::EthTransaction;
+use super::schema::MessageSchema;
+
+// A transaction published to a routed topic, with the block it was committed in.
+#[derive(Serialize, Deserialize, JsonSchema, Debug)]
+pub struct RoutedTransaction {
+    pub chain_name: String,
+    pub route: String,
+    pub block_number: u64,
+    #[schemars(with = "String")]
+    pub block_hash: H256,
+    #[schemars(with = "EthTransaction")]
+    pub transaction: Transaction,
+}
+
+impl MessageSchema for RoutedTransaction {
+    fn serialize(&self) -> Vec<u8> {
+        serde_json::to_vec(self).expect("Failed to serialize RoutedTransaction")
+    }
+
+    fn deserialize(data: &[u8]) -> Self {
+        serde_json::from_slice(data).expect("Failed to deserialize RoutedTransaction")
+    }
+}
@@ -1,7 +1,82 @@
-
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 
 // Define a trait for the message schema
 pub trait MessageSchema {
     fn serialize(&self) -> Vec<u8>;
     fn deserialize(data: &[u8]) -> Self;
+
+    /// Encodes the message in `format`, with the format byte in front for binary formats.
+    fn encode(&self, format: WireFormat) -> Result<Vec<u8>>
+    where
+        Self: Serialize + Sized,
+    {
+        encode(format, self)
+    }
+}
+
+/// How a topic's payloads are encoded. JSON payloads are written as-is so existing consumers keep
+/// working; binary payloads start with their format byte.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WireFormat {
+    #[default]
+    Json,
+    #[serde(rename = "messagepack")]
+    MessagePack,
+    /// Only for types without flattened or self-describing fields, so not for ethers blocks
+    /// and transactions.
+    Bincode,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct WireFormatConfig {
+    #[serde(default)]
+    pub default: WireFormat,
+    /// Formats by topic name without the `persistent://public/default/` prefix, e.g. `ETH-blocks`.
+    /// A `-historical` topic uses the format of its realtime topic unless listed itself.
+    #[serde(default)]
+    pub topics: HashMap<String, WireFormat>,
+}
+
+impl WireFormatConfig {
+    pub fn for_topic(&self, topic: &str) -> WireFormat {
+        self.topics
+            .get(topic)
+            .or_else(|| topic.strip_suffix("-historical").and_then(|realtime| self.topics.get(realtime)))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+const MESSAGEPACK_FORMAT_BYTE: u8 = 0x01;
+pub(crate) const BINCODE_FORMAT_BYTE: u8 = 0x02;
+
+pub fn encode<T: Serialize + ?Sized>(format: WireFormat, message: &T) -> Result<Vec<u8>> {
+    match format {
+        WireFormat::Json => Ok(serde_json::to_vec(message)?),
+        WireFormat::MessagePack => {
+            let mut payload = vec![MESSAGEPACK_FORMAT_BYTE];
+            // Named fields, so flattened and optional fields round-trip.
+            rmp_serde::encode::write_named(&mut payload, message)?;
+            Ok(payload)
+        }
+        WireFormat::Bincode => {
+            let mut payload = vec![BINCODE_FORMAT_BYTE];
+            bincode::serialize_into(&mut payload, message)?;
+            Ok(payload)
+        }
+    }
+}
+
+/// Decodes a payload written by `encode` in any format. JSON never starts with a format byte.
+pub fn decode<T: DeserializeOwned>(payload: &[u8]) -> Result<T> {
+    match payload.first() {
+        Some(&MESSAGEPACK_FORMAT_BYTE) => Ok(rmp_serde::from_slice(&payload[1..])?),
+        Some(&BINCODE_FORMAT_BYTE) => Ok(bincode::deserialize(&payload[1..])?),
+        Some(_) => Ok(serde_json::from_slice(payload)?),
+        None => Err(anyhow!("Empty message payload")),
+    }
 }
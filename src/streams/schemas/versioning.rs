@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Context, Result};
+use ethers::types::Block;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::schema::{decode, encode, WireFormat, BINCODE_FORMAT_BYTE};
+
+/// Top-level field holding a message's schema version. Payloads without it predate versioning
+/// and are version 1.
+pub const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+/// Top-level field holding the share of blocks a sampled stream keeps. Absent on unsampled
+/// streams, whose messages stand for every block.
+pub const SAMPLE_RATE_FIELD: &str = "sample_rate";
+
+/// Rewrites a message of one schema version into the next.
+pub type Upgrade = fn(Value) -> Result<Value>;
+
+/// A message type whose shape changes over time. Topics keep messages for a long time, so
+/// consumers must still read every version producers ever wrote: each breaking change bumps
+/// `SCHEMA_VERSION` and adds an up-converter from the previous version to `upgrades`.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Version new messages are written with.
+    const SCHEMA_VERSION: u32;
+
+    /// Up-converters keyed by the version they upgrade from, so `(1, f)` turns a version 1
+    /// message into version 2.
+    fn upgrades() -> Vec<(u32, Upgrade)> {
+        Vec::new()
+    }
+
+    /// Drops `SCHEMA_VERSION_FIELD` and `SAMPLE_RATE_FIELD` from a message decoded straight from
+    /// its payload. Only types that keep unknown fields have anything to drop.
+    fn unstamp(&mut self) {}
+}
+
+// Blocks are the long-retention topics. Version 1 is ethers' JSON-RPC encoding.
+impl<TX: Serialize + DeserializeOwned> Versioned for Block<TX> {
+    const SCHEMA_VERSION: u32 = 1;
+
+    // The stamp lands in the catch-all `other` fields.
+    fn unstamp(&mut self) {
+        self.other.remove(SCHEMA_VERSION_FIELD);
+        self.other.remove(SAMPLE_RATE_FIELD);
+    }
+}
+
+#[derive(Serialize)]
+struct Stamped<'a, T> {
+    schema_version: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    sample_rate: Option<f64>,
+    #[serde(flatten)]
+    message: &'a T,
+}
+
+/// Just the version stamp of a payload. Every other field is skipped while parsing, without
+/// being decoded or copied.
+#[derive(Deserialize)]
+struct Stamp {
+    schema_version: Option<u64>,
+}
+
+/// Encodes `message` stamped with its schema version. Bincode payloads can't carry the stamp,
+/// so bincode topics only ever hold the current version.
+pub fn encode_versioned<T: Versioned>(format: WireFormat, message: &T) -> Result<Vec<u8>> {
+    encode_sampled(format, message, None)
+}
+
+/// Like `encode_versioned`, also stamping the `sample_rate` of a sampled stream. Bincode payloads
+/// can't carry it either.
+pub fn encode_sampled<T: Versioned>(format: WireFormat, message: &T, sample_rate: Option<f64>) -> Result<Vec<u8>> {
+    match format {
+        WireFormat::Bincode => encode(format, message),
+        _ => encode(format, &Stamped { schema_version: T::SCHEMA_VERSION, sample_rate, message }),
+    }
+}
+
+/// Decodes a payload of any schema version of `T`, running the up-converters from its version to
+/// the current one first. Payloads from a newer version than this build knows are rejected, so
+/// they are never silently misread after a rollback.
+///
+/// Payloads of the current version, i.e. nearly all of them, are decoded straight from the
+/// payload bytes. Only older ones go through a `Value` tree for their up-converters, which
+/// allocates for every field.
+pub fn decode_versioned<T: Versioned>(payload: &[u8]) -> Result<T> {
+    if payload.first() == Some(&BINCODE_FORMAT_BYTE) {
+        return decode(payload);
+    }
+    let stamp: Stamp = decode(payload).with_context(|| format!("Invalid {}", SCHEMA_VERSION_FIELD))?;
+    let version = stamp.schema_version.unwrap_or(1);
+    if version == T::SCHEMA_VERSION as u64 {
+        let mut message: T = decode(payload)?;
+        message.unstamp();
+        return Ok(message);
+    }
+    if version > T::SCHEMA_VERSION as u64 {
+        return Err(anyhow!(
+            "Message has schema version {}, newer than the supported {}",
+            version,
+            T::SCHEMA_VERSION
+        ));
+    }
+
+    let mut value: Value = decode(payload)?;
+    if let Some(object) = value.as_object_mut() {
+        object.remove(SCHEMA_VERSION_FIELD);
+        object.remove(SAMPLE_RATE_FIELD);
+    }
+    let upgrades = T::upgrades();
+    for from in version as u32..T::SCHEMA_VERSION {
+        let (_, upgrade) = upgrades
+            .iter()
+            .find(|(version, _)| *version == from)
+            .ok_or_else(|| anyhow!("No up-converter from schema version {}", from))?;
+        value = upgrade(value).with_context(|| format!("Failed to upgrade message from schema version {}", from))?;
+    }
+    Ok(serde_json::from_value(value)?)
+}
@@ -0,0 +1,140 @@
+use anyhow::{anyhow, Result};
+use ethers::types::{Address, Block, Transaction, H256};
+use serde::{Deserialize, Serialize};
+
+/// Field stamped on blocks that carry only some of their transactions, like the part shard 0
+/// stores or a hash-only block, with the transaction count of the whole block.
+pub const TX_COUNT_FIELD: &str = "fullTxCount";
+
+/// Transaction count of the block `block` is a part of.
+pub fn tx_count(block: &Block<Transaction>) -> usize {
+    block
+        .other
+        .get_deserialized::<usize>(TX_COUNT_FIELD)
+        .and_then(|count| count.ok())
+        .unwrap_or(block.transactions.len())
+}
+
+/// What decides which shard a block's data is published to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShardKey {
+    /// Whole blocks, by block number modulo the shard count.
+    #[default]
+    BlockNumber,
+    /// Transactions, by sender address. Every other shard gets the block header with the
+    /// transactions of its senders. Shard 0 gets the whole block: it owns the block row, stores
+    /// only its own senders' transactions, and runs the consumer hooks once per block.
+    Address,
+}
+
+/// Marks the parts of an address-sharded block as complete. The producer publishes it to every
+/// shard topic that got a part, after all parts are published, and consumers only store parts
+/// once their marker arrives. A fan-out cut short by a crash is never seen half-stored: its
+/// parts are dropped, or stored once the producer publishes the block again. This covers the
+/// shard topics of one schema only; the topics of different schemas are published by separate
+/// producers and committed independently.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FanoutCommit {
+    /// Hash of the block whose parts are complete.
+    pub fanout_commit: H256,
+}
+
+/// Spreads a schema's output over `count` topics `{topic}-shard-{n}`, each read by its own
+/// consumer, so busy chains can be ingested by several consumers side by side.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+pub struct ShardConfig {
+    pub count: usize,
+    #[serde(default)]
+    pub key: ShardKey,
+    /// Uses the partitions `{topic}-partition-{n}` of a Pulsar partitioned topic `{topic}` as the
+    /// shards, so other readers can subscribe to the whole topic by its name.
+    #[serde(default)]
+    pub partitioned: bool,
+}
+
+impl ShardConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.count < 2 {
+            return Err(anyhow!("Shard count must be at least 2, not {}", self.count));
+        }
+        Ok(())
+    }
+
+    /// The shard topics of `topic`, in shard order.
+    pub fn topics(&self, topic: &str) -> Vec<String> {
+        let suffix = if self.partitioned { "partition" } else { "shard" };
+        (0..self.count).map(|shard| format!("{}-{}-{}", topic, suffix, shard)).collect()
+    }
+
+    /// Shard of a block-number-keyed message.
+    pub fn block_shard(&self, block_number: u64) -> usize {
+        (block_number % self.count as u64) as usize
+    }
+
+    fn address_shard(&self, address: &Address) -> usize {
+        // Addresses are hashes, so their low bytes are spread evenly already.
+        let low = u64::from_be_bytes(address.as_bytes()[12..].try_into().expect("8 bytes"));
+        (low % self.count as u64) as usize
+    }
+
+    /// The messages `block` is published as, with the shard each goes to.
+    pub fn split(&self, block: &Block<Transaction>) -> Vec<(usize, Block<Transaction>)> {
+        match self.key {
+            ShardKey::BlockNumber => vec![(self.block_shard(block.number.unwrap_or_default().as_u64()), block.clone())],
+            ShardKey::Address => {
+                let header = Block { transactions: Vec::new(), ..block.clone() };
+                let mut transactions: Vec<Vec<Transaction>> = vec![Vec::new(); self.count];
+                for transaction in &block.transactions {
+                    transactions[self.address_shard(&transaction.from)].push(transaction.clone());
+                }
+                let mut parts = vec![(0, block.clone())];
+                parts.extend(
+                    transactions
+                        .into_iter()
+                        .enumerate()
+                        .skip(1)
+                        .filter(|(_, transactions)| !transactions.is_empty())
+                        .map(|(shard, transactions)| (shard, Block { transactions, ..header.clone() })),
+                );
+                parts
+            }
+        }
+    }
+
+    /// What the consumer of shard 0 stores of a whole block: the header, stamped with the full
+    /// transaction count, and the transactions of shard 0's senders.
+    pub fn owned_part(&self, block: &Block<Transaction>) -> Block<Transaction> {
+        let mut part = Block {
+            transactions: block
+                .transactions
+                .iter()
+                .filter(|transaction| self.address_shard(&transaction.from) == 0)
+                .cloned()
+                .collect(),
+            ..block.clone()
+        };
+        part.other.insert(TX_COUNT_FIELD.to_string(), serde_json::json!(block.transactions.len()));
+        part
+    }
+
+    /// Whether the consumer of `shard` stores only transactions, leaving the block row to shard 0.
+    pub fn transactions_only(&self, shard: usize) -> bool {
+        self.key == ShardKey::Address && shard > 0
+    }
+}
+
+/// Topics consumers read a schema's `topic` from, each with whether its consumer stores
+/// transactions only. The consumers of those run no hooks, as shard 0's consumer sees every block
+/// whole.
+pub fn consumer_topics(topic: &str, sharding: Option<&ShardConfig>) -> Vec<(String, bool)> {
+    match sharding {
+        Some(sharding) => sharding
+            .topics(topic)
+            .into_iter()
+            .enumerate()
+            .map(|(shard, topic)| (topic, sharding.transactions_only(shard)))
+            .collect(),
+        None => vec![(topic.to_string(), false)],
+    }
+}
@@ -0,0 +1,95 @@
+//! Planning and running backfill chunks against a throwaway Postgres. Needs Docker.
+
+mod common;
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blockchain_data_ingestion::alerting::{Alerter, AlertingConfig};
+use blockchain_data_ingestion::streams::producers::backfill_jobs::{BackfillConfig, BackfillJobRunner};
+use blockchain_data_ingestion::streams::producers::producer::StreamProducer;
+use sqlx::PgPool;
+use std::sync::{Arc, Mutex};
+
+const CHAIN: &str = "MOCK";
+const SCHEMA: &str = "blocks";
+
+/// Records the ranges it is asked to produce.
+#[derive(Default)]
+struct RecordingProducer {
+    produced: Mutex<Vec<(u64, u64)>>,
+}
+
+#[async_trait]
+impl StreamProducer for RecordingProducer {
+    async fn produce_realtime(&self) -> Result<()> {
+        Ok(())
+    }
+
+    async fn produce_historical(&self, start_block: u64, end_block: u64) -> Result<()> {
+        self.produced.lock().unwrap().push((start_block, end_block));
+        Ok(())
+    }
+}
+
+fn runner(pool: &PgPool, producer: &Arc<RecordingProducer>) -> BackfillJobRunner {
+    let config = BackfillConfig { chunk_size: 100, ..Default::default() };
+    BackfillJobRunner::new(
+        Arc::new(pool.clone()),
+        Arc::clone(producer) as Arc<dyn StreamProducer + Send + Sync>,
+        CHAIN,
+        SCHEMA,
+        Alerter::new(&AlertingConfig::default(), &[]),
+        &config,
+    )
+}
+
+async fn chunks(pool: &PgPool) -> Result<Vec<(i64, i64, String)>> {
+    Ok(sqlx::query_as(
+        "SELECT start_block, end_block, status FROM backfill_chunks
+        WHERE chain_name = $1 AND schema_name = $2 ORDER BY start_block",
+    )
+    .bind(CHAIN)
+    .bind(SCHEMA)
+    .fetch_all(pool)
+    .await?)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn a_later_run_extends_the_trailing_partial_chunk() -> Result<()> {
+    let db = common::start_postgres().await?;
+    let producer = Arc::new(RecordingProducer::default());
+
+    let first = runner(&db.pool, &producer);
+    first.plan(50, 249).await?;
+    first.run().await?;
+    assert_eq!(*producer.produced.lock().unwrap(), vec![(50, 99), (100, 199), (200, 249)]);
+
+    // The head moved on: the partial chunk grows, and only its new blocks are produced.
+    producer.produced.lock().unwrap().clear();
+    let second = runner(&db.pool, &producer);
+    second.plan(50, 320).await?;
+    second.run().await?;
+    assert_eq!(*producer.produced.lock().unwrap(), vec![(250, 299), (300, 320)]);
+
+    let done = |start: i64, end: i64| (start, end, "done".to_string());
+    assert_eq!(chunks(&db.pool).await?, vec![done(50, 99), done(100, 199), done(200, 299), done(300, 320)]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn replanning_a_produced_range_produces_nothing() -> Result<()> {
+    let db = common::start_postgres().await?;
+    let producer = Arc::new(RecordingProducer::default());
+
+    runner(&db.pool, &producer).plan(0, 149).await?;
+    runner(&db.pool, &producer).run().await?;
+    producer.produced.lock().unwrap().clear();
+
+    runner(&db.pool, &producer).plan(0, 149).await?;
+    runner(&db.pool, &producer).run().await?;
+    assert!(producer.produced.lock().unwrap().is_empty());
+    assert_eq!(chunks(&db.pool).await?.len(), 2);
+    Ok(())
+}
@@ -0,0 +1,145 @@
+//! Per-block flows written by `BlockFlowAggregator` to Postgres, from blocks and receipts served
+//! by the mock adapter.
+
+mod common;
+
+use anyhow::Result;
+use blockchain_data_ingestion::aggregation::block_flows::{BlockFlowAggregator, BlockFlowsConfig};
+use blockchain_data_ingestion::blockchain::mock_adapter::MockAdapter;
+use blockchain_data_ingestion::enrichment::transfers::transfer_topic;
+use blockchain_data_ingestion::streams::consumers::hooks::ConsumerHook;
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, H256, U256, U64};
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+const CHAIN: &str = "MOCK";
+const BLOCK: u64 = 7;
+
+fn address(n: u64) -> Address {
+    Address::from_low_u64_be(n)
+}
+
+fn transaction(n: u64, from: Address, to: Address, value: u64) -> Transaction {
+    Transaction {
+        hash: H256::from_low_u64_be(n),
+        from,
+        to: Some(to),
+        value: U256::from(value),
+        block_number: Some(U64::from(BLOCK)),
+        ..Default::default()
+    }
+}
+
+fn erc20_transfer(tx: u64, log_index: u64, token: Address, from: Address, to: Address, value: u64) -> Log {
+    let mut data = [0u8; 32];
+    U256::from(value).to_big_endian(&mut data);
+    Log {
+        address: token,
+        topics: vec![transfer_topic(), H256::from(from), H256::from(to)],
+        data: Bytes::from(data.to_vec()),
+        block_number: Some(U64::from(BLOCK)),
+        transaction_hash: Some(H256::from_low_u64_be(tx)),
+        log_index: Some(U256::from(log_index)),
+        ..Default::default()
+    }
+}
+
+fn receipt(tx: u64, succeeded: bool, logs: Vec<Log>) -> TransactionReceipt {
+    TransactionReceipt {
+        transaction_hash: H256::from_low_u64_be(tx),
+        block_number: Some(U64::from(BLOCK)),
+        status: Some(U64::from(succeeded as u64)),
+        logs,
+        ..Default::default()
+    }
+}
+
+/// (address, token, inflow, outflow, net, transfer_count) rows of the block.
+async fn flows(pool: &PgPool) -> Result<Vec<(String, String, String, String, String, i32)>> {
+    let rows = sqlx::query(
+        "SELECT address, token, inflow::text AS inflow, outflow::text AS outflow, net::text AS net, transfer_count
+        FROM block_flows WHERE chain_name = $1 AND block_number = $2 ORDER BY token, address",
+    )
+    .bind(CHAIN)
+    .bind(BLOCK as i64)
+    .fetch_all(pool)
+    .await?;
+    rows.iter()
+        .map(|row| {
+            Ok((
+                row.try_get("address")?,
+                row.try_get("token")?,
+                row.try_get("inflow")?,
+                row.try_get("outflow")?,
+                row.try_get("net")?,
+                row.try_get("transfer_count")?,
+            ))
+        })
+        .collect()
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn reverted_transactions_move_no_value() -> Result<()> {
+    let database = common::start_postgres().await?;
+    let (alice, bob, token) = (address(1), address(2), address(100));
+    let block = Block {
+        number: Some(U64::from(BLOCK)),
+        hash: Some(H256::from_low_u64_be(BLOCK)),
+        transactions: vec![transaction(1, alice, bob, 50), transaction(2, alice, bob, 1000), transaction(3, bob, token, 0)],
+        ..Default::default()
+    };
+    // The second transfer reverted; the token transfer in the third went through.
+    let adapter = MockAdapter::new(vec![block.clone()], None).with_receipts(
+        BLOCK,
+        vec![
+            receipt(1, true, Vec::new()),
+            receipt(2, false, Vec::new()),
+            receipt(3, true, vec![erc20_transfer(3, 0, token, bob, alice, 30)]),
+        ],
+    );
+    let aggregator = BlockFlowAggregator::new(Arc::new(adapter), Arc::new(database.pool.clone()), &BlockFlowsConfig {
+        enabled: true,
+        ..Default::default()
+    });
+
+    aggregator.on_block_committed(CHAIN, &block).await?;
+
+    let token = format!("{:?}", token);
+    let (alice, bob) = (format!("{:?}", alice), format!("{:?}", bob));
+    let mut expected = vec![
+        (alice.clone(), token.clone(), "30".to_string(), "0".to_string(), "30".to_string(), 1),
+        (bob.clone(), token.clone(), "0".to_string(), "30".to_string(), "-30".to_string(), 1),
+        (alice, "native".to_string(), "0".to_string(), "50".to_string(), "-50".to_string(), 1),
+        (bob, "native".to_string(), "50".to_string(), "0".to_string(), "50".to_string(), 1),
+    ];
+    expected.sort_by(|a, b| (&a.1, &a.0).cmp(&(&b.1, &b.0)));
+    assert_eq!(flows(&database.pool).await?, expected);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn recommitting_a_block_replaces_its_flows() -> Result<()> {
+    let database = common::start_postgres().await?;
+    let (alice, bob) = (address(1), address(2));
+    let block = Block {
+        number: Some(U64::from(BLOCK)),
+        hash: Some(H256::from_low_u64_be(BLOCK)),
+        transactions: vec![transaction(1, alice, bob, 50)],
+        ..Default::default()
+    };
+    let adapter = MockAdapter::new(vec![block.clone()], None).with_receipts(BLOCK, vec![receipt(1, true, Vec::new())]);
+    let aggregator = BlockFlowAggregator::new(Arc::new(adapter), Arc::new(database.pool.clone()), &BlockFlowsConfig {
+        enabled: true,
+        ..Default::default()
+    });
+
+    aggregator.on_block_committed(CHAIN, &block).await?;
+    aggregator.on_block_committed(CHAIN, &block).await?;
+
+    let rows = flows(&database.pool).await?;
+    assert_eq!(rows.len(), 2);
+    assert!(rows.iter().all(|row| row.5 == 1));
+    Ok(())
+}
@@ -0,0 +1,94 @@
+//! Poll scheduling of `BlockTimeEstimator`, with block intervals simulated by short sleeps.
+
+use blockchain_data_ingestion::blockchain::block_time::{BlockTimeEstimator, PollBounds};
+use std::time::Duration;
+
+fn bounds() -> PollBounds {
+    PollBounds { min_interval: Duration::from_millis(1), max_interval: Duration::from_secs(30) }
+}
+
+#[test]
+fn polls_quickly_until_the_head_has_advanced() {
+    let mut estimator = BlockTimeEstimator::new(bounds());
+    assert_eq!(estimator.next_interval(), Duration::from_millis(500));
+    estimator.observe(100);
+    assert_eq!(estimator.block_time(), None);
+    assert_eq!(estimator.next_interval(), Duration::from_millis(500));
+}
+
+#[test]
+fn learns_the_block_time_from_head_advances() {
+    let mut estimator = BlockTimeEstimator::new(bounds());
+    estimator.observe(100);
+    std::thread::sleep(Duration::from_millis(100));
+    estimator.observe(102);
+
+    // Two blocks in about 100 ms.
+    let block_time = estimator.block_time().expect("block time after the head advanced");
+    assert!(block_time >= Duration::from_millis(50) && block_time < Duration::from_millis(90), "{:?}", block_time);
+    assert_eq!(estimator.next_interval(), block_time);
+}
+
+#[test]
+fn polls_at_a_quarter_block_time_after_a_miss() {
+    let mut estimator = BlockTimeEstimator::new(bounds());
+    estimator.observe(100);
+    std::thread::sleep(Duration::from_millis(80));
+    estimator.observe(101);
+    let block_time = estimator.block_time().unwrap();
+
+    estimator.observe(101);
+    assert_eq!(estimator.next_interval(), Duration::from_secs_f64(block_time.as_secs_f64() / 4.0));
+
+    // The next block resets the wait to a whole block time.
+    std::thread::sleep(Duration::from_millis(80));
+    estimator.observe(102);
+    assert_eq!(estimator.next_interval(), estimator.block_time().unwrap());
+}
+
+#[test]
+fn smooths_the_block_time_towards_new_intervals() {
+    let mut estimator = BlockTimeEstimator::new(bounds());
+    estimator.observe(100);
+    std::thread::sleep(Duration::from_millis(40));
+    estimator.observe(101);
+    let first = estimator.block_time().unwrap();
+    std::thread::sleep(Duration::from_millis(200));
+    estimator.observe(102);
+    let second = estimator.block_time().unwrap();
+
+    // One slow block moves the estimate a fifth of the way, not all the way.
+    assert!(second > first);
+    assert!(second < Duration::from_millis(120), "{:?}", second);
+}
+
+#[test]
+fn clamps_the_interval_to_the_bounds() {
+    let bounds = PollBounds { min_interval: Duration::from_millis(150), max_interval: Duration::from_millis(200) };
+    let mut estimator = BlockTimeEstimator::new(bounds);
+    estimator.observe(100);
+    std::thread::sleep(Duration::from_millis(20));
+    estimator.observe(101);
+    assert_eq!(estimator.next_interval(), Duration::from_millis(150));
+
+    let mut estimator = BlockTimeEstimator::new(bounds);
+    estimator.observe(100);
+    std::thread::sleep(Duration::from_millis(300));
+    estimator.observe(101);
+    assert_eq!(estimator.next_interval(), Duration::from_millis(200));
+    // Before anything is learned the quick poll is clamped too.
+    assert_eq!(BlockTimeEstimator::new(bounds).next_interval(), Duration::from_millis(200));
+}
+
+#[test]
+fn reads_poll_bounds_from_adapter_options() {
+    let options: toml::Table = toml::from_str("min_poll_ms = 250\nmax_poll_ms = 100").unwrap();
+    let bounds = PollBounds::from_options(&options);
+    assert_eq!(bounds.min_interval, Duration::from_millis(250));
+    // A maximum below the minimum is raised to it.
+    assert_eq!(bounds.max_interval, Duration::from_millis(250));
+
+    let defaults = PollBounds::from_options(&toml::Table::new());
+    assert_eq!(defaults.min_interval, Duration::from_millis(100));
+    assert_eq!(defaults.max_interval, Duration::from_secs(30));
+}
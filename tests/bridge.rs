@@ -0,0 +1,38 @@
+//! `bridge` copying topics between two in-memory brokers.
+
+use anyhow::Result;
+use blockchain_data_ingestion::streams::message_queue::bridge::bridge;
+use blockchain_data_ingestion::streams::message_queue::memory::InMemoryQueue;
+use blockchain_data_ingestion::streams::message_queue::queue::MessageQueue;
+use std::sync::Arc;
+use std::time::Duration;
+
+const TOPIC: &str = "mock-blocks";
+
+#[tokio::test]
+async fn block_messages_keep_their_key_on_the_target() -> Result<()> {
+    let source = Arc::new(InMemoryQueue::new());
+    let target = Arc::new(InMemoryQueue::new());
+    let publisher = source.publisher(TOPIC).await?;
+    publisher.publish_block(7, b"block 7".to_vec()).await?;
+    publisher.publish(b"unkeyed".to_vec()).await?;
+    publisher.publish_block(8, b"block 8".to_vec()).await?;
+
+    let copy = {
+        let (source, target) = (Arc::clone(&source) as Arc<dyn MessageQueue>, Arc::clone(&target) as Arc<dyn MessageQueue>);
+        tokio::spawn(async move { bridge(source, target, &[TOPIC.to_string()], "bridge").await })
+    };
+    let mut subscriber = target.subscriber(TOPIC, "reader").await?;
+    let mut copied = Vec::new();
+    for _ in 0..3 {
+        let message = tokio::time::timeout(Duration::from_secs(5), subscriber.next()).await?.unwrap()?;
+        copied.push((message.payload, message.block_key));
+    }
+    copy.abort();
+
+    assert_eq!(
+        copied,
+        vec![(b"block 7".to_vec(), Some(7)), (b"unkeyed".to_vec(), None), (b"block 8".to_vec(), Some(8))]
+    );
+    Ok(())
+}
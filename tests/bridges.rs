@@ -0,0 +1,309 @@
+//! `BridgeDecoder` rows for both sides of OP Stack, Arbitrum and custom bridge transfers, and that
+//! they join on `correlation_key`. Recorded in a throwaway Postgres, so most tests need Docker.
+
+mod common;
+
+use anyhow::Result;
+use blockchain_data_ingestion::blockchain::mock_adapter::MockAdapter;
+use blockchain_data_ingestion::enrichment::bridges::{BridgeConfig, BridgeDecoder};
+use blockchain_data_ingestion::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+use ethers::abi::{encode, parse_abi, Token};
+use ethers::types::{Address, Block, Bytes, Log, Transaction, TransactionReceipt, H256, U256, U64};
+use ethers::utils::keccak256;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+const L1: &str = "L1";
+const L2: &str = "L2";
+const L1_CHAIN_ID: u64 = 1;
+const L2_CHAIN_ID: u64 = 10;
+const BLOCK: u64 = 100;
+
+fn address(n: u64) -> Address {
+    Address::from_low_u64_be(n)
+}
+
+fn topic(token: &Token) -> H256 {
+    match token {
+        Token::Address(address) => H256::from(*address),
+        Token::Uint(value) => {
+            let mut bytes = [0u8; 32];
+            value.to_big_endian(&mut bytes);
+            H256::from(bytes)
+        }
+        Token::FixedBytes(bytes) => H256::from_slice(bytes),
+        other => panic!("no topic encoding for {:?}", other),
+    }
+}
+
+/// A log of `signature` emitted by `emitter`, with `params` in declaration order.
+fn event_log(emitter: Address, signature: &str, tx: u64, log_index: u64, params: Vec<Token>) -> Log {
+    let abi = parse_abi(&[format!("event {}", signature).as_str()]).unwrap();
+    let event = abi.events().next().unwrap();
+    let mut topics = vec![event.signature()];
+    let mut data = Vec::new();
+    for (input, param) in event.inputs.iter().zip(params) {
+        if input.indexed {
+            topics.push(topic(&param));
+        } else {
+            data.push(param);
+        }
+    }
+    Log {
+        address: emitter,
+        topics,
+        data: Bytes::from(encode(&data)),
+        transaction_hash: Some(H256::from_low_u64_be(tx)),
+        log_index: Some(U256::from(log_index)),
+        block_number: Some(U64::from(BLOCK)),
+        ..Default::default()
+    }
+}
+
+fn block(hash: u64) -> Block<Transaction> {
+    Block { number: Some(U64::from(BLOCK)), hash: Some(H256::from_low_u64_be(hash)), ..Default::default() }
+}
+
+fn decoder(pool: &PgPool, chain_id: u64, config: &str, logs: Vec<Log>) -> Result<BridgeDecoder> {
+    let config: BridgeConfig = toml::from_str(config)?;
+    let receipt = TransactionReceipt { logs, ..Default::default() };
+    let adapter = MockAdapter::new(vec![block(BLOCK)], None).with_receipts(BLOCK, vec![receipt]);
+    BridgeDecoder::new(Arc::new(adapter), Arc::new(pool.clone()), chain_id, &config)
+}
+
+#[derive(Debug, PartialEq)]
+struct Transfer {
+    direction: String,
+    source_chain_id: Option<i64>,
+    destination_chain_id: Option<i64>,
+    token: Option<String>,
+    remote_token: Option<String>,
+    from: Option<String>,
+    to: Option<String>,
+    amount: Option<String>,
+    correlation_key: Option<String>,
+}
+
+async fn transfers(pool: &PgPool, chain_name: &str) -> Result<Vec<Transfer>> {
+    let rows = sqlx::query(
+        "SELECT direction, source_chain_id, destination_chain_id, token, remote_token, from_address, to_address, amount::text AS amount, correlation_key
+        FROM bridge_transfers WHERE chain_name = $1 ORDER BY tx_hash, log_index",
+    )
+    .bind(chain_name)
+    .fetch_all(pool)
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| Transfer {
+            direction: row.get("direction"),
+            source_chain_id: row.get("source_chain_id"),
+            destination_chain_id: row.get("destination_chain_id"),
+            token: row.get("token"),
+            remote_token: row.get("remote_token"),
+            from: row.get("from_address"),
+            to: row.get("to_address"),
+            amount: row.get("amount"),
+            correlation_key: row.get("correlation_key"),
+        })
+        .collect())
+}
+
+fn hex(address: Address) -> Option<String> {
+    Some(format!("{:?}", address))
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn op_deposits_join_on_the_messenger_message_hash() -> Result<()> {
+    let db = common::start_postgres().await?;
+    let (l1_bridge, l1_messenger, l2_bridge, l2_messenger) = (address(0xb1), address(0xc1), address(0xb2), address(0xc2));
+    let (alice, bob) = (address(0xa1), address(0xa2));
+    let amount = U256::from(5_000u64);
+    let (nonce, gas_limit) = (U256::from(42u64), U256::from(200_000u64));
+    let message = vec![0x1a, 0x2b];
+
+    let l1_logs = vec![
+        event_log(l1_bridge, "ETHBridgeInitiated(address indexed from, address indexed to, uint256 amount, bytes extraData)", 1, 0, vec![
+            Token::Address(alice),
+            Token::Address(bob),
+            Token::Uint(amount),
+            Token::Bytes(Vec::new()),
+        ]),
+        event_log(l1_messenger, "SentMessage(address indexed target, address sender, bytes message, uint256 messageNonce, uint256 gasLimit)", 1, 1, vec![
+            Token::Address(l2_bridge),
+            Token::Address(l1_bridge),
+            Token::Bytes(message.clone()),
+            Token::Uint(nonce),
+            Token::Uint(gas_limit),
+        ]),
+        event_log(l1_messenger, "SentMessageExtension1(address indexed sender, uint256 value)", 1, 2, vec![
+            Token::Address(l1_bridge),
+            Token::Uint(amount),
+        ]),
+    ];
+    // The hash OP's CrossDomainMessenger relays the message under on L2.
+    let mut relay = keccak256("relayMessage(uint256,address,address,uint256,uint256,bytes)")[..4].to_vec();
+    relay.extend(encode(&[
+        Token::Uint(nonce),
+        Token::Address(l1_bridge),
+        Token::Address(l2_bridge),
+        Token::Uint(amount),
+        Token::Uint(gas_limit),
+        Token::Bytes(message),
+    ]));
+    let message_hash = H256::from(keccak256(relay));
+    let l2_logs = vec![
+        event_log(l2_bridge, "ETHBridgeFinalized(address indexed from, address indexed to, uint256 amount, bytes extraData)", 2, 0, vec![
+            Token::Address(alice),
+            Token::Address(bob),
+            Token::Uint(amount),
+            Token::Bytes(Vec::new()),
+        ]),
+        event_log(l2_messenger, "RelayedMessage(bytes32 indexed msgHash)", 2, 1, vec![Token::FixedBytes(message_hash.as_bytes().to_vec())]),
+    ];
+
+    let contract = |bridge: Address, remote_chain_id: u64| {
+        format!("[[contracts]]\nkind = \"op_standard_bridge\"\naddress = \"{:?}\"\nremote_chain_id = {}\n", bridge, remote_chain_id)
+    };
+    decoder(&db.pool, L1_CHAIN_ID, &contract(l1_bridge, L2_CHAIN_ID), l1_logs)?.on_block_committed(L1, &block(BLOCK)).await?;
+    decoder(&db.pool, L2_CHAIN_ID, &contract(l2_bridge, L1_CHAIN_ID), l2_logs)?.on_block_committed(L2, &block(BLOCK)).await?;
+
+    let key = Some(format!("op:{:?}", message_hash));
+    let expected = |direction: &str| Transfer {
+        direction: direction.to_string(),
+        source_chain_id: Some(L1_CHAIN_ID as i64),
+        destination_chain_id: Some(L2_CHAIN_ID as i64),
+        token: None,
+        remote_token: None,
+        from: hex(alice),
+        to: hex(bob),
+        amount: Some(amount.to_string()),
+        correlation_key: key.clone(),
+    };
+    assert_eq!(transfers(&db.pool, L1).await?, vec![expected("outbound")]);
+    assert_eq!(transfers(&db.pool, L2).await?, vec![expected("inbound")]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn arbitrum_deposits_join_and_name_the_l1_token_on_both_sides() -> Result<()> {
+    let db = common::start_postgres().await?;
+    let (l1_gateway, l2_gateway, l1_token) = (address(0xd1), address(0xd2), address(0xe1));
+    let (alice, bob) = (address(0xa1), address(0xa2));
+    let amount = U256::from(7u64);
+
+    let initiated = event_log(
+        l1_gateway,
+        "DepositInitiated(address l1Token, address indexed _from, address indexed _to, uint256 indexed _sequenceNumber, uint256 _amount)",
+        1,
+        0,
+        vec![Token::Address(l1_token), Token::Address(alice), Token::Address(bob), Token::Uint(U256::from(9u64)), Token::Uint(amount)],
+    );
+    let finalized = event_log(
+        l2_gateway,
+        "DepositFinalized(address indexed l1Token, address indexed _from, address indexed _to, uint256 _amount)",
+        2,
+        0,
+        vec![Token::Address(l1_token), Token::Address(alice), Token::Address(bob), Token::Uint(amount)],
+    );
+    let contract = |gateway: Address, remote_chain_id: u64| {
+        format!("[[contracts]]\nkind = \"arbitrum_gateway\"\naddress = \"{:?}\"\nremote_chain_id = {}\n", gateway, remote_chain_id)
+    };
+    decoder(&db.pool, L1_CHAIN_ID, &contract(l1_gateway, L2_CHAIN_ID), vec![initiated])?.on_block_committed(L1, &block(BLOCK)).await?;
+    decoder(&db.pool, L2_CHAIN_ID, &contract(l2_gateway, L1_CHAIN_ID), vec![finalized])?.on_block_committed(L2, &block(BLOCK)).await?;
+
+    let (l1, l2) = (transfers(&db.pool, L1).await?, transfers(&db.pool, L2).await?);
+    assert_eq!((l1.len(), l2.len()), (1, 1));
+    assert_eq!((l1[0].token.clone(), l1[0].remote_token.clone()), (hex(l1_token), None));
+    assert_eq!((l2[0].token.clone(), l2[0].remote_token.clone()), (None, hex(l1_token)));
+    assert_eq!(l1[0].correlation_key, l2[0].correlation_key);
+    assert!(l1[0].correlation_key.as_deref().unwrap().starts_with("arbitrum:deposit:"));
+    assert_eq!((l1[0].source_chain_id, l1[0].destination_chain_id), (Some(1), Some(10)));
+    assert_eq!((l2[0].source_chain_id, l2[0].destination_chain_id), (Some(1), Some(10)));
+    Ok(())
+}
+
+const CUSTOM: &str = r#"
+[[contracts]]
+kind = "custom"
+name = "my_bridge"
+address = "0x00000000000000000000000000000000000000f1"
+event = "Locked(address indexed token, address indexed from, address to, uint256 amount, uint256 nonce, uint256 dstChainId)"
+direction = "outbound"
+fields = { token = "token", from = "from", to = "to", amount = "amount", key = "nonce", remote_chain_id = "dstChainId" }
+"#;
+
+fn locked(tx: u64, nonce: u64) -> Log {
+    event_log(
+        address(0xf1),
+        "Locked(address indexed token, address indexed from, address to, uint256 amount, uint256 nonce, uint256 dstChainId)",
+        tx,
+        0,
+        vec![
+            Token::Address(address(0xe1)),
+            Token::Address(address(0xa1)),
+            Token::Address(address(0xa2)),
+            Token::Uint(U256::from(3u64)),
+            Token::Uint(U256::from(nonce)),
+            Token::Uint(U256::from(L2_CHAIN_ID)),
+        ],
+    )
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn custom_bridges_read_their_fields_and_skip_other_contracts() -> Result<()> {
+    let db = common::start_postgres().await?;
+    let mut elsewhere = locked(2, 8);
+    elsewhere.address = address(0xf2);
+    let hook = decoder(&db.pool, L1_CHAIN_ID, CUSTOM, vec![locked(1, 7), elsewhere])?;
+    hook.on_block_committed(L1, &block(BLOCK)).await?;
+    // Redelivered blocks don't add rows.
+    hook.on_block_committed(L1, &block(BLOCK)).await?;
+
+    assert_eq!(
+        transfers(&db.pool, L1).await?,
+        vec![Transfer {
+            direction: "outbound".to_string(),
+            source_chain_id: Some(L1_CHAIN_ID as i64),
+            destination_chain_id: Some(L2_CHAIN_ID as i64),
+            token: hex(address(0xe1)),
+            remote_token: None,
+            from: hex(address(0xa1)),
+            to: hex(address(0xa2)),
+            amount: Some("3".to_string()),
+            correlation_key: Some("my_bridge:7".to_string()),
+        }]
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn orphaned_blocks_lose_their_transfers() -> Result<()> {
+    let db = common::start_postgres().await?;
+    let hook = decoder(&db.pool, L1_CHAIN_ID, CUSTOM, vec![locked(1, 7)])?;
+    hook.on_block_committed(L1, &block(BLOCK)).await?;
+    hook.on_blocks_orphaned(
+        L1,
+        &[OrphanedBlock {
+            block_number: BLOCK as i64,
+            hash: format!("{:?}", H256::from_low_u64_be(BLOCK)),
+            replaced_by: format!("{:?}", H256::from_low_u64_be(BLOCK + 1)),
+        }],
+    )
+    .await?;
+    assert!(transfers(&db.pool, L1).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn custom_fields_must_name_event_parameters() -> Result<()> {
+    let pool = PgPoolOptions::new().connect_lazy("postgres://localhost/unused")?;
+    let config = CUSTOM.replace("key = \"nonce\"", "key = \"depositId\"");
+    let error = decoder(&pool, L1_CHAIN_ID, &config, Vec::new()).err().expect("unknown parameter is rejected");
+    assert!(error.to_string().contains("depositId"), "{}", error);
+    Ok(())
+}
@@ -0,0 +1,50 @@
+use anyhow::{anyhow, Result};
+use blockchain_data_ingestion::storage::db::run_migrations;
+use sqlx::postgres::PgPoolOptions;
+use sqlx::PgPool;
+use std::future::Future;
+use std::time::Duration;
+use testcontainers::runners::AsyncRunner;
+use testcontainers::ContainerAsync;
+use testcontainers_modules::postgres::Postgres;
+
+/// A throwaway Postgres with the repository's migrations applied. The container is removed when
+/// this is dropped.
+pub struct TestDatabase {
+    pub pool: PgPool,
+    _container: ContainerAsync<Postgres>,
+}
+
+pub async fn start_postgres() -> Result<TestDatabase> {
+    let container = Postgres::default().start().await?;
+    let database_url = format!(
+        "postgres://postgres:postgres@{}:{}/postgres",
+        container.get_host().await?,
+        container.get_host_port_ipv4(5432).await?
+    );
+    let pool = PgPoolOptions::new()
+        .max_connections(10)
+        .connect(&database_url)
+        .await?;
+    run_migrations(&pool).await?;
+
+    Ok(TestDatabase { pool, _container: container })
+}
+
+/// Polls `condition` until it returns true, failing after `timeout`.
+pub async fn wait_for<F, Fut>(timeout: Duration, mut condition: F) -> Result<()>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<bool>>,
+{
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        if condition().await? {
+            return Ok(());
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return Err(anyhow!("Condition not met within {:?}", timeout));
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+}
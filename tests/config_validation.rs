@@ -0,0 +1,32 @@
+//! `load_config` rejecting settings that would silently do nothing.
+
+use blockchain_data_ingestion::load_config;
+
+fn chain_config(extra: &str) -> String {
+    std::env::set_var("CONFIG_TEST_HTTP_URL", "http://127.0.0.1:8545");
+    format!(
+        r#"
+        [blockchains.ETH]
+        adapter_type = "EVM"
+        schemas = ["blocks", "traces"]
+        http_url = "CONFIG_TEST_HTTP_URL"
+        {extra}
+        "#
+    )
+}
+
+#[test]
+fn delivery_of_a_schema_is_accepted() {
+    assert!(load_config(&chain_config(r#"delivery = { traces = "at_most_once" }"#)).is_ok());
+}
+
+#[test]
+fn delivery_of_the_mempool_is_rejected() {
+    let error = load_config(&chain_config(r#"delivery = { mempool = "at_most_once" }"#)).unwrap_err();
+    assert!(error.to_string().contains("mempool"), "{}", error);
+}
+
+#[test]
+fn delivery_of_a_misspelled_schema_is_rejected() {
+    assert!(load_config(&chain_config(r#"delivery = { block = "at_most_once" }"#)).is_err());
+}
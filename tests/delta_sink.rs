@@ -0,0 +1,106 @@
+//! `DeltaSink` commits, redeliveries and logs, written to a scratch directory.
+
+use anyhow::Result;
+use blockchain_data_ingestion::blockchain::mock_adapter::MockAdapter;
+use blockchain_data_ingestion::storage::sinks::delta::{DeltaSink, DeltaSinkConfig};
+use blockchain_data_ingestion::storage::sinks::Sink;
+use deltalake::datafusion::prelude::SessionContext;
+use ethers::types::{Address, Block, Log, Transaction, TransactionReceipt, H256, U256, U64};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const CHAIN: &str = "MOCK";
+
+fn block(number: u64) -> Block<Transaction> {
+    Block { number: Some(U64::from(number)), hash: Some(H256::from_low_u64_be(number)), ..Default::default() }
+}
+
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("delta-sink-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    dir
+}
+
+fn sink(dir: &PathBuf, blocks_per_commit: usize) -> DeltaSink {
+    DeltaSink::new(&DeltaSinkConfig {
+        uri: dir.to_string_lossy().into_owned(),
+        blocks_per_commit,
+        zorder_every: None,
+        logs: false,
+    })
+}
+
+async fn rows(dir: &PathBuf, table: &str) -> Result<usize> {
+    let table = deltalake::open_table(dir.join(CHAIN).join(table).to_string_lossy()).await?;
+    let batches = SessionContext::new().read_table(Arc::new(table))?.collect().await?;
+    Ok(batches.iter().map(|batch| batch.num_rows()).sum())
+}
+
+#[tokio::test]
+async fn resumes_from_the_highest_block_with_bounded_commit_tags() -> Result<()> {
+    let dir = scratch_dir("resume");
+    let sink = sink(&dir, 2);
+    assert_eq!(sink.last_block(CHAIN).await?, None);
+
+    for number in [100, 101, 102, 103] {
+        sink.write_block(CHAIN, &block(number)).await?;
+    }
+    // A backfilled range committed after the head doesn't move the resume point back.
+    for number in [1, 2] {
+        sink.write_block(CHAIN, &block(number)).await?;
+    }
+    assert_eq!(sink.last_block(CHAIN).await?, Some(103));
+    assert_eq!(rows(&dir, "blocks").await?, 6);
+
+    let table = deltalake::open_table(dir.join(CHAIN).join("blocks").to_string_lossy()).await?;
+    let tags = table.get_app_transaction_version();
+    assert_eq!(tags.len(), 2, "{:?}", tags.keys());
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_redelivered_batch_is_not_appended_again() -> Result<()> {
+    let dir = scratch_dir("redelivery");
+    let sink = sink(&dir, 2);
+    let mut transaction = Transaction { block_number: Some(U64::from(11)), ..Default::default() };
+    transaction.hash = H256::from_low_u64_be(1);
+    let with_transaction = Block { transactions: vec![transaction], ..block(11) };
+
+    sink.write_block(CHAIN, &block(10)).await?;
+    assert!(sink.write_block(CHAIN, &with_transaction).await?.durable);
+    // Committed, but the acknowledgements were lost, so the same blocks come back.
+    sink.write_block(CHAIN, &block(10)).await?;
+    assert!(sink.write_block(CHAIN, &with_transaction).await?.durable);
+
+    assert_eq!(rows(&dir, "blocks").await?, 2);
+    assert_eq!(rows(&dir, "transactions").await?, 1);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn writes_the_logs_of_each_block() -> Result<()> {
+    let dir = scratch_dir("logs");
+    let log = |index: u64| Log {
+        address: Address::from_low_u64_be(100),
+        topics: vec![H256::from_low_u64_be(7)],
+        transaction_hash: Some(H256::from_low_u64_be(1)),
+        log_index: Some(U256::from(index)),
+        ..Default::default()
+    };
+    let receipt = TransactionReceipt { logs: vec![log(0), log(1)], ..Default::default() };
+    let adapter = MockAdapter::new(vec![block(20), block(21)], None).with_receipts(21, vec![receipt]);
+    let sink = sink(&dir, 2).with_logs(Arc::new(adapter));
+
+    sink.write_block(CHAIN, &block(20)).await?;
+    assert!(sink.write_block(CHAIN, &block(21)).await?.durable);
+
+    assert_eq!(rows(&dir, "logs").await?, 2);
+    assert_eq!(rows(&dir, "blocks").await?, 2);
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
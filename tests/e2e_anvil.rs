@@ -0,0 +1,115 @@
+//! End-to-end ingestion against a local Anvil node, the in-memory queue and a throwaway Postgres.
+//!
+//! Needs `anvil` (foundry) on the PATH and a running Docker daemon:
+//! `cargo test --test e2e_anvil -- --ignored`
+
+mod common;
+
+use anyhow::Result;
+use blockchain_data_ingestion::streams::message_queue::memory::InMemoryQueue;
+use blockchain_data_ingestion::{load_config, run_pipeline, Registries};
+use ethers::middleware::SignerMiddleware;
+use ethers::providers::{Http, Middleware, Provider};
+use ethers::signers::{LocalWallet, Signer};
+use ethers::types::{TransactionReceipt, TransactionRequest, U256};
+use ethers::utils::Anvil;
+use sqlx::Row;
+use std::env;
+use std::sync::Arc;
+use std::time::Duration;
+
+const CHAIN: &str = "ANVIL";
+const TRANSFERS: u64 = 5;
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires anvil and Docker"]
+async fn ingests_mined_blocks_into_postgres() -> Result<()> {
+    let anvil = Anvil::new().spawn();
+    let database = common::start_postgres().await?;
+    let pool = Arc::new(database.pool.clone());
+
+    // Anvil automines, so every transfer lands in its own block.
+    let provider = Provider::<Http>::try_from(anvil.endpoint())?;
+    let wallet: LocalWallet = anvil.keys()[0].clone().into();
+    let client = SignerMiddleware::new(provider.clone(), wallet.with_chain_id(anvil.chain_id()));
+    let mut receipts: Vec<TransactionReceipt> = Vec::new();
+    for i in 0..TRANSFERS {
+        let transfer = TransactionRequest::pay(anvil.addresses()[1], U256::from(1_000 + i));
+        let receipt = client
+            .send_transaction(transfer, None)
+            .await?
+            .await?
+            .expect("transfer was not mined");
+        receipts.push(receipt);
+    }
+    let head = provider.get_block_number().await?.as_u64();
+
+    env::set_var("E2E_HTTP_URL", anvil.endpoint());
+    env::set_var("E2E_WS_URL", anvil.ws_endpoint());
+    let config = load_config(&format!(
+        r#"
+        [blockchains.{CHAIN}]
+        adapter_type = "EVM"
+        schemas = ["blocks"]
+        start_block = 1
+        end_block = {head}
+        http_url = "E2E_HTTP_URL"
+        ws_url = "E2E_WS_URL"
+        "#
+    ))?;
+
+    let pipeline = tokio::spawn(run_pipeline(
+        config,
+        Arc::clone(&pool),
+        Arc::new(InMemoryQueue::new()),
+        Registries::default(),
+    ));
+
+    common::wait_for(Duration::from_secs(60), || {
+        let pool = Arc::clone(&pool);
+        async move {
+            let row = sqlx::query("SELECT COUNT(*) AS count FROM transactions WHERE chain_name = $1")
+                .bind(CHAIN)
+                .fetch_one(pool.as_ref())
+                .await?;
+            Ok(row.try_get::<i64, _>("count")? >= TRANSFERS as i64)
+        }
+    })
+    .await?;
+    pipeline.abort();
+
+    for block_number in 1..=head {
+        let expected = provider.get_block(block_number).await?.expect("block exists");
+        let row = sqlx::query(
+            "SELECT hash, parent_hash, tx_count FROM blocks WHERE chain_name = $1 AND block_number = $2 AND canonical",
+        )
+        .bind(CHAIN)
+        .bind(block_number as i64)
+        .fetch_one(pool.as_ref())
+        .await?;
+        assert_eq!(row.try_get::<String, _>("hash")?, format!("{:?}", expected.hash.unwrap()));
+        assert_eq!(row.try_get::<String, _>("parent_hash")?, format!("{:?}", expected.parent_hash));
+        assert_eq!(row.try_get::<i64, _>("tx_count")?, expected.transactions.len() as i64);
+    }
+
+    for receipt in &receipts {
+        let transaction = provider
+            .get_transaction(receipt.transaction_hash)
+            .await?
+            .expect("transaction exists");
+        let row = sqlx::query(
+            "SELECT block_number, from_address, to_address, value, nonce FROM transactions WHERE chain_name = $1 AND tx_hash = $2",
+        )
+        .bind(CHAIN)
+        .bind(format!("{:?}", receipt.transaction_hash))
+        .fetch_one(pool.as_ref())
+        .await?;
+        assert_eq!(row.try_get::<i64, _>("block_number")?, receipt.block_number.unwrap().as_u64() as i64);
+        assert_eq!(row.try_get::<String, _>("from_address")?, format!("{:?}", transaction.from));
+        assert_eq!(row.try_get::<Option<String>, _>("to_address")?, transaction.to.map(|to| format!("{:?}", to)));
+        assert_eq!(row.try_get::<String, _>("value")?, transaction.value.to_string());
+        assert_eq!(row.try_get::<i64, _>("nonce")?, transaction.nonce.as_u64() as i64);
+    }
+
+    Ok(())
+}
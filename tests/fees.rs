@@ -0,0 +1,66 @@
+//! Fee breakdowns of each fee model, from hand-built blocks, transactions and receipts.
+
+use blockchain_data_ingestion::enrichment::fees::{ArbitrumFeeModel, Eip1559FeeModel, FeeBreakdown, FeeModel, OpStackFeeModel};
+use ethers::types::{Block, Transaction, TransactionReceipt, U256};
+
+fn block(base_fee: u64) -> Block<Transaction> {
+    Block { base_fee_per_gas: Some(U256::from(base_fee)), ..Default::default() }
+}
+
+fn receipt(gas_used: u64, effective_gas_price: u64, extra: &[(&str, u64)]) -> TransactionReceipt {
+    let mut receipt = TransactionReceipt {
+        gas_used: Some(U256::from(gas_used)),
+        effective_gas_price: Some(U256::from(effective_gas_price)),
+        ..Default::default()
+    };
+    for (key, value) in extra {
+        receipt.other.insert(key.to_string(), serde_json::json!(format!("{:#x}", value)));
+    }
+    receipt
+}
+
+fn breakdown(base: u64, priority: u64, l1_data: u64, blob: u64) -> FeeBreakdown {
+    FeeBreakdown::new(U256::from(base), U256::from(priority), U256::from(l1_data), U256::from(blob))
+}
+
+#[test]
+fn eip1559_burns_the_base_fee_and_pays_the_rest_as_priority() {
+    let fee = Eip1559FeeModel.fee(&block(10), &Transaction::default(), &receipt(21_000, 12, &[]));
+    assert_eq!(fee, breakdown(210_000, 42_000, 0, 0));
+    assert_eq!(fee.total, U256::from(252_000));
+}
+
+#[test]
+fn eip1559_adds_the_blob_fee_of_blob_transactions() {
+    let receipt = receipt(21_000, 12, &[("blobGasUsed", 131_072), ("blobGasPrice", 3)]);
+    let fee = Eip1559FeeModel.fee(&block(10), &Transaction::default(), &receipt);
+    assert_eq!(fee, breakdown(210_000, 42_000, 0, 393_216));
+    assert_eq!(fee.total, U256::from(210_000 + 42_000 + 393_216));
+}
+
+#[test]
+fn falls_back_to_the_transaction_gas_price_without_an_effective_one() {
+    let transaction = Transaction { gas_price: Some(U256::from(15)), ..Default::default() };
+    let receipt = TransactionReceipt { gas_used: Some(U256::from(100)), ..Default::default() };
+    assert_eq!(Eip1559FeeModel.fee(&block(10), &transaction, &receipt), breakdown(1000, 500, 0, 0));
+}
+
+#[test]
+fn a_price_below_the_base_fee_is_all_burned() {
+    assert_eq!(Eip1559FeeModel.fee(&block(20), &Transaction::default(), &receipt(100, 12, &[])), breakdown(1200, 0, 0, 0));
+    // Pre-London blocks have no base fee at all, and everything goes to the miner.
+    assert_eq!(Eip1559FeeModel.fee(&Block::default(), &Transaction::default(), &receipt(100, 12, &[])), breakdown(0, 1200, 0, 0));
+}
+
+#[test]
+fn op_stack_adds_the_l1_fee() {
+    let fee = OpStackFeeModel.fee(&block(10), &Transaction::default(), &receipt(21_000, 11, &[("l1Fee", 5_000)]));
+    assert_eq!(fee, breakdown(210_000, 21_000, 5_000, 0));
+}
+
+#[test]
+fn arbitrum_prices_the_l1_gas_out_of_gas_used() {
+    let fee = ArbitrumFeeModel.fee(&block(10), &Transaction::default(), &receipt(30_000, 10, &[("gasUsedForL1", 9_000)]));
+    assert_eq!(fee, breakdown(210_000, 0, 90_000, 0));
+    assert_eq!(fee.total, U256::from(300_000));
+}
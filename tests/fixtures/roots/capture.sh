@@ -0,0 +1,22 @@
+#!/usr/bin/env bash
+# Captures mainnet blocks with their receipts as fixtures for tests/roots.rs, one file per block:
+# {"block": eth_getBlockByNumber(n, true), "receipts": eth_getBlockReceipts(n)}.
+#
+#   RPC_URL=https://... tests/fixtures/roots/capture.sh 19426589 ...
+#
+# Together the blocks should hold legacy, EIP-2930, EIP-1559 and EIP-4844 transactions.
+set -euo pipefail
+cd "$(dirname "$0")"
+
+rpc() {
+    curl -sf -X POST -H 'Content-Type: application/json' \
+        --data "{\"jsonrpc\":\"2.0\",\"id\":1,\"method\":\"$1\",\"params\":$2}" "$RPC_URL" | jq '.result'
+}
+
+for number in "$@"; do
+    hex=$(printf '0x%x' "$number")
+    jq -n --argjson block "$(rpc eth_getBlockByNumber "[\"$hex\", true]")" \
+        --argjson receipts "$(rpc eth_getBlockReceipts "[\"$hex\"]")" \
+        '{block: $block, receipts: $receipts}' > "mainnet_$number.json"
+    echo "mainnet_$number.json"
+done
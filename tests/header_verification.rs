@@ -0,0 +1,93 @@
+//! Header verification against a Postgres `blocks` table, with blocks whose header hashes are
+//! computed from `tests/fixtures/reorg.json`.
+
+mod common;
+
+use anyhow::Result;
+use blockchain_data_ingestion::integrity::headers::{compute_header_hash, HeaderVerifier, VerificationCheckpoint};
+use blockchain_data_ingestion::streams::consumers::hooks::ConsumerHook;
+use ethers::types::{Block, Transaction, H256, U64};
+use sqlx::{PgPool, Row};
+use std::sync::Arc;
+
+const CHAIN: &str = "MOCK";
+
+/// A block at `number` whose claimed hash matches its header.
+fn valid_block(number: u64) -> Result<Block<Transaction>> {
+    let blocks: Vec<Block<Transaction>> = serde_json::from_slice(&std::fs::read("tests/fixtures/reorg.json")?)?;
+    let mut block = blocks[0].clone();
+    block.number = Some(U64::from(number));
+    block.parent_hash = H256::from_low_u64_be(number - 1);
+    block.hash = compute_header_hash(&block);
+    assert!(block.hash.is_some(), "fixture has every header field");
+    Ok(block)
+}
+
+async fn insert_block(pool: &PgPool, number: i64, hash: &str, parent_hash: &str, header_valid: Option<bool>) -> Result<()> {
+    sqlx::query(
+        "INSERT INTO blocks (block_number, chain_name, hash, parent_hash, timestamp, miner, difficulty,
+            total_difficulty, gas_used, gas_limit, size, receipts_root, transactions, tx_count, header_valid)
+        VALUES ($1, $2, $3, $4, to_timestamp(0), '', '0', '0', 0, 0, 0, '', '[]', 0, $5)",
+    )
+    .bind(number)
+    .bind(CHAIN)
+    .bind(hash)
+    .bind(parent_hash)
+    .bind(header_valid)
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+async fn is_verified(pool: &PgPool, hash: &str) -> Result<bool> {
+    let row = sqlx::query("SELECT verified FROM blocks WHERE chain_name = $1 AND hash = $2")
+        .bind(CHAIN)
+        .bind(hash)
+        .fetch_one(pool)
+        .await?;
+    Ok(row.try_get("verified")?)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn an_invalid_parent_is_not_verified_through_its_child() -> Result<()> {
+    let database = common::start_postgres().await?;
+    let pool = Arc::new(database.pool.clone());
+
+    let block = valid_block(10)?;
+    let hash = format!("{:?}", block.hash.unwrap());
+    let parent_hash = format!("{:?}", block.parent_hash);
+    // The parent's header didn't hash to its claimed hash.
+    insert_block(&pool, 9, &parent_hash, &format!("{:?}", H256::from_low_u64_be(8)), Some(false)).await?;
+    insert_block(&pool, 10, &hash, &parent_hash, None).await?;
+
+    let checkpoint = VerificationCheckpoint { block_number: 10, hash: hash.clone() };
+    HeaderVerifier::new(Arc::clone(&pool), &checkpoint)?
+        .on_block_committed(CHAIN, &block)
+        .await?;
+
+    assert!(is_verified(&pool, &hash).await?);
+    assert!(!is_verified(&pool, &parent_hash).await?);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn a_valid_parent_is_verified_through_its_child() -> Result<()> {
+    let database = common::start_postgres().await?;
+    let pool = Arc::new(database.pool.clone());
+
+    let block = valid_block(10)?;
+    let hash = format!("{:?}", block.hash.unwrap());
+    let parent_hash = format!("{:?}", block.parent_hash);
+    insert_block(&pool, 9, &parent_hash, &format!("{:?}", H256::from_low_u64_be(8)), Some(true)).await?;
+    insert_block(&pool, 10, &hash, &parent_hash, None).await?;
+
+    let checkpoint = VerificationCheckpoint { block_number: 10, hash: hash.clone() };
+    HeaderVerifier::new(Arc::clone(&pool), &checkpoint)?
+        .on_block_committed(CHAIN, &block)
+        .await?;
+
+    assert!(is_verified(&pool, &parent_hash).await?);
+    Ok(())
+}
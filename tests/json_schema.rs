@@ -0,0 +1,141 @@
+//! The published JSON Schemas checked against payloads encoded the way producers encode them,
+//! from the fixture blocks. Fields a payload carries but its schema doesn't declare fail too, so
+//! the hand-written shapes can't drift from ethers' encoding unnoticed.
+
+use anyhow::Result;
+use blockchain_data_ingestion::streams::schemas::evm::{stamp_received_at, with_transaction_hashes};
+use blockchain_data_ingestion::streams::schemas::header::BlockHeader;
+use blockchain_data_ingestion::streams::schemas::json_schema::topic_schemas;
+use blockchain_data_ingestion::streams::schemas::schema::WireFormat;
+use blockchain_data_ingestion::streams::schemas::versioning::{encode_sampled, encode_versioned};
+use ethers::types::{Block, Transaction};
+use serde_json::Value;
+
+fn fixture_blocks() -> Result<Vec<Block<Transaction>>> {
+    let mut blocks: Vec<Block<Transaction>> = serde_json::from_str(&std::fs::read_to_string("tests/fixtures/reorg.json")?)?;
+    blocks.push(serde_json::from_str(&std::fs::read_to_string("tests/fixtures/block_v1.json")?)?);
+    Ok(blocks)
+}
+
+fn schema(name: &str) -> Value {
+    let schema = topic_schemas().into_iter().find(|schema| schema.name == name).expect("no such schema");
+    serde_json::to_value(schema.schema).unwrap()
+}
+
+fn matches_type(name: &str, value: &Value) -> bool {
+    match name {
+        "null" => value.is_null(),
+        "boolean" => value.is_boolean(),
+        "string" => value.is_string(),
+        "integer" => value.is_i64() || value.is_u64(),
+        "number" => value.is_number(),
+        "array" => value.is_array(),
+        "object" => value.is_object(),
+        _ => false,
+    }
+}
+
+/// The subset of JSON Schema schemars emits: `$ref`, `allOf`, `anyOf`, `type`, `properties`,
+/// `required` and `items`. Returns what doesn't match, by JSON path.
+fn violations(root: &Value, schema: &Value, value: &Value, path: &str) -> Vec<String> {
+    if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+        let name = reference.trim_start_matches("#/definitions/");
+        return violations(root, &root["definitions"][name], value, path);
+    }
+    let mut found = Vec::new();
+    if let Some(all) = schema.get("allOf").and_then(Value::as_array) {
+        for schema in all {
+            found.extend(violations(root, schema, value, path));
+        }
+    }
+    if let Some(any) = schema.get("anyOf").and_then(Value::as_array) {
+        if !any.iter().any(|schema| violations(root, schema, value, path).is_empty()) {
+            found.push(format!("{}: matches none of anyOf", path));
+        }
+    }
+    if let Some(types) = schema.get("type") {
+        let types: Vec<&str> = match types {
+            Value::Array(types) => types.iter().filter_map(Value::as_str).collect(),
+            other => other.as_str().into_iter().collect(),
+        };
+        if !types.iter().any(|name| matches_type(name, value)) {
+            found.push(format!("{}: {} is not {:?}", path, value, types));
+        }
+    }
+    if let (Some(properties), Some(object)) = (schema.get("properties").and_then(Value::as_object), value.as_object()) {
+        for required in schema.get("required").and_then(Value::as_array).into_iter().flatten() {
+            let required = required.as_str().unwrap();
+            if !object.contains_key(required) {
+                found.push(format!("{}.{}: missing", path, required));
+            }
+        }
+        for (key, field) in object {
+            match properties.get(key) {
+                Some(property) => found.extend(violations(root, property, field, &format!("{}.{}", path, key))),
+                None => found.push(format!("{}.{}: not in the schema", path, key)),
+            }
+        }
+    }
+    if let (Some(items), Some(array)) = (schema.get("items"), value.as_array()) {
+        for (index, item) in array.iter().enumerate() {
+            found.extend(violations(root, items, item, &format!("{}[{}]", path, index)));
+        }
+    }
+    found
+}
+
+fn assert_valid(schema: &Value, payload: &[u8]) -> Result<()> {
+    let value: Value = serde_json::from_slice(payload)?;
+    let found = violations(schema, schema, &value, "$");
+    assert!(found.is_empty(), "payload doesn't match its schema: {:#?}", found);
+    Ok(())
+}
+
+#[test]
+fn full_blocks_match_the_block_schema() -> Result<()> {
+    let schema = schema("block");
+    for block in fixture_blocks()? {
+        assert_valid(&schema, &encode_versioned(WireFormat::Json, &block)?)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn hash_only_blocks_match_the_block_schema() -> Result<()> {
+    let schema = schema("block");
+    for block in fixture_blocks()? {
+        assert_valid(&schema, &encode_versioned(WireFormat::Json, &with_transaction_hashes(&block)?)?)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn realtime_and_sampled_blocks_match_the_block_schema() -> Result<()> {
+    let schema = schema("block");
+    for mut block in fixture_blocks()? {
+        stamp_received_at(&mut block);
+        assert_valid(&schema, &encode_sampled(WireFormat::Json, &block, Some(0.25))?)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn headers_match_the_header_schema() -> Result<()> {
+    let schema = schema("block_header");
+    for block in fixture_blocks()? {
+        let header = BlockHeader::from(&block);
+        assert_valid(&schema, &encode_versioned(WireFormat::Json, &header)?)?;
+        assert_valid(&schema, &encode_sampled(WireFormat::Json, &header, Some(0.25))?)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn undeclared_fields_are_caught() -> Result<()> {
+    let schema = schema("block");
+    let mut block = fixture_blocks()?.remove(0);
+    block.other.insert("somethingNew".to_string(), Value::from(1));
+    let value: Value = serde_json::from_slice(&encode_versioned(WireFormat::Json, &block)?)?;
+    assert_eq!(violations(&schema, &schema, &value, "$"), vec!["$.somethingNew: not in the schema".to_string()]);
+    Ok(())
+}
@@ -0,0 +1,96 @@
+//! `MevDetector` sandwich findings over `Swap` logs, recorded in a throwaway Postgres. Needs Docker.
+
+mod common;
+
+use anyhow::Result;
+use blockchain_data_ingestion::blockchain::mock_adapter::MockAdapter;
+use blockchain_data_ingestion::enrichment::mev::{MevDetectionConfig, MevDetector};
+use blockchain_data_ingestion::streams::consumers::hooks::ConsumerHook;
+use ethers::types::{Address, Block, Log, Transaction, TransactionReceipt, H256, U64};
+use ethers::utils::keccak256;
+use sqlx::PgPool;
+use std::sync::Arc;
+
+const CHAIN: &str = "MOCK";
+const BLOCK: u64 = 100;
+
+fn address(n: u64) -> Address {
+    Address::from_low_u64_be(n)
+}
+
+/// A transaction from `from` to `to` that swaps in `pool`, sending the output to `recipient`.
+fn swap(index: u64, from: Address, to: Address, pool: Address, recipient: Address) -> (Transaction, TransactionReceipt) {
+    let hash = H256::from_low_u64_be(1000 + index);
+    let transaction = Transaction {
+        hash,
+        from,
+        to: Some(to),
+        transaction_index: Some(U64::from(index)),
+        block_number: Some(U64::from(BLOCK)),
+        ..Default::default()
+    };
+    let v2_swap = H256::from(keccak256("Swap(address,uint256,uint256,uint256,uint256,address)".as_bytes()));
+    let log = Log {
+        address: pool,
+        topics: vec![v2_swap, H256::from(to), H256::from(recipient)],
+        transaction_hash: Some(hash),
+        ..Default::default()
+    };
+    (transaction, TransactionReceipt { transaction_hash: hash, logs: vec![log], ..Default::default() })
+}
+
+fn detector(pool: &PgPool, swaps: Vec<(Transaction, TransactionReceipt)>) -> (MevDetector, Block<Transaction>) {
+    let (transactions, receipts): (Vec<_>, Vec<_>) = swaps.into_iter().unzip();
+    let block = Block {
+        number: Some(U64::from(BLOCK)),
+        hash: Some(H256::from_low_u64_be(BLOCK)),
+        transactions,
+        ..Default::default()
+    };
+    let adapter = MockAdapter::new(vec![block.clone()], None).with_receipts(BLOCK, receipts);
+    let config = MevDetectionConfig { backruns: false, builder_tags: false, ..Default::default() };
+    (MevDetector::new(Arc::new(adapter), Arc::new(pool.clone()), &config), block)
+}
+
+async fn sandwiches(pool: &PgPool) -> Result<i64> {
+    Ok(sqlx::query_scalar("SELECT COUNT(*) FROM mev_observations WHERE chain_name = $1 AND kind = 'sandwich'")
+        .bind(CHAIN)
+        .fetch_one(pool)
+        .await?)
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn a_bot_contract_around_a_victim_is_one_sandwich_however_often_the_block_comes() -> Result<()> {
+    let db = common::start_postgres().await?;
+    let (pool, bot, router, victim) = (address(1), address(2), address(3), address(4));
+    // The bot's two swaps are sent from different EOAs, but both pay out to the bot itself.
+    let (detector, block) = detector(
+        &db.pool,
+        vec![
+            swap(0, address(10), bot, pool, bot),
+            swap(1, victim, router, pool, victim),
+            swap(2, address(11), bot, pool, bot),
+        ],
+    );
+
+    detector.on_block_committed(CHAIN, &block).await?;
+    detector.on_block_committed(CHAIN, &block).await?;
+    assert_eq!(sandwiches(&db.pool).await?, 1);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn unrelated_users_of_one_router_are_not_a_sandwich() -> Result<()> {
+    let db = common::start_postgres().await?;
+    let (pool, router) = (address(1), address(3));
+    let (detector, block) = detector(
+        &db.pool,
+        (0..3).map(|i| swap(i, address(20 + i), router, pool, address(20 + i))).collect(),
+    );
+
+    detector.on_block_committed(CHAIN, &block).await?;
+    assert_eq!(sandwiches(&db.pool).await?, 0);
+    Ok(())
+}
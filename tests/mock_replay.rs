@@ -0,0 +1,182 @@
+//! Deterministic pipeline tests that replay `tests/fixtures/reorg.json` through `MockAdapter`.
+//!
+//! The fixture holds blocks 1..=4, where block 3 is first `0x..b3a` and is then replaced by
+//! `0x..b3b`, which block 4 builds on.
+
+mod common;
+
+use anyhow::Result;
+use blockchain_data_ingestion::blockchain::adapters::BlockchainAdapter;
+use blockchain_data_ingestion::blockchain::mock_adapter::MockAdapter;
+use blockchain_data_ingestion::streams::consumers::consumer::StreamConsumer;
+use blockchain_data_ingestion::streams::consumers::evm_consumer::EVMConsumer;
+use blockchain_data_ingestion::streams::message_queue::memory::InMemoryQueue;
+use blockchain_data_ingestion::streams::message_queue::queue::{MessageQueue, QueueSubscriber};
+use blockchain_data_ingestion::streams::producers::evm_producer::EVMProducer;
+use blockchain_data_ingestion::streams::producers::producer::StreamProducer;
+use blockchain_data_ingestion::streams::producers::watchdog::Heartbeat;
+use ethers::types::{Block, Transaction};
+use sqlx::Row;
+use std::sync::Arc;
+use std::time::Duration;
+
+const FIXTURE: &str = "tests/fixtures/reorg.json";
+const CHAIN: &str = "MOCK";
+const TOPIC: &str = "mock-blocks";
+
+fn hash(tag: &str) -> String {
+    format!("0x{:0>64}", tag)
+}
+
+async fn published_hashes(queue: &InMemoryQueue, count: usize) -> Result<Vec<String>> {
+    let mut subscriber = queue.subscriber(TOPIC, "test").await?;
+    let mut hashes = Vec::new();
+    for _ in 0..count {
+        let message = subscriber.next().await.expect("queue closed")?;
+        let block: Block<Transaction> = serde_json::from_slice(&message.payload)?;
+        hashes.push(format!("{:?}", block.hash.unwrap()));
+    }
+    Ok(hashes)
+}
+
+#[tokio::test]
+async fn realtime_replays_every_fixture_in_order() -> Result<()> {
+    let adapter = Arc::new(MockAdapter::from_fixtures(FIXTURE, None)?);
+    let queue = Arc::new(InMemoryQueue::new());
+    let producer = EVMProducer::new(adapter, Arc::clone(&queue) as Arc<dyn MessageQueue>, TOPIC.to_string()).await?;
+
+    producer.produce_realtime().await?;
+
+    assert_eq!(queue.len(TOPIC), 5);
+    assert_eq!(
+        published_hashes(&queue, 5).await?,
+        vec![hash("b1"), hash("b2"), hash("b3a"), hash("b3b"), hash("b4")]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn historical_backfill_sees_the_post_reorg_chain() -> Result<()> {
+    let adapter = Arc::new(MockAdapter::from_fixtures(FIXTURE, None)?);
+    assert_eq!(adapter.get_latest_block_number().await?, 4);
+
+    let queue = Arc::new(InMemoryQueue::new());
+    let producer = EVMProducer::new(adapter, Arc::clone(&queue) as Arc<dyn MessageQueue>, TOPIC.to_string()).await?;
+
+    producer.produce_historical(1, 4).await?;
+
+    assert_eq!(
+        published_hashes(&queue, 4).await?,
+        vec![hash("b1"), hash("b2"), hash("b3b"), hash("b4")]
+    );
+    Ok(())
+}
+
+#[tokio::test]
+async fn replay_respects_the_configured_rate() -> Result<()> {
+    let adapter = Arc::new(MockAdapter::from_fixtures(FIXTURE, Some(20.0))?);
+    let queue = Arc::new(InMemoryQueue::new());
+    let producer = EVMProducer::new(adapter, Arc::clone(&queue) as Arc<dyn MessageQueue>, TOPIC.to_string()).await?;
+
+    let started = tokio::time::Instant::now();
+    producer.produce_realtime().await?;
+
+    // Five blocks at 20/s leave four 50ms gaps.
+    assert!(started.elapsed() >= Duration::from_millis(200));
+    Ok(())
+}
+
+#[tokio::test]
+async fn gap_backfill_beats_the_heartbeat_per_block() -> Result<()> {
+    // The subscription jumps from block 1 to block 4, so blocks 2 and 3 are backfilled first.
+    let blocks: Vec<Block<Transaction>> = serde_json::from_slice(&std::fs::read(FIXTURE)?)?;
+    let replay = [hash("b1"), hash("b4"), hash("b2"), hash("b3b")]
+        .iter()
+        .map(|tag| blocks.iter().find(|block| format!("{:?}", block.hash.unwrap()) == *tag).unwrap().clone())
+        .collect();
+    let adapter = Arc::new(MockAdapter::new(replay, Some(10.0)));
+    let queue = Arc::new(InMemoryQueue::new());
+    let heartbeat = Arc::new(Heartbeat::default());
+    let producer = EVMProducer::new(adapter, Arc::clone(&queue) as Arc<dyn MessageQueue>, TOPIC.to_string())
+        .await?
+        .with_heartbeat(Arc::clone(&heartbeat));
+
+    let producing = tokio::spawn(async move { producer.produce_realtime().await });
+    let mut seen = Vec::new();
+    while !producing.is_finished() {
+        if let Some(block) = heartbeat.last_block() {
+            if seen.last() != Some(&block) {
+                seen.push(block);
+            }
+        }
+        tokio::time::sleep(Duration::from_millis(5)).await;
+    }
+    producing.await??;
+
+    // Block 2 is published a fetch before block 4; block 3 is followed by it straight away.
+    assert_eq!(seen.first(), Some(&1));
+    assert!(seen.contains(&2), "{:?}", seen);
+    assert_eq!(seen.last(), Some(&4));
+    assert_eq!(
+        published_hashes(&queue, 4).await?,
+        vec![hash("b1"), hash("b2"), hash("b3b"), hash("b4")]
+    );
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn consumer_orphans_the_replaced_block() -> Result<()> {
+    let database = common::start_postgres().await?;
+    let pool = Arc::new(database.pool.clone());
+    let queue: Arc<dyn MessageQueue> = Arc::new(InMemoryQueue::new());
+
+    let adapter = Arc::new(MockAdapter::from_fixtures(FIXTURE, None)?);
+    EVMProducer::new(adapter, Arc::clone(&queue), TOPIC.to_string())
+        .await?
+        .produce_realtime()
+        .await?;
+
+    let consumer_pool = Arc::clone(&pool);
+    let consumer = tokio::spawn(async move {
+        let mut consumer = EVMConsumer::new(queue, TOPIC.to_string(), "test".to_string(), Vec::new()).await;
+        consumer.postgres_consume(consumer_pool, CHAIN).await
+    });
+
+    common::wait_for(Duration::from_secs(30), || {
+        let pool = Arc::clone(&pool);
+        async move {
+            let row = sqlx::query("SELECT COUNT(*) AS count FROM blocks WHERE chain_name = $1")
+                .bind(CHAIN)
+                .fetch_one(pool.as_ref())
+                .await?;
+            Ok(row.try_get::<i64, _>("count")? == 5)
+        }
+    })
+    .await?;
+    consumer.abort();
+
+    let canonical_blocks: Vec<String> = sqlx::query(
+        "SELECT hash FROM blocks WHERE chain_name = $1 AND canonical ORDER BY block_number",
+    )
+    .bind(CHAIN)
+    .fetch_all(pool.as_ref())
+    .await?
+    .iter()
+    .map(|row| row.try_get("hash"))
+    .collect::<Result<_, _>>()?;
+    assert_eq!(canonical_blocks, vec![hash("b1"), hash("b2"), hash("b3b"), hash("b4")]);
+
+    let orphaned_transactions: Vec<String> = sqlx::query(
+        "SELECT tx_hash FROM transactions WHERE chain_name = $1 AND NOT canonical",
+    )
+    .bind(CHAIN)
+    .fetch_all(pool.as_ref())
+    .await?
+    .iter()
+    .map(|row| row.try_get("tx_hash"))
+    .collect::<Result<_, _>>()?;
+    assert_eq!(orphaned_transactions, vec![hash("a2")]);
+
+    Ok(())
+}
@@ -0,0 +1,92 @@
+//! `MongoSink` documents, and writes against a throwaway MongoDB (those need Docker).
+
+use anyhow::Result;
+use blockchain_data_ingestion::storage::projection::ProjectionConfig;
+use blockchain_data_ingestion::storage::sinks::mongo::{block_document, MongoSink, MongoSinkConfig};
+use blockchain_data_ingestion::storage::sinks::Sink;
+use ethers::types::{Address, Block, Bytes, Transaction, H256, U256, U64};
+use mongodb::bson::{doc, Bson};
+use std::collections::HashMap;
+use testcontainers::runners::AsyncRunner;
+use testcontainers_modules::mongo::Mongo;
+
+const CHAIN: &str = "MOCK";
+
+fn block(number: u64, hash: u64) -> Block<Transaction> {
+    let transaction = Transaction {
+        hash: H256::from_low_u64_be(1000 + number),
+        gas: U256::from(21_000),
+        gas_price: Some(U256::from(7)),
+        input: Bytes::from(vec![0xde, 0xad]),
+        ..Default::default()
+    };
+    Block {
+        number: Some(U64::from(number)),
+        hash: Some(H256::from_low_u64_be(hash)),
+        author: Some(Address::from_low_u64_be(1)),
+        transactions: vec![transaction],
+        ..Default::default()
+    }
+}
+
+fn excluding(table: &str, columns: &[&str]) -> ProjectionConfig {
+    ProjectionConfig {
+        exclude: HashMap::from([(table.to_string(), columns.iter().map(|column| column.to_string()).collect())]),
+    }
+}
+
+#[test]
+fn documents_are_keyed_by_chain_and_number() -> Result<()> {
+    let document = block_document(CHAIN, &block(5, 5), &ProjectionConfig::default())?;
+    assert_eq!(document.get_document("_id")?, &doc! { "chain": CHAIN, "number": 5i64 });
+    assert_eq!(document.get_i64("number")?, 5);
+    assert!(document.get_str("miner").is_ok());
+    Ok(())
+}
+
+#[test]
+fn excluded_transaction_columns_are_null_in_every_transaction() -> Result<()> {
+    let document = block_document(CHAIN, &block(5, 5), &excluding("transactions", &["input", "gas"]))?;
+    let transaction = document.get_array("transactions")?[0].as_document().unwrap();
+    assert_eq!(transaction.get("input"), Some(&Bson::Null));
+    assert_eq!(transaction.get("gas"), Some(&Bson::Null));
+    assert!(transaction.get_str("gasPrice").is_ok());
+    Ok(())
+}
+
+#[test]
+fn excluded_block_columns_are_null() -> Result<()> {
+    let document = block_document(CHAIN, &block(5, 5), &excluding("blocks", &["miner", "transactions"]))?;
+    assert_eq!(document.get("miner"), Some(&Bson::Null));
+    assert_eq!(document.get("transactions"), Some(&Bson::Null));
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn a_reorg_replacement_overwrites_its_height() -> Result<()> {
+    let container = Mongo::default().start().await?;
+    let uri = format!("mongodb://{}:{}", container.get_host().await?, container.get_host_port_ipv4(27017).await?);
+    std::env::set_var("MONGO_SINK_TEST_URI", uri);
+    let config = MongoSinkConfig {
+        uri: "MONGO_SINK_TEST_URI".to_string(),
+        database: "ingestion".to_string(),
+        collection: "blocks".to_string(),
+    };
+    let sink = MongoSink::new(&config).await?.with_projection(excluding("transactions", &["input"]));
+
+    sink.write_block(CHAIN, &block(1, 1)).await?;
+    sink.write_block(CHAIN, &block(2, 2)).await?;
+    sink.write_block(CHAIN, &block(2, 22)).await?;
+    assert_eq!(sink.last_block(CHAIN).await?, Some(2));
+    assert_eq!(sink.last_block("OTHER").await?, None);
+
+    let client = mongodb::Client::with_uri_str(std::env::var("MONGO_SINK_TEST_URI")?).await?;
+    let collection = client.database("ingestion").collection::<mongodb::bson::Document>("blocks");
+    assert_eq!(collection.count_documents(doc! { "chain_name": CHAIN }).await?, 2);
+    let stored = collection.find_one(doc! { "chain_name": CHAIN, "number": 2i64 }).await?.unwrap();
+    assert_eq!(stored.get_str("hash")?, format!("{:?}", H256::from_low_u64_be(22)));
+    let transaction = stored.get_array("transactions")?[0].as_document().unwrap();
+    assert_eq!(transaction.get("input"), Some(&Bson::Null));
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! Hooks of a consumer writing through several workers, with writes that finish out of order.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blockchain_data_ingestion::shutdown::Shutdown;
+use blockchain_data_ingestion::storage::sinks::{Sink, WriteOutcome};
+use blockchain_data_ingestion::streams::consumers::consumer::StreamConsumer;
+use blockchain_data_ingestion::streams::consumers::evm_consumer::EVMConsumer;
+use blockchain_data_ingestion::streams::consumers::hooks::ConsumerHook;
+use blockchain_data_ingestion::streams::message_queue::memory::InMemoryQueue;
+use blockchain_data_ingestion::streams::message_queue::queue::MessageQueue;
+use ethers::types::{Block, Transaction, H256, U64};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+const CHAIN: &str = "MOCK";
+const TOPIC: &str = "mock-blocks";
+
+/// A sink whose writes take longer the lower the block, so later blocks are stored first.
+struct SlowLowBlocksSink;
+
+#[async_trait]
+impl Sink for SlowLowBlocksSink {
+    async fn write_block(&self, _chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let number = block.number.unwrap_or_default().as_u64();
+        tokio::time::sleep(Duration::from_millis(60u64.saturating_sub(number * 10))).await;
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: true })
+    }
+}
+
+#[derive(Default)]
+struct CommitRecorder {
+    committed: Mutex<Vec<u64>>,
+    changed: Notify,
+}
+
+#[async_trait]
+impl ConsumerHook for CommitRecorder {
+    async fn on_block_committed(&self, _chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        self.committed.lock().unwrap().push(block.number.unwrap_or_default().as_u64());
+        self.changed.notify_one();
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn hooks_run_in_read_order_whatever_order_workers_finish_in() -> Result<()> {
+    let queue = Arc::new(InMemoryQueue::new());
+    for number in 1..=6u64 {
+        let block = Block::<Transaction> {
+            number: Some(U64::from(number)),
+            hash: Some(H256::from_low_u64_be(number)),
+            ..Default::default()
+        };
+        queue.publisher(TOPIC).await?.publish(serde_json::to_vec(&block)?).await?;
+    }
+    let shutdown = Shutdown::new();
+    let recorder = Arc::new(CommitRecorder::default());
+
+    let consumer = {
+        let queue = Arc::clone(&queue) as Arc<dyn MessageQueue>;
+        let shutdown = Arc::clone(&shutdown);
+        let hooks: Vec<Arc<dyn ConsumerHook>> = vec![Arc::clone(&recorder) as Arc<dyn ConsumerHook>];
+        tokio::spawn(async move {
+            EVMConsumer::new(queue, TOPIC.to_string(), "test".to_string(), hooks)
+                .await
+                .with_workers(3)
+                .with_shutdown(shutdown)
+                .consume(Arc::new(SlowLowBlocksSink), CHAIN)
+                .await
+        })
+    };
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while recorder.committed.lock().unwrap().len() < 6 {
+            recorder.changed.notified().await;
+        }
+    })
+    .await?;
+
+    shutdown.drain_consumers();
+    tokio::time::timeout(Duration::from_secs(5), consumer).await???;
+
+    assert_eq!(*recorder.committed.lock().unwrap(), vec![1, 2, 3, 4, 5, 6]);
+    let reports = shutdown.take_reports();
+    assert_eq!((reports[0].in_flight, reports[0].acked, reports[0].unacked), (0, 0, 0));
+    Ok(())
+}
@@ -0,0 +1,50 @@
+//! Per-consumer buffering of `ParquetSink`, written to a scratch directory.
+
+use anyhow::Result;
+use blockchain_data_ingestion::storage::sinks::parquet::{ParquetSink, ParquetSinkConfig};
+use blockchain_data_ingestion::storage::sinks::{for_consumer, Sink};
+use ethers::types::{Block, Transaction, H256, U64};
+use std::path::Path;
+use std::sync::Arc;
+
+const CHAIN: &str = "MOCK";
+
+fn block(number: u64) -> Block<Transaction> {
+    Block { number: Some(U64::from(number)), hash: Some(H256::from_low_u64_be(number)), ..Default::default() }
+}
+
+fn block_files(dir: &Path) -> Result<Vec<String>> {
+    let mut files = Vec::new();
+    for entry in std::fs::read_dir(dir.join(CHAIN))? {
+        let name = entry?.file_name().to_string_lossy().into_owned();
+        if name.starts_with("blocks-") {
+            files.push(name);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+#[tokio::test]
+async fn consumers_fill_their_own_files() -> Result<()> {
+    let dir = std::env::temp_dir().join(format!("parquet-sink-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    let sink: Arc<dyn Sink> = Arc::new(ParquetSink::new(&ParquetSinkConfig { dir: dir.clone(), blocks_per_file: 2 }));
+    let realtime = for_consumer(&sink);
+    let backfill = for_consumer(&sink);
+
+    assert!(!realtime.write_block(CHAIN, &block(100)).await?.durable);
+    assert!(!backfill.write_block(CHAIN, &block(1)).await?.durable);
+    // The backfill's second block fills its own buffer, not the realtime consumer's.
+    assert!(backfill.write_block(CHAIN, &block(2)).await?.durable);
+    let files = block_files(&dir)?;
+    assert_eq!(files.len(), 1);
+    assert!(files[0].starts_with("blocks-1-2-"), "{:?}", files);
+
+    assert!(realtime.flush(CHAIN).await?.is_some());
+    assert_eq!(block_files(&dir)?.len(), 2);
+    assert_eq!(sink.last_block(CHAIN).await?, Some(100));
+
+    std::fs::remove_dir_all(&dir)?;
+    Ok(())
+}
@@ -0,0 +1,24 @@
+//! Exchanges and routing keys `RabbitMqQueue` derives from topic names.
+
+use blockchain_data_ingestion::streams::message_queue::rabbitmq::route;
+
+fn chains(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+#[test]
+fn a_topic_goes_to_its_chains_exchange() {
+    assert_eq!(route("persistent://public/default/ETH-blocks", &chains(&["ETH"])), ("ETH", "ETH-blocks"));
+}
+
+#[test]
+fn hyphenated_chain_names_keep_their_exchange() {
+    let chains = chains(&["ARB", "ARB-NOVA"]);
+    assert_eq!(route("ARB-NOVA-blocks-historical", &chains), ("ARB-NOVA", "ARB-NOVA-blocks-historical"));
+    assert_eq!(route("ARB-blocks", &chains), ("ARB", "ARB-blocks"));
+}
+
+#[test]
+fn a_topic_of_no_chain_gets_its_own_exchange() {
+    assert_eq!(route("ETHEREUM-blocks", &chains(&["ETH"])), ("ETHEREUM-blocks", "ETHEREUM-blocks"));
+}
@@ -0,0 +1,149 @@
+//! `RedeliveryBackoff` delays, and consumers retrying failed writes in place.
+
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use blockchain_data_ingestion::shutdown::Shutdown;
+use blockchain_data_ingestion::storage::sinks::{Sink, WriteOutcome};
+use blockchain_data_ingestion::streams::consumers::consumer::StreamConsumer;
+use blockchain_data_ingestion::streams::consumers::evm_consumer::EVMConsumer;
+use blockchain_data_ingestion::streams::consumers::hooks::ConsumerHook;
+use blockchain_data_ingestion::streams::consumers::redelivery::{RedeliveryBackoff, RedeliveryConfig};
+use blockchain_data_ingestion::streams::message_queue::memory::InMemoryQueue;
+use blockchain_data_ingestion::streams::message_queue::queue::MessageQueue;
+use ethers::types::{Block, Transaction, H256, U64};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+const CHAIN: &str = "MOCK";
+const TOPIC: &str = "mock-blocks";
+
+fn config(initial_delay_ms: u64, multiplier: f64, max_delay_ms: u64) -> RedeliveryConfig {
+    RedeliveryConfig { initial_delay_ms, multiplier, max_delay_ms }
+}
+
+#[test]
+fn delays_grow_by_the_multiplier_up_to_the_cap() {
+    let mut backoff = RedeliveryBackoff::new(config(100, 2.0, 1000));
+    let delays: Vec<_> = (0..6).map(|_| backoff.next_delay().as_millis()).collect();
+    assert_eq!(delays, vec![100, 200, 400, 800, 1000, 1000]);
+}
+
+#[test]
+fn a_success_starts_over_from_the_initial_delay() {
+    let mut backoff = RedeliveryBackoff::new(config(100, 3.0, 60_000));
+    backoff.next_delay();
+    backoff.next_delay();
+    backoff.reset();
+    assert_eq!(backoff.next_delay(), Duration::from_millis(100));
+}
+
+#[test]
+fn a_multiplier_below_one_keeps_the_delay_flat() {
+    let mut backoff = RedeliveryBackoff::new(config(250, 0.5, 60_000));
+    assert!((0..5).all(|_| backoff.next_delay() == Duration::from_millis(250)));
+}
+
+#[test]
+fn many_failures_stay_at_the_cap() {
+    let mut backoff = RedeliveryBackoff::new(config(500, 2.0, 60_000));
+    for _ in 0..10_000 {
+        backoff.next_delay();
+    }
+    assert_eq!(backoff.next_delay(), Duration::from_millis(60_000));
+}
+
+/// A sink that fails the first `failures` writes of one block.
+struct FlakySink {
+    failing_block: u64,
+    failures: Mutex<u32>,
+    written: Mutex<Vec<u64>>,
+}
+
+#[async_trait]
+impl Sink for FlakySink {
+    async fn write_block(&self, _chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        let number = block.number.unwrap_or_default().as_u64();
+        if number == self.failing_block {
+            let mut failures = self.failures.lock().unwrap();
+            if *failures > 0 {
+                *failures -= 1;
+                return Err(anyhow!("database failing over"));
+            }
+        }
+        self.written.lock().unwrap().push(number);
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: true })
+    }
+}
+
+#[derive(Default)]
+struct CommitRecorder {
+    committed: Mutex<Vec<u64>>,
+    changed: Notify,
+}
+
+#[async_trait]
+impl ConsumerHook for CommitRecorder {
+    async fn on_block_committed(&self, _chain_name: &str, block: &Block<Transaction>) -> Result<()> {
+        self.committed.lock().unwrap().push(block.number.unwrap_or_default().as_u64());
+        self.changed.notify_one();
+        Ok(())
+    }
+}
+
+async fn consume_with_a_flaky_write(workers: usize) -> Result<()> {
+    let queue = Arc::new(InMemoryQueue::new());
+    for number in 1..=4u64 {
+        let block = Block::<Transaction> {
+            number: Some(U64::from(number)),
+            hash: Some(H256::from_low_u64_be(number)),
+            ..Default::default()
+        };
+        queue.publisher(TOPIC).await?.publish(serde_json::to_vec(&block)?).await?;
+    }
+    let shutdown = Shutdown::new();
+    let recorder = Arc::new(CommitRecorder::default());
+    let sink = Arc::new(FlakySink { failing_block: 2, failures: Mutex::new(2), written: Mutex::new(Vec::new()) });
+
+    let consumer = {
+        let queue = Arc::clone(&queue) as Arc<dyn MessageQueue>;
+        let shutdown = Arc::clone(&shutdown);
+        let sink = Arc::clone(&sink) as Arc<dyn Sink>;
+        let hooks: Vec<Arc<dyn ConsumerHook>> = vec![Arc::clone(&recorder) as Arc<dyn ConsumerHook>];
+        tokio::spawn(async move {
+            EVMConsumer::new(queue, TOPIC.to_string(), "test".to_string(), hooks)
+                .await
+                .with_workers(workers)
+                .with_redelivery(config(10, 2.0, 100))
+                .with_shutdown(shutdown)
+                .consume(sink, CHAIN)
+                .await
+        })
+    };
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while recorder.committed.lock().unwrap().len() < 4 {
+            recorder.changed.notified().await;
+        }
+    })
+    .await?;
+
+    shutdown.drain_consumers();
+    tokio::time::timeout(Duration::from_secs(5), consumer).await???;
+
+    // Each block's hooks ran once, in order, however often its write was tried.
+    assert_eq!(*recorder.committed.lock().unwrap(), vec![1, 2, 3, 4]);
+    if workers == 1 {
+        assert_eq!(*sink.written.lock().unwrap(), vec![1, 2, 3, 4]);
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn a_failed_write_is_retried_before_later_blocks() -> Result<()> {
+    consume_with_a_flaky_write(1).await
+}
+
+#[tokio::test]
+async fn parallel_workers_keep_hooks_in_order_across_a_retry() -> Result<()> {
+    consume_with_a_flaky_write(2).await
+}
@@ -0,0 +1,65 @@
+//! Reth blocks mapped to `ethers` types must hash and re-encode exactly like the originals, or
+//! header and root verification reject every block read from the database.
+#![cfg(feature = "reth")]
+
+use alloy_primitives::{address, b256, bytes, hex, Address, Bloom, B64, U256};
+use blockchain_data_ingestion::blockchain::reth_db_adapter::to_ethers_block;
+use blockchain_data_ingestion::integrity::headers::compute_header_hash;
+use ethers::types::H256;
+use reth_primitives::{Block, BlockBody, BlockWithSenders, Header, TransactionSigned};
+
+const EMPTY_ROOT: alloy_primitives::B256 = b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// The mainnet genesis header, hashing to 0xd4e5…8fa3.
+fn mainnet_genesis() -> Header {
+    Header {
+        ommers_hash: b256!("1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347"),
+        state_root: b256!("d7f8974fb5ac78d9ac099b9ad5018bedc2ce0a72dad1827a1709da30580f0544"),
+        transactions_root: EMPTY_ROOT,
+        receipts_root: EMPTY_ROOT,
+        logs_bloom: Bloom::ZERO,
+        difficulty: U256::from(0x400000000u64),
+        number: 0,
+        gas_limit: 5000,
+        gas_used: 0,
+        timestamp: 0,
+        extra_data: bytes!("11bbe8db4e347b4e8c937c1c8370e4b5ed33adb3db69cbdb7a38e1e50b1b82fa"),
+        nonce: B64::from(0x42u64),
+        ..Default::default()
+    }
+}
+
+fn with_senders(header: Header, transactions: Vec<TransactionSigned>, senders: Vec<Address>) -> BlockWithSenders {
+    BlockWithSenders {
+        block: Block { header, body: BlockBody { transactions, ..Default::default() } },
+        senders,
+    }
+}
+
+#[test]
+fn mapped_headers_hash_to_the_block_hash() {
+    let block = to_ethers_block(with_senders(mainnet_genesis(), Vec::new(), Vec::new()));
+
+    let expected: H256 = "0xd4e56740f876aef8c010b86a40d5f56745a118d0906a34e69aec8c0db1cb8fa3".parse().unwrap();
+    assert_eq!(block.hash, Some(expected));
+    assert_eq!(compute_header_hash(&block), Some(expected));
+}
+
+#[test]
+fn mapped_transactions_re_encode_byte_for_byte() {
+    // The signed example transaction of EIP-155.
+    let raw = hex!("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83");
+    let transaction = TransactionSigned::decode_enveloped(&mut &raw[..]).unwrap();
+    let sender = address!("9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f");
+
+    let block = to_ethers_block(with_senders(mainnet_genesis(), vec![transaction], vec![sender]));
+    let tx = &block.transactions[0];
+
+    assert_eq!(tx.rlp().as_ref(), &raw[..]);
+    let expected: H256 = "0x33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788".parse().unwrap();
+    assert_eq!(tx.hash, expected);
+    assert_eq!(tx.hash(), expected);
+    assert_eq!(tx.v.as_u64(), 37);
+    assert_eq!(tx.gas_price, Some(20_000_000_000u64.into()));
+    assert_eq!(tx.from, sender.0 .0.into());
+}
@@ -0,0 +1,53 @@
+//! Transactions and receipts roots recomputed by `integrity::roots`, against mainnet headers.
+
+use anyhow::Result;
+use blockchain_data_ingestion::integrity::roots::{ordered_trie_root, receipts_root, transactions_root};
+use ethers::types::{Block, Transaction, TransactionReceipt, H256};
+use serde::Deserialize;
+use std::collections::BTreeSet;
+use std::path::Path;
+
+/// A block as captured by `tests/fixtures/roots/capture.sh`.
+#[derive(Deserialize)]
+struct Fixture {
+    block: Block<Transaction>,
+    receipts: Vec<TransactionReceipt>,
+}
+
+fn fixtures() -> Result<Vec<(String, Fixture)>> {
+    let mut fixtures = Vec::new();
+    for entry in std::fs::read_dir(Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/roots"))? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|extension| extension == "json") {
+            let fixture = serde_json::from_slice(&std::fs::read(&path)?)?;
+            fixtures.push((path.display().to_string(), fixture));
+        }
+    }
+    Ok(fixtures)
+}
+
+#[test]
+fn an_empty_trie_has_the_empty_root() {
+    let empty: H256 = "0x56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421".parse().unwrap();
+    assert_eq!(ordered_trie_root(&[]), empty);
+    assert_eq!(transactions_root(&[]), Some(empty));
+    assert_eq!(receipts_root(&[]), empty);
+}
+
+#[test]
+#[ignore = "needs mainnet blocks captured with tests/fixtures/roots/capture.sh"]
+fn recomputed_roots_match_mainnet_headers() -> Result<()> {
+    let fixtures = fixtures()?;
+    let types: BTreeSet<u64> = fixtures
+        .iter()
+        .flat_map(|(_, fixture)| &fixture.block.transactions)
+        .map(|tx| tx.transaction_type.map_or(0, |kind| kind.as_u64()))
+        .collect();
+    assert!([0, 1, 2, 3].iter().all(|kind| types.contains(kind)), "fixtures only cover transaction types {:?}", types);
+
+    for (path, fixture) in &fixtures {
+        assert_eq!(transactions_root(&fixture.block.transactions), Some(fixture.block.transactions_root), "{}", path);
+        assert_eq!(receipts_root(&fixture.receipts), fixture.block.receipts_root, "{}", path);
+    }
+    Ok(())
+}
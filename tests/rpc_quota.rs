@@ -0,0 +1,47 @@
+//! `PriorityRateLimiter` splitting one request quota between realtime and backfill.
+
+use blockchain_data_ingestion::blockchain::rpc_quota::{Priority, PriorityRateLimiter, RpcQuotaConfig};
+use std::time::Duration;
+use tokio::time::{timeout, Instant};
+
+fn limiter(requests_per_second: f64, realtime_share: f64) -> PriorityRateLimiter {
+    PriorityRateLimiter::new(&RpcQuotaConfig { requests_per_second, realtime_share })
+}
+
+#[tokio::test]
+async fn backfill_borrows_an_idle_realtime_share_even_when_realtime_reserves_it_all() {
+    let limiter = limiter(10.0, 1.0);
+    // Its own 5% floor alone would take over two seconds for this.
+    let acquired = timeout(Duration::from_secs(3), async {
+        for _ in 0..15 {
+            limiter.acquire(Priority::Backfill).await;
+        }
+    })
+    .await;
+    assert!(acquired.is_ok(), "backfill stalled on an idle realtime share");
+}
+
+#[tokio::test]
+async fn realtime_keeps_a_burst_while_backfill_borrows() {
+    let limiter = limiter(10.0, 0.8);
+    for _ in 0..10 {
+        limiter.acquire(Priority::Backfill).await;
+    }
+    let started = Instant::now();
+    for _ in 0..3 {
+        limiter.acquire(Priority::Realtime).await;
+    }
+    assert!(started.elapsed() < Duration::from_millis(100), "realtime waited {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn a_small_realtime_bucket_can_still_be_borrowed() {
+    let limiter = limiter(1.0, 0.95);
+    let acquired = timeout(Duration::from_secs(5), async {
+        for _ in 0..3 {
+            limiter.acquire(Priority::Backfill).await;
+        }
+    })
+    .await;
+    assert!(acquired.is_ok());
+}
@@ -0,0 +1,53 @@
+//! Which blocks and mempool transactions `Sampler` keeps.
+
+use blockchain_data_ingestion::streams::sampling::Sampler;
+use ethers::types::H256;
+
+#[test]
+fn rejects_rates_outside_zero_to_one() {
+    for rate in [0.0, -0.5, 1.5, f64::NAN] {
+        assert!(Sampler::new(rate).is_err(), "{}", rate);
+    }
+    assert_eq!(Sampler::new(0.25).unwrap().rate(), 0.25);
+}
+
+#[test]
+fn a_rate_of_one_keeps_every_block() {
+    let sampler = Sampler::new(1.0).unwrap();
+    assert!((0..10_000).all(|block_number| sampler.keeps(block_number)));
+}
+
+#[test]
+fn keeps_about_its_rate_of_blocks_spread_across_the_range() {
+    let sampler = Sampler::new(0.1).unwrap();
+    let kept = (0..100_000u64).filter(|block_number| sampler.keeps(*block_number)).count();
+    assert!((9_500..10_500).contains(&kept), "{}", kept);
+
+    // Not in runs: every thousand consecutive blocks keep some.
+    for start in (0..100_000u64).step_by(1000) {
+        assert!((start..start + 1000).any(|block_number| sampler.keeps(block_number)), "{}", start);
+    }
+}
+
+#[test]
+fn decisions_depend_on_the_block_number_alone() {
+    let first = Sampler::new(0.3).unwrap();
+    let second = Sampler::new(0.3).unwrap();
+    assert!((0..10_000).all(|block_number| first.keeps(block_number) == second.keeps(block_number)));
+}
+
+#[test]
+fn a_higher_rate_keeps_every_block_a_lower_one_does() {
+    let low = Sampler::new(0.1).unwrap();
+    let high = Sampler::new(0.5).unwrap();
+    assert!((0..10_000).filter(|block_number| low.keeps(*block_number)).all(|block_number| high.keeps(block_number)));
+}
+
+#[test]
+fn samples_transactions_by_hash() {
+    let sampler = Sampler::new(0.2).unwrap();
+    let kept = (0..50_000u64).filter(|n| sampler.keeps_hash(&H256::from_low_u64_be(*n))).count();
+    assert!((9_000..11_000).contains(&kept), "{}", kept);
+    let hash = H256::from_low_u64_be(42);
+    assert_eq!(sampler.keeps_hash(&hash), sampler.keeps(42));
+}
@@ -0,0 +1,116 @@
+//! Pins payloads written by older message schema versions, so changes to the message structs
+//! can't silently break consumers of long-retention topics.
+//!
+//! `block_v1.json` is an unversioned block as producers wrote it before versioning. The
+//! `transfer_v*.json` fixtures exercise the up-converter chain on a test-local message type.
+
+use anyhow::{anyhow, Result};
+use blockchain_data_ingestion::streams::schemas::schema::WireFormat;
+use blockchain_data_ingestion::streams::schemas::versioning::{
+    decode_versioned, encode_sampled, encode_versioned, Upgrade, Versioned, SAMPLE_RATE_FIELD, SCHEMA_VERSION_FIELD,
+};
+use ethers::types::{Block, Transaction};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+fn fixture(name: &str) -> Vec<u8> {
+    std::fs::read(format!("tests/fixtures/{}", name)).expect("fixture exists")
+}
+
+/// Version 3 of a transfer message. Version 1 had a numeric `value`, version 2 renamed it to a
+/// string `amount` and version 3 added `memo`.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+struct Transfer {
+    from: String,
+    to: String,
+    amount: String,
+    memo: Option<String>,
+}
+
+fn v1_to_v2(mut message: Value) -> Result<Value> {
+    let object = message.as_object_mut().ok_or_else(|| anyhow!("Transfer is not an object"))?;
+    let value = object.remove("value").ok_or_else(|| anyhow!("Transfer has no value"))?;
+    object.insert("amount".to_string(), Value::String(value.to_string()));
+    Ok(message)
+}
+
+fn v2_to_v3(mut message: Value) -> Result<Value> {
+    message["memo"] = Value::Null;
+    Ok(message)
+}
+
+impl Versioned for Transfer {
+    const SCHEMA_VERSION: u32 = 3;
+
+    fn upgrades() -> Vec<(u32, Upgrade)> {
+        vec![(1, v1_to_v2), (2, v2_to_v3)]
+    }
+}
+
+fn expected_transfer() -> Transfer {
+    Transfer {
+        from: "0x00000000000000000000000000000000000000a1".to_string(),
+        to: "0x00000000000000000000000000000000000000a2".to_string(),
+        amount: "1000".to_string(),
+        memo: None,
+    }
+}
+
+#[test]
+fn unversioned_block_decodes_as_version_1() -> Result<()> {
+    let block: Block<Transaction> = decode_versioned(&fixture("block_v1.json"))?;
+    assert_eq!(block.number.map(|n| n.as_u64()), Some(1));
+    assert_eq!(format!("{:?}", block.hash.unwrap_or_default()), format!("0x{:0>64}", "b1"));
+    // The stamp never leaks into the block's catch-all fields.
+    assert!(!block.other.contains_key(SCHEMA_VERSION_FIELD));
+    Ok(())
+}
+
+#[test]
+fn blocks_round_trip_with_a_version_stamp() -> Result<()> {
+    let block: Block<Transaction> = serde_json::from_slice(&fixture("block_v1.json"))?;
+    for format in [WireFormat::Json, WireFormat::MessagePack] {
+        let payload = encode_versioned(format, &block)?;
+        let decoded: Block<Transaction> = decode_versioned(&payload)?;
+        assert_eq!(decoded, block, "{:?}", format);
+    }
+
+    let stamped: Value = serde_json::from_slice(&encode_versioned(WireFormat::Json, &block)?)?;
+    assert_eq!(stamped[SCHEMA_VERSION_FIELD], json!(1));
+    Ok(())
+}
+
+#[test]
+fn sampled_blocks_carry_their_sample_rate() -> Result<()> {
+    let block: Block<Transaction> = serde_json::from_slice(&fixture("block_v1.json"))?;
+    let payload = encode_sampled(WireFormat::Json, &block, Some(0.1))?;
+    let stamped: Value = serde_json::from_slice(&payload)?;
+    assert_eq!(stamped[SAMPLE_RATE_FIELD], json!(0.1));
+
+    let decoded: Block<Transaction> = decode_versioned(&payload)?;
+    assert_eq!(decoded, block);
+    assert!(!decoded.other.contains_key(SAMPLE_RATE_FIELD));
+
+    // Unsampled streams don't carry the field at all.
+    let unsampled: Value = serde_json::from_slice(&encode_versioned(WireFormat::Json, &block)?)?;
+    assert!(unsampled.get(SAMPLE_RATE_FIELD).is_none());
+    Ok(())
+}
+
+#[test]
+fn old_payloads_are_upgraded_to_the_current_version() -> Result<()> {
+    assert_eq!(decode_versioned::<Transfer>(&fixture("transfer_v1.json"))?, expected_transfer());
+    assert_eq!(decode_versioned::<Transfer>(&fixture("transfer_v2.json"))?, expected_transfer());
+
+    let current = encode_versioned(WireFormat::Json, &expected_transfer())?;
+    assert_eq!(decode_versioned::<Transfer>(&current)?, expected_transfer());
+    Ok(())
+}
+
+#[test]
+fn newer_payloads_are_rejected() {
+    let mut block: Value = serde_json::from_slice(&fixture("block_v1.json")).unwrap();
+    block[SCHEMA_VERSION_FIELD] = json!(2);
+    let payload = serde_json::to_vec(&block).unwrap();
+    assert!(decode_versioned::<Block<Transaction>>(&payload).is_err());
+}
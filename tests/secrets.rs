@@ -0,0 +1,54 @@
+//! Masking API keys and credentials in URLs before they reach a log line.
+
+use blockchain_data_ingestion::secrets::{looks_like_key, redact_url, SecretRedactionConfig};
+
+fn config() -> SecretRedactionConfig {
+    SecretRedactionConfig::default()
+}
+
+#[test]
+fn masks_a_key_in_the_path() {
+    assert_eq!(
+        redact_url("https://eth-mainnet.g.alchemy.com/v2/a1B2c3D4e5F6g7H8i9J0kLmN", &config()),
+        "https://eth-mainnet.g.alchemy.com/v2/***"
+    );
+}
+
+#[test]
+fn masks_credentials_but_keeps_the_user() {
+    assert_eq!(redact_url("postgres://ingest:hunter2@db:5432/chain", &config()), "postgres://ingest:***@db:5432/chain");
+    assert_eq!(redact_url("https://token@rpc.example.com/", &config()), "https://***@rpc.example.com/");
+}
+
+#[test]
+fn masks_key_query_parameters_case_insensitively_and_keeps_the_rest() {
+    assert_eq!(
+        redact_url("https://api.etherscan.io/api?module=contract&ApiKey=ABC123&action=getabi#top", &config()),
+        "https://api.etherscan.io/api?module=contract&ApiKey=***&action=getabi#top"
+    );
+}
+
+#[test]
+fn leaves_urls_without_secrets_alone() {
+    let url = "https://rpc.example.com/v1/mainnet?block=latest";
+    assert_eq!(redact_url(url, &config()), url);
+}
+
+#[test]
+fn a_key_is_long_and_mixes_letters_and_digits() {
+    assert!(looks_like_key("a1B2c3D4e5F6g7H8i9J0", 20));
+    assert!(looks_like_key("abc_123-def_456-ghi_789", 20));
+    // Too short, letters only, digits only, or with other characters.
+    assert!(!looks_like_key("a1B2c3D4e5", 20));
+    assert!(!looks_like_key("transactionsandblocks", 20));
+    assert!(!looks_like_key("123456789012345678901234", 20));
+    assert!(!looks_like_key("a1B2c3D4e5F6g7H8i9J0.json", 20));
+}
+
+#[test]
+fn a_min_length_of_zero_turns_path_masking_off() {
+    assert!(!looks_like_key("a1B2c3D4e5F6g7H8i9J0kLmN", 0));
+    let config = SecretRedactionConfig { min_key_length: 0, ..config() };
+    let url = "https://eth-mainnet.g.alchemy.com/v2/a1B2c3D4e5F6g7H8i9J0kLmN";
+    assert_eq!(redact_url(url, &config), url);
+}
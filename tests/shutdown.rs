@@ -0,0 +1,198 @@
+//! Draining consumers through `Shutdown`, with sinks that hang or buffer their writes.
+
+use anyhow::Result;
+use async_trait::async_trait;
+use blockchain_data_ingestion::shutdown::Shutdown;
+use blockchain_data_ingestion::storage::sinks::{Sink, WriteOutcome};
+use blockchain_data_ingestion::streams::consumers::consumer::StreamConsumer;
+use blockchain_data_ingestion::streams::consumers::evm_consumer::EVMConsumer;
+use blockchain_data_ingestion::streams::consumers::hooks::{ConsumerHook, OrphanedBlock};
+use blockchain_data_ingestion::streams::message_queue::memory::InMemoryQueue;
+use blockchain_data_ingestion::streams::message_queue::queue::MessageQueue;
+use ethers::types::{Block, Transaction, H256, U64};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+const CHAIN: &str = "MOCK";
+const TOPIC: &str = "mock-blocks";
+
+async fn publish_block(queue: &InMemoryQueue, number: u64) -> Result<()> {
+    let block = Block::<Transaction> {
+        number: Some(U64::from(number)),
+        hash: Some(H256::from_low_u64_be(number)),
+        ..Default::default()
+    };
+    queue.publisher(TOPIC).await?.publish(serde_json::to_vec(&block)?).await
+}
+
+/// A sink whose writes never complete, like one whose database stopped answering.
+struct HangingSink {
+    writing: Notify,
+}
+
+#[async_trait]
+impl Sink for HangingSink {
+    async fn write_block(&self, _chain_name: &str, _block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.writing.notify_one();
+        std::future::pending().await
+    }
+
+    async fn write_transactions(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.write_block(chain_name, block).await
+    }
+
+    async fn write_header(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.write_block(chain_name, block).await
+    }
+
+    async fn last_block(&self, _chain_name: &str) -> Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+/// A sink that buffers every write and only reports a reorg once it flushes.
+#[derive(Default)]
+struct BufferingSink {
+    buffered: Mutex<Vec<u64>>,
+    written: Notify,
+}
+
+#[async_trait]
+impl Sink for BufferingSink {
+    async fn write_block(&self, _chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.buffered.lock().unwrap().push(block.number.unwrap_or_default().as_u64());
+        self.written.notify_one();
+        Ok(WriteOutcome { orphaned: Vec::new(), durable: false })
+    }
+
+    async fn write_transactions(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.write_block(chain_name, block).await
+    }
+
+    async fn write_header(&self, chain_name: &str, block: &Block<Transaction>) -> Result<WriteOutcome> {
+        self.write_block(chain_name, block).await
+    }
+
+    async fn last_block(&self, _chain_name: &str) -> Result<Option<u64>> {
+        Ok(self.buffered.lock().unwrap().last().copied())
+    }
+
+    async fn flush(&self, _chain_name: &str) -> Result<Option<WriteOutcome>> {
+        let orphaned = self
+            .buffered
+            .lock()
+            .unwrap()
+            .drain(..)
+            .map(|number| OrphanedBlock {
+                block_number: number as i64,
+                hash: format!("{:?}", H256::from_low_u64_be(number + 100)),
+                replaced_by: format!("{:?}", H256::from_low_u64_be(number)),
+            })
+            .collect();
+        Ok(Some(WriteOutcome { orphaned, durable: true }))
+    }
+}
+
+#[derive(Default)]
+struct OrphanRecorder {
+    orphaned: Mutex<Vec<i64>>,
+}
+
+#[async_trait]
+impl ConsumerHook for OrphanRecorder {
+    async fn on_block_committed(&self, _chain_name: &str, _block: &Block<Transaction>) -> Result<()> {
+        Ok(())
+    }
+
+    async fn on_blocks_orphaned(&self, _chain_name: &str, orphaned: &[OrphanedBlock]) -> Result<()> {
+        self.orphaned.lock().unwrap().extend(orphaned.iter().map(|block| block.block_number));
+        Ok(())
+    }
+}
+
+#[tokio::test]
+async fn consumers_drain_only_after_producers_stop() {
+    let shutdown = Shutdown::new();
+    let draining = {
+        let shutdown = Arc::clone(&shutdown);
+        tokio::spawn(async move { shutdown.consumers_draining().await })
+    };
+
+    shutdown.stop_producers();
+    tokio::time::timeout(Duration::from_secs(1), shutdown.producers_stopping()).await.unwrap();
+    tokio::time::sleep(Duration::from_millis(50)).await;
+    assert!(!draining.is_finished());
+
+    shutdown.drain_consumers();
+    tokio::time::timeout(Duration::from_secs(1), draining).await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn a_hanging_write_does_not_hold_up_the_drain() -> Result<()> {
+    let queue = Arc::new(InMemoryQueue::new());
+    publish_block(&queue, 1).await?;
+    let shutdown = Shutdown::new();
+    let sink = Arc::new(HangingSink { writing: Notify::new() });
+
+    let consumer = {
+        let queue = Arc::clone(&queue) as Arc<dyn MessageQueue>;
+        let shutdown = Arc::clone(&shutdown);
+        let sink = Arc::clone(&sink) as Arc<dyn Sink>;
+        tokio::spawn(async move {
+            EVMConsumer::new(queue, TOPIC.to_string(), "test".to_string(), Vec::new())
+                .await
+                .with_shutdown(shutdown)
+                .consume(sink, CHAIN)
+                .await
+        })
+    };
+    tokio::time::timeout(Duration::from_secs(5), sink.writing.notified()).await?;
+
+    shutdown.drain_consumers();
+    tokio::time::timeout(Duration::from_secs(5), consumer).await???;
+
+    let reports = shutdown.take_reports();
+    assert_eq!(reports.len(), 1);
+    assert_eq!((reports[0].in_flight, reports[0].acked, reports[0].unacked), (1, 0, 1));
+    Ok(())
+}
+
+#[tokio::test]
+async fn draining_acknowledges_buffered_blocks_and_reports_flushed_orphans() -> Result<()> {
+    let queue = Arc::new(InMemoryQueue::new());
+    publish_block(&queue, 1).await?;
+    publish_block(&queue, 2).await?;
+    let shutdown = Shutdown::new();
+    let sink = Arc::new(BufferingSink::default());
+    let recorder = Arc::new(OrphanRecorder::default());
+
+    let consumer = {
+        let queue = Arc::clone(&queue) as Arc<dyn MessageQueue>;
+        let shutdown = Arc::clone(&shutdown);
+        let sink = Arc::clone(&sink) as Arc<dyn Sink>;
+        let hooks: Vec<Arc<dyn ConsumerHook>> = vec![Arc::clone(&recorder) as Arc<dyn ConsumerHook>];
+        tokio::spawn(async move {
+            EVMConsumer::new(queue, TOPIC.to_string(), "test".to_string(), hooks)
+                .await
+                .with_shutdown(shutdown)
+                .consume(sink, CHAIN)
+                .await
+        })
+    };
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while sink.buffered.lock().unwrap().len() < 2 {
+            sink.written.notified().await;
+        }
+    })
+    .await?;
+
+    shutdown.drain_consumers();
+    tokio::time::timeout(Duration::from_secs(5), consumer).await???;
+
+    let reports = shutdown.take_reports();
+    assert_eq!((reports[0].in_flight, reports[0].acked, reports[0].unacked), (2, 2, 0));
+    assert_eq!(reports[0].flushed_batches, 1);
+    assert_eq!(*recorder.orphaned.lock().unwrap(), vec![1, 2]);
+    Ok(())
+}
@@ -0,0 +1,52 @@
+//! `SqliteSink` durability settings and commits, against a scratch database file.
+
+use anyhow::Result;
+use blockchain_data_ingestion::storage::sinks::sqlite::{connect_options, SqliteSink, SqliteSinkConfig};
+use blockchain_data_ingestion::storage::sinks::Sink;
+use ethers::types::{Block, Transaction, H256, U64};
+use sqlx::{Connection, SqliteConnection};
+
+const CHAIN: &str = "MOCK";
+
+fn block(number: u64) -> Block<Transaction> {
+    Block { number: Some(U64::from(number)), hash: Some(H256::from_low_u64_be(number)), ..Default::default() }
+}
+
+fn scratch_path(name: &str) -> String {
+    let path = std::env::temp_dir().join(format!("sqlite-sink-{}-{}.db", name, std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    path.to_string_lossy().into_owned()
+}
+
+#[tokio::test]
+async fn syncs_every_commit_in_wal_mode() -> Result<()> {
+    let path = scratch_path("pragmas");
+    let mut connection = SqliteConnection::connect_with(&connect_options(&path)?).await?;
+    let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode").fetch_one(&mut connection).await?;
+    // 2 is FULL; NORMAL (1) may lose acknowledged commits on power loss.
+    let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous").fetch_one(&mut connection).await?;
+    assert_eq!((journal_mode.as_str(), synchronous), ("wal", 2));
+
+    connection.close().await?;
+    std::fs::remove_file(&path)?;
+    Ok(())
+}
+
+#[tokio::test]
+async fn acknowledged_blocks_survive_reopening() -> Result<()> {
+    let path = scratch_path("reopen");
+    let config = SqliteSinkConfig { path: path.clone(), blocks_per_transaction: 2 };
+    {
+        let sink = SqliteSink::new(&config).await?;
+        assert!(!sink.write_block(CHAIN, &block(1)).await?.durable);
+        assert!(sink.write_block(CHAIN, &block(2)).await?.durable);
+        assert!(!sink.write_block(CHAIN, &block(3)).await?.durable);
+        assert!(sink.flush(CHAIN).await?.is_some_and(|outcome| outcome.durable));
+    }
+
+    let reopened = SqliteSink::new(&config).await?;
+    assert_eq!(reopened.last_block(CHAIN).await?, Some(3));
+    drop(reopened);
+    let _ = std::fs::remove_file(&path);
+    Ok(())
+}
@@ -0,0 +1,95 @@
+//! Resolving `start_time`/`end_time` to seconds and to blocks, the latter against `MockAdapter`.
+
+use anyhow::Result;
+use blockchain_data_ingestion::blockchain::mock_adapter::MockAdapter;
+use blockchain_data_ingestion::blockchain::time_search::{first_block_at_or_after, unix_seconds};
+use ethers::types::{Block, Transaction, U256, U64};
+use toml::value::Datetime;
+
+fn seconds(value: &str) -> Result<u64> {
+    unix_seconds(&value.parse::<Datetime>()?)
+}
+
+/// Blocks `first..=last`, block n at 1000 + 10n seconds, so earlier ones look pruned.
+fn chain(first: u64, last: u64) -> MockAdapter {
+    let blocks = (first..=last)
+        .map(|number| Block::<Transaction> {
+            number: Some(U64::from(number)),
+            timestamp: U256::from(1000 + 10 * number),
+            ..Default::default()
+        })
+        .collect();
+    MockAdapter::new(blocks, None)
+}
+
+#[test]
+fn dates_and_times_are_counted_from_the_epoch() -> Result<()> {
+    assert_eq!(seconds("1970-01-01T00:00:00Z")?, 0);
+    assert_eq!(seconds("2024-03-01T00:00:00Z")?, 1_709_251_200);
+    assert_eq!(seconds("2024-04-01T12:00:00Z")?, 1_711_972_800);
+    Ok(())
+}
+
+#[test]
+fn dates_mean_midnight_utc() -> Result<()> {
+    assert_eq!(seconds("2024-03-01")?, seconds("2024-03-01T00:00:00Z")?);
+    assert_eq!(seconds("2024-03-01T06:30:00")?, seconds("2024-03-01T06:30:00Z")?);
+    Ok(())
+}
+
+#[test]
+fn leap_days_are_counted() -> Result<()> {
+    assert_eq!(seconds("2024-03-01")? - seconds("2024-02-28")?, 2 * 86_400);
+    assert_eq!(seconds("2023-03-01")? - seconds("2023-02-28")?, 86_400);
+    // Centuries are leap years only when divisible by 400.
+    assert_eq!(seconds("2000-03-01")? - seconds("2000-02-28")?, 2 * 86_400);
+    assert_eq!(seconds("2100-03-01")? - seconds("2100-02-28")?, 86_400);
+    assert_eq!(seconds("2000-02-29")?, 951_782_400);
+    Ok(())
+}
+
+#[test]
+fn offsets_are_subtracted() -> Result<()> {
+    assert_eq!(seconds("2024-03-01T02:00:00+02:00")?, seconds("2024-03-01T00:00:00Z")?);
+    assert_eq!(seconds("2024-02-29T19:00:00-05:00")?, seconds("2024-03-01T00:00:00Z")?);
+    assert_eq!(seconds("1970-01-01T05:30:00+05:30")?, 0);
+    Ok(())
+}
+
+#[test]
+fn times_before_1970_are_rejected() {
+    assert!(seconds("1969-12-31T23:59:59Z").is_err());
+    assert!(seconds("1900-01-01").is_err());
+    assert!(seconds("1970-01-01T00:30:00+01:00").is_err());
+}
+
+#[test]
+fn times_without_a_date_are_rejected() {
+    assert!(seconds("12:00:00").is_err());
+}
+
+#[tokio::test]
+async fn finds_the_first_block_at_or_after_a_time() -> Result<()> {
+    let adapter = chain(0, 100);
+    assert_eq!(first_block_at_or_after(&adapter, 1500).await?, Some(50));
+    assert_eq!(first_block_at_or_after(&adapter, 1495).await?, Some(50));
+    assert_eq!(first_block_at_or_after(&adapter, 1501).await?, Some(51));
+    assert_eq!(first_block_at_or_after(&adapter, 0).await?, Some(0));
+    assert_eq!(first_block_at_or_after(&adapter, 2000).await?, Some(100));
+    Ok(())
+}
+
+#[tokio::test]
+async fn times_after_the_head_have_no_block() -> Result<()> {
+    assert_eq!(first_block_at_or_after(&chain(0, 100), 2001).await?, None);
+    Ok(())
+}
+
+#[tokio::test]
+async fn pruned_chains_are_searched_from_their_oldest_block() -> Result<()> {
+    let adapter = chain(40, 100);
+    assert_eq!(first_block_at_or_after(&adapter, 1700).await?, Some(70));
+    assert_eq!(first_block_at_or_after(&adapter, 1400).await?, Some(40));
+    assert_eq!(first_block_at_or_after(&adapter, 0).await?, Some(40));
+    Ok(())
+}
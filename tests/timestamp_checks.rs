@@ -0,0 +1,100 @@
+//! Timestamp sanity checks against a Postgres `timestamp_anomalies` table, with realtime blocks
+//! stamped the way the producer stamps them.
+
+mod common;
+
+use anyhow::Result;
+use blockchain_data_ingestion::integrity::timestamps::{TimestampChecker, TimestampChecksConfig};
+use blockchain_data_ingestion::streams::consumers::hooks::ConsumerHook;
+use blockchain_data_ingestion::streams::schemas::evm::{received_at, stamp_received_at, RECEIVED_AT_FIELD};
+use ethers::types::{Block, Transaction, H256, U256, U64};
+use sqlx::{PgPool, Row};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CHAIN: &str = "MOCK";
+
+fn now() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs() as i64
+}
+
+fn block(number: u64, timestamp: i64) -> Block<Transaction> {
+    Block {
+        number: Some(U64::from(number)),
+        hash: Some(H256::from_low_u64_be(number)),
+        timestamp: U256::from(timestamp),
+        ..Default::default()
+    }
+}
+
+/// A block as the realtime producer publishes it after receiving it at `received_at`.
+fn realtime_block(number: u64, timestamp: i64, received_at: i64) -> Block<Transaction> {
+    let mut block = block(number, timestamp);
+    block.other.insert(RECEIVED_AT_FIELD.to_string(), serde_json::json!(received_at));
+    block
+}
+
+fn checker(pool: &PgPool) -> TimestampChecker {
+    let config = TimestampChecksConfig { enabled: true, max_future_secs: 15, max_past_secs: 600 };
+    TimestampChecker::new(std::sync::Arc::new(pool.clone()), &config)
+}
+
+async fn anomalies(pool: &PgPool) -> Result<Vec<(i64, String)>> {
+    let rows = sqlx::query("SELECT block_number, kind FROM timestamp_anomalies WHERE chain_name = $1 ORDER BY block_number, kind")
+        .bind(CHAIN)
+        .fetch_all(pool)
+        .await?;
+    rows.iter().map(|row| Ok((row.try_get("block_number")?, row.try_get("kind")?))).collect()
+}
+
+#[test]
+fn the_producer_stamp_round_trips_through_the_message() -> Result<()> {
+    let mut stamped = block(1, now());
+    stamp_received_at(&mut stamped);
+    let decoded: Block<Transaction> = serde_json::from_slice(&serde_json::to_vec(&stamped)?)?;
+    let received = received_at(&decoded).expect("stamp survives serialization");
+    assert!((now() - received).abs() <= 1);
+    assert_eq!(received_at(&block(1, now())), None);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn a_consumer_backlog_is_not_a_stale_block() -> Result<()> {
+    let database = common::start_postgres().await?;
+    let checker = checker(&database.pool);
+
+    // Received on time an hour ago, and only committed now.
+    let an_hour_ago = now() - 3600;
+    checker.on_block_committed(CHAIN, &realtime_block(10, an_hour_ago - 12, an_hour_ago)).await?;
+    assert!(anomalies(&database.pool).await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn flags_realtime_blocks_far_behind_their_receipt_but_not_backfilled_ones() -> Result<()> {
+    let database = common::start_postgres().await?;
+    let checker = checker(&database.pool);
+
+    // Served an hour stale by the node.
+    checker.on_block_committed(CHAIN, &realtime_block(10, now() - 3600, now())).await?;
+    // Backfilled, so old by design.
+    checker.on_block_committed(CHAIN, &block(11, now() - 7200)).await?;
+
+    assert_eq!(anomalies(&database.pool).await?, vec![(10, "past".to_string()), (11, "before_parent".to_string())]);
+    Ok(())
+}
+
+#[tokio::test(flavor = "multi_thread")]
+#[ignore = "requires Docker"]
+async fn flags_blocks_ahead_of_the_clock() -> Result<()> {
+    let database = common::start_postgres().await?;
+    let checker = checker(&database.pool);
+
+    checker.on_block_committed(CHAIN, &realtime_block(10, now() + 5, now())).await?;
+    checker.on_block_committed(CHAIN, &realtime_block(11, now() + 600, now())).await?;
+    checker.on_block_committed(CHAIN, &block(12, now() + 600)).await?;
+
+    assert_eq!(anomalies(&database.pool).await?, vec![(11, "future".to_string()), (12, "future".to_string())]);
+    Ok(())
+}
@@ -0,0 +1,29 @@
+//! Hash-only blocks, as `tx_detail = "hashes"` publishes them and consumers decode them.
+
+use anyhow::Result;
+use blockchain_data_ingestion::streams::schemas::evm::{with_transaction_hashes, without_transactions};
+use blockchain_data_ingestion::streams::sharding;
+use ethers::types::{Block, Transaction};
+
+#[test]
+fn hash_only_blocks_keep_their_transaction_count() -> Result<()> {
+    let blocks: Vec<Block<Transaction>> = serde_json::from_slice(&std::fs::read("tests/fixtures/reorg.json")?)?;
+    assert!(blocks.iter().any(|block| !block.transactions.is_empty()));
+
+    for block in &blocks {
+        let decoded = without_transactions(&with_transaction_hashes(block)?)?;
+        assert!(decoded.transactions.is_empty());
+        assert_eq!(sharding::tx_count(&decoded), block.transactions.len());
+        assert_eq!(decoded.hash, block.hash);
+    }
+    Ok(())
+}
+
+#[test]
+fn full_blocks_count_their_own_transactions() -> Result<()> {
+    let blocks: Vec<Block<Transaction>> = serde_json::from_slice(&std::fs::read("tests/fixtures/reorg.json")?)?;
+    for block in &blocks {
+        assert_eq!(sharding::tx_count(block), block.transactions.len());
+    }
+    Ok(())
+}